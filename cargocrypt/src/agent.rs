@@ -0,0 +1,531 @@
+//! Session key agent
+//!
+//! Similar to `ssh-agent`, `cargocrypt agent start` runs a small background
+//! process that keeps password-derived keys in memory for a limited time.
+//! [`CargoCrypt`](crate::CargoCrypt) instances (and therefore the git filters,
+//! which start a new process for every file) consult the agent before running
+//! Argon2, so only the first operation with a password pays the derivation
+//! cost.
+//!
+//! The agent listens on a unix socket that only the owning user can access
+//! (`CARGOCRYPT_AGENT_SOCK`, or a default path in the runtime directory).
+//! Requests are single JSON lines. Passwords used to derive keys never reach
+//! the agent; entries are looked up by an HMAC of the password (see
+//! [`password_id`](crate::crypto::cache::password_id)). The exception are
+//! credentials unlocked with `cargocrypt agent unlock`: the git filters
+//! fetch their passwords from the agent (see
+//! [`git::credentials`](crate::git::credentials)), so those are held in
+//! memory, for the same time as keys. Windows named pipes are not supported
+//! yet; there the agent is never used.
+//!
+//! On shared machines the default socket lives in a per-user directory and is
+//! named after the project (see [`utils::project_id`](crate::utils::project_id)),
+//! so each checkout gets its own agent. Clients refuse sockets owned by other
+//! users, and keys are namespaced by project even when several checkouts share
+//! one agent through `CARGOCRYPT_AGENT_SOCK`.
+
+use crate::crypto::cache::{password_id, KeyCache, MemoryKeyCache};
+use crate::crypto::{defaults, DerivedKey, Kdf, SecureString};
+use chacha20poly1305::Key;
+use crate::error::{CargoCryptError, CryptoResult};
+use crate::utils;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+use zeroize::Zeroize;
+
+/// Environment variable holding the agent socket path
+pub const AGENT_SOCKET_ENV: &str = "CARGOCRYPT_AGENT_SOCK";
+
+/// How long a client waits for the agent before falling back to Argon2
+const CLIENT_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Agent settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgentConfig {
+    /// Whether to consult a running agent for derived keys
+    pub enabled: bool,
+    /// How long the agent keeps a derived key, in seconds
+    pub ttl_secs: u64,
+}
+
+impl Default for AgentConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            ttl_secs: 900,
+        }
+    }
+}
+
+/// Default agent socket path for a project
+///
+/// Uses `CARGOCRYPT_AGENT_SOCK` if set. Otherwise the socket is
+/// `agent-<project id>.sock` in the [runtime directory](utils::runtime_dir).
+pub fn default_socket_path(project_root: Option<&Path>) -> PathBuf {
+    if let Some(path) = std::env::var_os(AGENT_SOCKET_ENV) {
+        return PathBuf::from(path);
+    }
+
+    let dir = utils::runtime_dir();
+    let file_name = match project_root {
+        Some(root) => format!("agent-{}.sock", utils::project_id(root)),
+        None => "agent.sock".to_string(),
+    };
+    dir.join(file_name)
+}
+
+/// Request sent to the agent
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum AgentRequest {
+    /// Look up a key, for a specific salt or the most recent one
+    Get {
+        #[serde(with = "hex")]
+        password_id: [u8; 32],
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        salt: Option<String>,
+    },
+    /// Store a derived key
+    Put {
+        #[serde(with = "hex")]
+        password_id: [u8; 32],
+        #[serde(with = "hex")]
+        salt: [u8; defaults::SALT_LENGTH],
+        #[serde(with = "hex")]
+        key: [u8; defaults::KEY_LENGTH],
+    },
+    /// Look up an unlocked credential
+    GetCredential { name: String },
+    /// Hold a credential for the git filters
+    PutCredential { name: String, secret: String },
+    /// Report the number of cached keys
+    Status,
+    /// Forget all cached keys and credentials
+    Clear,
+    /// Shut the agent down
+    Stop,
+}
+
+/// Response from the agent
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum AgentResponse {
+    /// A cached key
+    Key {
+        #[serde(with = "hex")]
+        salt: [u8; defaults::SALT_LENGTH],
+        #[serde(with = "hex")]
+        key: [u8; defaults::KEY_LENGTH],
+    },
+    /// An unlocked credential
+    Credential { secret: String },
+    /// No matching key is cached
+    NotFound,
+    /// Agent state
+    Status {
+        entries: usize,
+        ttl_secs: u64,
+        #[serde(default)]
+        credentials: usize,
+    },
+    /// The request was carried out
+    Ok,
+    /// The request could not be handled
+    Error { message: String },
+}
+
+/// Client for a running agent
+#[derive(Debug, Clone)]
+pub struct AgentClient {
+    socket_path: PathBuf,
+    namespace: String,
+}
+
+impl AgentClient {
+    /// Create a client for the agent at `socket_path`
+    pub fn new<P: AsRef<Path>>(socket_path: P) -> Self {
+        Self {
+            socket_path: socket_path.as_ref().to_path_buf(),
+            namespace: String::new(),
+        }
+    }
+
+    /// Client for a project's agent, if an agent socket exists
+    ///
+    /// Keys are namespaced by the project, so they are not shared with other
+    /// checkouts using the same agent.
+    pub fn for_project<P: AsRef<Path>>(project_root: P) -> Option<Self> {
+        let project_root = project_root.as_ref();
+        let socket_path = default_socket_path(Some(project_root));
+        socket_path
+            .exists()
+            .then(|| Self::new(socket_path).with_namespace(utils::project_id(project_root)))
+    }
+
+    /// Keep keys stored through this client separate from other namespaces
+    pub fn with_namespace<S: Into<String>>(mut self, namespace: S) -> Self {
+        self.namespace = namespace.into();
+        self
+    }
+
+    /// Path of the agent socket
+    pub fn socket_path(&self) -> &Path {
+        &self.socket_path
+    }
+
+    /// Send a request and wait for the response
+    #[cfg(unix)]
+    pub fn request(&self, request: &AgentRequest) -> CryptoResult<AgentResponse> {
+        use std::io::{BufRead, BufReader, Write};
+        use std::os::unix::net::UnixStream;
+
+        // Another user's agent would receive our derived keys
+        check_owner(&self.socket_path)?;
+
+        let stream = UnixStream::connect(&self.socket_path)?;
+        stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+        stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+
+        let mut line = serde_json::to_string(request)?;
+        line.push('\n');
+        (&stream).write_all(line.as_bytes())?;
+
+        let mut response = String::new();
+        BufReader::new(&stream).read_line(&mut response)?;
+        Ok(serde_json::from_str(&response)?)
+    }
+
+    /// Send a request and wait for the response
+    #[cfg(not(unix))]
+    pub fn request(&self, _request: &AgentRequest) -> CryptoResult<AgentResponse> {
+        Err(unsupported())
+    }
+
+    /// Number of cached keys and the agent's TTL in seconds
+    pub fn status(&self) -> CryptoResult<(usize, u64)> {
+        match self.request(&AgentRequest::Status)? {
+            AgentResponse::Status { entries, ttl_secs, .. } => Ok((entries, ttl_secs)),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Number of unlocked credentials
+    pub fn credential_count(&self) -> CryptoResult<usize> {
+        match self.request(&AgentRequest::Status)? {
+            AgentResponse::Status { credentials, .. } => Ok(credentials),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// The credential unlocked as `name`, if the agent holds it
+    pub fn credential(&self, name: &str) -> CryptoResult<Option<SecureString>> {
+        let request = AgentRequest::GetCredential { name: self.credential_name(name) };
+        match self.request(&request)? {
+            AgentResponse::Credential { secret } => Ok(Some(SecureString::from(secret))),
+            AgentResponse::NotFound => Ok(None),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Have the agent hold `secret` as `name` until its TTL runs out
+    pub fn add_credential(&self, name: &str, secret: &str) -> CryptoResult<()> {
+        let mut request = AgentRequest::PutCredential {
+            name: self.credential_name(name),
+            secret: secret.to_string(),
+        };
+        let response = self.request(&request);
+        if let AgentRequest::PutCredential { secret, .. } = &mut request {
+            secret.zeroize();
+        }
+        match response? {
+            AgentResponse::Ok => Ok(()),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    fn credential_name(&self, name: &str) -> String {
+        format!("{}/{}", self.namespace, name)
+    }
+
+    /// Forget all cached keys and credentials
+    pub fn clear(&self) -> CryptoResult<()> {
+        match self.request(&AgentRequest::Clear)? {
+            AgentResponse::Ok => Ok(()),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Shut the agent down
+    pub fn stop(&self) -> CryptoResult<()> {
+        match self.request(&AgentRequest::Stop)? {
+            AgentResponse::Ok => Ok(()),
+            other => Err(unexpected(other)),
+        }
+    }
+}
+
+impl KeyCache for AgentClient {
+    fn get(&self, password: &str, kdf: &Kdf, salt: Option<&[u8; defaults::SALT_LENGTH]>) -> Option<DerivedKey> {
+        let request = AgentRequest::Get {
+            password_id: password_id(&self.namespace, kdf, password),
+            salt: salt.map(hex::encode),
+        };
+
+        match self.request(&request) {
+            Ok(AgentResponse::Key { salt, key }) => Some(DerivedKey::from_raw_parts(*Key::from_slice(&key), salt)),
+            Ok(_) => None,
+            Err(e) => {
+                debug!("Key agent unavailable: {}", e);
+                None
+            }
+        }
+    }
+
+    fn put(&self, password: &str, kdf: &Kdf, key: &DerivedKey) {
+        let mut key_bytes = [0u8; defaults::KEY_LENGTH];
+        key_bytes.copy_from_slice(key.key().as_slice());
+        let request = AgentRequest::Put {
+            password_id: password_id(&self.namespace, kdf, password),
+            salt: *key.salt(),
+            key: key_bytes,
+        };
+        zeroize::Zeroize::zeroize(&mut key_bytes);
+
+        if let Err(e) = self.request(&request) {
+            debug!("Key agent unavailable: {}", e);
+        }
+    }
+}
+
+/// The agent process
+#[derive(Debug)]
+pub struct AgentServer {
+    socket_path: PathBuf,
+    cache: MemoryKeyCache,
+    credentials: Mutex<HashMap<String, (SecureString, Instant)>>,
+}
+
+impl AgentServer {
+    /// Create an agent that keeps keys for `ttl`
+    pub fn new<P: AsRef<Path>>(socket_path: P, ttl: Duration) -> Self {
+        Self {
+            socket_path: socket_path.as_ref().to_path_buf(),
+            cache: MemoryKeyCache::new(ttl),
+            credentials: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Unlocked credentials that have not expired
+    fn credentials(&self) -> std::sync::MutexGuard<'_, HashMap<String, (SecureString, Instant)>> {
+        let mut credentials = self.credentials.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        credentials.retain(|_, (_, expires_at)| *expires_at > now);
+        credentials
+    }
+
+    /// Handle a single request
+    pub fn handle(&self, request: AgentRequest) -> AgentResponse {
+        match request {
+            AgentRequest::Get { password_id, salt } => {
+                let salt = match salt.as_deref().map(parse_salt).transpose() {
+                    Ok(salt) => salt,
+                    Err(message) => return AgentResponse::Error { message },
+                };
+
+                match self.cache.get_by_id(&password_id, salt.as_ref()) {
+                    Some(key) => {
+                        let mut key_bytes = [0u8; defaults::KEY_LENGTH];
+                        key_bytes.copy_from_slice(key.key().as_slice());
+                        AgentResponse::Key { salt: *key.salt(), key: key_bytes }
+                    }
+                    None => AgentResponse::NotFound,
+                }
+            }
+            AgentRequest::Put { password_id, salt, key } => {
+                self.cache
+                    .put_by_id(password_id, DerivedKey::from_raw_parts(*Key::from_slice(&key), salt));
+                AgentResponse::Ok
+            }
+            AgentRequest::GetCredential { name } => match self.credentials().get(&name) {
+                Some((secret, _)) => AgentResponse::Credential { secret: secret.to_string() },
+                None => AgentResponse::NotFound,
+            },
+            AgentRequest::PutCredential { name, secret } => {
+                let expires_at = Instant::now() + self.cache.ttl();
+                self.credentials().insert(name, (SecureString::from(secret), expires_at));
+                AgentResponse::Ok
+            }
+            AgentRequest::Status => AgentResponse::Status {
+                entries: self.cache.len(),
+                ttl_secs: self.cache.ttl().as_secs(),
+                credentials: self.credentials().len(),
+            },
+            AgentRequest::Clear => {
+                self.cache.clear();
+                self.credentials().clear();
+                AgentResponse::Ok
+            }
+            AgentRequest::Stop => AgentResponse::Ok,
+        }
+    }
+
+    /// Listen on the socket until a stop request arrives
+    #[cfg(unix)]
+    pub async fn run(self) -> CryptoResult<()> {
+        use std::os::unix::fs::PermissionsExt;
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+        use tokio::net::UnixListener;
+
+        if self.socket_path.exists() {
+            if AgentClient::new(&self.socket_path).status().is_ok() {
+                return Err(CargoCryptError::Config {
+                    message: format!("An agent is already running at {}", self.socket_path.display()),
+                    suggestion: Some("Stop it with `cargocrypt agent stop` first".to_string()),
+                });
+            }
+            // Stale socket from an agent that did not shut down cleanly
+            std::fs::remove_file(&self.socket_path)?;
+        }
+
+        if let Some(parent) = self.socket_path.parent() {
+            std::fs::create_dir_all(parent)?;
+            // The directory may be in a shared location such as /tmp
+            check_owner(parent)?;
+            std::fs::set_permissions(parent, std::fs::Permissions::from_mode(0o700))?;
+        }
+
+        let listener = UnixListener::bind(&self.socket_path)?;
+        std::fs::set_permissions(&self.socket_path, std::fs::Permissions::from_mode(0o600))?;
+        info!("Key agent listening on {}", self.socket_path.display());
+
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let (reader, mut writer) = stream.into_split();
+
+            let mut line = String::new();
+            if BufReader::new(reader).read_line(&mut line).await.is_err() {
+                continue;
+            }
+
+            let (response, stop) = match serde_json::from_str::<AgentRequest>(&line) {
+                Ok(AgentRequest::Stop) => (AgentResponse::Ok, true),
+                Ok(request) => (self.handle(request), false),
+                Err(e) => (AgentResponse::Error { message: format!("Invalid request: {}", e) }, false),
+            };
+
+            let mut reply = serde_json::to_string(&response)?;
+            reply.push('\n');
+            let _ = writer.write_all(reply.as_bytes()).await;
+
+            if stop {
+                break;
+            }
+        }
+
+        info!("Key agent stopped");
+        std::fs::remove_file(&self.socket_path)?;
+        Ok(())
+    }
+
+    /// Listen on the socket until a stop request arrives
+    #[cfg(not(unix))]
+    pub async fn run(self) -> CryptoResult<()> {
+        Err(unsupported())
+    }
+}
+
+/// Fail unless `path` belongs to the current user
+#[cfg(unix)]
+fn check_owner(path: &Path) -> CryptoResult<()> {
+    use std::os::unix::fs::MetadataExt;
+
+    let owner = std::fs::metadata(path)?.uid();
+    match utils::current_uid() {
+        Some(uid) if uid != owner => Err(CargoCryptError::Config {
+            message: format!("{} belongs to another user (uid {})", path.display(), owner),
+            suggestion: Some(format!("Point {} at a socket you own", AGENT_SOCKET_ENV)),
+        }),
+        _ => Ok(()),
+    }
+}
+
+fn parse_salt(salt: &str) -> Result<[u8; defaults::SALT_LENGTH], String> {
+    let mut bytes = [0u8; defaults::SALT_LENGTH];
+    hex::decode_to_slice(salt, &mut bytes).map_err(|e| format!("Invalid salt: {}", e))?;
+    Ok(bytes)
+}
+
+fn unexpected(response: AgentResponse) -> CargoCryptError {
+    let message = match response {
+        AgentResponse::Error { message } => message,
+        other => format!("Unexpected agent response: {:?}", other),
+    };
+    CargoCryptError::Config {
+        message,
+        suggestion: None,
+    }
+}
+
+#[cfg(not(unix))]
+fn unsupported() -> CargoCryptError {
+    CargoCryptError::Config {
+        message: "The key agent is only supported on unix platforms".to_string(),
+        suggestion: None,
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_agent_roundtrip() {
+        let temp_dir = TempDir::new().unwrap();
+        let socket_path = temp_dir.path().join("agent").join("agent.sock");
+
+        let server = tokio::spawn(AgentServer::new(&socket_path, Duration::from_secs(60)).run());
+        for _ in 0..100 {
+            if socket_path.exists() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let result = tokio::task::spawn_blocking(move || {
+            let client = AgentClient::new(&socket_path);
+            let key = DerivedKey::from_password_with_random_salt("agent_password").unwrap();
+
+            assert!(client.get("agent_password", &Kdf::Argon2id, None).is_none());
+            client.put("agent_password", &Kdf::Argon2id, &key);
+            assert_eq!(client.get("agent_password", &Kdf::Argon2id, Some(key.salt())).unwrap().key(), key.key());
+            assert_eq!(client.get("agent_password", &Kdf::Argon2id, None).unwrap().salt(), key.salt());
+            assert!(client.get("other_password", &Kdf::Argon2id, None).is_none());
+            assert_eq!(client.status().unwrap(), (1, 60));
+
+            // Other projects sharing the agent never see the key
+            let other_project = AgentClient::new(&socket_path).with_namespace("other-project");
+            assert!(other_project.get("agent_password", &Kdf::Argon2id, None).is_none());
+
+            client.add_credential("filter", "filter_password").unwrap();
+            assert_eq!(client.credential("filter").unwrap().as_deref(), Some("filter_password"));
+            assert!(other_project.credential("filter").unwrap().is_none());
+            assert_eq!(client.credential_count().unwrap(), 1);
+
+            client.clear().unwrap();
+            assert_eq!(client.status().unwrap().0, 0);
+            assert!(client.credential("filter").unwrap().is_none());
+            client.stop().unwrap();
+            socket_path
+        })
+        .await
+        .unwrap();
+
+        server.await.unwrap().unwrap();
+        assert!(!result.exists());
+    }
+}
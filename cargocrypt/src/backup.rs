@@ -0,0 +1,261 @@
+//! Backups of a project's `.cargocrypt/` state
+//!
+//! A backup packs everything needed to decrypt the project's files again
+//! into one gzipped tar archive: the configuration, keyfiles and wrapped
+//! vault keys, the signing key, identities, team members and keys, and the
+//! secret store. Locks, caches and earlier backups are left out. The archive
+//! is then encrypted like any other file, with a password of its own, and
+//! tagged so `cargocrypt inspect` and [`list`] recognize it without the
+//! password.
+//!
+//! Backups go to `.cargocrypt/backups/` unless written elsewhere. A copy
+//! off the machine is worth having: a backup next to the state it protects
+//! is lost with it. Passwords kept in the OS keyring are not part of a
+//! backup.
+
+use crate::crypto::EncryptedSecret;
+use crate::error::{CargoCryptError, CryptoResult};
+use crate::lockout::ATTEMPTS_FILE;
+use crate::session::SESSIONS_FILE;
+use serde::Serialize;
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+use zeroize::Zeroizing;
+
+/// Directory holding the backups, inside `.cargocrypt/`
+pub const BACKUPS_DIR: &str = "backups";
+
+/// Extension of backup files
+pub const BACKUP_EXTENSION: &str = "ccbackup";
+
+/// Tag recorded in the header of every backup
+pub const BACKUP_TAG: &str = "cargocrypt-backup";
+
+/// Entries of `.cargocrypt/` that are not backed up
+///
+/// Open sessions and failed unlock attempts are per-machine runtime state,
+/// and restoring them would bring back stale sessions or lockouts.
+const EXCLUDED: &[&str] = &["locks", "scan-cache", "cache", SESSIONS_FILE, ATTEMPTS_FILE, BACKUPS_DIR];
+
+/// Directory of the backups of the project at `project_root`
+pub fn backups_dir<P: AsRef<Path>>(project_root: P) -> PathBuf {
+    project_root.as_ref().join(".cargocrypt").join(BACKUPS_DIR)
+}
+
+/// File name of a backup created at `created_at` (Unix timestamp), e.g. `20261015-091500.ccbackup`
+pub fn backup_file_name(created_at: u64) -> String {
+    let time = chrono::DateTime::from_timestamp(i64::try_from(created_at).unwrap_or(i64::MAX), 0).unwrap_or_default();
+    format!("{}.{}", time.format("%Y%m%d-%H%M%S"), BACKUP_EXTENSION)
+}
+
+/// A file in a backup
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BackupEntry {
+    /// Path relative to `.cargocrypt/`
+    pub path: PathBuf,
+    /// Size in bytes
+    pub size: u64,
+}
+
+/// A backup just written
+#[derive(Debug, Clone, Serialize)]
+pub struct Backup {
+    /// The backup file
+    pub path: PathBuf,
+    /// The files it holds
+    pub files: Vec<BackupEntry>,
+}
+
+/// Pack the files of the state directory `state_dir` into a gzipped tar archive
+pub fn pack(state_dir: &Path) -> CryptoResult<(Zeroizing<Vec<u8>>, Vec<BackupEntry>)> {
+    let mut entries = Vec::new();
+    let walker = walkdir::WalkDir::new(state_dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| entry.depth() != 1 || !EXCLUDED.iter().any(|excluded| entry.file_name() == *excluded));
+    for entry in walker {
+        let entry = entry.map_err(|e| CargoCryptError::Io {
+            message: format!("Cannot read {}: {}", state_dir.display(), e),
+            source: e.into(),
+        })?;
+        if entry.file_type().is_file() {
+            let path = entry.path().strip_prefix(state_dir).unwrap_or(entry.path()).to_path_buf();
+            entries.push(BackupEntry { path, size: entry.metadata().map_or(0, |metadata| metadata.len()) });
+        }
+    }
+
+    let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default()));
+    for entry in &entries {
+        let content = Zeroizing::new(std::fs::read(state_dir.join(&entry.path))?);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o600);
+        header.set_mtime(0);
+        builder.append_data(&mut header, &entry.path, content.as_slice())?;
+    }
+    let archive = builder.into_inner()?.finish()?;
+    Ok((Zeroizing::new(archive), entries))
+}
+
+/// The files of a packed backup with their contents
+///
+/// Fails on entries that are not plain files or whose paths would leave
+/// the state directory.
+pub fn unpack(archive: &[u8]) -> CryptoResult<Vec<(BackupEntry, Zeroizing<Vec<u8>>)>> {
+    let mut files = Vec::new();
+    let mut tar = tar::Archive::new(flate2::read::GzDecoder::new(archive));
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        let safe = path.components().all(|component| matches!(component, Component::Normal(_)));
+        if !entry.header().entry_type().is_file() || !safe || path.as_os_str().is_empty() {
+            return Err(invalid_backup(format!("it contains an unexpected entry {}", path.display())));
+        }
+        let mut content = Zeroizing::new(Vec::new());
+        entry.read_to_end(&mut content)?;
+        files.push((BackupEntry { path, size: content.len() as u64 }, content));
+    }
+    Ok(files)
+}
+
+/// Write a restored file readable only by the owner, replacing the old one atomically
+pub fn write_restored(path: &Path, content: &[u8]) -> CryptoResult<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let temp_path = path.with_file_name(format!(
+        ".{}.{}.restore",
+        path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default(),
+        std::process::id()
+    ));
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let written = options.open(&temp_path).and_then(|mut file| {
+        file.write_all(content)?;
+        file.sync_all()
+    });
+    if let Err(e) = written.and_then(|()| std::fs::rename(&temp_path, path)) {
+        let _ = std::fs::remove_file(&temp_path);
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// A backup in the backups directory, described from its header
+#[derive(Debug, Clone, Serialize)]
+pub struct BackupInfo {
+    /// The backup file
+    pub path: PathBuf,
+    /// Creation time (Unix timestamp)
+    pub created_at: Option<u64>,
+    /// Description recorded at creation, with the number of files
+    pub description: Option<String>,
+    /// Size of the backup file in bytes
+    pub size: u64,
+}
+
+impl BackupInfo {
+    /// Describe the backup at `path`, failing if it is not a backup
+    pub fn read<P: AsRef<Path>>(path: P) -> CryptoResult<Self> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)?;
+        let encrypted = EncryptedSecret::from_bytes(&bytes)?;
+        let metadata = encrypted.metadata();
+        if !metadata.tags.iter().any(|tag| tag == BACKUP_TAG) {
+            return Err(invalid_backup("it was not created by `cargocrypt backup create`".to_string()));
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            created_at: metadata.created_at,
+            description: metadata.description.clone(),
+            size: bytes.len() as u64,
+        })
+    }
+}
+
+/// The backups of the project at `project_root`, newest first
+///
+/// Files in the backups directory that are not backups are ignored.
+pub fn list<P: AsRef<Path>>(project_root: P) -> CryptoResult<Vec<BackupInfo>> {
+    let dir = backups_dir(project_root);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut backups = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == BACKUP_EXTENSION) {
+            if let Ok(info) = BackupInfo::read(&path) {
+                backups.push(info);
+            }
+        }
+    }
+    backups.sort_by(|a, b| b.created_at.cmp(&a.created_at).then_with(|| b.path.cmp(&a.path)));
+    Ok(backups)
+}
+
+fn invalid_backup(reason: String) -> CargoCryptError {
+    CargoCryptError::Validation {
+        message: format!("Not a valid CargoCrypt backup: {}", reason),
+        errors: Vec::new(),
+        warnings: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_pack_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let state_dir = temp_dir.path().join(".cargocrypt");
+        for name in ["config.toml", "vaults/prod/key.enc", "locks/state.lock", "backups/old.ccbackup", "team/locks/x", "sessions.toml", "attempts.toml"] {
+            let path = state_dir.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, name).unwrap();
+        }
+
+        let (archive, entries) = pack(&state_dir).unwrap();
+        let paths: Vec<_> = entries.iter().map(|entry| entry.path.to_string_lossy().into_owned()).collect();
+        assert_eq!(paths, ["config.toml", "team/locks/x", "vaults/prod/key.enc"]);
+
+        let files = unpack(&archive).unwrap();
+        assert_eq!(files.len(), 3);
+        assert_eq!(files[2].0, entries[2]);
+        assert_eq!(files[2].1.as_slice(), b"vaults/prod/key.enc");
+
+        let restored = temp_dir.path().join("restored/vaults/prod/key.enc");
+        write_restored(&restored, &files[2].1).unwrap();
+        assert_eq!(std::fs::read(&restored).unwrap(), b"vaults/prod/key.enc");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&restored).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+
+        assert_eq!(backup_file_name(0), "19700101-000000.ccbackup");
+    }
+
+    #[test]
+    fn test_unpack_rejects_escaping_paths() {
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default()));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(1);
+        header.set_mode(0o600);
+        header.as_gnu_mut().unwrap().name[..12].copy_from_slice(b"../escape.sh");
+        header.set_cksum();
+        builder.append(&header, &b"x"[..]).unwrap();
+        let archive = builder.into_inner().unwrap().finish().unwrap();
+
+        assert!(unpack(&archive).is_err());
+    }
+}
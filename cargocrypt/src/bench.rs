@@ -0,0 +1,225 @@
+//! Machine benchmarks for choosing a performance profile
+//!
+//! `cargocrypt bench` measures what encryption costs on the current machine:
+//! key derivation and a round trip for each [`PerformanceProfile`], cipher
+//! throughput for each algorithm, and synced file writes and reads in the
+//! project directory. The recommended profile is the most secure one whose
+//! key derivation fits the time budget. Profiles past the first one over
+//! budget are skipped, since they only get slower.
+
+use crate::crypto::{
+    Algorithm, CryptoEngine, EncryptedSecret, Keyfile, PerformanceBenchmark, PerformanceProfile,
+    PlaintextSecret, SecureRandom,
+};
+use crate::error::CryptoResult;
+use serde::Serialize;
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// File written in the benchmarked directory to measure I/O
+const SCRATCH_FILE: &str = ".cargocrypt-bench.tmp";
+
+/// What to benchmark
+#[derive(Debug, Clone)]
+pub struct BenchOptions {
+    /// Bytes encrypted, decrypted, written and read per measurement
+    pub data_size: usize,
+    /// Longest acceptable key derivation for the recommended profile
+    pub kdf_budget: Duration,
+}
+
+impl Default for BenchOptions {
+    fn default() -> Self {
+        Self {
+            data_size: 16 * 1024 * 1024,
+            kdf_budget: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Encryption and decryption speed of one algorithm
+#[derive(Debug, Clone, Serialize)]
+pub struct CipherThroughput {
+    /// Algorithm name, e.g. "ChaCha20-Poly1305"
+    pub algorithm: String,
+    /// Encryption throughput in MB/s, `None` if the algorithm is not implemented
+    pub encryption_mbps: Option<f64>,
+    /// Decryption throughput in MB/s, `None` if the algorithm is not implemented
+    pub decryption_mbps: Option<f64>,
+}
+
+/// Synced write and read speed of the benchmarked directory
+#[derive(Debug, Clone, Serialize)]
+pub struct FileIoThroughput {
+    /// Write throughput in MB/s, including `fsync`
+    pub write_mbps: f64,
+    /// Read throughput in MB/s; usually served from the page cache
+    pub read_mbps: f64,
+}
+
+/// Results of [`run`]
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchReport {
+    /// One benchmark per measured profile, fastest first
+    pub profiles: Vec<PerformanceBenchmark>,
+    /// Profiles not measured because a faster one was already over budget
+    pub skipped: Vec<PerformanceProfile>,
+    /// Throughput per algorithm and encryption mode
+    pub ciphers: Vec<CipherThroughput>,
+    /// File I/O in the benchmarked directory
+    pub file_io: FileIoThroughput,
+    /// Key derivation budget the recommendation was made for, in milliseconds
+    pub kdf_budget_ms: u64,
+    /// Most secure profile whose key derivation fits the budget
+    pub recommended: PerformanceProfile,
+}
+
+/// Benchmark this machine, measuring file I/O in `dir`
+pub fn run(dir: &Path, options: &BenchOptions) -> CryptoResult<BenchReport> {
+    let budget_ms = options.kdf_budget.as_secs_f64() * 1000.0;
+
+    let mut profiles: Vec<PerformanceBenchmark> = Vec::new();
+    let mut skipped = Vec::new();
+    for &profile in PerformanceProfile::all() {
+        if profiles.last().is_some_and(|last| last.key_derivation_ms > budget_ms) {
+            skipped.push(profile);
+            continue;
+        }
+        debug!("Benchmarking {:?} profile", profile);
+        profiles.push(CryptoEngine::with_performance_profile(profile).benchmark_with_data_size(options.data_size)?);
+    }
+
+    Ok(BenchReport {
+        recommended: recommend(&profiles, options.kdf_budget),
+        ciphers: cipher_throughput(options.data_size)?,
+        file_io: file_io(dir, options.data_size)?,
+        kdf_budget_ms: u64::try_from(options.kdf_budget.as_millis()).unwrap_or(u64::MAX),
+        profiles,
+        skipped,
+    })
+}
+
+/// Most secure benchmarked profile whose key derivation fits `budget`
+///
+/// Falls back to [`PerformanceProfile::Fast`] when none does.
+pub fn recommend(benchmarks: &[PerformanceBenchmark], budget: Duration) -> PerformanceProfile {
+    let budget_ms = budget.as_secs_f64() * 1000.0;
+    benchmarks
+        .iter()
+        .filter(|benchmark| benchmark.key_derivation_ms <= budget_ms)
+        .map(|benchmark| benchmark.profile)
+        .max_by_key(|profile| PerformanceProfile::all().iter().position(|p| p == profile))
+        .unwrap_or(PerformanceProfile::Fast)
+}
+
+fn cipher_throughput(data_size: usize) -> CryptoResult<Vec<CipherThroughput>> {
+    // A keyfile key skips the password KDF, which the profiles already cover
+    let key = Keyfile::generate()?.derive_key(&SecureRandom::generate_salt()?, None)?;
+    let data = vec![0x5a; data_size];
+    let mut ciphers = Vec::new();
+
+    for algorithm in Algorithm::all() {
+        if !algorithm.is_implemented() {
+            ciphers.push(CipherThroughput {
+                algorithm: algorithm.to_string(),
+                encryption_mbps: None,
+                decryption_mbps: None,
+            });
+            continue;
+        }
+
+        let plaintext = PlaintextSecret::from_bytes(data.clone());
+
+        let start = Instant::now();
+        let encrypted = EncryptedSecret::encrypt_with_key(plaintext, &key, None)?;
+        let encryption = start.elapsed();
+
+        let start = Instant::now();
+        encrypted.decrypt_with_key(&key)?;
+        let decryption = start.elapsed();
+
+        ciphers.push(CipherThroughput {
+            algorithm: algorithm.to_string(),
+            encryption_mbps: Some(mbps(data_size, encryption)),
+            decryption_mbps: Some(mbps(data_size, decryption)),
+        });
+    }
+
+    Ok(ciphers)
+}
+
+fn file_io(dir: &Path, data_size: usize) -> CryptoResult<FileIoThroughput> {
+    let path = dir.join(SCRATCH_FILE);
+    let data = vec![0x5a; data_size];
+
+    let result = (|| -> CryptoResult<FileIoThroughput> {
+        let start = Instant::now();
+        let mut file = std::fs::OpenOptions::new().write(true).create(true).truncate(true).open(&path)?;
+        file.write_all(&data)?;
+        file.sync_all()?;
+        let write = start.elapsed();
+
+        let start = Instant::now();
+        std::fs::read(&path)?;
+        let read = start.elapsed();
+
+        Ok(FileIoThroughput {
+            write_mbps: mbps(data_size, write),
+            read_mbps: mbps(data_size, read),
+        })
+    })();
+
+    let _ = std::fs::remove_file(&path);
+    result
+}
+
+fn mbps(bytes: usize, elapsed: Duration) -> f64 {
+    (bytes as f64 / 1_048_576.0) / elapsed.as_secs_f64().max(1e-9)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn benchmark(profile: PerformanceProfile, key_derivation_ms: f64) -> PerformanceBenchmark {
+        PerformanceBenchmark {
+            profile,
+            key_derivation_ms,
+            encryption_ms: 1.0,
+            decryption_ms: 1.0,
+            total_ms: key_derivation_ms + 2.0,
+            data_size: 1024,
+        }
+    }
+
+    #[test]
+    fn test_recommend_picks_most_secure_within_budget() {
+        let benchmarks = [
+            benchmark(PerformanceProfile::Fast, 5.0),
+            benchmark(PerformanceProfile::Balanced, 300.0),
+            benchmark(PerformanceProfile::Secure, 2500.0),
+        ];
+
+        assert_eq!(recommend(&benchmarks, Duration::from_secs(1)), PerformanceProfile::Balanced);
+        assert_eq!(recommend(&benchmarks, Duration::from_secs(3)), PerformanceProfile::Secure);
+        assert_eq!(recommend(&benchmarks, Duration::from_millis(1)), PerformanceProfile::Fast);
+    }
+
+    #[test]
+    fn test_run_measures_and_cleans_up() {
+        let dir = tempfile::TempDir::new().unwrap();
+        // A tiny budget keeps the test away from the slow profiles
+        let options = BenchOptions { data_size: 64 * 1024, kdf_budget: Duration::from_millis(1) };
+
+        let report = run(dir.path(), &options).unwrap();
+
+        assert_eq!(report.profiles[0].profile, PerformanceProfile::Fast);
+        assert_eq!(report.profiles.len() + report.skipped.len(), PerformanceProfile::all().len());
+        assert!(report.ciphers.iter().any(|c| c.encryption_mbps.is_some()));
+        assert!(report.ciphers.iter().any(|c| c.encryption_mbps.is_none()));
+        assert!(report.file_io.write_mbps > 0.0);
+        assert!(!dir.path().join(SCRATCH_FILE).exists());
+    }
+}
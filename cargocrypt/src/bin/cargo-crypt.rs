@@ -0,0 +1,33 @@
+//! `cargo crypt`: the `cargocrypt` CLI as a cargo subcommand
+//!
+//! Cargo runs `cargo-crypt crypt <args>` for `cargo crypt <args>`. This
+//! binary hands the arguments unchanged to the `cargocrypt` binary installed
+//! next to it (both come with `cargo install cargocrypt`), which drops the
+//! `crypt` and names itself `cargo crypt` in help and errors.
+
+use std::path::PathBuf;
+use std::process::Command;
+
+fn main() {
+    let cargocrypt = std::env::current_exe()
+        .ok()
+        .map(|exe| exe.with_file_name(format!("cargocrypt{}", std::env::consts::EXE_SUFFIX)))
+        .filter(|sibling| sibling.is_file())
+        .unwrap_or_else(|| PathBuf::from("cargocrypt"));
+    let mut command = Command::new(&cargocrypt);
+    command.args(std::env::args_os().skip(1));
+
+    #[cfg(unix)]
+    let error = {
+        use std::os::unix::process::CommandExt;
+        command.exec()
+    };
+    #[cfg(not(unix))]
+    let error = match command.status() {
+        Ok(status) => std::process::exit(status.code().unwrap_or(1)),
+        Err(error) => error,
+    };
+    eprintln!("❌ Error: cannot run {}: {}", cargocrypt.display(), error);
+    eprintln!("   Reinstall with `cargo install cargocrypt`, which installs both binaries");
+    std::process::exit(1);
+}
@@ -0,0 +1,175 @@
+//! The system clipboard, through the platform's command-line tools
+//!
+//! `cargocrypt secret get --clip` puts a value on the clipboard instead of
+//! printing it, so it stays out of shell history and terminal scrollback,
+//! and has it cleared after
+//! [`SecurityConfig::clipboard_clear_secs`](crate::core::SecurityConfig::clipboard_clear_secs).
+//! Clearing only happens while the clipboard still holds that value, so
+//! whatever was copied since is left alone.
+//!
+//! The tools are `pbcopy`/`pbpaste` on macOS, `clip.exe` and PowerShell on
+//! Windows and WSL, `wl-copy`/`wl-paste` on Wayland, and `xclip` or `xsel`
+//! on X11.
+
+use crate::error::{CargoCryptError, CryptoResult};
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// A clipboard tool
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Clipboard {
+    /// `pbcopy` and `pbpaste`
+    MacOs,
+    /// `clip.exe` and PowerShell's `Get-Clipboard`
+    Windows,
+    /// `wl-copy` and `wl-paste`
+    Wayland,
+    /// `xclip`
+    Xclip,
+    /// `xsel`
+    Xsel,
+}
+
+impl Clipboard {
+    /// The clipboard tool of this system
+    pub fn detect() -> CryptoResult<Self> {
+        if cfg!(target_os = "macos") {
+            return Ok(Self::MacOs);
+        }
+        if cfg!(windows) {
+            return Ok(Self::Windows);
+        }
+
+        let session = |name: &str| std::env::var_os(name).is_some_and(|value| !value.is_empty());
+        let candidates = [
+            (Self::Wayland, session("WAYLAND_DISPLAY")),
+            (Self::Xclip, session("DISPLAY")),
+            (Self::Xsel, session("DISPLAY")),
+            // WSL runs Windows programs
+            (Self::Windows, true),
+        ];
+        candidates
+            .into_iter()
+            .find(|(clipboard, available)| *available && on_path(clipboard.copy_command()[0]))
+            .map(|(clipboard, _)| clipboard)
+            .ok_or_else(|| CargoCryptError::Config {
+                message: "No clipboard tool found".to_string(),
+                suggestion: Some("Install wl-clipboard (Wayland), xclip or xsel (X11)".to_string()),
+            })
+    }
+
+    fn copy_command(&self) -> &'static [&'static str] {
+        match self {
+            Self::MacOs => &["pbcopy"],
+            Self::Windows => &["clip.exe"],
+            Self::Wayland => &["wl-copy"],
+            Self::Xclip => &["xclip", "-selection", "clipboard", "-in"],
+            Self::Xsel => &["xsel", "--clipboard", "--input"],
+        }
+    }
+
+    fn paste_command(&self) -> &'static [&'static str] {
+        match self {
+            Self::MacOs => &["pbpaste"],
+            Self::Windows => &["powershell.exe", "-NoProfile", "-Command", "Get-Clipboard -Raw"],
+            Self::Wayland => &["wl-paste", "--no-newline"],
+            Self::Xclip => &["xclip", "-selection", "clipboard", "-out"],
+            Self::Xsel => &["xsel", "--clipboard", "--output"],
+        }
+    }
+
+    /// Put `bytes` on the clipboard
+    pub fn copy(&self, bytes: &[u8]) -> CryptoResult<()> {
+        let [program, args @ ..] = self.copy_command() else {
+            unreachable!("clipboard commands are never empty")
+        };
+        // X11 and Wayland tools stay around to serve the selection; they must
+        // not hold on to our output
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| tool_error(program, e))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(bytes)?;
+        }
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(tool_error(program, format!("exited with {}", status)));
+        }
+        Ok(())
+    }
+
+    /// What the clipboard holds
+    pub fn paste(&self) -> CryptoResult<Vec<u8>> {
+        let [program, args @ ..] = self.paste_command() else {
+            unreachable!("clipboard commands are never empty")
+        };
+        let output = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .map_err(|e| tool_error(program, e))?;
+        // An empty clipboard is an error for some tools
+        Ok(if output.status.success() { output.stdout } else { Vec::new() })
+    }
+
+    /// Empty the clipboard if it still holds the value whose [`digest`] is `expected`
+    ///
+    /// Returns whether it did.
+    pub fn clear_if_unchanged(&self, expected: &str) -> CryptoResult<bool> {
+        if !holds(&self.paste()?, expected) {
+            return Ok(false);
+        }
+        self.copy(b"")?;
+        Ok(true)
+    }
+}
+
+/// Whether the clipboard contents `current` are the value whose [`digest`] is `expected`
+fn holds(current: &[u8], expected: &str) -> bool {
+    // Some tools add a line break when pasting
+    let trimmed = current.strip_suffix(b"\n").unwrap_or(current);
+    let trimmed = trimmed.strip_suffix(b"\r").unwrap_or(trimmed);
+    digest(current) == expected || digest(trimmed) == expected
+}
+
+/// Hex SHA-256 of a value, to recognise it on the clipboard without keeping it
+pub fn digest(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+fn on_path(program: &str) -> bool {
+    std::env::var_os("PATH").is_some_and(|path| std::env::split_paths(&path).any(|dir| is_file(&dir.join(program))))
+}
+
+fn is_file(path: &Path) -> bool {
+    path.metadata().is_ok_and(|metadata| metadata.is_file())
+}
+
+fn tool_error(program: &str, error: impl std::fmt::Display) -> CargoCryptError {
+    CargoCryptError::Config {
+        message: format!("Clipboard tool '{}' failed: {}", program, error),
+        suggestion: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_holds_only_the_copied_value() {
+        let expected = digest(b"s3cret");
+        assert!(holds(b"s3cret", &expected));
+        assert!(holds(b"s3cret\n", &expected));
+        assert!(holds(b"s3cret\r\n", &expected));
+        assert!(!holds(b"s3cret!", &expected));
+        assert!(!holds(b"", &expected));
+    }
+}
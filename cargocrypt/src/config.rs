@@ -0,0 +1,668 @@
+//! Layered configuration with named profiles
+//!
+//! The effective [`CryptoConfig`] is merged from, lowest first:
+//! 1. the defaults,
+//! 2. the user configuration, `~/.config/cargocrypt/config.toml` (or under
+//!    `$XDG_CONFIG_HOME`),
+//! 3. the project configuration, `.cargocrypt/config.toml`,
+//! 4. the active profile: the `[profiles.<name>]` tables of both files, the
+//!    project's last,
+//! 5. environment variables `CARGOCRYPT__<SECTION>__<KEY>`, e.g.
+//!    `CARGOCRYPT__KEY_PARAMS__MEMORY_COST=19456`.
+//!
+//! Each layer overrides single settings; tables are merged key by key, so a
+//! file only needs the settings it changes. The profile is chosen with
+//! `--profile` or `CARGOCRYPT_PROFILE`, and naming one that no file defines
+//! is an error.
+//!
+//! A `[detection]` table in any layer overrides the [`DetectionConfig`] of
+//! `.cargocrypt/detection.toml`. The user configuration sits below that file,
+//! the project configuration, profiles and environment above it.
+
+use crate::core::CryptoConfig;
+use crate::detection::DetectionConfig;
+use crate::error::{CargoCryptError, CryptoResult};
+use serde::{de::DeserializeOwned, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use toml::{Table, Value};
+
+/// Environment variable selecting the profile when `--profile` is not given
+pub const PROFILE_ENV: &str = "CARGOCRYPT_PROFILE";
+
+/// Prefix of environment variables overriding single settings
+pub const ENV_PREFIX: &str = "CARGOCRYPT__";
+
+/// Table of the named profiles in a configuration file
+const PROFILES: &str = "profiles";
+
+/// Table of detection settings in a layer
+const DETECTION: &str = "detection";
+
+/// Profile selected for this process
+static PROFILE: RwLock<Option<String>> = RwLock::new(None);
+
+/// Select the profile of this process, overriding `CARGOCRYPT_PROFILE`
+pub fn set_profile(profile: Option<String>) {
+    *PROFILE.write().unwrap_or_else(|e| e.into_inner()) = profile;
+}
+
+/// Profile of this process, if any
+pub fn active_profile() -> Option<String> {
+    PROFILE
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .or_else(|| std::env::var(PROFILE_ENV).ok())
+        .filter(|profile| !profile.is_empty())
+}
+
+/// Location of the user configuration file
+pub fn user_path() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_home.join("cargocrypt").join("config.toml"))
+}
+
+/// Where a layer comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    User,
+    Project,
+    Profile,
+    Environment,
+}
+
+/// One layer of settings
+#[derive(Debug, Clone)]
+pub struct Layer {
+    pub scope: Scope,
+    /// File, profile or environment the settings come from, for messages
+    pub source: String,
+    table: Table,
+}
+
+/// The layers of configuration of a project, lowest first
+#[derive(Debug, Clone, Default)]
+pub struct ConfigLayers {
+    /// The active profile
+    pub profile: Option<String>,
+    pub layers: Vec<Layer>,
+}
+
+impl ConfigLayers {
+    /// Layers of the project at `project_root`, with the active profile
+    pub fn load(project_root: &Path) -> CryptoResult<Self> {
+        let env = std::env::vars().filter(|(name, _)| name.starts_with(ENV_PREFIX));
+        Self::load_from(project_root, user_path().as_deref(), active_profile(), env)
+    }
+
+    fn load_from(
+        project_root: &Path,
+        user_path: Option<&Path>,
+        profile: Option<String>,
+        env: impl IntoIterator<Item = (String, String)>,
+    ) -> CryptoResult<Self> {
+        let mut files = Vec::new();
+        if let Some(path) = user_path.filter(|path| path.exists()) {
+            files.push((Scope::User, path.to_path_buf(), read_table(path)?));
+        }
+        let project_path = CryptoConfig::project_path(project_root);
+        if project_path.exists() {
+            files.push((Scope::Project, project_path.clone(), read_table(&project_path)?));
+        }
+
+        let mut layers: Vec<Layer> = files
+            .iter()
+            .map(|(scope, path, table)| Layer {
+                scope: *scope,
+                source: path.display().to_string(),
+                table: without(table, PROFILES),
+            })
+            .collect();
+
+        if let Some(name) = &profile {
+            let mut available = Vec::new();
+            for (_, path, table) in &files {
+                let Some(profiles) = table.get(PROFILES).and_then(Value::as_table) else {
+                    continue;
+                };
+                available.extend(profiles.keys().cloned());
+                if let Some(settings) = profiles.get(name) {
+                    let table = settings.as_table().cloned().ok_or_else(|| {
+                        config_error(format!("Profile '{}' in {} is not a table", name, path.display()), None)
+                    })?;
+                    layers.push(Layer {
+                        scope: Scope::Profile,
+                        source: format!("profile '{}' in {}", name, path.display()),
+                        table,
+                    });
+                }
+            }
+            if !layers.iter().any(|layer| layer.scope == Scope::Profile) {
+                available.sort();
+                available.dedup();
+                let suggestion = if available.is_empty() {
+                    format!("Define it as [profiles.{}] in {}", name, project_path.display())
+                } else {
+                    format!("Available profiles: {}", available.join(", "))
+                };
+                return Err(config_error(format!("Unknown configuration profile '{}'", name), Some(suggestion)));
+            }
+        }
+
+        let env = env_table(env);
+        if !env.is_empty() {
+            layers.push(Layer {
+                scope: Scope::Environment,
+                source: format!("{}* environment variables", ENV_PREFIX),
+                table: env,
+            });
+        }
+        Ok(Self { profile, layers })
+    }
+
+    /// The merged configuration for encryption
+    pub fn crypto_config(&self) -> CryptoResult<CryptoConfig> {
+        let layers = self.layers.iter().map(|layer| (layer.source.as_str(), without(&layer.table, DETECTION)));
+        apply(CryptoConfig::default(), layers)
+    }
+
+    /// The merged detection settings, with `.cargocrypt/detection.toml` of `project_root`
+    pub fn detection_config(&self, project_root: &Path) -> CryptoResult<DetectionConfig> {
+        let detection = |layer: &Layer| layer.table.get(DETECTION).and_then(Value::as_table).cloned();
+        let mut tables: Vec<(String, Table)> = Vec::new();
+        for layer in self.layers.iter().filter(|layer| layer.scope == Scope::User) {
+            tables.extend(detection(layer).map(|table| (layer.source.clone(), table)));
+        }
+        let file = DetectionConfig::project_path(project_root);
+        if file.exists() {
+            tables.push((file.display().to_string(), read_table(&file)?));
+        }
+        for layer in self.layers.iter().filter(|layer| layer.scope != Scope::User) {
+            tables.extend(detection(layer).map(|table| (layer.source.clone(), table)));
+        }
+
+        let config = apply(DetectionConfig::default(), tables.iter().map(|(source, table)| (source.as_str(), table.clone())))?;
+        config.finding_filter()?;
+        Ok(config)
+    }
+}
+
+impl ConfigLayers {
+    /// Effective value of the dotted `key`, e.g. `key_params.time_cost`,
+    /// and the layer it comes from; `None` for an unset setting
+    pub fn get(&self, key: &str) -> CryptoResult<Option<(Value, String)>> {
+        let keys = split_key(key)?;
+        let config = to_table(&self.crypto_config()?)?;
+        let Some(value) = lookup(&config, &keys).cloned() else {
+            return Ok(None);
+        };
+        let source = self
+            .layers
+            .iter()
+            .rev()
+            .find(|layer| lookup(&layer.table, &keys).is_some())
+            .map_or_else(|| "defaults".to_string(), |layer| layer.source.clone());
+        Ok(Some((value, source)))
+    }
+}
+
+/// Set the dotted `key` in the configuration file at `path` to `raw`
+///
+/// `raw` is read as a TOML value, and as a plain string if the setting
+/// does not take that type. Keys under `profiles.<name>.` set the setting
+/// for that profile. The setting must be one [`CryptoConfig`] knows and the
+/// value must have its type; the value as stored is returned.
+pub fn set(path: &Path, key: &str, raw: &str) -> CryptoResult<Value> {
+    let keys = split_key(key)?;
+    let setting = match keys.first().map(String::as_str) {
+        Some(PROFILES) if keys.len() > 2 => &keys[2..],
+        Some(PROFILES) => {
+            return Err(config_error(
+                format!("'{}' does not name a setting", key),
+                Some(format!("Use {}.<name>.<setting>, e.g. {}.ci.key_params.time_cost", PROFILES, PROFILES)),
+            ))
+        }
+        _ => &keys[..],
+    };
+    if setting[0] == DETECTION {
+        return Err(config_error(
+            format!("'{}' is a detection setting", key),
+            Some("Change detection settings with 'cargocrypt config edit'".to_string()),
+        ));
+    }
+
+    let parsed = parse_value(raw);
+    let mut candidates = vec![parsed.clone()];
+    if !parsed.is_str() {
+        candidates.push(Value::String(raw.to_string()));
+    }
+    let mut error = None;
+    let mut stored = None;
+    for value in candidates {
+        match typed_value(key, setting, value) {
+            Ok(value) => {
+                stored = Some(value);
+                break;
+            }
+            Err(e) => {
+                error.get_or_insert(e);
+            }
+        }
+    }
+    let Some(value) = stored else {
+        return Err(error.unwrap_or_else(|| config_error(format!("Invalid value for '{}'", key), None)));
+    };
+
+    let mut table = if path.exists() { read_table(path)? } else { Table::new() };
+    let (last, parents) = keys.split_last().unwrap_or_else(|| unreachable!("split_key returns at least one key"));
+    let mut parent = &mut table;
+    for (depth, key_part) in parents.iter().enumerate() {
+        let entry = parent.entry(key_part.clone()).or_insert_with(|| Value::Table(Table::new()));
+        parent = entry.as_table_mut().ok_or_else(|| {
+            config_error(
+                format!("'{}' in {} is not a table", keys[..=depth].join("."), path.display()),
+                Some("Fix it with 'cargocrypt config edit'".to_string()),
+            )
+        })?;
+    }
+    parent.insert(last.clone(), value.clone());
+    write_table(path, &table)?;
+    Ok(value)
+}
+
+/// Open the configuration file at `path` in the user's editor and save it once it is valid
+///
+/// The editor works on a draft next to the file, so the file only changes
+/// when the edited version is valid. An invalid draft is kept and opened by
+/// the next edit. Returns whether the file changed.
+pub fn edit(path: &Path) -> CryptoResult<bool> {
+    let draft = path.with_extension("toml.edit");
+    let original = if path.exists() { Some(std::fs::read_to_string(path)?) } else { None };
+    if !draft.exists() {
+        let content = match &original {
+            Some(content) => content.clone(),
+            None => serialize(&to_table(&CryptoConfig::default())?)?,
+        };
+        if let Some(dir) = draft.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&draft, content)?;
+    }
+
+    run_editor(&draft).map_err(|e| match e {
+        CargoCryptError::Config { message, .. } => {
+            config_error(message, Some(format!("Your changes are kept in {}", draft.display())))
+        }
+        other => other,
+    })?;
+
+    let content = std::fs::read_to_string(&draft)?;
+    let source = path.display().to_string();
+    let checked = content
+        .parse::<Table>()
+        .map_err(|e| config_error(format!("Invalid configuration in {}: {}", source, e), None))
+        .and_then(|table| check(&source, &table));
+    if let Err(e) = checked {
+        let message = match e {
+            CargoCryptError::Config { message, .. } => message,
+            other => other.to_string(),
+        };
+        return Err(config_error(
+            message,
+            Some(format!(
+                "Your changes are kept in {}; run 'cargocrypt config edit' again to fix them",
+                draft.display()
+            )),
+        ));
+    }
+
+    std::fs::rename(&draft, path)?;
+    Ok(original.as_deref() != Some(content.as_str()))
+}
+
+/// Change settings in the project configuration file
+///
+/// Only the project file is read and written, so settings of the user
+/// configuration, profiles and environment do not leak into it; its
+/// `[profiles]` and `[detection]` tables are kept.
+pub fn update_project<F: FnOnce(&mut CryptoConfig)>(project_root: &Path, update: F) -> CryptoResult<()> {
+    let path = CryptoConfig::project_path(project_root);
+    let existing = if path.exists() { read_table(&path)? } else { Table::new() };
+    let source = path.display().to_string();
+    let mut config = apply(
+        CryptoConfig::default(),
+        std::iter::once((source.as_str(), without(&without(&existing, PROFILES), DETECTION))),
+    )?;
+    update(&mut config);
+
+    let mut table = to_table(&config)?;
+    for key in [DETECTION, PROFILES] {
+        if let Some(value) = existing.get(key) {
+            table.insert(key.to_string(), value.clone());
+        }
+    }
+    write_table(&path, &table)
+}
+
+/// Check every part of a configuration file: its settings, including
+/// detection settings, and each of its profiles on top of them
+fn check(source: &str, table: &Table) -> CryptoResult<()> {
+    let base = (source.to_string(), without(table, PROFILES));
+    check_layers(std::slice::from_ref(&base))?;
+    let Some(profiles) = table.get(PROFILES) else {
+        return Ok(());
+    };
+    let profiles = profiles
+        .as_table()
+        .ok_or_else(|| config_error(format!("[{}] in {} is not a table", PROFILES, source), None))?;
+    for (name, settings) in profiles {
+        let settings = settings
+            .as_table()
+            .cloned()
+            .ok_or_else(|| config_error(format!("Profile '{}' in {} is not a table", name, source), None))?;
+        check_layers(&[base.clone(), (format!("profile '{}' in {}", name, source), settings)])?;
+    }
+    Ok(())
+}
+
+fn check_layers(layers: &[(String, Table)]) -> CryptoResult<()> {
+    let detection = |table: &Table| table.get(DETECTION).and_then(Value::as_table).cloned().unwrap_or_default();
+    apply(
+        CryptoConfig::default(),
+        layers.iter().map(|(source, table)| (source.as_str(), without(table, DETECTION))),
+    )?;
+    apply(DetectionConfig::default(), layers.iter().map(|(source, table)| (source.as_str(), detection(table))))?
+        .finding_filter()?;
+    Ok(())
+}
+
+/// `value` as the type `setting` has in [`CryptoConfig`]
+fn typed_value(key: &str, setting: &[String], value: Value) -> CryptoResult<Value> {
+    let nested = setting
+        .iter()
+        .rev()
+        .fold(value, |value, key| Value::Table(Table::from_iter([(key.clone(), value)])));
+    let Value::Table(table) = nested else {
+        unreachable!("settings have at least one key");
+    };
+    let mut merged = to_table(&CryptoConfig::default())?;
+    merge(&mut merged, table);
+    let config: CryptoConfig = Value::Table(merged).try_into().map_err(|e| {
+        config_error(
+            format!("Invalid value for '{}': {}", key, e),
+            Some(format!("See the current value with 'cargocrypt config get {}'", key)),
+        )
+    })?;
+    lookup(&to_table(&config)?, setting).cloned().ok_or_else(|| {
+        config_error(
+            format!("Unknown setting '{}'", key),
+            Some("Run 'cargocrypt config get <section>' to list the settings of a section".to_string()),
+        )
+    })
+}
+
+/// Dotted `key` split into its parts
+fn split_key(key: &str) -> CryptoResult<Vec<String>> {
+    let keys: Vec<String> = key.split('.').map(str::to_string).collect();
+    if keys.iter().any(String::is_empty) {
+        return Err(config_error(
+            format!("Invalid setting name '{}'", key),
+            Some("Name settings like key_params.time_cost".to_string()),
+        ));
+    }
+    Ok(keys)
+}
+
+fn lookup<'a>(table: &'a Table, keys: &[String]) -> Option<&'a Value> {
+    let (last, parents) = keys.split_last()?;
+    let mut table = table;
+    for key in parents {
+        table = table.get(key)?.as_table()?;
+    }
+    table.get(last)
+}
+
+/// `raw` as a TOML value, or as a string if it is not one
+fn parse_value(raw: &str) -> Value {
+    toml::from_str::<Table>(&format!("value = {}", raw))
+        .ok()
+        .and_then(|mut parsed| parsed.remove("value"))
+        .unwrap_or_else(|| Value::String(raw.to_string()))
+}
+
+/// Open `path` in the user's editor and wait for it to exit
+///
+/// The editor is `$VISUAL`, `$EDITOR` or `vi`; it failing to start or
+/// exiting unsuccessfully is an error.
+pub fn run_editor(path: &Path) -> CryptoResult<()> {
+    let editor = editor();
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+    let status = std::process::Command::new(program).args(words).arg(path).status().map_err(|e| {
+        config_error(
+            format!("Failed to start the editor '{}': {}", editor, e),
+            Some("Set VISUAL or EDITOR to your editor".to_string()),
+        )
+    })?;
+    if !status.success() {
+        return Err(config_error(format!("The editor '{}' exited with {}", editor, status), None));
+    }
+    Ok(())
+}
+
+/// Editor for `config edit` and `edit`: `$VISUAL`, `$EDITOR` or `vi`
+fn editor() -> String {
+    ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string())
+}
+
+/// Merge `layers` over `base`, checking the result after each layer so an
+/// invalid setting is reported with its source
+fn apply<'a, T: Serialize + DeserializeOwned>(
+    base: T,
+    layers: impl IntoIterator<Item = (&'a str, Table)>,
+) -> CryptoResult<T> {
+    let mut merged = to_table(&base)?;
+    let mut config = base;
+    for (source, table) in layers {
+        merge(&mut merged, table);
+        config = Value::Table(merged.clone()).try_into().map_err(|e| {
+            config_error(
+                format!("Invalid configuration in {}: {}", source, e),
+                Some("Fix or remove the setting".to_string()),
+            )
+        })?;
+    }
+    Ok(config)
+}
+
+/// Merge `overrides` into `table`, table by table
+fn merge(table: &mut Table, overrides: Table) {
+    for (key, value) in overrides {
+        match (table.get_mut(&key), value) {
+            (Some(Value::Table(existing)), Value::Table(value)) => merge(existing, value),
+            (_, value) => {
+                table.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Settings of `CARGOCRYPT__SECTION__KEY=value` variables as a table
+///
+/// Values are read as TOML (`true`, `19456`, `{ id = "scrypt" }`) and as a
+/// plain string otherwise.
+fn env_table(env: impl IntoIterator<Item = (String, String)>) -> Table {
+    let mut table = Table::new();
+    for (name, raw) in env {
+        let Some(path) = name.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+        let keys: Vec<String> = path.split("__").map(|key| key.to_ascii_lowercase()).collect();
+        if keys.iter().any(String::is_empty) {
+            continue;
+        }
+        let value = parse_value(&raw);
+        let nested = keys
+            .iter()
+            .rev()
+            .fold(value, |value, key| Value::Table(Table::from_iter([(key.clone(), value)])));
+        if let Value::Table(nested) = nested {
+            merge(&mut table, nested);
+        }
+    }
+    table
+}
+
+fn read_table(path: &Path) -> CryptoResult<Table> {
+    let content = std::fs::read_to_string(path)?;
+    content.parse().map_err(|e| {
+        config_error(
+            format!("Invalid configuration in {}: {}", path.display(), e),
+            Some("Fix the file or delete it and run 'cargocrypt init' to recreate it".to_string()),
+        )
+    })
+}
+
+fn write_table(path: &Path, table: &Table) -> CryptoResult<()> {
+    let content = serialize(table)?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+fn serialize(table: &Table) -> CryptoResult<String> {
+    toml::to_string_pretty(table).map_err(|e| CargoCryptError::Serialization {
+        message: format!("Failed to serialize config: {}", e),
+        source: Box::new(e),
+    })
+}
+
+fn to_table<T: Serialize>(value: &T) -> CryptoResult<Table> {
+    match Value::try_from(value) {
+        Ok(Value::Table(table)) => Ok(table),
+        Ok(_) => Err(config_error("Configuration is not a table".to_string(), None)),
+        Err(e) => Err(CargoCryptError::Serialization {
+            message: format!("Failed to serialize config: {}", e),
+            source: Box::new(e),
+        }),
+    }
+}
+
+fn without(table: &Table, key: &str) -> Table {
+    let mut table = table.clone();
+    table.remove(key);
+    table
+}
+
+fn config_error(message: String, suggestion: Option<String>) -> CargoCryptError {
+    CargoCryptError::Config { message, suggestion }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::Kdf;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_layers_and_profiles() {
+        let dir = TempDir::new().unwrap();
+        let user = dir.path().join("user.toml");
+        std::fs::write(
+            &user,
+            "[file_ops]\nbackup_originals = true\n\n[detection]\nmin_confidence = 0.9\n\n[profiles.ci.key_params]\nmemory_cost = 8192\n",
+        )
+        .unwrap();
+        let project = CryptoConfig::project_path(dir.path());
+        std::fs::create_dir_all(project.parent().unwrap()).unwrap();
+        std::fs::write(
+            &project,
+            "[key_params]\ntime_cost = 4\n\n[profiles.ci]\nkey_params = { kdf = { id = \"argon2id_custom\", m_cost = 8192, t_cost = 1, p_cost = 1 } }\ndetection = { enable_entropy = false }\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join(".cargocrypt/detection.toml"), "min_confidence = 0.5\n").unwrap();
+
+        // Without a profile: user file, then project file
+        let layers = ConfigLayers::load_from(dir.path(), Some(&user), None, []).unwrap();
+        let config = layers.crypto_config().unwrap();
+        assert!(config.file_ops.backup_originals);
+        assert_eq!(config.key_params.time_cost, 4);
+        assert_eq!(config.key_params.memory_cost, CryptoConfig::default().key_params.memory_cost);
+        let detection = layers.detection_config(dir.path()).unwrap();
+        assert_eq!(detection.min_confidence, 0.5);
+        assert!(detection.enable_entropy);
+
+        // The profile of both files, then the environment
+        let env = [
+            ("CARGOCRYPT__KEY_PARAMS__TIME_COST".to_string(), "2".to_string()),
+            ("CARGOCRYPT__FILE_OPS__ENCRYPTED_EXTENSION".to_string(), "sealed".to_string()),
+        ];
+        let layers = ConfigLayers::load_from(dir.path(), Some(&user), Some("ci".to_string()), env).unwrap();
+        let config = layers.crypto_config().unwrap();
+        assert_eq!(config.key_params.memory_cost, 8192);
+        assert_eq!(config.key_params.time_cost, 2);
+        assert_eq!(config.key_params.kdf, Kdf::Argon2idCustom { m_cost: 8192, t_cost: 1, p_cost: 1 });
+        assert_eq!(config.file_ops.encrypted_extension, "sealed");
+        assert!(!layers.detection_config(dir.path()).unwrap().enable_entropy);
+
+        let unknown = ConfigLayers::load_from(dir.path(), Some(&user), Some("prod".to_string()), []).unwrap_err();
+        assert!(unknown.to_string().contains("prod"));
+        let invalid = [("CARGOCRYPT__KEY_PARAMS__TIME_COST".to_string(), "many".to_string())];
+        let layers = ConfigLayers::load_from(dir.path(), None, None, invalid).unwrap();
+        assert!(layers.crypto_config().is_err());
+    }
+
+    #[test]
+    fn test_update_project_keeps_profiles() {
+        let dir = TempDir::new().unwrap();
+        let project = CryptoConfig::project_path(dir.path());
+        std::fs::create_dir_all(project.parent().unwrap()).unwrap();
+        std::fs::write(&project, "[key_params]\ntime_cost = 4\n\n[profiles.ci.key_params]\nmemory_cost = 8192\n").unwrap();
+
+        update_project(dir.path(), |config| config.key_params.parallelism = 2).unwrap();
+        let layers = ConfigLayers::load_from(dir.path(), None, Some("ci".to_string()), []).unwrap();
+        let config = layers.crypto_config().unwrap();
+        assert_eq!(config.key_params.time_cost, 4);
+        assert_eq!(config.key_params.parallelism, 2);
+        assert_eq!(config.key_params.memory_cost, 8192);
+    }
+
+    #[test]
+    fn test_set_and_get() {
+        let dir = TempDir::new().unwrap();
+        let project = CryptoConfig::project_path(dir.path());
+
+        assert_eq!(set(&project, "key_params.time_cost", "5").unwrap(), Value::Integer(5));
+        // Read as a string when the setting takes one
+        assert_eq!(set(&project, "file_ops.encrypted_extension", "123").unwrap(), Value::String("123".to_string()));
+        set(&project, "profiles.ci.key_params.memory_cost", "8192").unwrap();
+        assert!(set(&project, "key_params.time_cost", "many").is_err());
+        assert!(set(&project, "key_params.bogus", "1").is_err());
+        assert!(set(&project, "detection.min_confidence", "0.5").is_err());
+        assert!(set(&project, "key_params..time_cost", "1").is_err());
+
+        let layers = ConfigLayers::load_from(dir.path(), None, None, []).unwrap();
+        let (value, source) = layers.get("key_params.time_cost").unwrap().unwrap();
+        assert_eq!(value, Value::Integer(5));
+        assert_eq!(source, project.display().to_string());
+        let (_, source) = layers.get("key_params.parallelism").unwrap().unwrap();
+        assert_eq!(source, "defaults");
+        assert!(layers.get("limits.max_kdf_memory_mb").unwrap().is_none());
+
+        let layers = ConfigLayers::load_from(dir.path(), None, Some("ci".to_string()), []).unwrap();
+        let (value, source) = layers.get("key_params.memory_cost").unwrap().unwrap();
+        assert_eq!(value, Value::Integer(8192));
+        assert!(source.starts_with("profile 'ci'"));
+        check(&project.display().to_string(), &read_table(&project).unwrap()).unwrap();
+    }
+}
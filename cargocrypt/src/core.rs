@@ -3,18 +3,33 @@
 //! This module provides the main CargoCrypt struct and configuration types
 //! for zero-config cryptographic operations.
 
+use crate::agent::{AgentClient, AgentConfig};
+use crate::backup::{backup_file_name, backups_dir, Backup, BackupEntry, BackupInfo, BACKUP_TAG};
+use crate::bench::{BenchOptions, BenchReport};
 use crate::error::{CargoCryptError, CryptoResult};
-use crate::crypto::{CryptoEngine, PerformanceProfile, MemorySecretStore, SecretStore};
+use crate::expiry::{ExpiringItem, ExpiryChecker, ExpiryConfig};
+use crate::init::{InitOptions, KeyConfig};
+use crate::lock::FileLock;
+use crate::managed::{ManagedEvent, ManagedFiles, ManagedKeys, ManagedOutcome, ManagedReport, ManagedState, MANAGED_FILE};
+use crate::manifest::{IntegrityReport, Manifest, ProjectVerifier};
+use crate::plan::{Change, Plan};
+use crate::session::{Session, Sessions};
+use crate::progress::Progress;
+use crate::vault::{Vault, VaultInfo};
+use crate::crypto::{CryptoEngine, PerformanceProfile, MemorySecretStore, SecretStore, FileSecretStore, SigningKeyPair, DetachedSignature, Keyfile, KeySource, Kdf, Calibration, EncryptedSecret, FileMetadata, PlaintextSecret};
 use crate::resilience::{CircuitBreaker, RetryPolicy, GracefulDegradation, HealthStatus};
 use crate::validation::{InputValidator, ValidationResult};
 use crate::monitoring::{MonitoringManager, MonitoringConfig, CryptoOperation, CryptoOperationType, FileOperation, FileOperationType, PerformanceTracker};
+use std::collections::BTreeMap;
+use std::future::Future;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::RwLock;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::{RwLock, Semaphore};
 use serde::{Deserialize, Serialize};
-use zeroize::{Zeroize, ZeroizeOnDrop};
-use tracing::{info, warn, error};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+use tracing::{debug, info, warn, error};
 
 /// Secure bytes wrapper that zeroizes memory on drop
 #[derive(Clone, Zeroize, ZeroizeOnDrop)]
@@ -85,6 +100,18 @@ pub struct CryptoConfig {
     pub resilience: ResilienceConfig,
     /// Monitoring and telemetry settings
     pub monitoring: MonitoringConfig,
+    /// Resource limits for constrained environments (CI containers)
+    #[serde(default)]
+    pub limits: ResourceLimitsConfig,
+    /// Session key agent settings
+    #[serde(default)]
+    pub agent: AgentConfig,
+    /// Expiry warning settings
+    #[serde(default)]
+    pub expiry: ExpiryConfig,
+    /// How files are keyed, as chosen by `cargocrypt init --interactive`
+    #[serde(default)]
+    pub keys: KeyConfig,
 }
 
 /// Key derivation configuration
@@ -98,6 +125,45 @@ pub struct KeyDerivationConfig {
     pub parallelism: u32,
     /// Output length in bytes (default: 32)
     pub output_length: u32,
+    /// Key derivation function for new files (default: Argon2id)
+    ///
+    /// scrypt and PBKDF2-SHA256 are available for interop, e.g.
+    /// `kdf = { id = "scrypt", log_n = 17, r = 8, p = 1 }`.
+    #[serde(default)]
+    pub kdf: Kdf,
+    /// Wall-clock time one derivation should take when calibrating (default: 500 ms)
+    #[serde(default = "default_calibration_budget_ms")]
+    pub calibration_budget_ms: u64,
+}
+
+fn default_calibration_budget_ms() -> u64 {
+    500
+}
+
+impl KeyDerivationConfig {
+    /// Time budget for [`CryptoEngine::calibrate`]
+    pub fn calibration_budget(&self) -> Duration {
+        Duration::from_millis(self.calibration_budget_ms)
+    }
+
+    /// Use calibrated Argon2id parameters for new files
+    pub fn apply_calibration(&mut self, calibration: &Calibration) {
+        if let Kdf::Argon2idCustom { m_cost, t_cost, p_cost } = calibration.kdf {
+            self.memory_cost = m_cost;
+            self.time_cost = t_cost;
+            self.parallelism = p_cost;
+        }
+        self.kdf = calibration.kdf;
+    }
+
+    /// Use the Argon2id parameters of `profile` for new files
+    pub fn apply_profile(&mut self, profile: PerformanceProfile) {
+        let params = profile.argon2_params();
+        self.memory_cost = params.m_cost();
+        self.time_cost = params.t_cost();
+        self.parallelism = params.p_cost();
+        self.kdf = profile.kdf();
+    }
 }
 
 /// File operation configuration
@@ -115,6 +181,19 @@ pub struct FileOperationConfig {
     pub atomic_operations: bool,
     /// Preserve file metadata (timestamps, permissions)
     pub preserve_metadata: bool,
+    /// Also preserve `user.` extended attributes (Linux only)
+    #[serde(default)]
+    pub preserve_xattrs: bool,
+    /// Replace an existing encrypted file instead of refusing to encrypt
+    #[serde(default)]
+    pub overwrite_existing: bool,
+    /// Encrypt files of at least this many bytes in parallel chunks (0 disables)
+    #[serde(default = "default_chunk_threshold")]
+    pub chunk_threshold: u64,
+}
+
+fn default_chunk_threshold() -> u64 {
+    64 * 1024 * 1024
 }
 
 /// Security configuration
@@ -126,10 +205,55 @@ pub struct SecurityConfig {
     pub auto_zeroize: bool,
     /// Fail securely on errors (don't leave partial state)
     pub fail_secure: bool,
-    /// Maximum password attempts before lockout
+    /// Password prompts `cargocrypt decrypt` gives before failing
     pub max_password_attempts: u32,
+    /// Delay before asking again after a wrong password, doubled each time (milliseconds)
+    #[serde(default = "default_retry_delay_ms")]
+    pub retry_delay_ms: u64,
+    /// Wrong passwords in a row, across runs, that lock a file or vault (0 = never)
+    ///
+    /// Counted in `.cargocrypt/attempts.toml`, see [`crate::lockout`].
+    #[serde(default)]
+    pub lockout_after: u32,
+    /// How long a lockout lasts, in minutes
+    #[serde(default = "default_lockout_minutes")]
+    pub lockout_minutes: u64,
+    /// Make failed decryptions indistinguishable from each other
+    ///
+    /// A wrong password, corrupted ciphertext and a truncated or malformed
+    /// file all return the same error after the same amount of work, so
+    /// neither the message nor the timing tells them apart.
+    #[serde(default = "default_constant_time_operations")]
+    pub constant_time_operations: bool,
+    /// How long `secret get --clip` leaves a value on the clipboard, in seconds (0 = until replaced)
+    #[serde(default = "default_clipboard_clear_secs")]
+    pub clipboard_clear_secs: u64,
+}
+
+fn default_constant_time_operations() -> bool {
+    true
+}
+
+fn default_retry_delay_ms() -> u64 {
+    1000
 }
 
+fn default_lockout_minutes() -> u64 {
+    15
+}
+
+fn default_clipboard_clear_secs() -> u64 {
+    45
+}
+
+/// The one error message of a failed decryption when
+/// [`SecurityConfig::constant_time_operations`] is set
+pub const DECRYPTION_FAILED: &str = "wrong password or key, or the file is corrupted or truncated";
+
+/// Least time a failed decryption takes when
+/// [`SecurityConfig::constant_time_operations`] is set
+pub const DECRYPT_FAILURE_FLOOR: Duration = Duration::from_millis(100);
+
 /// Performance configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PerformanceConfig {
@@ -143,6 +267,69 @@ pub struct PerformanceConfig {
     pub key_caching: bool,
 }
 
+/// Resource limits for constrained environments
+///
+/// Every limit is optional and degrades gracefully: key derivations queue up
+/// instead of running concurrently, scans use fewer threads, and a scan that
+/// runs out of time reports the remaining files as skipped. Each limit can be
+/// overridden with an environment variable, which is the easiest way to set
+/// them in CI.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResourceLimitsConfig {
+    /// Memory budget for concurrent Argon2 derivations in MB (`CARGOCRYPT_MAX_KDF_MEMORY_MB`)
+    pub max_kdf_memory_mb: Option<u64>,
+    /// Maximum open files during parallel scans (`CARGOCRYPT_MAX_OPEN_FILES`)
+    pub max_open_files: Option<usize>,
+    /// Maximum wall time of a directory scan in seconds (`CARGOCRYPT_MAX_SCAN_SECS`)
+    pub max_scan_secs: Option<u64>,
+}
+
+impl ResourceLimitsConfig {
+    /// Apply overrides from `CARGOCRYPT_MAX_*` environment variables
+    pub fn with_env_overrides(mut self) -> Self {
+        fn env_limit<T: std::str::FromStr>(name: &str) -> Option<T> {
+            let value = std::env::var(name).ok()?;
+            match value.trim().parse() {
+                Ok(limit) => Some(limit),
+                Err(_) => {
+                    warn!("Ignoring invalid value for {}: {}", name, value);
+                    None
+                }
+            }
+        }
+
+        if let Some(limit) = env_limit("CARGOCRYPT_MAX_KDF_MEMORY_MB") {
+            self.max_kdf_memory_mb = Some(limit);
+        }
+        if let Some(limit) = env_limit("CARGOCRYPT_MAX_OPEN_FILES") {
+            self.max_open_files = Some(limit);
+        }
+        if let Some(limit) = env_limit("CARGOCRYPT_MAX_SCAN_SECS") {
+            self.max_scan_secs = Some(limit);
+        }
+        self
+    }
+
+    /// Install the key derivation memory budget for this process
+    ///
+    /// Without a configured limit any previously installed budget is kept.
+    pub fn apply_kdf_limit(&self) {
+        if let Some(limit_mb) = self.max_kdf_memory_mb {
+            crate::crypto::limits::set_kdf_memory_limit(Some(limit_mb.saturating_mul(1024)));
+        }
+    }
+
+    /// Apply the scan limits to a scan configuration
+    pub fn apply_to_scan(&self, scan_config: &mut crate::detection::scanner::ScanConfig) {
+        if let Some(max) = self.max_open_files {
+            scan_config.max_open_files = Some(max);
+        }
+        if let Some(secs) = self.max_scan_secs {
+            scan_config.max_scan_secs = Some(secs);
+        }
+    }
+}
+
 /// Resilience and error handling configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ResilienceConfig {
@@ -168,6 +355,9 @@ pub struct ResilienceConfig {
     pub health_check_interval_secs: u64,
 }
 
+/// Features that start enabled and may be disabled under degradation
+const DEFAULT_FEATURES: &[&str] = &["file_operations", "encryption", "tui", "git_integration"];
+
 /// Resilience manager that orchestrates all error handling systems
 #[derive(Clone)]
 pub struct ResilienceManager {
@@ -180,16 +370,12 @@ pub struct ResilienceManager {
 
 impl ResilienceManager {
     pub fn new() -> Self {
-        let degradation = Arc::new(GracefulDegradation::new());
+        let degradation = Arc::new(GracefulDegradation::with_features(DEFAULT_FEATURES));
         
-        // Initialize with default features enabled
+        // Features are enabled synchronously so the first operation never
+        // finds them disabled; circuit breakers register in the background
         let degradation_clone = Arc::clone(&degradation);
         tokio::spawn(async move {
-            degradation_clone.register_feature("file_operations", true).await;
-            degradation_clone.register_feature("encryption", true).await;
-            degradation_clone.register_feature("tui", true).await;
-            degradation_clone.register_feature("git_integration", true).await;
-            
             // Register circuit breakers
             degradation_clone.register_circuit_breaker("file_ops", 3, Duration::from_secs(30)).await;
             degradation_clone.register_circuit_breaker("crypto_ops", 5, Duration::from_secs(60)).await;
@@ -208,16 +394,11 @@ impl ResilienceManager {
     
     /// Create a new ResilienceManager with custom configuration
     pub fn with_config(config: ResilienceConfig) -> Self {
-        let degradation = Arc::new(GracefulDegradation::new());
+        let degradation = Arc::new(GracefulDegradation::with_features(DEFAULT_FEATURES));
         
-        // Initialize with configuration-based settings
+        // Features are enabled up front, circuit breakers from configuration
         let degradation_clone = Arc::clone(&degradation);
         tokio::spawn(async move {
-            degradation_clone.register_feature("file_operations", true).await;
-            degradation_clone.register_feature("encryption", true).await;
-            degradation_clone.register_feature("tui", true).await;
-            degradation_clone.register_feature("git_integration", true).await;
-            
             // Register circuit breakers with configured settings
             if config.circuit_breaker_enabled {
                 let timeout = Duration::from_secs(config.circuit_timeout_secs);
@@ -376,14 +557,31 @@ impl CargoCryptBuilder {
 
     /// Build the CargoCrypt instance
     pub async fn build(self) -> CryptoResult<CargoCrypt> {
-        let config = self.config.unwrap_or_default();
         let project_root = match self.project_root {
             Some(root) => root,
             None => crate::utils::find_project_root()?,
         };
 
+        let mut config = match self.config {
+            Some(config) => config,
+            None => CryptoConfig::load_project(&project_root)?,
+        };
+        config.limits = config.limits.with_env_overrides();
+        config.limits.apply_kdf_limit();
+
         // Initialize crypto engine and secret store
-        let engine = Arc::new(CryptoEngine::new());
+        config.key_params.kdf.validate()?;
+        if config.key_params.kdf.is_weak() {
+            warn!("Configured {} is below current recommendations", config.key_params.kdf);
+        }
+
+        let mut engine = CryptoEngine::new().with_kdf(config.key_params.kdf);
+        if config.agent.enabled {
+            if let Some(agent) = AgentClient::for_project(&project_root) {
+                engine = engine.with_key_cache(Arc::new(agent));
+            }
+        }
+        let engine = Arc::new(engine);
         let secret_store = Arc::new(MemorySecretStore::new()) as Arc<dyn SecretStore>;
 
         let monitoring = Arc::new(MonitoringManager::new(config.monitoring.clone()));
@@ -436,11 +634,68 @@ impl CargoCrypt {
         self.config.read().await.clone()
     }
 
+    /// Change the configuration of this instance (the config file is not touched)
+    pub async fn update_config<F: FnOnce(&mut CryptoConfig)>(&self, update: F) {
+        update(&mut *self.config.write().await);
+    }
+
+    /// Get the project root directory
+    pub fn project_root(&self) -> &Path {
+        &self.project_root
+    }
+
     /// Get the crypto engine
     pub fn crypto(&self) -> &CryptoEngine {
         &self.engine
     }
     
+    /// Calibrate Argon2 parameters for this machine and save them to the project configuration
+    ///
+    /// Uses the budget from the configuration unless one is given. Files
+    /// encrypted afterwards by new instances use the calibrated parameters;
+    /// existing files keep decrypting with the parameters in their headers.
+    pub async fn calibrate(&self, budget: Option<Duration>) -> CryptoResult<Calibration> {
+        let _lock = FileLock::state(&self.project_root).await?;
+        let mut config = self.config.write().await;
+        let budget = budget.unwrap_or_else(|| config.key_params.calibration_budget());
+        let calibration = Self::run_calibration(budget).await?;
+        let budget_ms = u64::try_from(budget.as_millis()).unwrap_or(u64::MAX);
+
+        config.key_params.apply_calibration(&calibration);
+        config.key_params.calibration_budget_ms = budget_ms;
+        crate::config::update_project(&self.project_root, |project| {
+            project.key_params.apply_calibration(&calibration);
+            project.key_params.calibration_budget_ms = budget_ms;
+        })?;
+        info!("Calibrated {} in {:?}", calibration.kdf, calibration.elapsed);
+
+        Ok(calibration)
+    }
+
+    async fn run_calibration(budget: Duration) -> CryptoResult<Calibration> {
+        tokio::task::spawn_blocking(move || CryptoEngine::calibrate(budget))
+            .await
+            .map_err(|e| CargoCryptError::Config {
+                message: format!("Calibration task failed: {}", e),
+                suggestion: None,
+            })?
+            .map_err(CargoCryptError::from)
+    }
+
+    /// Benchmark key derivation, ciphers and file I/O on this machine
+    ///
+    /// File I/O is measured in the project root, where encrypted files are
+    /// written. See [`crate::bench`] for how the profile is recommended.
+    pub async fn benchmark(&self, options: BenchOptions) -> CryptoResult<BenchReport> {
+        let root = self.project_root.clone();
+        tokio::task::spawn_blocking(move || crate::bench::run(&root, &options))
+            .await
+            .map_err(|e| CargoCryptError::Config {
+                message: format!("Benchmark task failed: {}", e),
+                suggestion: None,
+            })?
+    }
+
     /// Get the monitoring manager for accessing metrics and performance data
     pub fn monitoring(&self) -> &MonitoringManager {
         &self.monitoring
@@ -448,191 +703,1006 @@ impl CargoCrypt {
 
     /// Initialize CargoCrypt in a project directory
     pub async fn init_project() -> CryptoResult<()> {
+        Self::init_project_with(&InitOptions::default()).await
+    }
+
+    /// Initialize CargoCrypt with the choices of `cargocrypt init --interactive`
+    ///
+    /// Unlike [`Self::init_project`], an existing configuration is updated
+    /// with the chosen profile and key mode. Keyfile mode creates the
+    /// project keyfile unless it exists.
+    pub async fn init_project_with(options: &InitOptions) -> CryptoResult<()> {
         let project_root = crate::utils::find_project_root()?;
         let config_dir = project_root.join(".cargocrypt");
         
         if !config_dir.exists() {
             tokio::fs::create_dir_all(&config_dir).await?;
         }
+        let _lock = FileLock::state(&project_root).await?;
         
-        // Create default configuration file with resilience settings
-        let config_file = config_dir.join("config.toml");
+        // Create default configuration file with resilience settings and
+        // Argon2 parameters calibrated for this machine, unless a profile
+        // was chosen
+        let config_file = CryptoConfig::project_path(&project_root);
         if !config_file.exists() {
-            let default_config = CryptoConfig::default();
-            let config_toml = toml::to_string_pretty(&default_config)
-                .map_err(|e| CargoCryptError::Serialization {
-                    message: format!("Failed to serialize default config: {}", e),
-                    source: Box::new(e),
-                })?;
-            
-            tokio::fs::write(&config_file, config_toml).await?;
+            let mut config = CryptoConfig::default();
+            if options.profile.is_none() {
+                let calibration = Self::run_calibration(config.key_params.calibration_budget()).await?;
+                config.key_params.apply_calibration(&calibration);
+            }
+
+            config.save(&config_file)?;
             info!("Created default configuration at: {}", config_file.display());
         }
+
+        if options.profile.is_none() && options.key_mode.is_none() {
+            return Ok(());
+        }
+        let keys = options.key_mode.map(KeyConfig::for_mode);
+        crate::config::update_project(&project_root, |config| {
+            if let Some(profile) = options.profile {
+                config.performance_profile = profile;
+                config.key_params.apply_profile(profile);
+            }
+            if let Some(keys) = keys.clone() {
+                config.keys = keys;
+            }
+        })?;
+
+        if let Some(keyfile) = keys.and_then(|keys| keys.keyfile) {
+            let keyfile = project_root.join(keyfile);
+            if !keyfile.exists() {
+                Keyfile::generate()?.save(&keyfile)?;
+                info!("Created keyfile at: {}", keyfile.display());
+            }
+        }
         
         Ok(())
     }
 
+    /// What [`Self::init_project`] would create
+    pub fn plan_init_project() -> CryptoResult<Plan> {
+        let project_root = crate::utils::find_project_root()?;
+        let mut plan = Plan::new();
+        let config_dir = project_root.join(".cargocrypt");
+        if !config_dir.exists() {
+            plan.push(Change::Create, config_dir, None);
+        }
+        let config_file = CryptoConfig::project_path(&project_root);
+        if !config_file.exists() {
+            plan.push(Change::Create, config_file, Some("with Argon2 parameters calibrated for this machine"));
+        }
+        Ok(plan)
+    }
+
+    /// What encrypting `path` would write, and remove with `in_place`
+    ///
+    /// Needs no password. Fails like encrypting would when the file is
+    /// missing, or when its encrypted file exists and may not be replaced.
+    pub async fn plan_encrypt_file<P: AsRef<Path>>(&self, path: P, in_place: bool, shred: bool) -> CryptoResult<Plan> {
+        let path = path.as_ref();
+        if !path.is_file() {
+            return Err(CargoCryptError::file_not_found(path));
+        }
+        let encrypted_path = encrypted_path(path);
+        let config = self.config.read().await;
+        if !config.file_ops.overwrite_existing && encrypted_path.exists() {
+            return Err(CargoCryptError::already_exists(&encrypted_path));
+        }
+
+        let mut plan = Plan::new();
+        plan.write(&encrypted_path, None);
+        if in_place {
+            plan.delete(path, Some(if shred { "shredded first" } else { "plaintext" }));
+        } else if config.file_ops.backup_originals {
+            plan.write(backup_path(path), Some("backup of the original"));
+        }
+        Ok(plan)
+    }
+
     /// Encrypt a file with the given password
     pub async fn encrypt_file<P: AsRef<Path>>(&self, path: P, password: &str) -> CryptoResult<PathBuf> {
-        use crate::crypto::{PlaintextSecret, EncryptionOptions};
-        
-        let path = path.as_ref().to_path_buf();
-        let path_str = path.to_string_lossy().to_string();
-        
-        // Comprehensive input validation
-        let password_validation = self.resilience.validate_input("password", password);
-        if !password_validation.is_valid {
-            let error_messages: Vec<String> = password_validation.errors
-                .iter()
-                .filter(|e| e.severity == crate::validation::ValidationSeverity::Critical)
-                .map(|e| e.message.clone())
-                .collect();
-            
-            return Err(CargoCryptError::Validation {
-                message: "Password validation failed".to_string(),
-                errors: error_messages,
-                warnings: password_validation.warnings,
+        self.encrypt_file_inner(path.as_ref(), Some(password), None, Plaintext::Keep, None).await
+    }
+
+    /// Encrypt a file and remove the plaintext
+    ///
+    /// The ciphertext is written to a temporary file, synced and atomically
+    /// renamed into place before the original is deleted, so a complete copy
+    /// is on disk at every point. No backup of the original is made. With `shred`
+    /// the plaintext is overwritten with random data first; on copy-on-write
+    /// filesystems and SSDs this is best effort.
+    pub async fn encrypt_file_in_place<P: AsRef<Path>>(&self, path: P, password: &str, shred: bool) -> CryptoResult<PathBuf> {
+        self.encrypt_file_inner(path.as_ref(), Some(password), None, Plaintext::Remove { shred }, None).await
+    }
+
+    /// Encrypt a file with a keyfile, optionally mixed with a password
+    pub async fn encrypt_file_with_keyfile<P: AsRef<Path>>(
+        &self,
+        path: P,
+        keyfile: &Keyfile,
+        password: Option<&str>,
+    ) -> CryptoResult<PathBuf> {
+        self.encrypt_file_inner(path.as_ref(), password, Some(keyfile), Plaintext::Keep, None).await
+    }
+
+    /// Encrypt a file with a keyfile and remove the plaintext, see [`Self::encrypt_file_in_place`]
+    pub async fn encrypt_file_with_keyfile_in_place<P: AsRef<Path>>(
+        &self,
+        path: P,
+        keyfile: &Keyfile,
+        password: Option<&str>,
+        shred: bool,
+    ) -> CryptoResult<PathBuf> {
+        self.encrypt_file_inner(path.as_ref(), password, Some(keyfile), Plaintext::Remove { shred }, None).await
+    }
+
+    /// Create a vault with its own random key, protected by `password`
+    pub async fn create_vault(&self, name: &str, password: &str, description: Option<String>) -> CryptoResult<Vault> {
+        self.validate_password(password)?;
+        let vault = Vault::new(&self.project_root, name)?;
+        let _lock = FileLock::state(&self.project_root).await?;
+        if vault.key_path().exists() {
+            return Err(CargoCryptError::Config {
+                message: format!("Vault '{}' already exists", name),
+                suggestion: Some(format!("Change its password with `cargocrypt vault passwd {}`", name)),
             });
         }
-        
-        let path_validation = self.resilience.validate_input("file_path", &path_str);
-        if !path_validation.is_valid {
-            let error_messages: Vec<String> = path_validation.errors
-                .iter()
-                .filter(|e| e.severity == crate::validation::ValidationSeverity::Critical)
-                .map(|e| e.message.clone())
-                .collect();
-            
-            return Err(CargoCryptError::Validation {
-                message: "File path validation failed".to_string(),
-                errors: error_messages,
-                warnings: path_validation.warnings,
+
+        let key = Keyfile::generate()?;
+        self.wrap_vault_key(&vault, &key, password).await?;
+        let created_at = crate::utils::unix_now();
+        vault.save_info(&VaultInfo { created_at, description })?;
+
+        info!("Created vault '{}' at: {}", name, vault.dir().display());
+        Ok(vault)
+    }
+
+    /// All vaults of the project, sorted by name
+    pub fn vaults(&self) -> CryptoResult<Vec<Vault>> {
+        Vault::list(&self.project_root)
+    }
+
+    /// Unwrap a vault's key with its password
+    ///
+    /// The key works as a [`Keyfile`], e.g. to hand one vault to CI.
+    pub async fn unlock_vault(&self, name: &str, password: &str) -> CryptoResult<Keyfile> {
+        let vault = Vault::open(&self.project_root, name)?;
+        let encrypted = EncryptedSecret::from_bytes(&tokio::fs::read(vault.key_path()).await?)?;
+        let key = self.engine.decrypt(&encrypted, password).map_err(|e| match e {
+            crate::crypto::CryptoError::AuthenticationFailed => CargoCryptError::Auth {
+                message: format!("Wrong password for vault '{}'", name),
+                retry_suggestion: None,
+            },
+            e => e.into(),
+        })?;
+        Ok(Keyfile::from_bytes(key.as_bytes())?)
+    }
+
+    /// Change a vault's password; files in the vault are unaffected
+    pub async fn change_vault_password(&self, name: &str, old_password: &str, new_password: &str) -> CryptoResult<()> {
+        self.validate_password(new_password)?;
+        let _lock = FileLock::state(&self.project_root).await?;
+        let key = self.unlock_vault(name, old_password).await?;
+        self.wrap_vault_key(&Vault::open(&self.project_root, name)?, &key, new_password).await
+    }
+
+    /// Rewrap every key of the project that `old_password` opens under `new_password`
+    ///
+    /// Vault keys and the signing key are envelope keys: files and signatures
+    /// depend on the key, not on the password, so rewrapping them is the whole
+    /// password change and no file is re-encrypted. Keys the old password
+    /// does not open are left alone. Every new wrapping is staged before any
+    /// is put in place. Files encrypted directly with a password derive their
+    /// key from it and keep needing the old one; they are listed in the result.
+    pub async fn change_password(&self, old_password: &str, new_password: &str) -> CryptoResult<PasswordChange> {
+        self.validate_password(new_password)?;
+        let _lock = FileLock::state(&self.project_root).await?;
+
+        let mut key_files: Vec<PathBuf> = self.vaults()?.iter().map(Vault::key_path).collect();
+        let signing_key_path = self.signing_key_path();
+        if signing_key_path.exists() {
+            key_files.push(signing_key_path);
+        }
+
+        let mut change = PasswordChange::default();
+        let mut wrapped = Vec::new();
+        for path in key_files {
+            match self.rewrap_key(&path, old_password, new_password).await? {
+                Some(bytes) => wrapped.push((path, bytes)),
+                None => change.skipped.push(path),
+            }
+        }
+        if wrapped.is_empty() && !change.skipped.is_empty() {
+            return Err(CargoCryptError::Auth {
+                message: "The current password does not open any key of the project".to_string(),
+                retry_suggestion: Some("Check the current password and try again".to_string()),
             });
         }
-        
-        // Display validation warnings if any
-        for warning in &password_validation.warnings {
-            warn!("Password validation warning: {}", warning);
+
+        let mut staged = Vec::with_capacity(wrapped.len());
+        for (path, bytes) in &wrapped {
+            match stage_file(path, bytes).await {
+                Ok(temp_path) => staged.push((temp_path, path.clone())),
+                Err(e) => {
+                    for (temp_path, _) in &staged {
+                        let _ = tokio::fs::remove_file(temp_path).await;
+                    }
+                    return Err(e);
+                }
+            }
         }
-        for warning in &path_validation.warnings {
-            warn!("Path validation warning: {}", warning);
+        for (temp_path, path) in staged {
+            tokio::fs::rename(&temp_path, &path).await?;
+            sync_parent_dir(&path).await;
+            info!("Rewrapped {}", path.display());
+            change.rewrapped.push(path);
         }
-        
-        let config = self.config.read().await;
-        
-        // Execute file operations with resilience protection
-        let path_clone = path.clone();
-        let file_content = {
-            let path_str_clone = path_str.clone();
-            let path_for_read = path.clone();
-            self.resilience.execute_file_operation(move || {
-                let path_str = path_str_clone.clone();
-                let path_clone = path_for_read.clone();
-                async move {
-                    info!("Reading file for encryption: {}", path_str);
-                    let content = tokio::fs::read(&path_clone).await.map_err(|e| CargoCryptError::from(e))?;
-            
-            // Validate file content
-            let filename = path_clone.file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or("unknown");
-            let validator = InputValidator::new();
-            let content_validation = validator.validate_file_content(&content, filename);
-            
-            for warning in &content_validation.warnings {
-                warn!("File content warning: {}", warning);
+
+        // Keys and stores under `.cargocrypt/` are not project files
+        let state_dir = self.project_root.join(".cargocrypt");
+        let extension = self.config.read().await.file_ops.encrypted_extension.clone();
+        for path in crate::expiry::find_encrypted_files(&self.project_root, &extension) {
+            if path.starts_with(&state_dir) {
+                continue;
             }
-            
-                    Ok(content)
-                }
-            }).await?
+            let requires_password = tokio::fs::read(&path)
+                .await
+                .ok()
+                .and_then(|bytes| EncryptedSecret::from_bytes(&bytes).ok())
+                .is_some_and(|encrypted| encrypted.key_source().requires_password());
+            if requires_password {
+                change.password_files.push(path);
+            }
+        }
+
+        Ok(change)
+    }
+
+    /// The wrapped key at `path` rewrapped under `new_password`, or `None` if `old_password` does not open it
+    async fn rewrap_key(&self, path: &Path, old_password: &str, new_password: &str) -> CryptoResult<Option<Vec<u8>>> {
+        use crate::crypto::EncryptionOptions;
+
+        let encrypted = EncryptedSecret::from_bytes(&tokio::fs::read(path).await?)?;
+        let key = match self.engine.decrypt(&encrypted, old_password) {
+            Ok(key) => key,
+            Err(crate::crypto::CryptoError::AuthenticationFailed) => return Ok(None),
+            Err(e) => return Err(e.into()),
         };
-        
-        let plaintext = PlaintextSecret::new(file_content);
-        
-        // Execute crypto operations with circuit breaker protection
-        let password_str = password.to_string();
-        let engine_clone = Arc::clone(&self.engine);
-        let encrypted = {
-            info!("Encrypting file content");
-            self.engine.encrypt(
-                plaintext, 
-                &password_str, 
-                crate::crypto::EncryptionOptions::default()
-            ).await.map_err(|e| CargoCryptError::from(e))?
+        let options = EncryptionOptions::default().with_metadata(encrypted.metadata().clone());
+        let rewrapped = self.engine.encrypt(key, new_password, options).await?;
+        Ok(Some(rewrapped.to_bytes()?))
+    }
+
+    /// Back up `.cargocrypt/` into an archive encrypted with `password`
+    ///
+    /// The backup goes to `output`, or to a new file in
+    /// `.cargocrypt/backups/`. See [`crate::backup`] for what it holds.
+    pub async fn create_backup(&self, output: Option<&Path>, password: &str) -> CryptoResult<Backup> {
+        use crate::crypto::{EncryptionOptions, SecretMetadata};
+
+        self.validate_password(password)?;
+        let state_dir = self.project_root.join(".cargocrypt");
+        if !state_dir.is_dir() {
+            return Err(CargoCryptError::config_not_found());
+        }
+        let (mut archive, files) = {
+            let _lock = FileLock::state(&self.project_root).await?;
+            crate::backup::pack(&state_dir)?
         };
-        
-        // Create encrypted file path
-        let encrypted_path = path.with_extension(format!("{}.enc", 
-            path.extension().and_then(|ext| ext.to_str()).unwrap_or("dat")));
-        
-        // Write encrypted content with resilience protection
-        let encrypted_path_clone = encrypted_path.clone();
-        let atomic_ops = config.file_ops.atomic_operations;
-        {
-            let encrypted_for_write = encrypted.clone();
-            let path_for_write = encrypted_path_clone.clone();
-            self.resilience.execute_file_operation(move || {
-                let encrypted_bytes_result = encrypted_for_write.clone();
-                let encrypted_path_clone = path_for_write.clone();
-                async move {
-                    info!("Writing encrypted file: {}", encrypted_path_clone.display());
-                    let encrypted_bytes = encrypted_bytes_result.to_bytes().map_err(|e| CargoCryptError::from(e))?;
-                    
-                    // Atomic operation: write to temp file first, then move
-                    if atomic_ops {
-                        let temp_path = encrypted_path_clone.with_extension("tmp");
-                        tokio::fs::write(&temp_path, &encrypted_bytes).await.map_err(|e| CargoCryptError::from(e))?;
-                        tokio::fs::rename(&temp_path, &encrypted_path_clone).await.map_err(|e| CargoCryptError::from(e))?;
-                    } else {
-                        tokio::fs::write(&encrypted_path_clone, encrypted_bytes).await.map_err(|e| CargoCryptError::from(e))?;
-                    }
-                    
-                    Ok(())
-                }
-            }).await?
+
+        let mut metadata = SecretMetadata::with_description(format!("Backup of {} file(s) of .cargocrypt/", files.len()));
+        metadata.tags.push(BACKUP_TAG.to_string());
+        let path = match output {
+            Some(output) => output.to_path_buf(),
+            None => backups_dir(&self.project_root).join(backup_file_name(metadata.created_at.unwrap_or_default())),
         };
-        
-        // Optionally backup original with resilience protection
-        if config.file_ops.backup_originals {
-            let path_for_backup = path.clone();
-            self.resilience.execute_file_operation(move || {
-                let path_clone = path_for_backup.clone();
-                async move {
-                    let backup_path = path_clone.with_extension(format!("{}.backup", 
-                        path_clone.extension().and_then(|ext| ext.to_str()).unwrap_or("dat")));
-                    info!("Creating backup: {}", backup_path.display());
-                    tokio::fs::copy(&path_clone, backup_path).await.map_err(|e| CargoCryptError::from(e))?;
-                    Ok(())
-                }
-            }).await?;
+        if tokio::fs::try_exists(&path).await.unwrap_or(true) {
+            return Err(CargoCryptError::Config {
+                message: format!("{} already exists", path.display()),
+                suggestion: Some("Write the backup to another path with --output".to_string()),
+            });
         }
-        
-        info!("File encryption completed successfully: {}", encrypted_path.display());
-        Ok(encrypted_path)
+
+        let plaintext = PlaintextSecret::new(std::mem::take(&mut *archive));
+        let encrypted = self.engine.encrypt(plaintext, password, EncryptionOptions::default().with_metadata(metadata)).await?;
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        write_file_synced(&path, &encrypted.to_bytes()?).await?;
+        info!("Backed up {} files to {}", files.len(), path.display());
+        Ok(Backup { path, files })
     }
 
-    /// Decrypt a file with the given password
-    pub async fn decrypt_file<P: AsRef<Path>>(&self, path: P, password: &str) -> CryptoResult<PathBuf> {
+    /// The files in the backup at `path`, decrypted with `password`
+    pub async fn read_backup<P: AsRef<Path>>(
+        &self,
+        path: P,
+        password: &str,
+    ) -> CryptoResult<Vec<(BackupEntry, Zeroizing<Vec<u8>>)>> {
         let path = path.as_ref();
-        let path_str = path.to_string_lossy();
-        
-        // Comprehensive input validation
-        let password_validation = self.resilience.validate_input("password", password);
-        if !password_validation.is_valid {
-            let error_messages: Vec<String> = password_validation.errors
-                .iter()
-                .filter(|e| e.severity == crate::validation::ValidationSeverity::Critical)
-                .map(|e| e.message.clone())
-                .collect();
-            
-            return Err(CargoCryptError::Validation {
-                message: "Password validation failed".to_string(),
-                errors: error_messages,
-                warnings: password_validation.warnings,
-            });
-        }
-        
+        BackupInfo::read(path)?;
+        let encrypted = EncryptedSecret::from_bytes(&tokio::fs::read(path).await?)?;
+        let archive = self.engine.decrypt(&encrypted, password)?;
+        crate::backup::unpack(archive.as_bytes())
+    }
+
+    /// What restoring the backup at `path` would write into `.cargocrypt/`
+    ///
+    /// Files that already hold the backed up content are left out; files
+    /// that exist with other content are listed as modified.
+    pub async fn plan_restore_backup<P: AsRef<Path>>(&self, path: P, password: &str) -> CryptoResult<Plan> {
+        let files = self.read_backup(path, password).await?;
+        Ok(self.restore_plan(&files))
+    }
+
+    /// Restore `.cargocrypt/` from the backup at `path`, returning what was written
+    ///
+    /// Files that are not in the backup are left alone. If a file exists
+    /// with other content than the backup's, nothing is restored unless
+    /// `force` is set. Restored files are readable only by the owner.
+    pub async fn restore_backup<P: AsRef<Path>>(&self, path: P, password: &str, force: bool) -> CryptoResult<Plan> {
+        let files = self.read_backup(path, password).await?;
+        let state_dir = self.project_root.join(".cargocrypt");
+        let _lock = FileLock::state(&self.project_root).await?;
+
+        let plan = self.restore_plan(&files);
+        let changed: Vec<_> = plan
+            .iter()
+            .filter(|planned| planned.change == Change::Modify)
+            .map(|planned| planned.path.strip_prefix(&self.project_root).unwrap_or(&planned.path).display().to_string())
+            .collect();
+        if !force && !changed.is_empty() {
+            return Err(CargoCryptError::Config {
+                message: format!("Restoring would replace {} changed file(s): {}", changed.len(), changed.join(", ")),
+                suggestion: Some("Check them with --dry-run, then run with --force to replace them".to_string()),
+            });
+        }
+
+        for (entry, content) in &files {
+            let target = state_dir.join(&entry.path);
+            if plan.iter().any(|planned| planned.path == target) {
+                crate::backup::write_restored(&target, content)?;
+            }
+        }
+        info!("Restored {} of {} files from backup", plan.len(), files.len());
+        Ok(plan)
+    }
+
+    fn restore_plan(&self, files: &[(BackupEntry, Zeroizing<Vec<u8>>)]) -> Plan {
+        let state_dir = self.project_root.join(".cargocrypt");
+        let mut plan = Plan::new();
+        for (entry, content) in files {
+            plan.write_content(state_dir.join(&entry.path), content, None);
+        }
+        plan
+    }
+
+    async fn wrap_vault_key(&self, vault: &Vault, key: &Keyfile, password: &str) -> CryptoResult<()> {
+        use crate::crypto::{EncryptionOptions, PlaintextSecret, SecretType};
+
+        let options = EncryptionOptions::new()
+            .with_type(SecretType::PrivateKey)
+            .with_description(format!("Key of vault '{}'", vault.name()));
+        let secret = PlaintextSecret::new(key.as_bytes().to_vec());
+        let encrypted = self.engine.encrypt(secret, password, options).await?;
+
+        tokio::fs::create_dir_all(vault.dir()).await?;
+        write_file_synced(&vault.key_path(), &encrypted.to_bytes()?).await
+    }
+
+    /// Encrypt a file with a vault's key
+    pub async fn encrypt_file_in_vault<P: AsRef<Path>>(&self, path: P, vault: &str, password: &str) -> CryptoResult<PathBuf> {
+        let key = self.unlock_vault(vault, password).await?;
+        self.encrypt_file_inner(path.as_ref(), None, Some(&key), Plaintext::Keep, Some(vault)).await
+    }
+
+    /// Encrypt a file with a vault's key and remove the plaintext, see [`Self::encrypt_file_in_place`]
+    pub async fn encrypt_file_in_vault_in_place<P: AsRef<Path>>(
+        &self,
+        path: P,
+        vault: &str,
+        password: &str,
+        shred: bool,
+    ) -> CryptoResult<PathBuf> {
+        let key = self.unlock_vault(vault, password).await?;
+        self.encrypt_file_inner(path.as_ref(), None, Some(&key), Plaintext::Remove { shred }, Some(vault)).await
+    }
+
+    /// Decrypt a file that was encrypted in `vault`
+    pub async fn decrypt_file_in_vault<P: AsRef<Path>>(&self, path: P, vault: &str, password: &str) -> CryptoResult<PathBuf> {
+        let path = path.as_ref();
+        match self.file_vault(path).await? {
+            Some(file_vault) if file_vault == vault => {}
+            Some(file_vault) => {
+                return Err(crate::crypto::CryptoError::invalid_key(format!(
+                    "{} belongs to vault '{}', not '{}'",
+                    path.display(),
+                    file_vault,
+                    vault
+                ))
+                .into())
+            }
+            None => {
+                return Err(crate::crypto::CryptoError::invalid_key(format!("{} is not in a vault", path.display())).into())
+            }
+        }
+
+        let key = self.unlock_vault(vault, password).await?;
+        self.decrypt_file_inner(path, None, Some(&key)).await
+    }
+
+    /// The vault an encrypted file belongs to, read from its header
+    pub async fn file_vault<P: AsRef<Path>>(&self, path: P) -> CryptoResult<Option<String>> {
+        let encrypted = EncryptedSecret::from_bytes(&tokio::fs::read(path).await?)?;
+        Ok(encrypted.vault().map(str::to_string))
+    }
+
+    /// Path of the project's secret store
+    pub fn secret_store_path(&self) -> PathBuf {
+        self.project_root.join(".cargocrypt").join(crate::crypto::store::SECRETS_FILE)
+    }
+
+    /// Open the project's secret store, `.cargocrypt/secrets.enc`
+    ///
+    /// The store is created with the first secret saved to it.
+    pub async fn open_secret_store(&self, password: &str) -> CryptoResult<FileSecretStore> {
+        let path = self.secret_store_path();
+        if !path.exists() {
+            self.validate_password(password)?;
+        }
+        FileSecretStore::open_with_password(path, password, self.engine.kdf()).await
+    }
+
+    /// Start an empty `.env` file encrypted per variable, protected by `password`
+    ///
+    /// Returns the file and the key to fill it with
+    /// [`EncryptedDotenv::update`](crate::dotenv::EncryptedDotenv::update).
+    pub fn new_encrypted_dotenv(&self, password: &str) -> CryptoResult<(crate::dotenv::EncryptedDotenv, crate::crypto::DerivedKey)> {
+        self.validate_password(password)?;
+        crate::dotenv::EncryptedDotenv::new(password, self.engine.kdf())
+    }
+
+    /// Open the secret store of a vault, unlocking the vault with its password
+    pub async fn open_vault_secret_store(&self, vault: &str, password: &str) -> CryptoResult<FileSecretStore> {
+        let key = self.unlock_vault(vault, password).await?;
+        let path = Vault::open(&self.project_root, vault)?.secrets_path();
+        FileSecretStore::open_with_keyfile(path, &key, Some(vault)).await
+    }
+
+    /// Encrypt many files concurrently with the given password
+    ///
+    /// Up to [`BatchOptions::concurrency`] files are encrypted at a time, and
+    /// the report has one outcome per path in the order they were given. By
+    /// default a failure only affects its own file. In all-or-nothing mode
+    /// every file is encrypted in memory before anything is written, and if
+    /// any file fails, every `.enc` file the batch wrote is removed again and
+    /// any file it replaced is restored.
+    pub async fn encrypt_batch<I, P>(&self, paths: I, password: &str, options: BatchOptions) -> BatchReport
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let paths: Vec<PathBuf> = paths.into_iter().map(|path| path.as_ref().to_path_buf()).collect();
+        let limit = match options.concurrency {
+            Some(limit) => limit,
+            None => self.config.read().await.performance.max_concurrent_ops,
+        }
+        .max(1);
+
+        let progress = &options.progress;
+        progress.set_total(paths.len() as u64, paths.iter().map(|path| file_size(path)).sum());
+
+        if !options.all_or_nothing {
+            let results = for_each_bounded(&paths, limit, |path| {
+                let crypt = self.clone();
+                let password = password.to_string();
+                let progress = progress.clone();
+                async move {
+                    let file = progress.start_file(&path, file_size(&path));
+                    let result = crypt.encrypt_file_inner(&path, Some(&password), None, Plaintext::Keep, None).await;
+                    if result.is_ok() {
+                        file.finish();
+                    }
+                    result
+                }
+            })
+            .await;
+
+            let files = paths
+                .into_iter()
+                .zip(results)
+                .map(|(path, result)| match result {
+                    Ok(encrypted_path) => (path, BatchFileOutcome::Encrypted(encrypted_path)),
+                    Err(e) => (path, BatchFileOutcome::Failed(e)),
+                })
+                .collect();
+            return BatchReport { files, rolled_back: false };
+        }
+
+        // Held until every file is written or rolled back
+        let mut locks = Vec::with_capacity(paths.len());
+        let mut lock_failure = None;
+        for (i, path) in paths.iter().enumerate() {
+            match FileLock::path(&self.project_root, &encrypted_path(path)).await {
+                Ok(lock) => locks.push(lock),
+                Err(e) => {
+                    lock_failure = Some((i, e));
+                    break;
+                }
+            }
+        }
+        if let Some((failed, e)) = lock_failure {
+            warn!("Batch encryption could not lock {}, nothing was written", paths[failed].display());
+            let mut files: Vec<_> = paths
+                .into_iter()
+                .map(|path| (path, BatchFileOutcome::RolledBack))
+                .collect();
+            files[failed].1 = BatchFileOutcome::Failed(e);
+            return BatchReport { files, rolled_back: true };
+        }
+
+        let sealed = for_each_bounded(&paths, limit, |path| {
+            let crypt = self.clone();
+            let password = password.to_string();
+            let progress = progress.clone();
+            async move {
+                let file = progress.start_file(&path, file_size(&path));
+                let result = crypt.seal_file(&path, Some(&password), None, None).await;
+                if result.is_ok() {
+                    file.finish();
+                }
+                result
+            }
+        })
+        .await;
+
+        if sealed.iter().any(|result| result.is_err()) {
+            warn!("Batch encryption failed, nothing was written");
+            let files = paths
+                .into_iter()
+                .zip(sealed)
+                .map(|(path, result)| match result {
+                    Ok(_) => (path, BatchFileOutcome::RolledBack),
+                    Err(e) => (path, BatchFileOutcome::Failed(e)),
+                })
+                .collect();
+            return BatchReport { files, rolled_back: true };
+        }
+
+        let sealed: Vec<(EncryptedSecret, PathBuf)> = sealed.into_iter().filter_map(Result::ok).collect();
+        match self.commit_batch(&paths, &sealed).await {
+            Ok(()) => {
+                info!("Batch encrypted {} files", paths.len());
+                drop(locks);
+                let files: Vec<_> = paths.iter().cloned().zip(sealed.iter().map(|(_, encrypted_path)| encrypted_path.clone())).collect();
+                self.record_managed(&files, KeySource::Password, None, ManagedEvent::Encrypted).await;
+                let files = paths
+                    .into_iter()
+                    .zip(sealed)
+                    .map(|(path, (_, encrypted_path))| (path, BatchFileOutcome::Encrypted(encrypted_path)))
+                    .collect();
+                BatchReport { files, rolled_back: false }
+            }
+            Err((failed, e)) => {
+                warn!("Batch encryption failed at {}, rolled back", paths[failed].display());
+                let mut files: Vec<_> = paths
+                    .into_iter()
+                    .map(|path| (path, BatchFileOutcome::RolledBack))
+                    .collect();
+                files[failed].1 = BatchFileOutcome::Failed(e);
+                BatchReport { files, rolled_back: true }
+            }
+        }
+    }
+
+    /// The files below `dir` that encrypting the directory encrypts as a [batch](Self::encrypt_batch)
+    ///
+    /// Hidden files and directories (such as `.git` and `.cargocrypt`),
+    /// encrypted files and backups are left out, and so are files that are
+    /// encrypted already unless `overwrite_existing` is set. Paths are sorted.
+    pub async fn files_to_encrypt<P: AsRef<Path>>(&self, dir: P) -> CryptoResult<Vec<PathBuf>> {
+        let dir = dir.as_ref();
+        if !dir.is_dir() {
+            return Err(CargoCryptError::file_not_found(dir));
+        }
+        let overwrite = self.config.read().await.file_ops.overwrite_existing;
+
+        let mut files = Vec::new();
+        let walker = walkdir::WalkDir::new(dir)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|entry| entry.depth() == 0 || !entry.file_name().to_string_lossy().starts_with('.'));
+        for entry in walker {
+            let entry = entry.map_err(|e| CargoCryptError::Io {
+                message: format!("Cannot read {}: {}", dir.display(), e),
+                source: e.into(),
+            })?;
+            let path = entry.path();
+            let derived = matches!(path.extension().and_then(|ext| ext.to_str()), Some("enc" | "backup"));
+            if entry.file_type().is_file() && !derived && (overwrite || !encrypted_path(path).exists()) {
+                files.push(path.to_path_buf());
+            }
+        }
+        Ok(files)
+    }
+
+    /// Write every sealed file of an all-or-nothing batch, or none of them
+    ///
+    /// On failure, returns the index of the file that failed after undoing
+    /// everything written so far.
+    async fn commit_batch(
+        &self,
+        paths: &[PathBuf],
+        sealed: &[(EncryptedSecret, PathBuf)],
+    ) -> Result<(), (usize, CargoCryptError)> {
+        let mut transaction = BatchTransaction::default();
+
+        for (i, (encrypted, encrypted_path)) in sealed.iter().enumerate() {
+            let staged = match encrypted.to_bytes() {
+                Ok(bytes) => stage_file(encrypted_path, &bytes).await,
+                Err(e) => Err(e.into()),
+            };
+            match staged {
+                Ok(temp_path) => transaction.staged.push((temp_path, encrypted_path.clone())),
+                Err(e) => {
+                    transaction.rollback().await;
+                    return Err((i, e));
+                }
+            }
+        }
+
+        for i in 0..sealed.len() {
+            if let Err(e) = transaction.commit_next().await {
+                transaction.rollback().await;
+                return Err((i, e));
+            }
+        }
+
+        for (i, path) in paths.iter().enumerate() {
+            let backup_path = backup_path(path);
+            let existed = tokio::fs::try_exists(&backup_path).await.unwrap_or(true);
+            if let Err(e) = self.backup_original(path).await {
+                transaction.rollback().await;
+                return Err((i, e));
+            }
+            if !existed && tokio::fs::try_exists(&backup_path).await.unwrap_or(false) {
+                transaction.backups.push(backup_path);
+            }
+        }
+
+        transaction.finish().await;
+        Ok(())
+    }
+
+    async fn encrypt_file_inner(
+        &self,
+        path: &Path,
+        password: Option<&str>,
+        keyfile: Option<&Keyfile>,
+        plaintext_file: Plaintext,
+        vault: Option<&str>,
+    ) -> CryptoResult<PathBuf> {
+        let encrypted_path = self.write_encrypted_file(path, password, keyfile, plaintext_file, vault).await?;
+        let key = match (password, keyfile) {
+            (_, None) => KeySource::Password,
+            (None, Some(_)) => KeySource::Keyfile,
+            (Some(_), Some(_)) => KeySource::KeyfileAndPassword,
+        };
+        self.record_managed(&[(path.to_path_buf(), encrypted_path.clone())], key, vault, ManagedEvent::Encrypted).await;
+        Ok(encrypted_path)
+    }
+
+    async fn write_encrypted_file(
+        &self,
+        path: &Path,
+        password: Option<&str>,
+        keyfile: Option<&Keyfile>,
+        plaintext_file: Plaintext,
+        vault: Option<&str>,
+    ) -> CryptoResult<PathBuf> {
+        let path = path.to_path_buf();
+        let _lock = FileLock::path(&self.project_root, &encrypted_path(&path)).await?;
+        let (encrypted, encrypted_path) = self.seal_file(&path, password, keyfile, vault).await?;
+        
+        if let Plaintext::Remove { shred } = plaintext_file {
+            let encrypted_bytes = encrypted.to_bytes()?;
+            write_file_synced(&encrypted_path, &encrypted_bytes).await?;
+
+            if shred {
+                info!("Shredding plaintext: {}", path.display());
+                shred_file(&path).await?;
+            }
+            tokio::fs::remove_file(&path).await?;
+            sync_parent_dir(&path).await;
+
+            info!("File encrypted in place: {}", encrypted_path.display());
+            return Ok(encrypted_path);
+        }
+
+        // Write encrypted content with resilience protection
+        let encrypted_path_clone = encrypted_path.clone();
+        let atomic_ops = self.config.read().await.file_ops.atomic_operations;
+        {
+            let encrypted_for_write = encrypted.clone();
+            let path_for_write = encrypted_path_clone.clone();
+            self.resilience.execute_file_operation(move || {
+                let encrypted_bytes_result = encrypted_for_write.clone();
+                let encrypted_path_clone = path_for_write.clone();
+                async move {
+                    info!("Writing encrypted file: {}", encrypted_path_clone.display());
+                    let encrypted_bytes = encrypted_bytes_result.to_bytes().map_err(CargoCryptError::from)?;
+                    
+                    // Atomic operation: write to temp file first, then move
+                    if atomic_ops {
+                        let temp_path = encrypted_path_clone.with_extension("tmp");
+                        tokio::fs::write(&temp_path, &encrypted_bytes).await.map_err(CargoCryptError::from)?;
+                        tokio::fs::rename(&temp_path, &encrypted_path_clone).await.map_err(CargoCryptError::from)?;
+                    } else {
+                        tokio::fs::write(&encrypted_path_clone, encrypted_bytes).await.map_err(CargoCryptError::from)?;
+                    }
+                    
+                    Ok(())
+                }
+            }).await?
+        };
+        
+        self.backup_original(&path).await?;
+        
+        info!("File encryption completed successfully: {}", encrypted_path.display());
+        Ok(encrypted_path)
+    }
+
+    /// Validate, read and encrypt a file, returning the ciphertext and where it belongs
+    async fn seal_file(
+        &self,
+        path: &Path,
+        password: Option<&str>,
+        keyfile: Option<&Keyfile>,
+        vault: Option<&str>,
+    ) -> CryptoResult<(EncryptedSecret, PathBuf)> {
+        use crate::crypto::{PlaintextSecret, EncryptionOptions};
+        
+        let path = path.to_path_buf();
+        let path_str = path.to_string_lossy().to_string();
+        
+        // Comprehensive input validation
+        if let Some(password) = password {
+            self.validate_password(password)?;
+        }
+        
+        let path_validation = self.resilience.validate_input("file_path", &path_str);
+        if !path_validation.is_valid {
+            let error_messages: Vec<String> = path_validation.errors
+                .iter()
+                .filter(|e| e.severity == crate::validation::ValidationSeverity::Critical)
+                .map(|e| e.message.clone())
+                .collect();
+            
+            return Err(CargoCryptError::Validation {
+                message: "File path validation failed".to_string(),
+                errors: error_messages,
+                warnings: path_validation.warnings,
+            });
+        }
+        
+        // Display validation warnings if any
+        for warning in &path_validation.warnings {
+            warn!("Path validation warning: {}", warning);
+        }
+
+        let encrypted_path = encrypted_path(&path);
+
+        // An existing ciphertext may be the only copy of its plaintext
+        if !self.config.read().await.file_ops.overwrite_existing
+            && tokio::fs::try_exists(&encrypted_path).await.unwrap_or(true)
+        {
+            return Err(CargoCryptError::already_exists(&encrypted_path));
+        }
+        
+        // Execute file operations with resilience protection
+        let path_clone = path.clone();
+        let file_content = {
+            let path_str_clone = path_str.clone();
+            let path_for_read = path.clone();
+            self.resilience.execute_file_operation(move || {
+                let path_str = path_str_clone.clone();
+                let path_clone = path_for_read.clone();
+                async move {
+                    info!("Reading file for encryption: {}", path_str);
+                    let content = tokio::fs::read(&path_clone).await.map_err(|e| CargoCryptError::from(e))?;
+            
+            // Validate file content
+            let filename = path_clone.file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or("unknown");
+            let validator = InputValidator::new();
+            let content_validation = validator.validate_file_content(&content, filename);
+            
+            for warning in &content_validation.warnings {
+                warn!("File content warning: {}", warning);
+            }
+            
+                    Ok(content)
+                }
+            }).await?
+        };
+        
+        let plaintext = PlaintextSecret::new(file_content);
+
+        // Large files are split into chunks encrypted on all cores
+        let mut options = EncryptionOptions::default();
+        let chunk_threshold = self.config.read().await.file_ops.chunk_threshold;
+        if chunk_threshold > 0 {
+            options = options.chunked_above(usize::try_from(chunk_threshold).unwrap_or(usize::MAX));
+        }
+        
+        // Execute crypto operations with circuit breaker protection
+        let engine_clone = Arc::clone(&self.engine);
+        let mut encrypted = {
+            info!("Encrypting file content");
+            match (keyfile, password) {
+                (Some(keyfile), password) => self.engine.encrypt_with_keyfile(
+                    plaintext,
+                    keyfile,
+                    password,
+                    options
+                ).await,
+                (None, Some(password)) => self.engine.encrypt(
+                    plaintext, 
+                    password, 
+                    options
+                ).await,
+                (None, None) => return Err(CargoCryptError::from(
+                    crate::crypto::CryptoError::invalid_input("A password or keyfile is required")
+                )),
+            }.map_err(CargoCryptError::from)?
+            .with_vault(vault)
+        };
+        
+        // Record timestamps and permissions so decrypt can restore them
+        let (preserve_metadata, preserve_xattrs) = {
+            let config = self.config.read().await;
+            (config.file_ops.preserve_metadata, config.file_ops.preserve_xattrs)
+        };
+        if preserve_metadata {
+            match FileMetadata::capture(&path, preserve_xattrs) {
+                Ok(file_metadata) => {
+                    let mut metadata = encrypted.metadata().clone();
+                    metadata.set_file(Some(file_metadata));
+                    encrypted.set_metadata(metadata);
+                }
+                Err(e) => warn!("Could not record metadata of {}: {}", path.display(), e),
+            }
+        }
+        
+        Ok((encrypted, encrypted_path))
+    }
+
+    /// Copy the original next to its ciphertext when `backup_originals` is set
+    async fn backup_original(&self, path: &Path) -> CryptoResult<()> {
+        if !self.config.read().await.file_ops.backup_originals {
+            return Ok(());
+        }
+        
+        let path_for_backup = path.to_path_buf();
+        self.resilience.execute_file_operation(move || {
+            let path_clone = path_for_backup.clone();
+            async move {
+                let backup_path = backup_path(&path_clone);
+                info!("Creating backup: {}", backup_path.display());
+                tokio::fs::copy(&path_clone, backup_path).await.map_err(CargoCryptError::from)?;
+                Ok(())
+            }
+        }).await
+    }
+
+    /// Decrypt a file with the given password
+    pub async fn decrypt_file<P: AsRef<Path>>(&self, path: P, password: &str) -> CryptoResult<PathBuf> {
+        self.decrypt_file_inner(path.as_ref(), Some(password), None).await
+    }
+
+    /// Decrypt a file encrypted with a keyfile (and the password, if one was mixed in)
+    pub async fn decrypt_file_with_keyfile<P: AsRef<Path>>(
+        &self,
+        path: P,
+        keyfile: &Keyfile,
+        password: Option<&str>,
+    ) -> CryptoResult<PathBuf> {
+        self.decrypt_file_inner(path.as_ref(), password, Some(keyfile)).await
+    }
+
+    /// What decrypting `path` would write
+    ///
+    /// Only the header is read, so no password is needed; fails like
+    /// decrypting would when `path` is not an encrypted file.
+    pub async fn plan_decrypt_file<P: AsRef<Path>>(&self, path: P) -> CryptoResult<Plan> {
+        let path = path.as_ref();
+        EncryptedSecret::from_bytes(&tokio::fs::read(path).await?)?;
+        let mut plan = Plan::new();
+        plan.write(decrypted_path(path), None);
+        Ok(plan)
+    }
+
+    /// Decrypt a file into memory without writing the plaintext to disk
+    ///
+    /// For applications that read secrets at runtime. The returned secret is
+    /// zeroized when dropped; [`PlaintextSecret::into_secure_buffer`] turns it
+    /// into a [`SecureBuffer`](crate::crypto::SecureBuffer).
+    pub async fn decrypt_to_memory<P: AsRef<Path>>(&self, path: P, password: &str) -> CryptoResult<PlaintextSecret> {
+        let (_, plaintext) = self.read_and_decrypt(path.as_ref(), Some(password), None).await?;
+        Ok(plaintext)
+    }
+
+    /// Like [`Self::decrypt_to_memory`], with a keyfile and optional password
+    pub async fn decrypt_to_memory_with_keyfile<P: AsRef<Path>>(
+        &self,
+        path: P,
+        keyfile: &Keyfile,
+        password: Option<&str>,
+    ) -> CryptoResult<PlaintextSecret> {
+        let (_, plaintext) = self.read_and_decrypt(path.as_ref(), password, Some(keyfile)).await?;
+        Ok(plaintext)
+    }
+
+    async fn decrypt_file_inner(
+        &self,
+        path: &Path,
+        password: Option<&str>,
+        keyfile: Option<&Keyfile>,
+    ) -> CryptoResult<PathBuf> {
+        let _lock = FileLock::path(&self.project_root, path).await?;
+        let (encrypted, decrypted) = self.read_and_decrypt(path, password, keyfile).await?;
+        let config = self.config.read().await;
+        
+        let decrypted_path = decrypted_path(path);
+        
+        // Write decrypted content with resilience protection
+        self.resilience.execute_file_operation(|| async {
+            info!("Writing decrypted file: {}", decrypted_path.display());
+            
+            // Atomic operation: write to temp file first, then move
+            if config.file_ops.atomic_operations {
+                let temp_path = decrypted_path.with_extension("tmp");
+                tokio::fs::write(&temp_path, decrypted.as_bytes()).await.map_err(CargoCryptError::from)?;
+                tokio::fs::rename(&temp_path, &decrypted_path).await.map_err(CargoCryptError::from)?;
+            } else {
+                tokio::fs::write(&decrypted_path, decrypted.as_bytes()).await.map_err(CargoCryptError::from)?;
+            }
+            
+            Ok(())
+        }).await?;
+        
+        if config.file_ops.preserve_metadata {
+            if let Some(file_metadata) = &encrypted.metadata().file {
+                if let Err(e) = file_metadata.restore(&decrypted_path) {
+                    warn!("Could not restore metadata of {}: {}", decrypted_path.display(), e);
+                }
+            }
+        }
+        
+        info!("File decryption completed successfully: {}", decrypted_path.display());
+        drop(config);
+        drop(_lock);
+        let files = [(decrypted_path.clone(), path.to_path_buf())];
+        self.record_managed(&files, encrypted.key_source(), encrypted.vault(), ManagedEvent::Decrypted).await;
+        Ok(decrypted_path)
+    }
+
+    /// Validate, read and decrypt an encrypted file
+    async fn read_and_decrypt(
+        &self,
+        path: &Path,
+        password: Option<&str>,
+        keyfile: Option<&Keyfile>,
+    ) -> CryptoResult<(EncryptedSecret, PlaintextSecret)> {
+        let path_str = path.to_string_lossy();
+        
+        // Comprehensive input validation
+        if let Some(password) = password {
+            self.validate_password(password)?;
+        }
+        
         let path_validation = self.resilience.validate_input("file_path", &path_str);
         if !path_validation.is_valid {
             let error_messages: Vec<String> = path_validation.errors
@@ -647,60 +1717,731 @@ impl CargoCrypt {
                 warnings: path_validation.warnings,
             });
         }
-        
-        // Display validation warnings if any
-        for warning in &password_validation.warnings {
-            warn!("Password validation warning: {}", warning);
+        
+        // Display validation warnings if any
+        for warning in &path_validation.warnings {
+            warn!("Path validation warning: {}", warning);
+        }
+        
+        let constant_time = self.config.read().await.security.constant_time_operations;
+        
+        // Read encrypted content with resilience protection
+        let encrypted_bytes = self.resilience.execute_file_operation(|| async {
+            info!("Reading encrypted file: {}", path_str);
+            tokio::fs::read(path).await.map_err(|e| CargoCryptError::from(e))
+        }).await?;
+        
+        // Parse and decrypt the encrypted data
+        info!("Decrypting file content");
+        self.decrypt_bytes(&encrypted_bytes, password, keyfile, constant_time)
+    }
+
+    /// Encrypt everything read from `reader` and write the encrypted file to `writer`
+    ///
+    /// Backs `cargocrypt encrypt -` in shell pipelines. The input is buffered
+    /// in memory because the file format authenticates it as a whole. Returns
+    /// the number of bytes written.
+    pub async fn encrypt_reader_to_writer<R, W>(&self, reader: R, writer: W, password: &str) -> CryptoResult<u64>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        self.encrypt_stream(reader, writer, Some(password), None).await
+    }
+
+    /// Like [`Self::encrypt_reader_to_writer`], with a keyfile and optional password
+    pub async fn encrypt_reader_to_writer_with_keyfile<R, W>(
+        &self,
+        reader: R,
+        writer: W,
+        keyfile: &Keyfile,
+        password: Option<&str>,
+    ) -> CryptoResult<u64>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        self.encrypt_stream(reader, writer, password, Some(keyfile)).await
+    }
+
+    /// Decrypt an encrypted file read from `reader` and write the plaintext to `writer`
+    ///
+    /// Nothing is written unless decryption succeeds. Returns the number of
+    /// bytes written.
+    pub async fn decrypt_reader_to_writer<R, W>(&self, reader: R, writer: W, password: &str) -> CryptoResult<u64>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        self.decrypt_stream(reader, writer, Some(password), None).await
+    }
+
+    /// Like [`Self::decrypt_reader_to_writer`], with a keyfile and optional password
+    pub async fn decrypt_reader_to_writer_with_keyfile<R, W>(
+        &self,
+        reader: R,
+        writer: W,
+        keyfile: &Keyfile,
+        password: Option<&str>,
+    ) -> CryptoResult<u64>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        self.decrypt_stream(reader, writer, password, Some(keyfile)).await
+    }
+
+    async fn encrypt_stream<R, W>(
+        &self,
+        mut reader: R,
+        mut writer: W,
+        password: Option<&str>,
+        keyfile: Option<&Keyfile>,
+    ) -> CryptoResult<u64>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        use crate::crypto::{EncryptionOptions, PlaintextSecret};
+
+        if let Some(password) = password {
+            self.validate_password(password)?;
+        }
+
+        let mut input = Vec::new();
+        reader.read_to_end(&mut input).await?;
+        let plaintext = PlaintextSecret::new(input);
+
+        let encrypted = match (keyfile, password) {
+            (Some(keyfile), password) => self.engine.encrypt_with_keyfile(plaintext, keyfile, password, EncryptionOptions::default()).await?,
+            (None, Some(password)) => self.engine.encrypt(plaintext, password, EncryptionOptions::default()).await?,
+            (None, None) => return Err(crate::crypto::CryptoError::invalid_input("A password or keyfile is required").into()),
+        };
+
+        let output = encrypted.to_bytes()?;
+        writer.write_all(&output).await?;
+        writer.flush().await?;
+        Ok(output.len() as u64)
+    }
+
+    async fn decrypt_stream<R, W>(
+        &self,
+        mut reader: R,
+        mut writer: W,
+        password: Option<&str>,
+        keyfile: Option<&Keyfile>,
+    ) -> CryptoResult<u64>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let mut input = Vec::new();
+        reader.read_to_end(&mut input).await?;
+        let constant_time = self.config.read().await.security.constant_time_operations;
+        let (_, decrypted) = self.decrypt_bytes(&input, password, keyfile, constant_time)?;
+
+        writer.write_all(decrypted.as_bytes()).await?;
+        writer.flush().await?;
+        Ok(decrypted.as_bytes().len() as u64)
+    }
+
+    /// Parse and decrypt the on-disk bytes of an encrypted file
+    ///
+    /// With `constant_time` set, every failure past argument checking (a
+    /// malformed or truncated file, a wrong password or key, tampered
+    /// ciphertext) becomes [`DECRYPTION_FAILED`], of kind
+    /// [`AuthenticationFailed`](crate::error::CryptoErrorKind::AuthenticationFailed). A file that cannot be parsed
+    /// still pays for a key derivation, with its header's KDF when that is
+    /// readable, a failing cached key is derived again (see
+    /// [`CryptoEngine::decrypt`]), and failures are padded to
+    /// [`DECRYPT_FAILURE_FLOOR`], so the cases also take the same time.
+    fn decrypt_bytes(
+        &self,
+        bytes: &[u8],
+        password: Option<&str>,
+        keyfile: Option<&Keyfile>,
+        constant_time: bool,
+    ) -> CryptoResult<(EncryptedSecret, crate::crypto::PlaintextSecret)> {
+        use crate::crypto::{CryptoError, DerivedKey};
+
+        if keyfile.is_none() && password.is_none() {
+            return Err(CryptoError::invalid_input("A password or keyfile is required").into());
+        }
+
+        let start = std::time::Instant::now();
+        let decrypt = |encrypted: &EncryptedSecret| match keyfile {
+            Some(keyfile) => self.engine.decrypt_with_keyfile(encrypted, keyfile, password),
+            None => self.engine.decrypt(encrypted, password.unwrap_or_default()),
+        };
+        let result = match EncryptedSecret::from_bytes(bytes) {
+            Ok(encrypted) => decrypt(&encrypted).map(|plaintext| (encrypted, plaintext)),
+            Err(e) if constant_time => {
+                // Do the key derivation a well-formed file would have needed
+                let salt = [0u8; crate::crypto::defaults::SALT_LENGTH];
+                let kdf = EncryptedSecret::peek_kdf(bytes).unwrap_or_else(|| self.engine.kdf());
+                let password_key = password.and_then(|password| DerivedKey::from_password_with_kdf(password, &salt, &kdf).ok());
+                if let Some(keyfile) = keyfile {
+                    let _ = keyfile.derive_key(&salt, password_key.as_ref());
+                }
+                Err(e)
+            }
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(decrypted) => Ok(decrypted),
+            Err(e) if constant_time => {
+                debug!("Decryption failed: {}", e);
+                drop(crate::crypto::TimingDefense::new(start, DECRYPT_FAILURE_FLOOR));
+                Err(CargoCryptError::Crypto {
+                    message: CryptoError::decryption(DECRYPTION_FAILED).to_string(),
+                    kind: crate::error::CryptoErrorKind::AuthenticationFailed,
+                })
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Validate a password, logging any warnings
+    fn validate_password(&self, password: &str) -> CryptoResult<()> {
+        let password_validation = self.resilience.validate_input("password", password);
+        if !password_validation.is_valid {
+            let error_messages: Vec<String> = password_validation.errors
+                .iter()
+                .filter(|e| e.severity == crate::validation::ValidationSeverity::Critical)
+                .map(|e| e.message.clone())
+                .collect();
+            
+            return Err(CargoCryptError::Validation {
+                message: "Password validation failed".to_string(),
+                errors: error_messages,
+                warnings: password_validation.warnings,
+            });
+        }
+
+        for warning in &password_validation.warnings {
+            warn!("Password validation warning: {}", warning);
+        }
+        Ok(())
+    }
+
+    /// Set or clear the expiry date of an encrypted file
+    ///
+    /// Expiry is part of the unencrypted header, so no password is needed and
+    /// the ciphertext is left untouched.
+    pub async fn set_file_expiry<P: AsRef<Path>>(&self, path: P, expires_at: Option<u64>) -> CryptoResult<()> {
+        use crate::crypto::EncryptedSecret;
+
+        let path = path.as_ref();
+        let _lock = FileLock::path(&self.project_root, path).await?;
+        let mut encrypted = EncryptedSecret::from_bytes(&tokio::fs::read(path).await?)?;
+        let mut metadata = encrypted.metadata().clone();
+        metadata.set_expires_at(expires_at);
+        encrypted.set_metadata(metadata);
+
+        let temp_path = path.with_extension("tmp");
+        tokio::fs::write(&temp_path, encrypted.to_bytes()?).await?;
+        tokio::fs::rename(&temp_path, path).await?;
+        Ok(())
+    }
+
+    /// All encrypted files and store entries that have an expiry date
+    ///
+    /// Statuses use the configured warning window; filter with
+    /// [`ExpiryStatus::needs_attention`](crate::expiry::ExpiryStatus::needs_attention)
+    /// for items due for rotation.
+    pub async fn expiring_items(&self) -> CryptoResult<Vec<ExpiringItem>> {
+        let warning_window = self.config.read().await.expiry.warning_window();
+        let checker = ExpiryChecker::new(warning_window);
+
+        let mut items = checker.scan_directory(&self.project_root);
+        items.extend(checker.check_store(self.secret_store.as_ref()).await?);
+        items.sort_by_key(|item| item.expires_at);
+        Ok(items)
+    }
+
+    /// Password-protected files whose KDF is weaker than the configured one
+    ///
+    /// Only headers are inspected, so no password is needed. Returns each file
+    /// with the KDF it was encrypted with.
+    pub async fn outdated_files(&self) -> CryptoResult<Vec<(PathBuf, Kdf)>> {
+        let extension = self.config.read().await.file_ops.encrypted_extension.clone();
+        let target = self.engine.kdf();
+
+        let mut outdated = Vec::new();
+        for path in crate::expiry::find_encrypted_files(&self.project_root, &extension) {
+            let encrypted = match tokio::fs::read(&path).await.map_err(CargoCryptError::from)
+                .and_then(|bytes| Ok(EncryptedSecret::from_bytes(&bytes)?))
+            {
+                Ok(encrypted) => encrypted,
+                Err(e) => {
+                    debug!("Skipping {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+            if encrypted.key_source().requires_password() && encrypted.kdf().is_weaker_than(&target) {
+                outdated.push((path, *encrypted.kdf()));
+            }
+        }
+        Ok(outdated)
+    }
+
+    /// Re-encrypt [outdated files](Self::outdated_files) with the configured KDF
+    ///
+    /// Files that also need a keyfile are skipped, and files the password does
+    /// not open are reported as failed and left untouched. Metadata and
+    /// chunking carry over; each file is replaced atomically.
+    pub async fn upgrade_files(&self, password: &str) -> CryptoResult<UpgradeReport> {
+        self.upgrade_files_with_progress(password, &Progress::hidden()).await
+    }
+
+    /// [Upgrade files](Self::upgrade_files), reporting each file to `progress`
+    pub async fn upgrade_files_with_progress(&self, password: &str, progress: &Progress) -> CryptoResult<UpgradeReport> {
+        self.validate_password(password)?;
+        let chunk_threshold = self.config.read().await.file_ops.chunk_threshold;
+        let mut report = UpgradeReport { target: self.engine.kdf(), files: Vec::new() };
+
+        let outdated = self.outdated_files().await?;
+        progress.set_total(outdated.len() as u64, outdated.iter().map(|(path, _)| file_size(path)).sum());
+        for (path, from) in outdated {
+            let file = progress.start_file(&path, file_size(&path));
+            let outcome = self
+                .upgrade_file(&path, password, chunk_threshold)
+                .await
+                .unwrap_or_else(UpgradeOutcome::Failed);
+            if let UpgradeOutcome::Upgraded { .. } = outcome {
+                info!("Upgraded {} from {}", path.display(), from);
+                file.finish();
+            }
+            report.files.push((path, outcome));
         }
-        for warning in &path_validation.warnings {
-            warn!("Path validation warning: {}", warning);
+
+        Ok(report)
+    }
+
+    async fn upgrade_file(&self, path: &Path, password: &str, chunk_threshold: u64) -> CryptoResult<UpgradeOutcome> {
+        use crate::crypto::EncryptionOptions;
+
+        let _lock = FileLock::path(&self.project_root, path).await?;
+        let encrypted = EncryptedSecret::from_bytes(&tokio::fs::read(path).await?)?;
+        if encrypted.key_source().requires_keyfile() {
+            return Ok(UpgradeOutcome::Skipped("also needs its keyfile".to_string()));
         }
-        
-        let config = self.config.read().await;
-        
-        // Read encrypted content with resilience protection
-        let encrypted_bytes = self.resilience.execute_file_operation(|| async {
-            info!("Reading encrypted file: {}", path_str);
-            tokio::fs::read(path).await.map_err(|e| CargoCryptError::from(e))
-        }).await?;
-        
-        // Parse encrypted data
-        let encrypted = {
-            info!("Parsing encrypted data");
-            crate::crypto::EncryptedSecret::from_bytes(&encrypted_bytes).map_err(|e| CargoCryptError::from(e))?
-        };
-        
-        // Decrypt using the crypto engine with circuit breaker protection
-        let decrypted = {
-            info!("Decrypting file content");
-            self.engine.decrypt(&encrypted, password).map_err(|e| CargoCryptError::from(e))?
+        let plaintext = self.engine.decrypt(&encrypted, password)?;
+
+        let mut options = EncryptionOptions::default()
+            .with_metadata(encrypted.metadata().clone());
+        if encrypted.chunk_layout().is_some() {
+            options = options.chunked_above(0);
+        } else if chunk_threshold > 0 {
+            options = options.chunked_above(usize::try_from(chunk_threshold).unwrap_or(usize::MAX));
+        }
+        let upgraded = self.engine.encrypt(plaintext, password, options).await?;
+
+        write_file_synced(path, &upgraded.to_bytes()?).await?;
+        Ok(UpgradeOutcome::Upgraded { from: *encrypted.kdf() })
+    }
+
+    /// The files recorded in [`.cargocrypt/files.toml`](crate::managed)
+    pub fn managed_files(&self) -> CryptoResult<ManagedFiles> {
+        ManagedFiles::load(&self.project_root)
+    }
+
+    /// Encrypt the managed files whose plaintext changed or was never encrypted
+    ///
+    /// Their encrypted copies are replaced. With `in_place` the plaintext of
+    /// unchanged files is encrypted and removed too, see
+    /// [`Self::encrypt_file_in_place`]. Files whose key is not in `keys` are
+    /// skipped; a vault password that does not open its vault fails the
+    /// whole operation before anything is written.
+    pub async fn encrypt_managed(&self, keys: &ManagedKeys, in_place: bool, shred: bool) -> CryptoResult<ManagedReport> {
+        let vault_keys = self.unlock_managed_vaults(keys).await?;
+        let crypt = self.overwriting().await;
+        let plaintext_file = if in_place { Plaintext::Remove { shred } } else { Plaintext::Keep };
+
+        let mut report = ManagedReport::default();
+        for file in self.managed_files()?.files {
+            let skipped = match file.state(&self.project_root) {
+                ManagedState::Modified | ManagedState::Unencrypted => None,
+                ManagedState::Decrypted if in_place => None,
+                ManagedState::Decrypted => Some("unchanged since it was encrypted"),
+                ManagedState::Encrypted => Some("already encrypted"),
+                ManagedState::Missing => Some("the plaintext is missing"),
+            };
+            let key = match skipped {
+                Some(reason) => Err(reason.to_string()),
+                None => managed_key(&file, keys, &vault_keys),
+            };
+            let outcome = match key {
+                Ok((password, keyfile)) => {
+                    let path = self.project_root.join(&file.path);
+                    match crypt.encrypt_file_inner(&path, password, keyfile, plaintext_file, file.vault.as_deref()).await {
+                        Ok(encrypted_path) => ManagedOutcome::Written(encrypted_path),
+                        Err(e) => ManagedOutcome::Failed(e),
+                    }
+                }
+                Err(reason) => ManagedOutcome::Skipped(reason),
+            };
+            report.files.push((file.path, outcome));
+        }
+        Ok(report)
+    }
+
+    /// Decrypt the managed files whose encrypted copy exists
+    ///
+    /// Files whose plaintext changed after they were encrypted are skipped
+    /// so no edits are overwritten; encrypt them first. Keys work as for
+    /// [`Self::encrypt_managed`].
+    pub async fn decrypt_managed(&self, keys: &ManagedKeys) -> CryptoResult<ManagedReport> {
+        let vault_keys = self.unlock_managed_vaults(keys).await?;
+
+        let mut report = ManagedReport::default();
+        for file in self.managed_files()?.files {
+            let skipped = match file.state(&self.project_root) {
+                ManagedState::Encrypted | ManagedState::Decrypted => None,
+                ManagedState::Modified => Some("changed since it was encrypted; encrypt it first"),
+                ManagedState::Unencrypted => Some("not encrypted yet"),
+                ManagedState::Missing => Some("the encrypted file is missing"),
+            };
+            let key = match skipped {
+                Some(reason) => Err(reason.to_string()),
+                None => managed_key(&file, keys, &vault_keys),
+            };
+            let outcome = match key {
+                Ok((password, keyfile)) => {
+                    let path = self.project_root.join(&file.encrypted);
+                    match self.decrypt_file_inner(&path, password, keyfile).await {
+                        Ok(decrypted_path) => ManagedOutcome::Written(decrypted_path),
+                        Err(e) => ManagedOutcome::Failed(e),
+                    }
+                }
+                Err(reason) => ManagedOutcome::Skipped(reason),
+            };
+            report.files.push((file.path, outcome));
+        }
+        Ok(report)
+    }
+
+    /// The keys of the vaults in `keys`, failing if a password does not open its vault
+    async fn unlock_managed_vaults(&self, keys: &ManagedKeys) -> CryptoResult<BTreeMap<String, Keyfile>> {
+        let mut vault_keys = BTreeMap::new();
+        for (vault, password) in &keys.vaults {
+            vault_keys.insert(vault.clone(), self.unlock_vault(vault, password.as_str()).await?);
+        }
+        Ok(vault_keys)
+    }
+
+    /// A copy of this instance that replaces existing encrypted files
+    async fn overwriting(&self) -> Self {
+        let mut config = self.config().await;
+        config.file_ops.overwrite_existing = true;
+        Self {
+            config: Arc::new(RwLock::new(config)),
+            ..self.clone()
+        }
+    }
+
+    /// Record files in the [managed files](crate::managed); a failure is only logged
+    async fn record_managed(&self, files: &[(PathBuf, PathBuf)], key: KeySource, vault: Option<&str>, event: ManagedEvent) {
+        let root = &self.project_root;
+        let updated = ManagedFiles::update(root, |managed| {
+            for (plaintext, encrypted) in files {
+                managed.record(root, plaintext, encrypted, key, vault, event);
+            }
+        })
+        .await;
+        if let Err(e) = updated {
+            warn!("Could not record {} file(s) in {}: {}", files.len(), MANAGED_FILE, e);
+        }
+    }
+
+    /// Decrypt `path` for `ttl`, recording a [session](crate::session)
+    ///
+    /// The plaintext goes to the private [session directory](crate::session::session_dir),
+    /// or next to the encrypted file with `in_place`. `password` is the
+    /// vault's for files in a vault. Ending the session is up to the caller:
+    /// [`Self::close_file`] once it expires.
+    pub async fn open_file<P: AsRef<Path>>(
+        &self,
+        path: P,
+        password: Option<&str>,
+        keyfile: Option<&Path>,
+        ttl: Duration,
+        in_place: bool,
+    ) -> CryptoResult<Session> {
+        let path = std::path::absolute(path.as_ref())?;
+        let relative = path.strip_prefix(&self.project_root).map(Path::to_path_buf).map_err(|_| CargoCryptError::Config {
+            message: format!("{} is outside the project at {}", path.display(), self.project_root.display()),
+            suggestion: Some("Decrypt it with `cargocrypt decrypt` instead".to_string()),
+        })?;
+        if let Some(session) = Sessions::load(&self.project_root)?.get(&relative) {
+            return Err(session_open(session));
+        }
+
+        let header = EncryptedSecret::from_bytes(&tokio::fs::read(&path).await?)?;
+        let vault = header.vault().map(str::to_string);
+        let (password, key) = match &vault {
+            Some(vault) => {
+                let password = password.ok_or_else(|| session_key_missing(&path, "the vault password"))?;
+                (None, Some(self.unlock_vault(vault, password).await?))
+            }
+            None => {
+                let key = match keyfile {
+                    Some(keyfile) => Some(Keyfile::load(keyfile)?),
+                    None if header.key_source().requires_keyfile() => return Err(session_key_missing(&path, "a keyfile")),
+                    None => None,
+                };
+                (password.filter(|_| header.key_source().requires_password()), key)
+            }
         };
-        
-        // Create decrypted file path (remove .enc extension)
-        let decrypted_path = if path.extension().and_then(|ext| ext.to_str()) == Some("enc") {
-            path.with_extension("")
+        let (encrypted, plaintext) = self.read_and_decrypt(&path, password, key.as_ref()).await?;
+
+        let plaintext_path = if in_place {
+            decrypted_path(&path)
         } else {
-            path.with_extension("decrypted")
+            crate::session::create_session_dir(&self.project_root)?.join(decrypted_path(&relative))
         };
-        
-        // Write decrypted content with resilience protection
-        self.resilience.execute_file_operation(|| async {
-            info!("Writing decrypted file: {}", decrypted_path.display());
-            
-            // Atomic operation: write to temp file first, then move
-            if config.file_ops.atomic_operations {
-                let temp_path = decrypted_path.with_extension("tmp");
-                tokio::fs::write(&temp_path, decrypted.as_bytes()).await.map_err(|e| CargoCryptError::from(e))?;
-                tokio::fs::rename(&temp_path, &decrypted_path).await.map_err(|e| CargoCryptError::from(e))?;
+        if in_place && tokio::fs::try_exists(&plaintext_path).await.unwrap_or(true) {
+            return Err(CargoCryptError::already_exists(&plaintext_path));
+        }
+        if let Some(parent) = plaintext_path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let mut options = tokio::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        let mut file = options.open(&plaintext_path).await?;
+        file.write_all(plaintext.as_bytes()).await?;
+        file.sync_all().await?;
+
+        let now = crate::utils::unix_now();
+        let session = Session {
+            encrypted: relative.clone(),
+            plaintext: plaintext_path.clone(),
+            in_place,
+            key: encrypted.key_source(),
+            vault: vault.clone(),
+            keyfile: keyfile.map(std::path::absolute).transpose()?,
+            opened_at: now,
+            expires_at: now + ttl.as_secs().max(1),
+            digest: crate::session::digest(plaintext.as_bytes()),
+        };
+        let recorded = Sessions::update(&self.project_root, |sessions| match sessions.get(&relative) {
+            Some(other) => Err(session_open(other)),
+            None => {
+                sessions.sessions.push(session.clone());
+                Ok(())
+            }
+        })
+        .await;
+        if let Err(e) = recorded {
+            let _ = tokio::fs::remove_file(&plaintext_path).await;
+            return Err(e);
+        }
+
+        if in_place {
+            let files = [(plaintext_path, path)];
+            self.record_managed(&files, encrypted.key_source(), vault.as_deref(), ManagedEvent::Decrypted).await;
+        }
+        info!("Opened {} until {}", relative.display(), session.expires_at);
+        Ok(session)
+    }
+
+    /// End a session from [`Self::open_file`]
+    ///
+    /// A plaintext that changed is encrypted over the encrypted file first,
+    /// with the keyfile it was opened with and `password` (the vault's for
+    /// files in a vault). Then the plaintext is removed, overwritten first
+    /// with `shred`, along with the session record.
+    pub async fn close_file(&self, session: &Session, password: Option<&str>, shred: bool) -> CryptoResult<SessionClose> {
+        let outcome = if !tokio::fs::try_exists(&session.plaintext).await? {
+            SessionClose::Missing
+        } else if session.is_modified()? {
+            let plaintext = &session.plaintext;
+            let key = match (&session.vault, &session.keyfile) {
+                (Some(vault), _) => {
+                    let password = password.ok_or_else(|| session_key_missing(plaintext, "the vault password"))?;
+                    Some(self.unlock_vault(vault, password).await?)
+                }
+                (None, Some(keyfile)) => Some(Keyfile::load(keyfile)?),
+                (None, None) => None,
+            };
+            let password = if session.vault.is_none() && session.key.requires_password() {
+                Some(password.ok_or_else(|| session_key_missing(plaintext, "the password"))?)
             } else {
-                tokio::fs::write(&decrypted_path, decrypted.as_bytes()).await.map_err(|e| CargoCryptError::from(e))?;
+                None
+            };
+
+            let encrypted_path = self.project_root.join(&session.encrypted);
+            if password.is_some() {
+                // A mistyped password would otherwise become the file's new one
+                self.read_and_decrypt(&encrypted_path, password, key.as_ref()).await?;
             }
-            
+            {
+                let _lock = FileLock::path(&self.project_root, &encrypted_path).await?;
+                let crypt = self.overwriting().await;
+                let (encrypted, _) = crypt.seal_file(plaintext, password, key.as_ref(), session.vault.as_deref()).await?;
+                write_file_synced(&encrypted_path, &encrypted.to_bytes()?).await?;
+            }
+            let files = [(decrypted_path(&encrypted_path), encrypted_path)];
+            self.record_managed(&files, session.key, session.vault.as_deref(), ManagedEvent::Encrypted).await;
+            SessionClose::Reencrypted
+        } else {
+            SessionClose::Unchanged
+        };
+
+        if outcome != SessionClose::Missing {
+            if shred {
+                shred_file(&session.plaintext).await?;
+            }
+            tokio::fs::remove_file(&session.plaintext).await?;
+            sync_parent_dir(&session.plaintext).await;
+        }
+        if !session.in_place {
+            // Drop directories the plaintext needed, up to the session directory
+            let session_dir = crate::session::session_dir(&self.project_root);
+            let mut dir = session.plaintext.parent();
+            while let Some(parent) = dir.filter(|dir| dir.starts_with(&session_dir) && *dir != session_dir) {
+                if std::fs::remove_dir(parent).is_err() {
+                    break;
+                }
+                dir = parent.parent();
+            }
+        }
+        Sessions::update(&self.project_root, |sessions| {
+            sessions.sessions.retain(|other| other.encrypted != session.encrypted || other.opened_at != session.opened_at);
             Ok(())
-        }).await?;
-        
-        info!("File decryption completed successfully: {}", decrypted_path.display());
-        Ok(decrypted_path)
+        })
+        .await?;
+        info!("Closed {} ({:?})", session.encrypted.display(), outcome);
+        Ok(outcome)
+    }
+
+    /// The files open through [`Self::open_file`]
+    pub fn sessions(&self) -> CryptoResult<Vec<Session>> {
+        Ok(Sessions::load(&self.project_root)?.sessions)
+    }
+
+    /// Path of the project's signed integrity manifest
+    pub fn manifest_path(&self) -> PathBuf {
+        self.project_root.join(".cargocrypt").join(crate::manifest::MANIFEST_FILE)
+    }
+
+    /// Check every encrypted file in the project against the signed manifest
+    ///
+    /// With a password, authentication tags are checked by decrypting in
+    /// memory. `expected_public_key` (hex) pins the manifest signer.
+    pub async fn verify_project(
+        &self,
+        password: Option<&str>,
+        expected_public_key: Option<&str>,
+    ) -> CryptoResult<IntegrityReport> {
+        let expected = expected_public_key
+            .map(hex::decode)
+            .transpose()
+            .map_err(crate::crypto::CryptoError::from)?;
+        self.project_verifier().await.with_expected_key(expected).run(password)
+    }
+
+    /// Sign a manifest of the project's encrypted files and write it
+    ///
+    /// Fails without writing anything if any encrypted file is malformed.
+    pub async fn write_manifest(&self, signing_password: &str) -> CryptoResult<Manifest> {
+        let _lock = FileLock::state(&self.project_root).await?;
+        let entries = self.project_verifier().await.entries()?;
+        let keypair = self.signing_key(signing_password).await?;
+        let manifest = Manifest::sign(entries, &keypair)?;
+        manifest.save(self.manifest_path())?;
+
+        info!("Wrote manifest of {} files signed by {}", manifest.entries.len(), keypair.fingerprint());
+        Ok(manifest)
+    }
+
+    async fn project_verifier(&self) -> ProjectVerifier {
+        let extension = self.config.read().await.file_ops.encrypted_extension.clone();
+        ProjectVerifier::new(&self.project_root).with_encrypted_extension(extension)
+    }
+
+    /// Path of the project's password-protected Ed25519 signing key
+    pub fn signing_key_path(&self) -> PathBuf {
+        self.project_root.join(".cargocrypt").join("signing.key")
+    }
+
+    /// Load the project signing key, generating and storing one on first use
+    pub async fn load_or_create_signing_key(&self, password: &str) -> CryptoResult<SigningKeyPair> {
+        let _lock = FileLock::state(&self.project_root).await?;
+        self.signing_key(password).await
+    }
+
+    /// [`Self::load_or_create_signing_key`] for callers already holding the state lock
+    async fn signing_key(&self, password: &str) -> CryptoResult<SigningKeyPair> {
+        use crate::crypto::{EncryptedSecret, EncryptionOptions, PlaintextSecret, SecretType};
+
+        let key_path = self.signing_key_path();
+        if key_path.exists() {
+            let bytes = tokio::fs::read(&key_path).await?;
+            let encrypted = EncryptedSecret::from_bytes(&bytes)?;
+            let secret = self.engine.decrypt(&encrypted, password)?;
+            return Ok(SigningKeyPair::from_secret_bytes(secret.as_bytes())?);
+        }
+
+        let keypair = SigningKeyPair::generate();
+        let options = EncryptionOptions::new()
+            .with_type(SecretType::PrivateKey)
+            .with_description("Ed25519 signing key");
+        let secret = PlaintextSecret::new(keypair.secret_bytes().to_vec());
+        let encrypted = self.engine.encrypt(secret, password, options).await?;
+
+        if let Some(parent) = key_path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(&key_path, encrypted.to_bytes()?).await?;
+        info!("Created signing key {} at: {}", keypair.fingerprint(), key_path.display());
+
+        Ok(keypair)
+    }
+
+    /// Sign a file, writing a detached signature to `<file>.sig`
+    pub async fn sign_file<P: AsRef<Path>>(&self, path: P, password: &str) -> CryptoResult<PathBuf> {
+        let path = path.as_ref();
+        let keypair = self.load_or_create_signing_key(password).await?;
+        let data = tokio::fs::read(path).await?;
+
+        let signature = self.engine.sign(&data, &keypair);
+        let signature_path = crate::crypto::signing::signature_path(path);
+        tokio::fs::write(&signature_path, signature.to_json()?).await?;
+
+        info!("Signed {} with key {}", path.display(), keypair.fingerprint());
+        Ok(signature_path)
+    }
+
+    /// Verify a file against its detached signature
+    ///
+    /// `signature_path` defaults to `<file>.sig`. When `expected_public_key` (hex)
+    /// is given, the signature must also come from that key.
+    pub async fn verify_file<P: AsRef<Path>>(
+        &self,
+        path: P,
+        signature_path: Option<&Path>,
+        expected_public_key: Option<&str>,
+    ) -> CryptoResult<DetachedSignature> {
+        let path = path.as_ref();
+        let signature_path = signature_path
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| crate::crypto::signing::signature_path(path));
+
+        let data = tokio::fs::read(path).await?;
+        let signature_json = tokio::fs::read_to_string(&signature_path).await?;
+        let signature = DetachedSignature::from_json(&signature_json)?;
+
+        let expected = expected_public_key
+            .map(hex::decode)
+            .transpose()
+            .map_err(crate::crypto::CryptoError::from)?;
+        self.engine.verify(&data, &signature, expected.as_deref())?;
+
+        Ok(signature)
     }
 }
 
@@ -715,6 +2456,10 @@ impl Default for CryptoConfig {
             performance: PerformanceConfig::default(),
             resilience: ResilienceConfig::default(),
             monitoring: MonitoringConfig::default(),
+            limits: ResourceLimitsConfig::default(),
+            agent: AgentConfig::default(),
+            expiry: ExpiryConfig::default(),
+            keys: KeyConfig::default(),
         }
     }
 }
@@ -743,6 +2488,8 @@ impl Default for KeyDerivationConfig {
             time_cost: 3,
             parallelism: 4,
             output_length: 32,
+            kdf: Kdf::default(),
+            calibration_budget_ms: default_calibration_budget_ms(),
         }
     }
 }
@@ -756,6 +2503,9 @@ impl Default for FileOperationConfig {
             compression: false,
             atomic_operations: true,
             preserve_metadata: true,
+            preserve_xattrs: false,
+            overwrite_existing: false,
+            chunk_threshold: default_chunk_threshold(),
         }
     }
 }
@@ -767,6 +2517,11 @@ impl Default for SecurityConfig {
             auto_zeroize: true,
             fail_secure: true,
             max_password_attempts: 3,
+            retry_delay_ms: default_retry_delay_ms(),
+            lockout_after: 0,
+            lockout_minutes: default_lockout_minutes(),
+            constant_time_operations: true,
+            clipboard_clear_secs: default_clipboard_clear_secs(),
         }
     }
 }
@@ -788,6 +2543,406 @@ impl Default for CargoCryptBuilder {
     }
 }
 
+/// Options for [`CargoCrypt::encrypt_batch`]
+#[derive(Debug, Clone, Default)]
+pub struct BatchOptions {
+    concurrency: Option<usize>,
+    all_or_nothing: bool,
+    progress: Progress,
+}
+
+impl BatchOptions {
+    /// Default options: concurrency from the config, failures stay per file
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Encrypt at most `limit` files at a time instead of `performance.max_concurrent_ops`
+    pub fn concurrency(mut self, limit: usize) -> Self {
+        self.concurrency = Some(limit);
+        self
+    }
+
+    /// Leave no `.enc` file behind unless every file in the batch succeeds
+    pub fn all_or_nothing(mut self, enabled: bool) -> Self {
+        self.all_or_nothing = enabled;
+        self
+    }
+
+    /// Show the files being encrypted and the throughput on `progress`
+    pub fn progress(mut self, progress: Progress) -> Self {
+        self.progress = progress;
+        self
+    }
+}
+
+/// What happened to one file in [`CargoCrypt::upgrade_files`]
+#[derive(Debug)]
+pub enum UpgradeOutcome {
+    /// Re-encrypted; it was encrypted with `from` before
+    Upgraded { from: Kdf },
+    /// Left alone for the given reason
+    Skipped(String),
+    /// Could not be upgraded (e.g. the password does not open it); left untouched
+    Failed(CargoCryptError),
+}
+
+/// Per-file results of [`CargoCrypt::upgrade_files`]
+#[derive(Debug)]
+pub struct UpgradeReport {
+    /// KDF the files were upgraded to
+    pub target: Kdf,
+    /// Each outdated file with its outcome
+    pub files: Vec<(PathBuf, UpgradeOutcome)>,
+}
+
+impl UpgradeReport {
+    /// Whether no file failed
+    pub fn is_success(&self) -> bool {
+        !self.files.iter().any(|(_, outcome)| matches!(outcome, UpgradeOutcome::Failed(_)))
+    }
+
+    /// Number of files re-encrypted
+    pub fn upgraded(&self) -> usize {
+        self.files.iter().filter(|(_, outcome)| matches!(outcome, UpgradeOutcome::Upgraded { .. })).count()
+    }
+}
+
+/// Result of [`CargoCrypt::change_password`]
+#[derive(Debug, Default)]
+pub struct PasswordChange {
+    /// Key files now wrapped with the new password
+    pub rewrapped: Vec<PathBuf>,
+    /// Key files the old password does not open, left unchanged
+    pub skipped: Vec<PathBuf>,
+    /// Encrypted files whose key comes from a password; they still need the old one
+    pub password_files: Vec<PathBuf>,
+}
+
+/// What happened to one file of a batch
+#[derive(Debug)]
+pub enum BatchFileOutcome {
+    /// Encrypted to the given path
+    Encrypted(PathBuf),
+    /// This file could not be encrypted
+    Failed(CargoCryptError),
+    /// Encrypted, but undone because another file of an all-or-nothing batch failed
+    RolledBack,
+}
+
+/// Per-file results of [`CargoCrypt::encrypt_batch`], in input order
+#[derive(Debug)]
+pub struct BatchReport {
+    /// Each input path with its outcome
+    pub files: Vec<(PathBuf, BatchFileOutcome)>,
+    /// Whether an all-or-nothing batch was rolled back
+    pub rolled_back: bool,
+}
+
+impl BatchReport {
+    /// Whether every file was encrypted
+    pub fn is_success(&self) -> bool {
+        self.files.iter().all(|(_, outcome)| matches!(outcome, BatchFileOutcome::Encrypted(_)))
+    }
+
+    /// Paths of the encrypted files that were written
+    pub fn encrypted(&self) -> impl Iterator<Item = &Path> {
+        self.files.iter().filter_map(|(_, outcome)| match outcome {
+            BatchFileOutcome::Encrypted(path) => Some(path.as_path()),
+            _ => None,
+        })
+    }
+
+    /// Input paths that failed, with their errors
+    pub fn failures(&self) -> impl Iterator<Item = (&Path, &CargoCryptError)> {
+        self.files.iter().filter_map(|(path, outcome)| match outcome {
+            BatchFileOutcome::Failed(e) => Some((path.as_path(), e)),
+            _ => None,
+        })
+    }
+}
+
+/// Files written so far by an all-or-nothing batch, so they can be undone
+#[derive(Default)]
+struct BatchTransaction {
+    /// Synced temporary files and the targets they will replace
+    staged: Vec<(PathBuf, PathBuf)>,
+    /// Targets in place, with the file each one replaced moved aside
+    committed: Vec<(PathBuf, Option<PathBuf>)>,
+    /// Backups of originals created by this batch
+    backups: Vec<PathBuf>,
+}
+
+impl BatchTransaction {
+    /// Rename the next staged file into place, moving any existing file aside
+    async fn commit_next(&mut self) -> CryptoResult<()> {
+        let (temp_path, target) = self.staged.remove(0);
+        let previous = if tokio::fs::try_exists(&target).await? {
+            let previous = hidden_sibling(&target, "prev");
+            tokio::fs::rename(&target, &previous).await?;
+            Some(previous)
+        } else {
+            None
+        };
+
+        if let Err(e) = tokio::fs::rename(&temp_path, &target).await {
+            let _ = tokio::fs::remove_file(&temp_path).await;
+            if let Some(previous) = previous {
+                let _ = tokio::fs::rename(&previous, &target).await;
+            }
+            return Err(e.into());
+        }
+        self.committed.push((target, previous));
+        Ok(())
+    }
+
+    /// Remove everything the batch wrote and restore what it replaced
+    async fn rollback(self) {
+        for (temp_path, _) in &self.staged {
+            let _ = tokio::fs::remove_file(temp_path).await;
+        }
+        for (target, previous) in self.committed.iter().rev() {
+            let restored = match previous {
+                Some(previous) => tokio::fs::rename(previous, target).await,
+                None => tokio::fs::remove_file(target).await,
+            };
+            if let Err(e) = restored {
+                error!("Failed to roll back {}: {}", target.display(), e);
+            }
+            sync_parent_dir(target).await;
+        }
+        for backup in &self.backups {
+            let _ = tokio::fs::remove_file(backup).await;
+        }
+    }
+
+    /// Drop the replaced files once the whole batch is in place
+    async fn finish(self) {
+        for (target, previous) in &self.committed {
+            if let Some(previous) = previous {
+                let _ = tokio::fs::remove_file(previous).await;
+            }
+            sync_parent_dir(target).await;
+        }
+    }
+}
+
+/// Run `task` for every path, at most `limit` at a time, keeping input order
+async fn for_each_bounded<T, F, Fut>(paths: &[PathBuf], limit: usize, task: F) -> Vec<CryptoResult<T>>
+where
+    F: Fn(PathBuf) -> Fut,
+    Fut: Future<Output = CryptoResult<T>> + Send + 'static,
+    T: Send + 'static,
+{
+    let permits = Arc::new(Semaphore::new(limit));
+    let handles: Vec<_> = paths
+        .iter()
+        .map(|path| {
+            let permits = Arc::clone(&permits);
+            let work = task(path.clone());
+            tokio::spawn(async move {
+                let _permit = permits.acquire_owned().await.expect("semaphore is never closed");
+                work.await
+            })
+        })
+        .collect();
+
+    let mut results = Vec::with_capacity(handles.len());
+    for handle in handles {
+        results.push(handle.await.unwrap_or_else(|e| {
+            Err(CargoCryptError::encryption_failed(&format!("Batch task failed: {}", e)))
+        }));
+    }
+    results
+}
+
+/// Size of the file at `path`, zero if it cannot be read
+fn file_size(path: &Path) -> u64 {
+    std::fs::metadata(path).map_or(0, |metadata| metadata.len())
+}
+
+/// Where the encrypted file of `path` goes
+fn encrypted_path(path: &Path) -> PathBuf {
+    path.with_extension(format!("{}.enc",
+        path.extension().and_then(|ext| ext.to_str()).unwrap_or("dat")))
+}
+
+/// Where the decrypted file of `path` goes: without `.enc`, or with `.decrypted`
+fn decrypted_path(path: &Path) -> PathBuf {
+    if path.extension().and_then(|ext| ext.to_str()) == Some("enc") {
+        path.with_extension("")
+    } else {
+        path.with_extension("decrypted")
+    }
+}
+
+/// Where the backup of an original file goes
+fn backup_path(path: &Path) -> PathBuf {
+    path.with_extension(format!("{}.backup",
+        path.extension().and_then(|ext| ext.to_str()).unwrap_or("dat")))
+}
+
+/// A hidden, process-unique path next to `path`
+fn hidden_sibling(path: &Path, suffix: &str) -> PathBuf {
+    let file_name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    path.with_file_name(format!(".{}.{}.{}", file_name, std::process::id(), suffix))
+}
+
+/// Password and keyfile of a managed file, or why it is skipped
+fn managed_key<'k>(
+    file: &crate::managed::ManagedFile,
+    keys: &'k ManagedKeys,
+    vault_keys: &'k BTreeMap<String, Keyfile>,
+) -> Result<(Option<&'k str>, Option<&'k Keyfile>), String> {
+    if let Some(vault) = &file.vault {
+        return match vault_keys.get(vault) {
+            Some(key) => Ok((None, Some(key))),
+            None => Err(format!("needs the password of vault '{}'", vault)),
+        };
+    }
+    let password = match &keys.password {
+        Some(password) if file.key.requires_password() => Some(password.as_str()),
+        None if file.key.requires_password() => return Err("needs the password".to_string()),
+        _ => None,
+    };
+    let keyfile = match &keys.keyfile {
+        Some(keyfile) if file.key.requires_keyfile() => Some(keyfile),
+        None if file.key.requires_keyfile() => return Err("needs its keyfile".to_string()),
+        _ => None,
+    };
+    Ok((password, keyfile))
+}
+
+/// What [`CargoCrypt::close_file`] did with the plaintext
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionClose {
+    /// Unchanged, so only removed
+    Unchanged,
+    /// Changed, so encrypted again before it was removed
+    Reencrypted,
+    /// Already gone
+    Missing,
+}
+
+fn session_open(session: &Session) -> CargoCryptError {
+    CargoCryptError::Config {
+        message: format!("{} is already open at {}", session.encrypted.display(), session.plaintext.display()),
+        suggestion: Some(format!("Close it first with `cargocrypt close {}`", session.encrypted.display())),
+    }
+}
+
+fn session_key_missing(path: &Path, key: &str) -> CargoCryptError {
+    CargoCryptError::from(crate::crypto::CryptoError::invalid_key(format!("{} needs {}", path.display(), key)))
+}
+
+/// What happens to the plaintext once a file is encrypted
+#[derive(Debug, Clone, Copy)]
+enum Plaintext {
+    Keep,
+    Remove { shred: bool },
+}
+
+/// Write `bytes` to `path` through a synced temporary file and an atomic rename
+pub(crate) async fn write_file_synced(path: &Path, bytes: &[u8]) -> CryptoResult<()> {
+    let temp_path = stage_file(path, bytes).await?;
+
+    if let Err(e) = tokio::fs::rename(&temp_path, path).await {
+        let _ = tokio::fs::remove_file(&temp_path).await;
+        return Err(e.into());
+    }
+    sync_parent_dir(path).await;
+    Ok(())
+}
+
+/// Write `bytes` to a synced temporary file next to `path` and return its path
+async fn stage_file(path: &Path, bytes: &[u8]) -> CryptoResult<PathBuf> {
+    let temp_path = hidden_sibling(path, "tmp");
+
+    let result = async {
+        let mut file = tokio::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp_path)
+            .await?;
+        file.write_all(bytes).await?;
+        file.sync_all().await
+    }
+    .await;
+
+    if let Err(e) = result {
+        let _ = tokio::fs::remove_file(&temp_path).await;
+        return Err(e.into());
+    }
+    Ok(temp_path)
+}
+
+/// Overwrite a file's contents with random data and sync it to disk
+async fn shred_file(path: &Path) -> CryptoResult<()> {
+    use rand::RngCore;
+
+    let mut file = tokio::fs::OpenOptions::new().write(true).open(path).await?;
+    let mut remaining = file.metadata().await?.len();
+    let mut block = vec![0u8; 64 * 1024];
+
+    while remaining > 0 {
+        let len = remaining.min(block.len() as u64) as usize;
+        rand::rngs::OsRng.fill_bytes(&mut block[..len]);
+        file.write_all(&block[..len]).await?;
+        remaining -= len as u64;
+    }
+    file.sync_all().await?;
+    Ok(())
+}
+
+/// Persist a rename or unlink by syncing the containing directory
+async fn sync_parent_dir(path: &Path) {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    // Directories cannot be opened for syncing on every platform
+    if let Ok(dir) = tokio::fs::File::open(dir).await {
+        let _ = dir.sync_all().await;
+    }
+}
+
+impl CryptoConfig {
+    /// Location of the configuration file in a project
+    pub fn project_path<P: AsRef<Path>>(project_root: P) -> PathBuf {
+        project_root.as_ref().join(".cargocrypt").join("config.toml")
+    }
+
+    /// Load the configuration of a project
+    ///
+    /// The project file is merged with the user configuration, the active
+    /// profile and environment overrides over the defaults; see
+    /// [`crate::config`].
+    pub fn load_project<P: AsRef<Path>>(project_root: P) -> CryptoResult<Self> {
+        crate::config::ConfigLayers::load(project_root.as_ref())?.crypto_config()
+    }
+
+    /// Load a configuration file
+    pub fn load<P: AsRef<Path>>(path: P) -> CryptoResult<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)?;
+        toml::from_str(&content).map_err(|e| CargoCryptError::Config {
+            message: format!("Invalid configuration in {}: {}", path.display(), e),
+            suggestion: Some("Fix the file or delete it and run 'cargocrypt init' to recreate it".to_string()),
+        })
+    }
+
+    /// Write the configuration to a file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> CryptoResult<()> {
+        let content = toml::to_string_pretty(self).map_err(|e| CargoCryptError::Serialization {
+            message: format!("Failed to serialize config: {}", e),
+            source: Box::new(e),
+        })?;
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+}
+
 // Validation methods
 impl CryptoConfig {
     /// Validate the configuration
@@ -803,6 +2958,8 @@ impl CryptoConfig {
         if self.key_params.parallelism < 1 {
             return Err(CargoCryptError::config_not_found());
         }
+
+        self.key_params.kdf.validate()?;
         
         Ok(())
     }
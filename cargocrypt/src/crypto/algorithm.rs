@@ -171,6 +171,11 @@ impl Algorithm {
         // Prefer ChaCha20-Poly1305 for its side-channel resistance
         Algorithm::ChaCha20Poly1305
     }
+
+    /// Check whether files can be encrypted with this algorithm yet
+    pub fn is_implemented(&self) -> bool {
+        matches!(self, Algorithm::ChaCha20Poly1305)
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,188 @@
+//! Caching of password-derived keys
+//!
+//! Argon2 derivation deliberately takes hundreds of milliseconds to seconds.
+//! When many files are processed with the same password (git filters run once
+//! per tracked file, on every `git status`) a [`KeyCache`] lets the engine
+//! skip derivations it has already done. Entries are keyed by an HMAC of the
+//! password rather than the password itself, and expire after a TTL. The HMAC
+//! also covers a namespace (the project, see
+//! [`utils::project_id`](crate::utils::project_id)), so a cache shared by
+//! several checkouts never hands out another project's key.
+
+use crate::crypto::{DerivedKey, Kdf, defaults};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Domain separation label for password identifiers
+const PASSWORD_ID_LABEL: &[u8] = b"cargocrypt-key-cache-v1";
+
+/// Source of previously derived keys
+pub trait KeyCache: Send + Sync + std::fmt::Debug {
+    /// Look up the key derived from `password` with `kdf` for `salt`
+    ///
+    /// Without a salt, any cached key for the password is returned, which lets
+    /// new encryptions reuse a cached derivation.
+    fn get(&self, password: &str, kdf: &Kdf, salt: Option<&[u8; defaults::SALT_LENGTH]>) -> Option<DerivedKey>;
+
+    /// Remember a key derived from `password` with `kdf`
+    fn put(&self, password: &str, kdf: &Kdf, key: &DerivedKey);
+}
+
+/// Identifier of a password and KDF within a namespace, used as cache key
+pub fn password_id(namespace: &str, kdf: &Kdf, password: &str) -> [u8; 32] {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(password.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(PASSWORD_ID_LABEL);
+    mac.update(&[0]);
+    mac.update(namespace.as_bytes());
+    mac.update(&[0]);
+    // KDF parameters are part of the identity: the same password and salt
+    // give different keys under different KDFs
+    mac.update(kdf.to_string().as_bytes());
+    mac.finalize().into_bytes().into()
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    password_id: [u8; 32],
+    key: DerivedKey,
+    expires_at: Instant,
+}
+
+/// In-memory key cache with a fixed time-to-live
+#[derive(Debug)]
+pub struct MemoryKeyCache {
+    ttl: Duration,
+    namespace: String,
+    entries: Mutex<Vec<CacheEntry>>,
+}
+
+impl MemoryKeyCache {
+    /// Create a cache whose entries expire `ttl` after they were added
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            namespace: String::new(),
+            entries: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Keep keys of this cache separate from other namespaces
+    pub fn with_namespace<S: Into<String>>(mut self, namespace: S) -> Self {
+        self.namespace = namespace.into();
+        self
+    }
+
+    /// Time-to-live of cache entries
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Look up a key by password identifier
+    pub fn get_by_id(&self, password_id: &[u8; 32], salt: Option<&[u8; defaults::SALT_LENGTH]>) -> Option<DerivedKey> {
+        let mut entries = self.lock();
+        Self::purge(&mut entries);
+
+        entries
+            .iter()
+            .rev()
+            .find(|entry| &entry.password_id == password_id && salt.is_none_or(|salt| entry.key.salt() == salt))
+            .map(|entry| entry.key.clone())
+    }
+
+    /// Store a key by password identifier, replacing an entry for the same salt
+    pub fn put_by_id(&self, password_id: [u8; 32], key: DerivedKey) {
+        let mut entries = self.lock();
+        Self::purge(&mut entries);
+
+        entries.retain(|entry| !(entry.password_id == password_id && entry.key.salt() == key.salt()));
+        entries.push(CacheEntry {
+            password_id,
+            key,
+            expires_at: Instant::now() + self.ttl,
+        });
+    }
+
+    /// Number of live entries
+    pub fn len(&self) -> usize {
+        let mut entries = self.lock();
+        Self::purge(&mut entries);
+        entries.len()
+    }
+
+    /// Whether the cache holds no live entries
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drop all entries
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<CacheEntry>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn purge(entries: &mut Vec<CacheEntry>) {
+        let now = Instant::now();
+        entries.retain(|entry| entry.expires_at > now);
+    }
+}
+
+impl KeyCache for MemoryKeyCache {
+    fn get(&self, password: &str, kdf: &Kdf, salt: Option<&[u8; defaults::SALT_LENGTH]>) -> Option<DerivedKey> {
+        self.get_by_id(&password_id(&self.namespace, kdf, password), salt)
+    }
+
+    fn put(&self, password: &str, kdf: &Kdf, key: &DerivedKey) {
+        self.put_by_id(password_id(&self.namespace, kdf, password), key.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_cache_lookup() {
+        let cache = MemoryKeyCache::new(Duration::from_secs(60));
+        let key = DerivedKey::from_password_with_random_salt("cache_password").unwrap();
+        cache.put("cache_password", &Kdf::Argon2id, &key);
+
+        assert_eq!(cache.get("cache_password", &Kdf::Argon2id, Some(key.salt())).unwrap().key(), key.key());
+        assert_eq!(cache.get("cache_password", &Kdf::Argon2id, None).unwrap().salt(), key.salt());
+        assert!(cache.get("other_password", &Kdf::Argon2id, None).is_none());
+        assert!(cache.get("cache_password", &Kdf::Argon2id, Some(&[0u8; defaults::SALT_LENGTH])).is_none());
+
+        cache.put("cache_password", &Kdf::Argon2id, &key);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_namespaces_are_separate() {
+        let key = DerivedKey::from_password_with_random_salt("cache_password").unwrap();
+        let cache = MemoryKeyCache::new(Duration::from_secs(60)).with_namespace("project-a");
+        cache.put("cache_password", &Kdf::Argon2id, &key);
+
+        let id_a = password_id("project-a", &Kdf::Argon2id, "cache_password");
+        let id_b = password_id("project-b", &Kdf::Argon2id, "cache_password");
+        assert_ne!(id_a, id_b);
+        assert!(cache.get_by_id(&id_a, None).is_some());
+        assert!(cache.get_by_id(&id_b, None).is_none());
+        assert!(cache.get("cache_password", &Kdf::pbkdf2_sha256(), None).is_none());
+    }
+
+    #[test]
+    fn test_memory_cache_expiry() {
+        let cache = MemoryKeyCache::new(Duration::from_millis(10));
+        let key = DerivedKey::from_password_with_random_salt("cache_password").unwrap();
+        cache.put("cache_password", &Kdf::Argon2id, &key);
+
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(cache.get("cache_password", &Kdf::Argon2id, None).is_none());
+        assert!(cache.is_empty());
+    }
+}
@@ -0,0 +1,197 @@
+//! Chunked parallel encryption for large files
+//!
+//! Above a size threshold the plaintext is split into fixed-size chunks that
+//! are encrypted independently on all cores. Each chunk has its own subkey,
+//! expanded with HKDF-SHA256 from the file key, its index and the chunk
+//! count; the file nonce is shared, which is safe because no two chunks use
+//! the same key. Binding the index prevents reordering, and binding the
+//! count means a truncated file (or a header claiming fewer chunks) fails to
+//! authenticate.
+//!
+//! The ciphertext is the chunks' ciphertexts back to back, each followed by
+//! its tag. Every chunk but the last holds exactly
+//! [`ChunkLayout::chunk_size`] bytes of plaintext.
+
+use crate::crypto::{defaults, CryptoError, CryptoResult, DerivedKey};
+use chacha20poly1305::{aead::{AeadInPlace, KeyInit}, ChaCha20Poly1305, Key, Nonce, Tag};
+use hkdf::Hkdf;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use zeroize::Zeroize;
+
+/// HKDF info prefix for chunk subkeys
+const CHUNK_INFO: &[u8] = b"cargocrypt-chunk-v1";
+
+/// How a chunked ciphertext is laid out, recorded in the file header
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkLayout {
+    /// Plaintext bytes per chunk
+    pub chunk_size: u32,
+    /// Number of chunks (at least one, even for empty plaintext)
+    pub chunks: u64,
+}
+
+impl ChunkLayout {
+    /// Layout for `len` bytes of plaintext in chunks of `chunk_size` bytes
+    pub fn for_len(len: usize, chunk_size: usize) -> CryptoResult<Self> {
+        let chunk_size = u32::try_from(chunk_size)
+            .ok()
+            .filter(|&size| size > 0)
+            .ok_or_else(|| CryptoError::invalid_input("Chunk size must be between 1 byte and 4 GiB"))?;
+        Ok(Self {
+            chunk_size,
+            chunks: (len.div_ceil(chunk_size as usize).max(1)) as u64,
+        })
+    }
+
+    /// Plaintext length of a ciphertext of `ciphertext_len` bytes, if it fits the layout
+    fn plaintext_len(&self, ciphertext_len: usize) -> Option<usize> {
+        let chunks = usize::try_from(self.chunks).ok().filter(|&chunks| chunks > 0)?;
+        let len = ciphertext_len.checked_sub(chunks.checked_mul(defaults::TAG_LENGTH)?)?;
+        let full = (chunks - 1).checked_mul(self.chunk_size as usize)?;
+        let last = len.checked_sub(full)?;
+        // Only a single chunk may be empty
+        (last <= self.chunk_size as usize && (last > 0 || chunks == 1)).then_some(len)
+    }
+}
+
+/// Encrypt `plaintext` in parallel chunks
+pub(crate) fn seal(
+    key: &DerivedKey,
+    nonce: &[u8; defaults::NONCE_LENGTH],
+    plaintext: &[u8],
+    chunk_size: usize,
+) -> CryptoResult<(Vec<u8>, ChunkLayout)> {
+    let layout = ChunkLayout::for_len(plaintext.len(), chunk_size)?;
+    let mut ciphertext = vec![0u8; plaintext.len() + layout.chunks as usize * defaults::TAG_LENGTH];
+
+    ciphertext
+        .par_chunks_mut(chunk_size + defaults::TAG_LENGTH)
+        .enumerate()
+        .try_for_each(|(index, out)| -> CryptoResult<()> {
+            let (body, tag_out) = out.split_at_mut(out.len() - defaults::TAG_LENGTH);
+            let start = index * chunk_size;
+            body.copy_from_slice(&plaintext[start..start + body.len()]);
+
+            let subkey = subkey(key, index as u64, layout.chunks)?;
+            let tag = ChaCha20Poly1305::new(&subkey)
+                .encrypt_in_place_detached(Nonce::from_slice(nonce), &[], body)
+                .map_err(CryptoError::from)?;
+            tag_out.copy_from_slice(&tag);
+            crate::crypto::nonce::record(subkey.as_slice(), nonce, out)
+        })?;
+
+    Ok((ciphertext, layout))
+}
+
+/// Decrypt a chunked ciphertext in parallel
+pub(crate) fn open(
+    key: &DerivedKey,
+    nonce: &[u8; defaults::NONCE_LENGTH],
+    ciphertext: &[u8],
+    layout: &ChunkLayout,
+) -> CryptoResult<Vec<u8>> {
+    let len = layout
+        .plaintext_len(ciphertext.len())
+        .ok_or_else(|| CryptoError::decryption("Ciphertext does not match its chunk layout"))?;
+    let chunk_size = layout.chunk_size as usize;
+    let mut plaintext = vec![0u8; len];
+
+    if len == 0 {
+        // Nothing to pair the single chunk with below, but its tag still counts
+        return open_chunk(key, nonce, layout, 0, ciphertext, &mut []).map(|()| plaintext);
+    }
+
+    let result = ciphertext
+        .par_chunks(chunk_size + defaults::TAG_LENGTH)
+        .zip(plaintext.par_chunks_mut(chunk_size))
+        .enumerate()
+        .try_for_each(|(index, (chunk, out))| open_chunk(key, nonce, layout, index as u64, chunk, out));
+
+    if result.is_err() {
+        plaintext.zeroize();
+    }
+    result.map(|()| plaintext)
+}
+
+fn open_chunk(
+    key: &DerivedKey,
+    nonce: &[u8; defaults::NONCE_LENGTH],
+    layout: &ChunkLayout,
+    index: u64,
+    chunk: &[u8],
+    out: &mut [u8],
+) -> CryptoResult<()> {
+    let (body, tag) = chunk.split_at(chunk.len() - defaults::TAG_LENGTH);
+    out.copy_from_slice(body);
+
+    let subkey = subkey(key, index, layout.chunks)?;
+    ChaCha20Poly1305::new(&subkey)
+        .decrypt_in_place_detached(Nonce::from_slice(nonce), &[], out, Tag::from_slice(tag))
+        .map_err(|_| CryptoError::AuthenticationFailed)
+}
+
+/// Subkey of chunk `index` out of `chunks`
+fn subkey(key: &DerivedKey, index: u64, chunks: u64) -> CryptoResult<Key> {
+    let mut info = Vec::with_capacity(CHUNK_INFO.len() + 16);
+    info.extend_from_slice(CHUNK_INFO);
+    info.extend_from_slice(&index.to_le_bytes());
+    info.extend_from_slice(&chunks.to_le_bytes());
+
+    let mut key_bytes = [0u8; defaults::KEY_LENGTH];
+    Hkdf::<Sha256>::new(Some(key.salt()), key.key().as_slice())
+        .expand(&info, &mut key_bytes)
+        .map_err(|e| CryptoError::key_derivation(format!("HKDF expansion failed: {}", e)))?;
+
+    let subkey = *Key::from_slice(&key_bytes);
+    key_bytes.zeroize();
+    Ok(subkey)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key() -> DerivedKey {
+        DerivedKey::from_raw_parts(*Key::from_slice(&[3u8; defaults::KEY_LENGTH]), [4u8; defaults::SALT_LENGTH])
+    }
+
+    #[test]
+    fn test_seal_open_roundtrip() {
+        let key = key();
+        for (len, nonce_byte) in [(0usize, 1u8), (1, 2), (1000, 3), (1024, 4), (1025, 5)] {
+            let plaintext: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let nonce = [nonce_byte; defaults::NONCE_LENGTH];
+
+            let (ciphertext, layout) = seal(&key, &nonce, &plaintext, 256).unwrap();
+            assert_eq!(layout.chunks, len.div_ceil(256).max(1) as u64);
+            assert_eq!(ciphertext.len(), len + layout.chunks as usize * defaults::TAG_LENGTH);
+            assert_eq!(open(&key, &nonce, &ciphertext, &layout).unwrap(), plaintext);
+        }
+    }
+
+    #[test]
+    fn test_tampering_is_detected() {
+        let key = key();
+        let nonce = [6u8; defaults::NONCE_LENGTH];
+        let plaintext = vec![0x42u8; 1000];
+        let (ciphertext, layout) = seal(&key, &nonce, &plaintext, 256).unwrap();
+        let stride = 256 + defaults::TAG_LENGTH;
+
+        // Swapping two full chunks
+        let mut swapped = ciphertext.clone();
+        swapped[..stride].copy_from_slice(&ciphertext[stride..2 * stride]);
+        swapped[stride..2 * stride].copy_from_slice(&ciphertext[..stride]);
+        assert!(matches!(open(&key, &nonce, &swapped, &layout), Err(CryptoError::AuthenticationFailed)));
+
+        // Dropping the last chunk and claiming one chunk fewer
+        let truncated = &ciphertext[..3 * stride];
+        let shorter = ChunkLayout { chunks: 3, ..layout };
+        assert!(matches!(open(&key, &nonce, truncated, &shorter), Err(CryptoError::AuthenticationFailed)));
+
+        // Lengths that cannot come from the layout
+        assert!(open(&key, &nonce, &ciphertext[..ciphertext.len() - 300], &layout).is_err());
+        assert!(open(&key, &nonce, &[], &layout).is_err());
+    }
+}
@@ -0,0 +1,60 @@
+//! Deterministic (SIV-style) encryption for git filters
+//!
+//! Git runs the clean filter on every `git status` / `git add`, and compares
+//! the output with what is stored in the index. With a random salt and nonce
+//! every run produces different ciphertext, so unchanged files always look
+//! modified. In deterministic mode:
+//! - the key is derived with a fixed salt, generated at random once per
+//!   repository and kept in `.cargocrypt/git.toml` (see
+//!   [`FilterSettings::salt`](crate::git::config::FilterSettings::salt)), so
+//!   Argon2 runs once per password rather than once per file
+//! - the nonce is `HMAC-SHA256(key, path || plaintext)`, i.e. a synthetic IV
+//!
+//! Identical plaintext at the same path under the same key therefore yields
+//! identical ciphertext, and a nonce is only ever reused for the very same
+//! message. The salt never depends on the password or the plaintext, so a
+//! password guess still costs a full key derivation. The trade-off is that
+//! unchanged files are recognizable as unchanged, which is inherent to
+//! getting a clean `git status`. Decryption is unchanged since the salt and
+//! nonce are stored in the header as usual.
+
+use crate::crypto::{defaults, DerivedKey};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Domain separation label for nonce derivation
+const NONCE_LABEL: &[u8] = b"cargocrypt-deterministic-nonce-v2";
+
+/// Derive the synthetic nonce from the encryption key, the file's path and its plaintext
+pub fn derive_nonce(key: &DerivedKey, path: &str, plaintext: &[u8]) -> [u8; defaults::NONCE_LENGTH] {
+    let mut mac = <HmacSha256 as Mac>::new_from_slice(key.key().as_slice()).expect("HMAC accepts keys of any length");
+    mac.update(NONCE_LABEL);
+    // The length keeps the path and plaintext apart
+    mac.update(&(path.len() as u64).to_be_bytes());
+    mac.update(path.as_bytes());
+    mac.update(plaintext);
+    let digest: [u8; 32] = mac.finalize().into_bytes().into();
+
+    let mut nonce = [0u8; defaults::NONCE_LENGTH];
+    nonce.copy_from_slice(&digest[..defaults::NONCE_LENGTH]);
+    nonce
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_derivation_is_deterministic() {
+        let key = DerivedKey::from_password_with_salt("pw", &[7u8; defaults::SALT_LENGTH]).unwrap();
+        assert_eq!(derive_nonce(&key, "a.env", b"data"), derive_nonce(&key, "a.env", b"data"));
+        assert_ne!(derive_nonce(&key, "a.env", b"data"), derive_nonce(&key, "a.env", b"other"));
+        assert_ne!(derive_nonce(&key, "a.env", b"data"), derive_nonce(&key, "b.env", b"data"));
+        assert_ne!(derive_nonce(&key, "a.env", b"xdata"), derive_nonce(&key, "a.envx", b"data"));
+
+        let other = DerivedKey::from_password_with_salt("other", &[7u8; defaults::SALT_LENGTH]).unwrap();
+        assert_ne!(derive_nonce(&key, "a.env", b"data"), derive_nonce(&other, "a.env", b"data"));
+    }
+}
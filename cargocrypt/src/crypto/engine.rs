@@ -2,7 +2,9 @@
 
 use crate::crypto::{
     CryptoError, CryptoResult, DerivedKey, EncryptedSecret, PlaintextSecret, 
-    SecretMetadata, SecretType, defaults, keys::SecureRandom
+    SecretMetadata, SecretType, defaults, keys::SecureRandom,
+    DetachedSignature, SigningKeyPair, Keyfile, KeySource, KeyCache, Kdf,
+    HybridKeyPair, HybridPublicKey,
 };
 use crate::resilience::{CircuitBreaker, RetryPolicy};
 use crate::validation::InputValidator;
@@ -32,6 +34,10 @@ pub struct CryptoEngine {
     validator: InputValidator,
     /// Feature flags for graceful degradation
     features_enabled: Arc<RwLock<CryptoFeatures>>,
+    /// Cache of password-derived keys (e.g. the session agent)
+    key_cache: Option<Arc<dyn KeyCache>>,
+    /// Key derivation function for new password-encrypted secrets
+    kdf: Kdf,
 }
 
 /// Feature flags for crypto engine capabilities
@@ -96,6 +102,21 @@ impl PerformanceProfile {
             ).expect("Valid Argon2 params"),
         }
     }
+
+    /// All profiles, from the fastest to the most secure
+    pub fn all() -> &'static [PerformanceProfile] {
+        &[Self::Fast, Self::Balanced, Self::Secure, Self::Paranoid]
+    }
+
+    /// Key derivation function with this profile's Argon2 parameters
+    pub fn kdf(&self) -> Kdf {
+        let params = self.argon2_params();
+        Kdf::Argon2idCustom {
+            m_cost: params.m_cost(),
+            t_cost: params.t_cost(),
+            p_cost: params.p_cost(),
+        }
+    }
 }
 
 /// Encryption options
@@ -107,6 +128,15 @@ pub struct EncryptionOptions {
     pub performance_profile: Option<PerformanceProfile>,
     /// Custom salt (if None, random salt is generated)
     pub salt: Option<[u8; defaults::SALT_LENGTH]>,
+    /// Path of the file whose nonce is derived from its path and plaintext,
+    /// so equal input gives equal output; needs a fixed `salt`
+    pub deterministic: Option<String>,
+    /// Encrypt plaintexts of at least this many bytes in parallel chunks
+    pub chunk_threshold: Option<usize>,
+    /// Bind the key to `recipient` with hybrid X25519 + ML-KEM-768 encapsulation
+    pub post_quantum: bool,
+    /// Hybrid public key the secret is bound to in post-quantum mode
+    pub recipient: Option<HybridPublicKey>,
 }
 
 impl Default for EncryptionOptions {
@@ -115,6 +145,10 @@ impl Default for EncryptionOptions {
             metadata: None,
             performance_profile: None,
             salt: None,
+            deterministic: None,
+            chunk_threshold: None,
+            post_quantum: false,
+            recipient: None,
         }
     }
 }
@@ -143,6 +177,43 @@ impl EncryptionOptions {
         self
     }
 
+    /// Encrypt the file at `path` deterministically (used by the git clean filter)
+    ///
+    /// Together with a fixed salt ([`Self::with_salt`]), unchanged plaintext
+    /// at the same path then produces byte-identical output, at the cost of
+    /// revealing when an encrypted file is unchanged. No creation timestamp
+    /// is recorded unless metadata is set explicitly.
+    pub fn deterministic<S: Into<String>>(mut self, path: S) -> Self {
+        self.deterministic = Some(path.into());
+        self
+    }
+
+    /// Encrypt plaintexts of at least `threshold` bytes in parallel chunks
+    ///
+    /// Chunks hold [`defaults::CHUNK_SIZE`] bytes each. Deterministic
+    /// encryption ignores this, since its nonce is derived from the whole
+    /// plaintext anyway.
+    pub fn chunked_above(mut self, threshold: usize) -> Self {
+        self.chunk_threshold = Some(threshold);
+        self
+    }
+
+    /// Enable or disable post-quantum hybrid encryption
+    ///
+    /// Needs a recipient ([`Self::with_recipient`]), whose secret key is then
+    /// required to decrypt along with the password or keyfile; see
+    /// [`crate::crypto::hybrid`]. The mode is recorded in the file header.
+    pub fn post_quantum(mut self, enabled: bool) -> Self {
+        self.post_quantum = enabled;
+        self
+    }
+
+    /// Set the hybrid public key to bind the secret to in post-quantum mode
+    pub fn with_recipient(mut self, recipient: HybridPublicKey) -> Self {
+        self.recipient = Some(recipient);
+        self
+    }
+
     /// Set description metadata
     pub fn with_description<S: Into<String>>(mut self, description: S) -> Self {
         let mut metadata = self.metadata.unwrap_or_default();
@@ -191,6 +262,8 @@ impl CryptoEngine {
                 batch_operations: true,
                 direct_operations: true,
             })),
+            key_cache: None,
+            kdf: Kdf::Argon2id,
         }
     }
 
@@ -226,9 +299,35 @@ impl CryptoEngine {
                 batch_operations: true,
                 direct_operations: true,
             })),
+            key_cache: None,
+            kdf: Kdf::Argon2id,
         }
     }
 
+    /// Consult a key cache before running Argon2 for a password
+    pub fn with_key_cache(mut self, cache: Arc<dyn KeyCache>) -> Self {
+        self.key_cache = Some(cache);
+        self
+    }
+
+    /// Get the key cache, if one is configured
+    pub fn key_cache(&self) -> Option<&Arc<dyn KeyCache>> {
+        self.key_cache.as_ref()
+    }
+
+    /// Use a different key derivation function for new secrets
+    ///
+    /// Decryption always uses the function recorded in the secret's header.
+    pub fn with_kdf(mut self, kdf: Kdf) -> Self {
+        self.kdf = kdf;
+        self
+    }
+
+    /// Get the key derivation function used for new secrets
+    pub fn kdf(&self) -> Kdf {
+        self.kdf
+    }
+
     /// Get the current performance profile
     pub fn performance_profile(&self) -> PerformanceProfile {
         self.performance_profile
@@ -239,6 +338,17 @@ impl CryptoEngine {
         self.encrypt_bytes(data, password, EncryptionOptions::default()).await
     }
     
+    /// Encrypt the file at `path` deterministically with a password and a fixed salt (convenience method for git filters)
+    pub async fn encrypt_data_deterministic(
+        &self,
+        data: &[u8],
+        password: &str,
+        salt: [u8; defaults::SALT_LENGTH],
+        path: &str,
+    ) -> CryptoResult<EncryptedSecret> {
+        self.encrypt_bytes(data, password, EncryptionOptions::default().with_salt(salt).deterministic(path)).await
+    }
+
     /// Decrypt data with a password (convenience method) 
     pub fn decrypt_data(&self, encrypted: &EncryptedSecret, password: &str) -> CryptoResult<Vec<u8>> {
         self.decrypt_to_bytes(encrypted, password)
@@ -300,7 +410,7 @@ impl CryptoEngine {
         }
         
         let secret = PlaintextSecret::from_string(plaintext.to_string());
-        self.encrypt_with_resilience(secret, password, options).await
+        self.encrypt_with_resilience(secret, Some(password), None, options).await
     }
 
     /// Encrypt bytes with a password
@@ -321,14 +431,29 @@ impl CryptoEngine {
         password: &str,
         options: EncryptionOptions,
     ) -> CryptoResult<EncryptedSecret> {
-        self.encrypt_with_resilience(plaintext, password, options).await
+        self.encrypt_with_resilience(plaintext, Some(password), None, options).await
+    }
+
+    /// Encrypt a plaintext secret with a keyfile, optionally mixed with a password
+    ///
+    /// Without a password no Argon2 derivation takes place, which makes this
+    /// suitable for CI where prompting is impossible.
+    pub async fn encrypt_with_keyfile(
+        &self,
+        plaintext: PlaintextSecret,
+        keyfile: &Keyfile,
+        password: Option<&str>,
+        options: EncryptionOptions,
+    ) -> CryptoResult<EncryptedSecret> {
+        self.encrypt_with_resilience(plaintext, password, Some(keyfile), options).await
     }
     
     /// Internal encrypt method with circuit breaker and retry logic
     async fn encrypt_with_resilience(
         &self,
         plaintext: PlaintextSecret,
-        password: &str,
+        password: Option<&str>,
+        keyfile: Option<&Keyfile>,
         options: EncryptionOptions,
     ) -> CryptoResult<EncryptedSecret> {
         // Execute with circuit breaker protection
@@ -343,9 +468,18 @@ impl CryptoEngine {
             let profile = options.performance_profile.unwrap_or(performance_profile);
             
             // Create or use provided salt
-            let salt = match options.salt {
-                Some(salt) => salt,
-                None => SecureRandom::generate_salt()?,
+            let salt = match (options.salt, password) {
+                (Some(salt), _) => salt,
+                // A random salt would change the output on every run
+                (None, _) if options.deterministic.is_some() => {
+                    return Err(CryptoError::invalid_input("Deterministic encryption needs a fixed salt"));
+                }
+                // Reuse the salt of a cached derivation so Argon2 does not run again
+                (None, Some(password)) => match self.cached_key(password, None) {
+                    Some(key) => *key.salt(),
+                    None => SecureRandom::generate_salt()?,
+                },
+                _ => SecureRandom::generate_salt()?,
             };
 
             // Validate salt length
@@ -356,11 +490,38 @@ impl CryptoEngine {
             }
 
             // Derive key using the specified performance profile with validation
-            let key_result = self.derive_key_with_profile(password, &salt, profile);
-            let key = key_result?;
+            let password_key = password
+                .map(|password| self.derive_password_key(password, &salt, profile))
+                .transpose()?;
+            let (key, key_source) = match (keyfile, password_key) {
+                (Some(keyfile), None) => (keyfile.derive_key(&salt, None)?, KeySource::Keyfile),
+                (Some(keyfile), Some(password_key)) => {
+                    (keyfile.derive_key(&salt, Some(&password_key))?, KeySource::KeyfileAndPassword)
+                }
+                (None, Some(password_key)) => (password_key, KeySource::Password),
+                (None, None) => return Err(CryptoError::invalid_input("A password or keyfile is required")),
+            };
+            let (key, encapsulation) = match (options.post_quantum, &options.recipient) {
+                (false, _) => (key, None),
+                (true, None) => {
+                    return Err(CryptoError::invalid_input("Post-quantum encryption needs a recipient hybrid public key"));
+                }
+                // A fresh encapsulation would change the output on every run
+                (true, Some(_)) if options.deterministic.is_some() => {
+                    return Err(CryptoError::invalid_input("Post-quantum encryption cannot be deterministic"));
+                }
+                (true, Some(recipient)) => {
+                    let (key, encapsulation) = recipient.encapsulate(&key)?;
+                    (key, Some(encapsulation))
+                }
+            };
             
-            // Set up metadata
-            let metadata = options.metadata.or_else(|| {
+            // Set up metadata (a timestamp would make deterministic output differ)
+            let metadata = options.metadata.clone().or_else(|| {
+                if options.deterministic.is_some() {
+                    return None;
+                }
+
                 let mut meta = SecretMetadata::new();
                 meta.created_at = Some(
                     SystemTime::now()
@@ -371,7 +532,22 @@ impl CryptoEngine {
                 Some(meta)
             });
 
-            EncryptedSecret::encrypt_with_key(plaintext.clone(), &key, metadata)
+            let chunked = options.chunk_threshold.is_some_and(|threshold| plaintext.len() >= threshold);
+            let encrypted = if let Some(path) = &options.deterministic {
+                EncryptedSecret::encrypt_with_key_deterministic(plaintext.clone(), &key, path, metadata)
+            } else if chunked {
+                EncryptedSecret::encrypt_with_key_chunked(plaintext.clone(), &key, defaults::CHUNK_SIZE, metadata)
+            } else {
+                EncryptedSecret::encrypt_with_key(plaintext.clone(), &key, metadata)
+            };
+            encrypted.map(|encrypted| {
+                let encrypted = encrypted.with_key_source(key_source).with_post_quantum(encapsulation);
+                if key_source.requires_password() {
+                    encrypted.with_kdf(self.kdf)
+                } else {
+                    encrypted
+                }
+            })
         }).await;
         
         match result {
@@ -394,8 +570,77 @@ impl CryptoEngine {
     }
 
     /// Decrypt an encrypted secret with a password
+    ///
+    /// With a key cache configured, a cached key for the secret's salt is tried
+    /// first, and a freshly derived key is cached once it decrypts successfully.
+    /// When the cached key fails the key is derived again, so a damaged file
+    /// takes as long to reject as a wrong password.
     pub fn decrypt(&self, encrypted: &EncryptedSecret, password: &str) -> CryptoResult<PlaintextSecret> {
-        encrypted.decrypt_with_password(password)
+        encrypted.check_not_post_quantum()?;
+        let Some(cache) = self.key_cache.as_ref().filter(|_| !encrypted.key_source().requires_keyfile()) else {
+            return encrypted.decrypt_with_password(password);
+        };
+
+        let kdf = encrypted.kdf();
+        if let Some(key) = cache.get(password, kdf, Some(encrypted.salt())) {
+            if let Ok(plaintext) = encrypted.decrypt_with_key(&key) {
+                return Ok(plaintext);
+            }
+        }
+
+        let key = DerivedKey::from_password_with_kdf(password, encrypted.salt(), kdf)?;
+        let plaintext = encrypted.decrypt_with_key(&key)?;
+        cache.put(password, kdf, &key);
+        Ok(plaintext)
+    }
+
+    /// Derive a password key, consulting and filling the key cache
+    fn derive_password_key(
+        &self,
+        password: &str,
+        salt: &[u8; defaults::SALT_LENGTH],
+        profile: PerformanceProfile,
+    ) -> CryptoResult<DerivedKey> {
+        if let Some(key) = self.cached_key(password, Some(salt)) {
+            return Ok(key);
+        }
+
+        let key = match self.kdf {
+            Kdf::Argon2id => self.derive_key_with_profile(password, salt, profile)?,
+            kdf => DerivedKey::from_password_with_kdf(password, salt, &kdf)?,
+        };
+        if let Some(cache) = &self.key_cache {
+            cache.put(password, &self.kdf, &key);
+        }
+        Ok(key)
+    }
+
+    /// Look up a previously derived key in the key cache
+    fn cached_key(&self, password: &str, salt: Option<&[u8; defaults::SALT_LENGTH]>) -> Option<DerivedKey> {
+        self.key_cache.as_ref()?.get(password, &self.kdf, salt)
+    }
+
+    /// Decrypt a secret encrypted with a keyfile (and the password, if one was mixed in)
+    pub fn decrypt_with_keyfile(
+        &self,
+        encrypted: &EncryptedSecret,
+        keyfile: &Keyfile,
+        password: Option<&str>,
+    ) -> CryptoResult<PlaintextSecret> {
+        encrypted.decrypt_with_keyfile(keyfile, password)
+    }
+
+    /// Decrypt a secret bound to a post-quantum recipient with that recipient's key pair
+    ///
+    /// `password` and `keyfile` are what the secret was encrypted with.
+    pub fn decrypt_with_identity(
+        &self,
+        encrypted: &EncryptedSecret,
+        identity: &HybridKeyPair,
+        password: Option<&str>,
+        keyfile: Option<&Keyfile>,
+    ) -> CryptoResult<PlaintextSecret> {
+        encrypted.decrypt_with_identity(identity, password, keyfile)
     }
 
     /// Decrypt to string (convenience method)
@@ -450,7 +695,28 @@ impl CryptoEngine {
         BatchEncryptionResult { successes, failures }
     }
 
+    /// Create an Ed25519 detached signature over data (e.g. an encrypted file)
+    pub fn sign(&self, data: &[u8], key: &SigningKeyPair) -> DetachedSignature {
+        key.sign(data)
+    }
+
+    /// Verify a detached signature, optionally pinning the expected signer
+    pub fn verify(
+        &self,
+        data: &[u8],
+        signature: &DetachedSignature,
+        expected_public_key: Option<&[u8]>,
+    ) -> CryptoResult<()> {
+        match expected_public_key {
+            Some(public_key) => signature.verify_with_key(data, public_key),
+            None => signature.verify(data),
+        }
+    }
+
     /// Direct encryption with ChaCha20-Poly1305 (low-level API)
+    ///
+    /// Fails with [`CryptoError::NonceReuse`] if this process already used
+    /// `key` and `nonce` for a different message.
     pub fn encrypt_direct(
         &self,
         plaintext: &[u8],
@@ -460,9 +726,11 @@ impl CryptoEngine {
         let cipher = ChaCha20Poly1305::new(key);
         let nonce_obj = Nonce::from_slice(nonce);
         
-        cipher
+        let ciphertext = cipher
             .encrypt(nonce_obj, plaintext)
-            .map_err(CryptoError::from)
+            .map_err(CryptoError::from)?;
+        super::nonce::record(key.as_slice(), nonce, &ciphertext)?;
+        Ok(ciphertext)
     }
 
     /// Direct decryption with ChaCha20-Poly1305 (low-level API)
@@ -488,6 +756,7 @@ impl CryptoEngine {
         profile: PerformanceProfile,
     ) -> CryptoResult<DerivedKey> {
         let params = profile.argon2_params();
+        let memory_cost_kib = params.m_cost() as u64;
         let argon2 = Argon2::new(
             defaults::ARGON2_ALGORITHM,
             defaults::ARGON2_VERSION,
@@ -495,10 +764,13 @@ impl CryptoEngine {
         );
 
         let mut key_bytes = [0u8; defaults::KEY_LENGTH];
+        // Released before the final derivation below takes its own permit
+        let permit = super::limits::acquire(memory_cost_kib);
         
         argon2
             .hash_password_into(password.as_bytes(), salt, &mut key_bytes)
             .map_err(CryptoError::from)?;
+        drop(permit);
 
         let _key = Key::from_slice(&key_bytes).clone();
         
@@ -569,6 +841,77 @@ impl CryptoEngine {
         })
     }
 
+    /// Benchmark this profile on `data_size` bytes of data
+    ///
+    /// Unlike [`benchmark_performance`](Self::benchmark_performance), the key
+    /// is derived with exactly the profile's Argon2 parameters, so the timings
+    /// are what a file of that size costs with this profile.
+    pub fn benchmark_with_data_size(&self, data_size: usize) -> CryptoResult<PerformanceBenchmark> {
+        let salt = Self::generate_salt()?;
+        let start_time = std::time::Instant::now();
+
+        let key = DerivedKey::from_password_with_kdf("benchmark_password_12345", &salt, &self.performance_profile.kdf())?;
+        let derive_duration = start_time.elapsed();
+
+        let plaintext = PlaintextSecret::from_bytes(vec![0x5a; data_size]);
+        let encrypt_start = std::time::Instant::now();
+        let encrypted = EncryptedSecret::encrypt_with_key(plaintext, &key, None)?;
+        let encrypt_duration = encrypt_start.elapsed();
+
+        let decrypt_start = std::time::Instant::now();
+        encrypted.decrypt_with_key(&key)?;
+        let decrypt_duration = decrypt_start.elapsed();
+
+        Ok(PerformanceBenchmark {
+            profile: self.performance_profile,
+            key_derivation_ms: derive_duration.as_micros() as f64 / 1000.0,
+            encryption_ms: encrypt_duration.as_micros() as f64 / 1000.0,
+            decryption_ms: decrypt_duration.as_micros() as f64 / 1000.0,
+            total_ms: start_time.elapsed().as_micros() as f64 / 1000.0,
+            data_size,
+        })
+    }
+
+    /// Pick Argon2id parameters that take about `budget` on this machine
+    ///
+    /// A probe derivation at the minimum cost measures the host; memory is
+    /// then scaled up to 1 GiB (or the configured KDF memory limit) and, once
+    /// memory is capped, the number of passes. Parameters never go below the
+    /// OWASP minimum of 19 MiB with two passes, so on slow hosts derivation
+    /// can take longer than the budget.
+    pub fn calibrate(budget: Duration) -> CryptoResult<Calibration> {
+        const MIN_MEMORY_KIB: u32 = 19 * 1024;
+        const MAX_MEMORY_KIB: u32 = 1024 * 1024;
+        const MIN_PASSES: u32 = 2;
+        const MAX_PASSES: u32 = 10;
+
+        let lanes = std::thread::available_parallelism().map_or(1, |n| n.get()).min(4) as u32;
+        let max_memory = super::limits::kdf_memory_limit()
+            .map_or(MAX_MEMORY_KIB, |limit| limit.min(u64::from(MAX_MEMORY_KIB)) as u32)
+            .max(MIN_MEMORY_KIB);
+
+        let salt = [0u8; defaults::SALT_LENGTH];
+        let time = |kdf: &Kdf| -> CryptoResult<Duration> {
+            let start = std::time::Instant::now();
+            DerivedKey::from_password_with_kdf("cargocrypt-calibration", &salt, kdf)?;
+            Ok(start.elapsed())
+        };
+
+        let probe = time(&Kdf::Argon2idCustom { m_cost: MIN_MEMORY_KIB, t_cost: MIN_PASSES, p_cost: lanes })?;
+
+        // Argon2 run time grows linearly with memory times passes
+        let work = budget.as_secs_f64() / probe.as_secs_f64().max(1e-6) * f64::from(MIN_MEMORY_KIB * MIN_PASSES);
+        let m_cost = ((work / f64::from(MIN_PASSES)) as u64).clamp(u64::from(MIN_MEMORY_KIB), u64::from(max_memory)) as u32;
+        // Whole MiB keep the numbers readable in config files
+        let m_cost = (m_cost / 1024 * 1024).max(MIN_MEMORY_KIB);
+        let t_cost = ((work / f64::from(m_cost)) as u64).clamp(u64::from(MIN_PASSES), u64::from(MAX_PASSES)) as u32;
+
+        let kdf = Kdf::Argon2idCustom { m_cost, t_cost, p_cost: lanes };
+        let elapsed = time(&kdf)?;
+
+        Ok(Calibration { kdf, elapsed, budget })
+    }
+
     /// Encrypt a file with derived key using resilience protection
     pub async fn encrypt_file<P: AsRef<std::path::Path>>(
         &self,
@@ -696,8 +1039,19 @@ impl Default for CryptoEngine {
     }
 }
 
+/// Argon2 parameters picked by [`CryptoEngine::calibrate`]
+#[derive(Debug, Clone, Copy)]
+pub struct Calibration {
+    /// Key derivation function with the chosen parameters
+    pub kdf: Kdf,
+    /// Time one derivation took with these parameters
+    pub elapsed: Duration,
+    /// Time budget calibration aimed for
+    pub budget: Duration,
+}
+
 /// Performance benchmark results
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct PerformanceBenchmark {
     /// Performance profile used
     pub profile: PerformanceProfile,
@@ -740,20 +1094,20 @@ impl PerformanceBenchmark {
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_crypto_engine_basic_operations() {
+    #[tokio::test]
+    async fn test_crypto_engine_basic_operations() {
         let engine = CryptoEngine::new();
         let plaintext = "Hello, World!";
         let password = "test_password";
         
-        let encrypted = engine.encrypt_string(plaintext, password, EncryptionOptions::new()).unwrap();
+        let encrypted = engine.encrypt_string(plaintext, password, EncryptionOptions::new()).await.unwrap();
         let decrypted = engine.decrypt_to_string(&encrypted, password).unwrap();
         
         assert_eq!(plaintext, decrypted);
     }
 
-    #[test]
-    fn test_performance_profiles() {
+    #[tokio::test]
+    async fn test_performance_profiles() {
         let profiles = [
             PerformanceProfile::Fast,
             PerformanceProfile::Balanced,
@@ -766,15 +1120,15 @@ mod tests {
             let plaintext = "Test data";
             let password = "test_password";
             
-            let encrypted = engine.encrypt_string(plaintext, password, EncryptionOptions::new()).unwrap();
+            let encrypted = engine.encrypt_string(plaintext, password, EncryptionOptions::new()).await.unwrap();
             let decrypted = engine.decrypt_to_string(&encrypted, password).unwrap();
             
             assert_eq!(plaintext, decrypted);
         }
     }
 
-    #[test]
-    fn test_batch_encryption() {
+    #[tokio::test]
+    async fn test_batch_encryption() {
         let engine = CryptoEngine::new();
         let secrets = vec![
             ("api_key".to_string(), "sk-1234567890"),
@@ -783,7 +1137,7 @@ mod tests {
         ];
         
         let password = "master_password";
-        let result = engine.encrypt_batch(secrets, password, EncryptionOptions::new());
+        let result = engine.encrypt_batch(secrets, password, EncryptionOptions::new()).await;
         
         assert_eq!(result.successes.len(), 3);
         assert_eq!(result.failures.len(), 0);
@@ -796,14 +1150,14 @@ mod tests {
         }
     }
 
-    #[test]
-    fn test_password_change() {
+    #[tokio::test]
+    async fn test_password_change() {
         let engine = CryptoEngine::new();
         let plaintext = "Secret data";
         let old_password = "old_password";
         let new_password = "new_password";
         
-        let encrypted = engine.encrypt_string(plaintext, old_password, EncryptionOptions::new()).unwrap();
+        let encrypted = engine.encrypt_string(plaintext, old_password, EncryptionOptions::new()).await.unwrap();
         let reencrypted = engine.change_password(&encrypted, old_password, new_password).unwrap();
         
         // Old password should not work
@@ -827,10 +1181,17 @@ mod tests {
         let decrypted = engine.decrypt_direct(&ciphertext, &key, &nonce).unwrap();
         
         assert_eq!(plaintext, decrypted.as_slice());
+
+        // Same key and nonce: the same message again is harmless, another is refused
+        assert_eq!(engine.encrypt_direct(plaintext, &key, &nonce).unwrap(), ciphertext);
+        assert!(matches!(
+            engine.encrypt_direct(b"A different message", &key, &nonce),
+            Err(CryptoError::NonceReuse)
+        ));
     }
 
-    #[test]
-    fn test_encryption_options() {
+    #[tokio::test]
+    async fn test_encryption_options() {
         let engine = CryptoEngine::new();
         let plaintext = "Test with options";
         let password = "test_password";
@@ -840,7 +1201,7 @@ mod tests {
             .with_type(SecretType::ApiKey)
             .with_performance_profile(PerformanceProfile::Fast);
         
-        let encrypted = engine.encrypt_string(plaintext, password, options).unwrap();
+        let encrypted = engine.encrypt_string(plaintext, password, options).await.unwrap();
         let metadata = encrypted.metadata();
         
         assert_eq!(metadata.description.as_ref().unwrap(), "Test secret");
@@ -850,6 +1211,171 @@ mod tests {
         assert_eq!(plaintext, decrypted);
     }
 
+    #[tokio::test]
+    async fn test_post_quantum_option() {
+        let engine = CryptoEngine::with_performance_profile(PerformanceProfile::Fast);
+        let password = "test_password";
+        let recipient = HybridKeyPair::generate();
+
+        let options = EncryptionOptions::new().post_quantum(true).with_recipient(recipient.public_key());
+        let encrypted = engine.encrypt_string("PQ data", password, options).await.unwrap();
+        let encapsulation = encrypted.post_quantum().unwrap();
+        assert_eq!(encapsulation.recipient, recipient.public_key().fingerprint());
+
+        let encrypted = EncryptedSecret::from_bytes(&encrypted.to_bytes().unwrap()).unwrap();
+        let decrypted = engine.decrypt_with_identity(&encrypted, &recipient, Some(password), None).unwrap();
+        assert_eq!(decrypted.as_bytes(), b"PQ data");
+        assert!(engine.decrypt(&encrypted, password).is_err());
+        assert!(engine.decrypt_with_identity(&encrypted, &recipient, Some("wrong_password"), None).is_err());
+        assert!(engine.decrypt_with_identity(&encrypted, &HybridKeyPair::generate(), Some(password), None).is_err());
+
+        let no_recipient = EncryptionOptions::new().post_quantum(true);
+        assert!(engine.encrypt_string("PQ data", password, no_recipient).await.is_err());
+        let classic = engine.encrypt_string("PQ data", password, EncryptionOptions::new()).await.unwrap();
+        assert!(classic.post_quantum().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_keyfile_encryption() {
+        let engine = CryptoEngine::with_performance_profile(PerformanceProfile::Fast);
+        let keyfile = Keyfile::generate().unwrap();
+        let plaintext = || PlaintextSecret::from_string("CI token".to_string());
+
+        let encrypted = engine
+            .encrypt_with_keyfile(plaintext(), &keyfile, None, EncryptionOptions::new())
+            .await
+            .unwrap();
+        assert_eq!(encrypted.key_source(), KeySource::Keyfile);
+        let decrypted = engine.decrypt_with_keyfile(&encrypted, &keyfile, None).unwrap();
+        assert_eq!(decrypted.as_string().unwrap(), "CI token");
+
+        let mixed = engine
+            .encrypt_with_keyfile(plaintext(), &keyfile, Some("test_password"), EncryptionOptions::new())
+            .await
+            .unwrap();
+        assert_eq!(mixed.key_source(), KeySource::KeyfileAndPassword);
+        assert!(engine.decrypt_with_keyfile(&mixed, &keyfile, None).is_err());
+        assert!(engine.decrypt_with_keyfile(&mixed, &keyfile, Some("wrong_password")).is_err());
+        let decrypted = engine.decrypt_with_keyfile(&mixed, &keyfile, Some("test_password")).unwrap();
+        assert_eq!(decrypted.as_string().unwrap(), "CI token");
+
+        // Deterministic keyfile encryption, as used by the git clean filter
+        let deterministic = EncryptionOptions::new().with_salt([7u8; defaults::SALT_LENGTH]).deterministic("ci.env");
+        let first = engine.encrypt_with_keyfile(plaintext(), &keyfile, None, deterministic.clone()).await.unwrap();
+        let second = engine.encrypt_with_keyfile(plaintext(), &keyfile, None, deterministic).await.unwrap();
+        assert_eq!(first.to_bytes().unwrap(), second.to_bytes().unwrap());
+        let decrypted = engine.decrypt_with_keyfile(&first, &keyfile, None).unwrap();
+        assert_eq!(decrypted.as_string().unwrap(), "CI token");
+    }
+
+    #[tokio::test]
+    async fn test_alternative_kdf() {
+        let kdf = Kdf::Pbkdf2Sha256 { iterations: 1_000 };
+        let engine = CryptoEngine::with_performance_profile(PerformanceProfile::Fast).with_kdf(kdf);
+        let plaintext = PlaintextSecret::from_string("interop secret".to_string());
+
+        let encrypted = engine.encrypt(plaintext, "test_password", EncryptionOptions::new()).await.unwrap();
+        let parsed = EncryptedSecret::from_bytes(&encrypted.to_bytes().unwrap()).unwrap();
+        assert_eq!(parsed.kdf(), &kdf);
+
+        // Any engine decrypts using the KDF from the header
+        let default_engine = CryptoEngine::new();
+        assert_eq!(default_engine.decrypt(&parsed, "test_password").unwrap().as_string().unwrap(), "interop secret");
+        assert!(default_engine.decrypt(&parsed, "wrong_password").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_calibrate() {
+        let calibration = CryptoEngine::calibrate(std::time::Duration::from_millis(50)).unwrap();
+        let Kdf::Argon2idCustom { m_cost, t_cost, p_cost } = calibration.kdf else {
+            panic!("calibration should pick Argon2id parameters, got {}", calibration.kdf);
+        };
+        assert!(m_cost >= 19 * 1024 && t_cost >= 2 && p_cost >= 1);
+        assert!(!calibration.kdf.is_weak());
+
+        let engine = CryptoEngine::new().with_kdf(calibration.kdf);
+        let plaintext = PlaintextSecret::from_string("calibrated secret".to_string());
+        let encrypted = engine.encrypt(plaintext, "test_password", EncryptionOptions::new()).await.unwrap();
+        let parsed = EncryptedSecret::from_bytes(&encrypted.to_bytes().unwrap()).unwrap();
+        assert_eq!(parsed.kdf(), &calibration.kdf);
+        assert_eq!(CryptoEngine::new().decrypt(&parsed, "test_password").unwrap().as_string().unwrap(), "calibrated secret");
+    }
+
+    #[tokio::test]
+    async fn test_key_cache() {
+        let cache = Arc::new(crate::crypto::MemoryKeyCache::new(std::time::Duration::from_secs(60)));
+        let engine = CryptoEngine::with_performance_profile(PerformanceProfile::Fast).with_key_cache(cache.clone());
+        let plaintext = || PlaintextSecret::from_string("cached secret".to_string());
+
+        let first = engine.encrypt(plaintext(), "test_password", EncryptionOptions::new()).await.unwrap();
+        let second = engine.encrypt(plaintext(), "test_password", EncryptionOptions::new()).await.unwrap();
+        assert_eq!(first.salt(), second.salt(), "cached derivation should be reused");
+        assert_eq!(cache.len(), 1);
+
+        // A fresh engine without the cache must still decrypt
+        let uncached = CryptoEngine::with_performance_profile(PerformanceProfile::Fast);
+        assert_eq!(uncached.decrypt(&second, "test_password").unwrap().as_string().unwrap(), "cached secret");
+
+        // Wrong passwords never populate the cache
+        cache.clear();
+        assert!(engine.decrypt(&first, "wrong_password").is_err());
+        assert!(cache.is_empty());
+        assert_eq!(engine.decrypt(&first, "test_password").unwrap().as_string().unwrap(), "cached secret");
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_deterministic_option() {
+        let engine = CryptoEngine::with_performance_profile(PerformanceProfile::Fast);
+        let password = "test_password";
+
+        let salt = [7u8; defaults::SALT_LENGTH];
+
+        let first = engine.encrypt_data_deterministic(b"tracked file", password, salt, "a.env").await.unwrap();
+        let second = engine.encrypt_data_deterministic(b"tracked file", password, salt, "a.env").await.unwrap();
+        assert_eq!(first.to_bytes().unwrap(), second.to_bytes().unwrap());
+        assert_eq!(first.salt(), &salt);
+        assert_eq!(engine.decrypt_data(&first, password).unwrap(), b"tracked file");
+
+        let changed = engine.encrypt_data_deterministic(b"tracked file!", password, salt, "a.env").await.unwrap();
+        assert_ne!(first.to_bytes().unwrap(), changed.to_bytes().unwrap());
+        let moved = engine.encrypt_data_deterministic(b"tracked file", password, salt, "b.env").await.unwrap();
+        assert_ne!(first.nonce(), moved.nonce());
+
+        // The salt is never derived from the password or plaintext
+        let options = EncryptionOptions::new().deterministic("a.env");
+        assert!(engine.encrypt_bytes(b"tracked file", password, options).await.is_err());
+
+        let random = engine.encrypt_data(b"tracked file", password).await.unwrap();
+        assert_ne!(first.to_bytes().unwrap(), random.to_bytes().unwrap());
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let engine = CryptoEngine::new();
+        let key = SigningKeyPair::generate();
+
+        let signature = engine.sign(b"ciphertext bytes", &key);
+        assert!(engine.verify(b"ciphertext bytes", &signature, None).is_ok());
+        assert!(engine.verify(b"ciphertext bytes", &signature, Some(&key.public_key())).is_ok());
+        assert!(matches!(
+            engine.verify(b"other bytes", &signature, None),
+            Err(CryptoError::InvalidSignature { .. })
+        ));
+    }
+
+    #[test]
+    fn test_benchmark_with_data_size() {
+        let engine = CryptoEngine::with_performance_profile(PerformanceProfile::Fast);
+        let benchmark = engine.benchmark_with_data_size(256 * 1024).unwrap();
+
+        assert_eq!(benchmark.profile, PerformanceProfile::Fast);
+        assert_eq!(benchmark.data_size, 256 * 1024);
+        assert!(benchmark.key_derivation_ms > 0.0);
+        assert!(benchmark.encryption_throughput_mbps() > 0.0);
+        assert!(benchmark.total_ms >= benchmark.key_derivation_ms);
+    }
+
     #[test]
     fn test_performance_benchmark() {
         let engine = CryptoEngine::new();
@@ -872,14 +1398,14 @@ mod tests {
         assert!(benchmark.total_ms > 0.0);
     }
 
-    #[test]
-    fn test_wrong_password_fails() {
+    #[tokio::test]
+    async fn test_wrong_password_fails() {
         let engine = CryptoEngine::new();
         let plaintext = "Secret data";
         let password = "correct_password";
         let wrong_password = "wrong_password";
         
-        let encrypted = engine.encrypt_string(plaintext, password, EncryptionOptions::new()).unwrap();
+        let encrypted = engine.encrypt_string(plaintext, password, EncryptionOptions::new()).await.unwrap();
         
         assert!(!engine.verify_password(&encrypted, wrong_password));
         
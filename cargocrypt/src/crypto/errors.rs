@@ -28,6 +28,10 @@ pub enum CryptoError {
     #[error("Invalid nonce: {reason}")]
     InvalidNonce { reason: String },
 
+    /// A key and nonce were about to encrypt a second, different message
+    #[error("Refused to reuse a nonce with the same key for a different message")]
+    NonceReuse,
+
     /// Invalid salt format or length
     #[error("Invalid salt: {reason}")]
     InvalidSalt { reason: String },
@@ -48,6 +52,10 @@ pub enum CryptoError {
     #[error("Authentication failed - data may have been tampered with")]
     AuthenticationFailed,
 
+    /// Signature verification failed
+    #[error("Invalid signature: {reason}")]
+    InvalidSignature { reason: String },
+
     /// Generic cryptographic error
     #[error("Cryptographic operation failed: {message}")]
     Generic { message: String },
@@ -117,6 +125,13 @@ impl CryptoError {
         }
     }
 
+    /// Create an invalid signature error
+    pub fn invalid_signature<S: Into<String>>(reason: S) -> Self {
+        Self::InvalidSignature {
+            reason: reason.into(),
+        }
+    }
+
     /// Create a generic error
     pub fn generic<S: Into<String>>(message: S) -> Self {
         Self::Generic {
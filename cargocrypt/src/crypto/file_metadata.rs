@@ -0,0 +1,284 @@
+//! Filesystem metadata carried through encryption
+//!
+//! When `file_ops.preserve_metadata` is set, the modification time and
+//! permissions of the original file are recorded in the encrypted file's
+//! header and put back on decrypt, so a round trip does not invalidate build
+//! caches or loosen a `0600` config. Extended attributes in the `user.`
+//! namespace can be carried as well (Linux only).
+//!
+//! The header is stored in the clear and is not authenticated, so restoring
+//! is deliberately conservative: only the permission bits are applied, never
+//! setuid, setgid or sticky, and only `user.` attributes are written back.
+
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Permission bits that are restored; setuid, setgid and sticky are dropped
+const PERMISSION_BITS: u32 = 0o777;
+
+/// Only extended attributes in this namespace are captured and restored
+const XATTR_NAMESPACE: &str = "user.";
+
+/// Metadata of the original file, recorded in the encrypted file's header
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileMetadata {
+    /// Modification time, whole seconds since the Unix epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified_secs: Option<u64>,
+    /// Sub-second part of the modification time
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub modified_nanos: u32,
+    /// Unix permission bits
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<u32>,
+    /// Read-only flag, used where Unix permissions are not available
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub readonly: bool,
+    /// Extended attributes in the `user.` namespace
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub xattrs: Vec<ExtendedAttribute>,
+}
+
+/// A single extended attribute
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExtendedAttribute {
+    /// Full attribute name, e.g. `user.mime_type`
+    pub name: String,
+    /// Raw attribute value
+    #[serde(with = "hex")]
+    pub value: Vec<u8>,
+}
+
+fn is_zero(value: &u32) -> bool {
+    *value == 0
+}
+
+impl FileMetadata {
+    /// Record the metadata of the file at `path`, including `user.` extended
+    /// attributes when `xattrs` is set
+    pub fn capture<P: AsRef<Path>>(path: P, xattrs: bool) -> io::Result<Self> {
+        let path = path.as_ref();
+        let metadata = std::fs::metadata(path)?;
+
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok());
+
+        Ok(Self {
+            modified_secs: modified.map(|modified| modified.as_secs()),
+            modified_nanos: modified.map(|modified| modified.subsec_nanos()).unwrap_or(0),
+            mode: unix_mode(&metadata),
+            readonly: metadata.permissions().readonly(),
+            xattrs: if xattrs { xattr::list(path)? } else { Vec::new() },
+        })
+    }
+
+    /// Modification time, if one was recorded
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.modified_secs
+            .map(|secs| UNIX_EPOCH + Duration::new(secs, self.modified_nanos.min(999_999_999)))
+    }
+
+    /// Apply the recorded metadata to the file at `path`
+    ///
+    /// The modification time is set before the permissions, since a
+    /// read-only file can no longer be opened to change it.
+    pub fn restore<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+
+        for attribute in &self.xattrs {
+            if attribute.name.starts_with(XATTR_NAMESPACE) {
+                xattr::set(path, attribute)?;
+            }
+        }
+
+        if let Some(modified) = self.modified() {
+            let file = std::fs::OpenOptions::new().write(true).open(path)?;
+            file.set_modified(modified)?;
+        }
+
+        let mut permissions = std::fs::metadata(path)?.permissions();
+        match self.mode {
+            Some(mode) => set_unix_mode(&mut permissions, mode & PERMISSION_BITS),
+            None => permissions.set_readonly(self.readonly),
+        }
+        std::fs::set_permissions(path, permissions)
+    }
+}
+
+#[cfg(unix)]
+fn unix_mode(metadata: &std::fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(metadata.permissions().mode() & PERMISSION_BITS)
+}
+
+#[cfg(not(unix))]
+fn unix_mode(_metadata: &std::fs::Metadata) -> Option<u32> {
+    None
+}
+
+#[cfg(unix)]
+fn set_unix_mode(permissions: &mut std::fs::Permissions, mode: u32) {
+    use std::os::unix::fs::PermissionsExt;
+    permissions.set_mode(mode);
+}
+
+#[cfg(not(unix))]
+fn set_unix_mode(permissions: &mut std::fs::Permissions, mode: u32) {
+    // Without Unix permissions, a mode without any write bit means read-only
+    permissions.set_readonly(mode & 0o222 == 0);
+}
+
+#[cfg(target_os = "linux")]
+mod xattr {
+    use super::{ExtendedAttribute, XATTR_NAMESPACE};
+    use std::ffi::{CStr, CString};
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    fn c_path(path: &Path) -> io::Result<CString> {
+        CString::new(path.as_os_str().as_bytes())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "path contains a NUL byte"))
+    }
+
+    /// Call a size-probing xattr function twice: once for the size, once for the data
+    fn read_sized(mut call: impl FnMut(*mut libc::c_void, usize) -> isize) -> io::Result<Vec<u8>> {
+        loop {
+            let size = call(std::ptr::null_mut(), 0);
+            if size < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mut buffer = vec![0u8; size as usize];
+            let read = call(buffer.as_mut_ptr().cast(), buffer.len());
+            if read >= 0 {
+                buffer.truncate(read as usize);
+                return Ok(buffer);
+            }
+            let error = io::Error::last_os_error();
+            // The attribute grew between the two calls; try again
+            if error.raw_os_error() != Some(libc::ERANGE) {
+                return Err(error);
+            }
+        }
+    }
+
+    fn is_unsupported(error: &io::Error) -> bool {
+        error.raw_os_error() == Some(libc::ENOTSUP)
+    }
+
+    pub fn list(path: &Path) -> io::Result<Vec<ExtendedAttribute>> {
+        let path = c_path(path)?;
+        // SAFETY: `path` is NUL-terminated and the buffer pointer/size pair is valid
+        let names = match read_sized(|buffer, size| unsafe { libc::listxattr(path.as_ptr(), buffer.cast(), size) }) {
+            Ok(names) => names,
+            Err(e) if is_unsupported(&e) => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let mut attributes = Vec::new();
+        for name in names.split(|byte| *byte == 0).filter(|name| !name.is_empty()) {
+            let name = CString::new(name).expect("split on NUL");
+            let Ok(display_name) = name.to_str() else { continue };
+            if !display_name.starts_with(XATTR_NAMESPACE) {
+                continue;
+            }
+            let name_ptr: &CStr = &name;
+            // SAFETY: both strings are NUL-terminated and the buffer pointer/size pair is valid
+            let value = read_sized(|buffer, size| unsafe {
+                libc::getxattr(path.as_ptr(), name_ptr.as_ptr(), buffer, size)
+            })?;
+            attributes.push(ExtendedAttribute { name: display_name.to_string(), value });
+        }
+        Ok(attributes)
+    }
+
+    pub fn set(path: &Path, attribute: &ExtendedAttribute) -> io::Result<()> {
+        let path = c_path(path)?;
+        let name = CString::new(attribute.name.as_bytes())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "attribute name contains a NUL byte"))?;
+        // SAFETY: both strings are NUL-terminated and the value slice is valid for its length
+        let result = unsafe {
+            libc::setxattr(path.as_ptr(), name.as_ptr(), attribute.value.as_ptr().cast(), attribute.value.len(), 0)
+        };
+        if result < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod xattr {
+    use super::ExtendedAttribute;
+    use std::io;
+    use std::path::Path;
+
+    pub fn list(_path: &Path) -> io::Result<Vec<ExtendedAttribute>> {
+        Ok(Vec::new())
+    }
+
+    pub fn set(_path: &Path, _attribute: &ExtendedAttribute) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_capture_and_restore() {
+        let temp_dir = TempDir::new().unwrap();
+        let original = temp_dir.path().join("original.toml");
+        let restored = temp_dir.path().join("restored.toml");
+        std::fs::write(&original, "key = 1").unwrap();
+        std::fs::write(&restored, "key = 1").unwrap();
+
+        let mtime = UNIX_EPOCH + Duration::new(1_600_000_000, 123_456_789);
+        std::fs::File::options().write(true).open(&original).unwrap().set_modified(mtime).unwrap();
+        let mut permissions = std::fs::metadata(&original).unwrap().permissions();
+        set_unix_mode(&mut permissions, 0o400);
+        std::fs::set_permissions(&original, permissions).unwrap();
+
+        let metadata = FileMetadata::capture(&original, true).unwrap();
+        assert_eq!(metadata.modified(), Some(mtime));
+        metadata.restore(&restored).unwrap();
+
+        let restored_metadata = std::fs::metadata(&restored).unwrap();
+        assert_eq!(restored_metadata.modified().unwrap(), mtime);
+        assert!(restored_metadata.permissions().readonly());
+        assert_eq!(unix_mode(&restored_metadata), metadata.mode);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_restore_drops_special_bits() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("script.sh");
+        std::fs::write(&path, "#!/bin/sh").unwrap();
+
+        let metadata = FileMetadata { mode: Some(0o4755), ..Default::default() };
+        metadata.restore(&path).unwrap();
+        assert_eq!(unix_mode(&std::fs::metadata(&path).unwrap()), Some(0o755));
+    }
+
+    #[test]
+    fn test_serialization_skips_empty_fields() {
+        let json = serde_json::to_string(&FileMetadata::default()).unwrap();
+        assert_eq!(json, "{}");
+
+        let metadata = FileMetadata {
+            modified_secs: Some(1),
+            xattrs: vec![ExtendedAttribute { name: "user.tag".to_string(), value: b"ok".to_vec() }],
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&metadata).unwrap();
+        assert!(json.contains("\"value\":\"6f6b\""));
+        assert_eq!(serde_json::from_str::<FileMetadata>(&json).unwrap(), metadata);
+    }
+}
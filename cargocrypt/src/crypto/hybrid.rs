@@ -0,0 +1,341 @@
+//! Post-quantum hybrid recipients (X25519 + ML-KEM-768)
+//!
+//! For secrets that must stay confidential for a long time, the key derived
+//! from the password (or keyfile) can additionally be bound to a recipient's
+//! hybrid key pair. Encryption encapsulates two fresh shared secrets to the
+//! recipient's public key:
+//! - an X25519 Diffie-Hellman secret (classical security)
+//! - an ML-KEM-768 (Kyber) encapsulated secret (post-quantum security)
+//!
+//! Both are mixed into the file key with HKDF-SHA256, so decrypting needs the
+//! password *and* the recipient's secret key, and recorded ciphertext stays
+//! protected even if X25519 falls to a quantum computer. The ephemeral X25519
+//! public key, the ML-KEM ciphertext and the recipient's fingerprint are
+//! recorded in the file header.
+//!
+//! The secret key is kept as its 96-byte seed (X25519 secret, then the ML-KEM
+//! `d` and `z` seeds) in hex; the public key is shared as base64.
+
+use crate::crypto::{CryptoError, CryptoResult, DerivedKey, defaults};
+use base64ct::{Base64, Encoding};
+use chacha20poly1305::Key;
+use hkdf::Hkdf;
+use ml_kem::kem::{Decapsulate, Encapsulate};
+use ml_kem::{array::Array, EncodedSizeUser, KemCore, MlKem768, B32};
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::Path;
+use x25519_dalek::{EphemeralSecret, PublicKey, StaticSecret};
+use zeroize::{Zeroize, Zeroizing};
+
+/// HKDF info string for combining the shared secrets into the file key
+const COMBINE_INFO: &[u8] = b"cargocrypt-pq-hybrid-v2";
+
+/// Length of an X25519 public key in bytes
+pub const X25519_PUBLIC_KEY_LENGTH: usize = 32;
+
+/// Length of an ML-KEM-768 encapsulation (public) key in bytes
+pub const ML_KEM_768_PUBLIC_KEY_LENGTH: usize = 1184;
+
+/// Length of an ML-KEM-768 ciphertext in bytes
+pub const ML_KEM_768_CIPHERTEXT_LENGTH: usize = 1088;
+
+/// Length of a hybrid secret key seed in bytes
+pub const SECRET_KEY_LENGTH: usize = 96;
+
+type MlKemDecapsulationKey = <MlKem768 as KemCore>::DecapsulationKey;
+type MlKemEncapsulationKey = <MlKem768 as KemCore>::EncapsulationKey;
+
+/// Hybrid KEM data recorded in the header of a post-quantum encrypted secret
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HybridEncapsulation {
+    /// Fingerprint of the recipient's public key
+    pub recipient: String,
+    /// Ephemeral X25519 public key used for the classical exchange
+    #[serde(with = "hex")]
+    pub x25519_ephemeral: [u8; X25519_PUBLIC_KEY_LENGTH],
+    /// ML-KEM-768 ciphertext carrying the post-quantum shared secret
+    #[serde(with = "hex")]
+    pub ml_kem_ciphertext: Vec<u8>,
+}
+
+/// Public half of a hybrid key pair, which secrets are encrypted for
+#[derive(Clone, PartialEq, Eq)]
+pub struct HybridPublicKey {
+    x25519: [u8; X25519_PUBLIC_KEY_LENGTH],
+    ml_kem: Vec<u8>,
+}
+
+impl HybridPublicKey {
+    /// Parse a public key from [`Self::to_base64`]
+    pub fn from_base64(encoded: &str) -> CryptoResult<Self> {
+        let bytes = Base64::decode_vec(encoded.trim())
+            .map_err(|_| CryptoError::invalid_key("Hybrid public key is not valid base64"))?;
+        if bytes.len() != X25519_PUBLIC_KEY_LENGTH + ML_KEM_768_PUBLIC_KEY_LENGTH {
+            return Err(CryptoError::invalid_key(format!(
+                "Hybrid public key must be {} bytes, got {}",
+                X25519_PUBLIC_KEY_LENGTH + ML_KEM_768_PUBLIC_KEY_LENGTH,
+                bytes.len()
+            )));
+        }
+        let (x25519, ml_kem) = bytes.split_at(X25519_PUBLIC_KEY_LENGTH);
+        Ok(Self {
+            x25519: x25519.try_into().expect("split at the X25519 key length"),
+            ml_kem: ml_kem.to_vec(),
+        })
+    }
+
+    /// Encode as base64: the X25519 key followed by the ML-KEM key
+    pub fn to_base64(&self) -> String {
+        Base64::encode_string(&[self.x25519.as_slice(), &self.ml_kem].concat())
+    }
+
+    /// Short identifier of the key, recorded in the header of files encrypted for it
+    pub fn fingerprint(&self) -> String {
+        let digest = Sha256::new().chain_update(self.x25519).chain_update(&self.ml_kem).finalize();
+        hex::encode(&digest[..8])
+    }
+
+    /// Encapsulate fresh shared secrets for this recipient and bind `base` to them
+    ///
+    /// Returns the key to encrypt with and the encapsulation to record in the header.
+    pub fn encapsulate(&self, base: &DerivedKey) -> CryptoResult<(DerivedKey, HybridEncapsulation)> {
+        let ephemeral = EphemeralSecret::random_from_rng(OsRng);
+        let ephemeral_public = PublicKey::from(&ephemeral);
+        let classical = ephemeral.diffie_hellman(&PublicKey::from(self.x25519));
+        if !classical.was_contributory() {
+            return Err(CryptoError::invalid_key("Recipient X25519 key is a low-order point"));
+        }
+
+        let encoded = Array::try_from(self.ml_kem.as_slice()).expect("length checked on parsing");
+        let (ciphertext, post_quantum) = MlKemEncapsulationKey::from_bytes(&encoded)
+            .encapsulate(&mut OsRng)
+            .map_err(|_| CryptoError::key_derivation("ML-KEM encapsulation failed"))?;
+
+        let encapsulation = HybridEncapsulation {
+            recipient: self.fingerprint(),
+            x25519_ephemeral: *ephemeral_public.as_bytes(),
+            ml_kem_ciphertext: ciphertext.to_vec(),
+        };
+        let key = combine(base, classical.as_bytes(), post_quantum.as_slice(), &encapsulation)?;
+        Ok((key, encapsulation))
+    }
+}
+
+impl std::fmt::Debug for HybridPublicKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HybridPublicKey").field("fingerprint", &self.fingerprint()).finish()
+    }
+}
+
+/// A recipient's X25519 + ML-KEM-768 key pair (secret seeds are zeroized on drop)
+#[derive(Clone)]
+pub struct HybridKeyPair {
+    x25519: StaticSecret,
+    ml_kem_seed: Zeroizing<[u8; 64]>,
+}
+
+impl HybridKeyPair {
+    /// Generate a new random key pair
+    pub fn generate() -> Self {
+        let mut ml_kem_seed = Zeroizing::new([0u8; 64]);
+        OsRng.fill_bytes(ml_kem_seed.as_mut_slice());
+        Self {
+            x25519: StaticSecret::random_from_rng(OsRng),
+            ml_kem_seed,
+        }
+    }
+
+    /// Restore a key pair from its 96-byte secret seed
+    pub fn from_secret_bytes(bytes: &[u8]) -> CryptoResult<Self> {
+        if bytes.len() != SECRET_KEY_LENGTH {
+            return Err(CryptoError::invalid_key(format!(
+                "Hybrid secret key must be {} bytes, got {}",
+                SECRET_KEY_LENGTH,
+                bytes.len()
+            )));
+        }
+        let mut x25519: [u8; 32] = bytes[..32].try_into().expect("length checked above");
+        let mut ml_kem_seed = Zeroizing::new([0u8; 64]);
+        ml_kem_seed.copy_from_slice(&bytes[32..]);
+        let pair = Self {
+            x25519: StaticSecret::from(x25519),
+            ml_kem_seed,
+        };
+        x25519.zeroize();
+        Ok(pair)
+    }
+
+    /// Get the 96-byte secret seed (handle with care)
+    pub fn secret_bytes(&self) -> Zeroizing<[u8; SECRET_KEY_LENGTH]> {
+        let mut bytes = Zeroizing::new([0u8; SECRET_KEY_LENGTH]);
+        bytes[..32].copy_from_slice(self.x25519.as_bytes());
+        bytes[32..].copy_from_slice(self.ml_kem_seed.as_slice());
+        bytes
+    }
+
+    /// Get the public key to encrypt for
+    pub fn public_key(&self) -> HybridPublicKey {
+        let (_, ml_kem) = self.ml_kem_keys();
+        HybridPublicKey {
+            x25519: *PublicKey::from(&self.x25519).as_bytes(),
+            ml_kem: ml_kem.as_bytes().to_vec(),
+        }
+    }
+
+    /// Load a key pair saved with [`HybridKeyPair::save`]
+    pub fn load<P: AsRef<Path>>(path: P) -> CryptoResult<Self> {
+        let path = path.as_ref();
+        let content = Zeroizing::new(std::fs::read_to_string(path).map_err(|e| {
+            CryptoError::invalid_key(format!("Failed to read hybrid identity {}: {}", path.display(), e))
+        })?);
+        let bytes = hex::decode(content.trim())
+            .map(Zeroizing::new)
+            .map_err(|_| CryptoError::invalid_key(format!("{} is not a hex hybrid identity", path.display())))?;
+        Self::from_secret_bytes(&bytes)
+    }
+
+    /// Save the secret seed as hex, readable only by the owner
+    ///
+    /// Fails if the file already exists.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> CryptoResult<()> {
+        let path = path.as_ref();
+        let io_error = |e: std::io::Error| {
+            CryptoError::invalid_key(format!("Failed to write hybrid identity {}: {}", path.display(), e))
+        };
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).map_err(io_error)?;
+        }
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut encoded = Zeroizing::new(hex::encode(self.secret_bytes().as_slice()));
+        encoded.push('\n');
+        options.open(path).and_then(|mut file| file.write_all(encoded.as_bytes())).map_err(io_error)
+    }
+
+    /// Recover the key that [`HybridPublicKey::encapsulate`] bound `base` to
+    pub fn decapsulate(&self, base: &DerivedKey, encapsulation: &HybridEncapsulation) -> CryptoResult<DerivedKey> {
+        let fingerprint = self.public_key().fingerprint();
+        if encapsulation.recipient != fingerprint {
+            return Err(CryptoError::invalid_key(format!(
+                "Secret was encrypted for hybrid key {}, not {}",
+                encapsulation.recipient, fingerprint
+            )));
+        }
+
+        let classical = self.x25519.diffie_hellman(&PublicKey::from(encapsulation.x25519_ephemeral));
+        if !classical.was_contributory() {
+            return Err(CryptoError::decryption("X25519 exchange produced a non-contributory secret"));
+        }
+
+        let ciphertext = Array::try_from(encapsulation.ml_kem_ciphertext.as_slice()).map_err(|_| {
+            CryptoError::decryption(format!(
+                "ML-KEM ciphertext must be {} bytes, got {}",
+                ML_KEM_768_CIPHERTEXT_LENGTH,
+                encapsulation.ml_kem_ciphertext.len()
+            ))
+        })?;
+        let (decapsulation_key, _) = self.ml_kem_keys();
+        let post_quantum = decapsulation_key
+            .decapsulate(&ciphertext)
+            .map_err(|_| CryptoError::decryption("ML-KEM decapsulation failed"))?;
+
+        combine(base, classical.as_bytes(), post_quantum.as_slice(), encapsulation)
+    }
+
+    fn ml_kem_keys(&self) -> (MlKemDecapsulationKey, MlKemEncapsulationKey) {
+        let d = B32::try_from(&self.ml_kem_seed[..32]).expect("64-byte seed");
+        let z = B32::try_from(&self.ml_kem_seed[32..]).expect("64-byte seed");
+        MlKem768::generate_deterministic(&d, &z)
+    }
+}
+
+impl std::fmt::Debug for HybridKeyPair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HybridKeyPair")
+            .field("secret", &"[REDACTED]")
+            .field("fingerprint", &self.public_key().fingerprint())
+            .finish()
+    }
+}
+
+/// Mix the password-derived key with both shared secrets and the values they were exchanged with
+fn combine(
+    base: &DerivedKey,
+    classical: &[u8],
+    post_quantum: &[u8],
+    encapsulation: &HybridEncapsulation,
+) -> CryptoResult<DerivedKey> {
+    let mut ikm = Zeroizing::new(Vec::with_capacity(defaults::KEY_LENGTH + classical.len() + post_quantum.len()));
+    ikm.extend_from_slice(base.key().as_slice());
+    ikm.extend_from_slice(classical);
+    ikm.extend_from_slice(post_quantum);
+    ikm.extend_from_slice(&encapsulation.x25519_ephemeral);
+    ikm.extend_from_slice(&encapsulation.ml_kem_ciphertext);
+
+    let mut key_bytes = Zeroizing::new([0u8; defaults::KEY_LENGTH]);
+    Hkdf::<Sha256>::new(Some(base.salt()), &ikm)
+        .expand(COMBINE_INFO, key_bytes.as_mut_slice())
+        .map_err(|e| CryptoError::key_derivation(format!("HKDF expansion failed: {}", e)))?;
+
+    Ok(DerivedKey::from_raw_parts(*Key::from_slice(key_bytes.as_slice()), *base.salt()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_encapsulate_decapsulate_roundtrip() {
+        let base = DerivedKey::from_password_with_random_salt("pq_password").unwrap();
+        let recipient = HybridKeyPair::generate();
+        let public_key = HybridPublicKey::from_base64(&recipient.public_key().to_base64()).unwrap();
+        let (key, encapsulation) = public_key.encapsulate(&base).unwrap();
+
+        assert_eq!(encapsulation.recipient, public_key.fingerprint());
+        assert_eq!(encapsulation.ml_kem_ciphertext.len(), ML_KEM_768_CIPHERTEXT_LENGTH);
+        assert_ne!(key.key().as_slice(), base.key().as_slice());
+
+        let recovered = recipient.decapsulate(&base, &encapsulation).unwrap();
+        assert_eq!(key.key().as_slice(), recovered.key().as_slice());
+        assert_eq!(key.salt(), recovered.salt());
+    }
+
+    #[test]
+    fn test_needs_both_password_and_recipient_key() {
+        let salt = [7u8; defaults::SALT_LENGTH];
+        let base = DerivedKey::from_password_with_salt("right_password", &salt).unwrap();
+        let other = DerivedKey::from_password_with_salt("wrong_password", &salt).unwrap();
+        let recipient = HybridKeyPair::generate();
+        let (key, encapsulation) = recipient.public_key().encapsulate(&base).unwrap();
+
+        let recovered = recipient.decapsulate(&other, &encapsulation).unwrap();
+        assert_ne!(key.key().as_slice(), recovered.key().as_slice());
+        assert!(HybridKeyPair::generate().decapsulate(&base, &encapsulation).is_err());
+
+        let mut truncated = encapsulation.clone();
+        truncated.ml_kem_ciphertext.truncate(100);
+        assert!(recipient.decapsulate(&base, &truncated).is_err());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("pq.key");
+        let pair = HybridKeyPair::generate();
+        pair.save(&path).unwrap();
+        assert!(pair.save(&path).is_err(), "existing identities must not be overwritten");
+
+        let loaded = HybridKeyPair::load(&path).unwrap();
+        assert!(loaded.public_key() == pair.public_key());
+        assert!(HybridPublicKey::from_base64("AAAA").is_err());
+    }
+}
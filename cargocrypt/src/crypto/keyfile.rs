@@ -0,0 +1,225 @@
+//! Keyfile-based encryption for non-interactive environments
+//!
+//! A keyfile holds 32 random bytes. Since it already carries full key
+//! entropy it is used without Argon2 stretching: the file key is expanded
+//! from the keyfile and the per-file salt with HKDF-SHA256. A keyfile can
+//! also be combined with a password, in which case both are needed to
+//! decrypt. Which key material was used is recorded in the file header.
+
+use crate::crypto::{CryptoError, CryptoResult, DerivedKey, SecureRandom, defaults};
+use chacha20poly1305::Key;
+use hkdf::Hkdf;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::io::Write;
+use std::path::Path;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// Size of a keyfile in bytes
+pub const KEYFILE_LENGTH: usize = 32;
+
+/// HKDF info for keyfile-only keys
+const KEYFILE_INFO: &[u8] = b"cargocrypt-keyfile-v1";
+
+/// HKDF info for keys mixing a keyfile with a password
+const KEYFILE_PASSWORD_INFO: &[u8] = b"cargocrypt-keyfile-password-v1";
+
+/// Key material a secret was encrypted with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeySource {
+    /// Password only (Argon2)
+    #[default]
+    Password,
+    /// Keyfile only
+    Keyfile,
+    /// Keyfile mixed with a password
+    KeyfileAndPassword,
+}
+
+impl KeySource {
+    /// Whether a keyfile is needed to decrypt
+    pub fn requires_keyfile(&self) -> bool {
+        !matches!(self, Self::Password)
+    }
+
+    /// Whether a password is needed to decrypt
+    pub fn requires_password(&self) -> bool {
+        !matches!(self, Self::Keyfile)
+    }
+
+    pub(crate) fn is_password(&self) -> bool {
+        matches!(self, Self::Password)
+    }
+}
+
+/// A 32-byte random keyfile
+#[derive(Clone, ZeroizeOnDrop)]
+pub struct Keyfile {
+    bytes: [u8; KEYFILE_LENGTH],
+}
+
+impl Keyfile {
+    /// Generate a new random keyfile
+    pub fn generate() -> CryptoResult<Self> {
+        let random = SecureRandom::generate_bytes(KEYFILE_LENGTH)?;
+        let mut bytes = [0u8; KEYFILE_LENGTH];
+        bytes.copy_from_slice(&random);
+        Ok(Self { bytes })
+    }
+
+    /// Create a keyfile from raw bytes
+    pub fn from_bytes(bytes: &[u8]) -> CryptoResult<Self> {
+        if bytes.len() != KEYFILE_LENGTH {
+            return Err(CryptoError::invalid_key(format!(
+                "Keyfile must be exactly {} bytes, got {}",
+                KEYFILE_LENGTH,
+                bytes.len()
+            )));
+        }
+
+        let mut key = [0u8; KEYFILE_LENGTH];
+        key.copy_from_slice(bytes);
+        Ok(Self { bytes: key })
+    }
+
+    /// Raw key bytes (handle with care)
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Load a keyfile from disk
+    ///
+    /// Logs a warning if the file is readable by other users.
+    pub fn load<P: AsRef<Path>>(path: P) -> CryptoResult<Self> {
+        let path = path.as_ref();
+        let mut bytes = std::fs::read(path).map_err(|e| {
+            CryptoError::invalid_key(format!("Failed to read keyfile {}: {}", path.display(), e))
+        })?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            if let Ok(metadata) = std::fs::metadata(path) {
+                if metadata.permissions().mode() & 0o077 != 0 {
+                    tracing::warn!(
+                        "Keyfile {} is accessible by other users; run chmod 600 on it",
+                        path.display()
+                    );
+                }
+            }
+        }
+
+        let keyfile = Self::from_bytes(&bytes);
+        bytes.zeroize();
+        keyfile
+    }
+
+    /// Write the keyfile to a new file readable only by the owner
+    ///
+    /// Fails if the file already exists.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> CryptoResult<()> {
+        let path = path.as_ref();
+        let io_error = |e: std::io::Error| {
+            CryptoError::invalid_key(format!("Failed to write keyfile {}: {}", path.display(), e))
+        };
+
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).map_err(io_error)?;
+        }
+
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+
+        let mut file = options.open(path).map_err(io_error)?;
+        file.write_all(&self.bytes).map_err(io_error)?;
+        file.sync_all().map_err(io_error)
+    }
+
+    /// Derive the encryption key for a salt, optionally mixing in a password-derived key
+    pub fn derive_key(
+        &self,
+        salt: &[u8; defaults::SALT_LENGTH],
+        password_key: Option<&DerivedKey>,
+    ) -> CryptoResult<DerivedKey> {
+        let mut ikm = Vec::with_capacity(KEYFILE_LENGTH + defaults::KEY_LENGTH);
+        ikm.extend_from_slice(&self.bytes);
+        let info = match password_key {
+            Some(password_key) => {
+                ikm.extend_from_slice(password_key.key().as_slice());
+                KEYFILE_PASSWORD_INFO
+            }
+            None => KEYFILE_INFO,
+        };
+
+        let mut key_bytes = [0u8; defaults::KEY_LENGTH];
+        let result = Hkdf::<Sha256>::new(Some(salt), &ikm).expand(info, &mut key_bytes);
+        ikm.zeroize();
+        result.map_err(|e| CryptoError::key_derivation(format!("HKDF expansion failed: {}", e)))?;
+
+        let key = *Key::from_slice(&key_bytes);
+        key_bytes.zeroize();
+
+        Ok(DerivedKey::from_raw_parts(key, *salt))
+    }
+}
+
+impl std::fmt::Debug for Keyfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Keyfile").field("bytes", &"[REDACTED]").finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_save_and_load() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("keys").join("ci.key");
+
+        let keyfile = Keyfile::generate().unwrap();
+        keyfile.save(&path).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), KEYFILE_LENGTH as u64);
+        assert!(keyfile.save(&path).is_err(), "existing keyfiles must not be overwritten");
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let salt = SecureRandom::generate_salt().unwrap();
+        let loaded = Keyfile::load(&path).unwrap();
+        assert_eq!(
+            keyfile.derive_key(&salt, None).unwrap().key(),
+            loaded.derive_key(&salt, None).unwrap().key()
+        );
+    }
+
+    #[test]
+    fn test_password_changes_key() {
+        let keyfile = Keyfile::generate().unwrap();
+        let salt = SecureRandom::generate_salt().unwrap();
+        let password_key = DerivedKey::from_password_with_salt("ci_password", &salt).unwrap();
+
+        let keyfile_only = keyfile.derive_key(&salt, None).unwrap();
+        let mixed = keyfile.derive_key(&salt, Some(&password_key)).unwrap();
+        assert_ne!(keyfile_only.key(), mixed.key());
+        assert_ne!(password_key.key(), mixed.key());
+    }
+
+    #[test]
+    fn test_invalid_length() {
+        assert!(Keyfile::from_bytes(&[0u8; 16]).is_err());
+    }
+}
@@ -4,6 +4,8 @@ use crate::crypto::{CryptoError, CryptoResult, defaults};
 use argon2::{Argon2, Params};
 use chacha20poly1305::Key;
 use rand::{RngCore, rngs::OsRng};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
 use zeroize::{Zeroize, ZeroizeOnDrop};
 use subtle::ConstantTimeEq;
 
@@ -19,6 +21,13 @@ pub struct DerivedKey {
 impl DerivedKey {
     /// Create a new derived key from a password
     pub fn from_password(password: &str, params: &KeyDerivationParams) -> CryptoResult<Self> {
+        match params.kdf {
+            Kdf::Argon2id => {}
+            Kdf::Argon2idCustom { .. } | Kdf::Scrypt { .. } | Kdf::Pbkdf2Sha256 { .. } => {
+                return Self::from_password_with_kdf(password, &params.salt, &params.kdf);
+            }
+        }
+
         let argon2 = Argon2::new(
             defaults::ARGON2_ALGORITHM,
             defaults::ARGON2_VERSION,
@@ -26,6 +35,7 @@ impl DerivedKey {
         );
 
         let mut key_bytes = [0u8; defaults::KEY_LENGTH];
+        let _permit = crate::crypto::limits::acquire(params.argon2_params.m_cost() as u64);
         
         argon2
             .hash_password_into(password.as_bytes(), &params.salt, &mut key_bytes)
@@ -65,6 +75,48 @@ impl DerivedKey {
         Self::from_password(password, &params)
     }
 
+    /// Derive a key with a KDF recorded in a file header
+    ///
+    /// `Kdf::Argon2id` uses the default Argon2 parameters.
+    pub fn from_password_with_kdf(
+        password: &str,
+        salt: &[u8; defaults::SALT_LENGTH],
+        kdf: &Kdf,
+    ) -> CryptoResult<Self> {
+        let mut key_bytes = [0u8; defaults::KEY_LENGTH];
+
+        match *kdf {
+            Kdf::Argon2id => return Self::from_password(password, &KeyDerivationParams::from_salt(*salt)?),
+            Kdf::Argon2idCustom { m_cost, t_cost, p_cost } => {
+                kdf.validate()?;
+                let params = KeyDerivationParams::with_custom_params(m_cost, t_cost, p_cost, *salt)?;
+                return Self::from_password(password, &params);
+            }
+            Kdf::Scrypt { log_n, r, p } => {
+                kdf.validate()?;
+                let params = scrypt::Params::new(log_n, r, p, defaults::KEY_LENGTH)
+                    .map_err(|e| CryptoError::key_derivation(format!("Invalid scrypt parameters: {}", e)))?;
+                let _permit = crate::crypto::limits::acquire(kdf.memory_cost_kib());
+                scrypt::scrypt(password.as_bytes(), salt, &params, &mut key_bytes)
+                    .map_err(|e| CryptoError::key_derivation(e.to_string()))?;
+            }
+            Kdf::Pbkdf2Sha256 { iterations } => {
+                kdf.validate()?;
+                pbkdf2::pbkdf2_hmac::<Sha256>(password.as_bytes(), salt, iterations, &mut key_bytes);
+            }
+        }
+
+        let key = *Key::from_slice(&key_bytes);
+        key_bytes.zeroize();
+
+        Ok(Self { key, salt: *salt })
+    }
+
+    /// Assemble a derived key from raw key material and the salt it belongs to
+    pub(crate) fn from_raw_parts(key: Key, salt: [u8; defaults::SALT_LENGTH]) -> Self {
+        Self { key, salt }
+    }
+
     /// Get the key for encryption/decryption
     pub fn key(&self) -> &Key {
         &self.key
@@ -145,6 +197,136 @@ impl std::fmt::Debug for DerivedKey {
     }
 }
 
+/// Upper bound on KDF memory, so a crafted header cannot exhaust memory (4 GiB)
+const MAX_KDF_MEMORY_KIB: u64 = 4 * 1024 * 1024;
+
+/// Password-based key derivation function
+///
+/// Argon2id is the default and should be preferred. scrypt and PBKDF2-SHA256
+/// exist to read files produced by other tools and for environments where
+/// Argon2's memory use is not acceptable. The function and its parameters are
+/// stored in the file header, so decryption always uses the right one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "id", rename_all = "snake_case")]
+pub enum Kdf {
+    /// Argon2id with the default parameters
+    #[default]
+    Argon2id,
+    /// Argon2id with memory `m_cost` KiB, `t_cost` passes and `p_cost` lanes,
+    /// usually picked by [`CryptoEngine::calibrate`](crate::crypto::CryptoEngine::calibrate)
+    Argon2idCustom { m_cost: u32, t_cost: u32, p_cost: u32 },
+    /// scrypt with cost `2^log_n`, block size `r` and parallelism `p`
+    Scrypt { log_n: u8, r: u32, p: u32 },
+    /// PBKDF2 with HMAC-SHA256
+    Pbkdf2Sha256 { iterations: u32 },
+}
+
+impl Kdf {
+    /// scrypt with the parameters recommended for interactive use
+    pub const fn scrypt() -> Self {
+        Self::Scrypt { log_n: 17, r: 8, p: 1 }
+    }
+
+    /// PBKDF2-SHA256 with the OWASP-recommended iteration count
+    pub const fn pbkdf2_sha256() -> Self {
+        Self::Pbkdf2Sha256 { iterations: 600_000 }
+    }
+
+    /// Identifier stored in the file header
+    pub fn id(&self) -> &'static str {
+        match self {
+            Self::Argon2id => "argon2id",
+            Self::Argon2idCustom { .. } => "argon2id_custom",
+            Self::Scrypt { .. } => "scrypt",
+            Self::Pbkdf2Sha256 { .. } => "pbkdf2_sha256",
+        }
+    }
+
+    /// Approximate memory needed for one derivation, in KiB
+    pub fn memory_cost_kib(&self) -> u64 {
+        match *self {
+            Self::Argon2id => defaults::ARGON2_PARAMS.m_cost() as u64,
+            Self::Argon2idCustom { m_cost, .. } => u64::from(m_cost),
+            // 128 * r * N bytes
+            Self::Scrypt { log_n, r, .. } => (128 * u64::from(r)).saturating_mul(1u64 << log_n.min(63)) / 1024,
+            Self::Pbkdf2Sha256 { .. } => 0,
+        }
+    }
+
+    /// Check that the parameters can be used for derivation
+    pub fn validate(&self) -> CryptoResult<()> {
+        match *self {
+            Self::Argon2id => Ok(()),
+            Self::Argon2idCustom { .. } if self.memory_cost_kib() > MAX_KDF_MEMORY_KIB => Err(CryptoError::key_derivation(
+                format!("Argon2 parameters need {} KiB, more than the {} KiB allowed", self.memory_cost_kib(), MAX_KDF_MEMORY_KIB),
+            )),
+            Self::Argon2idCustom { m_cost, t_cost, p_cost } => Params::new(m_cost, t_cost, p_cost, Some(defaults::KEY_LENGTH))
+                .map(|_| ())
+                .map_err(|e| CryptoError::key_derivation(format!("Invalid Argon2 parameters: {}", e))),
+            Self::Scrypt { log_n: 0, .. } => Err(CryptoError::key_derivation("scrypt cost must be at least 2^1")),
+            Self::Scrypt { .. } if self.memory_cost_kib() > MAX_KDF_MEMORY_KIB => Err(CryptoError::key_derivation(
+                format!("scrypt parameters need {} KiB, more than the {} KiB allowed", self.memory_cost_kib(), MAX_KDF_MEMORY_KIB),
+            )),
+            Self::Scrypt { log_n, r, p } => scrypt::Params::new(log_n, r, p, defaults::KEY_LENGTH)
+                .map(|_| ())
+                .map_err(|e| CryptoError::key_derivation(format!("Invalid scrypt parameters: {}", e))),
+            Self::Pbkdf2Sha256 { iterations: 0 } => {
+                Err(CryptoError::key_derivation("PBKDF2 needs at least one iteration"))
+            }
+            Self::Pbkdf2Sha256 { .. } => Ok(()),
+        }
+    }
+
+    /// Whether the parameters are below current recommendations
+    ///
+    /// Weak parameters are still accepted (for interop) but should not be used
+    /// to encrypt new files.
+    pub fn is_weak(&self) -> bool {
+        match *self {
+            Self::Argon2id => false,
+            // OWASP minimum: 19 MiB with two passes, or an equivalent trade-off
+            Self::Argon2idCustom { m_cost, t_cost, .. } => u64::from(m_cost) * u64::from(t_cost) < 2 * 19 * 1024,
+            Self::Scrypt { log_n, r, .. } => log_n < 15 || r < 8,
+            Self::Pbkdf2Sha256 { iterations } => iterations < 100_000,
+        }
+    }
+
+    /// Whether keys derived with these parameters are cheaper to brute-force than with `other`
+    ///
+    /// Memory-hard functions rank above PBKDF2, and Argon2id above scrypt.
+    /// Within one function, memory times passes (or the iteration count)
+    /// decides; lanes do not change an attacker's cost.
+    pub fn is_weaker_than(&self, other: &Kdf) -> bool {
+        self.strength() < other.strength()
+    }
+
+    fn strength(&self) -> (u8, u64) {
+        match *self {
+            Self::Pbkdf2Sha256 { iterations } => (0, u64::from(iterations)),
+            Self::Scrypt { .. } => (1, self.memory_cost_kib()),
+            Self::Argon2id => (2, self.memory_cost_kib() * u64::from(defaults::ARGON2_PARAMS.t_cost())),
+            Self::Argon2idCustom { m_cost, t_cost, .. } => (2, u64::from(m_cost) * u64::from(t_cost)),
+        }
+    }
+
+    pub(crate) fn is_default(&self) -> bool {
+        matches!(self, Self::Argon2id)
+    }
+}
+
+impl std::fmt::Display for Kdf {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Argon2id => write!(f, "Argon2id"),
+            Self::Argon2idCustom { m_cost, t_cost, p_cost } => {
+                write!(f, "Argon2id (m={} KiB, t={}, p={})", m_cost, t_cost, p_cost)
+            }
+            Self::Scrypt { log_n, r, p } => write!(f, "scrypt (N=2^{}, r={}, p={})", log_n, r, p),
+            Self::Pbkdf2Sha256 { iterations } => write!(f, "PBKDF2-SHA256 ({} iterations)", iterations),
+        }
+    }
+}
+
 /// Parameters for key derivation
 #[derive(Debug, Clone)]
 pub struct KeyDerivationParams {
@@ -152,6 +334,8 @@ pub struct KeyDerivationParams {
     pub argon2_params: Params,
     /// Salt for key derivation
     pub salt: [u8; defaults::SALT_LENGTH],
+    /// Key derivation function (`argon2_params` only apply to Argon2id)
+    pub kdf: Kdf,
 }
 
 impl KeyDerivationParams {
@@ -165,6 +349,7 @@ impl KeyDerivationParams {
         Ok(Self {
             argon2_params: defaults::ARGON2_PARAMS,
             salt,
+            kdf: Kdf::Argon2id,
         })
     }
 
@@ -173,6 +358,7 @@ impl KeyDerivationParams {
         Ok(Self {
             argon2_params: defaults::ARGON2_PARAMS,
             salt,
+            kdf: Kdf::Argon2id,
         })
     }
 
@@ -194,9 +380,16 @@ impl KeyDerivationParams {
         Ok(Self {
             argon2_params,
             salt,
+            kdf: Kdf::Argon2id,
         })
     }
 
+    /// Use a different key derivation function
+    pub fn with_kdf(mut self, kdf: Kdf) -> Self {
+        self.kdf = kdf;
+        self
+    }
+
     /// Get the salt as a slice
     pub fn salt(&self) -> &[u8] {
         &self.salt
@@ -352,6 +545,71 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_alternative_kdfs() {
+        let salt = SecureRandom::generate_salt().unwrap();
+        let scrypt = Kdf::Scrypt { log_n: 10, r: 8, p: 1 };
+        let pbkdf2 = Kdf::Pbkdf2Sha256 { iterations: 1_000 };
+
+        let scrypt_key = DerivedKey::from_password_with_kdf("interop_password", &salt, &scrypt).unwrap();
+        let pbkdf2_key = DerivedKey::from_password_with_kdf("interop_password", &salt, &pbkdf2).unwrap();
+        assert_ne!(scrypt_key.key(), pbkdf2_key.key());
+        assert!(scrypt.is_weak() && pbkdf2.is_weak());
+        assert!(!Kdf::scrypt().is_weak() && !Kdf::pbkdf2_sha256().is_weak());
+
+        let params = KeyDerivationParams::from_salt(salt).unwrap().with_kdf(pbkdf2);
+        assert_eq!(DerivedKey::from_password("interop_password", &params).unwrap().key(), pbkdf2_key.key());
+
+        // RFC 7914 section 12 test vector (first 32 bytes)
+        let mut output = [0u8; 32];
+        let params = scrypt::Params::new(4, 1, 1, 32).unwrap();
+        scrypt::scrypt(b"", b"", &params, &mut output).unwrap();
+        assert_eq!(hex::encode(output), "77d6576238657b203b19ca42c18a0497f16b4844e3074ae8dfdffa3fede21442");
+
+        assert!(DerivedKey::from_password_with_kdf("pw", &salt, &Kdf::Pbkdf2Sha256 { iterations: 0 }).is_err());
+        assert!(DerivedKey::from_password_with_kdf("pw", &salt, &Kdf::Scrypt { log_n: 0, r: 8, p: 1 }).is_err());
+    }
+
+    #[test]
+    fn test_custom_argon2_params() {
+        let salt = SecureRandom::generate_salt().unwrap();
+        let defaults = Kdf::Argon2idCustom { m_cost: 65536, t_cost: 3, p_cost: 4 };
+        let small = Kdf::Argon2idCustom { m_cost: 8192, t_cost: 1, p_cost: 1 };
+
+        let default_key = DerivedKey::from_password_with_kdf("tuned_password", &salt, &Kdf::Argon2id).unwrap();
+        let custom_key = DerivedKey::from_password_with_kdf("tuned_password", &salt, &defaults).unwrap();
+        let small_key = DerivedKey::from_password_with_kdf("tuned_password", &salt, &small).unwrap();
+        assert_eq!(default_key.key(), custom_key.key());
+        assert_ne!(default_key.key(), small_key.key());
+        assert!(small.is_weak() && !defaults.is_weak());
+
+        let oversized = Kdf::Argon2idCustom { m_cost: u32::MAX, t_cost: 1, p_cost: 1 };
+        assert!(oversized.validate().is_err());
+        assert!(Kdf::Argon2idCustom { m_cost: 65536, t_cost: 0, p_cost: 1 }.validate().is_err());
+    }
+
+    #[test]
+    fn test_kdf_strength_ordering() {
+        let small = Kdf::Argon2idCustom { m_cost: 19 * 1024, t_cost: 2, p_cost: 1 };
+        let large = Kdf::Argon2idCustom { m_cost: 256 * 1024, t_cost: 3, p_cost: 1 };
+
+        assert!(small.is_weaker_than(&Kdf::Argon2id));
+        assert!(Kdf::Argon2id.is_weaker_than(&large));
+        assert!(Kdf::pbkdf2_sha256().is_weaker_than(&Kdf::scrypt()));
+        assert!(Kdf::scrypt().is_weaker_than(&small));
+        // Equal cost is not weaker, whatever the lanes
+        let relaned = Kdf::Argon2idCustom { m_cost: 65536, t_cost: 3, p_cost: 1 };
+        assert!(!relaned.is_weaker_than(&Kdf::Argon2id) && !Kdf::Argon2id.is_weaker_than(&relaned));
+    }
+
+    #[test]
+    fn test_kdf_serialization() {
+        let json = serde_json::to_string(&Kdf::Scrypt { log_n: 15, r: 8, p: 1 }).unwrap();
+        assert_eq!(json, r#"{"id":"scrypt","log_n":15,"r":8,"p":1}"#);
+        let parsed: Kdf = serde_json::from_str(r#"{"id":"pbkdf2_sha256","iterations":600000}"#).unwrap();
+        assert_eq!(parsed, Kdf::pbkdf2_sha256());
+    }
+
     #[test]
     fn test_key_params_creation() {
         let params = KeyDerivationParams::new_random().unwrap();
@@ -0,0 +1,117 @@
+//! Process-wide memory budget for Argon2 key derivations
+//!
+//! Each Argon2 derivation allocates its full memory cost up front (64 MB for
+//! the default profile), so a handful of concurrent derivations can exceed the
+//! memory limit of a small CI container. When a budget is set, derivations
+//! wait until enough of it is free instead of running all at once. A single
+//! derivation larger than the whole budget is still allowed to run on its own
+//! (the parameters are fixed by the file being decrypted), so the budget only
+//! ever slows things down and never makes an operation fail.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Condvar, Mutex};
+
+/// Budget limit in KiB (0 = unlimited)
+static LIMIT_KIB: AtomicU64 = AtomicU64::new(0);
+
+/// Memory currently reserved by running derivations, in KiB
+static IN_USE_KIB: Mutex<u64> = Mutex::new(0);
+
+/// Signalled whenever a reservation is released
+static RELEASED: Condvar = Condvar::new();
+
+/// Set the memory budget for concurrent key derivations (in KiB, `None` = unlimited)
+pub fn set_kdf_memory_limit(limit_kib: Option<u64>) {
+    LIMIT_KIB.store(limit_kib.unwrap_or(0), Ordering::SeqCst);
+    RELEASED.notify_all();
+}
+
+/// Current memory budget for key derivations in KiB, if one is set
+pub fn kdf_memory_limit() -> Option<u64> {
+    match LIMIT_KIB.load(Ordering::SeqCst) {
+        0 => None,
+        limit => Some(limit),
+    }
+}
+
+/// Reservation of part of the budget, released on drop
+#[derive(Debug)]
+pub(crate) struct KdfPermit {
+    cost_kib: u64,
+}
+
+/// Reserve `cost_kib` of the budget, blocking until it is available
+pub(crate) fn acquire(cost_kib: u64) -> KdfPermit {
+    let mut in_use = IN_USE_KIB.lock().unwrap_or_else(|e| e.into_inner());
+
+    loop {
+        let fits = match kdf_memory_limit() {
+            None => true,
+            Some(limit) => *in_use == 0 || *in_use + cost_kib <= limit,
+        };
+        if fits {
+            break;
+        }
+        in_use = RELEASED.wait(in_use).unwrap_or_else(|e| e.into_inner());
+    }
+
+    if let Some(limit) = kdf_memory_limit() {
+        if cost_kib > limit {
+            tracing::warn!(
+                "Key derivation needs {} KiB, above the {} KiB limit; running it alone",
+                cost_kib,
+                limit
+            );
+        }
+    }
+
+    *in_use += cost_kib;
+    KdfPermit { cost_kib }
+}
+
+impl Drop for KdfPermit {
+    fn drop(&mut self) {
+        let mut in_use = IN_USE_KIB.lock().unwrap_or_else(|e| e.into_inner());
+        *in_use = in_use.saturating_sub(self.cost_kib);
+        RELEASED.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_budget_serializes_derivations() {
+        // Large enough that other tests' derivations never wait on this one
+        const COST: u64 = 1 << 40;
+        let previous = kdf_memory_limit();
+        set_kdf_memory_limit(Some(COST + COST / 2));
+
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+        let handles: Vec<_> = (0..3)
+            .map(|_| {
+                let running = Arc::clone(&running);
+                let max_running = Arc::clone(&max_running);
+                thread::spawn(move || {
+                    let _permit = acquire(COST);
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_running.fetch_max(now, Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(20));
+                    running.fetch_sub(1, Ordering::SeqCst);
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        set_kdf_memory_limit(previous);
+        assert_eq!(max_running.load(Ordering::SeqCst), 1);
+    }
+}
@@ -2,6 +2,10 @@
 //! 
 //! This module provides secure cryptographic operations using ChaCha20-Poly1305
 //! for authenticated encryption and Argon2 for key derivation.
+//!
+//! Files are encrypted with a 256-bit symmetric key, which Grover's algorithm
+//! only weakens to 128 bits. For long retention a file key can also be bound
+//! to a recipient's X25519 + ML-KEM-768 key pair, see [`hybrid`].
 
 pub mod engine;
 pub mod keys;
@@ -11,14 +15,33 @@ pub mod algorithm;
 pub mod store;
 pub mod mock;
 pub mod security;
+pub mod signing;
+pub mod recipient;
+pub mod hybrid;
+pub mod openpgp;
+pub mod deterministic;
+pub mod limits;
+pub mod keyfile;
+pub mod cache;
+pub mod file_metadata;
+pub mod nonce;
+pub mod chunked;
+pub mod shamir;
 
-pub use engine::{CryptoEngine, PerformanceProfile, EncryptionOptions, PerformanceBenchmark, BatchEncryptionResult};
-pub use keys::{DerivedKey, KeyDerivationParams, SecureRandom};
-pub use secrets::{EncryptedSecret, PlaintextSecret, SecretMetadata, SecretType};
+pub use engine::{CryptoEngine, PerformanceProfile, EncryptionOptions, PerformanceBenchmark, BatchEncryptionResult, Calibration};
+pub use keys::{DerivedKey, Kdf, KeyDerivationParams, SecureRandom};
+pub use secrets::{EncryptedSecret, PlaintextSecret, SecretHeader, SecretMetadata, SecretType};
+pub use signing::{SigningKeyPair, DetachedSignature};
+pub use recipient::RecipientKeyPair;
+pub use hybrid::{HybridEncapsulation, HybridKeyPair, HybridPublicKey};
+pub use keyfile::{Keyfile, KeySource};
+pub use cache::{KeyCache, MemoryKeyCache};
+pub use file_metadata::{FileMetadata, ExtendedAttribute};
+pub use chunked::ChunkLayout;
 pub use errors::{CryptoError, CryptoResult};
 pub use algorithm::{Algorithm, AlgorithmExt};
-pub use store::{SecretStore, MemorySecretStore};
-pub use security::{SecureBuffer, SecureRandom as SecurityRandom, TimingDefense, KeyDerivationValidator, constant_time_compare};
+pub use store::{SecretStore, MemorySecretStore, FileSecretStore, SecretVersion};
+pub use security::{SecureBuffer, SecureBytes, SecureString, SecureRandom as SecurityRandom, TimingDefense, KeyDerivationValidator, constant_time_compare};
 
 // Re-export commonly used types
 pub use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
@@ -51,6 +74,12 @@ pub mod defaults {
 
     /// Key length for ChaCha20-Poly1305 (32 bytes)
     pub const KEY_LENGTH: usize = 32;
+
+    /// Authentication tag length for ChaCha20-Poly1305 (16 bytes)
+    pub const TAG_LENGTH: usize = 16;
+
+    /// Plaintext bytes per chunk in chunked encryption (4 MiB)
+    pub const CHUNK_SIZE: usize = 4 * 1024 * 1024;
 }
 
 #[cfg(test)]
@@ -0,0 +1,89 @@
+//! Nonce-misuse detection
+//!
+//! ChaCha20-Poly1305 loses both confidentiality and authenticity when two
+//! different messages are encrypted under the same key and nonce. Nonces are
+//! either random or, in deterministic mode, derived from the plaintext, so a
+//! repeat should never happen; this registry makes sure of it for the lifetime
+//! of the process.
+//!
+//! Every encryption records a fingerprint of its (key, nonce) pair together
+//! with the authentication tag it produced. A second encryption under the same
+//! pair is refused unless it produced the same tag, i.e. the same message was
+//! encrypted again. That is what deterministic mode does for an unchanged file,
+//! and it reveals nothing the first ciphertext did not. The key itself is never
+//! stored, only an HMAC keyed with it.
+
+use crate::crypto::{defaults, CryptoError, CryptoResult};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Domain separation label for (key, nonce) fingerprints
+const PAIR_LABEL: &[u8] = b"cargocrypt-nonce-pair-v1";
+
+/// Tags seen for each (key, nonce) fingerprint in this process
+static USED: OnceLock<Mutex<HashMap<[u8; 32], [u8; defaults::TAG_LENGTH]>>> = OnceLock::new();
+
+/// Record an encryption under `key` and `nonce`, refusing a reused pair
+///
+/// `ciphertext` is the AEAD output, whose last [`defaults::TAG_LENGTH`] bytes
+/// are the authentication tag. Call this before the ciphertext is handed out,
+/// and discard the ciphertext if it fails.
+pub(crate) fn record(key: &[u8], nonce: &[u8; defaults::NONCE_LENGTH], ciphertext: &[u8]) -> CryptoResult<()> {
+    let tag_start = ciphertext
+        .len()
+        .checked_sub(defaults::TAG_LENGTH)
+        .ok_or_else(|| CryptoError::encryption("Ciphertext is shorter than an authentication tag"))?;
+    let mut tag = [0u8; defaults::TAG_LENGTH];
+    tag.copy_from_slice(&ciphertext[tag_start..]);
+
+    let mut used = USED.get_or_init(Default::default).lock().unwrap_or_else(|e| e.into_inner());
+    match used.insert(fingerprint(key, nonce), tag) {
+        Some(previous) if previous != tag => {
+            // Keep the first tag so every later attempt is refused as well
+            used.insert(fingerprint(key, nonce), previous);
+            tracing::error!("Refused to encrypt a second message under a key and nonce already used");
+            Err(CryptoError::NonceReuse)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Number of (key, nonce) pairs recorded in this process
+pub fn recorded_pairs() -> usize {
+    USED.get().map_or(0, |used| used.lock().unwrap_or_else(|e| e.into_inner()).len())
+}
+
+fn fingerprint(key: &[u8], nonce: &[u8; defaults::NONCE_LENGTH]) -> [u8; 32] {
+    let mut mac = <HmacSha256 as Mac>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(PAIR_LABEL);
+    mac.update(nonce);
+    mac.finalize().into_bytes().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reuse_is_refused() {
+        let key = [7u8; defaults::KEY_LENGTH];
+        let nonce = [9u8; defaults::NONCE_LENGTH];
+        let first = [[1u8; 4].as_slice(), &[0xaa; defaults::TAG_LENGTH]].concat();
+        let second = [[2u8; 4].as_slice(), &[0xbb; defaults::TAG_LENGTH]].concat();
+
+        record(&key, &nonce, &first).unwrap();
+        // The same message again is what deterministic mode does for unchanged files
+        record(&key, &nonce, &first).unwrap();
+        assert!(matches!(record(&key, &nonce, &second), Err(CryptoError::NonceReuse)));
+        assert!(matches!(record(&key, &nonce, &second), Err(CryptoError::NonceReuse)));
+
+        // Either half of the pair changing makes it a new pair
+        record(&[8u8; defaults::KEY_LENGTH], &nonce, &second).unwrap();
+        record(&key, &[10u8; defaults::NONCE_LENGTH], &second).unwrap();
+        assert!(recorded_pairs() >= 3);
+    }
+}
@@ -0,0 +1,214 @@
+//! Wrapping keys for OpenPGP recipients through `gpg`
+//!
+//! Teams that already manage GPG keys can list members by fingerprint
+//! instead of an X25519 public key. Keys are then wrapped with `gpg
+//! --encrypt` to the member's key from the local keyring, and unwrapped with
+//! `gpg --decrypt`, which finds the secret key and prompts through
+//! gpg-agent, so no secret key material passes through CargoCrypt.
+//!
+//! Team changes can likewise be signed with a GPG key ([`sign`]) and checked
+//! with [`verify`].
+
+use crate::crypto::{CryptoError, CryptoResult};
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use zeroize::Zeroize;
+
+/// Armor header of wrapped keys
+pub const ARMOR_HEADER: &str = "-----BEGIN PGP MESSAGE-----";
+
+/// A key of the local GPG keyring
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GpgKey {
+    /// Fingerprint of the primary key, upper case hex
+    pub fingerprint: String,
+    /// Primary user ID
+    pub user_id: String,
+    /// Whether the key (or one of its subkeys) can encrypt
+    pub can_encrypt: bool,
+}
+
+/// Whether a wrapped key was wrapped with OpenPGP
+pub fn is_wrapped(wrapped: &str) -> bool {
+    wrapped.trim_start().starts_with(ARMOR_HEADER)
+}
+
+/// Public keys of the keyring matching `query` (a fingerprint, key ID, email or name)
+pub fn find_keys(query: &str) -> CryptoResult<Vec<GpgKey>> {
+    let output = gpg(&["--with-colons", "--fingerprint", "--list-keys", "--", query], None)?;
+    Ok(parse_key_listing(&output, "pub"))
+}
+
+/// Keys of the keyring with a usable encryption key matching `query`
+///
+/// Fails unless exactly one key matches, so a fingerprint can be recorded.
+pub fn find_recipient(query: &str) -> CryptoResult<GpgKey> {
+    let keys: Vec<GpgKey> = find_keys(query)
+        .map_err(|_| CryptoError::invalid_key(format!("No GPG key matches '{}' in the keyring", query)))?
+        .into_iter()
+        .filter(|key| key.can_encrypt)
+        .collect();
+    match keys.len() {
+        1 => Ok(keys.into_iter().next().expect("one key")),
+        0 => Err(CryptoError::invalid_key(format!(
+            "No valid GPG key that can encrypt matches '{}'; import it with 'gpg --import'",
+            query
+        ))),
+        n => Err(CryptoError::invalid_key(format!(
+            "{} GPG keys match '{}'; use the full fingerprint",
+            n, query
+        ))),
+    }
+}
+
+/// Fingerprints of the secret keys available to unwrap with
+pub fn secret_key_fingerprints() -> Vec<String> {
+    gpg(&["--with-colons", "--fingerprint", "--list-secret-keys"], None)
+        .map(|output| parse_key_listing(&output, "sec").into_iter().map(|key| key.fingerprint).collect())
+        .unwrap_or_default()
+}
+
+/// Wrap `key` to the GPG key with `fingerprint`, ASCII-armored
+pub fn wrap_key(key: &[u8], fingerprint: &str) -> CryptoResult<String> {
+    let output = gpg(
+        &["--trust-model", "always", "--armor", "--encrypt", "--recipient", fingerprint],
+        Some(key),
+    )?;
+    String::from_utf8(output).map_err(|_| CryptoError::encryption("gpg produced invalid armor"))
+}
+
+/// Unwrap an armored key with a secret key of the keyring
+pub fn unwrap_key(wrapped: &str) -> CryptoResult<Vec<u8>> {
+    gpg(&["--decrypt"], Some(wrapped.as_bytes()))
+        .map_err(|e| CryptoError::decryption(format!("gpg could not unwrap the key: {}", e)))
+}
+
+/// Detached, armored signature of `data` made with the secret key `fingerprint`
+pub fn sign(data: &[u8], fingerprint: &str) -> CryptoResult<String> {
+    let output = gpg(&["--armor", "--detach-sign", "--local-user", fingerprint], Some(data))?;
+    String::from_utf8(output).map_err(|_| CryptoError::invalid_signature("gpg produced invalid armor"))
+}
+
+/// Verify a detached signature of `data`, returning the signer's primary key fingerprint
+pub fn verify(data: &[u8], signature: &str) -> CryptoResult<String> {
+    static SIGNATURES: AtomicUsize = AtomicUsize::new(0);
+
+    // gpg takes the data on stdin, so the signature goes through a file
+    let path = std::env::temp_dir().join(format!(
+        "cargocrypt-signature-{}-{}.asc",
+        std::process::id(),
+        SIGNATURES.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::write(&path, signature)
+        .map_err(|e| CryptoError::invalid_signature(format!("Failed to write signature for gpg: {}", e)))?;
+    let path_arg = path.to_string_lossy().into_owned();
+    let output = gpg(&["--status-fd", "1", "--verify", &path_arg, "-"], Some(data));
+    let _ = std::fs::remove_file(&path);
+
+    let output = output.map_err(|e| CryptoError::invalid_signature(format!("GPG signature does not verify: {}", e)))?;
+    parse_valid_signature(&output)
+        .ok_or_else(|| CryptoError::invalid_signature("gpg did not report a valid signature"))
+}
+
+/// Run gpg in batch mode and return its standard output
+fn gpg(args: &[&str], input: Option<&[u8]>) -> CryptoResult<Vec<u8>> {
+    let mut child = Command::new("gpg")
+        .args(["--batch", "--quiet", "--yes"])
+        .args(args)
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| CryptoError::invalid_key(format!("gpg is needed for OpenPGP recipients but could not be run: {}", e)))?;
+    let io_error = |e: std::io::Error| CryptoError::invalid_key(format!("Failed to run gpg: {}", e));
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        stdin.write_all(input).map_err(io_error)?;
+    }
+    let mut output = child.wait_with_output().map_err(io_error)?;
+    if !output.status.success() {
+        output.stdout.zeroize();
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(CryptoError::invalid_key(format!("gpg failed: {}", stderr.trim())));
+    }
+    Ok(output.stdout)
+}
+
+/// Primary key fingerprint from the `VALIDSIG` line of a `--status-fd` report
+fn parse_valid_signature(status: &[u8]) -> Option<String> {
+    String::from_utf8_lossy(status).lines().find_map(|line| {
+        let fields: Vec<&str> = line.strip_prefix("[GNUPG:] VALIDSIG ")?.split(' ').collect();
+        // The primary key fingerprint is last when the signature was made by a subkey
+        fields.get(9).or(fields.first()).map(|fingerprint| fingerprint.to_string())
+    })
+}
+
+/// Keys of a `--with-colons` listing whose primary records are `kind`
+fn parse_key_listing(output: &[u8], kind: &str) -> Vec<GpgKey> {
+    let mut keys: Vec<GpgKey> = Vec::new();
+    let mut in_primary = false;
+    for line in String::from_utf8_lossy(output).lines() {
+        let fields: Vec<&str> = line.split(':').collect();
+        match fields.first().copied() {
+            Some(record) if record == kind => {
+                // Revoked, expired, invalid and disabled keys cannot be encrypted to
+                let validity = fields.get(1).copied().unwrap_or("");
+                let capabilities = fields.get(11).copied().unwrap_or("");
+                keys.push(GpgKey {
+                    fingerprint: String::new(),
+                    user_id: String::new(),
+                    can_encrypt: !matches!(validity, "r" | "e" | "i" | "d") && capabilities.contains('E'),
+                });
+                in_primary = true;
+            }
+            Some("sub") | Some("ssb") => in_primary = false,
+            Some("fpr") if in_primary => {
+                if let (Some(key), Some(fingerprint)) = (keys.last_mut(), fields.get(9)) {
+                    key.fingerprint = fingerprint.to_string();
+                }
+                in_primary = false;
+            }
+            Some("uid") => {
+                if let (Some(key), Some(user_id)) = (keys.last_mut(), fields.get(9)) {
+                    if key.user_id.is_empty() {
+                        key.user_id = user_id.to_string();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_key_listing() {
+        let listing = b"tru::1:1700000000:0:3:1:5\n\
+pub:u:255:22:ABCDEF0123456789:1700000000:::u:::scESC::::::23::0:\n\
+fpr:::::::::0123456789ABCDEF0123456789ABCDEF01234567:\n\
+uid:u::::1700000000::HASH::Alice <alice@example.com>::::::::::0:\n\
+sub:u:255:18:1111111111111111:1700000000::::::e::::::23:\n\
+fpr:::::::::FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF:\n\
+pub:r:255:22:9999999999999999:1600000000:::u:::scESC::::::23::0:\n\
+fpr:::::::::9999999999999999999999999999999999999999:\n\
+uid:r::::1600000000::HASH::Mallory <mallory@example.com>::::::::::0:\n";
+
+        let keys = parse_key_listing(listing, "pub");
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[0].fingerprint, "0123456789ABCDEF0123456789ABCDEF01234567");
+        assert_eq!(keys[0].user_id, "Alice <alice@example.com>");
+        assert!(keys[0].can_encrypt);
+        assert!(!keys[1].can_encrypt);
+        assert!(is_wrapped("-----BEGIN PGP MESSAGE-----\n\nhQEMA..."));
+        assert!(!is_wrapped("AAAAC3NzaC1lZDI1NTE5"));
+
+        let status = b"[GNUPG:] NEWSIG\n\
+[GNUPG:] VALIDSIG FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF 2024-01-01 1700000000 0 4 0 22 10 00 0123456789ABCDEF0123456789ABCDEF01234567\n";
+        assert_eq!(parse_valid_signature(status).as_deref(), Some("0123456789ABCDEF0123456789ABCDEF01234567"));
+        assert_eq!(parse_valid_signature(b"[GNUPG:] BADSIG 1111 Mallory\n"), None);
+    }
+}
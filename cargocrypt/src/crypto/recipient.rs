@@ -0,0 +1,214 @@
+//! Wrapping keys for X25519 recipients (age-style)
+//!
+//! A data key is wrapped for a recipient's public key with a fresh ephemeral
+//! X25519 key pair: the shared secret is stretched with HKDF-SHA256 (salted
+//! with both public keys) into a single-use ChaCha20-Poly1305 key. Only the
+//! holder of the recipient's secret key can unwrap it, so a list of wrapped
+//! copies grants access to exactly the listed recipients.
+//!
+//! A wrapped key is `ephemeral public key (32) || ciphertext`, base64-encoded
+//! when stored in text.
+
+use crate::crypto::{CryptoError, CryptoResult, defaults::KEY_LENGTH};
+use base64ct::{Base64, Encoding};
+use chacha20poly1305::{aead::{Aead, KeyInit}, ChaCha20Poly1305, Key, Nonce};
+use hkdf::Hkdf;
+use rand::rngs::OsRng;
+use sha2::Sha256;
+use std::io::Write;
+use std::path::Path;
+use x25519_dalek::{EphemeralSecret, PublicKey, StaticSecret};
+use zeroize::Zeroize;
+
+/// HKDF info string for deriving the wrapping key
+const WRAP_INFO: &[u8] = b"cargocrypt-recipient-wrap-v1";
+
+/// Length of an X25519 public key in bytes
+pub const PUBLIC_KEY_LENGTH: usize = 32;
+
+/// An X25519 key pair of a recipient (secret half is zeroized on drop)
+#[derive(Clone)]
+pub struct RecipientKeyPair {
+    secret: StaticSecret,
+}
+
+impl RecipientKeyPair {
+    /// Generate a new random key pair
+    pub fn generate() -> Self {
+        Self {
+            secret: StaticSecret::random_from_rng(OsRng),
+        }
+    }
+
+    /// Restore a key pair from its 32-byte secret key
+    pub fn from_secret_bytes(bytes: &[u8]) -> CryptoResult<Self> {
+        let mut secret: [u8; KEY_LENGTH] = bytes.try_into().map_err(|_| {
+            CryptoError::invalid_key(format!(
+                "X25519 secret key must be {} bytes, got {}",
+                KEY_LENGTH,
+                bytes.len()
+            ))
+        })?;
+        let pair = Self {
+            secret: StaticSecret::from(secret),
+        };
+        secret.zeroize();
+        Ok(pair)
+    }
+
+    /// Get the 32-byte secret key (handle with care)
+    pub fn secret_bytes(&self) -> [u8; KEY_LENGTH] {
+        self.secret.to_bytes()
+    }
+
+    /// Get the public key bytes
+    pub fn public_key(&self) -> [u8; PUBLIC_KEY_LENGTH] {
+        *PublicKey::from(&self.secret).as_bytes()
+    }
+
+    /// Get the public key as hex, the form members are listed with
+    pub fn public_key_hex(&self) -> String {
+        hex::encode(self.public_key())
+    }
+
+    /// Load a key pair saved with [`RecipientKeyPair::save`]
+    pub fn load<P: AsRef<Path>>(path: P) -> CryptoResult<Self> {
+        let path = path.as_ref();
+        let mut content = std::fs::read_to_string(path).map_err(|e| {
+            CryptoError::invalid_key(format!("Failed to read identity {}: {}", path.display(), e))
+        })?;
+        let bytes = hex::decode(content.trim());
+        content.zeroize();
+        let mut bytes = bytes.map_err(|_| CryptoError::invalid_key(format!("{} is not a hex X25519 identity", path.display())))?;
+        let pair = Self::from_secret_bytes(&bytes);
+        bytes.zeroize();
+        pair
+    }
+
+    /// Save the secret key as hex, readable only by the owner
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> CryptoResult<()> {
+        let path = path.as_ref();
+        let io_error = |e: std::io::Error| {
+            CryptoError::invalid_key(format!("Failed to write identity {}: {}", path.display(), e))
+        };
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).map_err(io_error)?;
+        }
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut secret = self.secret_bytes();
+        let mut encoded = hex::encode(secret);
+        secret.zeroize();
+        encoded.push('\n');
+        let result = options.open(path).and_then(|mut file| file.write_all(encoded.as_bytes()));
+        encoded.zeroize();
+        result.map_err(io_error)
+    }
+
+    /// Unwrap a key wrapped for this key pair
+    pub fn unwrap_key(&self, wrapped: &[u8]) -> CryptoResult<Vec<u8>> {
+        if wrapped.len() <= PUBLIC_KEY_LENGTH {
+            return Err(CryptoError::decryption("Wrapped key is truncated"));
+        }
+        let (ephemeral, ciphertext) = wrapped.split_at(PUBLIC_KEY_LENGTH);
+        let ephemeral: [u8; PUBLIC_KEY_LENGTH] = ephemeral.try_into().expect("split at the public key length");
+
+        let shared = self.secret.diffie_hellman(&PublicKey::from(ephemeral));
+        if !shared.was_contributory() {
+            return Err(CryptoError::decryption("X25519 exchange produced a non-contributory secret"));
+        }
+        let cipher = wrapping_cipher(shared.as_bytes(), &ephemeral, &self.public_key())?;
+        cipher
+            .decrypt(Nonce::from_slice(&[0u8; 12]), ciphertext)
+            .map_err(|_| CryptoError::decryption("Key was not wrapped for this identity"))
+    }
+
+    /// Unwrap a base64-encoded key wrapped for this key pair
+    pub fn unwrap_key_base64(&self, wrapped: &str) -> CryptoResult<Vec<u8>> {
+        let wrapped = Base64::decode_vec(wrapped)
+            .map_err(|e| CryptoError::decryption(format!("Invalid wrapped key encoding: {}", e)))?;
+        self.unwrap_key(&wrapped)
+    }
+}
+
+impl std::fmt::Debug for RecipientKeyPair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RecipientKeyPair")
+            .field("secret", &"[REDACTED]")
+            .field("public_key", &self.public_key_hex())
+            .finish()
+    }
+}
+
+/// Parse a hex X25519 public key as members list it
+pub fn parse_public_key(public_key: &str) -> CryptoResult<[u8; PUBLIC_KEY_LENGTH]> {
+    hex::decode(public_key.trim())
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| CryptoError::invalid_key(format!("Not a hex X25519 public key: '{}'", public_key)))
+}
+
+/// Wrap `key` so only the holder of the secret key of `recipient` can unwrap it
+pub fn wrap_key(key: &[u8], recipient: &[u8; PUBLIC_KEY_LENGTH]) -> CryptoResult<Vec<u8>> {
+    let ephemeral = EphemeralSecret::random_from_rng(OsRng);
+    let ephemeral_public = PublicKey::from(&ephemeral);
+    let shared = ephemeral.diffie_hellman(&PublicKey::from(*recipient));
+    if !shared.was_contributory() {
+        return Err(CryptoError::invalid_key("Recipient public key is a low-order point"));
+    }
+
+    // The wrapping key is used once, so a fixed nonce is safe
+    let cipher = wrapping_cipher(shared.as_bytes(), ephemeral_public.as_bytes(), recipient)?;
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&[0u8; 12]), key)
+        .map_err(|_| CryptoError::encryption("Failed to wrap key"))?;
+
+    let mut wrapped = Vec::with_capacity(PUBLIC_KEY_LENGTH + ciphertext.len());
+    wrapped.extend_from_slice(ephemeral_public.as_bytes());
+    wrapped.extend_from_slice(&ciphertext);
+    Ok(wrapped)
+}
+
+/// [`wrap_key`] encoded as base64
+pub fn wrap_key_base64(key: &[u8], recipient: &[u8; PUBLIC_KEY_LENGTH]) -> CryptoResult<String> {
+    Ok(Base64::encode_string(&wrap_key(key, recipient)?))
+}
+
+fn wrapping_cipher(shared: &[u8], ephemeral: &[u8; PUBLIC_KEY_LENGTH], recipient: &[u8; PUBLIC_KEY_LENGTH]) -> CryptoResult<ChaCha20Poly1305> {
+    let mut salt = [0u8; 2 * PUBLIC_KEY_LENGTH];
+    salt[..PUBLIC_KEY_LENGTH].copy_from_slice(ephemeral);
+    salt[PUBLIC_KEY_LENGTH..].copy_from_slice(recipient);
+
+    let mut key_bytes = [0u8; KEY_LENGTH];
+    Hkdf::<Sha256>::new(Some(&salt), shared)
+        .expand(WRAP_INFO, &mut key_bytes)
+        .map_err(|e| CryptoError::key_derivation(format!("HKDF expansion failed: {}", e)))?;
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(&key_bytes));
+    key_bytes.zeroize();
+    Ok(cipher)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_and_unwrap() {
+        let alice = RecipientKeyPair::generate();
+        let bob = RecipientKeyPair::generate();
+
+        let wrapped = wrap_key_base64(b"data key", &parse_public_key(&alice.public_key_hex()).unwrap()).unwrap();
+        assert_eq!(alice.unwrap_key_base64(&wrapped).unwrap(), b"data key");
+        assert!(bob.unwrap_key_base64(&wrapped).is_err());
+
+        let restored = RecipientKeyPair::from_secret_bytes(&alice.secret_bytes()).unwrap();
+        assert_eq!(restored.public_key(), alice.public_key());
+        assert!(parse_public_key("public_key_alice").is_err());
+        assert!(wrap_key(b"data key", &[0u8; 32]).is_err());
+    }
+}
@@ -1,23 +1,90 @@
 //! Encrypted secret storage with automatic zeroization
 
-use crate::crypto::{CryptoError, CryptoResult, defaults, DerivedKey};
+use crate::crypto::{CryptoError, CryptoResult, defaults, deterministic, DerivedKey, keyfile::{Keyfile, KeySource}, file_metadata::FileMetadata, Kdf, chunked::{self, ChunkLayout}, hybrid::{HybridEncapsulation, HybridKeyPair}, security::SecureBuffer};
 use chacha20poly1305::{ChaCha20Poly1305, Nonce, aead::{Aead, KeyInit}};
 use serde::{Deserialize, Serialize};
 use zeroize::ZeroizeOnDrop;
 use std::fmt;
 
+/// Magic bytes at the start of every header-bearing encrypted file
+pub const FILE_MAGIC: &[u8; 6] = b"CCRYPT";
+
+/// Current on-disk format version
+///
+/// Version 1 was a bare bincode encoding without magic or header and is still
+/// accepted by [`EncryptedSecret::from_bytes`].
+pub const FORMAT_VERSION: u8 = 2;
+
 /// An encrypted secret that automatically zeroizes plaintext data
 #[derive(Clone, Serialize, Deserialize)]
 pub struct EncryptedSecret {
     /// Encrypted data
     ciphertext: Vec<u8>,
+    /// Unencrypted header describing how the secret was encrypted
+    #[serde(flatten)]
+    header: SecretHeader,
+}
+
+/// Unencrypted header stored in front of the ciphertext
+///
+/// The header is serialized as JSON so new optional fields can be added
+/// without breaking files written by older versions.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SecretHeader {
     /// Nonce used for encryption
-    nonce: [u8; defaults::NONCE_LENGTH],
+    pub nonce: [u8; defaults::NONCE_LENGTH],
     /// Salt used for key derivation
-    salt: [u8; defaults::SALT_LENGTH],
+    pub salt: [u8; defaults::SALT_LENGTH],
     /// Optional metadata (not encrypted)
     #[serde(default)]
-    metadata: SecretMetadata,
+    pub metadata: SecretMetadata,
+    /// Key material the secret was encrypted with
+    #[serde(default, skip_serializing_if = "KeySource::is_password")]
+    pub key_source: KeySource,
+    /// Password-based key derivation function and its parameters
+    #[serde(default, skip_serializing_if = "Kdf::is_default")]
+    pub kdf: Kdf,
+    /// Named vault whose key the secret was encrypted with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vault: Option<String>,
+    /// Chunk layout when the ciphertext was encrypted in parallel chunks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunked: Option<ChunkLayout>,
+    /// Hybrid KEM data when the key is bound to a post-quantum recipient
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_quantum: Option<HybridEncapsulation>,
+}
+
+/// Layout of format version 1 files (bare bincode, no header)
+#[derive(Deserialize)]
+struct LegacyEncryptedSecret {
+    ciphertext: Vec<u8>,
+    nonce: [u8; defaults::NONCE_LENGTH],
+    salt: [u8; defaults::SALT_LENGTH],
+    metadata: LegacySecretMetadata,
+}
+
+/// Metadata layout of format version 1 files
+#[derive(Serialize, Deserialize)]
+struct LegacySecretMetadata {
+    description: Option<String>,
+    created_at: Option<u64>,
+    tags: Vec<String>,
+    secret_type: Option<SecretType>,
+}
+
+impl From<LegacySecretMetadata> for SecretMetadata {
+    fn from(legacy: LegacySecretMetadata) -> Self {
+        Self {
+            description: legacy.description,
+            created_at: legacy.created_at,
+            tags: legacy.tags,
+            secret_type: legacy.secret_type,
+            expires_at: None,
+            ttl_secs: None,
+            file: None,
+        }
+    }
 }
 
 /// Metadata associated with an encrypted secret
@@ -31,6 +98,15 @@ pub struct SecretMetadata {
     pub tags: Vec<String>,
     /// Secret type hint
     pub secret_type: Option<SecretType>,
+    /// Expiry timestamp (Unix timestamp), e.g. a certificate's valid-until date
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    /// Maximum age in seconds before the secret should be rotated, counted from `created_at`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl_secs: Option<u64>,
+    /// Timestamps and permissions of the original file, restored on decrypt
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<FileMetadata>,
 }
 
 /// Types of secrets that can be stored
@@ -117,6 +193,11 @@ impl PlaintextSecret {
     pub fn is_empty(&self) -> bool {
         self.data.is_empty()
     }
+
+    /// Move the data into a [`SecureBuffer`] without copying it
+    pub fn into_secure_buffer(mut self) -> SecureBuffer {
+        SecureBuffer::new(std::mem::take(&mut self.data))
+    }
 }
 
 impl fmt::Debug for PlaintextSecret {
@@ -146,38 +227,180 @@ impl EncryptedSecret {
     ) -> CryptoResult<Self> {
         // Generate random nonce
         let nonce_bytes = crate::crypto::keys::SecureRandom::generate_nonce()?;
-        let nonce = Nonce::from_slice(&nonce_bytes);
+        Self::encrypt_with_key_and_nonce(plaintext, key, nonce_bytes, None, metadata)
+    }
 
-        // Initialize cipher
-        let cipher = ChaCha20Poly1305::new(key.key());
+    /// Encrypt a plaintext secret with a derived key in parallel chunks
+    ///
+    /// Meant for large files; see [`crate::crypto::chunked`] for the layout.
+    /// Decryption detects the layout from the header.
+    pub fn encrypt_with_key_chunked(
+        plaintext: PlaintextSecret,
+        key: &DerivedKey,
+        chunk_size: usize,
+        metadata: Option<SecretMetadata>,
+    ) -> CryptoResult<Self> {
+        let nonce_bytes = crate::crypto::keys::SecureRandom::generate_nonce()?;
+        Self::encrypt_with_key_and_nonce(plaintext, key, nonce_bytes, Some(chunk_size), metadata)
+    }
+
+    /// Encrypt deterministically: the nonce is derived from the key, `path` and plaintext
+    ///
+    /// See [`crate::crypto::deterministic`] for when this is appropriate.
+    pub fn encrypt_with_key_deterministic(
+        plaintext: PlaintextSecret,
+        key: &DerivedKey,
+        path: &str,
+        metadata: Option<SecretMetadata>,
+    ) -> CryptoResult<Self> {
+        let nonce_bytes = deterministic::derive_nonce(key, path, plaintext.as_bytes());
+        Self::encrypt_with_key_and_nonce(plaintext, key, nonce_bytes, None, metadata)
+    }
 
-        // Encrypt the data
-        let ciphertext = cipher
-            .encrypt(nonce, plaintext.as_bytes())
-            .map_err(CryptoError::from)?;
+    fn encrypt_with_key_and_nonce(
+        plaintext: PlaintextSecret,
+        key: &DerivedKey,
+        nonce_bytes: [u8; defaults::NONCE_LENGTH],
+        chunk_size: Option<usize>,
+        metadata: Option<SecretMetadata>,
+    ) -> CryptoResult<Self> {
+        let (ciphertext, chunked) = match chunk_size {
+            Some(chunk_size) => {
+                let (ciphertext, layout) = chunked::seal(key, &nonce_bytes, plaintext.as_bytes(), chunk_size)?;
+                (ciphertext, Some(layout))
+            }
+            None => {
+                let cipher = ChaCha20Poly1305::new(key.key());
+                let ciphertext = cipher
+                    .encrypt(Nonce::from_slice(&nonce_bytes), plaintext.as_bytes())
+                    .map_err(CryptoError::from)?;
+                crate::crypto::nonce::record(key.key().as_slice(), &nonce_bytes, &ciphertext)?;
+                (ciphertext, None)
+            }
+        };
 
         Ok(Self {
             ciphertext,
-            nonce: nonce_bytes,
-            salt: *key.salt(),
-            metadata: metadata.unwrap_or_default(),
+            header: SecretHeader {
+                nonce: nonce_bytes,
+                salt: *key.salt(),
+                metadata: metadata.unwrap_or_default(),
+                key_source: KeySource::Password,
+                kdf: Kdf::Argon2id,
+                vault: None,
+                chunked,
+                post_quantum: None,
+            },
         })
     }
 
+    /// Record the key material used for encryption
+    pub(crate) fn with_key_source(mut self, key_source: KeySource) -> Self {
+        self.header.key_source = key_source;
+        self
+    }
+
+    /// Record the password-based key derivation function used for encryption
+    pub(crate) fn with_kdf(mut self, kdf: Kdf) -> Self {
+        self.header.kdf = kdf;
+        self
+    }
+
+    /// Record the hybrid KEM data of a key bound to a post-quantum recipient
+    pub(crate) fn with_post_quantum(mut self, encapsulation: Option<HybridEncapsulation>) -> Self {
+        self.header.post_quantum = encapsulation;
+        self
+    }
+
+    /// Fail for secrets that also need a post-quantum recipient's key
+    pub(crate) fn check_not_post_quantum(&self) -> CryptoResult<()> {
+        match &self.header.post_quantum {
+            Some(encapsulation) => Err(CryptoError::invalid_key(format!(
+                "Secret is bound to post-quantum recipient {}; decrypt it with that hybrid key",
+                encapsulation.recipient
+            ))),
+            None => Ok(()),
+        }
+    }
+
     /// Decrypt the secret with a password
     pub fn decrypt_with_password(&self, password: &str) -> CryptoResult<PlaintextSecret> {
-        let key = DerivedKey::from_password_with_salt(password, &self.salt)?;
+        self.check_not_post_quantum()?;
+        if let Some(vault) = &self.header.vault {
+            return Err(CryptoError::invalid_key(format!("Secret belongs to vault '{}'; decrypt it with --vault {}", vault, vault)));
+        }
+        if self.header.key_source.requires_keyfile() {
+            return Err(CryptoError::invalid_key("Secret was encrypted with a keyfile"));
+        }
+
+        let key = DerivedKey::from_password_with_kdf(password, &self.header.salt, &self.header.kdf)?;
         self.decrypt_with_key(&key)
     }
 
-    /// Decrypt the secret with a derived key
+    /// Decrypt the secret with a keyfile, plus the password if one was mixed in
+    pub fn decrypt_with_keyfile(&self, keyfile: &Keyfile, password: Option<&str>) -> CryptoResult<PlaintextSecret> {
+        self.check_not_post_quantum()?;
+        let password_key = match (self.header.key_source, password) {
+            (KeySource::Password, _) => {
+                return Err(CryptoError::invalid_key("Secret was encrypted with a password, not a keyfile"));
+            }
+            (KeySource::Keyfile, _) => None,
+            (KeySource::KeyfileAndPassword, Some(password)) => {
+                Some(DerivedKey::from_password_with_kdf(password, &self.header.salt, &self.header.kdf)?)
+            }
+            (KeySource::KeyfileAndPassword, None) => {
+                return Err(CryptoError::invalid_key("Secret requires both the keyfile and a password"));
+            }
+        };
+
+        let key = keyfile.derive_key(&self.header.salt, password_key.as_ref())?;
+        self.decrypt_with_key(&key)
+    }
+
+    /// Decrypt a secret bound to a post-quantum recipient
+    ///
+    /// Needs the recipient's hybrid key pair as well as the password and/or
+    /// keyfile the secret was encrypted with.
+    pub fn decrypt_with_identity(
+        &self,
+        identity: &HybridKeyPair,
+        password: Option<&str>,
+        keyfile: Option<&Keyfile>,
+    ) -> CryptoResult<PlaintextSecret> {
+        let encapsulation = self
+            .header
+            .post_quantum
+            .as_ref()
+            .ok_or_else(|| CryptoError::invalid_key("Secret is not bound to a post-quantum recipient"))?;
+
+        let password_key = |password: Option<&str>| {
+            let password = password.ok_or_else(|| CryptoError::invalid_key("Secret requires a password"))?;
+            DerivedKey::from_password_with_kdf(password, &self.header.salt, &self.header.kdf)
+        };
+        let base = match (self.header.key_source, keyfile) {
+            (KeySource::Password, _) => password_key(password)?,
+            (KeySource::Keyfile, Some(keyfile)) => keyfile.derive_key(&self.header.salt, None)?,
+            (KeySource::KeyfileAndPassword, Some(keyfile)) => {
+                keyfile.derive_key(&self.header.salt, Some(&password_key(password)?))?
+            }
+            (_, None) => return Err(CryptoError::invalid_key("Secret was encrypted with a keyfile")),
+        };
+        self.decrypt_with_key(&identity.decapsulate(&base, encapsulation)?)
+    }
+
+    /// Decrypt the secret with the exact key it was encrypted with
     pub fn decrypt_with_key(&self, key: &DerivedKey) -> CryptoResult<PlaintextSecret> {
         // Verify the salt matches
-        if key.salt() != &self.salt {
+        if key.salt() != &self.header.salt {
             return Err(CryptoError::decryption("Salt mismatch"));
         }
 
-        let nonce = Nonce::from_slice(&self.nonce);
+        if let Some(layout) = &self.header.chunked {
+            let plaintext_bytes = chunked::open(key, &self.header.nonce, &self.ciphertext, layout)?;
+            return Ok(PlaintextSecret::from_bytes(plaintext_bytes));
+        }
+
+        let nonce = Nonce::from_slice(&self.header.nonce);
         let cipher = ChaCha20Poly1305::new(key.key());
 
         let plaintext_bytes = cipher
@@ -187,24 +410,60 @@ impl EncryptedSecret {
         Ok(PlaintextSecret::from_bytes(plaintext_bytes))
     }
 
+    /// Get the unencrypted header
+    pub fn header(&self) -> &SecretHeader {
+        &self.header
+    }
+
     /// Get the metadata
     pub fn metadata(&self) -> &SecretMetadata {
-        &self.metadata
+        &self.header.metadata
     }
 
     /// Update the metadata (does not re-encrypt)
     pub fn set_metadata(&mut self, metadata: SecretMetadata) {
-        self.metadata = metadata;
+        self.header.metadata = metadata;
     }
 
     /// Get the salt used for key derivation
     pub fn salt(&self) -> &[u8; defaults::SALT_LENGTH] {
-        &self.salt
+        &self.header.salt
     }
 
     /// Get the nonce used for encryption
     pub fn nonce(&self) -> &[u8; defaults::NONCE_LENGTH] {
-        &self.nonce
+        &self.header.nonce
+    }
+
+    /// Get the key material the secret was encrypted with
+    pub fn key_source(&self) -> KeySource {
+        self.header.key_source
+    }
+
+    /// Get the password-based key derivation function
+    pub fn kdf(&self) -> &Kdf {
+        &self.header.kdf
+    }
+
+    /// Get the vault the secret was encrypted in, if any
+    pub fn vault(&self) -> Option<&str> {
+        self.header.vault.as_deref()
+    }
+
+    /// Record the vault the secret was encrypted in
+    pub(crate) fn with_vault(mut self, vault: Option<&str>) -> Self {
+        self.header.vault = vault.map(str::to_string);
+        self
+    }
+
+    /// Get the chunk layout if the secret was encrypted in parallel chunks
+    pub fn chunk_layout(&self) -> Option<&ChunkLayout> {
+        self.header.chunked.as_ref()
+    }
+
+    /// Get the hybrid KEM data if the key is bound to a post-quantum recipient
+    pub fn post_quantum(&self) -> Option<&HybridEncapsulation> {
+        self.header.post_quantum.as_ref()
     }
 
     /// Get the ciphertext length
@@ -222,22 +481,96 @@ impl EncryptedSecret {
         serde_json::from_str(json).map_err(CryptoError::from)
     }
 
-    /// Serialize to bytes (bincode)
+    /// Serialize to the on-disk format
+    ///
+    /// Layout: `FILE_MAGIC | version (u8) | header length (u32 LE) | header JSON | ciphertext`
     pub fn to_bytes(&self) -> CryptoResult<Vec<u8>> {
-        bincode::serialize(self)
-            .map_err(|e| CryptoError::serialization(e.to_string()))
+        let header = serde_json::to_vec(&self.header)?;
+        let header_len = u32::try_from(header.len())
+            .map_err(|_| CryptoError::serialization("Header too large"))?;
+
+        let mut bytes = Vec::with_capacity(FILE_MAGIC.len() + 5 + header.len() + self.ciphertext.len());
+        bytes.extend_from_slice(FILE_MAGIC);
+        bytes.push(FORMAT_VERSION);
+        bytes.extend_from_slice(&header_len.to_le_bytes());
+        bytes.extend_from_slice(&header);
+        bytes.extend_from_slice(&self.ciphertext);
+        Ok(bytes)
+    }
+
+    /// Format version of serialized secret bytes (1 for legacy files without magic)
+    pub fn format_version(bytes: &[u8]) -> u8 {
+        match bytes.strip_prefix(FILE_MAGIC.as_slice()) {
+            Some(rest) => rest.first().copied().unwrap_or(0),
+            None => 1,
+        }
     }
 
-    /// Deserialize from bytes (bincode)
+    /// Deserialize from the on-disk format (current or legacy bincode)
     pub fn from_bytes(bytes: &[u8]) -> CryptoResult<Self> {
-        bincode::deserialize(bytes)
-            .map_err(|e| CryptoError::serialization(e.to_string()))
+        if !bytes.starts_with(FILE_MAGIC) {
+            return Self::from_legacy_bytes(bytes);
+        }
+
+        let rest = &bytes[FILE_MAGIC.len()..];
+        let (&version, rest) = rest
+            .split_first()
+            .ok_or_else(|| CryptoError::serialization("Truncated header: missing format version"))?;
+        if version != FORMAT_VERSION {
+            return Err(CryptoError::serialization(format!(
+                "Unsupported format version {} (expected {})",
+                version, FORMAT_VERSION
+            )));
+        }
+
+        if rest.len() < 4 {
+            return Err(CryptoError::serialization("Truncated header: missing header length"));
+        }
+        let (len_bytes, rest) = rest.split_at(4);
+        let header_len = u32::from_le_bytes([len_bytes[0], len_bytes[1], len_bytes[2], len_bytes[3]]) as usize;
+        if rest.len() < header_len {
+            return Err(CryptoError::serialization("Truncated header"));
+        }
+
+        let (header, ciphertext) = rest.split_at(header_len);
+        Ok(Self {
+            ciphertext: ciphertext.to_vec(),
+            header: serde_json::from_slice(header)?,
+        })
+    }
+
+    /// KDF recorded in the header of `bytes`, even when the rest cannot be read
+    pub(crate) fn peek_kdf(bytes: &[u8]) -> Option<Kdf> {
+        let rest = bytes.strip_prefix(FILE_MAGIC.as_slice())?.get(1..)?;
+        let header_len = u32::from_le_bytes(rest.get(..4)?.try_into().ok()?) as usize;
+        let header: SecretHeader = serde_json::from_slice(rest.get(4..)?.get(..header_len)?).ok()?;
+        Some(header.kdf)
+    }
+
+    /// Deserialize a format version 1 (bare bincode) secret
+    fn from_legacy_bytes(bytes: &[u8]) -> CryptoResult<Self> {
+        let legacy: LegacyEncryptedSecret = bincode::deserialize(bytes)
+            .map_err(|e| CryptoError::serialization(e.to_string()))?;
+
+        Ok(Self {
+            ciphertext: legacy.ciphertext,
+            header: SecretHeader {
+                nonce: legacy.nonce,
+                salt: legacy.salt,
+                metadata: legacy.metadata.into(),
+                key_source: KeySource::Password,
+                kdf: Kdf::Argon2id,
+                vault: None,
+                chunked: None,
+                post_quantum: None,
+            },
+        })
     }
 
     /// Create a new secret with updated encryption (re-encrypt with new password)
     pub fn reencrypt_with_password(&self, old_password: &str, new_password: &str) -> CryptoResult<Self> {
         let plaintext = self.decrypt_with_password(old_password)?;
-        Self::encrypt_with_password(plaintext, new_password, Some(self.metadata.clone()))
+        Self::encrypt_with_password(plaintext, new_password, Some(self.header.metadata.clone()))
     }
 
     /// Verify that the secret can be decrypted with the given password
@@ -250,9 +583,11 @@ impl fmt::Debug for EncryptedSecret {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("EncryptedSecret")
             .field("ciphertext_len", &self.ciphertext.len())
-            .field("nonce", &hex::encode(&self.nonce))
-            .field("salt", &hex::encode(&self.salt))
-            .field("metadata", &self.metadata)
+            .field("nonce", &hex::encode(self.header.nonce))
+            .field("salt", &hex::encode(self.header.salt))
+            .field("metadata", &self.header.metadata)
+            .field("chunked", &self.header.chunked)
+            .field("post_quantum", &self.header.post_quantum.as_ref().map(|e| &e.recipient))
             .finish()
     }
 }
@@ -270,6 +605,9 @@ impl SecretMetadata {
             ),
             tags: Vec::new(),
             secret_type: None,
+            expires_at: None,
+            ttl_secs: None,
+            file: None,
         }
     }
 
@@ -299,6 +637,29 @@ impl SecretMetadata {
         self
     }
 
+    /// Set or clear the expiry timestamp
+    pub fn set_expires_at(&mut self, expires_at: Option<u64>) -> &mut Self {
+        self.expires_at = expires_at;
+        self
+    }
+
+    /// Set or clear the rotation interval in seconds
+    pub fn set_ttl(&mut self, ttl_secs: Option<u64>) -> &mut Self {
+        self.ttl_secs = ttl_secs;
+        self
+    }
+
+    /// When the secret is due for rotation, if it has a TTL and a creation time
+    pub fn rotation_deadline(&self) -> Option<u64> {
+        Some(self.created_at?.saturating_add(self.ttl_secs?))
+    }
+
+    /// Set or clear the metadata of the original file
+    pub fn set_file(&mut self, file: Option<FileMetadata>) -> &mut Self {
+        self.file = file;
+        self
+    }
+
     /// Set the secret type
     pub fn set_type(&mut self, secret_type: SecretType) -> &mut Self {
         self.secret_type = Some(secret_type);
@@ -387,6 +748,98 @@ mod tests {
         assert_eq!(decrypted.as_string().unwrap(), secret_data);
     }
 
+    #[test]
+    fn test_binary_format_roundtrip() {
+        let plaintext = PlaintextSecret::from_string("format test".to_string());
+        let encrypted = EncryptedSecret::encrypt_with_password(
+            plaintext,
+            "test_password_123",
+            Some(SecretMetadata::with_description("Binary format")),
+        ).unwrap();
+
+        let bytes = encrypted.to_bytes().unwrap();
+        assert!(bytes.starts_with(FILE_MAGIC));
+        assert_eq!(bytes[FILE_MAGIC.len()], FORMAT_VERSION);
+
+        let parsed = EncryptedSecret::from_bytes(&bytes).unwrap();
+        assert_eq!(parsed.metadata().description.as_deref(), Some("Binary format"));
+        let decrypted = parsed.decrypt_with_password("test_password_123").unwrap();
+        assert_eq!(decrypted.as_string().unwrap(), "format test");
+
+        // The KDF is still readable when the rest of the file is not
+        let mut unsupported = bytes.clone();
+        unsupported[FILE_MAGIC.len()] = FORMAT_VERSION + 1;
+        assert!(EncryptedSecret::from_bytes(&unsupported).is_err());
+        assert_eq!(EncryptedSecret::peek_kdf(&unsupported), Some(Kdf::Argon2id));
+        assert_eq!(EncryptedSecret::peek_kdf(&bytes[..FILE_MAGIC.len() + 3]), None);
+    }
+
+    #[test]
+    fn test_chunked_roundtrip() {
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let key = DerivedKey::from_password_with_random_salt("test_password_123").unwrap();
+        let encrypted = EncryptedSecret::encrypt_with_key_chunked(
+            PlaintextSecret::from_bytes(data.clone()),
+            &key,
+            4096,
+            None,
+        ).unwrap();
+        assert_eq!(encrypted.chunk_layout(), Some(&ChunkLayout { chunk_size: 4096, chunks: 3 }));
+
+        // The layout survives the on-disk format and selects chunked decryption
+        let parsed = EncryptedSecret::from_bytes(&encrypted.to_bytes().unwrap()).unwrap();
+        assert_eq!(parsed.chunk_layout(), encrypted.chunk_layout());
+        assert_eq!(parsed.decrypt_with_password("test_password_123").unwrap().as_bytes(), data.as_slice());
+    }
+
+    #[test]
+    fn test_legacy_bincode_format() {
+        #[derive(Serialize)]
+        struct V1<'a> {
+            ciphertext: &'a [u8],
+            nonce: [u8; defaults::NONCE_LENGTH],
+            salt: [u8; defaults::SALT_LENGTH],
+            metadata: LegacySecretMetadata,
+        }
+
+        let plaintext = PlaintextSecret::from_string("legacy".to_string());
+        let encrypted = EncryptedSecret::encrypt_with_password(plaintext, "test_password_123", None).unwrap();
+        let legacy = bincode::serialize(&V1 {
+            ciphertext: &encrypted.ciphertext,
+            nonce: *encrypted.nonce(),
+            salt: *encrypted.salt(),
+            metadata: LegacySecretMetadata {
+                description: None,
+                created_at: encrypted.metadata().created_at,
+                tags: Vec::new(),
+                secret_type: None,
+            },
+        }).unwrap();
+
+        let parsed = EncryptedSecret::from_bytes(&legacy).unwrap();
+        let decrypted = parsed.decrypt_with_password("test_password_123").unwrap();
+        assert_eq!(decrypted.as_string().unwrap(), "legacy");
+    }
+
+    #[test]
+    fn test_keyfile_roundtrip() {
+        let keyfile = Keyfile::generate().unwrap();
+        let salt = crate::crypto::SecureRandom::generate_salt().unwrap();
+        let key = keyfile.derive_key(&salt, None).unwrap();
+        let plaintext = PlaintextSecret::from_string("ci secret".to_string());
+        let encrypted = EncryptedSecret::encrypt_with_key(plaintext, &key, None)
+            .unwrap()
+            .with_key_source(KeySource::Keyfile);
+
+        let parsed = EncryptedSecret::from_bytes(&encrypted.to_bytes().unwrap()).unwrap();
+        assert_eq!(parsed.key_source(), KeySource::Keyfile);
+        let decrypted = parsed.decrypt_with_keyfile(&keyfile, None).unwrap();
+        assert_eq!(decrypted.as_string().unwrap(), "ci secret");
+
+        assert!(parsed.decrypt_with_password("test_password_123").is_err());
+        assert!(parsed.decrypt_with_keyfile(&Keyfile::generate().unwrap(), None).is_err());
+    }
+
     #[test]
     fn test_reencryption() {
         let secret_data = "This is a secret message!";
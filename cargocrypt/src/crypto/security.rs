@@ -1,7 +1,9 @@
 //! Security hardening utilities for cryptographic operations
 
+use std::fmt;
+use std::ops::Deref;
 use std::time::{Duration, Instant};
-use zeroize::Zeroize;
+use zeroize::{Zeroize, ZeroizeOnDrop};
 
 /// Timing defense mechanism to prevent timing attacks
 pub struct TimingDefense {
@@ -64,6 +66,106 @@ impl Drop for SecureBuffer {
     }
 }
 
+/// Zeroizing byte buffer, the byte counterpart of [`SecureString`]
+pub type SecureBytes = SecureBuffer;
+
+impl From<Vec<u8>> for SecureBuffer {
+    fn from(data: Vec<u8>) -> Self {
+        Self::new(data)
+    }
+}
+
+impl fmt::Debug for SecureBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecureBuffer([REDACTED; {} bytes])", self.data.len())
+    }
+}
+
+/// String for passwords and other secrets that is zeroized on drop
+///
+/// Growing the string never leaves a copy behind: the contents move to a
+/// larger buffer and the old one is wiped. `Debug` is redacted and there is
+/// no `Display`, so a password cannot end up in a log line by accident.
+/// Derefs to `str`, so it can be passed wherever a `&str` password is taken.
+#[derive(Clone, Default, Zeroize, ZeroizeOnDrop)]
+pub struct SecureString {
+    inner: String,
+}
+
+impl SecureString {
+    /// Create an empty string
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an empty string that can hold `capacity` bytes without growing
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self { inner: String::with_capacity(capacity) }
+    }
+
+    /// The secret as a string slice
+    pub fn as_str(&self) -> &str {
+        &self.inner
+    }
+
+    /// Append a character, e.g. from a key press
+    pub fn push(&mut self, c: char) {
+        if self.inner.len() + c.len_utf8() > self.inner.capacity() {
+            self.regrow((self.inner.capacity() * 2).max(32), self.inner.len());
+        }
+        self.inner.push(c);
+    }
+
+    /// Remove the last character
+    pub fn pop(&mut self) -> Option<char> {
+        let c = self.inner.chars().next_back()?;
+        self.regrow(self.inner.capacity(), self.inner.len() - c.len_utf8());
+        Some(c)
+    }
+
+    /// Move the first `len` bytes into a fresh buffer and wipe the old one
+    fn regrow(&mut self, capacity: usize, len: usize) {
+        let mut regrown = String::with_capacity(capacity);
+        regrown.push_str(&self.inner[..len]);
+        self.inner.zeroize();
+        self.inner = regrown;
+    }
+}
+
+impl From<String> for SecureString {
+    fn from(inner: String) -> Self {
+        Self { inner }
+    }
+}
+
+impl From<&str> for SecureString {
+    fn from(s: &str) -> Self {
+        Self { inner: s.to_string() }
+    }
+}
+
+impl Deref for SecureString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.inner
+    }
+}
+
+impl PartialEq for SecureString {
+    fn eq(&self, other: &Self) -> bool {
+        constant_time_compare(self.inner.as_bytes(), other.inner.as_bytes())
+    }
+}
+
+impl Eq for SecureString {}
+
+impl fmt::Debug for SecureString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecureString([REDACTED])")
+    }
+}
+
 /// Secure random number generation with validation
 pub struct SecureRandom;
 
@@ -213,4 +315,23 @@ mod tests {
         buffer.zeroize();
         assert_eq!(buffer.as_slice(), &[0, 0, 0, 0]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_secure_string() {
+        let mut password = SecureString::with_capacity(1);
+        for c in "pässwörd".chars() {
+            password.push(c);
+        }
+        assert_eq!(password.as_str(), "pässwörd");
+        assert_eq!(password.pop(), Some('d'));
+        assert_eq!(&*password, "pässwör");
+
+        assert_eq!(password, SecureString::from("pässwör"));
+        assert_ne!(password, SecureString::from("pässwö"));
+        assert_eq!(format!("{:?}", password), "SecureString([REDACTED])");
+
+        password.zeroize();
+        assert!(password.is_empty());
+        assert_eq!(SecureString::new().pop(), None);
+    }
+}
@@ -0,0 +1,165 @@
+//! Shamir secret sharing over GF(2^8)
+//!
+//! A secret is split byte by byte: each byte is the constant term of a random
+//! polynomial of degree `threshold - 1`, and share `x` holds the values of
+//! those polynomials at `x`. Any `threshold` shares determine the
+//! polynomials by Lagrange interpolation; fewer reveal nothing about the
+//! secret. Arithmetic uses the AES field polynomial `x^8 + x^4 + x^3 + x + 1`
+//! without lookup tables, so it does not index memory by secret values.
+
+use crate::crypto::{CryptoError, CryptoResult};
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// One share of a split secret
+#[derive(Clone, Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+pub struct Share {
+    /// Point the polynomials were evaluated at (1 to 255)
+    pub index: u8,
+    /// One byte per byte of the secret
+    #[serde(with = "hex::serde")]
+    pub value: Vec<u8>,
+}
+
+impl std::fmt::Debug for Share {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Share")
+            .field("index", &self.index)
+            .field("value", &"[REDACTED]")
+            .finish()
+    }
+}
+
+/// Split `secret` into `count` shares, any `threshold` of which recover it
+pub fn split(secret: &[u8], threshold: u8, count: u8) -> CryptoResult<Vec<Share>> {
+    if threshold == 0 || threshold > count {
+        return Err(CryptoError::invalid_input(format!(
+            "Threshold must be between 1 and the number of shares ({}), got {}",
+            count, threshold
+        )));
+    }
+    if secret.is_empty() {
+        return Err(CryptoError::invalid_input("Cannot split an empty secret"));
+    }
+
+    let mut shares: Vec<Share> = (1..=count)
+        .map(|index| Share {
+            index,
+            value: Vec::with_capacity(secret.len()),
+        })
+        .collect();
+    let mut coefficients = vec![0u8; threshold as usize];
+    for &byte in secret {
+        coefficients[0] = byte;
+        OsRng.fill_bytes(&mut coefficients[1..]);
+        for share in &mut shares {
+            // Horner's rule, highest coefficient first
+            let value = coefficients.iter().rev().fold(0u8, |acc, &c| mul(acc, share.index) ^ c);
+            share.value.push(value);
+        }
+    }
+    coefficients.zeroize();
+    Ok(shares)
+}
+
+/// Recover the secret from at least `threshold` shares of it
+///
+/// With fewer shares than the threshold the result is a wrong secret, not an
+/// error; callers check it, e.g. by decrypting with it.
+pub fn combine(shares: &[Share]) -> CryptoResult<Vec<u8>> {
+    let Some(first) = shares.first() else {
+        return Err(CryptoError::invalid_input("No shares to combine"));
+    };
+    for (i, share) in shares.iter().enumerate() {
+        if share.index == 0 {
+            return Err(CryptoError::invalid_input("Share index 0 would be the secret itself"));
+        }
+        if share.value.len() != first.value.len() {
+            return Err(CryptoError::invalid_input("Shares have different lengths; they belong to different secrets"));
+        }
+        if shares[..i].iter().any(|other| other.index == share.index) {
+            return Err(CryptoError::invalid_input(format!("Share {} was given twice", share.index)));
+        }
+    }
+
+    // Lagrange basis polynomials evaluated at 0
+    let weights: Vec<u8> = shares
+        .iter()
+        .map(|share| {
+            shares
+                .iter()
+                .filter(|other| other.index != share.index)
+                .fold(1u8, |acc, other| mul(acc, div(other.index, other.index ^ share.index)))
+        })
+        .collect();
+
+    let secret = (0..first.value.len())
+        .map(|i| {
+            shares
+                .iter()
+                .zip(&weights)
+                .fold(0u8, |acc, (share, &weight)| acc ^ mul(share.value[i], weight))
+        })
+        .collect();
+    Ok(secret)
+}
+
+/// Multiplication in GF(2^8)
+fn mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0u8;
+    for _ in 0..8 {
+        product ^= a & 0u8.wrapping_sub(b & 1);
+        let carry = 0u8.wrapping_sub(a >> 7);
+        a = (a << 1) ^ (carry & 0x1b);
+        b >>= 1;
+    }
+    product
+}
+
+/// Division in GF(2^8); `b` must not be 0
+fn div(a: u8, b: u8) -> u8 {
+    // b^254 is the inverse of b
+    let mut inverse = 1u8;
+    let mut power = b;
+    for bit in 0..8 {
+        if (254u8 >> bit) & 1 == 1 {
+            inverse = mul(inverse, power);
+        }
+        power = mul(power, power);
+    }
+    mul(a, inverse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field_arithmetic() {
+        // Example from FIPS-197 section 4.2
+        assert_eq!(mul(0x57, 0x83), 0xc1);
+        for b in 1..=255u8 {
+            assert_eq!(mul(div(1, b), b), 1);
+        }
+    }
+
+    #[test]
+    fn test_any_threshold_of_shares_recovers_the_secret() {
+        let secret = b"0123456789abcdef0123456789abcdef".to_vec();
+        let shares = split(&secret, 3, 5).unwrap();
+        assert_eq!(shares.len(), 5);
+
+        for picked in [[0, 1, 2], [4, 2, 0], [1, 3, 4]] {
+            let subset: Vec<Share> = picked.iter().map(|&i| shares[i].clone()).collect();
+            assert_eq!(combine(&subset).unwrap(), secret);
+        }
+        assert_eq!(combine(&shares).unwrap(), secret);
+        assert_ne!(combine(&shares[..2]).unwrap(), secret);
+
+        let twice = [shares[0].clone(), shares[0].clone(), shares[1].clone()];
+        assert!(combine(&twice).is_err());
+        assert!(split(&secret, 4, 3).is_err());
+        assert_eq!(combine(&split(&secret, 1, 1).unwrap()).unwrap(), secret);
+    }
+}
@@ -0,0 +1,206 @@
+//! Ed25519 detached signatures for encrypted artifacts
+//!
+//! Signatures are stored next to the signed file as a small JSON document
+//! (`<file>.sig`) carrying the signer's public key, so a file can be verified
+//! without any other context. Callers that care about *who* signed should
+//! additionally compare the public key against a trusted value.
+
+use crate::crypto::{CryptoError, CryptoResult, defaults::KEY_LENGTH};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+/// Algorithm identifier recorded in detached signatures
+pub const SIGNATURE_ALGORITHM: &str = "ed25519";
+
+/// File extension used for detached signatures
+pub const SIGNATURE_EXTENSION: &str = "sig";
+
+/// An Ed25519 signing key pair (secret half is zeroized on drop)
+#[derive(Clone)]
+pub struct SigningKeyPair {
+    signing_key: SigningKey,
+}
+
+impl SigningKeyPair {
+    /// Generate a new random key pair
+    pub fn generate() -> Self {
+        Self {
+            signing_key: SigningKey::generate(&mut OsRng),
+        }
+    }
+
+    /// Restore a key pair from its 32-byte secret seed
+    pub fn from_secret_bytes(bytes: &[u8]) -> CryptoResult<Self> {
+        let seed: [u8; KEY_LENGTH] = bytes.try_into().map_err(|_| {
+            CryptoError::invalid_key(format!(
+                "Ed25519 secret key must be {} bytes, got {}",
+                KEY_LENGTH,
+                bytes.len()
+            ))
+        })?;
+
+        Ok(Self {
+            signing_key: SigningKey::from_bytes(&seed),
+        })
+    }
+
+    /// Get the 32-byte secret seed (handle with care)
+    pub fn secret_bytes(&self) -> [u8; KEY_LENGTH] {
+        self.signing_key.to_bytes()
+    }
+
+    /// Get the public key bytes
+    pub fn public_key(&self) -> [u8; KEY_LENGTH] {
+        self.signing_key.verifying_key().to_bytes()
+    }
+
+    /// Get the public key as hex
+    pub fn public_key_hex(&self) -> String {
+        hex::encode(self.public_key())
+    }
+
+    /// Short fingerprint of the public key for display
+    pub fn fingerprint(&self) -> String {
+        fingerprint(&self.public_key())
+    }
+
+    /// Sign a message
+    pub fn sign(&self, message: &[u8]) -> DetachedSignature {
+        let signature = self.signing_key.sign(message);
+
+        DetachedSignature {
+            algorithm: SIGNATURE_ALGORITHM.to_string(),
+            public_key: self.public_key(),
+            signature: signature.to_bytes(),
+            signed_at: crate::utils::unix_now(),
+        }
+    }
+}
+
+impl std::fmt::Debug for SigningKeyPair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SigningKeyPair")
+            .field("secret", &"[REDACTED]")
+            .field("public_key", &self.public_key_hex())
+            .finish()
+    }
+}
+
+/// A detached signature over some artifact
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DetachedSignature {
+    /// Signature algorithm (always `ed25519`)
+    pub algorithm: String,
+    /// Public key of the signer
+    #[serde(with = "hex")]
+    pub public_key: [u8; KEY_LENGTH],
+    /// Raw signature bytes
+    #[serde(with = "hex")]
+    pub signature: [u8; 64],
+    /// Signing time (Unix timestamp)
+    pub signed_at: u64,
+}
+
+impl DetachedSignature {
+    /// Verify the signature against a message using the embedded public key
+    pub fn verify(&self, message: &[u8]) -> CryptoResult<()> {
+        if self.algorithm != SIGNATURE_ALGORITHM {
+            return Err(CryptoError::invalid_signature(format!(
+                "Unsupported signature algorithm '{}'",
+                self.algorithm
+            )));
+        }
+
+        let verifying_key = VerifyingKey::from_bytes(&self.public_key)
+            .map_err(|e| CryptoError::invalid_signature(format!("Invalid public key: {}", e)))?;
+        let signature = Signature::from_bytes(&self.signature);
+
+        verifying_key
+            .verify(message, &signature)
+            .map_err(|_| CryptoError::invalid_signature("Signature does not match the data"))
+    }
+
+    /// Verify the signature and require it to come from a specific public key
+    pub fn verify_with_key(&self, message: &[u8], expected_public_key: &[u8]) -> CryptoResult<()> {
+        if !crate::crypto::constant_time_compare(&self.public_key, expected_public_key) {
+            return Err(CryptoError::invalid_signature("Signed by an unexpected key"));
+        }
+        self.verify(message)
+    }
+
+    /// Short fingerprint of the signer's public key
+    pub fn signer_fingerprint(&self) -> String {
+        fingerprint(&self.public_key)
+    }
+
+    /// Serialize to JSON
+    pub fn to_json(&self) -> CryptoResult<String> {
+        serde_json::to_string_pretty(self).map_err(CryptoError::from)
+    }
+
+    /// Deserialize from JSON
+    pub fn from_json(json: &str) -> CryptoResult<Self> {
+        serde_json::from_str(json).map_err(CryptoError::from)
+    }
+}
+
+/// Path of the detached signature for a file (`<file>.sig`)
+pub fn signature_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    let path = path.as_ref();
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(SIGNATURE_EXTENSION);
+    path.with_file_name(name)
+}
+
+/// SHA-256 based fingerprint of a public key (first 16 hex characters)
+fn fingerprint(public_key: &[u8]) -> String {
+    let digest = Sha256::digest(public_key);
+    hex::encode(&digest[..8])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let keypair = SigningKeyPair::generate();
+        let signature = keypair.sign(b"encrypted artifact");
+
+        assert!(signature.verify(b"encrypted artifact").is_ok());
+        assert!(signature.verify(b"tampered artifact").is_err());
+        assert!(signature.verify_with_key(b"encrypted artifact", &keypair.public_key()).is_ok());
+
+        let other = SigningKeyPair::generate();
+        assert!(signature.verify_with_key(b"encrypted artifact", &other.public_key()).is_err());
+    }
+
+    #[test]
+    fn test_signature_json_roundtrip() {
+        let keypair = SigningKeyPair::generate();
+        let signature = keypair.sign(b"data");
+
+        let parsed = DetachedSignature::from_json(&signature.to_json().unwrap()).unwrap();
+        assert_eq!(parsed, signature);
+        assert_eq!(parsed.signer_fingerprint(), keypair.fingerprint());
+    }
+
+    #[test]
+    fn test_keypair_restore() {
+        let keypair = SigningKeyPair::generate();
+        let restored = SigningKeyPair::from_secret_bytes(&keypair.secret_bytes()).unwrap();
+        assert_eq!(keypair.public_key(), restored.public_key());
+
+        assert!(SigningKeyPair::from_secret_bytes(&[1u8; 16]).is_err());
+    }
+
+    #[test]
+    fn test_signature_path() {
+        assert_eq!(signature_path("secrets.env.enc"), PathBuf::from("secrets.env.enc.sig"));
+        assert_eq!(signature_path("dir/file"), PathBuf::from("dir/file.sig"));
+    }
+}
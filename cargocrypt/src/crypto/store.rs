@@ -1,11 +1,29 @@
 //! Secret storage traits and implementations
 
 use async_trait::async_trait;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::RwLock;
-use crate::crypto::{EncryptedSecret, PlaintextSecret};
-use crate::error::CryptoResult;
+use crate::crypto::{
+    CryptoError, DerivedKey, EncryptedSecret, Kdf, KeyDerivationParams, KeySource, Keyfile, PlaintextSecret,
+    SecretMetadata, SecretType, SecureRandom,
+};
+use crate::error::{CargoCryptError, CryptoResult};
+use crate::lock::FileLock;
+use crate::utils::unix_now;
+
+/// File name of a secret store, in `.cargocrypt/` or a vault directory
+pub const SECRETS_FILE: &str = "secrets.enc";
+
+/// Version of the decrypted secret store document
+///
+/// Version 1 documents kept only the current value of each secret.
+const DOCUMENT_VERSION: u32 = 2;
+
+/// Longest accepted secret name
+const MAX_NAME_LENGTH: usize = 128;
 
 /// Trait for secret storage backends
 #[async_trait]
@@ -69,6 +87,390 @@ impl SecretStore for MemorySecretStore {
     }
 }
 
+/// Check that `name` is usable as a secret name: letters, digits, `_`, `-`, `.` and `/`
+pub fn validate_secret_name(name: &str) -> CryptoResult<()> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_NAME_LENGTH
+        && name.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'_' | b'-' | b'.' | b'/'));
+    if valid {
+        return Ok(());
+    }
+    Err(CargoCryptError::Validation {
+        message: format!("Invalid secret name '{}'", name),
+        errors: vec![format!("Use up to {} letters, digits, '_', '-', '.' and '/'", MAX_NAME_LENGTH)],
+        warnings: Vec::new(),
+    })
+}
+
+/// One saved value of a secret
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SecretVersion {
+    /// Version number, counting from 1
+    pub version: u32,
+    /// When the value was saved (Unix timestamp)
+    pub created_at: u64,
+    /// Who saved it, from git's `user.name` and `user.email`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    /// Version whose value this one brought back, for rollbacks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub restored_from: Option<u32>,
+    /// The value, encrypted under the store key
+    secret: EncryptedSecret,
+}
+
+impl SecretVersion {
+    /// The encrypted value
+    pub fn secret(&self) -> &EncryptedSecret {
+        &self.secret
+    }
+}
+
+/// All versions of one secret, oldest first
+#[derive(Clone, Default, Serialize, Deserialize)]
+struct SecretEntry {
+    versions: Vec<SecretVersion>,
+}
+
+impl SecretEntry {
+    fn current(&self) -> Option<&SecretVersion> {
+        self.versions.last()
+    }
+
+    fn find(&self, version: u32) -> Option<&SecretVersion> {
+        self.versions.iter().find(|saved| saved.version == version)
+    }
+
+    /// Add `secret` as the newest version and return its number
+    fn push(&mut self, secret: EncryptedSecret, author: Option<String>, restored_from: Option<u32>) -> u32 {
+        let version = self.current().map_or(1, |current| current.version + 1);
+        self.versions.push(SecretVersion {
+            version,
+            created_at: unix_now(),
+            author,
+            restored_from,
+            secret,
+        });
+        version
+    }
+}
+
+/// Decrypted contents of a secret store file
+#[derive(Clone, Serialize, Deserialize)]
+struct SecretDocument {
+    version: u32,
+    secrets: BTreeMap<String, SecretEntry>,
+}
+
+/// Layout of version 1 documents
+#[derive(Deserialize)]
+struct LegacyDocument {
+    secrets: BTreeMap<String, EncryptedSecret>,
+}
+
+impl From<LegacyDocument> for SecretDocument {
+    fn from(legacy: LegacyDocument) -> Self {
+        let secrets = legacy
+            .secrets
+            .into_iter()
+            .map(|(name, secret)| {
+                let version = SecretVersion {
+                    version: 1,
+                    created_at: secret.metadata().created_at.unwrap_or(0),
+                    author: None,
+                    restored_from: None,
+                    secret,
+                };
+                (name, SecretEntry { versions: vec![version] })
+            })
+            .collect();
+        Self { version: DOCUMENT_VERSION, secrets }
+    }
+}
+
+/// Secret store kept in a single encrypted file
+///
+/// The file is an ordinary encrypted file whose plaintext maps names to
+/// entries. Each entry is encrypted again under the same key with a nonce of
+/// its own, so names and metadata can be listed without decrypting values.
+/// The key is derived once when the store is opened, and every change
+/// rewrites the file atomically.
+///
+/// Saving a secret adds a version rather than replacing the value, so
+/// earlier values stay available for auditing and rollback until the secret
+/// is removed.
+pub struct FileSecretStore {
+    path: PathBuf,
+    key: DerivedKey,
+    key_source: KeySource,
+    kdf: Kdf,
+    vault: Option<String>,
+    author: Option<String>,
+    document: RwLock<SecretDocument>,
+}
+
+impl FileSecretStore {
+    /// Open the store at `path` with a password, or start a new one there
+    ///
+    /// A new store derives its key with `kdf`; an existing one uses the KDF
+    /// recorded in its header. Nothing is written until the first change.
+    pub async fn open_with_password<P: AsRef<Path>>(path: P, password: &str, kdf: Kdf) -> CryptoResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        let Some(file) = read_file(&path).await? else {
+            let key = DerivedKey::from_password(password, &KeyDerivationParams::new_random()?.with_kdf(kdf))?;
+            return Ok(Self::new(path, key, KeySource::Password, kdf, None, empty_document()));
+        };
+
+        if file.key_source() != KeySource::Password || file.vault().is_some() {
+            return Err(CryptoError::invalid_key(format!("{} is not protected by a password", path.display())).into());
+        }
+        let key = DerivedKey::from_password_with_kdf(password, file.salt(), file.kdf())?;
+        let document = decrypt_document(&path, &file, &key)?;
+        Ok(Self::new(path, key, KeySource::Password, *file.kdf(), None, document))
+    }
+
+    /// Open the store at `path` with a keyfile, or start a new one there
+    ///
+    /// `vault` names the vault whose key `keyfile` is, and is recorded in the
+    /// store's header.
+    pub async fn open_with_keyfile<P: AsRef<Path>>(path: P, keyfile: &Keyfile, vault: Option<&str>) -> CryptoResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        let vault = vault.map(str::to_string);
+        let Some(file) = read_file(&path).await? else {
+            let key = keyfile.derive_key(&SecureRandom::generate_salt()?, None)?;
+            return Ok(Self::new(path, key, KeySource::Keyfile, Kdf::Argon2id, vault, empty_document()));
+        };
+
+        if file.key_source() != KeySource::Keyfile || file.vault() != vault.as_deref() {
+            return Err(CryptoError::invalid_key(format!("{} is protected by a different key", path.display())).into());
+        }
+        let key = keyfile.derive_key(file.salt(), None)?;
+        let document = decrypt_document(&path, &file, &key)?;
+        Ok(Self::new(path, key, KeySource::Keyfile, Kdf::Argon2id, vault, document))
+    }
+
+    fn new(
+        path: PathBuf,
+        key: DerivedKey,
+        key_source: KeySource,
+        kdf: Kdf,
+        vault: Option<String>,
+        document: SecretDocument,
+    ) -> Self {
+        let author = path.ancestors().find(|dir| dir.exists()).and_then(crate::utils::author);
+        Self {
+            path,
+            key,
+            key_source,
+            kdf,
+            vault,
+            author,
+            document: RwLock::new(document),
+        }
+    }
+
+    /// Record `author` on changes instead of the git user
+    pub fn with_author<S: Into<String>>(mut self, author: S) -> Self {
+        self.author = Some(author.into());
+        self
+    }
+
+    /// Path of the store file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Vault the store belongs to
+    pub fn vault(&self) -> Option<&str> {
+        self.vault.as_deref()
+    }
+
+    /// Encrypt `value` under the store key and save it as a new version of
+    /// `name`, returning the version number
+    pub async fn set(&self, name: &str, value: PlaintextSecret, metadata: SecretMetadata) -> CryptoResult<u32> {
+        validate_secret_name(name)?;
+        let secret = self.seal(value, metadata)?;
+        self.update(|document| {
+            let entry = document.secrets.entry(name.to_string()).or_default();
+            Ok(entry.push(secret, self.author.clone(), None))
+        })
+        .await
+    }
+
+    /// Decrypt the current value of `name`
+    pub async fn get(&self, name: &str) -> CryptoResult<Option<PlaintextSecret>> {
+        let document = self.document.read().await;
+        let Some(current) = document.secrets.get(name).and_then(SecretEntry::current) else { return Ok(None) };
+        Ok(Some(current.secret.decrypt_with_key(&self.key)?))
+    }
+
+    /// Decrypt an earlier value of `name`
+    pub async fn get_version(&self, name: &str, version: u32) -> CryptoResult<Option<PlaintextSecret>> {
+        let document = self.document.read().await;
+        let Some(saved) = document.secrets.get(name).and_then(|entry| entry.find(version)) else { return Ok(None) };
+        Ok(Some(saved.secret.decrypt_with_key(&self.key)?))
+    }
+
+    /// All versions of `name`, oldest first; empty if there is no such secret
+    pub async fn history(&self, name: &str) -> Vec<SecretVersion> {
+        let document = self.document.read().await;
+        document.secrets.get(name).map(|entry| entry.versions.clone()).unwrap_or_default()
+    }
+
+    /// Make the value of `version` current again by saving it as a new
+    /// version, which is returned
+    pub async fn rollback(&self, name: &str, version: u32) -> CryptoResult<u32> {
+        self.update(|document| {
+            let entry = document.secrets.get_mut(name).ok_or_else(|| CargoCryptError::Config {
+                message: format!("No secret named '{}'", name),
+                suggestion: Some("Run `cargocrypt secret list` to see stored secrets".to_string()),
+            })?;
+            let saved = entry.find(version).ok_or_else(|| CargoCryptError::Config {
+                message: format!("Secret '{}' has no version {}", name, version),
+                suggestion: Some(format!("Run `cargocrypt secret history {}` to see its versions", name)),
+            })?;
+            if entry.current().map(|current| current.version) == Some(version) {
+                return Err(CargoCryptError::Config {
+                    message: format!("Version {} is already the current value of '{}'", version, name),
+                    suggestion: None,
+                });
+            }
+            let secret = saved.secret.clone();
+            Ok(entry.push(secret, self.author.clone(), Some(version)))
+        })
+        .await
+    }
+
+    /// Remove the secret `name` with all its versions, returning whether it existed
+    pub async fn remove(&self, name: &str) -> CryptoResult<bool> {
+        if !self.document.read().await.secrets.contains_key(name) {
+            return Ok(false);
+        }
+        self.update(|document| {
+            document.secrets.remove(name);
+            Ok(())
+        })
+        .await?;
+        Ok(true)
+    }
+
+    /// Encrypt `value` under the store key, recording how the key is derived
+    fn seal(&self, value: PlaintextSecret, metadata: SecretMetadata) -> CryptoResult<EncryptedSecret> {
+        let secret = EncryptedSecret::encrypt_with_key(value, &self.key, Some(metadata))?
+            .with_key_source(self.key_source)
+            .with_vault(self.vault.as_deref());
+        Ok(match self.key_source {
+            KeySource::Password => secret.with_kdf(self.kdf),
+            _ => secret,
+        })
+    }
+
+    /// Apply `change` to a copy of the document, save it, then keep it
+    ///
+    /// The store file is locked and re-read first, so changes another process
+    /// saved since this store was opened are not lost.
+    async fn update<R>(&self, change: impl FnOnce(&mut SecretDocument) -> CryptoResult<R>) -> CryptoResult<R> {
+        let mut document = self.document.write().await;
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let _lock = FileLock::acquire(&lock_file(&self.path), &self.path, crate::lock::wait()).await?;
+        if let Some(file) = read_file(&self.path).await? {
+            *document = decrypt_document(&self.path, &file, &self.key)?;
+        }
+
+        let mut updated = document.clone();
+        let result = change(&mut updated)?;
+
+        let mut metadata = SecretMetadata::with_type(SecretType::Config);
+        metadata.set_description("CargoCrypt secret store");
+        let file = self.seal(PlaintextSecret::from_bytes(serde_json::to_vec(&updated)?), metadata)?;
+
+        crate::core::write_file_synced(&self.path, &file.to_bytes()?).await?;
+        *document = updated;
+        Ok(result)
+    }
+}
+
+impl std::fmt::Debug for FileSecretStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileSecretStore")
+            .field("path", &self.path)
+            .field("vault", &self.vault)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl SecretStore for FileSecretStore {
+    async fn store(&self, key: &str, secret: EncryptedSecret) -> CryptoResult<()> {
+        validate_secret_name(key)?;
+        self.update(|document| {
+            document.secrets.entry(key.to_string()).or_default().push(secret, self.author.clone(), None);
+            Ok(())
+        })
+        .await
+    }
+
+    async fn retrieve(&self, key: &str) -> CryptoResult<Option<EncryptedSecret>> {
+        let document = self.document.read().await;
+        Ok(document.secrets.get(key).and_then(SecretEntry::current).map(|current| current.secret.clone()))
+    }
+
+    async fn delete(&self, key: &str) -> CryptoResult<()> {
+        self.remove(key).await.map(|_| ())
+    }
+
+    async fn list(&self) -> CryptoResult<Vec<String>> {
+        Ok(self.document.read().await.secrets.keys().cloned().collect())
+    }
+}
+
+fn empty_document() -> SecretDocument {
+    SecretDocument {
+        version: DOCUMENT_VERSION,
+        secrets: BTreeMap::new(),
+    }
+}
+
+/// Lock file guarding writes to the store at `path`
+fn lock_file(path: &Path) -> PathBuf {
+    let file_name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    path.with_file_name(format!(".{}.lock", file_name))
+}
+
+async fn read_file(path: &Path) -> CryptoResult<Option<EncryptedSecret>> {
+    match tokio::fs::read(path).await {
+        Ok(bytes) => Ok(Some(EncryptedSecret::from_bytes(&bytes)?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn decrypt_document(path: &Path, file: &EncryptedSecret, key: &DerivedKey) -> CryptoResult<SecretDocument> {
+    let plaintext = file.decrypt_with_key(key).map_err(|e| match e {
+        CryptoError::AuthenticationFailed => CargoCryptError::Auth {
+            message: format!("Wrong password or key for {}", path.display()),
+            retry_suggestion: None,
+        },
+        e => e.into(),
+    })?;
+
+    #[derive(Deserialize)]
+    struct Version {
+        version: u32,
+    }
+
+    match serde_json::from_slice::<Version>(plaintext.as_bytes())?.version {
+        1 => Ok(serde_json::from_slice::<LegacyDocument>(plaintext.as_bytes())?.into()),
+        version if version > DOCUMENT_VERSION => Err(CargoCryptError::Config {
+            message: format!("{} was written by a newer version of CargoCrypt", path.display()),
+            suggestion: Some("Upgrade cargocrypt to read it".to_string()),
+        }),
+        _ => Ok(serde_json::from_slice(plaintext.as_bytes())?),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -78,7 +480,10 @@ mod tests {
         let store = MemorySecretStore::new();
         
         // Test store and retrieve
-        let secret = EncryptedSecret::default();
+        let secret = crate::crypto::CryptoEngine::with_performance_profile(crate::crypto::PerformanceProfile::Fast)
+            .encrypt_string("value", "test_password", crate::crypto::EncryptionOptions::new())
+            .await
+            .unwrap();
         store.store("test", secret.clone()).await.unwrap();
         
         let retrieved = store.retrieve("test").await.unwrap();
@@ -94,4 +499,125 @@ mod tests {
         let retrieved = store.retrieve("test").await.unwrap();
         assert!(retrieved.is_none());
     }
-}
\ No newline at end of file
+
+    fn test_kdf() -> Kdf {
+        Kdf::Argon2idCustom { m_cost: 8192, t_cost: 1, p_cost: 1 }
+    }
+
+    #[tokio::test]
+    async fn test_file_store_round_trip() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join(".cargocrypt").join(SECRETS_FILE);
+
+        let store = FileSecretStore::open_with_password(&path, "store password", test_kdf()).await.unwrap();
+        assert!(!path.exists());
+        store
+            .set("DATABASE_URL", PlaintextSecret::from_string("postgres://db".to_string()), SecretMetadata::new())
+            .await
+            .unwrap();
+        store
+            .set("stripe/api_key", PlaintextSecret::from_string("sk_live".to_string()), SecretMetadata::new())
+            .await
+            .unwrap();
+        assert!(store.remove("stripe/api_key").await.unwrap());
+        assert!(!store.remove("stripe/api_key").await.unwrap());
+
+        let reopened = FileSecretStore::open_with_password(&path, "store password", Kdf::Argon2id).await.unwrap();
+        assert_eq!(reopened.list().await.unwrap(), ["DATABASE_URL"]);
+        let value = reopened.get("DATABASE_URL").await.unwrap().unwrap();
+        assert_eq!(value.as_string().unwrap(), "postgres://db");
+        assert!(reopened.get("missing").await.unwrap().is_none());
+
+        // Names are not visible in the file
+        let bytes = std::fs::read(&path).unwrap();
+        assert!(!bytes.windows(12).any(|w| w == b"DATABASE_URL"));
+
+        assert!(matches!(
+            FileSecretStore::open_with_password(&path, "wrong password", test_kdf()).await,
+            Err(CargoCryptError::Auth { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_file_store_with_keyfile() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join(SECRETS_FILE);
+        let keyfile = Keyfile::generate().unwrap();
+
+        let store = FileSecretStore::open_with_keyfile(&path, &keyfile, Some("prod")).await.unwrap();
+        store.set("token", PlaintextSecret::from_string("t0k3n".to_string()), SecretMetadata::new()).await.unwrap();
+        assert!(store.set("has space", PlaintextSecret::from_string(String::new()), SecretMetadata::new()).await.is_err());
+
+        let reopened = FileSecretStore::open_with_keyfile(&path, &keyfile, Some("prod")).await.unwrap();
+        assert_eq!(reopened.get("token").await.unwrap().unwrap().as_string().unwrap(), "t0k3n");
+        assert!(FileSecretStore::open_with_keyfile(&path, &keyfile, Some("dev")).await.is_err());
+        assert!(FileSecretStore::open_with_password(&path, "password", test_kdf()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_file_store_keeps_concurrent_changes() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join(SECRETS_FILE);
+        let keyfile = Keyfile::generate().unwrap();
+        let first = FileSecretStore::open_with_keyfile(&path, &keyfile, None).await.unwrap();
+        first.set("a", PlaintextSecret::from_string("1".to_string()), SecretMetadata::new()).await.unwrap();
+
+        // A second process opens the store and changes it behind the first one's back
+        let second = FileSecretStore::open_with_keyfile(&path, &keyfile, None).await.unwrap();
+        second.set("b", PlaintextSecret::from_string("2".to_string()), SecretMetadata::new()).await.unwrap();
+        first.set("c", PlaintextSecret::from_string("3".to_string()), SecretMetadata::new()).await.unwrap();
+
+        let reopened = FileSecretStore::open_with_keyfile(&path, &keyfile, None).await.unwrap();
+        assert_eq!(reopened.list().await.unwrap(), ["a", "b", "c"]);
+    }
+
+    #[tokio::test]
+    async fn test_file_store_versions() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join(SECRETS_FILE);
+        let value = |text: &str| PlaintextSecret::from_string(text.to_string());
+
+        let store = FileSecretStore::open_with_keyfile(&path, &Keyfile::generate().unwrap(), None)
+            .await
+            .unwrap()
+            .with_author("Ada <ada@example.com>");
+        assert_eq!(store.set("token", value("one"), SecretMetadata::new()).await.unwrap(), 1);
+        assert_eq!(store.set("token", value("two"), SecretMetadata::new()).await.unwrap(), 2);
+
+        assert_eq!(store.rollback("token", 1).await.unwrap(), 3);
+        assert_eq!(store.get("token").await.unwrap().unwrap().as_string().unwrap(), "one");
+        assert_eq!(store.get_version("token", 2).await.unwrap().unwrap().as_string().unwrap(), "two");
+        assert!(store.rollback("token", 3).await.is_err());
+        assert!(store.rollback("token", 7).await.is_err());
+
+        let history = store.history("token").await;
+        let versions: Vec<_> = history.iter().map(|saved| (saved.version, saved.restored_from)).collect();
+        assert_eq!(versions, [(1, None), (2, None), (3, Some(1))]);
+        assert_eq!(history[2].author.as_deref(), Some("Ada <ada@example.com>"));
+    }
+
+    #[tokio::test]
+    async fn test_file_store_reads_version_1_documents() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join(SECRETS_FILE);
+        let keyfile = Keyfile::generate().unwrap();
+
+        // Write a version 1 document through a store's key
+        let store = FileSecretStore::open_with_keyfile(&path, &keyfile, None).await.unwrap();
+        let entry = EncryptedSecret::encrypt_with_key(
+            PlaintextSecret::from_string("legacy".to_string()),
+            &store.key,
+            Some(SecretMetadata::new()),
+        )
+        .unwrap();
+        let legacy = serde_json::json!({ "version": 1, "secrets": { "token": entry } });
+        let file = store
+            .seal(PlaintextSecret::from_bytes(serde_json::to_vec(&legacy).unwrap()), SecretMetadata::new())
+            .unwrap();
+        std::fs::write(&path, file.to_bytes().unwrap()).unwrap();
+
+        let reopened = FileSecretStore::open_with_keyfile(&path, &keyfile, None).await.unwrap();
+        assert_eq!(reopened.get("token").await.unwrap().unwrap().as_string().unwrap(), "legacy");
+        assert_eq!(reopened.history("token").await.len(), 1);
+    }
+}
@@ -0,0 +1,160 @@
+//! Persistent cache of files known to be clean
+//!
+//! Most files of a project do not change between two scans. The cache in
+//! `.cargocrypt/scan-cache` remembers, per file, the SHA-256 of the content
+//! that was scanned without findings, so an unchanged file is not scanned
+//! again. Files with findings are never cached (and nothing about a secret is
+//! written to disk), so they are always reported.
+//!
+//! Every entry belongs to a rule set fingerprint covering the built-in
+//! patterns, custom rules, entropy settings and the cargocrypt version;
+//! when any of them changes the whole cache is discarded.
+
+use crate::error::CryptoResult;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// Version of the cache file format
+const CACHE_VERSION: u32 = 1;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheFile {
+    version: u32,
+    ruleset: String,
+    /// Content hash of each clean file, by path
+    clean: BTreeMap<String, String>,
+}
+
+/// Scan cache of one project
+#[derive(Debug)]
+pub struct ScanCache {
+    path: PathBuf,
+    ruleset: String,
+    clean: Mutex<BTreeMap<String, String>>,
+    dirty: AtomicBool,
+}
+
+impl ScanCache {
+    /// Location of the scan cache of a project
+    pub fn project_path<P: AsRef<Path>>(project_root: P) -> PathBuf {
+        project_root.as_ref().join(".cargocrypt").join("scan-cache")
+    }
+
+    /// Load the cache at `path` for the rule set `ruleset`
+    ///
+    /// A missing or unreadable cache, or one written for another rule set,
+    /// starts out empty.
+    pub fn load<P: AsRef<Path>>(path: P, ruleset: &str) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let clean = std::fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<CacheFile>(&bytes).ok())
+            .filter(|file| file.version == CACHE_VERSION && file.ruleset == ruleset)
+            .map(|file| file.clean)
+            .unwrap_or_default();
+
+        Self {
+            path,
+            ruleset: ruleset.to_string(),
+            clean: Mutex::new(clean),
+            dirty: AtomicBool::new(false),
+        }
+    }
+
+    /// Whether `file` was clean when its content hashed to `content_hash`
+    pub fn is_clean(&self, file: &Path, content_hash: &str) -> bool {
+        let clean = self.clean.lock().unwrap_or_else(|e| e.into_inner());
+        clean.get(&key(file)).is_some_and(|hash| hash == content_hash)
+    }
+
+    /// Record the result of scanning `file` with content hash `content_hash`
+    pub fn record(&self, file: &Path, content_hash: &str, is_clean: bool) {
+        let mut clean = self.clean.lock().unwrap_or_else(|e| e.into_inner());
+        let changed = if is_clean {
+            clean.insert(key(file), content_hash.to_string()).as_deref() != Some(content_hash)
+        } else {
+            clean.remove(&key(file)).is_some()
+        };
+        if changed {
+            self.dirty.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Number of files known to be clean
+    pub fn len(&self) -> usize {
+        self.clean.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    /// Whether no file is known to be clean
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Write the cache if it changed, dropping entries of deleted files
+    pub fn save(&self) -> CryptoResult<()> {
+        if !self.dirty.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        let mut clean = self.clean.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        clean.retain(|file, _| Path::new(file).exists());
+        let file = CacheFile {
+            version: CACHE_VERSION,
+            ruleset: self.ruleset.clone(),
+            clean,
+        };
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Write next to the cache and rename, so a concurrent scan never reads half a file
+        let temp = self.path.with_extension(format!("tmp{}", std::process::id()));
+        std::fs::write(&temp, serde_json::to_vec(&file)?)?;
+        std::fs::rename(&temp, &self.path)?;
+        self.dirty.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// Cache key of a file
+fn key(file: &Path) -> String {
+    std::path::absolute(file)
+        .unwrap_or_else(|_| file.to_path_buf())
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_scan_cache() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache_path = ScanCache::project_path(temp_dir.path());
+        let file = temp_dir.path().join("main.rs");
+        std::fs::write(&file, "fn main() {}").unwrap();
+
+        let cache = ScanCache::load(&cache_path, "rules-v1");
+        assert!(!cache.is_clean(&file, "hash-a"));
+        cache.record(&file, "hash-a", true);
+        cache.record(&temp_dir.path().join("deleted.rs"), "hash-b", true);
+        cache.save().unwrap();
+
+        let cache = ScanCache::load(&cache_path, "rules-v1");
+        assert!(cache.is_clean(&file, "hash-a"));
+        assert!(!cache.is_clean(&file, "hash-changed"));
+        assert_eq!(cache.len(), 1);
+
+        // A file with findings is forgotten
+        cache.record(&file, "hash-a", false);
+        assert!(!cache.is_clean(&file, "hash-a"));
+
+        // Another rule set invalidates everything
+        assert!(ScanCache::load(&cache_path, "rules-v2").is_empty());
+    }
+}
@@ -0,0 +1,198 @@
+//! Per-repository confidence calibration from labeled findings
+//!
+//! `cargocrypt scan feedback <id> --true-positive|--false-positive` labels a
+//! finding (ids are shown by `cargocrypt scan`). Labels are kept in
+//! `.cargocrypt/feedback.json`, which is meant to be committed, and every scan
+//! of the project:
+//! - hides findings labeled as false positives, and
+//! - scales the confidence of every finding of a rule by that rule's weight,
+//!   `(true positives + 1) / (labels + 1)`, so a rule that keeps being wrong in
+//!   this repository gradually drops below the reporting threshold while one
+//!   confirmed finding is enough to hold it back up.
+
+use crate::detection::{ConfidenceLevel, Finding};
+use crate::error::{CargoCryptError, CryptoResult};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Version of the feedback file format
+const FEEDBACK_VERSION: u32 = 1;
+
+/// Lowest weight a rule can get, so even a noisy rule still reports its strongest matches
+pub const MIN_RULE_WEIGHT: f64 = 0.1;
+
+/// Whether a finding is a real secret
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Label {
+    TruePositive,
+    FalsePositive,
+}
+
+impl fmt::Display for Label {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Label::TruePositive => f.write_str("true positive"),
+            Label::FalsePositive => f.write_str("false positive"),
+        }
+    }
+}
+
+/// The label of one finding
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LabeledFinding {
+    /// Rule that found it, see [`Finding::rule_id`]
+    pub rule_id: String,
+    pub label: Label,
+    /// File the finding was labeled in, for reference
+    pub file: String,
+    pub labeled_at: DateTime<Utc>,
+}
+
+/// Labels of a repository's findings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Feedback {
+    version: u32,
+    /// Labels by finding id, see [`Finding::id`]
+    #[serde(default)]
+    pub findings: BTreeMap<String, LabeledFinding>,
+}
+
+impl Default for Feedback {
+    fn default() -> Self {
+        Self {
+            version: FEEDBACK_VERSION,
+            findings: BTreeMap::new(),
+        }
+    }
+}
+
+impl Feedback {
+    /// Location of the labels of a project
+    pub fn project_path<P: AsRef<Path>>(project_root: P) -> PathBuf {
+        project_root.as_ref().join(".cargocrypt").join("feedback.json")
+    }
+
+    /// Load labels from `path`; a missing file has none
+    pub fn load<P: AsRef<Path>>(path: P) -> CryptoResult<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let feedback: Self = serde_json::from_slice(&std::fs::read(path)?).map_err(|e| CargoCryptError::Config {
+            message: format!("Invalid feedback file {}: {}", path.display(), e),
+            suggestion: Some("Fix or delete the file; it only holds finding labels".to_string()),
+        })?;
+        if feedback.version != FEEDBACK_VERSION {
+            return Err(CargoCryptError::Config {
+                message: format!("Unsupported feedback file version {} in {}", feedback.version, path.display()),
+                suggestion: Some("Upgrade cargocrypt".to_string()),
+            });
+        }
+        Ok(feedback)
+    }
+
+    /// Write the labels to `path`
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> CryptoResult<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut json = serde_json::to_string_pretty(self)?;
+        json.push('\n');
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+
+    /// Label `finding`, replacing an earlier label
+    pub fn record(&mut self, finding: &Finding, label: Label) {
+        self.findings.insert(
+            finding.id(),
+            LabeledFinding {
+                rule_id: finding.rule_id(),
+                label,
+                file: finding.file_path.to_string_lossy().replace('\\', "/"),
+                labeled_at: Utc::now(),
+            },
+        );
+    }
+
+    /// Confidence weight of `rule_id`, 1.0 when it has no false positives
+    pub fn rule_weight(&self, rule_id: &str) -> f64 {
+        let (mut labels, mut true_positives) = (0, 0);
+        for labeled in self.findings.values().filter(|labeled| labeled.rule_id == rule_id) {
+            labels += 1;
+            if labeled.label == Label::TruePositive {
+                true_positives += 1;
+            }
+        }
+        ((true_positives + 1) as f64 / (labels + 1) as f64).max(MIN_RULE_WEIGHT)
+    }
+
+    /// Weight of every labeled rule
+    pub fn rule_weights(&self) -> BTreeMap<String, f64> {
+        self.findings
+            .values()
+            .map(|labeled| (labeled.rule_id.clone(), self.rule_weight(&labeled.rule_id)))
+            .collect()
+    }
+
+    /// Hide labeled false positives and weight the confidence of the other findings
+    pub fn apply(&self, findings: &mut [Finding]) {
+        if self.findings.is_empty() {
+            return;
+        }
+        let weights = self.rule_weights();
+        for finding in findings {
+            if self.findings.get(&finding.id()).is_some_and(|labeled| labeled.label == Label::FalsePositive) {
+                finding.is_ignored = true;
+                continue;
+            }
+            if let Some(weight) = weights.get(&finding.rule_id()) {
+                finding.confidence *= weight;
+                finding.confidence_level = ConfidenceLevel::from_score(finding.confidence);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detection::findings::test_finding;
+    use tempfile::TempDir;
+
+    fn finding(value: &str) -> Finding {
+        test_finding("src/lib.rs", "High-Entropy String", value, 1, 0.8)
+    }
+
+    #[test]
+    fn test_feedback_weights() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = Feedback::project_path(temp_dir.path());
+        let mut feedback = Feedback::load(&path).unwrap();
+        assert_eq!(feedback.rule_weight("high-entropy-string"), 1.0);
+
+        feedback.record(&finding("c2VjcmV0IG5vdCBzZWNyZXQ"), Label::FalsePositive);
+        feedback.record(&finding("dGhpcyBpcyBmaW5l"), Label::FalsePositive);
+        feedback.record(&finding("dGhpcyBpcyBmaW5l"), Label::FalsePositive);
+        assert_eq!(feedback.findings.len(), 2);
+        assert!((feedback.rule_weight("high-entropy-string") - 1.0 / 3.0).abs() < 1e-9);
+        feedback.save(&path).unwrap();
+
+        let feedback = Feedback::load(&path).unwrap();
+        let mut findings = vec![finding("c2VjcmV0IG5vdCBzZWNyZXQ"), finding("YW5vdGhlciBvbmU")];
+        feedback.apply(&mut findings);
+        assert!(findings[0].is_ignored);
+        assert!(!findings[1].is_ignored);
+        assert!((findings[1].confidence - 0.8 / 3.0).abs() < 1e-9);
+
+        // A confirmed finding pulls the rule back up
+        let mut feedback = feedback;
+        feedback.record(&finding("YW5vdGhlciBvbmU"), Label::TruePositive);
+        assert_eq!(feedback.rule_weight("high-entropy-string"), 0.5);
+    }
+}
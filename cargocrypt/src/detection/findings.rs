@@ -1,6 +1,9 @@
 //! Detection findings and result types
 
+use crate::detection::patterns::{SecretCategory, SecretType, Severity};
+use crate::detection::verify::VerificationStatus;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::path::PathBuf;
 
 /// Confidence level for detection results
@@ -59,6 +62,9 @@ pub struct FoundSecret {
     pub column_number: usize,
     /// Whether the value was truncated for security
     pub is_truncated: bool,
+    /// SHA-256 of the full value (hex), for allowlisting it without storing it
+    #[serde(default)]
+    pub value_hash: String,
 }
 
 impl FoundSecret {
@@ -71,6 +77,7 @@ impl FoundSecret {
         line_number: usize,
         column_number: usize,
     ) -> Self {
+        let value_hash = hex::encode(Sha256::digest(value.as_bytes()));
         let is_truncated = value.len() > 50;
         let value = if is_truncated {
             format!("{}...", &value[..47])
@@ -86,6 +93,7 @@ impl FoundSecret {
             line_number,
             column_number,
             is_truncated,
+            value_hash,
         }
     }
 
@@ -116,6 +124,30 @@ pub struct Finding {
     pub entropy_score: Option<f64>,
     /// Surrounding context lines for review
     pub context_lines: Vec<String>,
+    /// How to fix this finding, when there is specific advice
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remediation: Option<String>,
+    /// Severity of the secret type, or as set by the custom rule that found it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub severity: Option<Severity>,
+    /// Provider or kind of the secret, for grouping reports
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<SecretCategory>,
+    /// Result of checking the credential against its service (`scan --verify`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verification: Option<VerificationStatus>,
+    /// Inline `cargocrypt:ignore` annotation that suppressed this finding
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suppression: Option<Suppression>,
+}
+
+/// An inline `cargocrypt:ignore` annotation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Suppression {
+    /// Line of the annotation (1-based)
+    pub line_number: usize,
+    /// Reason given with `reason="..."`, if any
+    pub reason: Option<String>,
 }
 
 impl Finding {
@@ -138,6 +170,11 @@ impl Finding {
             is_ignored: false,
             entropy_score: None,
             context_lines: Vec::new(),
+            remediation: None,
+            severity: None,
+            category: None,
+            verification: None,
+            suppression: None,
         }
     }
 
@@ -159,12 +196,44 @@ impl Finding {
         self
     }
 
+    /// Set remediation advice
+    pub fn with_remediation<S: Into<String>>(mut self, remediation: S) -> Self {
+        self.remediation = Some(remediation.into());
+        self
+    }
+
+    /// Set the severity
+    pub fn with_severity(mut self, severity: Severity) -> Self {
+        self.severity = Some(severity);
+        self
+    }
+
+    /// Set severity, category and remediation advice from the type of the secret
+    pub fn classified_as(mut self, secret_type: &SecretType) -> Self {
+        self.severity = Some(secret_type.severity_level());
+        self.category = Some(secret_type.category());
+        self.remediation = secret_type.remediation().map(str::to_string);
+        self
+    }
+
     /// Mark this finding as ignored
     pub fn ignore(mut self) -> Self {
         self.is_ignored = true;
         self
     }
 
+    /// Mark this finding as suppressed by an inline annotation
+    pub fn suppress(mut self, suppression: Suppression) -> Self {
+        self.is_ignored = true;
+        self.suppression = Some(suppression);
+        self
+    }
+
+    /// Check if an inline annotation suppressed this finding
+    pub fn is_suppressed(&self) -> bool {
+        self.suppression.is_some()
+    }
+
     /// Check if this is a high-confidence finding
     pub fn is_high_confidence(&self) -> bool {
         self.confidence >= 0.7
@@ -175,6 +244,36 @@ impl Finding {
         !self.is_ignored && self.confidence >= 0.3
     }
 
+    /// Stable identifier of the rule behind this finding, e.g. `aws-access-key`
+    ///
+    /// Derived from the secret type; findings of custom rules use the rule's id.
+    pub fn rule_id(&self) -> String {
+        let name = if self.secret.secret_type == "custom_rule" {
+            &self.detector_name
+        } else {
+            &self.secret.secret_type
+        };
+
+        let mut id = String::with_capacity(name.len());
+        for ch in name.chars() {
+            if ch.is_ascii_alphanumeric() {
+                id.push(ch.to_ascii_lowercase());
+            } else if !id.is_empty() && !id.ends_with('-') {
+                id.push('-');
+            }
+        }
+        id.trim_end_matches('-').to_string()
+    }
+
+    /// Short identifier of this finding, e.g. `3f2a9c01b7de`
+    ///
+    /// Derived from the rule and the secret, so it survives lines moving and
+    /// files being renamed, and the same secret found by another rule differs.
+    pub fn id(&self) -> String {
+        let digest = Sha256::digest(format!("{}:{}", self.rule_id(), self.secret.value_hash).as_bytes());
+        hex::encode(&digest[..6])
+    }
+
     /// Get a summary string for this finding
     pub fn summary(&self) -> String {
         format!(
@@ -204,6 +303,10 @@ impl Finding {
             desc.push_str(&format!(", context: {}", context));
         }
 
+        if let Some(remediation) = &self.remediation {
+            desc.push_str(&format!(". Fix: {}", remediation));
+        }
+
         desc
     }
 }
@@ -290,6 +393,13 @@ impl FindingCollection {
     }
 }
 
+/// A finding of `value` at the start of line `line` of `path`, for tests
+#[cfg(test)]
+pub(crate) fn test_finding<P: Into<PathBuf>>(path: P, secret_type: &str, value: &str, line: usize, confidence: f64) -> Finding {
+    let secret = FoundSecret::new(value.to_string(), secret_type.to_string(), 0, value.len(), line, 1);
+    Finding::new(path.into(), secret, confidence, "pattern_matcher".to_string())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -342,6 +452,8 @@ mod tests {
         assert!(finding.is_high_confidence());
         assert!(finding.should_report());
         assert_eq!(finding.confidence_level, ConfidenceLevel::High);
+        assert_eq!(finding.rule_id(), "api-key");
+        assert_eq!(finding.id().len(), 12);
     }
 
     #[test]
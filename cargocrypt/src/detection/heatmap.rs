@@ -0,0 +1,389 @@
+//! Secrets heatmap report
+//!
+//! Aggregates findings by directory and severity into a tree, so leads can see
+//! which components carry the most secret sprawl. The tree can be exported as
+//! JSON or as a self-contained HTML treemap (no external assets, safe to
+//! attach to a ticket). Secret values are never included, only counts.
+
+use crate::detection::{Finding, Severity};
+use crate::error::{CargoCryptError, CryptoResult};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+
+/// Placeholder in the HTML template replaced with the heatmap JSON
+const DATA_PLACEHOLDER: &str = "__HEATMAP_DATA__";
+
+/// Risk weight of a finding with the given severity
+pub fn risk_weight(severity: Severity) -> f64 {
+    match severity {
+        Severity::Critical => 10.0,
+        Severity::High => 5.0,
+        Severity::Medium => 3.0,
+        Severity::Low => 1.0,
+    }
+}
+
+/// A directory or file in the heatmap tree
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HeatmapNode {
+    /// Name of this directory or file
+    pub name: String,
+    /// Path relative to the scanned root
+    pub path: PathBuf,
+    /// Whether this node is a file
+    pub is_file: bool,
+    /// Total number of findings at or below this node
+    pub findings: usize,
+    /// Number of findings by severity
+    pub by_severity: BTreeMap<Severity, usize>,
+    /// Weighted risk score (sum of finding weights)
+    pub risk_score: f64,
+    /// Child directories and files, highest risk first
+    pub children: Vec<HeatmapNode>,
+}
+
+impl HeatmapNode {
+    fn new(name: String, path: PathBuf, is_file: bool) -> Self {
+        Self {
+            name,
+            path,
+            is_file,
+            ..Self::default()
+        }
+    }
+
+    fn record(&mut self, severity: Severity) {
+        self.findings += 1;
+        *self.by_severity.entry(severity).or_insert(0) += 1;
+        self.risk_score += risk_weight(severity);
+    }
+
+    fn child_mut(&mut self, name: &str, is_file: bool) -> &mut HeatmapNode {
+        let index = match self.children.iter().position(|c| c.name == name && c.is_file == is_file) {
+            Some(index) => index,
+            None => {
+                let path = self.path.join(name);
+                self.children.push(HeatmapNode::new(name.to_string(), path, is_file));
+                self.children.len() - 1
+            }
+        };
+        &mut self.children[index]
+    }
+
+    fn sort(&mut self) {
+        self.children.sort_by(|a, b| {
+            b.risk_score
+                .partial_cmp(&a.risk_score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        for child in &mut self.children {
+            child.sort();
+        }
+    }
+
+    /// Find a node by its path relative to the root
+    pub fn find<P: AsRef<Path>>(&self, path: P) -> Option<&HeatmapNode> {
+        let mut node = self;
+        for component in path.as_ref().components() {
+            let name = component.as_os_str().to_string_lossy();
+            node = node.children.iter().find(|c| c.name == name)?;
+        }
+        Some(node)
+    }
+}
+
+/// Findings aggregated by directory and severity
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecretsHeatmap {
+    /// Path that was scanned
+    pub scanned_path: PathBuf,
+    /// Generation time (RFC 3339)
+    pub generated_at: String,
+    /// Root of the directory tree
+    pub root: HeatmapNode,
+}
+
+impl SecretsHeatmap {
+    /// Build a heatmap from findings under `scanned_path`
+    pub fn from_findings<P: AsRef<Path>>(scanned_path: P, findings: &[Finding]) -> Self {
+        let scanned_path = scanned_path.as_ref();
+        let root_name = scanned_path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| scanned_path.display().to_string());
+        let mut root = HeatmapNode::new(root_name, PathBuf::new(), false);
+
+        for finding in findings.iter().filter(|f| !f.is_ignored) {
+            let relative = finding
+                .file_path
+                .strip_prefix(scanned_path)
+                .unwrap_or(&finding.file_path);
+            let components: Vec<String> = relative
+                .components()
+                .filter_map(|c| match c {
+                    Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+                    _ => None,
+                })
+                .collect();
+
+            // Findings without a known severity count as medium, as in policy checks
+            let severity = finding.severity.unwrap_or(Severity::Medium);
+            root.record(severity);
+
+            let mut node = &mut root;
+            for (index, name) in components.iter().enumerate() {
+                let is_file = index + 1 == components.len();
+                node = node.child_mut(name, is_file);
+                node.record(severity);
+            }
+        }
+
+        root.sort();
+
+        Self {
+            scanned_path: scanned_path.to_path_buf(),
+            generated_at: chrono::Utc::now().to_rfc3339(),
+            root,
+        }
+    }
+
+    /// Total number of findings
+    pub fn total_findings(&self) -> usize {
+        self.root.findings
+    }
+
+    /// Directories with the highest risk score (at most `limit`)
+    pub fn hotspots(&self, limit: usize) -> Vec<&HeatmapNode> {
+        let mut directories = Vec::new();
+        let mut stack: Vec<&HeatmapNode> = self.root.children.iter().collect();
+        while let Some(node) = stack.pop() {
+            if !node.is_file {
+                directories.push(node);
+                stack.extend(node.children.iter());
+            }
+        }
+
+        directories.sort_by(|a, b| b.risk_score.partial_cmp(&a.risk_score).unwrap_or(std::cmp::Ordering::Equal));
+        directories.truncate(limit);
+        directories
+    }
+
+    /// Export the heatmap to JSON
+    pub fn to_json(&self) -> CryptoResult<String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| CargoCryptError::detection_error(&format!("Failed to serialize heatmap: {}", e)))
+    }
+
+    /// Export the heatmap as a self-contained HTML treemap
+    pub fn to_html(&self) -> CryptoResult<String> {
+        let json = serde_json::to_string(self)
+            .map_err(|e| CargoCryptError::detection_error(&format!("Failed to serialize heatmap: {}", e)))?;
+        // Keep file names from closing the script element early
+        let json = json.replace("</", "<\\/");
+
+        Ok(HTML_TEMPLATE.replace(DATA_PLACEHOLDER, &json))
+    }
+}
+
+const HTML_TEMPLATE: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>CargoCrypt secrets heatmap</title>
+<style>
+  body { font-family: -apple-system, "Segoe UI", Helvetica, Arial, sans-serif; margin: 0; background: #111418; color: #e6e6e6; }
+  header { padding: 16px 24px; border-bottom: 1px solid #2a2f36; }
+  h1 { font-size: 18px; margin: 0 0 4px 0; }
+  #meta { font-size: 12px; color: #9aa4b2; }
+  #crumbs { padding: 8px 24px; font-size: 13px; }
+  #crumbs a { color: #7cb7ff; cursor: pointer; text-decoration: none; }
+  #map { position: relative; margin: 0 24px; height: 70vh; border: 1px solid #2a2f36; }
+  .cell { position: absolute; box-sizing: border-box; border: 1px solid #111418; overflow: hidden; padding: 4px; font-size: 12px; cursor: pointer; }
+  .cell .label { font-weight: 600; white-space: nowrap; text-overflow: ellipsis; overflow: hidden; }
+  .cell .count { font-size: 11px; opacity: 0.85; }
+  .cell.file { cursor: default; }
+  #legend { padding: 12px 24px; font-size: 12px; color: #9aa4b2; }
+  #legend span { display: inline-block; width: 12px; height: 12px; margin: 0 4px 0 12px; vertical-align: middle; }
+  table { margin: 0 24px 24px 24px; border-collapse: collapse; font-size: 13px; }
+  th, td { text-align: left; padding: 4px 12px; border-bottom: 1px solid #2a2f36; }
+  th { color: #9aa4b2; font-weight: 500; }
+  .empty { padding: 48px; text-align: center; color: #9aa4b2; }
+</style>
+</head>
+<body>
+<header>
+  <h1>Secrets heatmap</h1>
+  <div id="meta"></div>
+</header>
+<div id="crumbs"></div>
+<div id="map"></div>
+<div id="legend">Area = number of findings, colour = weighted risk per finding
+  <span style="background:#3b6e4a"></span>low<span style="background:#b58a2d"></span>medium<span style="background:#b5482d"></span>high<span style="background:#8f1d2c"></span>critical
+</div>
+<table id="severities"></table>
+<script>
+const report = __HEATMAP_DATA__;
+const SEVERITIES = ["critical", "high", "medium", "low"];
+const mapEl = document.getElementById("map");
+let trail = [report.root];
+
+function colour(node) {
+  const avg = node.findings ? node.risk_score / node.findings : 0;
+  if (avg >= 8) return "#8f1d2c";
+  if (avg >= 4.5) return "#b5482d";
+  if (avg >= 2.5) return "#b58a2d";
+  return "#3b6e4a";
+}
+
+// Squarified treemap layout
+function layout(nodes, x, y, w, h, out) {
+  const total = nodes.reduce((s, n) => s + n.findings, 0);
+  if (!nodes.length || total === 0) return;
+  const scale = (w * h) / total;
+  let row = [], rest = nodes.slice();
+  function worst(r, side) {
+    const areas = r.map(n => n.findings * scale);
+    const sum = areas.reduce((a, b) => a + b, 0);
+    const max = Math.max(...areas), min = Math.min(...areas);
+    return Math.max((side * side * max) / (sum * sum), (sum * sum) / (side * side * min));
+  }
+  while (rest.length) {
+    const side = Math.min(w, h);
+    const next = rest[0];
+    if (!row.length || worst(row.concat([next]), side) <= worst(row, side)) {
+      row.push(next); rest.shift(); continue;
+    }
+    [x, y, w, h] = place(row, x, y, w, h, scale, out);
+    row = [];
+  }
+  if (row.length) place(row, x, y, w, h, scale, out);
+}
+
+function place(row, x, y, w, h, scale, out) {
+  const sum = row.reduce((s, n) => s + n.findings * scale, 0);
+  if (w >= h) {
+    const cw = sum / h; let cy = y;
+    row.forEach(n => { const ch = (n.findings * scale) / cw; out.push([n, x, cy, cw, ch]); cy += ch; });
+    return [x + cw, y, w - cw, h];
+  }
+  const ch = sum / w; let cx = x;
+  row.forEach(n => { const cw = (n.findings * scale) / ch; out.push([n, cx, y, cw, ch]); cx += cw; });
+  return [x, y + ch, w, h - ch];
+}
+
+function render() {
+  const node = trail[trail.length - 1];
+  mapEl.textContent = "";
+  const crumbs = document.getElementById("crumbs");
+  crumbs.textContent = "";
+  trail.forEach((n, i) => {
+    if (i) crumbs.appendChild(document.createTextNode(" / "));
+    const a = document.createElement("a");
+    a.textContent = n.name || "(root)";
+    a.onclick = () => { trail = trail.slice(0, i + 1); render(); };
+    crumbs.appendChild(a);
+  });
+
+  const children = node.children.filter(c => c.findings > 0);
+  if (!children.length) {
+    const empty = document.createElement("div");
+    empty.className = "empty";
+    empty.textContent = node.findings ? "No further breakdown" : "No findings";
+    mapEl.appendChild(empty);
+  }
+  const cells = [];
+  layout(children, 0, 0, mapEl.clientWidth, mapEl.clientHeight, cells);
+  cells.forEach(([n, x, y, w, h]) => {
+    const cell = document.createElement("div");
+    cell.className = "cell" + (n.is_file ? " file" : "");
+    Object.assign(cell.style, { left: x + "px", top: y + "px", width: w + "px", height: h + "px", background: colour(n) });
+    cell.title = n.path + "\n" + n.findings + " findings, risk " + n.risk_score.toFixed(1);
+    const label = document.createElement("div");
+    label.className = "label"; label.textContent = n.name + (n.is_file ? "" : "/");
+    const count = document.createElement("div");
+    count.className = "count"; count.textContent = n.findings + " findings";
+    cell.append(label, count);
+    if (!n.is_file) cell.onclick = () => { trail.push(n); render(); };
+    mapEl.appendChild(cell);
+  });
+
+  const table = document.getElementById("severities");
+  table.textContent = "";
+  const head = table.insertRow();
+  ["Path", "Findings"].concat(SEVERITIES).concat(["Risk"]).forEach(t => {
+    const th = document.createElement("th"); th.textContent = t; head.appendChild(th);
+  });
+  children.forEach(n => {
+    const row = table.insertRow();
+    [n.path, n.findings].concat(SEVERITIES.map(s => n.by_severity[s] || 0)).concat([n.risk_score.toFixed(1)])
+      .forEach(v => { row.insertCell().textContent = v; });
+  });
+}
+
+document.getElementById("meta").textContent =
+  report.scanned_path + " - " + report.root.findings + " findings - generated " + report.generated_at;
+window.addEventListener("resize", render);
+render();
+</script>
+</body>
+</html>
+"##;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detection::findings::test_finding;
+
+    fn finding(path: &str, severity: Severity) -> Finding {
+        test_finding(path, "api_key", "value", 1, 0.8).with_severity(severity)
+    }
+
+    #[test]
+    fn test_heatmap_aggregation() {
+        let findings = vec![
+            finding("proj/services/api/config.env", Severity::Critical),
+            finding("proj/services/api/config.env", Severity::Medium),
+            finding("proj/services/web/app.js", Severity::Low),
+            test_finding("proj/README.md", "api_key", "value", 1, 0.95),
+        ];
+
+        let heatmap = SecretsHeatmap::from_findings("proj", &findings);
+        assert_eq!(heatmap.total_findings(), 4);
+
+        let services = heatmap.root.find("services").unwrap();
+        assert_eq!(services.findings, 3);
+        assert!(!services.is_file);
+        assert_eq!(services.by_severity[&Severity::Critical], 1);
+
+        let config = heatmap.root.find("services/api/config.env").unwrap();
+        assert!(config.is_file);
+        assert_eq!(config.findings, 2);
+        assert_eq!(config.risk_score, 13.0);
+
+        // No severity counts as medium
+        let readme = heatmap.root.find("README.md").unwrap();
+        assert_eq!(readme.by_severity[&Severity::Medium], 1);
+
+        // Highest risk first
+        assert_eq!(heatmap.root.children[0].name, "services");
+        assert_eq!(heatmap.hotspots(1)[0].path, PathBuf::from("services"));
+    }
+
+    #[test]
+    fn test_heatmap_exports() {
+        let findings = vec![finding("proj/</script>/x.env", Severity::High)];
+        let heatmap = SecretsHeatmap::from_findings("proj", &findings);
+
+        let json = heatmap.to_json().unwrap();
+        let parsed: SecretsHeatmap = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.root, heatmap.root);
+
+        let html = heatmap.to_html().unwrap();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(!html.contains(DATA_PLACEHOLDER));
+        assert_eq!(html.matches("</script>").count(), 1);
+        assert!(!html.contains("value"), "secret values must not be embedded");
+    }
+}
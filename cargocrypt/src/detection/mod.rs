@@ -36,18 +36,59 @@
 //! ```
 
 pub mod detector;
+pub mod allowlist;
+pub mod archive;
+pub mod binary;
+pub mod cache;
+pub mod feedback;
 pub mod patterns;
+pub mod encoded;
 pub mod entropy;
+pub mod language;
 pub mod rules;
+pub mod rule_packs;
 pub mod scanner;
 pub mod findings;
+pub mod priority;
+pub mod normalize;
+pub mod objects;
+pub mod policy;
+pub mod heatmap;
+pub mod history;
+pub mod sarif;
+pub mod siem;
+pub mod report;
+pub mod suppress;
+pub mod triage;
+pub mod rust_sinks;
+pub mod staged;
+pub mod verify;
+pub mod watch;
 
 pub use detector::{SecretDetector, ScanOptions, DetectionConfig};
-pub use patterns::{SecretPattern, SecretType, PatternMatch};
+pub use allowlist::{Allowlist, Denylist, FindingFilter};
+pub use archive::{ArchiveKind, ArchiveLimits};
+pub use binary::ExtractedStrings;
+pub use cache::ScanCache;
+pub use feedback::{Feedback, Label};
+pub use patterns::{SecretPattern, SecretType, SecretCategory, Severity, PatternMatch};
 pub use entropy::{EntropyAnalyzer, EntropyResult};
-pub use rules::{CustomRule, RuleEngine, RuleType};
-pub use scanner::{FileScanner, ScanResult};
-pub use findings::{Finding, ConfidenceLevel, FoundSecret};
+pub use language::Language;
+pub use rules::{CustomRule, RuleConfig, RuleEngine, RuleIssue, RuleType};
+pub use rule_packs::{PackSource, RulePack, RulePacks};
+pub use scanner::{CancellationToken, FileScanner, ScanProgress, ScanResult};
+pub use findings::{Finding, ConfidenceLevel, FoundSecret, Suppression};
+pub use policy::{Policy, PolicyMode, PolicyStage, StagePolicy};
+pub use priority::{GitActivity, FileActivity};
+pub use heatmap::{SecretsHeatmap, HeatmapNode};
+pub use history::{HistoryFinding, HistoryReport, HistoryScan};
+pub use sarif::SarifLog;
+pub use siem::SiemExport;
+pub use report::{ScanReport, ScanRecord, SuppressedRecord};
+pub use triage::{FindingsDb, SyncSummary, TrackedFinding, TriageState};
+pub use rust_sinks::RustSinkDetector;
+pub use verify::{VerificationStatus, Verifier};
+pub use watch::{SecretWatcher, WatchEvent};
 
 use crate::error::CryptoResult;
 use std::path::Path;
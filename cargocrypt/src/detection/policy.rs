@@ -0,0 +1,187 @@
+//! Which findings fail a commit, a push or a CI run
+//!
+//! `.cargocrypt/policy.toml` has one table per stage:
+//!
+//! ```toml
+//! [commit]
+//! mode = "warn"              # report findings but never block
+//!
+//! [push]
+//! min_severity = "high"      # block high and critical findings
+//! allow = ["generic-secret"] # rule ids that never block
+//!
+//! [ci]
+//! min_severity = "medium"
+//! block = ["private-key"]    # rule ids that always block
+//! ```
+//!
+//! The pre-commit hook and `cargocrypt scan --staged` gate with `commit`, the
+//! pre-push hook with `push` and `cargocrypt scan --ci` with `ci`. A stage
+//! that is not listed blocks every finding, as does a project without a
+//! policy, so teams can start with `mode = "warn"` and ratchet up.
+
+use crate::detection::{Finding, Severity};
+use crate::error::{CargoCryptError, CryptoResult};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Point at which findings are gated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicyStage {
+    Commit,
+    Push,
+    Ci,
+}
+
+impl fmt::Display for PolicyStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolicyStage::Commit => f.write_str("commit"),
+            PolicyStage::Push => f.write_str("push"),
+            PolicyStage::Ci => f.write_str("ci"),
+        }
+    }
+}
+
+/// Whether a stage can fail
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicyMode {
+    #[default]
+    Block,
+    Warn,
+}
+
+/// Gating rules of one stage
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StagePolicy {
+    pub mode: PolicyMode,
+    /// Lowest severity that blocks; findings without a severity count as medium
+    pub min_severity: Severity,
+    /// Rule ids that block whatever their severity
+    pub block: Vec<String>,
+    /// Rule ids that never block
+    pub allow: Vec<String>,
+}
+
+impl Default for StagePolicy {
+    fn default() -> Self {
+        Self {
+            mode: PolicyMode::Block,
+            min_severity: Severity::Low,
+            block: Vec::new(),
+            allow: Vec::new(),
+        }
+    }
+}
+
+impl StagePolicy {
+    /// Whether `finding` fails this stage
+    pub fn blocks(&self, finding: &Finding) -> bool {
+        if self.mode == PolicyMode::Warn || finding.is_ignored {
+            return false;
+        }
+        let rule_id = finding.rule_id();
+        if self.allow.contains(&rule_id) {
+            return false;
+        }
+        self.block.contains(&rule_id) || finding.severity.unwrap_or(Severity::Medium) >= self.min_severity
+    }
+
+    /// The findings that fail this stage
+    pub fn blocking<'a>(&self, findings: &'a [Finding]) -> Vec<&'a Finding> {
+        findings.iter().filter(|finding| self.blocks(finding)).collect()
+    }
+}
+
+/// A project's gating policy
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Policy {
+    pub commit: StagePolicy,
+    pub push: StagePolicy,
+    pub ci: StagePolicy,
+}
+
+impl Policy {
+    /// Location of the policy of a project
+    pub fn project_path<P: AsRef<Path>>(project_root: P) -> PathBuf {
+        project_root.as_ref().join(".cargocrypt").join("policy.toml")
+    }
+
+    /// Load the project's policy, blocking every finding when there is none
+    pub fn load_project<P: AsRef<Path>>(project_root: P) -> CryptoResult<Self> {
+        let path = Self::project_path(project_root);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Self::load(&path)
+    }
+
+    /// Load a policy file
+    pub fn load<P: AsRef<Path>>(path: P) -> CryptoResult<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)?;
+        toml::from_str(&content).map_err(|e| CargoCryptError::Config {
+            message: format!("Invalid policy in {}: {}", path.display(), e),
+            suggestion: Some("Stages are [commit], [push] and [ci], with mode, min_severity, block and allow".to_string()),
+        })
+    }
+
+    /// The rules of `stage`
+    pub fn stage(&self, stage: PolicyStage) -> &StagePolicy {
+        match stage {
+            PolicyStage::Commit => &self.commit,
+            PolicyStage::Push => &self.push,
+            PolicyStage::Ci => &self.ci,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detection::findings::test_finding;
+
+    fn finding(secret_type: &str, severity: Option<Severity>) -> Finding {
+        let mut finding = test_finding("src/main.rs", secret_type, "value", 1, 0.9);
+        finding.severity = severity;
+        finding
+    }
+
+    #[test]
+    fn stages_gate_by_mode_severity_and_rule() {
+        let policy: Policy = toml::from_str(
+            r#"
+            [commit]
+            mode = "warn"
+
+            [push]
+            min_severity = "high"
+            allow = ["aws-access-key"]
+
+            [ci]
+            min_severity = "critical"
+            block = ["jwt-token"]
+            "#,
+        )
+        .unwrap();
+
+        let aws = finding("aws_access_key", Some(Severity::Critical));
+        let jwt = finding("jwt_token", Some(Severity::Medium));
+        let unrated = finding("generic_secret", None);
+        let findings = vec![aws, jwt, unrated];
+
+        assert!(policy.stage(PolicyStage::Commit).blocking(&findings).is_empty());
+        assert!(policy.stage(PolicyStage::Push).blocking(&findings).is_empty());
+        let ci: Vec<_> = policy.stage(PolicyStage::Ci).blocking(&findings).iter().map(|f| f.rule_id()).collect();
+        assert_eq!(ci, ["aws-access-key", "jwt-token"]);
+
+        // Without a policy everything blocks, as `scan --staged` always did
+        assert_eq!(Policy::default().stage(PolicyStage::Push).blocking(&findings).len(), 3);
+        assert!(toml::from_str::<Policy>("[deploy]\nmode = \"warn\"").is_err());
+    }
+}
@@ -0,0 +1,232 @@
+//! Scan scheduling hints from git activity
+//!
+//! Files that were changed recently or churn often are the most likely places
+//! for a freshly leaked secret, so interactive scans look at them first. The
+//! statistics come from a bounded walk over recent history plus the working
+//! tree status; outside a git repository no hints are produced and files are
+//! scanned in walk order.
+
+use git2::{DiffOptions, Repository, StatusOptions};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Default number of commits inspected when collecting activity
+pub const DEFAULT_HISTORY_DEPTH: usize = 500;
+
+/// Half-life (in days) of the recency component of the priority score
+const RECENCY_HALF_LIFE_DAYS: f64 = 7.0;
+
+/// Score bonus for files with uncommitted changes
+///
+/// Larger than the combined maximum of recency and churn (1.0 each), so work in
+/// progress is always scanned before committed history.
+const UNCOMMITTED_BONUS: f64 = 3.0;
+
+/// Git activity recorded for a single file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FileActivity {
+    /// Number of inspected commits touching the file
+    pub commits: usize,
+    /// Time of the most recent commit touching the file (Unix timestamp)
+    pub last_modified: Option<i64>,
+    /// Whether the file has uncommitted or untracked changes
+    pub uncommitted: bool,
+}
+
+/// Per-file git activity for a repository
+#[derive(Debug, Clone)]
+pub struct GitActivity {
+    files: HashMap<PathBuf, FileActivity>,
+    max_commits: usize,
+    now: i64,
+}
+
+impl GitActivity {
+    /// Collect activity for the repository containing `path`
+    ///
+    /// Returns `None` when `path` is not inside a git repository.
+    pub fn collect<P: AsRef<Path>>(path: P, history_depth: usize) -> Option<Self> {
+        let repo = Repository::discover(path.as_ref()).ok()?;
+        let workdir = repo.workdir()?;
+        let workdir = fs::canonicalize(workdir).unwrap_or_else(|_| workdir.to_path_buf());
+
+        let mut files: HashMap<PathBuf, FileActivity> = HashMap::new();
+
+        if let Err(e) = Self::collect_history(&repo, &workdir, history_depth, &mut files) {
+            tracing::debug!("Failed to read git history for scan priority: {}", e);
+        }
+
+        let mut status_options = StatusOptions::new();
+        status_options.include_untracked(true).recurse_untracked_dirs(true);
+        if let Ok(statuses) = repo.statuses(Some(&mut status_options)) {
+            for entry in statuses.iter() {
+                if let Some(rel) = entry.path() {
+                    files.entry(workdir.join(rel)).or_default().uncommitted = true;
+                }
+            }
+        }
+
+        let max_commits = files.values().map(|a| a.commits).max().unwrap_or(0);
+        let now = crate::utils::unix_now() as i64;
+
+        Some(Self { files, max_commits, now })
+    }
+
+    fn collect_history(
+        repo: &Repository,
+        workdir: &Path,
+        history_depth: usize,
+        files: &mut HashMap<PathBuf, FileActivity>,
+    ) -> Result<(), git2::Error> {
+        let mut revwalk = repo.revwalk()?;
+        if revwalk.push_head().is_err() {
+            // Unborn HEAD (no commits yet)
+            return Ok(());
+        }
+
+        for oid in revwalk.take(history_depth) {
+            let commit = repo.find_commit(oid?)?;
+            let tree = commit.tree()?;
+            let parent_tree = match commit.parent(0) {
+                Ok(parent) => Some(parent.tree()?),
+                Err(_) => None,
+            };
+
+            let mut diff_options = DiffOptions::new();
+            let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), Some(&mut diff_options))?;
+            let time = commit.time().seconds();
+
+            for delta in diff.deltas() {
+                if let Some(rel) = delta.new_file().path() {
+                    let activity = files.entry(workdir.join(rel)).or_default();
+                    activity.commits += 1;
+                    activity.last_modified = Some(activity.last_modified.map_or(time, |t| t.max(time)));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Activity for a file, if any was recorded
+    pub fn get<P: AsRef<Path>>(&self, path: P) -> Option<&FileActivity> {
+        let path = path.as_ref();
+        self.files.get(path).or_else(|| {
+            let canonical = fs::canonicalize(path).ok()?;
+            self.files.get(&canonical)
+        })
+    }
+
+    /// Priority score for a file (higher is scanned earlier, 0.0 = no activity)
+    pub fn score<P: AsRef<Path>>(&self, path: P) -> f64 {
+        let Some(activity) = self.get(path) else {
+            return 0.0;
+        };
+
+        let recency = activity.last_modified.map_or(0.0, |time| {
+            let age_days = (self.now - time).max(0) as f64 / 86_400.0;
+            0.5f64.powf(age_days / RECENCY_HALF_LIFE_DAYS)
+        });
+        let churn = if self.max_commits > 0 {
+            activity.commits as f64 / self.max_commits as f64
+        } else {
+            0.0
+        };
+        let uncommitted = if activity.uncommitted { UNCOMMITTED_BONUS } else { 0.0 };
+
+        uncommitted + recency + churn
+    }
+
+    /// Reorder files so the most active ones come first
+    ///
+    /// The sort is stable, so files without activity keep their walk order.
+    pub fn prioritize(&self, files: &mut [PathBuf]) {
+        let mut scored: Vec<(f64, PathBuf)> = files
+            .iter()
+            .map(|file| (self.score(file), file.clone()))
+            .collect();
+        scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+
+        for (slot, (_, file)) in files.iter_mut().zip(scored) {
+            *slot = file;
+        }
+    }
+
+    /// Number of files with recorded activity
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Whether no activity was recorded
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::Signature;
+    use tempfile::TempDir;
+
+    fn commit_files(repo: &Repository, files: &[&str], message: &str) {
+        let workdir = repo.workdir().unwrap().to_path_buf();
+        let mut index = repo.index().unwrap();
+        for file in files {
+            let path = workdir.join(file);
+            let previous = fs::read_to_string(&path).unwrap_or_default();
+            fs::write(&path, format!("{}{}\n", previous, message)).unwrap();
+            index.add_path(Path::new(file)).unwrap();
+        }
+        index.write().unwrap();
+
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::now("Test", "test@example.com").unwrap();
+        let parent = repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+        repo.commit(Some("HEAD"), &signature, &signature, message, &tree, &parents)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_prioritize_by_churn_and_uncommitted() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = Repository::init(temp_dir.path()).unwrap();
+
+        commit_files(&repo, &["quiet.rs", "busy.rs"], "initial");
+        commit_files(&repo, &["busy.rs"], "second");
+        commit_files(&repo, &["busy.rs"], "third");
+        fs::write(temp_dir.path().join("new.env"), "API_KEY=abc\n").unwrap();
+
+        let activity = GitActivity::collect(temp_dir.path(), DEFAULT_HISTORY_DEPTH).unwrap();
+        let busy = activity.get(temp_dir.path().join("busy.rs")).unwrap();
+        assert_eq!(busy.commits, 3);
+        assert!(activity.get(temp_dir.path().join("new.env")).unwrap().uncommitted);
+
+        let mut files = vec![
+            temp_dir.path().join("untracked_elsewhere.txt"),
+            temp_dir.path().join("quiet.rs"),
+            temp_dir.path().join("busy.rs"),
+            temp_dir.path().join("new.env"),
+        ];
+        activity.prioritize(&mut files);
+
+        assert_eq!(files[0], temp_dir.path().join("new.env"));
+        assert_eq!(files[1], temp_dir.path().join("busy.rs"));
+        assert_eq!(files[2], temp_dir.path().join("quiet.rs"));
+        assert_eq!(files[3], temp_dir.path().join("untracked_elsewhere.txt"));
+    }
+
+    #[test]
+    fn test_no_repository() {
+        let temp_dir = TempDir::new().unwrap();
+        let nested = temp_dir.path().join("not_a_repo");
+        fs::create_dir(&nested).unwrap();
+
+        // TempDir may itself live inside a repository on some machines
+        if Repository::discover(&nested).is_err() {
+            assert!(GitActivity::collect(&nested, DEFAULT_HISTORY_DEPTH).is_none());
+        }
+    }
+}
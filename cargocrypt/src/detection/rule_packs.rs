@@ -0,0 +1,530 @@
+//! Shared rule packs
+//!
+//! A security team publishes detection policy for all its repositories as a
+//! rule pack: a rules file with a `name` and a `version`,
+//!
+//! ```yaml
+//! name: acme-security
+//! version: "1.4.0"
+//! rules:
+//!   - id: acme-api-key
+//!     name: ACME API key
+//!     type: regex
+//!     pattern: "acme_[a-z0-9]{32}"
+//! ```
+//!
+//! next to an Ed25519 detached signature of it (`ruleset.yml.sig`, as written
+//! by `cargocrypt sign`), either at an HTTPS URL or in a git repository.
+//!
+//! `cargocrypt rules add <source> --key <public key>` fetches both, checks the
+//! signature against the team's key and installs the pack into
+//! `.cargocrypt/rule-packs/`, recording its source, version, key and checksum
+//! in `.cargocrypt/rule-packs.toml`. Both are meant to be committed: scans then
+//! need no network access, every clone uses the same version, and
+//! `cargocrypt rules update` moves to newer versions signed with the same key.
+//!
+//! Installed packs are verified again whenever rules are loaded. Rules of the
+//! project's own rules file replace pack rules with the same id, so a
+//! repository can tune or disable (`enabled: false`) a shared rule.
+
+use crate::crypto::DetachedSignature;
+use crate::detection::rules::{RuleConfig, RuleConfigItem};
+use crate::error::{CargoCryptError, CryptoResult};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// File a pack is read from in a git repository unless another is given
+pub const DEFAULT_PACK_FILE: &str = "ruleset.yml";
+
+/// Largest pack file that is accepted
+pub const MAX_PACK_SIZE: usize = 1024 * 1024; // 1MB
+
+/// Timeout of downloading a pack over HTTPS
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Where a rule pack is published
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackSource {
+    /// HTTPS URL of the pack file, or URL (or local path) of a git repository
+    pub url: String,
+    /// Branch, tag or commit of a git repository (default: its default branch)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_ref: Option<String>,
+    /// File of the pack in a git repository
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
+
+impl PackSource {
+    /// Source at `url`, a git repository when it is not an `https://` link to a file
+    /// or when `git_ref` or `path` is given
+    pub fn new<S: Into<String>>(url: S, git_ref: Option<String>, path: Option<String>) -> Self {
+        let mut source = Self { url: url.into(), git_ref, path };
+        if source.is_git() && source.path.is_none() {
+            source.path = Some(DEFAULT_PACK_FILE.to_string());
+        }
+        source
+    }
+
+    /// Whether the pack is read from a git repository rather than downloaded
+    pub fn is_git(&self) -> bool {
+        self.git_ref.is_some()
+            || self.path.is_some()
+            || self.url.starts_with("git+")
+            || self.url.ends_with(".git")
+            || !(self.url.starts_with("https://") || self.url.starts_with("http://"))
+    }
+
+    /// Whether the pack file is TOML rather than YAML
+    fn is_toml(&self) -> bool {
+        self.path.as_deref().unwrap_or(&self.url).ends_with(".toml")
+    }
+
+    /// Download the pack and its signature
+    pub async fn fetch(&self) -> CryptoResult<FetchedPack> {
+        if self.is_git() {
+            let source = self.clone();
+            return tokio::task::spawn_blocking(move || source.fetch_git())
+                .await
+                .map_err(|e| CargoCryptError::detection_error(&format!("Failed to fetch rule pack: {}", e)))?;
+        }
+        if !self.url.starts_with("https://") {
+            return Err(CargoCryptError::Config {
+                message: format!("Rule pack URL {} is not HTTPS", self.url),
+                suggestion: Some("Publish the pack over HTTPS or in a git repository".to_string()),
+            });
+        }
+
+        let client = reqwest::Client::builder()
+            .timeout(FETCH_TIMEOUT)
+            .user_agent(concat!("cargocrypt/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+        Ok(FetchedPack {
+            content: download(&client, &self.url).await?,
+            signature: download(&client, &format!("{}.sig", self.url)).await?,
+            revision: None,
+        })
+    }
+
+    fn fetch_git(&self) -> CryptoResult<FetchedPack> {
+        static CLONES: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "cargocrypt-rule-pack-{}-{}",
+            std::process::id(),
+            CLONES.fetch_add(1, Ordering::Relaxed)
+        ));
+        let result = self.read_from_git(&dir);
+        let _ = std::fs::remove_dir_all(&dir);
+        result
+    }
+
+    /// Clone the repository into `dir` and read the pack from the requested commit
+    fn read_from_git(&self, dir: &Path) -> CryptoResult<FetchedPack> {
+        let url = self.url.strip_prefix("git+").unwrap_or(&self.url);
+        let path = self.path.as_deref().unwrap_or(DEFAULT_PACK_FILE);
+        let repo = git2::build::RepoBuilder::new().bare(true).clone(url, dir)?;
+        let commit = repo
+            .revparse_single(self.git_ref.as_deref().unwrap_or("HEAD"))?
+            .peel_to_commit()?;
+        let tree = commit.tree()?;
+
+        let read = |path: &str| -> CryptoResult<Vec<u8>> {
+            let entry = tree.get_path(Path::new(path)).map_err(|_| CargoCryptError::Config {
+                message: format!("No {} in {} at {}", path, url, commit.id()),
+                suggestion: Some("Pass the pack file with --path, and sign it with `cargocrypt sign`".to_string()),
+            })?;
+            Ok(entry.to_object(&repo)?.peel_to_blob()?.content().to_vec())
+        };
+        Ok(FetchedPack {
+            content: read(path)?,
+            signature: read(&format!("{}.sig", path))?,
+            revision: Some(commit.id().to_string()),
+        })
+    }
+}
+
+impl fmt::Display for PackSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.url)?;
+        if self.is_git() {
+            write!(f, " ({}", self.path.as_deref().unwrap_or(DEFAULT_PACK_FILE))?;
+            if let Some(git_ref) = &self.git_ref {
+                write!(f, " at {}", git_ref)?;
+            }
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+/// A pack file and its signature, as fetched from its source
+#[derive(Debug, Clone)]
+pub struct FetchedPack {
+    pub content: Vec<u8>,
+    /// Detached signature (JSON) of `content`
+    pub signature: Vec<u8>,
+    /// Commit a git pack was read from
+    pub revision: Option<String>,
+}
+
+impl FetchedPack {
+    /// SHA-256 (hex) of the pack file
+    pub fn sha256(&self) -> String {
+        hex::encode(Sha256::digest(&self.content))
+    }
+}
+
+/// A published set of rules
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RulePack {
+    /// Name the pack is installed under
+    pub name: String,
+    /// Version of the pack, e.g. "1.4.0"
+    pub version: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub rules: Vec<RuleConfigItem>,
+}
+
+impl RulePack {
+    /// Parse a pack file, TOML when `toml` is set and YAML otherwise, and validate its rules
+    pub fn parse(content: &[u8], toml: bool) -> CryptoResult<Self> {
+        let text = String::from_utf8_lossy(content);
+        let parsed = if toml {
+            toml::from_str::<Self>(&text).map_err(|e| e.to_string())
+        } else {
+            serde_yaml::from_str::<Self>(&text).map_err(|e| e.to_string())
+        };
+        let pack = parsed.map_err(|e| CargoCryptError::Config {
+            message: format!("Failed to parse rule pack: {}", e),
+            suggestion: Some("A rule pack has a `name`, a `version` and a `rules` list".to_string()),
+        })?;
+
+        let mut problems = Vec::new();
+        if !is_valid_name(&pack.name) {
+            problems.push(format!("`name` must be letters, digits, '-', '_' or '.', got '{}'", pack.name));
+        }
+        if pack.version.trim().is_empty() {
+            problems.push("missing `version`".to_string());
+        }
+        let config = RuleConfig { rules: pack.rules.clone() };
+        problems.extend(config.lint().iter().map(ToString::to_string));
+        if !problems.is_empty() {
+            return Err(CargoCryptError::Config {
+                message: format!("Invalid rule pack '{}': {}", pack.name, problems.join("; ")),
+                suggestion: Some("Ask the publisher of the pack to run `cargocrypt rules lint` on it".to_string()),
+            });
+        }
+        Ok(pack)
+    }
+}
+
+/// A rule pack installed in a project
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstalledPack {
+    pub name: String,
+    pub version: String,
+    /// Commit a git pack was read from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<String>,
+    /// Public key (hex) the pack must be signed with
+    pub public_key: String,
+    /// SHA-256 (hex) of the installed pack file
+    pub sha256: String,
+    /// Name of the pack file in `.cargocrypt/rule-packs/`
+    pub file: String,
+    pub source: PackSource,
+}
+
+/// The rule packs installed in a project (`.cargocrypt/rule-packs.toml`)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RulePacks {
+    #[serde(default)]
+    pub packs: Vec<InstalledPack>,
+}
+
+impl RulePacks {
+    /// Location of the list of installed packs of a project
+    pub fn project_path<P: AsRef<Path>>(project_root: P) -> PathBuf {
+        project_root.as_ref().join(".cargocrypt").join("rule-packs.toml")
+    }
+
+    /// Directory the pack files of a project are installed in
+    pub fn pack_dir<P: AsRef<Path>>(project_root: P) -> PathBuf {
+        project_root.as_ref().join(".cargocrypt").join("rule-packs")
+    }
+
+    /// Load the installed packs of a project; none when nothing was installed
+    pub fn load_project<P: AsRef<Path>>(project_root: P) -> CryptoResult<Self> {
+        let path = Self::project_path(project_root);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        toml::from_str(&std::fs::read_to_string(&path)?).map_err(|e| CargoCryptError::Config {
+            message: format!("Invalid rule pack list {}: {}", path.display(), e),
+            suggestion: Some("Reinstall the packs with `cargocrypt rules add`".to_string()),
+        })
+    }
+
+    /// Save the list of installed packs of a project
+    pub fn save_project<P: AsRef<Path>>(&self, project_root: P) -> CryptoResult<()> {
+        let path = Self::project_path(project_root);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let content = toml::to_string_pretty(self).map_err(|e| CargoCryptError::Config {
+            message: format!("Failed to serialize rule pack list: {}", e),
+            suggestion: None,
+        })?;
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+
+    /// The installed pack called `name`
+    pub fn get(&self, name: &str) -> Option<&InstalledPack> {
+        self.packs.iter().find(|pack| pack.name == name)
+    }
+
+    /// Verify a fetched pack against `public_key` (hex) and install it into the project
+    ///
+    /// Replaces an installed pack of the same name and source. The list of
+    /// packs still has to be saved with [`save_project`](Self::save_project).
+    pub fn install<P: AsRef<Path>>(
+        &mut self,
+        project_root: P,
+        source: PackSource,
+        public_key: &str,
+        fetched: &FetchedPack,
+    ) -> CryptoResult<&InstalledPack> {
+        if fetched.content.len() > MAX_PACK_SIZE {
+            return Err(CargoCryptError::Config {
+                message: format!("Rule pack at {} is larger than {} bytes", source, MAX_PACK_SIZE),
+                suggestion: None,
+            });
+        }
+        verify_signature(&fetched.content, &fetched.signature, public_key).map_err(|e| CargoCryptError::Config {
+            message: format!("Rule pack at {} failed signature verification: {}", source, e),
+            suggestion: Some("Check that --key is the public key of the pack's publisher".to_string()),
+        })?;
+        let pack = RulePack::parse(&fetched.content, source.is_toml())?;
+        if let Some(existing) = self.get(&pack.name).filter(|existing| existing.source != source) {
+            return Err(CargoCryptError::Config {
+                message: format!("A rule pack named '{}' is already installed from {}", pack.name, existing.source),
+                suggestion: Some(format!("Remove it first with `cargocrypt rules remove {}`", pack.name)),
+            });
+        }
+
+        let file = format!("{}.{}", pack.name, if source.is_toml() { "toml" } else { "yml" });
+        let dir = Self::pack_dir(project_root);
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join(&file), &fetched.content)?;
+        std::fs::write(dir.join(format!("{}.sig", file)), &fetched.signature)?;
+
+        let installed = InstalledPack {
+            name: pack.name,
+            version: pack.version,
+            revision: fetched.revision.clone(),
+            public_key: public_key.to_ascii_lowercase(),
+            sha256: fetched.sha256(),
+            file,
+            source,
+        };
+        let index = match self.packs.iter().position(|pack| pack.name == installed.name) {
+            Some(index) => {
+                self.packs[index] = installed;
+                index
+            }
+            None => {
+                self.packs.push(installed);
+                self.packs.len() - 1
+            }
+        };
+        Ok(&self.packs[index])
+    }
+
+    /// Uninstall the pack called `name`, returning it when it was installed
+    pub fn remove<P: AsRef<Path>>(&mut self, project_root: P, name: &str) -> CryptoResult<Option<InstalledPack>> {
+        let Some(index) = self.packs.iter().position(|pack| pack.name == name) else {
+            return Ok(None);
+        };
+        let pack = self.packs.remove(index);
+        let dir = Self::pack_dir(project_root);
+        for file in [dir.join(&pack.file), dir.join(format!("{}.sig", pack.file))] {
+            if file.exists() {
+                std::fs::remove_file(file)?;
+            }
+        }
+        Ok(Some(pack))
+    }
+
+    /// Rules of every installed pack, in install order
+    ///
+    /// Fails when a pack file was changed since it was installed or its
+    /// signature no longer matches the recorded key.
+    pub fn rules<P: AsRef<Path>>(&self, project_root: P) -> CryptoResult<Vec<RuleConfigItem>> {
+        let dir = Self::pack_dir(project_root);
+        let mut rules = Vec::new();
+        for pack in &self.packs {
+            let path = dir.join(&pack.file);
+            let tampered = |reason: String| CargoCryptError::Config {
+                message: format!("Rule pack '{}' ({}) {}", pack.name, path.display(), reason),
+                suggestion: Some(format!("Reinstall it with `cargocrypt rules add {}`", pack.source.url)),
+            };
+
+            let content = std::fs::read(&path).map_err(|e| tampered(format!("cannot be read: {}", e)))?;
+            if hex::encode(Sha256::digest(&content)) != pack.sha256 {
+                return Err(tampered("was modified after it was installed".to_string()));
+            }
+            let signature = std::fs::read(dir.join(format!("{}.sig", pack.file)))
+                .map_err(|e| tampered(format!("has no readable signature: {}", e)))?;
+            verify_signature(&content, &signature, &pack.public_key)
+                .map_err(|e| tampered(format!("failed signature verification: {}", e)))?;
+            rules.extend(RulePack::parse(&content, pack.file.ends_with(".toml"))?.rules);
+        }
+        Ok(rules)
+    }
+}
+
+async fn download(client: &reqwest::Client, url: &str) -> CryptoResult<Vec<u8>> {
+    let response = client.get(url).send().await?.error_for_status()?;
+    Ok(response.bytes().await?.to_vec())
+}
+
+/// Check the detached `signature` (JSON) of `content` against `public_key` (hex)
+fn verify_signature(content: &[u8], signature: &[u8], public_key: &str) -> CryptoResult<()> {
+    let public_key = hex::decode(public_key.trim())
+        .ok()
+        .filter(|key| key.len() == 32)
+        .ok_or_else(|| CargoCryptError::Config {
+            message: "Public keys are 64 hex characters".to_string(),
+            suggestion: Some("Use the `public_key` of a signature written by `cargocrypt sign`".to_string()),
+        })?;
+    let signature = DetachedSignature::from_json(&String::from_utf8_lossy(signature))?;
+    signature.verify_with_key(content, &public_key)?;
+    Ok(())
+}
+
+/// Whether `name` can be used as a pack (file) name
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::SigningKeyPair;
+    use tempfile::TempDir;
+
+    const PACK: &str = r#"
+name: acme-security
+version: "1.4.0"
+rules:
+  - id: acme-api-key
+    name: ACME API key
+    type: regex
+    pattern: "acme_[a-z0-9]{8}"
+"#;
+
+    fn commit_files(repo: &git2::Repository, files: &[(&str, &[u8])]) {
+        let workdir = repo.workdir().unwrap().to_path_buf();
+        let mut index = repo.index().unwrap();
+        for (name, content) in files {
+            std::fs::write(workdir.join(name), content).unwrap();
+            index.add_path(Path::new(name)).unwrap();
+        }
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = git2::Signature::now("Security", "security@example.com").unwrap();
+        let parent = repo.head().ok().map(|head| head.peel_to_commit().unwrap());
+        let parents: Vec<_> = parent.iter().collect();
+        repo.commit(Some("HEAD"), &signature, &signature, "Rules", &tree, &parents).unwrap();
+    }
+
+    #[test]
+    fn test_install_verifies_signature() {
+        let project = TempDir::new().unwrap();
+        let keypair = SigningKeyPair::generate();
+        let source = PackSource::new("https://rules.example.com/ruleset.yml", None, None);
+        assert!(!source.is_git());
+        let fetched = FetchedPack {
+            content: PACK.as_bytes().to_vec(),
+            signature: keypair.sign(PACK.as_bytes()).to_json().unwrap().into_bytes(),
+            revision: None,
+        };
+
+        let mut packs = RulePacks::default();
+        let other_key = SigningKeyPair::generate().public_key_hex();
+        assert!(packs.install(project.path(), source.clone(), &other_key, &fetched).is_err());
+        let tampered = FetchedPack {
+            content: PACK.replace("{8}", "{4}").into_bytes(),
+            ..fetched.clone()
+        };
+        assert!(packs.install(project.path(), source.clone(), &keypair.public_key_hex(), &tampered).is_err());
+        assert!(packs.packs.is_empty());
+
+        let installed = packs.install(project.path(), source, &keypair.public_key_hex(), &fetched).unwrap();
+        assert_eq!((installed.name.as_str(), installed.version.as_str()), ("acme-security", "1.4.0"));
+        packs.save_project(project.path()).unwrap();
+
+        // Own rules win over pack rules with the same id
+        let rules_path = RuleConfig::project_path(project.path());
+        std::fs::write(
+            &rules_path,
+            "rules:\n  - id: acme-api-key\n    name: ACME API key\n    type: regex\n    pattern: \"acme_[a-z]{8}\"\n    enabled: false\n",
+        )
+        .unwrap();
+        let config = RuleConfig::load_project(project.path()).unwrap();
+        assert_eq!(config.rules.len(), 1);
+        assert_eq!(config.rules[0].enabled, Some(false));
+        std::fs::remove_file(rules_path).unwrap();
+        assert_eq!(RuleConfig::load_project(project.path()).unwrap().rules[0].id, "acme-api-key");
+
+        // Editing an installed pack is detected
+        let pack_file = RulePacks::pack_dir(project.path()).join("acme-security.yml");
+        std::fs::write(&pack_file, PACK.replace("{8}", "{4}")).unwrap();
+        assert!(RuleConfig::load_project(project.path()).is_err());
+
+        let mut packs = RulePacks::load_project(project.path()).unwrap();
+        assert!(packs.remove(project.path(), "acme-security").unwrap().is_some());
+        assert!(!pack_file.exists());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_from_git() {
+        let publisher = TempDir::new().unwrap();
+        let project = TempDir::new().unwrap();
+        let keypair = SigningKeyPair::generate();
+        let repo = git2::Repository::init(publisher.path()).unwrap();
+        let signature = keypair.sign(PACK.as_bytes()).to_json().unwrap();
+        commit_files(&repo, &[("ruleset.yml", PACK.as_bytes()), ("ruleset.yml.sig", signature.as_bytes())]);
+        let first = repo.head().unwrap().peel_to_commit().unwrap().id().to_string();
+
+        let newer = PACK.replace("1.4.0", "1.5.0");
+        let signature = keypair.sign(newer.as_bytes()).to_json().unwrap();
+        commit_files(&repo, &[("ruleset.yml", newer.as_bytes()), ("ruleset.yml.sig", signature.as_bytes())]);
+
+        let url = publisher.path().display().to_string();
+        let source = PackSource::new(url.clone(), Some(first.clone()), None);
+        let fetched = source.fetch().await.unwrap();
+        assert_eq!(fetched.revision.as_deref(), Some(first.as_str()));
+
+        let mut packs = RulePacks::default();
+        let installed = packs.install(project.path(), source, &keypair.public_key_hex(), &fetched).unwrap();
+        assert_eq!(installed.version, "1.4.0");
+
+        let source = PackSource::new(url, None, None);
+        let fetched = source.fetch().await.unwrap();
+        // The same pack name from another source is refused
+        assert!(packs.install(project.path(), source, &keypair.public_key_hex(), &fetched).is_err());
+
+        let missing = PackSource::new(publisher.path().display().to_string(), None, Some("other.yml".to_string()));
+        assert!(missing.fetch().await.is_err());
+    }
+}
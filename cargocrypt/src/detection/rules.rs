@@ -2,12 +2,36 @@
 //!
 //! This module allows users to define their own detection rules using various
 //! rule types including regex patterns, entropy thresholds, and composite rules.
+//!
+//! Projects define rules in `.cargocrypt/rules.yml` (or `rules.toml`):
+//!
+//! ```yaml
+//! rules:
+//!   - id: acme-api-key
+//!     name: ACME API key
+//!     type: regex
+//!     pattern: "acme_[a-z0-9]{32}"
+//!     severity: high
+//!     remediation: Revoke the key in the ACME console and load it from ACME_API_KEY
+//!   - id: internal-token
+//!     name: Internal token
+//!     type: keyword
+//!     keywords: [x-internal-token]
+//!     require_high_entropy: true
+//! ```
+//!
+//! Rules are validated when loaded; `cargocrypt rules lint` reports every
+//! problem of a rules file at once. Rules shared between repositories are
+//! installed as [rule packs](crate::detection::rule_packs).
 
-use crate::detection::SecretType;
+use crate::detection::rule_packs::RulePacks;
+use crate::detection::{SecretType, Severity};
 use crate::error::{CargoCryptError, CryptoResult};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
 
 /// Types of custom rules
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -77,6 +101,12 @@ pub struct CustomRule {
     pub tags: Vec<String>,
     /// Whether to validate matches
     pub validate: bool,
+    /// Impact of a match, overriding the confidence-based severity
+    #[serde(default)]
+    pub severity: Option<Severity>,
+    /// How to fix a match
+    #[serde(default)]
+    pub remediation: Option<String>,
 }
 
 impl CustomRule {
@@ -99,6 +129,8 @@ impl CustomRule {
             enabled: true,
             tags: Vec::new(),
             validate: false,
+            severity: None,
+            remediation: None,
         }
     }
 
@@ -114,6 +146,18 @@ impl CustomRule {
         self
     }
 
+    /// Set the severity of matches
+    pub fn with_severity(mut self, severity: Severity) -> Self {
+        self.severity = Some(severity);
+        self
+    }
+
+    /// Set remediation advice for matches
+    pub fn with_remediation<S: Into<String>>(mut self, remediation: S) -> Self {
+        self.remediation = Some(remediation.into());
+        self
+    }
+
     /// Disable this rule
     pub fn disable(mut self) -> Self {
         self.enabled = false;
@@ -453,8 +497,17 @@ impl RuleEngine {
         Ok(all_matches)
     }
 
-    /// Load rules from configuration
+    /// Load rules from configuration, failing if any rule is invalid
     pub fn load_rules_from_config(&mut self, config: &RuleConfig) -> CryptoResult<()> {
+        let issues = config.lint();
+        if !issues.is_empty() {
+            let issues: Vec<String> = issues.iter().map(ToString::to_string).collect();
+            return Err(CargoCryptError::Config {
+                message: format!("Invalid custom rules: {}", issues.join("; ")),
+                suggestion: Some("Run `cargocrypt rules lint` for details".to_string()),
+            });
+        }
+
         for rule_config in &config.rules {
             let rule = self.create_rule_from_config(rule_config)?;
             self.add_rule(rule);
@@ -476,9 +529,9 @@ impl RuleEngine {
             }
             "entropy" => {
                 RuleType::Entropy {
-                    min_entropy: config.min_entropy.unwrap_or(4.0),
-                    min_length: config.min_length.unwrap_or(8),
-                    max_length: config.max_length.unwrap_or(100),
+                    min_entropy: config.min_entropy.unwrap_or(DEFAULT_MIN_ENTROPY),
+                    min_length: config.min_length.unwrap_or(DEFAULT_MIN_LENGTH),
+                    max_length: config.max_length.unwrap_or(DEFAULT_MAX_LENGTH),
                 }
             }
             "keyword" => {
@@ -504,14 +557,19 @@ impl RuleEngine {
             SecretType::Custom("unknown".to_string())
         };
 
-        Ok(CustomRule::new(
+        let mut rule = CustomRule::new(
             config.id.clone(),
             config.name.clone(),
             config.description.clone().unwrap_or_default(),
             rule_type,
             secret_type,
             config.confidence.unwrap_or(0.7),
-        ))
+        )
+        .with_tags(config.tags.clone().unwrap_or_default());
+        rule.enabled = config.enabled.unwrap_or(true);
+        rule.severity = config.severity;
+        rule.remediation = config.remediation.clone();
+        Ok(rule)
     }
 
     /// Enable or disable the rule engine
@@ -520,21 +578,39 @@ impl RuleEngine {
     }
 }
 
+/// Defaults of entropy rules
+const DEFAULT_MIN_ENTROPY: f64 = 4.0;
+const DEFAULT_MIN_LENGTH: usize = 8;
+const DEFAULT_MAX_LENGTH: usize = 100;
+
+/// Rule files of a project, in order of preference
+const PROJECT_RULE_FILES: &[&str] = &["rules.yml", "rules.yaml", "rules.toml"];
+
 /// Configuration for loading rules
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct RuleConfig {
+    #[serde(default)]
     pub rules: Vec<RuleConfigItem>,
 }
 
 /// Configuration for a single rule
-#[derive(Debug, Serialize, Deserialize)]
+///
+/// Missing fields are reported by [`RuleConfig::lint`] rather than failing to parse.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct RuleConfigItem {
     pub id: String,
     pub name: String,
     pub description: Option<String>,
+    /// `regex`, `entropy` or `keyword`
+    #[serde(alias = "type")]
     pub rule_type: String,
     pub secret_type: Option<String>,
     pub confidence: Option<f64>,
+    pub severity: Option<Severity>,
+    pub remediation: Option<String>,
+    pub enabled: Option<bool>,
+    pub tags: Option<Vec<String>>,
     
     // Regex rule fields
     pub pattern: Option<String>,
@@ -551,6 +627,158 @@ pub struct RuleConfigItem {
     pub require_high_entropy: Option<bool>,
 }
 
+/// A problem with one rule of a rules file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleIssue {
+    /// Id of the rule, or its position when it has no id
+    pub rule: String,
+    pub message: String,
+}
+
+impl fmt::Display for RuleIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.rule, self.message)
+    }
+}
+
+impl RuleConfig {
+    /// Location of the rules file of a project
+    ///
+    /// The first existing of `.cargocrypt/rules.yml`, `rules.yaml` and
+    /// `rules.toml`; `rules.yml` when there is none.
+    pub fn project_path<P: AsRef<Path>>(project_root: P) -> PathBuf {
+        let dir = project_root.as_ref().join(".cargocrypt");
+        PROJECT_RULE_FILES
+            .iter()
+            .map(|name| dir.join(name))
+            .find(|path| path.exists())
+            .unwrap_or_else(|| dir.join(PROJECT_RULE_FILES[0]))
+    }
+
+    /// Load the rules of a project, or no rules when it has no rules file
+    ///
+    /// Rules of installed [rule packs](crate::detection::rule_packs) follow the
+    /// project's own; a project rule replaces a pack rule with the same id.
+    pub fn load_project<P: AsRef<Path>>(project_root: P) -> CryptoResult<Self> {
+        let project_root = project_root.as_ref();
+        let path = Self::project_path(project_root);
+        let mut config = if path.exists() { Self::load(path)? } else { Self::default() };
+
+        for rule in RulePacks::load_project(project_root)?.rules(project_root)? {
+            if !config.rules.iter().any(|own| own.id == rule.id) {
+                config.rules.push(rule);
+            }
+        }
+        Ok(config)
+    }
+
+    /// Load and validate a rules file
+    pub fn load<P: AsRef<Path>>(path: P) -> CryptoResult<Self> {
+        let path = path.as_ref();
+        let config = Self::read(path)?;
+        let issues = config.lint();
+        if issues.is_empty() {
+            return Ok(config);
+        }
+
+        let issues: Vec<String> = issues.iter().map(ToString::to_string).collect();
+        Err(CargoCryptError::Config {
+            message: format!("Invalid rules in {}: {}", path.display(), issues.join("; ")),
+            suggestion: Some(format!("Run `cargocrypt rules lint {}` for details", path.display())),
+        })
+    }
+
+    /// Parse a rules file without validating its rules
+    ///
+    /// Files ending in `.toml` are TOML, anything else is YAML.
+    pub fn read<P: AsRef<Path>>(path: P) -> CryptoResult<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)?;
+        let parsed = if path.extension().is_some_and(|ext| ext == "toml") {
+            toml::from_str(&content).map_err(|e| e.to_string())
+        } else {
+            serde_yaml::from_str(&content).map_err(|e| e.to_string())
+        };
+
+        parsed.map_err(|e| CargoCryptError::Config {
+            message: format!("Failed to parse rules file {}: {}", path.display(), e),
+            suggestion: Some("Rules go in a top-level `rules` list".to_string()),
+        })
+    }
+
+    /// Every problem with the rules, in file order
+    pub fn lint(&self) -> Vec<RuleIssue> {
+        let mut issues = Vec::new();
+        let mut seen_ids = HashSet::new();
+
+        for (index, item) in self.rules.iter().enumerate() {
+            let rule = if item.id.trim().is_empty() {
+                format!("rule #{}", index + 1)
+            } else {
+                item.id.clone()
+            };
+            let mut issue = |message: String| issues.push(RuleIssue { rule: rule.clone(), message });
+
+            if item.id.trim().is_empty() {
+                issue("missing `id`".to_string());
+            } else if !seen_ids.insert(item.id.as_str()) {
+                issue("duplicate `id`".to_string());
+            }
+            if item.name.trim().is_empty() {
+                issue("missing `name`".to_string());
+            }
+            if let Some(confidence) = item.confidence {
+                if !(0.0..=1.0).contains(&confidence) {
+                    issue(format!("`confidence` must be between 0.0 and 1.0, got {}", confidence));
+                }
+            }
+
+            match item.rule_type.as_str() {
+                "regex" => match &item.pattern {
+                    None => issue("regex rule requires `pattern`".to_string()),
+                    Some(pattern) if pattern.is_empty() => issue("`pattern` is empty".to_string()),
+                    Some(pattern) => {
+                        let pattern = if item.case_sensitive.unwrap_or(true) {
+                            pattern.clone()
+                        } else {
+                            format!("(?i){}", pattern)
+                        };
+                        if let Err(e) = Regex::new(&pattern) {
+                            issue(format!("invalid `pattern`: {}", e));
+                        }
+                    }
+                },
+                "entropy" => {
+                    let min_entropy = item.min_entropy.unwrap_or(DEFAULT_MIN_ENTROPY);
+                    let min_length = item.min_length.unwrap_or(DEFAULT_MIN_LENGTH);
+                    let max_length = item.max_length.unwrap_or(DEFAULT_MAX_LENGTH);
+                    if !(0.0..=8.0).contains(&min_entropy) {
+                        issue(format!("`min_entropy` must be between 0.0 and 8.0 bits, got {}", min_entropy));
+                    }
+                    if min_length == 0 {
+                        issue("`min_length` must be at least 1".to_string());
+                    }
+                    if min_length > max_length {
+                        issue(format!("`min_length` ({}) is greater than `max_length` ({})", min_length, max_length));
+                    }
+                }
+                "keyword" => match &item.keywords {
+                    None => issue("keyword rule requires `keywords`".to_string()),
+                    Some(keywords) if keywords.is_empty() => issue("`keywords` is empty".to_string()),
+                    Some(keywords) if keywords.iter().any(|k| k.trim().is_empty()) => {
+                        issue("`keywords` contains an empty keyword".to_string())
+                    }
+                    Some(_) => {}
+                },
+                "" => issue("missing `type` (regex, entropy or keyword)".to_string()),
+                other => issue(format!("unknown `type` '{}' (expected regex, entropy or keyword)", other)),
+            }
+        }
+
+        issues
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -635,4 +863,82 @@ mod tests {
         let matches = engine.execute_rules("This is secret_key and SECRET_TOKEN", None).unwrap();
         assert_eq!(matches.len(), 2);
     }
+    #[test]
+    fn test_load_project_rules() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        assert!(RuleConfig::load_project(temp_dir.path()).unwrap().rules.is_empty());
+
+        let path = temp_dir.path().join(".cargocrypt").join("rules.yml");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(
+            &path,
+            r#"
+rules:
+  - id: acme-api-key
+    name: ACME API key
+    type: regex
+    pattern: "acme_[a-z0-9]{8}"
+    severity: high
+    remediation: Revoke the key in the ACME console
+  - id: internal-token
+    name: Internal token
+    type: keyword
+    keywords: [x-internal-token]
+"#,
+        )
+        .unwrap();
+        assert_eq!(RuleConfig::project_path(temp_dir.path()), path);
+
+        let config = RuleConfig::load_project(temp_dir.path()).unwrap();
+        let mut engine = RuleEngine::new();
+        engine.load_rules_from_config(&config).unwrap();
+
+        let rule = engine.get_rule("acme-api-key").unwrap();
+        assert_eq!(rule.severity, Some(Severity::High));
+        assert_eq!(rule.remediation.as_deref(), Some("Revoke the key in the ACME console"));
+        let matches = engine.execute_rules("key = acme_0a1b2c3d", None).unwrap();
+        assert_eq!(matches[0].rule_id, "acme-api-key");
+    }
+
+    #[test]
+    fn test_rule_config_lint() {
+        let config: RuleConfig = toml::from_str(
+            r#"
+[[rules]]
+id = "bad-regex"
+name = "Bad regex"
+type = "regex"
+pattern = "(unclosed"
+
+[[rules]]
+id = "bad-regex"
+name = "Duplicate"
+type = "entropy"
+min_length = 40
+max_length = 20
+confidence = 1.5
+
+[[rules]]
+name = "No id"
+type = "keyword"
+keywords = []
+
+[[rules]]
+id = "unknown"
+name = "Unknown"
+type = "magic"
+"#,
+        )
+        .unwrap();
+
+        let issues: Vec<String> = config.lint().iter().map(ToString::to_string).collect();
+        assert_eq!(issues.len(), 7, "{:?}", issues);
+        assert!(issues[0].starts_with("bad-regex: invalid `pattern`"));
+        assert!(issues.contains(&"bad-regex: duplicate `id`".to_string()));
+        assert!(issues.contains(&"rule #3: missing `id`".to_string()));
+        assert!(issues.contains(&"rule #3: `keywords` is empty".to_string()));
+        assert!(issues.iter().any(|issue| issue.starts_with("unknown: unknown `type`")));
+
+        assert!(RuleEngine::new().load_rules_from_config(&config).is_err());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,243 @@
+//! Detection of Rust-specific secret sinks
+//!
+//! Generic patterns only recognize secrets by their shape. In Rust code a few
+//! idioms put secrets into the source or the compiled binary regardless of
+//! what the value looks like:
+//! - `env::var("API_KEY").unwrap_or("...")` and `option_env!` with a
+//!   hard-coded fallback
+//! - `env!("API_KEY")` / `option_env!("API_KEY")`, which bake the value of a
+//!   build-time variable into the binary
+//! - `include_str!("secret.pem")` / `include_bytes!`, which embed a key file
+//! - `const SECRET: &str = "..."` and `static` equivalents
+//! - `dotenv!("API_KEY")`, which copies a `.env` value into the binary
+//!
+//! Each sink is reported with its own [`SecretType`] so that the finding can
+//! carry a targeted remediation (see [`SecretType::remediation`]).
+
+use crate::detection::patterns::{PatternMatch, SecretType};
+use regex::Regex;
+use std::path::Path;
+
+/// Name fragments that mark a variable or constant as secret
+const SECRET_NAME_HINTS: &[&str] = &[
+    "KEY", "SECRET", "TOKEN", "PASSWORD", "PASSWD", "PWD", "CREDENTIAL", "AUTH", "PRIVATE",
+];
+
+/// File extensions of key material that should never be embedded
+const SECRET_FILE_EXTENSIONS: &[&str] = &["pem", "key", "p12", "pfx", "jks", "keystore", "ppk", "env"];
+
+/// File name fragments that mark an included file as secret
+const SECRET_FILE_HINTS: &[&str] = &[
+    "secret", "credential", "password", "token", "private", "id_rsa", "id_ed25519", "id_ecdsa",
+    "service-account", "service_account",
+];
+
+/// Detector for Rust-specific secret sinks
+#[derive(Debug, Clone)]
+pub struct RustSinkDetector {
+    env_fallback: Regex,
+    compile_time_env: Regex,
+    include_file: Regex,
+    const_secret: Regex,
+    dotenv_macro: Regex,
+}
+
+impl RustSinkDetector {
+    /// Create a detector with the built-in sink patterns
+    pub fn new() -> Result<Self, regex::Error> {
+        Ok(Self {
+            env_fallback: Regex::new(
+                r#"(?:\boption_env!|\benv::var(?:_os)?)\s*\(\s*"(\w+)"\s*\)\s*(?:\.\s*ok\s*\(\s*\)\s*)?\.\s*unwrap_or(?:_else)?\s*\(\s*(?:\|[^|]*\|\s*)?(?:String::from\s*\(\s*)?"((?:[^"\\]|\\.){4,})""#,
+            )?,
+            compile_time_env: Regex::new(r#"\b(?:option_env|env)!\s*\(\s*"(\w+)"\s*\)"#)?,
+            include_file: Regex::new(r#"\binclude_(?:str|bytes)!\s*\(\s*"([^"]+)"\s*\)"#)?,
+            const_secret: Regex::new(
+                r#"\b(?:const|static)\s+(?:mut\s+)?(\w+)\s*:\s*&\s*(?:'static\s+)?(?:str|\[u8\]|\[u8;\s*\d+\])\s*=\s*b?"((?:[^"\\]|\\.){8,})""#,
+            )?,
+            dotenv_macro: Regex::new(r#"\b(?:dotenv|dotenvy)!\s*\(\s*"(\w+)"\s*\)"#)?,
+        })
+    }
+
+    /// Whether a file should be checked for Rust sinks
+    pub fn applies_to(path: &Path) -> bool {
+        path.extension().and_then(|e| e.to_str()) == Some("rs")
+    }
+
+    /// Find all Rust secret sinks in source code
+    ///
+    /// For sinks that contain the secret itself (constants and fallbacks) the
+    /// match covers the string literal; otherwise it covers the macro call.
+    pub fn find_sinks(&self, content: &str) -> Vec<PatternMatch> {
+        let mut matches = Vec::new();
+        let mut fallback_starts = Vec::new();
+
+        for caps in self.env_fallback.captures_iter(content) {
+            let (Some(whole), Some(name), Some(literal)) = (caps.get(0), caps.get(1), caps.get(2)) else {
+                continue;
+            };
+            if !is_secret_name(name.as_str()) {
+                continue;
+            }
+            fallback_starts.push(whole.start());
+            matches.push(sink_match(literal, SecretType::RustEnvFallback, 0.8));
+        }
+
+        for caps in self.compile_time_env.captures_iter(content) {
+            let (Some(whole), Some(name)) = (caps.get(0), caps.get(1)) else {
+                continue;
+            };
+            // Already reported together with its fallback
+            if fallback_starts.contains(&whole.start()) || !is_secret_name(name.as_str()) {
+                continue;
+            }
+            matches.push(sink_match(whole, SecretType::RustCompileTimeEnv, 0.55));
+        }
+
+        for caps in self.include_file.captures_iter(content) {
+            let (Some(whole), Some(file)) = (caps.get(0), caps.get(1)) else {
+                continue;
+            };
+            if is_secret_file(file.as_str()) {
+                matches.push(sink_match(whole, SecretType::RustIncludedFile, 0.75));
+            }
+        }
+
+        for caps in self.const_secret.captures_iter(content) {
+            let (Some(name), Some(literal)) = (caps.get(1), caps.get(2)) else {
+                continue;
+            };
+            if is_secret_name(name.as_str()) {
+                matches.push(sink_match(literal, SecretType::RustConstSecret, 0.7));
+            }
+        }
+
+        for caps in self.dotenv_macro.captures_iter(content) {
+            let (Some(whole), Some(name)) = (caps.get(0), caps.get(1)) else {
+                continue;
+            };
+            let confidence = if is_secret_name(name.as_str()) { 0.75 } else { 0.45 };
+            matches.push(sink_match(whole, SecretType::RustDotenvMacro, confidence));
+        }
+
+        matches.sort_by_key(|m| m.start);
+        matches
+    }
+}
+
+impl Default for RustSinkDetector {
+    fn default() -> Self {
+        Self::new().expect("Failed to compile Rust sink patterns")
+    }
+}
+
+fn sink_match(m: regex::Match<'_>, secret_type: SecretType, confidence: f64) -> PatternMatch {
+    PatternMatch {
+        matched_text: m.as_str().to_string(),
+        start: m.start(),
+        end: m.end(),
+        secret_type,
+        base_confidence: confidence,
+    }
+}
+
+/// Whether an identifier names a secret (`API_KEY`, `dbPassword`, ...)
+pub(crate) fn is_secret_name(name: &str) -> bool {
+    let upper = name.to_uppercase();
+    // Variables set by cargo itself (CARGO_PKG_AUTHORS, ...) are never secret
+    !upper.starts_with("CARGO_") && SECRET_NAME_HINTS.iter().any(|hint| upper.contains(hint))
+}
+
+/// Whether an included file path looks like key material
+fn is_secret_file(path: &str) -> bool {
+    let path = Path::new(path);
+    let extension = path.extension().and_then(|e| e.to_str()).map(str::to_lowercase);
+    let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("").to_lowercase();
+
+    extension.is_some_and(|ext| SECRET_FILE_EXTENSIONS.contains(&ext.as_str()))
+        || file_name == ".env"
+        || SECRET_FILE_HINTS.iter().any(|hint| file_name.contains(hint))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sink_types(content: &str) -> Vec<(SecretType, String)> {
+        RustSinkDetector::new()
+            .unwrap()
+            .find_sinks(content)
+            .into_iter()
+            .map(|m| (m.secret_type, m.matched_text))
+            .collect()
+    }
+
+    #[test]
+    fn test_env_fallback() {
+        let content = r#"
+            let key = std::env::var("STRIPE_API_KEY").unwrap_or_else(|_| "sk_live_1234abcd".to_string());
+            let token = option_env!("GH_TOKEN").unwrap_or("ghp_fallback");
+            let port = env::var("PORT").unwrap_or("8080".to_string());
+        "#;
+
+        assert_eq!(
+            sink_types(content),
+            vec![
+                (SecretType::RustEnvFallback, "sk_live_1234abcd".to_string()),
+                (SecretType::RustEnvFallback, "ghp_fallback".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compile_time_env_and_dotenv() {
+        let content = r#"
+            const KEY: &str = env!("API_KEY");
+            let authors = env!("CARGO_PKG_AUTHORS");
+            let db = dotenv!("DATABASE_PASSWORD");
+        "#;
+
+        assert_eq!(
+            sink_types(content),
+            vec![
+                (SecretType::RustCompileTimeEnv, r#"env!("API_KEY")"#.to_string()),
+                (SecretType::RustDotenvMacro, r#"dotenv!("DATABASE_PASSWORD")"#.to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_included_secret_file() {
+        let content = r#"
+            static CERT: &str = include_str!("../certs/server.pem");
+            static README: &str = include_str!("../README.md");
+            static KEY: &[u8] = include_bytes!("keys/id_ed25519");
+        "#;
+
+        let found = sink_types(content);
+        assert_eq!(found.len(), 2);
+        assert!(found.iter().all(|(t, _)| *t == SecretType::RustIncludedFile));
+    }
+
+    #[test]
+    fn test_const_secret() {
+        let content = r#"
+            pub const API_SECRET: &str = "s3cr3t-v4lu3-abc";
+            static DB_PASSWORD: &'static str = "hunter2hunter2";
+            const GREETING: &str = "hello world, friend";
+        "#;
+
+        assert_eq!(
+            sink_types(content),
+            vec![
+                (SecretType::RustConstSecret, "s3cr3t-v4lu3-abc".to_string()),
+                (SecretType::RustConstSecret, "hunter2hunter2".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_applies_only_to_rust() {
+        assert!(RustSinkDetector::applies_to(Path::new("src/main.rs")));
+        assert!(!RustSinkDetector::applies_to(Path::new("config.toml")));
+    }
+}
@@ -0,0 +1,412 @@
+//! Checks for common problems in a CargoCrypt project
+//!
+//! `cargocrypt doctor` looks for set-up mistakes that let secrets slip into
+//! git or make files harder to protect than they should be:
+//! - hooks that are enabled but not installed, outdated or replaced
+//! - `.gitattributes` naming the CargoCrypt filters while the git config
+//!   does not define them
+//! - files marked for encryption that are staged or committed in plaintext
+//! - stale `.enc` files whose plaintext source also exists
+//! - weak key derivation for new files, and files encrypted with weaker
+//!   parameters than configured
+//! - keyring entries that cannot be read or no longer open their vault
+//!
+//! Every problem comes with a suggestion. Installing hooks, configuring
+//! the filters and calibrating key derivation are safe to do unattended and
+//! are applied by [`Doctor::fix`] (`cargocrypt doctor --fix`); nothing that
+//! deletes files, touches the index or needs a password is.
+
+use crate::core::{CargoCrypt, CryptoConfig};
+use crate::error::CryptoResult;
+use crate::git::{
+    encryption_patterns, plaintext_in_commits, plaintext_in_index, Credential, GitAttributes, GitCryptConfig, GitHooks,
+    GitRepo, HookState, HookStatus, Workspace,
+};
+use crate::keyring;
+use serde::Serialize;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Kind of problem found by [`Doctor::diagnose`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProblemKind {
+    /// The project has no `.cargocrypt/config.toml`
+    NotInitialized,
+    /// An enabled hook is missing or is not CargoCrypt's
+    HookNotInstalled,
+    /// An enabled hook differs from what this version installs
+    HookOutdated,
+    /// `.gitattributes` uses filters the git config does not define
+    FiltersNotConfigured,
+    /// A file marked for encryption is stored in plaintext in git
+    PlaintextInGit,
+    /// An encrypted file whose plaintext also exists
+    StaleEncryptedFile,
+    /// New files are encrypted with weak key derivation parameters
+    WeakKdf,
+    /// A file is encrypted with weaker key derivation than configured
+    OutdatedKdf,
+    /// A keyring entry cannot be read
+    KeyringUnreadable,
+    /// A keyring entry does not open its vault
+    KeyringStale,
+}
+
+impl fmt::Display for ProblemKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::NotInitialized => "not initialized",
+            Self::HookNotInstalled => "hook not installed",
+            Self::HookOutdated => "hook outdated",
+            Self::FiltersNotConfigured => "filters not configured",
+            Self::PlaintextInGit => "plaintext in git",
+            Self::StaleEncryptedFile => "stale encrypted file",
+            Self::WeakKdf => "weak key derivation",
+            Self::OutdatedKdf => "outdated key derivation",
+            Self::KeyringUnreadable => "keyring unreadable",
+            Self::KeyringStale => "stale keyring entry",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A problem and how to fix it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Problem {
+    /// What is wrong
+    pub kind: ProblemKind,
+    /// The file concerned, relative to the project root when inside it
+    pub path: Option<PathBuf>,
+    /// Human-readable detail
+    pub detail: String,
+    /// What to run or change
+    pub suggestion: String,
+    /// Whether [`Doctor::fix`] fixes it
+    pub fixable: bool,
+}
+
+/// Result of [`Doctor::diagnose`]
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DoctorReport {
+    /// Whether the project is in a git working tree, so the git checks ran
+    pub git: bool,
+    /// Problems found
+    pub problems: Vec<Problem>,
+}
+
+impl DoctorReport {
+    /// Whether no problems were found
+    pub fn is_clean(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    /// Number of problems [`Doctor::fix`] fixes
+    pub fn fixable(&self) -> usize {
+        self.problems.iter().filter(|problem| problem.fixable).count()
+    }
+}
+
+/// Project checker
+pub struct Doctor<'a> {
+    crypt: &'a CargoCrypt,
+}
+
+impl<'a> Doctor<'a> {
+    /// Create a checker for the project of `crypt`
+    pub fn new(crypt: &'a CargoCrypt) -> Self {
+        Self { crypt }
+    }
+
+    /// Run all checks; the git checks are skipped outside a working tree
+    pub async fn diagnose(&self) -> CryptoResult<DoctorReport> {
+        let mut report = DoctorReport::default();
+        let root = self.crypt.project_root();
+        if !CryptoConfig::project_path(root).exists() {
+            report.problems.push(Problem {
+                kind: ProblemKind::NotInitialized,
+                path: None,
+                detail: "CargoCrypt is not initialized in this project".to_string(),
+                suggestion: "Run `cargocrypt init`".to_string(),
+                fixable: false,
+            });
+        }
+
+        if let Some(repo) = self.repo() {
+            report.git = true;
+            let hooks = GitHooks::new(&repo)?;
+            for status in hooks.status()? {
+                report.problems.extend(self.hook_problem(&status, &hooks));
+            }
+            report.problems.extend(self.filters_problem(&repo)?);
+            report.problems.extend(self.plaintext_problems(&repo)?);
+        }
+
+        report.problems.extend(self.stale_encrypted_files().await);
+        report.problems.extend(self.kdf_problems().await?);
+        if let Some(repo) = self.repo() {
+            report.problems.extend(self.keyring_problems(repo.workdir()).await?);
+        }
+        Ok(report)
+    }
+
+    /// Fix the problems marked fixable; returns what was done
+    pub async fn fix(&self) -> CryptoResult<Vec<String>> {
+        let mut fixed = Vec::new();
+        if let Some(repo) = self.repo() {
+            let hooks = GitHooks::new(&repo)?;
+            for status in hooks.status()? {
+                if self.hook_problem(&status, &hooks).is_some_and(|problem| problem.fixable) {
+                    hooks.install_managed(&status.hook_type).await?;
+                    fixed.push(format!("Installed the {} hook", status.hook_type.filename()));
+                }
+            }
+            if self.filters_problem(&repo)?.is_some() {
+                let config = GitCryptConfig::load_project(repo.workdir())?;
+                GitAttributes::new(&repo)?.configure_filters(&config).await?;
+                fixed.push("Configured the git filters and merge drivers".to_string());
+            }
+        }
+
+        if self.crypt.crypto().kdf().is_weak() {
+            let calibration = self.crypt.calibrate(None).await?;
+            fixed.push(format!("Calibrated key derivation for new files to {}", calibration.kdf));
+        }
+        Ok(fixed)
+    }
+
+    /// The working tree the project is in
+    fn repo(&self) -> Option<GitRepo> {
+        GitRepo::discover(self.crypt.project_root()).filter(|repo| !repo.is_bare())
+    }
+
+    /// `path` relative to the project root when inside it
+    fn relative(&self, path: &Path) -> PathBuf {
+        path.strip_prefix(self.crypt.project_root()).unwrap_or(path).to_path_buf()
+    }
+
+    fn hook_problem(&self, status: &HookStatus, hooks: &GitHooks) -> Option<Problem> {
+        let name = status.hook_type.filename();
+        let install = "Install it with `cargocrypt git install-hooks`".to_string();
+        let (kind, detail, suggestion, fixable) = match status.state {
+            _ if !status.enabled => return None,
+            HookState::Current => return None,
+            HookState::Missing => (ProblemKind::HookNotInstalled, format!("The {} hook is enabled but not installed", name), install, true),
+            HookState::Foreign if hooks.config().backup_existing && !status.chained => (
+                ProblemKind::HookNotInstalled,
+                format!("The {} hook is not CargoCrypt's, so its checks do not run", name),
+                "Run `cargocrypt git install-hooks`; the existing hook is kept and runs first".to_string(),
+                true,
+            ),
+            HookState::Foreign => (
+                ProblemKind::HookNotInstalled,
+                format!("The {} hook is not CargoCrypt's, so its checks do not run", name),
+                "Merge it with the hook CargoCrypt chain-loads, then run `cargocrypt git install-hooks`".to_string(),
+                false,
+            ),
+            HookState::Outdated => (
+                ProblemKind::HookOutdated,
+                format!("The {} hook was installed by another version of CargoCrypt", name),
+                install,
+                true,
+            ),
+            HookState::Modified => (
+                ProblemKind::HookOutdated,
+                format!("The {} hook was edited after it was installed", name),
+                "Move your changes to a hook CargoCrypt chain-loads, then run `cargocrypt git install-hooks`".to_string(),
+                false,
+            ),
+        };
+        Some(Problem {
+            kind,
+            path: Some(self.relative(&hooks.hooks_dir().join(name))),
+            detail,
+            suggestion,
+            fixable,
+        })
+    }
+
+    fn filters_problem(&self, repo: &GitRepo) -> CryptoResult<Option<Problem>> {
+        if encryption_patterns(repo.workdir())?.is_empty() {
+            return Ok(None);
+        }
+        let missing = GitAttributes::new(repo)?.missing_filters()?;
+        if missing.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Problem {
+            kind: ProblemKind::FiltersNotConfigured,
+            path: Some(self.relative(&repo.workdir().join(".gitattributes"))),
+            detail: format!(
+                ".gitattributes marks files for encryption but the git config lacks {}, so git stores them in plaintext",
+                missing.join(", ")
+            ),
+            suggestion: "Run `cargocrypt git configure-attributes`".to_string(),
+            fixable: true,
+        }))
+    }
+
+    fn plaintext_problems(&self, repo: &GitRepo) -> CryptoResult<Vec<Problem>> {
+        let mut problems = Vec::new();
+        let mut committed = Vec::new();
+        if let Ok(head) = repo.inner().head().and_then(|head| head.peel_to_commit()) {
+            committed = plaintext_in_commits(repo.inner(), &[head.id()])?
+                .into_iter()
+                .map(|found| found.path)
+                .collect();
+        }
+        for path in &committed {
+            problems.push(Problem {
+                kind: ProblemKind::PlaintextInGit,
+                path: Some(self.relative(&repo.workdir().join(path))),
+                detail: "Marked for encryption but committed in plaintext".to_string(),
+                suggestion: "Commit it encrypted after `git add --renormalize .`, and rotate the secret: it stays readable in history".to_string(),
+                fixable: false,
+            });
+        }
+        for path in plaintext_in_index(repo.workdir())? {
+            if committed.contains(&path) {
+                continue;
+            }
+            problems.push(Problem {
+                kind: ProblemKind::PlaintextInGit,
+                path: Some(self.relative(&repo.workdir().join(path))),
+                detail: "Marked for encryption but staged in plaintext".to_string(),
+                suggestion: "Stage it again encrypted with `git add --renormalize .`".to_string(),
+                fixable: false,
+            });
+        }
+        Ok(problems)
+    }
+
+    async fn stale_encrypted_files(&self) -> Vec<Problem> {
+        let root = self.crypt.project_root();
+        let extension = self.crypt.config().await.file_ops.encrypted_extension;
+        let mut problems = Vec::new();
+        for path in crate::expiry::find_encrypted_files(root, &extension) {
+            let plaintext = path.with_extension("");
+            if path.starts_with(root.join(".cargocrypt")) || !plaintext.is_file() {
+                continue;
+            }
+            let relative = self.relative(&plaintext);
+            let modified = |path: &Path| std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+            let (detail, suggestion) = if modified(&plaintext) > modified(&path) {
+                (
+                    format!("{} was changed after it was encrypted", relative.display()),
+                    format!("Re-encrypt it with `cargocrypt encrypt {} --in-place --force`", relative.display()),
+                )
+            } else {
+                (
+                    format!("{} is also here in plaintext", relative.display()),
+                    "Delete the plaintext once you are done with it; `cargocrypt decrypt` recreates it".to_string(),
+                )
+            };
+            problems.push(Problem {
+                kind: ProblemKind::StaleEncryptedFile,
+                path: Some(self.relative(&path)),
+                detail,
+                suggestion,
+                fixable: false,
+            });
+        }
+        problems
+    }
+
+    async fn kdf_problems(&self) -> CryptoResult<Vec<Problem>> {
+        let mut problems = Vec::new();
+        let target = self.crypt.crypto().kdf();
+        if target.is_weak() {
+            problems.push(Problem {
+                kind: ProblemKind::WeakKdf,
+                path: Some(self.relative(&CryptoConfig::project_path(self.crypt.project_root()))),
+                detail: format!("New files use {}, below current recommendations", target),
+                suggestion: "Run `cargocrypt calibrate`".to_string(),
+                fixable: true,
+            });
+        }
+        for (path, kdf) in self.crypt.outdated_files().await? {
+            problems.push(Problem {
+                kind: ProblemKind::OutdatedKdf,
+                path: Some(self.relative(&path)),
+                detail: format!("Encrypted with {}, weaker than the configured {}", kdf, target),
+                suggestion: "Re-encrypt it with `cargocrypt upgrade`".to_string(),
+                fixable: false,
+            });
+        }
+        Ok(problems)
+    }
+
+    /// Keyring entries of the filter password and of the vaults the filters unlock
+    async fn keyring_problems(&self, workdir: &Path) -> CryptoResult<Vec<Problem>> {
+        if !keyring::is_supported() {
+            return Ok(Vec::new());
+        }
+        let settings = match Workspace::discover(workdir) {
+            Ok(workspace) => workspace.filter_settings(),
+            Err(_) => GitCryptConfig::load_project(workdir)?.filter,
+        };
+        let mut credentials = vec![Credential::Filter];
+        for vault in settings.keys.iter().filter_map(|key| key.vault.clone()) {
+            if !credentials.contains(&Credential::Vault(vault.clone())) {
+                credentials.push(Credential::Vault(vault));
+            }
+        }
+
+        let mut problems = Vec::new();
+        for credential in credentials {
+            let store = match &credential {
+                Credential::Filter => "cargocrypt git credentials store".to_string(),
+                Credential::Vault(vault) => format!("cargocrypt git credentials store --vault {}", vault),
+            };
+            match keyring::read(&credential.keyring_account(workdir)) {
+                Err(e) => problems.push(Problem {
+                    kind: ProblemKind::KeyringUnreadable,
+                    path: None,
+                    detail: format!("The keyring entry of the {} cannot be read: {}", credential, e),
+                    suggestion: format!("Unlock the keyring, or store the password again with `{}`", store),
+                    fixable: false,
+                }),
+                Ok(Some(password)) => {
+                    if let Credential::Vault(vault) = &credential {
+                        if let Err(e) = self.crypt.unlock_vault(vault, password.as_str()).await {
+                            problems.push(Problem {
+                                kind: ProblemKind::KeyringStale,
+                                path: None,
+                                detail: format!("The keyring entry of the {} does not open it: {}", credential, e),
+                                suggestion: format!("Store the current password with `{}`", store),
+                                fixable: false,
+                            });
+                        }
+                    }
+                }
+                Ok(None) => {}
+            }
+        }
+        Ok(problems)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_stale_encrypted_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::write(root.join(".env"), "KEY=value").unwrap();
+        std::fs::write(root.join(".env.enc"), "encrypted").unwrap();
+        std::fs::write(root.join("api.key.enc"), "encrypted").unwrap();
+        std::fs::create_dir_all(root.join(".cargocrypt/vaults/prod")).unwrap();
+        std::fs::write(root.join(".cargocrypt/vaults/prod/key"), "key").unwrap();
+        std::fs::write(root.join(".cargocrypt/vaults/prod/key.enc"), "wrapped").unwrap();
+
+        let crypt = CargoCrypt::builder().project_root(root).build().await.unwrap();
+        let problems = Doctor::new(&crypt).stale_encrypted_files().await;
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].kind, ProblemKind::StaleEncryptedFile);
+        assert_eq!(problems[0].path.as_deref(), Some(Path::new(".env.enc")));
+        assert!(!problems[0].fixable);
+    }
+}
@@ -0,0 +1,626 @@
+//! `.env` files, in plaintext and encrypted per variable
+//!
+//! `cargocrypt env encrypt` turns a `.env` file into `.env.enc`, which keeps
+//! the variable names readable and encrypts each value on its own:
+//!
+//! ```text
+//! # cargocrypt-env: {"version":1,"kdf":{"id":"argon2id"},"salt":"…","check":"…"}
+//! DATABASE_URL=enc:v1:…
+//! API_KEY=enc:v1:…
+//! ```
+//!
+//! One key is derived from the password and the salt in the header; each
+//! value is sealed with ChaCha20-Poly1305 under a random nonce, with the
+//! variable name as associated data so values cannot be swapped between
+//! names. The `check` value seals an empty message, so a wrong password is
+//! reported even for a file without variables. Re-encrypting keeps the
+//! ciphertext of unchanged values, so `git diff` shows which variables
+//! changed and nothing else.
+//!
+//! A committed `.env.example` documents the variables with harmless
+//! defaults; decrypting merges the encrypted values over it.
+
+use crate::crypto::{defaults, CryptoError, DerivedKey, Kdf, KeyDerivationParams, SecureRandom, SecureString};
+use crate::error::{CargoCryptError, CryptoResult};
+use base64ct::{Base64, Encoding};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Nonce};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+use zeroize::Zeroize;
+
+/// Start of the header line of an encrypted `.env` file
+const HEADER_PREFIX: &str = "# cargocrypt-env: ";
+
+/// Start of an encrypted value
+const VALUE_PREFIX: &str = "enc:v1:";
+
+/// Associated data of the password check value
+const CHECK_AAD: &[u8] = b"cargocrypt-env-check-v1";
+
+/// Variables of a `.env` file, in file order
+#[derive(Clone, Default)]
+pub struct Dotenv {
+    vars: Vec<(String, SecureString)>,
+}
+
+impl Dotenv {
+    /// An empty file
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse the contents of a `.env` file
+    ///
+    /// Lines are `NAME=value`, with an optional `export ` prefix; `#` starts
+    /// a comment. Values in single quotes are taken literally, values in
+    /// double quotes understand `\n`, `\t`, `\r`, `\"`, `\$` and `\\`. A
+    /// name assigned twice keeps its first position and its last value.
+    pub fn parse(content: &str) -> CryptoResult<Self> {
+        let mut dotenv = Self::new();
+        let mut errors = Vec::new();
+        for (number, line) in content.lines().enumerate() {
+            match parse_line(line) {
+                Ok(Some((name, value))) => dotenv.set(name, value),
+                Ok(None) => {}
+                Err(error) => errors.push(format!("line {}: {}", number + 1, error)),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(invalid(format!("Invalid .env file: {}", errors.join("; ")), errors));
+        }
+        Ok(dotenv)
+    }
+
+    /// Read and parse a `.env` file
+    pub fn load(path: &Path) -> CryptoResult<Self> {
+        let mut content = std::fs::read_to_string(path)?;
+        let dotenv = Self::parse(&content).map_err(|e| match e {
+            CargoCryptError::Validation { message, errors, warnings } => CargoCryptError::Validation {
+                message: format!("{}: {}", path.display(), message),
+                errors,
+                warnings,
+            },
+            e => e,
+        });
+        content.zeroize();
+        dotenv
+    }
+
+    /// Write the variables to `path`, readable only by the owner on Unix
+    pub fn save(&self, path: &Path) -> CryptoResult<()> {
+        use std::io::Write;
+
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        options.open(path)?.write_all(self.to_dotenv().as_bytes())?;
+        Ok(())
+    }
+
+    /// Set `name` to `value`, replacing an earlier value in place
+    pub fn insert(&mut self, name: &str, value: SecureString) -> CryptoResult<()> {
+        if !is_env_name(name) {
+            return Err(invalid(format!("'{}' is not a valid environment variable name", name), Vec::new()));
+        }
+        if value.contains('\0') {
+            return Err(invalid(format!("The value of {} contains a NUL byte", name), Vec::new()));
+        }
+        self.set(name, value);
+        Ok(())
+    }
+
+    fn set(&mut self, name: &str, value: SecureString) {
+        match self.vars.iter_mut().find(|(existing, _)| existing == name) {
+            Some((_, existing)) => *existing = value,
+            None => self.vars.push((name.to_string(), value)),
+        }
+    }
+
+    /// Set every variable of `other`, which wins over this file's values
+    pub fn overlay(&mut self, other: &Dotenv) {
+        for (name, value) in &other.vars {
+            self.set(name, value.clone());
+        }
+    }
+
+    /// Keep only the variables for which `keep` returns true
+    pub fn retain(&mut self, mut keep: impl FnMut(&str, &str) -> bool) {
+        self.vars.retain(|(name, value)| keep(name, value));
+    }
+
+    /// The value of `name`
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.vars.iter().find(|(existing, _)| existing == name).map(|(_, value)| value.as_str())
+    }
+
+    /// Names and values, in file order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.vars.iter().map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    /// Names, in file order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.vars.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Number of variables
+    pub fn len(&self) -> usize {
+        self.vars.len()
+    }
+
+    /// Whether there are no variables
+    pub fn is_empty(&self) -> bool {
+        self.vars.is_empty()
+    }
+
+    /// The file as `.env` text that [`Self::parse`] reads back unchanged
+    pub fn to_dotenv(&self) -> SecureString {
+        let mut out = SecureString::new();
+        for (name, value) in &self.vars {
+            push_str(&mut out, name);
+            out.push('=');
+            if value.chars().all(|c| c.is_ascii_alphanumeric() || "_-.,:/@+%".contains(c)) {
+                push_str(&mut out, value);
+            } else if !value.contains(['\'', '\n', '\r']) {
+                // Single quotes, so loaders that expand `$VAR` leave the value alone
+                out.push('\'');
+                push_str(&mut out, value);
+                out.push('\'');
+            } else {
+                out.push('"');
+                for c in value.chars() {
+                    match c {
+                        '\n' => push_str(&mut out, "\\n"),
+                        '\r' => push_str(&mut out, "\\r"),
+                        '\t' => push_str(&mut out, "\\t"),
+                        '"' | '\\' | '$' => {
+                            out.push('\\');
+                            out.push(c);
+                        }
+                        c => out.push(c),
+                    }
+                }
+                out.push('"');
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// `export NAME='value'` lines for `eval` in a POSIX shell
+    pub fn to_shell(&self) -> SecureString {
+        let mut out = SecureString::new();
+        for (name, value) in &self.vars {
+            push_str(&mut out, "export ");
+            push_str(&mut out, name);
+            push_str(&mut out, "='");
+            for c in value.chars() {
+                match c {
+                    '\'' => push_str(&mut out, "'\\''"),
+                    c => out.push(c),
+                }
+            }
+            push_str(&mut out, "'\n");
+        }
+        out
+    }
+
+    /// A JSON object of names and values, in file order
+    pub fn to_json(&self) -> SecureString {
+        let mut out = SecureString::new();
+        out.push('{');
+        for (i, (name, value)) in self.vars.iter().enumerate() {
+            push_str(&mut out, if i == 0 { "\n  " } else { ",\n  " });
+            push_json_string(&mut out, name);
+            push_str(&mut out, ": ");
+            push_json_string(&mut out, value);
+        }
+        push_str(&mut out, if self.vars.is_empty() { "}\n" } else { "\n}\n" });
+        out
+    }
+}
+
+impl fmt::Debug for Dotenv {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Dotenv").field("names", &self.names().collect::<Vec<_>>()).finish()
+    }
+}
+
+/// Differences between two sets of variables, by name
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct EnvChanges {
+    /// Names only in the new file
+    pub added: Vec<String>,
+    /// Names only in the old file
+    pub removed: Vec<String>,
+    /// Names in both with different values
+    pub changed: Vec<String>,
+}
+
+impl EnvChanges {
+    /// Names added and removed between `old` and `new`, without comparing values
+    pub fn between_names<'a>(
+        old: impl IntoIterator<Item = &'a str>,
+        new: impl IntoIterator<Item = &'a str>,
+    ) -> Self {
+        let old: Vec<&str> = old.into_iter().collect();
+        let new: Vec<&str> = new.into_iter().collect();
+        Self {
+            added: new.iter().filter(|name| !old.contains(name)).map(|name| name.to_string()).collect(),
+            removed: old.iter().filter(|name| !new.contains(name)).map(|name| name.to_string()).collect(),
+            changed: Vec::new(),
+        }
+    }
+
+    /// Names added, removed and changed between `old` and `new`
+    pub fn between(old: &Dotenv, new: &Dotenv) -> Self {
+        let mut changes = Self::between_names(old.names(), new.names());
+        changes.changed = new
+            .iter()
+            .filter(|(name, value)| old.get(name).is_some_and(|old| old != *value))
+            .map(|(name, _)| name.to_string())
+            .collect();
+        changes
+    }
+
+    /// Whether nothing differs
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Header line of an encrypted `.env` file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Header {
+    version: u32,
+    kdf: Kdf,
+    #[serde(with = "hex")]
+    salt: [u8; defaults::SALT_LENGTH],
+    /// An empty message sealed with the key, to check the password
+    check: String,
+}
+
+/// A `.env` file with each value encrypted, as written to `.env.enc`
+#[derive(Debug, Clone)]
+pub struct EncryptedDotenv {
+    header: Header,
+    vars: Vec<(String, String)>,
+}
+
+impl EncryptedDotenv {
+    /// An empty file protected by `password`, with the key to fill it
+    pub fn new(password: &str, kdf: Kdf) -> CryptoResult<(Self, DerivedKey)> {
+        kdf.validate()?;
+        let params = KeyDerivationParams::new_random()?.with_kdf(kdf);
+        let key = DerivedKey::from_password(password, &params)?;
+        let header = Header {
+            version: 1,
+            kdf,
+            salt: params.salt,
+            check: seal(&key, CHECK_AAD, b"")?,
+        };
+        Ok((Self { header, vars: Vec::new() }, key))
+    }
+
+    /// Whether `content` looks like an encrypted `.env` file
+    pub fn is_encrypted_dotenv(content: &[u8]) -> bool {
+        content.starts_with(HEADER_PREFIX.as_bytes())
+    }
+
+    /// Parse an encrypted `.env` file
+    pub fn parse(content: &str) -> CryptoResult<Self> {
+        let mut lines = content.lines().enumerate();
+        let header = lines
+            .next()
+            .and_then(|(_, line)| line.strip_prefix(HEADER_PREFIX))
+            .ok_or_else(|| invalid("Not an encrypted .env file: the cargocrypt-env header is missing".to_string(), Vec::new()))?;
+        let header: Header = serde_json::from_str(header)
+            .map_err(|e| invalid(format!("Invalid encrypted .env header: {}", e), Vec::new()))?;
+        if header.version != 1 {
+            return Err(invalid(
+                format!("Encrypted .env version {} is not supported; upgrade cargocrypt", header.version),
+                Vec::new(),
+            ));
+        }
+
+        let mut vars: Vec<(String, String)> = Vec::new();
+        let mut errors = Vec::new();
+        for (number, line) in lines {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line.split_once('=') {
+                Some((name, value)) if is_env_name(name) && value.starts_with(VALUE_PREFIX) => {
+                    if vars.iter().any(|(existing, _)| existing == name) {
+                        errors.push(format!("line {}: {} is set twice", number + 1, name));
+                    }
+                    vars.push((name.to_string(), value.to_string()));
+                }
+                _ => errors.push(format!("line {}: expected NAME={}…", number + 1, VALUE_PREFIX)),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(invalid(format!("Invalid encrypted .env file: {}", errors.join("; ")), errors));
+        }
+        Ok(Self { header, vars })
+    }
+
+    /// Derive the key from `password`, failing if it is the wrong password
+    pub fn unlock(&self, password: &str) -> CryptoResult<DerivedKey> {
+        let params = KeyDerivationParams::from_salt(self.header.salt)?.with_kdf(self.header.kdf);
+        let key = DerivedKey::from_password(password, &params)?;
+        open(&key, CHECK_AAD, &self.header.check).map_err(|_| CargoCryptError::from(CryptoError::authentication_failed()))?;
+        Ok(key)
+    }
+
+    /// Names of the variables, in file order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.vars.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Number of variables
+    pub fn len(&self) -> usize {
+        self.vars.len()
+    }
+
+    /// Whether there are no variables
+    pub fn is_empty(&self) -> bool {
+        self.vars.is_empty()
+    }
+
+    /// Decrypt every value
+    pub fn decrypt(&self, key: &DerivedKey) -> CryptoResult<Dotenv> {
+        let mut dotenv = Dotenv::new();
+        for (name, value) in &self.vars {
+            let plaintext = open(key, name.as_bytes(), value)?;
+            let plaintext = String::from_utf8(plaintext).map_err(|e| {
+                e.into_bytes().zeroize();
+                invalid(format!("The value of {} is not UTF-8 text", name), Vec::new())
+            })?;
+            dotenv.insert(name, SecureString::from(plaintext))?;
+        }
+        Ok(dotenv)
+    }
+
+    /// How `dotenv` differs from the encrypted values
+    pub fn diff(&self, key: &DerivedKey, dotenv: &Dotenv) -> CryptoResult<EnvChanges> {
+        Ok(EnvChanges::between(&self.decrypt(key)?, dotenv))
+    }
+
+    /// The variables of `dotenv` to encrypt, without the defaults of `example`
+    ///
+    /// A variable set to its value in `example` comes back with the example
+    /// when decrypting, so it is left out unless this file already has it.
+    pub fn overrides(&self, dotenv: &Dotenv, example: Option<&Dotenv>) -> Dotenv {
+        let mut overrides = dotenv.clone();
+        if let Some(example) = example {
+            overrides.retain(|name, value| example.get(name) != Some(value) || self.names().any(|existing| existing == name));
+        }
+        overrides
+    }
+
+    /// Replace the variables with those of `dotenv`, returning what changed
+    ///
+    /// Unchanged values keep their ciphertext.
+    pub fn update(&mut self, key: &DerivedKey, dotenv: &Dotenv) -> CryptoResult<EnvChanges> {
+        let changes = self.diff(key, dotenv)?;
+        let mut vars = Vec::with_capacity(dotenv.len());
+        for (name, value) in dotenv.iter() {
+            let unchanged = !changes.added.iter().chain(&changes.changed).any(|changed| changed == name);
+            let sealed = match self.vars.iter().find(|(existing, _)| existing == name) {
+                Some((_, sealed)) if unchanged => sealed.clone(),
+                _ => seal(key, name.as_bytes(), value.as_bytes())?,
+            };
+            vars.push((name.to_string(), sealed));
+        }
+        self.vars = vars;
+        Ok(changes)
+    }
+}
+
+impl fmt::Display for EncryptedDotenv {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let header = serde_json::to_string(&self.header).map_err(|_| fmt::Error)?;
+        writeln!(f, "{}{}", HEADER_PREFIX, header)?;
+        for (name, value) in &self.vars {
+            writeln!(f, "{}={}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether `name` can be exported by a POSIX shell
+pub(crate) fn is_env_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// One `.env` line: `None` for blank lines and comments
+fn parse_line(line: &str) -> Result<Option<(&str, SecureString)>, String> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+    let line = line.strip_prefix("export ").map(str::trim_start).unwrap_or(line);
+    let Some((name, value)) = line.split_once('=') else {
+        return Err("expected NAME=value".to_string());
+    };
+    let name = name.trim_end();
+    if !is_env_name(name) {
+        return Err(format!("'{}' is not a valid variable name", name));
+    }
+
+    let value = value.trim_start();
+    let mut parsed = SecureString::with_capacity(value.len());
+    let rest = if let Some(quoted) = value.strip_prefix('\'') {
+        let end = quoted.find('\'').ok_or_else(|| format!("unterminated quote in {}", name))?;
+        push_str(&mut parsed, &quoted[..end]);
+        &quoted[end + 1..]
+    } else if let Some(quoted) = value.strip_prefix('"') {
+        let mut chars = quoted.char_indices();
+        let end = loop {
+            match chars.next() {
+                Some((i, '"')) => break i,
+                Some((_, '\\')) => match chars.next() {
+                    Some((_, 'n')) => parsed.push('\n'),
+                    Some((_, 't')) => parsed.push('\t'),
+                    Some((_, 'r')) => parsed.push('\r'),
+                    Some((_, c)) => parsed.push(c),
+                    None => return Err(format!("unterminated quote in {}", name)),
+                },
+                Some((_, c)) => parsed.push(c),
+                None => return Err(format!("unterminated quote in {}", name)),
+            }
+        };
+        &quoted[end + 1..]
+    } else {
+        // A `#` after whitespace starts a comment; `a#b` is part of the value
+        let end = match value.starts_with('#') {
+            true => 0,
+            false => value.find(" #").or_else(|| value.find("\t#")).unwrap_or(value.len()),
+        };
+        push_str(&mut parsed, value[..end].trim_end());
+        ""
+    };
+
+    let rest = rest.trim_start();
+    if !rest.is_empty() && !rest.starts_with('#') {
+        return Err(format!("unexpected text after the value of {}", name));
+    }
+    Ok(Some((name, parsed)))
+}
+
+fn push_str(out: &mut SecureString, s: &str) {
+    s.chars().for_each(|c| out.push(c));
+}
+
+fn push_json_string(out: &mut SecureString, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => push_str(out, "\\\""),
+            '\\' => push_str(out, "\\\\"),
+            '\n' => push_str(out, "\\n"),
+            '\r' => push_str(out, "\\r"),
+            '\t' => push_str(out, "\\t"),
+            c if (c as u32) < 0x20 => {
+                push_str(out, "\\u00");
+                out.push(char::from_digit(c as u32 >> 4, 16).unwrap_or('0'));
+                out.push(char::from_digit(c as u32 & 0xf, 16).unwrap_or('0'));
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn seal(key: &DerivedKey, aad: &[u8], plaintext: &[u8]) -> CryptoResult<String> {
+    let nonce = SecureRandom::generate_nonce()?;
+    let ciphertext = ChaCha20Poly1305::new(key.key())
+        .encrypt(Nonce::from_slice(&nonce), Payload { msg: plaintext, aad })
+        .map_err(|_| CryptoError::encryption("Failed to encrypt a .env value"))?;
+    Ok(format!("{}{}", VALUE_PREFIX, Base64::encode_string(&[nonce.as_slice(), &ciphertext].concat())))
+}
+
+fn open(key: &DerivedKey, aad: &[u8], sealed: &str) -> CryptoResult<Vec<u8>> {
+    let name = String::from_utf8_lossy(aad);
+    let sealed = sealed
+        .strip_prefix(VALUE_PREFIX)
+        .and_then(|sealed| Base64::decode_vec(sealed).ok())
+        .filter(|sealed| sealed.len() >= defaults::NONCE_LENGTH + defaults::TAG_LENGTH)
+        .ok_or_else(|| CryptoError::decryption(format!("The encrypted value of {} is corrupt", name)))?;
+    let (nonce, ciphertext) = sealed.split_at(defaults::NONCE_LENGTH);
+    let plaintext = ChaCha20Poly1305::new(key.key())
+        .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad })
+        .map_err(|_| CryptoError::decryption(format!("The value of {} does not decrypt with this password", name)))?;
+    Ok(plaintext)
+}
+
+fn invalid(message: String, errors: Vec<String>) -> CargoCryptError {
+    CargoCryptError::Validation { message, errors, warnings: Vec::new() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn weak_kdf() -> Kdf {
+        Kdf::Pbkdf2Sha256 { iterations: 1 }
+    }
+
+    #[test]
+    fn test_dotenv_parsing() {
+        let content = [
+            "# Local development",
+            "DATABASE_URL=postgres://app:pw@localhost/app  # inline comment",
+            "export API_KEY = 'sk_live_#not_a_comment'",
+            "GREETING=\"line one\\nline \\\"two\\\"\"",
+            "",
+            "EMPTY=   # nothing yet",
+            "TOKEN=abc#def",
+            "DATABASE_URL=postgres://override",
+        ]
+        .join("\n");
+        let dotenv = Dotenv::parse(&content).unwrap();
+        assert_eq!(dotenv.names().collect::<Vec<_>>(), ["DATABASE_URL", "API_KEY", "GREETING", "EMPTY", "TOKEN"]);
+        assert_eq!(dotenv.get("DATABASE_URL"), Some("postgres://override"));
+        assert_eq!(dotenv.get("API_KEY"), Some("sk_live_#not_a_comment"));
+        assert_eq!(dotenv.get("GREETING"), Some("line one\nline \"two\""));
+        assert_eq!(dotenv.get("EMPTY"), Some(""));
+        assert_eq!(dotenv.get("TOKEN"), Some("abc#def"));
+
+        // Written back, the values read the same
+        let written = Dotenv::parse(&dotenv.to_dotenv()).unwrap();
+        assert_eq!(written.iter().collect::<Vec<_>>(), dotenv.iter().collect::<Vec<_>>());
+        assert!(dotenv.to_shell().contains("export API_KEY='sk_live_#not_a_comment'\n"));
+        let json: serde_json::Value = serde_json::from_str(&dotenv.to_json()).unwrap();
+        assert_eq!(json["GREETING"], "line one\nline \"two\"");
+
+        match Dotenv::parse("OK=1\nnot an assignment\n2BAD=x\nOPEN=\"never closed\n") {
+            Err(CargoCryptError::Validation { errors, .. }) => assert_eq!(errors.len(), 3),
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_encrypted_dotenv_roundtrip() {
+        let dotenv = Dotenv::parse("DATABASE_URL=postgres://local\nAPI_KEY=sk_test_1\n").unwrap();
+        let (mut encrypted, key) = EncryptedDotenv::new("correct horse", weak_kdf()).unwrap();
+        let changes = encrypted.update(&key, &dotenv).unwrap();
+        assert_eq!(changes.added, ["DATABASE_URL", "API_KEY"]);
+
+        let written = encrypted.to_string();
+        assert!(EncryptedDotenv::is_encrypted_dotenv(written.as_bytes()));
+        assert!(written.contains("\nDATABASE_URL=enc:v1:"));
+        assert!(!written.contains("sk_test_1"));
+
+        let parsed = EncryptedDotenv::parse(&written).unwrap();
+        assert!(parsed.unlock("wrong password").is_err());
+        let key = parsed.unlock("correct horse").unwrap();
+        let decrypted = parsed.decrypt(&key).unwrap();
+        assert_eq!(decrypted.iter().collect::<Vec<_>>(), dotenv.iter().collect::<Vec<_>>());
+
+        // Only the changed value gets new ciphertext
+        let mut updated = parsed.clone();
+        let edited = Dotenv::parse("DATABASE_URL=postgres://local\nAPI_KEY=sk_test_2\nDEBUG=1\n").unwrap();
+        let changes = updated.update(&key, &edited).unwrap();
+        assert_eq!(changes, EnvChanges { added: vec!["DEBUG".into()], removed: vec![], changed: vec!["API_KEY".into()] });
+        let old_lines: Vec<String> = written.lines().map(str::to_string).collect();
+        let new_text = updated.to_string();
+        let new_lines: Vec<&str> = new_text.lines().collect();
+        assert_eq!(new_lines[1], old_lines[1]);
+        assert_ne!(new_lines[2], old_lines[2]);
+
+        // A value moved to another name does not decrypt
+        let swapped = written.replacen("DATABASE_URL=", "OTHER=", 1);
+        let swapped = EncryptedDotenv::parse(&swapped).unwrap();
+        assert!(swapped.decrypt(&key).is_err());
+    }
+}
@@ -13,6 +13,29 @@ pub enum ErrorSeverity {
     Critical,
 }
 
+/// Exit codes of the `cargocrypt` command
+///
+/// These are stable so scripts can branch on them; new codes may be added,
+/// but existing ones keep their meaning.
+pub mod exit_code {
+    /// Success
+    pub const OK: i32 = 0;
+    /// Any failure without a more specific code
+    pub const FAILURE: i32 = 1;
+    /// A scan, hook, policy or check found secrets or violations
+    pub const FINDINGS: i32 = 2;
+    /// Wrong password or key, a file that does not decrypt, or a signature that does not verify
+    pub const AUTH: i32 = 3;
+    /// Invalid configuration, or no project to work on
+    pub const CONFIG: i32 = 4;
+    /// Input is needed, but prompting is disabled with `--non-interactive`
+    pub const INPUT_REQUIRED: i32 = 5;
+    /// Another cargocrypt process holds a lock
+    pub const LOCKED: i32 = 6;
+    /// Invalid command-line arguments
+    pub const USAGE: i32 = 64;
+}
+
 /// Result type alias for CargoCrypt operations
 pub type CryptoResult<T> = Result<T, CargoCryptError>;
 
@@ -86,6 +109,20 @@ pub enum CargoCryptError {
         source: Option<git2::Error>,
     },
 
+    /// Another cargocrypt process holds the lock on a file
+    #[error("{} is locked by another cargocrypt process", path.display())]
+    Locked {
+        path: std::path::PathBuf,
+        retry_suggestion: Option<String>,
+    },
+
+    /// Input is needed, but prompting is disabled
+    #[error("Input required: {message}")]
+    InputRequired {
+        message: String,
+        suggestion: Option<String>,
+    },
+
     /// Validation errors
     #[error("Validation failed: {message}")]
     Validation {
@@ -114,6 +151,8 @@ pub enum CryptoErrorKind {
     UnsupportedAlgorithm,
     /// Random number generation failed
     RandomGenerationFailed,
+    /// Signature verification failed
+    InvalidSignature,
 }
 
 /// Commonly used error constructors for better ergonomics
@@ -123,6 +162,7 @@ impl CargoCryptError {
         match self {
             CargoCryptError::Crypto { kind, .. } => match kind {
                 CryptoErrorKind::AuthenticationFailed |
+                CryptoErrorKind::InvalidSignature |
                 CryptoErrorKind::Decryption |
                 CryptoErrorKind::InvalidKey => ErrorSeverity::Critical,
                 _ => ErrorSeverity::Warning,
@@ -136,6 +176,8 @@ impl CargoCryptError {
             CargoCryptError::Config { .. } => ErrorSeverity::Info,
             CargoCryptError::Project { .. } => ErrorSeverity::Info,
             CargoCryptError::Serialization { .. } => ErrorSeverity::Warning,
+            CargoCryptError::Locked { .. } => ErrorSeverity::Warning,
+            CargoCryptError::InputRequired { .. } => ErrorSeverity::Info,
         }
     }
     /// Create a project not found error with helpful suggestion
@@ -173,6 +215,33 @@ impl CargoCryptError {
         }
     }
 
+    /// Create an error for an encrypted file that would be overwritten
+    pub fn already_exists(path: &std::path::Path) -> Self {
+        Self::Io {
+            message: format!("{} already exists (use --force to overwrite it)", path.display()),
+            source: std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("File '{}' already exists", path.display()),
+            ),
+        }
+    }
+
+    /// Create an error for a file locked by another process
+    pub fn locked(path: &std::path::Path) -> Self {
+        Self::Locked {
+            path: path.to_path_buf(),
+            retry_suggestion: Some("Wait for the other operation to finish and retry, or pass --wait <SECONDS> to wait for it".to_string()),
+        }
+    }
+
+    /// Create an error for input that cannot be prompted for
+    pub fn input_required(message: impl Into<String>, suggestion: impl Into<String>) -> Self {
+        Self::InputRequired {
+            message: message.into(),
+            suggestion: Some(suggestion.into()),
+        }
+    }
+
     /// Create a decryption failure error
     pub fn decryption_failed(details: &str) -> Self {
         Self::Crypto {
@@ -237,11 +306,20 @@ impl CargoCryptError {
         }
     }
 
+    /// Whether a wrong password or key may have caused this error, so asking again can help
+    pub fn is_authentication_failure(&self) -> bool {
+        matches!(
+            self,
+            CargoCryptError::Auth { .. } | CargoCryptError::Crypto { kind: CryptoErrorKind::AuthenticationFailed, .. }
+        )
+    }
+
     /// Check if this error is recoverable (user can retry)
     pub fn is_recoverable(&self) -> bool {
         match self {
             CargoCryptError::Auth { .. } => true,
             CargoCryptError::Network { .. } => true,
+            CargoCryptError::Locked { .. } => true,
             CargoCryptError::Io { source, .. } => matches!(
                 source.kind(),
                 std::io::ErrorKind::NotFound
@@ -264,9 +342,29 @@ impl CargoCryptError {
             CargoCryptError::Project { suggestion, .. } => suggestion.as_deref(),
             CargoCryptError::Auth { retry_suggestion, .. } => retry_suggestion.as_deref(),
             CargoCryptError::KeyManagement { recovery_suggestion, .. } => recovery_suggestion.as_deref(),
+            CargoCryptError::Locked { retry_suggestion, .. } => retry_suggestion.as_deref(),
+            CargoCryptError::InputRequired { suggestion, .. } => suggestion.as_deref(),
             _ => None,
         }
     }
+
+    /// The [`exit_code`] the command exits with for this error
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CargoCryptError::Auth { .. } => exit_code::AUTH,
+            CargoCryptError::Crypto {
+                kind: CryptoErrorKind::AuthenticationFailed
+                    | CryptoErrorKind::InvalidSignature
+                    | CryptoErrorKind::Decryption
+                    | CryptoErrorKind::InvalidKey,
+                ..
+            } => exit_code::AUTH,
+            CargoCryptError::Config { .. } | CargoCryptError::Project { .. } => exit_code::CONFIG,
+            CargoCryptError::InputRequired { .. } => exit_code::INPUT_REQUIRED,
+            CargoCryptError::Locked { .. } => exit_code::LOCKED,
+            _ => exit_code::FAILURE,
+        }
+    }
 }
 
 /// Convert from standard I/O errors
@@ -290,8 +388,9 @@ impl From<crate::crypto::CryptoError> for CargoCryptError {
             CryptoError::Decryption { .. } => CryptoErrorKind::Decryption,
             CryptoError::AuthenticationFailed => CryptoErrorKind::AuthenticationFailed,
             CryptoError::InvalidKey { .. } => CryptoErrorKind::InvalidKey,
-            CryptoError::InvalidNonce { .. } => CryptoErrorKind::InvalidNonce,
+            CryptoError::InvalidNonce { .. } | CryptoError::NonceReuse => CryptoErrorKind::InvalidNonce,
             CryptoError::RandomGeneration { .. } => CryptoErrorKind::RandomGenerationFailed,
+            CryptoError::InvalidSignature { .. } => CryptoErrorKind::InvalidSignature,
             _ => CryptoErrorKind::Encryption, // Default fallback
         };
         
@@ -352,7 +451,8 @@ impl From<crate::git::GitError> for CargoCryptError {
 }
 
 /// Error kind enumeration for programmatic error handling
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ErrorKind {
     /// Configuration-related errors
     Config,
@@ -364,6 +464,8 @@ pub enum ErrorKind {
     Network,
     /// Authentication/authorization errors
     Auth,
+    /// A password or key that does not decrypt, or data that does not authenticate
+    AuthenticationFailed,
     /// Git operation errors
     Git,
     /// Project structure errors
@@ -380,6 +482,7 @@ impl CargoCryptError {
         match self {
             CargoCryptError::Config { .. } => ErrorKind::Config,
             CargoCryptError::Io { .. } => ErrorKind::Io,
+            CargoCryptError::Crypto { kind: CryptoErrorKind::AuthenticationFailed, .. } => ErrorKind::AuthenticationFailed,
             CargoCryptError::Crypto { .. } => ErrorKind::Crypto,
             CargoCryptError::Network { .. } => ErrorKind::Network,
             CargoCryptError::Auth { .. } => ErrorKind::Auth,
@@ -388,6 +491,8 @@ impl CargoCryptError {
             CargoCryptError::KeyManagement { .. } => ErrorKind::KeyManagement,
             CargoCryptError::Serialization { .. } => ErrorKind::Serialization,
             CargoCryptError::Validation { .. } => ErrorKind::Config,
+            CargoCryptError::Locked { .. } => ErrorKind::Io,
+            CargoCryptError::InputRequired { .. } => ErrorKind::Config,
         }
     }
 }
@@ -431,4 +536,17 @@ mod tests {
         let config_err = CargoCryptError::config_not_found();
         assert!(!config_err.is_recoverable());
     }
+
+    #[test]
+    fn test_exit_codes() {
+        assert_eq!(CargoCryptError::invalid_password().exit_code(), exit_code::AUTH);
+        assert_eq!(CargoCryptError::decryption_failed("test").exit_code(), exit_code::AUTH);
+        assert_eq!(CargoCryptError::encryption_failed("test").exit_code(), exit_code::FAILURE);
+        assert_eq!(CargoCryptError::config_not_found().exit_code(), exit_code::CONFIG);
+        assert_eq!(CargoCryptError::project_not_found().exit_code(), exit_code::CONFIG);
+        assert_eq!(CargoCryptError::locked(std::path::Path::new("a.enc")).exit_code(), exit_code::LOCKED);
+        let err = CargoCryptError::input_required("A password is needed", "Pass --password-stdin");
+        assert_eq!(err.exit_code(), exit_code::INPUT_REQUIRED);
+        assert_eq!(err.suggestion(), Some("Pass --password-stdin"));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,414 @@
+//! Expiry tracking for secrets and encrypted files
+//!
+//! Secrets such as TLS certificates or rotating API keys have a lifetime. An
+//! expiry date can be attached via [`SecretMetadata::expires_at`], and a
+//! rotation interval via [`SecretMetadata::ttl_secs`], which makes a secret
+//! due `ttl_secs` after it was created; whichever comes first counts. Since
+//! file metadata lives in the unencrypted header, files can be checked
+//! without a password. Items due within the warning window (30 days by
+//! default) are reported by `cargocrypt status`, the git hooks, the TUI,
+//! `cargocrypt report expiring` and, for the secret store,
+//! `cargocrypt secret expiring`.
+
+use crate::crypto::{EncryptedSecret, SecretMetadata, SecretStore};
+use crate::error::{CargoCryptError, CryptoResult};
+use crate::utils::unix_now;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::debug;
+use walkdir::WalkDir;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Directories never searched for encrypted files
+const SKIPPED_DIRS: &[&str] = &[".git", "target", "node_modules"];
+
+/// Expiry warning settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExpiryConfig {
+    /// Warn about items expiring within this many days
+    pub warning_days: u32,
+}
+
+impl Default for ExpiryConfig {
+    fn default() -> Self {
+        Self { warning_days: 30 }
+    }
+}
+
+impl ExpiryConfig {
+    /// The warning window as a duration
+    pub fn warning_window(&self) -> Duration {
+        Duration::from_secs(u64::from(self.warning_days) * SECONDS_PER_DAY)
+    }
+}
+
+/// Where an item with an expiry date lives
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpirySource {
+    /// An encrypted file
+    File(PathBuf),
+    /// An entry in the secret store
+    StoreEntry(String),
+}
+
+impl fmt::Display for ExpirySource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::File(path) => write!(f, "{}", path.display()),
+            Self::StoreEntry(key) => write!(f, "store:{}", key),
+        }
+    }
+}
+
+/// How close an item is to its expiry date
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpiryStatus {
+    /// Expires after the warning window
+    Valid,
+    /// Expires within the warning window
+    ExpiringSoon,
+    /// Already expired
+    Expired,
+}
+
+impl ExpiryStatus {
+    /// Status of an expiry timestamp relative to `now`
+    pub fn at(expires_at: u64, now: u64, warning_window: Duration) -> Self {
+        if expires_at <= now {
+            Self::Expired
+        } else if expires_at - now <= warning_window.as_secs() {
+            Self::ExpiringSoon
+        } else {
+            Self::Valid
+        }
+    }
+
+    /// Whether the item should be rotated soon
+    pub fn needs_attention(&self) -> bool {
+        !matches!(self, Self::Valid)
+    }
+}
+
+impl fmt::Display for ExpiryStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Valid => "valid",
+            Self::ExpiringSoon => "expiring soon",
+            Self::Expired => "expired",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A secret or file with an expiry date
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExpiringItem {
+    /// Where the item lives
+    pub source: ExpirySource,
+    /// Expiry time (Unix timestamp)
+    pub expires_at: u64,
+    /// Status at the time of the check
+    pub status: ExpiryStatus,
+    /// Description from the secret metadata
+    pub description: Option<String>,
+    /// Whether `expires_at` is a rotation deadline from the TTL rather than an expiry date
+    #[serde(default)]
+    pub rotation: bool,
+}
+
+impl ExpiringItem {
+    /// Whole days until expiry, negative once expired
+    pub fn days_remaining(&self, now: u64) -> i64 {
+        (self.expires_at as i64 - now as i64).div_euclid(SECONDS_PER_DAY as i64)
+    }
+
+    /// Expiry date formatted as `YYYY-MM-DD`
+    pub fn expiry_date(&self) -> String {
+        format_expiry(self.expires_at)
+    }
+}
+
+/// Checks expiry dates of encrypted files and store entries
+#[derive(Debug, Clone)]
+pub struct ExpiryChecker {
+    warning_window: Duration,
+    encrypted_extension: String,
+    now: u64,
+}
+
+impl ExpiryChecker {
+    /// Create a checker warning about items expiring within `warning_window`
+    pub fn new(warning_window: Duration) -> Self {
+        Self {
+            warning_window,
+            encrypted_extension: "enc".to_string(),
+            now: unix_now(),
+        }
+    }
+
+    /// Set the extension used for encrypted files (default: `enc`)
+    pub fn with_encrypted_extension<S: Into<String>>(mut self, extension: S) -> Self {
+        self.encrypted_extension = extension.into();
+        self
+    }
+
+    /// Evaluate expiry at `now` instead of the current time
+    pub fn at_time(mut self, now: u64) -> Self {
+        self.now = now;
+        self
+    }
+
+    /// The time expiry is evaluated at (Unix timestamp)
+    pub fn now(&self) -> u64 {
+        self.now
+    }
+
+    /// Check the metadata of one item, if it has an expiry date or a TTL
+    pub fn check(&self, source: ExpirySource, metadata: &SecretMetadata) -> Option<ExpiringItem> {
+        let (expires_at, rotation) = match (metadata.expires_at, metadata.rotation_deadline()) {
+            (Some(expires_at), Some(deadline)) if deadline < expires_at => (deadline, true),
+            (Some(expires_at), _) => (expires_at, false),
+            (None, Some(deadline)) => (deadline, true),
+            (None, None) => return None,
+        };
+        Some(ExpiringItem {
+            source,
+            expires_at,
+            status: ExpiryStatus::at(expires_at, self.now, self.warning_window),
+            description: metadata.description.clone(),
+            rotation,
+        })
+    }
+
+    /// Find encrypted files with an expiry date below `root`
+    ///
+    /// Paths are reported relative to `root`. Unreadable files are skipped.
+    pub fn scan_directory<P: AsRef<Path>>(&self, root: P) -> Vec<ExpiringItem> {
+        let root = root.as_ref();
+
+        let mut items = Vec::new();
+        for path in find_encrypted_files(root, &self.encrypted_extension) {
+            let path = path.as_path();
+            let secret = match read_secret(path) {
+                Ok(secret) => secret,
+                Err(e) => {
+                    debug!("Skipping {} for expiry check: {}", path.display(), e);
+                    continue;
+                }
+            };
+
+            let relative = path.strip_prefix(root).unwrap_or(path).to_path_buf();
+            items.extend(self.check(ExpirySource::File(relative), secret.metadata()));
+        }
+
+        sort_items(&mut items);
+        items
+    }
+
+    /// Find store entries with an expiry date
+    pub async fn check_store(&self, store: &dyn SecretStore) -> CryptoResult<Vec<ExpiringItem>> {
+        let mut items = Vec::new();
+        for key in store.list().await? {
+            if let Some(secret) = store.retrieve(&key).await? {
+                items.extend(self.check(ExpirySource::StoreEntry(key), secret.metadata()));
+            }
+        }
+
+        sort_items(&mut items);
+        Ok(items)
+    }
+}
+
+/// Parse an expiry given as `YYYY-MM-DD` (end of that day, UTC) or RFC 3339
+pub fn parse_expiry(value: &str) -> CryptoResult<u64> {
+    let timestamp = if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        date.and_hms_opt(23, 59, 59).map(|dt| dt.and_utc().timestamp())
+    } else {
+        DateTime::parse_from_rfc3339(value).ok().map(|dt| dt.timestamp())
+    };
+
+    timestamp
+        .and_then(|ts| u64::try_from(ts).ok())
+        .ok_or_else(|| CargoCryptError::Validation {
+            message: format!("Invalid expiry date: {}", value),
+            errors: vec!["Expected YYYY-MM-DD or an RFC 3339 timestamp".to_string()],
+            warnings: Vec::new(),
+        })
+}
+
+/// Parse a rotation interval such as `90d`, `12w` or `36h`; a bare number is days
+pub fn parse_ttl(value: &str) -> CryptoResult<u64> {
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => value.split_at(index),
+        None => (value, "d"),
+    };
+    let unit_secs = match unit {
+        "h" => 60 * 60,
+        "d" => SECONDS_PER_DAY,
+        "w" => 7 * SECONDS_PER_DAY,
+        _ => 0,
+    };
+
+    number
+        .parse::<u64>()
+        .ok()
+        .filter(|number| *number > 0 && unit_secs > 0)
+        .and_then(|number| number.checked_mul(unit_secs))
+        .ok_or_else(|| CargoCryptError::Validation {
+            message: format!("Invalid rotation interval: {}", value),
+            errors: vec!["Expected a number of hours, days or weeks, e.g. 36h, 90d or 12w".to_string()],
+            warnings: Vec::new(),
+        })
+}
+
+/// Format a rotation interval in the largest whole unit, e.g. `90d`
+pub fn format_ttl(ttl_secs: u64) -> String {
+    if ttl_secs.is_multiple_of(7 * SECONDS_PER_DAY) {
+        format!("{}w", ttl_secs / (7 * SECONDS_PER_DAY))
+    } else if ttl_secs.is_multiple_of(SECONDS_PER_DAY) {
+        format!("{}d", ttl_secs / SECONDS_PER_DAY)
+    } else {
+        format!("{}h", ttl_secs / (60 * 60))
+    }
+}
+
+/// Format an expiry timestamp as `YYYY-MM-DD`
+pub fn format_expiry(expires_at: u64) -> String {
+    i64::try_from(expires_at)
+        .ok()
+        .and_then(|ts| DateTime::<Utc>::from_timestamp(ts, 0))
+        .map(|dt| dt.format("%Y-%m-%d").to_string())
+        .unwrap_or_else(|| expires_at.to_string())
+}
+
+/// Encrypted files below `root`, skipping VCS, build and dependency directories
+pub(crate) fn find_encrypted_files(root: &Path, extension: &str) -> Vec<PathBuf> {
+    let walker = WalkDir::new(root).sort_by_file_name().into_iter().filter_entry(|entry| {
+        entry.depth() == 0
+            || !entry.file_type().is_dir()
+            || !SKIPPED_DIRS.iter().any(|dir| entry.file_name() == *dir)
+    });
+
+    walker
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            entry.file_type().is_file() && entry.path().extension().and_then(|e| e.to_str()) == Some(extension)
+        })
+        .map(|entry| entry.into_path())
+        .collect()
+}
+
+fn read_secret(path: &Path) -> CryptoResult<EncryptedSecret> {
+    Ok(EncryptedSecret::from_bytes(&std::fs::read(path)?)?)
+}
+
+fn sort_items(items: &mut [ExpiringItem]) {
+    items.sort_by(|a, b| a.expires_at.cmp(&b.expires_at).then_with(|| a.source.to_string().cmp(&b.source.to_string())));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{MemorySecretStore, PlaintextSecret};
+    use tempfile::TempDir;
+
+    const NOW: u64 = 1_800_000_000;
+
+    fn encrypted_with_expiry(expires_at: Option<u64>) -> EncryptedSecret {
+        let mut metadata = SecretMetadata::new();
+        metadata.expires_at = expires_at;
+        let plaintext = PlaintextSecret::from_string("cert".to_string());
+        EncryptedSecret::encrypt_with_password(plaintext, "expiry_password", Some(metadata)).unwrap()
+    }
+
+    #[test]
+    fn test_expiry_status() {
+        let window = Duration::from_secs(30 * SECONDS_PER_DAY);
+        assert_eq!(ExpiryStatus::at(NOW - 1, NOW, window), ExpiryStatus::Expired);
+        assert_eq!(ExpiryStatus::at(NOW + 10 * SECONDS_PER_DAY, NOW, window), ExpiryStatus::ExpiringSoon);
+        assert_eq!(ExpiryStatus::at(NOW + 60 * SECONDS_PER_DAY, NOW, window), ExpiryStatus::Valid);
+    }
+
+    #[test]
+    fn test_parse_expiry() {
+        let date = parse_expiry("2027-03-01").unwrap();
+        assert_eq!(format_expiry(date), "2027-03-01");
+        assert_eq!(parse_expiry("2027-03-01T00:00:00Z").unwrap(), date - (SECONDS_PER_DAY - 1));
+        assert!(parse_expiry("next week").is_err());
+    }
+
+    #[test]
+    fn test_scan_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        let soon = NOW + 5 * SECONDS_PER_DAY;
+        std::fs::create_dir(temp_dir.path().join("certs")).unwrap();
+        std::fs::write(temp_dir.path().join("certs/tls.pem.enc"), encrypted_with_expiry(Some(soon)).to_bytes().unwrap()).unwrap();
+        std::fs::write(temp_dir.path().join("plain.env.enc"), encrypted_with_expiry(None).to_bytes().unwrap()).unwrap();
+        std::fs::write(temp_dir.path().join("broken.enc"), b"not encrypted").unwrap();
+
+        let checker = ExpiryChecker::new(Duration::from_secs(30 * SECONDS_PER_DAY)).at_time(NOW);
+        let items = checker.scan_directory(temp_dir.path());
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].source, ExpirySource::File(PathBuf::from("certs/tls.pem.enc")));
+        assert_eq!(items[0].status, ExpiryStatus::ExpiringSoon);
+        assert_eq!(items[0].days_remaining(NOW), 5);
+    }
+
+    #[test]
+    fn test_parse_ttl() {
+        assert_eq!(parse_ttl("90d").unwrap(), 90 * SECONDS_PER_DAY);
+        assert_eq!(parse_ttl("90").unwrap(), 90 * SECONDS_PER_DAY);
+        assert_eq!(parse_ttl("2w").unwrap(), 14 * SECONDS_PER_DAY);
+        assert_eq!(parse_ttl("36h").unwrap(), 36 * 60 * 60);
+        for invalid in ["", "0d", "d", "5y", "-3d"] {
+            assert!(parse_ttl(invalid).is_err(), "{}", invalid);
+        }
+        assert_eq!(format_ttl(parse_ttl("14d").unwrap()), "2w");
+        assert_eq!(format_ttl(parse_ttl("90d").unwrap()), "90d");
+    }
+
+    #[test]
+    fn test_rotation_deadline() {
+        let checker = ExpiryChecker::new(Duration::from_secs(30 * SECONDS_PER_DAY)).at_time(NOW);
+        let mut metadata = SecretMetadata::new();
+        metadata.created_at = Some(NOW - 80 * SECONDS_PER_DAY);
+        metadata.set_ttl(Some(90 * SECONDS_PER_DAY));
+
+        let item = checker.check(ExpirySource::StoreEntry("api_key".to_string()), &metadata).unwrap();
+        assert!(item.rotation);
+        assert_eq!(item.status, ExpiryStatus::ExpiringSoon);
+        assert_eq!(item.days_remaining(NOW), 10);
+
+        // An earlier expiry date wins over the rotation deadline
+        metadata.set_expires_at(Some(NOW - 1));
+        let item = checker.check(ExpirySource::StoreEntry("api_key".to_string()), &metadata).unwrap();
+        assert!(!item.rotation);
+        assert_eq!(item.status, ExpiryStatus::Expired);
+    }
+
+    #[tokio::test]
+    async fn test_check_store() {
+        let store = MemorySecretStore::new();
+        store.store("expired", encrypted_with_expiry(Some(NOW - SECONDS_PER_DAY))).await.unwrap();
+        store.store("valid", encrypted_with_expiry(Some(NOW + 90 * SECONDS_PER_DAY))).await.unwrap();
+        store.store("forever", encrypted_with_expiry(None)).await.unwrap();
+
+        let checker = ExpiryChecker::new(Duration::from_secs(30 * SECONDS_PER_DAY)).at_time(NOW);
+        let items = checker.check_store(&store).await.unwrap();
+        let statuses: Vec<_> = items.iter().map(|i| (i.source.to_string(), i.status)).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ("store:expired".to_string(), ExpiryStatus::Expired),
+                ("store:valid".to_string(), ExpiryStatus::Valid),
+            ]
+        );
+        assert_eq!(items[0].days_remaining(NOW), -1);
+    }
+}
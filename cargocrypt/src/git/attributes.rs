@@ -3,8 +3,11 @@
 //! This module handles .gitattributes configuration for automatic encryption/decryption
 //! through git clean/smudge filters, similar to git-crypt and transcrypt patterns.
 
-use super::{GitRepo, GitError, GitResult, GitCryptConfig};
-use std::collections::HashMap;
+use super::gitcrypt;
+use super::{GitRepo, GitError, GitResult, GitCryptConfig, Workspace};
+use crate::plan::Plan;
+use crate::crypto::secrets::{EncryptedSecret, FILE_MAGIC};
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use tokio::fs;
 use serde::{Deserialize, Serialize};
@@ -16,6 +19,12 @@ pub struct AttributeConfig {
     pub patterns: HashMap<String, String>,
     /// Custom filter configurations
     pub filters: HashMap<String, FilterConfig>,
+    /// Merge drivers for encrypted files
+    #[serde(default)]
+    pub merge_drivers: HashMap<String, MergeDriverConfig>,
+    /// Diff drivers showing encrypted files decrypted
+    #[serde(default)]
+    pub diff_drivers: HashMap<String, DiffDriverConfig>,
     /// Whether to use clean/smudge filters
     pub enable_filters: bool,
     /// Default encryption attribute name
@@ -25,25 +34,40 @@ pub struct AttributeConfig {
 impl Default for AttributeConfig {
     fn default() -> Self {
         let mut patterns = HashMap::new();
-        patterns.insert("*.secret".to_string(), "cargocrypt-encrypt".to_string());
-        patterns.insert("*.key".to_string(), "cargocrypt-encrypt".to_string());
-        patterns.insert("secrets/*".to_string(), "cargocrypt-encrypt".to_string());
-        patterns.insert("config/secrets.*".to_string(), "cargocrypt-encrypt".to_string());
-        patterns.insert("*.env.local".to_string(), "cargocrypt-encrypt".to_string());
-        patterns.insert("*.env.production".to_string(), "cargocrypt-encrypt".to_string());
+        patterns.insert("*.secret".to_string(), "filter=cargocrypt-encrypt".to_string());
+        patterns.insert("*.key".to_string(), "filter=cargocrypt-encrypt".to_string());
+        patterns.insert("secrets/*".to_string(), "filter=cargocrypt-encrypt".to_string());
+        patterns.insert("config/secrets.*".to_string(), "filter=cargocrypt-encrypt".to_string());
+        patterns.insert("*.env.local".to_string(), "filter=cargocrypt-encrypt".to_string());
+        patterns.insert("*.env.production".to_string(), "filter=cargocrypt-encrypt".to_string());
+        patterns.insert("*.enc".to_string(), "merge=cargocrypt".to_string());
         
         let mut filters = HashMap::new();
         filters.insert("cargocrypt-encrypt".to_string(), FilterConfig {
-            clean: "cargocrypt filter-clean %f".to_string(),
-            smudge: "cargocrypt filter-smudge %f".to_string(),
+            clean: "cargocrypt git filter-clean %f".to_string(),
+            smudge: "cargocrypt git filter-smudge %f".to_string(),
             required: true,
         });
+
+        let mut merge_drivers = HashMap::new();
+        merge_drivers.insert("cargocrypt".to_string(), MergeDriverConfig {
+            name: "CargoCrypt merge of encrypted files".to_string(),
+            driver: "cargocrypt git merge-driver %O %A %B %P".to_string(),
+        });
+
+        let mut diff_drivers = HashMap::new();
+        diff_drivers.insert("cargocrypt".to_string(), DiffDriverConfig {
+            textconv: "cargocrypt git textconv".to_string(),
+            cache_textconv: false,
+        });
         
         Self {
             patterns,
             filters,
+            merge_drivers,
+            diff_drivers,
             enable_filters: true,
-            default_encrypt_attr: "cargocrypt-encrypt".to_string(),
+            default_encrypt_attr: "filter=cargocrypt-encrypt".to_string(),
         }
     }
 }
@@ -59,12 +83,30 @@ pub struct FilterConfig {
     pub required: bool,
 }
 
+/// Git merge driver configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergeDriverConfig {
+    /// Description shown by git
+    pub name: String,
+    /// Merge command, with git's %O (base), %A (ours and result), %B (theirs) and %P (path)
+    pub driver: String,
+}
+
+/// Git diff driver configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffDriverConfig {
+    /// Command git runs on a file holding a blob, printing its text
+    pub textconv: String,
+    /// Whether git may cache the text in `refs/notes/textconv`, which stores it unencrypted
+    pub cache_textconv: bool,
+}
+
 /// Represents an encryption pattern for git attributes
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct EncryptionPattern {
     /// File pattern (e.g., "*.secret")
     pub pattern: String,
-    /// Attribute name (e.g., "cargocrypt-encrypt")
+    /// Attribute (e.g., "filter=cargocrypt-encrypt")
     pub attribute: String,
     /// Additional attributes
     pub extra_attrs: Vec<String>,
@@ -117,11 +159,26 @@ impl EncryptionPattern {
         }
     }
     
+    /// Whether files matching this pattern go through a CargoCrypt filter
+    pub fn is_cargocrypt(&self) -> bool {
+        std::iter::once(&self.attribute)
+            .chain(&self.extra_attrs)
+            .any(|attr| attr.contains("cargocrypt"))
+    }
+
     /// Check if this pattern matches a file path
     pub fn matches_path(&self, path: &Path) -> bool {
         // Simple pattern matching - could be enhanced with glob patterns
         let path_str = path.to_string_lossy();
         
+        // `dir/**/pattern`, what patterns of nested packages are scoped to
+        if let Some((dir, inner)) = self.pattern.split_once("/**/") {
+            let inner = EncryptionPattern::new(inner, &self.attribute);
+            return path.strip_prefix(dir).is_ok_and(|relative| {
+                inner.matches_path(relative) || relative.file_name().is_some_and(|name| inner.matches_path(Path::new(name)))
+            });
+        }
+        
         if self.pattern.starts_with("*.") {
             let extension = &self.pattern[2..];
             path_str.ends_with(&format!(".{}", extension))
@@ -134,6 +191,123 @@ impl EncryptionPattern {
     }
 }
 
+/// The CargoCrypt encryption patterns of the `.gitattributes` in `workdir`
+///
+/// A synchronous counterpart of [`GitAttributes::load`] for the scanner,
+/// which has no [`GitRepo`]; a missing file has no patterns.
+pub fn encryption_patterns(workdir: &Path) -> std::io::Result<Vec<EncryptionPattern>> {
+    let path = workdir.join(".gitattributes");
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    Ok(parse_encryption_patterns(&std::fs::read_to_string(path)?))
+}
+
+/// The CargoCrypt encryption patterns of `.gitattributes` content
+fn parse_encryption_patterns(content: &str) -> Vec<EncryptionPattern> {
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.trim().starts_with('#'))
+        .filter_map(EncryptionPattern::from_line)
+        .filter(EncryptionPattern::is_cargocrypt)
+        .collect()
+}
+
+/// Whether a blob stored in git is ciphertext
+///
+/// That is a CargoCrypt file whose header parses, or a git-crypt file as the
+/// clean filter writes in compatibility mode. Content that merely starts
+/// with the file magic is not enough.
+pub fn is_stored_encrypted(blob: &[u8]) -> bool {
+    if gitcrypt::is_encrypted(blob) {
+        return true;
+    }
+    blob.starts_with(FILE_MAGIC) && EncryptedSecret::from_bytes(blob).is_ok()
+}
+
+/// A file marked for encryption that a commit stores in plaintext
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlaintextBlob {
+    /// The newest commit of those checked storing the file in plaintext
+    pub commit: git2::Oid,
+    /// Path of the file in the commit
+    pub path: PathBuf,
+}
+
+/// Files marked for encryption whose staged content is not encrypted
+///
+/// With the clean filter configured, git stores these files encrypted even
+/// though the working tree holds them decrypted, so the index is where a
+/// missing filter shows: any staged blob that is not ciphertext is
+/// plaintext that the next commit would publish.
+pub fn plaintext_in_index(workdir: &Path) -> GitResult<Vec<PathBuf>> {
+    let patterns = encryption_patterns(workdir)
+        .map_err(|e| GitError::AttributesFailed(format!("Failed to read .gitattributes: {}", e)))?;
+    if patterns.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let repo = git2::Repository::open(workdir)?;
+    let index = repo.index()?;
+    let mut plaintext = Vec::new();
+    for entry in index.iter() {
+        let path = PathBuf::from(String::from_utf8_lossy(&entry.path).into_owned());
+        if !patterns.iter().any(|pattern| pattern.matches_path(&path)) {
+            continue;
+        }
+        let blob = repo.find_blob(entry.id)?;
+        if !is_stored_encrypted(blob.content()) {
+            plaintext.push(path);
+        }
+    }
+    Ok(plaintext)
+}
+
+/// Files marked for encryption that `commits` store in plaintext
+///
+/// Each commit is checked against the patterns of the `.gitattributes` it
+/// contains, so a push is judged by what it publishes rather than by the
+/// working tree. Every file is checked in full, not only the changes of the
+/// commit, and is reported once, for the first commit in `commits` that
+/// stores it in plaintext.
+pub fn plaintext_in_commits(repo: &git2::Repository, commits: &[git2::Oid]) -> GitResult<Vec<PlaintextBlob>> {
+    let mut checked: HashSet<(PathBuf, git2::Oid)> = HashSet::new();
+    let mut plaintext: Vec<PlaintextBlob> = Vec::new();
+    for &commit in commits {
+        let tree = repo.find_commit(commit)?.tree()?;
+        let patterns = match tree.get_path(Path::new(".gitattributes")) {
+            Ok(entry) => match entry.to_object(repo)?.as_blob() {
+                Some(blob) => parse_encryption_patterns(&String::from_utf8_lossy(blob.content())),
+                None => Vec::new(),
+            },
+            Err(_) => Vec::new(),
+        };
+        if patterns.is_empty() {
+            continue;
+        }
+
+        let mut blobs: Vec<(PathBuf, git2::Oid)> = Vec::new();
+        tree.walk(git2::TreeWalkMode::PreOrder, |dir, entry| {
+            if entry.kind() == Some(git2::ObjectType::Blob) {
+                let path = PathBuf::from(format!("{}{}", dir, String::from_utf8_lossy(entry.name_bytes())));
+                if patterns.iter().any(|pattern| pattern.matches_path(&path)) {
+                    blobs.push((path, entry.id()));
+                }
+            }
+            git2::TreeWalkResult::Ok
+        })?;
+        for (path, id) in blobs {
+            if !checked.insert((path.clone(), id)) || plaintext.iter().any(|found| found.path == path) {
+                continue;
+            }
+            if !is_stored_encrypted(repo.find_blob(id)?.content()) {
+                plaintext.push(PlaintextBlob { commit, path });
+            }
+        }
+    }
+    Ok(plaintext)
+}
+
 /// Manages .gitattributes file for CargoCrypt integration
 pub struct GitAttributes {
     repo: GitRepo,
@@ -186,6 +360,14 @@ impl GitAttributes {
     
     /// Save .gitattributes file
     pub async fn save(&self) -> GitResult<()> {
+        fs::write(&self.attributes_path, self.content()).await
+            .map_err(|e| GitError::AttributesFailed(format!("Failed to write .gitattributes: {}", e)))?;
+        
+        Ok(())
+    }
+    
+    /// Content of the .gitattributes file
+    fn content(&self) -> String {
         let mut content = String::new();
         
         // Add header comment
@@ -197,11 +379,7 @@ impl GitAttributes {
             content.push_str(&pattern.to_line());
             content.push('\n');
         }
-        
-        fs::write(&self.attributes_path, content).await
-            .map_err(|e| GitError::AttributesFailed(format!("Failed to write .gitattributes: {}", e)))?;
-        
-        Ok(())
+        content
     }
     
     /// Add an encryption pattern
@@ -216,6 +394,37 @@ impl GitAttributes {
         Ok(())
     }
     
+    /// Add a pattern encrypted by the default filter, merged (and diffed,
+    /// once `configure-diff` ran) like the existing encrypted patterns
+    ///
+    /// Returns whether the pattern was added; a pattern already in the file is left as it is.
+    pub async fn add_encryption_pattern(&mut self, pattern: &str) -> GitResult<bool> {
+        if self.patterns.iter().any(|p| p.pattern == pattern) {
+            return Ok(false);
+        }
+        let mut encryption_pattern = EncryptionPattern::new(pattern, &self.config.default_encrypt_attr);
+        let drivers = self.config.merge_drivers.keys().map(|name| format!("merge={}", name));
+        let diff_drivers: Vec<String> = self.config.diff_drivers.keys().map(|name| format!("diff={}", name)).collect();
+        let diffed = self.patterns.iter().any(|p| p.extra_attrs.iter().any(|attr| diff_drivers.contains(attr)));
+        for attr in drivers.chain(diffed.then_some(diff_drivers).into_iter().flatten()) {
+            encryption_pattern = encryption_pattern.with_attr(&attr);
+        }
+        self.patterns.push(encryption_pattern);
+        Ok(true)
+    }
+    
+    /// Add the encryption patterns of the packages of `workspace`, scoped to their directories
+    ///
+    /// Returns the number of patterns added.
+    pub async fn add_workspace_patterns(&mut self, workspace: &Workspace) -> GitResult<usize> {
+        let before = self.patterns.len();
+        let attribute = self.config.default_encrypt_attr.clone();
+        for pattern in workspace.encryption_patterns() {
+            self.add_pattern(&pattern, &attribute).await?;
+        }
+        Ok(self.patterns.len() - before)
+    }
+    
     /// Add default CargoCrypt patterns
     pub async fn add_cargocrypt_patterns(&mut self) -> GitResult<()> {
         let patterns: Vec<(String, String)> = self.config.patterns.clone()
@@ -224,21 +433,84 @@ impl GitAttributes {
         for (pattern, attribute) in patterns {
             self.add_pattern(&pattern, &attribute).await?;
         }
+
+        // Encrypted files also merge through the merge drivers, which decrypt them
+        let merge_attrs: Vec<String> = self.config.merge_drivers.keys().map(|name| format!("merge={}", name)).collect();
+        for pattern in self.patterns.iter_mut().filter(|p| p.attribute == self.config.default_encrypt_attr) {
+            for attr in &merge_attrs {
+                if !pattern.extra_attrs.contains(attr) {
+                    pattern.extra_attrs.push(attr.clone());
+                }
+            }
+        }
         
         Ok(())
     }
     
-    /// Configure git filters for automatic clean/smudge
+    /// Configure git filters for automatic clean/smudge, and the merge drivers
+    ///
+    /// The clean filter's [salt](super::config::FilterSettings::salt) is
+    /// added to `.cargocrypt/git.toml` if it has none yet.
     pub async fn configure_filters(&self, _git_config: &GitCryptConfig) -> GitResult<()> {
         if !self.config.enable_filters {
             return Ok(());
         }
         
-        let git_config_path = self.repo.git_dir().join("config");
+        let git_config_path = self.git_config_path();
+        let config_content = self.git_config_with_filters()?;
+        fs::write(&git_config_path, config_content).await
+            .map_err(|e| GitError::AttributesFailed(format!("Failed to write git config: {}", e)))?;
+
+        let mut project_config = GitCryptConfig::load_or_default(&self.repo).await?;
+        if project_config.filter.ensure_salt()? {
+            project_config.save(&self.repo).await?;
+        }
+        
+        Ok(())
+    }
+    
+    /// Filters and merge drivers [`Self::configure_filters`] would add to the git config
+    pub fn missing_filters(&self) -> GitResult<Vec<String>> {
+        if !self.config.enable_filters {
+            return Ok(Vec::new());
+        }
+
+        let config = self.repo.inner().config()?;
+        let mut missing: Vec<String> = self.config.filters.keys()
+            .filter(|name| config.get_string(&format!("filter.{}.clean", name)).is_err())
+            .map(|name| format!("filter.{}", name))
+            .collect();
+        missing.extend(self.config.merge_drivers.keys()
+            .filter(|name| config.get_string(&format!("merge.{}.driver", name)).is_err())
+            .map(|name| format!("merge.{}", name)));
+        missing.sort();
+        Ok(missing)
+    }
+
+    /// Record what [`Self::save`] and [`Self::configure_filters`] would write in `plan`
+    pub fn plan_save(&self, plan: &mut Plan) -> GitResult<()> {
+        plan.write_content(&self.attributes_path, self.content().as_bytes(), None);
+        if self.config.enable_filters {
+            plan.write_content(self.git_config_path(), self.git_config_with_filters()?.as_bytes(), Some("filters and merge drivers"));
+            if GitCryptConfig::load_project(self.repo.workdir())?.filter.salt.is_none() {
+                plan.write(GitCryptConfig::project_path(self.repo.workdir()), Some("filter salt"));
+            }
+        }
+        Ok(())
+    }
+    
+    /// Worktrees read the config of the common git directory
+    fn git_config_path(&self) -> PathBuf {
+        self.repo.common_dir().join("config")
+    }
+    
+    /// The git config with the filters and merge drivers added
+    fn git_config_with_filters(&self) -> GitResult<String> {
+        let git_config_path = self.git_config_path();
         
         // Read existing git config
         let mut config_content = if git_config_path.exists() {
-            fs::read_to_string(&git_config_path).await
+            std::fs::read_to_string(&git_config_path)
                 .map_err(|e| GitError::AttributesFailed(format!("Failed to read git config: {}", e)))?
         } else {
             String::new()
@@ -259,13 +531,49 @@ impl GitAttributes {
                 config_content.push_str(&filter_section);
             }
         }
+
+        for (driver_name, driver_config) in &self.config.merge_drivers {
+            if !config_content.contains(&format!("[merge \"{}\"]", driver_name)) {
+                config_content.push_str(&format!(
+                    "\n[merge \"{}\"]\n\tname = {}\n\tdriver = {}\n",
+                    driver_name, driver_config.name, driver_config.driver
+                ));
+            }
+        }
         
-        fs::write(&git_config_path, config_content).await
-            .map_err(|e| GitError::AttributesFailed(format!("Failed to write git config: {}", e)))?;
-        
-        Ok(())
+        Ok(config_content)
     }
     
+    /// Show the files of the CargoCrypt patterns decrypted in `git diff` and `git log -p`
+    ///
+    /// Adds the diff drivers to the patterns in `.gitattributes` (or to
+    /// `*.enc` when there are none) and registers them in the git config.
+    /// Returns the number of patterns updated.
+    pub async fn configure_diff(&mut self) -> GitResult<usize> {
+        self.load().await?;
+        if !self.patterns.iter().any(EncryptionPattern::is_cargocrypt) {
+            self.patterns.push(EncryptionPattern::new("*.enc", "merge=cargocrypt"));
+        }
+
+        let diff_attrs: Vec<String> = self.config.diff_drivers.keys().map(|name| format!("diff={}", name)).collect();
+        let mut updated = 0;
+        for pattern in self.patterns.iter_mut().filter(|p| p.is_cargocrypt()) {
+            let missing: Vec<&String> = diff_attrs.iter().filter(|attr| !pattern.extra_attrs.contains(attr) && pattern.attribute != **attr).collect();
+            if !missing.is_empty() {
+                pattern.extra_attrs.extend(missing.into_iter().cloned());
+                updated += 1;
+            }
+        }
+        self.save().await?;
+
+        let mut config = self.repo.inner().config()?;
+        for (driver_name, driver_config) in &self.config.diff_drivers {
+            config.set_str(&format!("diff.{}.textconv", driver_name), &driver_config.textconv)?;
+            config.set_bool(&format!("diff.{}.cachetextconv", driver_name), driver_config.cache_textconv)?;
+        }
+        Ok(updated)
+    }
+
     /// Check if a file should be encrypted based on patterns
     pub fn should_encrypt(&self, file_path: &Path) -> bool {
         self.patterns.iter().any(|pattern| pattern.matches_path(file_path))
@@ -464,6 +772,12 @@ mod tests {
         assert_eq!(attributes.patterns.len(), 1);
         assert!(attributes.should_encrypt(Path::new("test.secret")));
         assert!(!attributes.should_encrypt(Path::new("test.txt")));
+        
+        // Suggested patterns get the filter and the merge driver
+        assert!(attributes.add_encryption_pattern("*.pem").await.unwrap());
+        assert!(!attributes.add_encryption_pattern("*.pem").await.unwrap());
+        assert_eq!(attributes.patterns[1].to_line(), "*.pem filter=cargocrypt-encrypt merge=cargocrypt");
+        assert!(attributes.patterns[1].is_cargocrypt());
     }
     
     #[test]
@@ -493,6 +807,80 @@ mod tests {
         assert!(!dir_pattern.matches_path(Path::new("config/api.key")));
     }
     
+    #[test]
+    fn test_plaintext_in_index() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = git2::Repository::init(temp_dir.path()).unwrap();
+        std::fs::write(
+            temp_dir.path().join(".gitattributes"),
+            "*.secret filter=cargocrypt-encrypt\n*.txt text\n",
+        )
+        .unwrap();
+        std::fs::write(temp_dir.path().join("api.secret"), "token=abc").unwrap();
+        std::fs::write(temp_dir.path().join("db.secret"), encrypted_blob()).unwrap();
+        // The magic alone does not make a file encrypted
+        let mut forged = FILE_MAGIC.to_vec();
+        forged.extend_from_slice(b"\x02token=abc");
+        std::fs::write(temp_dir.path().join("forged.secret"), forged).unwrap();
+        std::fs::write(temp_dir.path().join("notes.txt"), "plain").unwrap();
+
+        let mut index = repo.index().unwrap();
+        for file in ["api.secret", "db.secret", "forged.secret", "notes.txt"] {
+            index.add_path(Path::new(file)).unwrap();
+        }
+        index.write().unwrap();
+
+        let patterns = encryption_patterns(temp_dir.path()).unwrap();
+        assert_eq!(patterns.len(), 1);
+        assert_eq!(
+            plaintext_in_index(temp_dir.path()).unwrap(),
+            vec![PathBuf::from("api.secret"), PathBuf::from("forged.secret")]
+        );
+    }
+
+    #[test]
+    fn test_plaintext_in_commits() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = git2::Repository::init(temp_dir.path()).unwrap();
+        let signature = git2::Signature::now("Dev", "dev@example.com").unwrap();
+        let commit = |files: &[(&str, &[u8])]| -> git2::Oid {
+            let mut index = repo.index().unwrap();
+            for (file, content) in files {
+                let path = temp_dir.path().join(file);
+                std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+                std::fs::write(path, content).unwrap();
+                index.add_path(Path::new(file)).unwrap();
+            }
+            index.write().unwrap();
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let parents: Vec<git2::Commit> = repo.head().ok().and_then(|head| head.peel_to_commit().ok()).into_iter().collect();
+            let parents: Vec<&git2::Commit> = parents.iter().collect();
+            repo.commit(Some("HEAD"), &signature, &signature, "commit", &tree, &parents).unwrap()
+        };
+
+        // Before .gitattributes marks them, files are not checked
+        let first = commit(&[("secrets/api.env", b"API_KEY=abc")]);
+        assert!(plaintext_in_commits(&repo, &[first]).unwrap().is_empty());
+
+        let encrypted = encrypted_blob();
+        let second = commit(&[(".gitattributes", b"secrets/* filter=cargocrypt-encrypt
+"), ("secrets/db.env", &encrypted)]);
+        let third = commit(&[("secrets/db.env", b"DB_PASSWORD=hunter2")]);
+        assert_eq!(
+            plaintext_in_commits(&repo, &[third, second, first]).unwrap(),
+            vec![
+                PlaintextBlob { commit: third, path: PathBuf::from("secrets/api.env") },
+                PlaintextBlob { commit: third, path: PathBuf::from("secrets/db.env") },
+            ]
+        );
+        assert_eq!(plaintext_in_commits(&repo, &[second]).unwrap().len(), 1);
+    }
+
+    fn encrypted_blob() -> Vec<u8> {
+        let plaintext = crate::crypto::PlaintextSecret::from_bytes(b"token=abc".to_vec());
+        EncryptedSecret::encrypt_with_password(plaintext, "password", None).unwrap().to_bytes().unwrap()
+    }
+
     #[tokio::test]
     async fn test_save_and_load() {
         let temp_dir = TempDir::new().unwrap();
@@ -520,4 +908,22 @@ mod tests {
         assert!(attributes.should_encrypt(Path::new("api.secret")));
         assert!(attributes.should_encrypt(Path::new("secrets/database.key")));
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_configure_diff() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = GitRepo::init(temp_dir.path()).unwrap();
+        std::fs::write(temp_dir.path().join(".gitattributes"), "*.secret filter=cargocrypt-encrypt\n*.md text\n").unwrap();
+        let mut attributes = GitAttributes::new(&repo).unwrap();
+
+        assert_eq!(attributes.configure_diff().await.unwrap(), 1);
+        assert_eq!(attributes.configure_diff().await.unwrap(), 0);
+        let content = std::fs::read_to_string(temp_dir.path().join(".gitattributes")).unwrap();
+        assert!(content.contains("*.secret filter=cargocrypt-encrypt diff=cargocrypt"));
+        assert!(content.contains("*.md text\n"));
+
+        let config = repo.inner().config().unwrap();
+        assert_eq!(config.get_string("diff.cargocrypt.textconv").unwrap(), "cargocrypt git textconv");
+        assert!(!config.get_bool("diff.cargocrypt.cachetextconv").unwrap());
+    }
+}
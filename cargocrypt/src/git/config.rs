@@ -3,12 +3,19 @@
 //! This module handles all configuration aspects of CargoCrypt's git integration,
 //! including repository setup, integration modes, and feature toggles.
 
-use super::{GitRepo, GitError, GitResult};
+use super::{GitRepo, GitError, GitResult, EncryptionPattern};
+use super::hooks::FailLevel;
+use crate::crypto::{defaults, SecureRandom};
+use globset::GlobBuilder;
+use std::path::{Path, PathBuf};
 use tokio::fs;
 use serde::{Deserialize, Serialize};
 
 /// Main configuration for Git integration
+///
+/// Sections left out of `.cargocrypt/git.toml` take their defaults.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct GitCryptConfig {
     /// Integration mode
     pub mode: IntegrationMode,
@@ -20,6 +27,12 @@ pub struct GitCryptConfig {
     pub git: GitSettings,
     /// Performance settings
     pub performance: PerformanceConfig,
+    /// Keys of the clean/smudge filters
+    #[serde(default)]
+    pub filter: FilterSettings,
+    /// Policies of branches by name; the last matching policy wins
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub branches: Vec<BranchPolicy>,
 }
 
 impl Default for GitCryptConfig {
@@ -30,14 +43,45 @@ impl Default for GitCryptConfig {
             features: FeatureConfig::default(),
             git: GitSettings::default(),
             performance: PerformanceConfig::default(),
+            filter: FilterSettings::default(),
+            branches: Vec::new(),
         }
     }
 }
 
 impl GitCryptConfig {
+    /// Location of the git configuration of a project
+    pub fn project_path<P: AsRef<Path>>(workdir: P) -> PathBuf {
+        workdir.as_ref().join(".cargocrypt").join("git.toml")
+    }
+
+    /// Load the configuration of the working tree at `workdir` without creating it
+    ///
+    /// The git filters run on every checkout and must not write files.
+    pub fn load_project<P: AsRef<Path>>(workdir: P) -> GitResult<Self> {
+        let config_path = Self::project_path(workdir);
+        if !config_path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(&config_path)
+            .map_err(|e| GitError::InitializationFailed(format!("Failed to read git config: {}", e)))?;
+        toml::from_str(&content)
+            .map_err(|e| GitError::InitializationFailed(format!("Failed to parse git config: {}", e)))
+    }
+
     /// Load configuration from repository
+    ///
+    /// A bare repository has no working tree to create the configuration
+    /// in, so its configuration is read from HEAD, or defaults.
     pub async fn load_or_default(repo: &GitRepo) -> GitResult<Self> {
-        let config_path = repo.workdir().join(".cargocrypt").join("git.toml");
+        if repo.is_bare() {
+            return match repo.head_blob(".cargocrypt/git.toml") {
+                Some(content) => toml::from_str(&String::from_utf8_lossy(&content))
+                    .map_err(|e| GitError::InitializationFailed(format!("Failed to parse git config at HEAD: {}", e))),
+                None => Ok(Self::default()),
+            };
+        }
+        let config_path = Self::project_path(repo.workdir());
         
         if config_path.exists() {
             let content = fs::read_to_string(&config_path).await
@@ -55,7 +99,8 @@ impl GitCryptConfig {
     
     /// Save configuration to repository
     pub async fn save(&self, repo: &GitRepo) -> GitResult<()> {
-        let config_path = repo.workdir().join(".cargocrypt").join("git.toml");
+        repo.require_worktree("Saving .cargocrypt/git.toml")?;
+        let config_path = Self::project_path(repo.workdir());
         
         // Ensure directory exists
         if let Some(parent) = config_path.parent() {
@@ -139,6 +184,7 @@ pub enum IntegrationMode {
 
 /// Repository setup configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct RepositorySetup {
     /// Automatically initialize on first use
     pub auto_init: bool,
@@ -148,7 +194,7 @@ pub struct RepositorySetup {
     pub create_examples: bool,
     /// Initialize with team setup
     pub setup_team: bool,
-    /// Default encryption patterns
+    /// Encryption patterns added to .gitattributes by `cargocrypt init --git`
     pub default_patterns: Vec<String>,
 }
 
@@ -381,6 +427,168 @@ impl Default for CompressionConfig {
     }
 }
 
+/// Keys of the clean/smudge filters, by path
+///
+/// ```toml
+/// [filter]
+/// on_decrypt_failure = "fail"     # or "passthrough" to check out ciphertext
+/// salt = "5f0c…"                  # generated when the filters are configured
+///
+/// [[filter.keys]]
+/// pattern = "secrets/prod/*"
+/// vault = "prod"
+///
+/// [[filter.keys]]
+/// pattern = "*.key"
+/// keyfile = ".cargocrypt/keys/ci.key"
+///
+/// [[filter.keys]]
+/// pattern = "secrets/team/*"
+/// team_key = "secrets"
+/// ```
+///
+/// Files matching no pattern use the filter password.
+///
+/// Commit `.cargocrypt/git.toml` so every clone cleans with the same salt.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FilterSettings {
+    /// Keys by pattern; the last matching pattern wins, as in .gitattributes
+    pub keys: Vec<FilterKey>,
+    /// What the smudge filter does with a file it cannot decrypt
+    pub on_decrypt_failure: DecryptFailure,
+    /// Name of the git-crypt key for files encrypted by git-crypt, `default` when unset
+    ///
+    /// The key is read from `.git/git-crypt/keys/<name>`, where
+    /// `cargocrypt migrate git-crypt` and `git-crypt unlock` put it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub git_crypt_key: Option<String>,
+    /// Random salt (hex) the clean filter derives its keys with
+    ///
+    /// Deterministic encryption needs a fixed salt; one per repository keeps
+    /// it independent of passwords and content (see [`crate::crypto::deterministic`]).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub salt: Option<String>,
+}
+
+impl FilterSettings {
+    /// The salt the clean filter derives its keys with, if one was generated
+    pub fn salt(&self) -> GitResult<Option<[u8; defaults::SALT_LENGTH]>> {
+        let Some(salt) = &self.salt else {
+            return Ok(None);
+        };
+        let mut bytes = [0u8; defaults::SALT_LENGTH];
+        hex::decode_to_slice(salt, &mut bytes)
+            .map_err(|e| GitError::InitializationFailed(format!("Invalid filter salt in git config: {}", e)))?;
+        Ok(Some(bytes))
+    }
+
+    /// Generate the [salt](Self::salt) if there is none yet; returns whether it did
+    pub fn ensure_salt(&mut self) -> GitResult<bool> {
+        if self.salt.is_some() {
+            return Ok(false);
+        }
+        self.salt = Some(hex::encode(SecureRandom::generate_salt()?));
+        Ok(true)
+    }
+
+    /// The key of the files matching `path`, if one is configured
+    pub fn key_for(&self, path: &Path) -> Option<&FilterKey> {
+        self.keys
+            .iter()
+            .rev()
+            .find(|key| EncryptionPattern::new(&key.pattern, "").matches_path(path))
+    }
+}
+
+/// The key of the files matching a pattern: a vault, a keyfile or a team key
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FilterKey {
+    /// Pattern as in .gitattributes
+    pub pattern: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vault: Option<String>,
+    /// Relative to the working tree
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keyfile: Option<PathBuf>,
+    /// Purpose of a team shared key; the newest key of that purpose encrypts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub team_key: Option<String>,
+}
+
+/// What the smudge filter does with a file it cannot decrypt
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DecryptFailure {
+    /// Fail the checkout
+    #[default]
+    Fail,
+    /// Check out the encrypted content as is
+    Passthrough,
+}
+
+/// How strictly the hooks guard the branches matching a pattern
+///
+/// ```toml
+/// [[branches]]
+/// pattern = "main"
+/// require_encryption = true   # every file marked for encryption stored encrypted
+/// enforce_hooks = true        # hooks always run and block, CARGOCRYPT_SKIP is refused
+///
+/// [[branches]]
+/// pattern = "scratch/*"
+/// fail_level = "warn"         # report findings but never block
+/// ```
+///
+/// Patterns are globs over branch names where `*` stays within one `/`
+/// component. A branch matching no pattern uses `.cargocrypt/hooks.toml` as is.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BranchPolicy {
+    /// Glob of branch names, without `refs/heads/`
+    pub pattern: String,
+    /// Run the encryption check on the branch even where `hooks.toml` leaves it out
+    pub require_encryption: bool,
+    /// Run the hooks even where `hooks.toml` disables them, refuse skips and never only warn
+    pub enforce_hooks: bool,
+    /// Fail level of the hooks on the branch instead of the one in `hooks.toml`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fail_level: Option<FailLevel>,
+}
+
+impl BranchPolicy {
+    /// Whether the policy applies to `branch`
+    pub fn matches(&self, branch: &str) -> bool {
+        GlobBuilder::new(&self.pattern)
+            .literal_separator(true)
+            .build()
+            .is_ok_and(|glob| glob.compile_matcher().is_match(branch))
+    }
+
+    /// The strictest of several policies, as for a push updating several branches
+    pub fn strictest<'a, I: IntoIterator<Item = &'a BranchPolicy>>(policies: I) -> Option<BranchPolicy> {
+        let strictness = |level: Option<FailLevel>| match level {
+            Some(FailLevel::Warn) => 0,
+            None => 1,
+            Some(FailLevel::Policy) => 2,
+            Some(FailLevel::Block) => 3,
+        };
+        policies.into_iter().cloned().reduce(|a, b| BranchPolicy {
+            pattern: if a.pattern == b.pattern { a.pattern } else { format!("{}, {}", a.pattern, b.pattern) },
+            require_encryption: a.require_encryption || b.require_encryption,
+            enforce_hooks: a.enforce_hooks || b.enforce_hooks,
+            fail_level: if strictness(a.fail_level) >= strictness(b.fail_level) { a.fail_level } else { b.fail_level },
+        })
+    }
+}
+
+impl GitCryptConfig {
+    /// The policy of `branch`: the last one whose pattern matches
+    pub fn branch_policy(&self, branch: &str) -> Option<&BranchPolicy> {
+        self.branches.iter().rev().find(|policy| policy.matches(branch))
+    }
+}
+
 /// Configuration validation and migration
 impl GitCryptConfig {
     /// Validate configuration settings
@@ -414,6 +622,21 @@ impl GitCryptConfig {
             errors.push("Auto encryption requires git attributes to be enabled".to_string());
         }
         
+        for key in &self.filter.keys {
+            if key.vault.is_some() == key.keyfile.is_some() {
+                errors.push(format!("Filter key for '{}' needs exactly one of vault and keyfile", key.pattern));
+            }
+        }
+        
+        for policy in &self.branches {
+            if let Err(e) = GlobBuilder::new(&policy.pattern).build() {
+                errors.push(format!("Invalid branch pattern '{}': {}", policy.pattern, e));
+            }
+            if policy.enforce_hooks && policy.fail_level == Some(FailLevel::Warn) {
+                errors.push(format!("Branch policy '{}' cannot both enforce hooks and only warn", policy.pattern));
+            }
+        }
+        
         if errors.is_empty() {
             Ok(())
         } else {
@@ -579,4 +802,82 @@ mod tests {
         assert!(!summary.enabled_features.is_empty());
         assert_eq!(summary.git_refs.len(), 3);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_filter_keys() {
+        let filter: FilterSettings = toml::from_str(
+            r#"
+            on_decrypt_failure = "passthrough"
+
+            [[keys]]
+            pattern = "secrets/*"
+            vault = "staging"
+
+            [[keys]]
+            pattern = "secrets/*"
+            vault = "prod"
+
+            [[keys]]
+            pattern = "*.key"
+            keyfile = "ci.key"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(filter.on_decrypt_failure, DecryptFailure::Passthrough);
+        assert_eq!(filter.key_for(Path::new("secrets/db.env")).unwrap().vault.as_deref(), Some("prod"));
+        assert_eq!(filter.key_for(Path::new("deploy/ci.key")).unwrap().keyfile, Some(PathBuf::from("ci.key")));
+        assert!(filter.key_for(Path::new("src/main.rs")).is_none());
+
+        let mut config = GitCryptConfig { filter, ..GitCryptConfig::default() };
+        assert!(config.validate().is_ok());
+        config.filter.keys[0].keyfile = Some(PathBuf::from("staging.key"));
+        assert!(config.validate().is_err());
+
+        let mut filter = FilterSettings::default();
+        assert_eq!(filter.salt().unwrap(), None);
+        assert!(filter.ensure_salt().unwrap());
+        let salt = filter.salt().unwrap().unwrap();
+        assert!(!filter.ensure_salt().unwrap());
+        assert_eq!(filter.salt().unwrap(), Some(salt));
+        filter.salt = Some("not hex".to_string());
+        assert!(filter.salt().is_err());
+    }
+
+    #[test]
+    fn test_branch_policies() {
+        let config: GitCryptConfig = toml::from_str(
+            r#"
+            [[branches]]
+            pattern = "*"
+            fail_level = "warn"
+
+            [[branches]]
+            pattern = "main"
+            require_encryption = true
+            enforce_hooks = true
+
+            [[branches]]
+            pattern = "release/*"
+            fail_level = "block"
+            "#,
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+
+        let main = config.branch_policy("main").unwrap();
+        assert!(main.require_encryption && main.enforce_hooks);
+        assert_eq!(config.branch_policy("release/1.0").unwrap().fail_level, Some(FailLevel::Block));
+        // `*` stays within one component
+        assert_eq!(config.branch_policy("scratch").unwrap().fail_level, Some(FailLevel::Warn));
+        assert!(config.branch_policy("scratch/idea").is_none());
+
+        let strictest = BranchPolicy::strictest([&config.branches[0], &config.branches[2], &config.branches[1]]).unwrap();
+        assert_eq!(strictest.fail_level, Some(FailLevel::Block));
+        assert!(strictest.enforce_hooks && strictest.require_encryption);
+        assert!(BranchPolicy::strictest([]).is_none());
+
+        let mut config = config;
+        config.branches[1].fail_level = Some(FailLevel::Warn);
+        assert!(config.validate().unwrap_err().iter().any(|e| e.contains("cannot both enforce hooks and only warn")));
+    }
+}
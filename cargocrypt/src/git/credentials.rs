@@ -0,0 +1,360 @@
+//! Passwords for the git filters
+//!
+//! The clean and smudge filters run without a terminal, so their passwords
+//! (the filter password for paths without a key, and the password of each
+//! vault the filters unlock) come from the first of these sources that has
+//! one:
+//!
+//! 1. the key agent, after `cargocrypt agent unlock` (see [`crate::agent`])
+//! 2. the OS keyring, after `cargocrypt git credentials store` (see
+//!    [`crate::keyring`])
+//! 3. an identity file holding the password, named by
+//!    `CARGOCRYPT_IDENTITY_FILE` or `git config cargocrypt.identityFile`, and
+//!    only readable by its owner
+//! 4. the environment: `CARGOCRYPT_PASSWORD`
+//!
+//! Vaults use `CARGOCRYPT_VAULT_IDENTITY_FILE_<NAME>`,
+//! `cargocrypt.vault.<name>.identityFile` and
+//! `CARGOCRYPT_VAULT_PASSWORD_<NAME>` instead (upper case, other characters
+//! as `_`).
+//!
+//! A password in `git config cargocrypt.password` is still read as a last
+//! resort, with a warning, since it is stored in plaintext in `.git/config`;
+//! `cargocrypt git credentials store --from-git-config` moves it to the
+//! keyring. When no source has a password, resolution fails with an
+//! authentication error rather than falling back to a default.
+
+use crate::agent::AgentClient;
+use crate::crypto::{SecureBytes, SecureString};
+use crate::error::{CargoCryptError, CryptoResult};
+use crate::{keyring, utils};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+/// A password the filters need
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Credential {
+    /// The password of paths without a configured key
+    Filter,
+    /// The password of a vault
+    Vault(String),
+}
+
+/// Where a password was found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CredentialSource {
+    Agent,
+    Keyring,
+    IdentityFile(PathBuf),
+    Environment(String),
+    GitConfig(String),
+}
+
+/// A resolved password and where it came from
+#[derive(Debug)]
+pub struct ResolvedCredential {
+    pub secret: SecureString,
+    pub source: CredentialSource,
+}
+
+impl Credential {
+    /// Name of the credential in the agent
+    pub fn name(&self) -> String {
+        match self {
+            Self::Filter => "filter".to_string(),
+            Self::Vault(vault) => format!("vault/{}", vault),
+        }
+    }
+
+    /// Keyring account of the credential for the working tree at `workdir`
+    pub fn keyring_account(&self, workdir: &Path) -> String {
+        format!("{}/{}", utils::project_id(workdir), self.name())
+    }
+
+    /// Environment variable holding the password
+    pub fn password_variable(&self) -> String {
+        match self {
+            Self::Filter => "CARGOCRYPT_PASSWORD".to_string(),
+            Self::Vault(vault) => format!("CARGOCRYPT_VAULT_PASSWORD_{}", variable_suffix(vault)),
+        }
+    }
+
+    /// Environment variable naming the identity file
+    pub fn identity_file_variable(&self) -> String {
+        match self {
+            Self::Filter => "CARGOCRYPT_IDENTITY_FILE".to_string(),
+            Self::Vault(vault) => format!("CARGOCRYPT_VAULT_IDENTITY_FILE_{}", variable_suffix(vault)),
+        }
+    }
+
+    /// Prefix of the credential's git configuration keys
+    pub fn git_config_prefix(&self) -> String {
+        match self {
+            Self::Filter => "cargocrypt".to_string(),
+            Self::Vault(vault) => format!("cargocrypt.vault.{}", vault),
+        }
+    }
+}
+
+impl fmt::Display for Credential {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Filter => f.write_str("filter password"),
+            Self::Vault(vault) => write!(f, "password of vault '{}'", vault),
+        }
+    }
+}
+
+impl fmt::Display for CredentialSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Agent => f.write_str("key agent"),
+            Self::Keyring => write!(f, "keyring ({})", keyring::backend_name()),
+            Self::IdentityFile(path) => write!(f, "identity file {}", path.display()),
+            Self::Environment(variable) => write!(f, "environment ({})", variable),
+            Self::GitConfig(key) => write!(f, "git config {} (plaintext, deprecated)", key),
+        }
+    }
+}
+
+/// The password of `credential` for the working tree at `workdir`
+pub fn resolve(workdir: &Path, credential: &Credential) -> CryptoResult<ResolvedCredential> {
+    resolve_with(workdir, credential, &|variable| std::env::var(variable).ok(), &|key| git_config_secret(workdir, key))
+}
+
+fn resolve_with(
+    workdir: &Path,
+    credential: &Credential,
+    env: &dyn Fn(&str) -> Option<String>,
+    git_config: &dyn Fn(&str) -> Option<SecureString>,
+) -> CryptoResult<ResolvedCredential> {
+    let found = |secret: SecureString, source: CredentialSource| {
+        debug!("Using the {} from the {}", credential, source);
+        Ok(ResolvedCredential { secret, source })
+    };
+
+    if let Some(agent) = AgentClient::for_project(workdir) {
+        match agent.credential(&credential.name()) {
+            Ok(Some(secret)) => return found(secret, CredentialSource::Agent),
+            Ok(None) => {}
+            Err(e) => debug!("Key agent unavailable: {}", e),
+        }
+    }
+
+    if let Some(secret) = keyring::get(&credential.keyring_account(workdir)) {
+        return found(secret, CredentialSource::Keyring);
+    }
+
+    let identity_key = format!("{}.identityFile", credential.git_config_prefix());
+    let identity_file = env(&credential.identity_file_variable())
+        .filter(|path| !path.is_empty())
+        .or_else(|| git_config(&identity_key).map(|path| path.as_str().to_string()));
+    if let Some(path) = identity_file {
+        let path = workdir.join(expand_home(&path, env));
+        return found(read_identity_file(&path)?, CredentialSource::IdentityFile(path));
+    }
+
+    let variable = credential.password_variable();
+    if let Some(password) = env(&variable).filter(|password| !password.is_empty()) {
+        return found(SecureString::from(password), CredentialSource::Environment(variable));
+    }
+
+    let password_key = format!("{}.password", credential.git_config_prefix());
+    if let Some(password) = git_config(&password_key) {
+        warn!(
+            "Reading the {} from plaintext git config {}; move it with 'cargocrypt git credentials store --from-git-config'",
+            credential, password_key
+        );
+        return found(password, CredentialSource::GitConfig(password_key));
+    }
+
+    let vault_flag = match credential {
+        Credential::Filter => String::new(),
+        Credential::Vault(vault) => format!(" --vault {}", vault),
+    };
+    Err(CargoCryptError::Auth {
+        message: format!(
+            "No {} for the git filters: not unlocked in the key agent, not in the keyring, no identity file ({}), and {} is not set",
+            credential,
+            credential.identity_file_variable(),
+            variable
+        ),
+        retry_suggestion: Some(format!(
+            "Run 'cargocrypt agent unlock{flag}' or 'cargocrypt git credentials store{flag}', or set {}",
+            variable,
+            flag = vault_flag
+        )),
+    })
+}
+
+/// Read a password from an identity file that only its owner can read
+fn read_identity_file(path: &Path) -> CryptoResult<SecureString> {
+    let unreadable = |reason: String| CargoCryptError::Auth {
+        message: format!("Cannot use identity file {}: {}", path.display(), reason),
+        retry_suggestion: Some(format!("Write the password to it and run 'chmod 600 {}'", path.display())),
+    };
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mode = std::fs::metadata(path).map_err(|e| unreadable(e.to_string()))?.permissions().mode();
+        if mode & 0o077 != 0 {
+            return Err(unreadable(format!("it is accessible by other users (mode {:o})", mode & 0o777)));
+        }
+    }
+
+    let content = SecureBytes::from(std::fs::read(path).map_err(|e| unreadable(e.to_string()))?);
+    let password = std::str::from_utf8(content.as_slice())
+        .map_err(|_| unreadable("it is not UTF-8".to_string()))?
+        .lines()
+        .next()
+        .unwrap_or("");
+    if password.is_empty() {
+        return Err(unreadable("it is empty".to_string()));
+    }
+    Ok(SecureString::from(password))
+}
+
+/// `path` with a leading `~/` replaced by the home directory
+fn expand_home(path: &str, env: &dyn Fn(&str) -> Option<String>) -> PathBuf {
+    match (path.strip_prefix("~/"), env("HOME")) {
+        (Some(rest), Some(home)) => Path::new(&home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// `name` as part of an environment variable name
+pub(super) fn variable_suffix(name: &str) -> String {
+    name.chars()
+        .map(|ch| if ch.is_ascii_alphanumeric() { ch.to_ascii_uppercase() } else { '_' })
+        .collect()
+}
+
+/// A value of the git configuration of `workdir`, read without keeping copies around
+pub(super) fn git_config_secret(workdir: &Path, key: &str) -> Option<SecureString> {
+    std::process::Command::new("git")
+        .arg("-C")
+        .arg(workdir)
+        .args(["config", "--get", key])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| SecureBytes::from(output.stdout))
+        .and_then(|stdout| std::str::from_utf8(stdout.as_slice()).ok().map(|s| SecureString::from(s.trim())))
+}
+
+/// Every value of the git config `key` in `workdir`, for keys that may be given more than once
+pub(super) fn git_config_values(workdir: &Path, key: &str) -> Vec<String> {
+    std::process::Command::new("git")
+        .arg("-C")
+        .arg(workdir)
+        .args(["config", "--get-all", key])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).lines().map(|line| line.trim().to_string()).filter(|line| !line.is_empty()).collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tempfile::TempDir;
+
+    fn resolve_in(
+        workdir: &Path,
+        credential: &Credential,
+        env: &[(&str, &str)],
+        git_config: &[(&str, &str)],
+    ) -> CryptoResult<ResolvedCredential> {
+        let env: HashMap<String, String> = env.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        let git_config: HashMap<String, String> = git_config.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        resolve_with(
+            workdir,
+            credential,
+            &|variable| env.get(variable).cloned(),
+            &|key| git_config.get(key).map(|value| SecureString::from(value.as_str())),
+        )
+    }
+
+    #[test]
+    fn test_resolution_order() {
+        let temp_dir = TempDir::new().unwrap();
+        let workdir = temp_dir.path();
+        let identity = workdir.join("filter.pass");
+        std::fs::write(&identity, "from-identity-file\n").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&identity, std::fs::Permissions::from_mode(0o600)).unwrap();
+        }
+
+        // Nothing configured is an error, not a default password
+        let error = resolve_in(workdir, &Credential::Filter, &[], &[]).unwrap_err();
+        assert!(matches!(error, CargoCryptError::Auth { .. }));
+
+        let resolved = resolve_in(workdir, &Credential::Filter, &[], &[("cargocrypt.password", "from-git-config")]).unwrap();
+        assert_eq!(resolved.secret.as_str(), "from-git-config");
+        assert_eq!(resolved.source, CredentialSource::GitConfig("cargocrypt.password".to_string()));
+
+        let resolved = resolve_in(
+            workdir,
+            &Credential::Filter,
+            &[("CARGOCRYPT_PASSWORD", "from-env")],
+            &[("cargocrypt.password", "from-git-config")],
+        )
+        .unwrap();
+        assert_eq!(resolved.secret.as_str(), "from-env");
+
+        // The identity file comes before the environment, relative to the working tree
+        let resolved = resolve_in(
+            workdir,
+            &Credential::Filter,
+            &[("CARGOCRYPT_PASSWORD", "from-env")],
+            &[("cargocrypt.identityFile", "filter.pass")],
+        )
+        .unwrap();
+        assert_eq!(resolved.secret.as_str(), "from-identity-file");
+        assert_eq!(resolved.source, CredentialSource::IdentityFile(identity.clone()));
+
+        // Vaults have their own variables and keys
+        let vault = Credential::Vault("prod-eu".to_string());
+        assert!(resolve_in(workdir, &vault, &[("CARGOCRYPT_PASSWORD", "from-env")], &[]).is_err());
+        let resolved = resolve_in(workdir, &vault, &[("CARGOCRYPT_VAULT_PASSWORD_PROD_EU", "vault-env")], &[]).unwrap();
+        assert_eq!(resolved.secret.as_str(), "vault-env");
+        let resolved = resolve_in(
+            workdir,
+            &vault,
+            &[("CARGOCRYPT_VAULT_IDENTITY_FILE_PROD_EU", identity.to_str().unwrap())],
+            &[],
+        )
+        .unwrap();
+        assert_eq!(resolved.secret.as_str(), "from-identity-file");
+
+        // An identity file others can read is refused rather than skipped
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&identity, std::fs::Permissions::from_mode(0o644)).unwrap();
+            let error = resolve_in(
+                workdir,
+                &Credential::Filter,
+                &[("CARGOCRYPT_IDENTITY_FILE", "filter.pass"), ("CARGOCRYPT_PASSWORD", "from-env")],
+                &[],
+            )
+            .unwrap_err();
+            assert!(error.to_string().contains("other users"));
+        }
+    }
+
+    #[test]
+    fn test_credential_names() {
+        assert_eq!(variable_suffix("prod-eu.1"), "PROD_EU_1");
+        assert_eq!(Credential::Filter.git_config_prefix(), "cargocrypt");
+        assert_eq!(Credential::Vault("ci".to_string()).name(), "vault/ci");
+        assert_eq!(Credential::Vault("ci".to_string()).git_config_prefix(), "cargocrypt.vault.ci");
+    }
+}
@@ -0,0 +1,465 @@
+//! Clean and smudge filters for `.gitattributes`-driven encryption
+//!
+//! `cargocrypt git configure-attributes` registers `cargocrypt git
+//! filter-clean %f` and `cargocrypt git filter-smudge %f` for the files
+//! marked for encryption. Git passes the path of each file, which selects its
+//! key from `[filter]` in `.cargocrypt/git.toml` (see [`FilterSettings`]): a
+//! vault, a keyfile, or for paths without a configured key the filter
+//! password. Filter and vault passwords come from the key agent, the OS
+//! keyring, an identity file or the environment (see [`super::credentials`]);
+//! without one the filters fail instead of using a default password.
+//! In a workspace, the `.cargocrypt/git.toml` of the package containing the
+//! file is consulted first (see [`super::workspace`]).
+//!
+//! Clean encrypts deterministically, with the salt in `[filter]`, so unchanged
+//! files keep their blob and `git status` stays clean. Smudge fails the checkout when an encrypted file
+//! cannot be decrypted rather than leaving ciphertext in the working tree,
+//! unless `on_decrypt_failure = "passthrough"`. A missing password always
+//! fails the checkout, so it is not mistaken for a wrong key.
+//!
+//! Files encrypted by git-crypt are decrypted with the git-crypt key in
+//! `.git/git-crypt/keys/<name>` (see [`super::gitcrypt`]). In
+//! compatibility mode clean also writes git-crypt's format, so a team can
+//! switch tools one member at a time.
+//!
+//! The `cargocrypt` merge driver (`cargocrypt git merge-driver %O %A %B
+//! %P`) decrypts the three sides of a merge with the same keys, merges the
+//! text and encrypts the result again, so encrypted files merge like any
+//! other file instead of conflicting as binaries.
+//!
+//! The `cargocrypt` diff driver (`cargocrypt git configure-diff`) has
+//! `git diff` and `git log -p` show encrypted files decrypted through
+//! [`GitFilter::textconv`]. Its output is never cached, since git would keep
+//! the cache as plaintext blobs in `refs/notes/textconv`.
+//!
+//! Files keyed by a team key (`team_key = "<purpose>"`) are encrypted with
+//! the newest shared key of that purpose and decrypted with any key of it,
+//! archived ones included, unwrapped with whichever local identity the key
+//! is wrapped for (see [`TeamIdentity::discover`]).
+
+use super::credentials::{self, Credential};
+use super::config::{DecryptFailure, FilterKey, FilterSettings, GitCryptConfig, IntegrationMode};
+use super::gitcrypt::{self, GitCryptKey};
+use super::team::{TeamIdentity, TeamKeySharing};
+use super::workspace::{self, Workspace};
+use super::{GitError, GitRepo};
+use crate::crypto::secrets::FILE_MAGIC;
+use crate::crypto::{CryptoError, EncryptedSecret, EncryptionOptions, Keyfile, PlaintextSecret, SecureString};
+use crate::error::{CargoCryptError, CryptoResult};
+use crate::CargoCrypt;
+use std::path::{Path, PathBuf};
+
+/// The key a file is encrypted with
+enum FileKey {
+    Password(SecureString),
+    Keyfile { keyfile: Keyfile, vault: Option<String> },
+}
+
+/// What the smudge filter checks out
+#[derive(Debug)]
+pub struct Smudged {
+    pub content: Vec<u8>,
+    /// Why `content` is not decrypted plaintext, if it is not
+    pub warning: Option<String>,
+}
+
+/// What the merge driver leaves for the merged file
+#[derive(Debug)]
+pub struct Merged {
+    /// Encrypted like the sides of the merge, with conflict markers inside
+    pub content: Vec<u8>,
+    pub conflicts: bool,
+}
+
+/// The clean and smudge filters of one working tree
+pub struct GitFilter {
+    crypt: CargoCrypt,
+    workdir: PathBuf,
+    settings: FilterSettings,
+    git_crypt: Option<GitCryptKey>,
+    compatibility: bool,
+}
+
+impl GitFilter {
+    /// Filters for the working tree at `workdir`, keyed by its `.cargocrypt/git.toml`
+    pub fn new<P: Into<PathBuf>>(crypt: CargoCrypt, workdir: P) -> CryptoResult<Self> {
+        let workdir = workdir.into();
+        let config = GitCryptConfig::load_project(&workdir)?;
+        let key_name = config.filter.git_crypt_key.as_deref().unwrap_or(gitcrypt::DEFAULT_KEY_NAME);
+        // A linked worktree may have its own unlocked key, else uses the main checkout's
+        let git_crypt = match git2::Repository::open(&workdir) {
+            Ok(repo) => [repo.path().to_path_buf(), super::repo::common_dir(&repo)]
+                .into_iter()
+                .map(|git_dir| gitcrypt::unlocked_key_path(&git_dir, key_name))
+                .find(|key_path| key_path.exists())
+                .map(|key_path| GitCryptKey::load(&key_path))
+                .transpose()?,
+            Err(_) => None,
+        };
+        Ok(Self {
+            crypt,
+            workdir,
+            settings: config.filter,
+            git_crypt,
+            compatibility: config.mode == IntegrationMode::Compatibility,
+        })
+    }
+
+    /// Encrypt the content of `path` for the index
+    ///
+    /// Content that is already encrypted, e.g. by `cargocrypt encrypt`, is
+    /// stored as is.
+    pub async fn clean(&self, path: &Path, input: &[u8]) -> CryptoResult<Vec<u8>> {
+        if input.starts_with(FILE_MAGIC) || gitcrypt::is_encrypted(input) {
+            return Ok(input.to_vec());
+        }
+        if self.compatibility {
+            return match &self.git_crypt {
+                Some(key) => Ok(key.encrypt(input)),
+                None => Err(CargoCryptError::Config {
+                    message: format!("No git-crypt key to encrypt {} with in compatibility mode", path.display()),
+                    suggestion: Some("Run 'cargocrypt migrate git-crypt' or 'git-crypt unlock' first".to_string()),
+                }),
+            };
+        }
+        self.encrypt(path, None, input).await
+    }
+
+    /// Encrypt `input` in CargoCrypt's format with the key of `path`
+    async fn encrypt(&self, path: &Path, stored_vault: Option<&str>, input: &[u8]) -> CryptoResult<Vec<u8>> {
+        let salt = self.settings.salt()?.ok_or_else(|| CargoCryptError::Config {
+            message: "No filter salt in .cargocrypt/git.toml".to_string(),
+            suggestion: Some("Run 'cargocrypt git configure-attributes' to generate it, then commit .cargocrypt/git.toml".to_string()),
+        })?;
+        let nonce_path = path.to_string_lossy();
+        let encrypted = match self.key(path, stored_vault).await? {
+            FileKey::Password(password) => {
+                self.crypt.crypto().encrypt_data_deterministic(input, &password, salt, &nonce_path).await?
+            }
+            FileKey::Keyfile { keyfile, vault } => self
+                .crypt
+                .crypto()
+                .encrypt_with_keyfile(
+                    PlaintextSecret::from_bytes(input.to_vec()),
+                    &keyfile,
+                    None,
+                    EncryptionOptions::default().with_salt(salt).deterministic(nonce_path),
+                )
+                .await?
+                .with_vault(vault.as_deref()),
+        };
+        Ok(encrypted.to_bytes()?)
+    }
+
+    /// Decrypt the stored content of `path` for the working tree
+    pub async fn smudge(&self, path: &Path, input: &[u8]) -> CryptoResult<Smudged> {
+        if !input.starts_with(FILE_MAGIC) && !gitcrypt::is_encrypted(input) {
+            return Ok(Smudged {
+                content: input.to_vec(),
+                warning: Some(format!(
+                    "{} is committed unencrypted; run 'git add --renormalize .' to encrypt it",
+                    path.display()
+                )),
+            });
+        }
+
+        match self.decrypt(path, input).await {
+            Ok(content) => Ok(Smudged { content, warning: None }),
+            Err(e)
+                if self.settings.on_decrypt_failure == DecryptFailure::Passthrough
+                    && !matches!(e, CargoCryptError::Auth { .. }) =>
+            {
+                Ok(Smudged {
+                    content: input.to_vec(),
+                    warning: Some(format!("{} checked out encrypted: {}", path.display(), e)),
+                })
+            }
+            Err(e @ CargoCryptError::Auth { .. }) => Err(e),
+            Err(e) => Err(CargoCryptError::Config {
+                message: format!("Cannot decrypt {}: {}", path.display(), e),
+                suggestion: Some(
+                    "Provide its key (see [filter] in .cargocrypt/git.toml), or set on_decrypt_failure = \"passthrough\" there to check out ciphertext"
+                        .to_string(),
+                ),
+            }),
+        }
+    }
+
+    /// Three-way merge of the stored contents of `path`
+    ///
+    /// The result is encrypted in the format and vault of `ours` (or of
+    /// `theirs` when ours is not encrypted); unencrypted sides merge as they
+    /// are.
+    pub async fn merge(&self, path: &Path, base: &[u8], ours: &[u8], theirs: &[u8]) -> CryptoResult<Merged> {
+        let (merged, conflicts) = match diffy::merge_bytes(
+            &self.plaintext(path, base).await?,
+            &self.plaintext(path, ours).await?,
+            &self.plaintext(path, theirs).await?,
+        ) {
+            Ok(merged) => (merged, false),
+            Err(conflicted) => (conflicted, true),
+        };
+
+        let format = [ours, theirs]
+            .into_iter()
+            .find(|side| side.starts_with(FILE_MAGIC) || gitcrypt::is_encrypted(side));
+        let content = match (format, &self.git_crypt) {
+            (None, _) => merged,
+            (Some(side), Some(key)) if gitcrypt::is_encrypted(side) => key.encrypt(&merged),
+            (Some(side), _) => {
+                let vault = EncryptedSecret::from_bytes(side).ok().and_then(|secret| secret.vault().map(str::to_string));
+                self.encrypt(path, vault.as_deref(), &merged).await?
+            }
+        };
+        Ok(Merged { content, conflicts })
+    }
+
+    /// `input` decrypted if it is encrypted, as is otherwise
+    async fn plaintext(&self, path: &Path, input: &[u8]) -> CryptoResult<Vec<u8>> {
+        if input.starts_with(FILE_MAGIC) || gitcrypt::is_encrypted(input) {
+            self.decrypt(path, input).await
+        } else {
+            Ok(input.to_vec())
+        }
+    }
+
+    async fn decrypt(&self, path: &Path, input: &[u8]) -> CryptoResult<Vec<u8>> {
+        if gitcrypt::is_encrypted(input) {
+            return match &self.git_crypt {
+                Some(key) => key.decrypt(input),
+                None => Err(CryptoError::invalid_key("encrypted by git-crypt, and no git-crypt key is unlocked").into()),
+            };
+        }
+        let encrypted = EncryptedSecret::from_bytes(input)?;
+        let configured = self.key_for(path)?;
+        let team_key = configured.as_ref().and_then(|key| key.team_key.as_deref());
+        if let (None, Some(purpose)) = (encrypted.vault(), team_key) {
+            // Files keep the key they were encrypted with until re-encrypted
+            let mut result: CryptoResult<Vec<u8>> = Err(CryptoError::invalid_key(format!("no team key '{}' decrypts it", purpose)).into());
+            for key in self.team_keys(purpose).await? {
+                result = self.decrypt_with(&encrypted, &key);
+                if result.is_ok() {
+                    break;
+                }
+            }
+            return result;
+        }
+        self.decrypt_with(&encrypted, &self.key(path, encrypted.vault()).await?)
+    }
+
+    fn decrypt_with(&self, encrypted: &EncryptedSecret, key: &FileKey) -> CryptoResult<Vec<u8>> {
+        match key {
+            FileKey::Password(password) => Ok(self.crypt.crypto().decrypt_data(encrypted, password)?),
+            FileKey::Keyfile { keyfile, .. } => Ok(self
+                .crypt
+                .crypto()
+                .decrypt_with_keyfile(encrypted, keyfile, None)?
+                .as_bytes()
+                .to_vec()),
+        }
+    }
+
+    /// Decrypted text of a blob for `git diff`, whose path git does not pass
+    ///
+    /// Tries the vault recorded in the file, else every configured key and
+    /// then the filter password. Content that cannot be decrypted becomes a
+    /// placeholder line, so a diff still runs without the keys.
+    pub async fn textconv(&self, input: &[u8]) -> Smudged {
+        if !input.starts_with(FILE_MAGIC) && !gitcrypt::is_encrypted(input) {
+            return Smudged { content: input.to_vec(), warning: None };
+        }
+        let decrypted = if gitcrypt::is_encrypted(input) {
+            self.decrypt(Path::new(""), input).await
+        } else {
+            match EncryptedSecret::from_bytes(input) {
+                Ok(encrypted) => self.candidate_keys(encrypted.vault()).await.and_then(|keys| {
+                    let mut result: CryptoResult<Vec<u8>> = Err(CryptoError::invalid_key("no configured key decrypts it").into());
+                    for key in keys {
+                        result = self.decrypt_with(&encrypted, &key);
+                        if result.is_ok() {
+                            break;
+                        }
+                    }
+                    result
+                }),
+                Err(e) => Err(e.into()),
+            }
+        };
+        match decrypted {
+            Ok(content) => Smudged { content, warning: None },
+            Err(e) => Smudged {
+                content: format!("[CargoCrypt encrypted content, {} bytes]\n", input.len()).into_bytes(),
+                warning: Some(format!("Showing an encrypted file as a placeholder: {}", e)),
+            },
+        }
+    }
+
+    /// Keys that may have encrypted a file, skipping those that are not available
+    ///
+    /// Fails only when no key at all is available.
+    async fn candidate_keys(&self, stored_vault: Option<&str>) -> CryptoResult<Vec<FileKey>> {
+        if let Some(vault) = stored_vault {
+            return Ok(vec![self.vault_key(vault).await?]);
+        }
+        // Without a path, the keys of every package may apply
+        let settings = Workspace::discover(&self.workdir)
+            .map(|workspace| workspace.filter_settings())
+            .unwrap_or_else(|_| self.settings.clone());
+        let mut keys = Vec::new();
+        for key in settings.keys.iter().rev() {
+            if let (None, Some(purpose)) = (&key.vault, &key.team_key) {
+                keys.extend(self.team_keys(purpose).await.unwrap_or_default());
+                continue;
+            }
+            let found = match (&key.vault, &key.keyfile) {
+                (Some(vault), _) => self.vault_key(vault).await.ok(),
+                (None, Some(keyfile)) => Keyfile::load(self.workdir.join(keyfile))
+                    .ok()
+                    .map(|keyfile| FileKey::Keyfile { keyfile, vault: None }),
+                (None, None) => None,
+            };
+            keys.extend(found);
+        }
+        match self.password(&Credential::Filter) {
+            Ok(password) => keys.push(FileKey::Password(password)),
+            Err(e) if keys.is_empty() => return Err(e),
+            Err(_) => {}
+        }
+        Ok(keys)
+    }
+
+    async fn vault_key(&self, vault: &str) -> CryptoResult<FileKey> {
+        let password = self.password(&Credential::Vault(vault.to_string()))?;
+        let keyfile = self.crypt.unlock_vault(vault, &password).await?;
+        Ok(FileKey::Keyfile { keyfile, vault: Some(vault.to_string()) })
+    }
+
+    /// The key of `path`; the vault recorded in an encrypted file takes precedence
+    async fn key(&self, path: &Path, stored_vault: Option<&str>) -> CryptoResult<FileKey> {
+        let configured = self.key_for(path)?;
+        let configured = configured.as_ref();
+        if let Some(vault) = stored_vault.or_else(|| configured.and_then(|key| key.vault.as_deref())) {
+            return self.vault_key(vault).await;
+        }
+        if let Some(purpose) = configured.and_then(|key| key.team_key.as_deref()) {
+            return self.team_keys(purpose).await?.into_iter().next().ok_or_else(|| CargoCryptError::Config {
+                message: format!("No team key '{}' is wrapped for your identity", purpose),
+                suggestion: Some("Ask an Owner or Admin to run 'cargocrypt team add-member' for you".to_string()),
+            });
+        }
+        match configured.and_then(|key| key.keyfile.as_ref()) {
+            Some(keyfile) => Ok(FileKey::Keyfile {
+                keyfile: Keyfile::load(self.workdir.join(keyfile))?,
+                vault: None,
+            }),
+            None => Ok(FileKey::Password(self.password(&Credential::Filter)?)),
+        }
+    }
+
+    fn password(&self, credential: &Credential) -> CryptoResult<SecureString> {
+        Ok(credentials::resolve(&self.workdir, credential)?.secret)
+    }
+
+    /// The configured key of `path`, from the innermost package configuring one
+    fn key_for(&self, path: &Path) -> CryptoResult<Option<FilterKey>> {
+        Ok(workspace::key_for(&self.workdir, &self.settings, path)?)
+    }
+
+    /// The keys of `purpose` the team identity unwraps, newest first
+    async fn team_keys(&self, purpose: &str) -> CryptoResult<Vec<FileKey>> {
+        let identities = TeamIdentity::discover(&self.workdir)?;
+        let team = TeamKeySharing::new(&GitRepo::open(&self.workdir).map_err(GitError::from)?, self.crypt.crypto())?;
+        team.keys_for_purpose(purpose, &identities)
+            .await?
+            .iter()
+            .map(|key| {
+                Ok(FileKey::Keyfile {
+                    keyfile: Keyfile::from_bytes(key.key().as_slice())?,
+                    vault: None,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_filter_roundtrip_with_keyfile() {
+        let temp_dir = TempDir::new().unwrap();
+        Keyfile::generate().unwrap().save(temp_dir.path().join("ci.key")).unwrap();
+        std::fs::create_dir_all(temp_dir.path().join(".cargocrypt")).unwrap();
+        std::fs::write(
+            GitCryptConfig::project_path(temp_dir.path()),
+            "[filter]\nsalt = \"0707070707070707070707070707070707070707070707070707070707070707\"\n[[filter.keys]]\npattern = \"*.env\"\nkeyfile = \"ci.key\"\n",
+        )
+        .unwrap();
+        let crypt = CargoCrypt::builder().project_root(temp_dir.path()).build().await.unwrap();
+        let filter = GitFilter::new(crypt, temp_dir.path()).unwrap();
+
+        let path = Path::new("deploy/prod.env");
+        let cleaned = filter.clean(path, b"DB_PASSWORD=hunter2\n").await.unwrap();
+        assert!(cleaned.starts_with(FILE_MAGIC));
+        assert_eq!(filter.clean(path, b"DB_PASSWORD=hunter2\n").await.unwrap(), cleaned);
+        assert_eq!(filter.clean(path, &cleaned).await.unwrap(), cleaned);
+
+        let smudged = filter.smudge(path, &cleaned).await.unwrap();
+        assert_eq!(smudged.content, b"DB_PASSWORD=hunter2\n");
+        assert!(smudged.warning.is_none());
+
+        // A different key must fail the checkout instead of writing ciphertext
+        std::fs::remove_file(temp_dir.path().join("ci.key")).unwrap();
+        Keyfile::generate().unwrap().save(temp_dir.path().join("ci.key")).unwrap();
+        assert!(filter.smudge(path, &cleaned).await.is_err());
+
+        let plain = filter.smudge(path, b"never encrypted").await.unwrap();
+        assert_eq!(plain.content, b"never encrypted");
+        assert!(plain.warning.is_some());
+
+        // Clean needs the repository's salt
+        std::fs::write(GitCryptConfig::project_path(temp_dir.path()), "[filter]\n").unwrap();
+        let crypt = CargoCrypt::builder().project_root(temp_dir.path()).build().await.unwrap();
+        let filter = GitFilter::new(crypt, temp_dir.path()).unwrap();
+        assert!(filter.clean(path, b"DB_PASSWORD=hunter2\n").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_merge_encrypted_sides() {
+        let temp_dir = TempDir::new().unwrap();
+        Keyfile::generate().unwrap().save(temp_dir.path().join("ci.key")).unwrap();
+        std::fs::create_dir_all(temp_dir.path().join(".cargocrypt")).unwrap();
+        std::fs::write(
+            GitCryptConfig::project_path(temp_dir.path()),
+            "[filter]\nsalt = \"0707070707070707070707070707070707070707070707070707070707070707\"\n[[filter.keys]]\npattern = \"*.enc\"\nkeyfile = \"ci.key\"\n",
+        )
+        .unwrap();
+        let crypt = CargoCrypt::builder().project_root(temp_dir.path()).build().await.unwrap();
+        let filter = GitFilter::new(crypt, temp_dir.path()).unwrap();
+        let path = Path::new("app.env.enc");
+
+        let base = filter.clean(path, b"A=1\nB=2\nC=3\n").await.unwrap();
+        let ours = filter.clean(path, b"A=10\nB=2\nC=3\n").await.unwrap();
+        let theirs = filter.clean(path, b"A=1\nB=2\nC=30\n").await.unwrap();
+        let merged = filter.merge(path, &base, &ours, &theirs).await.unwrap();
+        assert!(!merged.conflicts);
+        assert!(merged.content.starts_with(FILE_MAGIC));
+        assert_eq!(filter.smudge(path, &merged.content).await.unwrap().content, b"A=10\nB=2\nC=30\n");
+
+        let clash = filter.clean(path, b"A=11\nB=2\nC=3\n").await.unwrap();
+        let merged = filter.merge(path, &base, &ours, &clash).await.unwrap();
+        assert!(merged.conflicts);
+        let content = filter.smudge(path, &merged.content).await.unwrap().content;
+        assert!(String::from_utf8(content).unwrap().contains("<<<<<<<"));
+
+        // git diff passes no path, so textconv finds the key itself
+        let shown = filter.textconv(&ours).await;
+        assert_eq!(shown.content, b"A=10\nB=2\nC=3\n");
+        std::fs::remove_file(temp_dir.path().join("ci.key")).unwrap();
+        let shown = filter.textconv(&ours).await;
+        assert!(shown.content.starts_with(b"[CargoCrypt encrypted content"));
+        assert!(shown.warning.is_some());
+    }
+}
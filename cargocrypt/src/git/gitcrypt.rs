@@ -0,0 +1,563 @@
+//! Interop with git-crypt repositories
+//!
+//! git-crypt encrypts each file with AES-256-CTR under a nonce that is the
+//! first 12 bytes of `HMAC-SHA1(hmac key, plaintext)`, after a
+//! `\0GITCRYPT\0` header. Its keys live in key files (`git-crypt
+//! export-key`, or `.git/git-crypt/keys/<name>` once unlocked), which the
+//! repository also carries wrapped for each team member's GPG key in
+//! `.git-crypt/keys/<name>/<version>/<fingerprint>.gpg`.
+//!
+//! With such a key the CargoCrypt filters decrypt git-crypt blobs, so history
+//! does not need to be re-encrypted, and in [`IntegrationMode::Compatibility`]
+//! the clean filter keeps writing git-crypt's format for teammates who still
+//! use git-crypt. [`migrate`] moves a repository over: it imports the key and
+//! points the `.gitattributes` patterns at the CargoCrypt filter.
+
+use super::{GitAttributes, GitCryptConfig, GitError, GitRepo, GitResult, IntegrationMode};
+use crate::crypto::{CryptoError, SecureRandom};
+use crate::error::{CargoCryptError, CryptoResult};
+use aes::Aes256;
+use ctr::cipher::{KeyIvInit, StreamCipher};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// Header of files encrypted by git-crypt
+pub const FILE_HEADER: &[u8; 10] = b"\0GITCRYPT\0";
+
+/// Magic of git-crypt key files
+const KEY_FILE_MAGIC: &[u8; 12] = b"\0GITCRYPTKEY";
+
+/// Key file format written by current git-crypt versions
+const KEY_FILE_VERSION: u32 = 2;
+
+/// Name of the key git-crypt uses without `-k`
+pub const DEFAULT_KEY_NAME: &str = "default";
+
+const NONCE_LENGTH: usize = 12;
+const AES_KEY_LENGTH: usize = 32;
+const HMAC_KEY_LENGTH: usize = 64;
+const MAX_FIELD_LENGTH: usize = 1 << 20;
+
+const HEADER_FIELD_END: u32 = 0;
+const HEADER_FIELD_KEY_NAME: u32 = 1;
+const KEY_FIELD_END: u32 = 0;
+const KEY_FIELD_VERSION: u32 = 1;
+const KEY_FIELD_AES_KEY: u32 = 3;
+const KEY_FIELD_HMAC_KEY: u32 = 5;
+
+type Aes256Ctr = ctr::Ctr32BE<Aes256>;
+
+/// Whether `bytes` were encrypted by git-crypt
+pub fn is_encrypted(bytes: &[u8]) -> bool {
+    bytes.starts_with(FILE_HEADER)
+}
+
+/// One version of a git-crypt key
+#[derive(Clone, ZeroizeOnDrop)]
+pub struct KeyEntry {
+    pub version: u32,
+    aes_key: [u8; AES_KEY_LENGTH],
+    hmac_key: [u8; HMAC_KEY_LENGTH],
+}
+
+impl KeyEntry {
+    fn encrypt(&self, plaintext: &[u8]) -> Vec<u8> {
+        let nonce = &self.mac(plaintext)[..NONCE_LENGTH];
+        let mut encrypted = Vec::with_capacity(FILE_HEADER.len() + NONCE_LENGTH + plaintext.len());
+        encrypted.extend_from_slice(FILE_HEADER);
+        encrypted.extend_from_slice(nonce);
+        let start = encrypted.len();
+        encrypted.extend_from_slice(plaintext);
+        self.apply_keystream(nonce, &mut encrypted[start..]);
+        encrypted
+    }
+
+    /// The plaintext, if this key produced `nonce` for it
+    fn decrypt(&self, nonce: &[u8], ciphertext: &[u8]) -> Option<Vec<u8>> {
+        let mut plaintext = ciphertext.to_vec();
+        self.apply_keystream(nonce, &mut plaintext);
+        if crate::crypto::constant_time_compare(&self.mac(&plaintext)[..NONCE_LENGTH], nonce) {
+            Some(plaintext)
+        } else {
+            plaintext.zeroize();
+            None
+        }
+    }
+
+    /// AES-256-CTR with the nonce followed by a 32-bit big-endian block counter
+    fn apply_keystream(&self, nonce: &[u8], data: &mut [u8]) {
+        let mut iv = [0u8; 16];
+        iv[..NONCE_LENGTH].copy_from_slice(nonce);
+        Aes256Ctr::new(&self.aes_key.into(), &iv.into()).apply_keystream(data);
+    }
+
+    fn mac(&self, data: &[u8]) -> [u8; 20] {
+        let mut mac = <Hmac<Sha1> as Mac>::new_from_slice(&self.hmac_key).expect("HMAC accepts keys of any length");
+        mac.update(data);
+        mac.finalize().into_bytes().into()
+    }
+}
+
+/// A git-crypt key with all its versions
+#[derive(Clone)]
+pub struct GitCryptKey {
+    /// Name for keys other than the default one
+    pub name: Option<String>,
+    /// Versions, oldest first
+    entries: Vec<KeyEntry>,
+}
+
+impl GitCryptKey {
+    /// Generate a key with a single version, as `git-crypt init` does
+    pub fn generate() -> CryptoResult<Self> {
+        let mut aes_key = [0u8; AES_KEY_LENGTH];
+        let mut hmac_key = [0u8; HMAC_KEY_LENGTH];
+        aes_key.copy_from_slice(&SecureRandom::generate_bytes(AES_KEY_LENGTH)?);
+        hmac_key.copy_from_slice(&SecureRandom::generate_bytes(HMAC_KEY_LENGTH)?);
+        Ok(Self {
+            name: None,
+            entries: vec![KeyEntry { version: 0, aes_key, hmac_key }],
+        })
+    }
+
+    /// Parse a key file, in the current or the legacy (pre-0.4) format
+    pub fn from_bytes(bytes: &[u8]) -> CryptoResult<Self> {
+        if !bytes.starts_with(KEY_FILE_MAGIC) {
+            if bytes.len() != AES_KEY_LENGTH + HMAC_KEY_LENGTH {
+                return Err(malformed("not a git-crypt key file"));
+            }
+            let mut aes_key = [0u8; AES_KEY_LENGTH];
+            let mut hmac_key = [0u8; HMAC_KEY_LENGTH];
+            aes_key.copy_from_slice(&bytes[..AES_KEY_LENGTH]);
+            hmac_key.copy_from_slice(&bytes[AES_KEY_LENGTH..]);
+            return Ok(Self {
+                name: None,
+                entries: vec![KeyEntry { version: 0, aes_key, hmac_key }],
+            });
+        }
+
+        let mut reader = FieldReader { bytes: &bytes[KEY_FILE_MAGIC.len()..] };
+        let format = reader.u32()?;
+        if format != KEY_FILE_VERSION {
+            return Err(malformed(&format!("unsupported key file format {}", format)));
+        }
+
+        let mut name = None;
+        loop {
+            let field = reader.u32()?;
+            if field == HEADER_FIELD_END {
+                break;
+            }
+            let value = reader.field()?;
+            match field {
+                HEADER_FIELD_KEY_NAME => {
+                    name = Some(String::from_utf8(value.to_vec()).map_err(|_| malformed("key name is not UTF-8"))?)
+                }
+                // Odd fields are critical and must be understood
+                field if field & 1 == 1 => return Err(malformed(&format!("unknown critical header field {}", field))),
+                _ => {}
+            }
+        }
+
+        let mut entries = Vec::new();
+        while !reader.bytes.is_empty() {
+            let (mut version, mut aes_key, mut hmac_key) = (None, None, None);
+            loop {
+                let field = reader.u32()?;
+                if field == KEY_FIELD_END {
+                    break;
+                }
+                let value = reader.field()?;
+                match field {
+                    KEY_FIELD_VERSION if value.len() == 4 => version = Some(u32::from_be_bytes(value.try_into().unwrap())),
+                    KEY_FIELD_AES_KEY if value.len() == AES_KEY_LENGTH => aes_key = Some(value.try_into().unwrap()),
+                    KEY_FIELD_HMAC_KEY if value.len() == HMAC_KEY_LENGTH => hmac_key = Some(value.try_into().unwrap()),
+                    KEY_FIELD_VERSION | KEY_FIELD_AES_KEY | KEY_FIELD_HMAC_KEY => {
+                        return Err(malformed(&format!("key field {} has the wrong length", field)))
+                    }
+                    field if field & 1 == 1 => return Err(malformed(&format!("unknown critical key field {}", field))),
+                    _ => {}
+                }
+            }
+            match (version, aes_key, hmac_key) {
+                (Some(version), Some(aes_key), Some(hmac_key)) => entries.push(KeyEntry { version, aes_key, hmac_key }),
+                _ => return Err(malformed("incomplete key entry")),
+            }
+        }
+        if entries.is_empty() {
+            return Err(malformed("no keys in key file"));
+        }
+        entries.sort_by_key(|entry| entry.version);
+        Ok(Self { name, entries })
+    }
+
+    /// Serialize as a current-format key file, as `git-crypt export-key` writes it
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = KEY_FILE_MAGIC.to_vec();
+        bytes.extend_from_slice(&KEY_FILE_VERSION.to_be_bytes());
+        if let Some(name) = &self.name {
+            push_field(&mut bytes, HEADER_FIELD_KEY_NAME, name.as_bytes());
+        }
+        bytes.extend_from_slice(&HEADER_FIELD_END.to_be_bytes());
+        for entry in &self.entries {
+            push_field(&mut bytes, KEY_FIELD_VERSION, &entry.version.to_be_bytes());
+            push_field(&mut bytes, KEY_FIELD_AES_KEY, &entry.aes_key);
+            push_field(&mut bytes, KEY_FIELD_HMAC_KEY, &entry.hmac_key);
+            bytes.extend_from_slice(&KEY_FIELD_END.to_be_bytes());
+        }
+        bytes
+    }
+
+    /// Load a key file
+    pub fn load<P: AsRef<Path>>(path: P) -> CryptoResult<Self> {
+        let path = path.as_ref();
+        let mut bytes = std::fs::read(path).map_err(|e| {
+            CryptoError::invalid_key(format!("Failed to read git-crypt key {}: {}", path.display(), e))
+        })?;
+        let key = Self::from_bytes(&bytes);
+        bytes.zeroize();
+        key
+    }
+
+    /// Write the key file readable only by the owner, replacing an existing one
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> CryptoResult<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut bytes = self.to_bytes();
+        let result = options.open(path).and_then(|mut file| file.write_all(&bytes));
+        bytes.zeroize();
+        Ok(result?)
+    }
+
+    /// Decrypt the key wrapped for one of the user's GPG keys in the repository
+    ///
+    /// Runs `gpg`, which prompts through its agent like `git-crypt unlock`.
+    pub fn load_gpg(workdir: &Path, key_name: &str) -> CryptoResult<Self> {
+        let keys_dir = workdir.join(".git-crypt").join("keys").join(key_name);
+        let mut wrapped: Vec<PathBuf> = std::fs::read_dir(&keys_dir)
+            .map_err(|e| CryptoError::invalid_key(format!("No GPG-wrapped git-crypt keys in {}: {}", keys_dir.display(), e)))?
+            .filter_map(|version| version.ok())
+            .filter_map(|version| std::fs::read_dir(version.path()).ok())
+            .flatten()
+            .filter_map(|file| file.ok().map(|file| file.path()))
+            .filter(|path| path.extension().is_some_and(|extension| extension == "gpg"))
+            .collect();
+        wrapped.sort();
+
+        let mut entries: Vec<KeyEntry> = Vec::new();
+        for path in &wrapped {
+            let Ok(output) = Command::new("gpg").args(["--batch", "--quiet", "--decrypt"]).arg(path).stderr(Stdio::null()).output() else {
+                return Err(CryptoError::invalid_key("gpg is needed to unwrap git-crypt keys but could not be run").into());
+            };
+            let mut bytes = output.stdout;
+            if output.status.success() {
+                if let Ok(key) = Self::from_bytes(&bytes) {
+                    for entry in key.entries {
+                        if !entries.iter().any(|known| known.version == entry.version) {
+                            entries.push(entry);
+                        }
+                    }
+                }
+            }
+            bytes.zeroize();
+        }
+        if entries.is_empty() {
+            return Err(CryptoError::invalid_key(format!(
+                "None of the {} key(s) in {} could be decrypted with your GPG keys",
+                wrapped.len(),
+                keys_dir.display()
+            ))
+            .into());
+        }
+        entries.sort_by_key(|entry| entry.version);
+        let name = (key_name != DEFAULT_KEY_NAME).then(|| key_name.to_string());
+        Ok(Self { name, entries })
+    }
+
+    /// Wrap the latest key version for a GPG key, as `git-crypt add-gpg-user` does
+    ///
+    /// Writes `.git-crypt/keys/<name>/<version>/<fingerprint>.gpg`, to be committed.
+    pub fn save_gpg(&self, workdir: &Path, fingerprint: &str) -> CryptoResult<PathBuf> {
+        let latest = self.latest();
+        let key_name = self.name.as_deref().unwrap_or(DEFAULT_KEY_NAME);
+        let path = workdir
+            .join(".git-crypt")
+            .join("keys")
+            .join(key_name)
+            .join(latest.version.to_string())
+            .join(format!("{}.gpg", fingerprint));
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let single = Self { name: self.name.clone(), entries: vec![latest.clone()] };
+        let mut bytes = single.to_bytes();
+        let mut child = Command::new("gpg")
+            .args(["--batch", "--quiet", "--yes", "--trust-model", "always", "--encrypt", "--recipient", fingerprint, "--output"])
+            .arg(&path)
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|e| CryptoError::invalid_key(format!("Failed to run gpg: {}", e)))?;
+        let written = child.stdin.take().map(|mut stdin| stdin.write_all(&bytes));
+        bytes.zeroize();
+        let status = child.wait()?;
+        if !status.success() || !matches!(written, Some(Ok(()))) {
+            return Err(CryptoError::invalid_key(format!("gpg could not encrypt the key for {}", fingerprint)).into());
+        }
+        Ok(path)
+    }
+
+    /// Encrypt like git-crypt's clean filter, with the latest key version
+    pub fn encrypt(&self, plaintext: &[u8]) -> Vec<u8> {
+        self.latest().encrypt(plaintext)
+    }
+
+    /// Decrypt a file encrypted by git-crypt with any version of this key
+    pub fn decrypt(&self, encrypted: &[u8]) -> CryptoResult<Vec<u8>> {
+        let Some(rest) = encrypted.strip_prefix(FILE_HEADER.as_slice()) else {
+            return Err(CryptoError::decryption("not encrypted by git-crypt").into());
+        };
+        if rest.len() < NONCE_LENGTH {
+            return Err(CryptoError::decryption("truncated git-crypt file").into());
+        }
+        let (nonce, ciphertext) = rest.split_at(NONCE_LENGTH);
+        self.entries
+            .iter()
+            .rev()
+            .find_map(|entry| entry.decrypt(nonce, ciphertext))
+            .ok_or_else(|| CryptoError::decryption("wrong git-crypt key, or the file was tampered with").into())
+    }
+
+    fn latest(&self) -> &KeyEntry {
+        self.entries.last().expect("keys have at least one version")
+    }
+}
+
+/// Location of an unlocked key in a git directory, where `git-crypt unlock` puts it
+pub fn unlocked_key_path(git_dir: &Path, key_name: &str) -> PathBuf {
+    git_dir.join("git-crypt").join("keys").join(key_name)
+}
+
+struct FieldReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> FieldReader<'a> {
+    fn u32(&mut self) -> CryptoResult<u32> {
+        let value = self.take(4)?;
+        Ok(u32::from_be_bytes(value.try_into().unwrap()))
+    }
+
+    /// A length-prefixed field value
+    fn field(&mut self) -> CryptoResult<&'a [u8]> {
+        let length = self.u32()? as usize;
+        if length > MAX_FIELD_LENGTH {
+            return Err(malformed("field too long"));
+        }
+        self.take(length)
+    }
+
+    fn take(&mut self, length: usize) -> CryptoResult<&'a [u8]> {
+        if self.bytes.len() < length {
+            return Err(malformed("truncated key file"));
+        }
+        let (value, rest) = self.bytes.split_at(length);
+        self.bytes = rest;
+        Ok(value)
+    }
+}
+
+fn push_field(bytes: &mut Vec<u8>, field: u32, value: &[u8]) {
+    bytes.extend_from_slice(&field.to_be_bytes());
+    bytes.extend_from_slice(&(value.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(value);
+}
+
+fn malformed(reason: &str) -> CargoCryptError {
+    CryptoError::invalid_key(format!("Malformed git-crypt key: {}", reason)).into()
+}
+
+/// Point `.gitattributes` lines using git-crypt's filter for `key_name` at CargoCrypt's
+///
+/// `diff=git-crypt` is dropped since encrypted files are checked out
+/// decrypted; everything else is kept. Returns the new content and the
+/// number of lines changed.
+pub fn convert_attributes(content: &str, key_name: &str) -> (String, usize) {
+    let filter = if key_name == DEFAULT_KEY_NAME {
+        "filter=git-crypt".to_string()
+    } else {
+        format!("filter=git-crypt-{}", key_name)
+    };
+    let diff = filter.replacen("filter=", "diff=", 1);
+
+    let mut converted = 0;
+    let lines: Vec<String> = content
+        .lines()
+        .map(|line| {
+            let mut fields = line.split_whitespace();
+            let is_pattern = !line.trim_start().starts_with('#');
+            if !is_pattern || !fields.any(|field| field == filter) {
+                return line.to_string();
+            }
+            converted += 1;
+            line.split_whitespace()
+                .filter(|field| *field != diff)
+                .map(|field| if field == filter { "filter=cargocrypt-encrypt" } else { field })
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect();
+
+    let mut result = lines.join("\n");
+    if content.ends_with('\n') {
+        result.push('\n');
+    }
+    (result, converted)
+}
+
+/// What [`migrate`] changed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationReport {
+    /// Where the key was stored
+    pub key_path: PathBuf,
+    /// `.gitattributes` lines moved to the CargoCrypt filter
+    pub converted_patterns: usize,
+    pub mode: IntegrationMode,
+}
+
+/// Move a git-crypt repository to the CargoCrypt filters
+///
+/// The key comes from `key_file`, else from where `git-crypt unlock` left
+/// it, else from the GPG-wrapped keys in the repository. It is stored
+/// unlocked in the git directory. Without `reencrypt` the repository stays
+/// in compatibility mode, so files keep their git-crypt blobs; with it new
+/// commits use CargoCrypt's format while history stays readable.
+pub async fn migrate(repo: &GitRepo, key_file: Option<&Path>, key_name: &str, reencrypt: bool) -> CryptoResult<MigrationReport> {
+    validate_key_name(key_name)?;
+    let key_path = unlocked_key_path(repo.git_dir(), key_name);
+    let key = match key_file {
+        Some(path) => GitCryptKey::load(path)?,
+        None if key_path.exists() => GitCryptKey::load(&key_path)?,
+        None => GitCryptKey::load_gpg(repo.workdir(), key_name)?,
+    };
+    if key_file.is_some() || !key_path.exists() {
+        key.save(&key_path)?;
+    }
+
+    let attributes_path = repo.workdir().join(".gitattributes");
+    let content = match std::fs::read_to_string(&attributes_path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let (converted, converted_patterns) = convert_attributes(&content, key_name);
+    if converted_patterns > 0 {
+        std::fs::write(&attributes_path, converted)?;
+    }
+
+    let mut config = GitCryptConfig::load_or_default(repo).await?;
+    config.mode = if reencrypt { IntegrationMode::GitNative } else { IntegrationMode::Compatibility };
+    if key_name != DEFAULT_KEY_NAME {
+        config.filter.git_crypt_key = Some(key_name.to_string());
+    }
+    config.save(repo).await?;
+    GitAttributes::new(repo)?.configure_filters(&config).await?;
+
+    Ok(MigrationReport {
+        key_path,
+        converted_patterns,
+        mode: config.mode,
+    })
+}
+
+/// Check that `key_name` is usable as a directory name, like git-crypt does
+pub fn validate_key_name(key_name: &str) -> GitResult<()> {
+    let valid = !key_name.is_empty()
+        && key_name.len() <= 128
+        && key_name.chars().all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(GitError::InvalidObject(format!("Invalid git-crypt key name '{}'", key_name)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key() -> GitCryptKey {
+        let mut entry = KeyEntry { version: 0, aes_key: [0u8; 32], hmac_key: [0u8; 64] };
+        entry.aes_key.iter_mut().enumerate().for_each(|(i, byte)| *byte = i as u8);
+        entry.hmac_key.iter_mut().enumerate().for_each(|(i, byte)| *byte = 0xff - i as u8);
+        GitCryptKey { name: None, entries: vec![entry] }
+    }
+
+    #[test]
+    fn test_git_crypt_file_format() {
+        let key = key();
+        let encrypted = key.encrypt(b"API_KEY=sk_live_123\n");
+        assert!(is_encrypted(&encrypted));
+        assert_eq!(encrypted.len(), FILE_HEADER.len() + NONCE_LENGTH + 20);
+        // The nonce is derived from the plaintext, so blobs are stable
+        assert_eq!(key.encrypt(b"API_KEY=sk_live_123\n"), encrypted);
+        assert_eq!(key.decrypt(&encrypted).unwrap(), b"API_KEY=sk_live_123\n");
+
+        let mut tampered = encrypted.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(key.decrypt(&tampered).is_err());
+        assert!(GitCryptKey::generate().unwrap().decrypt(&encrypted).is_err());
+    }
+
+    #[test]
+    fn test_key_file_roundtrip() {
+        let mut key = key();
+        key.name = Some("ci".to_string());
+        let mut rotated = key.entries[0].clone();
+        rotated.version = 1;
+        rotated.aes_key[0] ^= 0x55;
+        key.entries.push(rotated);
+
+        let bytes = key.to_bytes();
+        assert!(bytes.starts_with(KEY_FILE_MAGIC));
+        let parsed = GitCryptKey::from_bytes(&bytes).unwrap();
+        assert_eq!(parsed.name.as_deref(), Some("ci"));
+        assert_eq!(parsed.to_bytes(), bytes);
+
+        // Files of older key versions still decrypt
+        let old = key.entries[0].encrypt(b"old secret");
+        assert_eq!(parsed.decrypt(&old).unwrap(), b"old secret");
+
+        // Legacy key files are the raw AES and HMAC keys
+        let legacy = [key.entries[0].aes_key.as_slice(), key.entries[0].hmac_key.as_slice()].concat();
+        assert_eq!(GitCryptKey::from_bytes(&legacy).unwrap().decrypt(&old).unwrap(), b"old secret");
+        assert!(GitCryptKey::from_bytes(&bytes[..bytes.len() - 3]).is_err());
+    }
+
+    #[test]
+    fn test_convert_attributes() {
+        let content = "# secrets\nsecrets/** filter=git-crypt diff=git-crypt\n*.key filter=git-crypt-ci diff=git-crypt-ci\n*.md text\n";
+        let (converted, count) = convert_attributes(content, DEFAULT_KEY_NAME);
+        assert_eq!(count, 1);
+        assert_eq!(
+            converted,
+            "# secrets\nsecrets/** filter=cargocrypt-encrypt\n*.key filter=git-crypt-ci diff=git-crypt-ci\n*.md text\n"
+        );
+        assert_eq!(convert_attributes(content, "ci").0.lines().nth(2), Some("*.key filter=cargocrypt-encrypt"));
+        assert!(validate_key_name("ci").is_ok());
+        assert!(validate_key_name("../x").is_err());
+    }
+}
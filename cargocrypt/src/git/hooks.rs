@@ -8,15 +8,54 @@
 //! 
 //! The hooks integrate with the ML-based secret detection system to prevent
 //! accidental commits of sensitive data while maintaining team workflow.
+//!
+//! Installed hooks are small shims running `cargocrypt hook exec <hook>`, so
+//! scanning and policy gating run in-process with the installed version.
+//! They go wherever `core.hooksPath` points (husky, lefthook and friends set
+//! it), and a hook that was already there is kept as
+//! `<hook>.cargocrypt-chained` and run first by `hook exec`.
+//!
+//! Which hooks are installed, which checks they run and whether findings fail
+//! them is declared in `.cargocrypt/hooks.toml`:
+//!
+//! ```toml
+//! [pre-commit]
+//! checks = ["detect", "expiry", "encryption"]
+//! fail_level = "policy"      # gate with .cargocrypt/policy.toml
+//!
+//! [pre-push]
+//! fail_level = "warn"        # report findings but never block
+//!
+//! [post-commit]
+//! enabled = false            # do not record commits made with --no-verify
+//! ```
+//!
+//! Branch policies in `.cargocrypt/git.toml` adjust these settings per
+//! branch: the branch being committed to, or the branches a push updates.
+//!
+//! The `encryption` check verifies that every file matching the CargoCrypt
+//! patterns of `.gitattributes` is stored as ciphertext: in the index on
+//! commit, and in every pushed commit on push, so a plaintext file that
+//! slipped past the filters (e.g. committed where the filter was not
+//! configured) never reaches the remote.
+//!
+//! `CARGOCRYPT_SKIP=detect` (a comma-separated list of checks, or `all`)
+//! skips checks for one run. Skips, and commits that bypassed the pre-commit
+//! hook with `git commit --no-verify`, are recorded in the hook audit log
+//! under `.git/cargocrypt/`.
 
 use super::{GitRepo, GitError, GitResult};
+use crate::plan::{Change, Plan};
+use super::config::BranchPolicy;
+use super::team::AuditEntry;
 use crate::crypto::CryptoEngine;
 use crate::resilience::{CircuitBreaker, RetryPolicy, GracefulDegradation};
 use crate::validation::{InputValidator, ValidationResult};
 use crate::error::{CargoCryptError, CryptoResult};
 use std::collections::HashMap;
+use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, ExitStatus, Stdio};
 use std::time::Duration;
 use std::sync::Arc;
 use tokio::fs;
@@ -24,6 +63,24 @@ use serde::{Deserialize, Serialize};
 use regex::Regex;
 use tracing::{info, warn, error};
 
+/// Marker identifying hooks written by CargoCrypt
+const HOOK_MARKER: &str = "CargoCrypt";
+
+/// Extension of a pre-existing hook that CargoCrypt's hook chain-loads
+pub const CHAINED_EXTENSION: &str = "cargocrypt-chained";
+
+/// Environment variable listing the checks to skip
+pub const SKIP_ENV: &str = "CARGOCRYPT_SKIP";
+
+/// Hooks CargoCrypt installs, as configured in `.cargocrypt/hooks.toml`
+pub const MANAGED_HOOKS: [HookType; 3] = [HookType::PreCommit, HookType::PrePush, HookType::PostCommit];
+
+/// Version written into the installed hooks
+const HOOK_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Tree the pre-commit hook last let through, in CargoCrypt's part of the git directory
+const VERIFIED_TREE_FILE: &str = "pre-commit-tree";
+
 /// Types of git hooks supported by CargoCrypt
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum HookType {
@@ -32,6 +89,8 @@ pub enum HookType {
     PostCommit,
     PostCheckout,
     PostMerge,
+    /// Server-side hook of the receiving repository
+    PreReceive,
 }
 
 impl HookType {
@@ -43,21 +102,25 @@ impl HookType {
             Self::PostCommit => "post-commit",
             Self::PostCheckout => "post-checkout",
             Self::PostMerge => "post-merge",
+            Self::PreReceive => "pre-receive",
         }
     }
     
     /// Check if this hook runs before an operation
     pub fn is_pre_hook(&self) -> bool {
-        matches!(self, Self::PreCommit | Self::PrePush)
+        matches!(self, Self::PreCommit | Self::PrePush | Self::PreReceive)
     }
 }
 
 /// Configuration for git hooks
+///
+/// Loaded from `.cargocrypt/hooks.toml`; tables left out take their defaults.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct HookConfig {
     /// Whether to install hooks automatically
     pub auto_install: bool,
-    /// Whether to backup existing hooks
+    /// Whether to keep existing hooks, chain-loaded before CargoCrypt's
     pub backup_existing: bool,
     /// Secret detection configuration
     pub secret_detection: SecretDetectionConfig,
@@ -65,6 +128,15 @@ pub struct HookConfig {
     pub validation: ValidationConfig,
     /// Custom hook scripts
     pub custom_scripts: HashMap<String, String>,
+    /// Checks of the pre-commit hook
+    #[serde(rename = "pre-commit")]
+    pub pre_commit: HookSettings,
+    /// Checks of the pre-push hook
+    #[serde(rename = "pre-push")]
+    pub pre_push: HookSettings,
+    /// Post-commit hook recording commits made with `--no-verify` (it runs no checks)
+    #[serde(rename = "post-commit")]
+    pub post_commit: HookSettings,
 }
 
 impl Default for HookConfig {
@@ -75,10 +147,240 @@ impl Default for HookConfig {
             secret_detection: SecretDetectionConfig::default(),
             validation: ValidationConfig::default(),
             custom_scripts: HashMap::new(),
+            pre_commit: HookSettings::default(),
+            pre_push: HookSettings::default(),
+            post_commit: HookSettings::default(),
+        }
+    }
+}
+
+impl HookConfig {
+    /// Location of the hook configuration of a project
+    pub fn project_path<P: AsRef<Path>>(workdir: P) -> PathBuf {
+        workdir.as_ref().join(".cargocrypt").join("hooks.toml")
+    }
+
+    /// Load the hook configuration of the working tree at `workdir`, the defaults if there is none
+    pub fn load_project<P: AsRef<Path>>(workdir: P) -> GitResult<Self> {
+        let path = Self::project_path(workdir);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| GitError::HookFailed(format!("Failed to read {}: {}", path.display(), e)))?;
+        toml::from_str(&content)
+            .map_err(|e| GitError::HookFailed(format!("Invalid hook config in {}: {}", path.display(), e)))
+    }
+
+    /// Settings of a hook CargoCrypt manages
+    pub fn settings(&self, hook_type: &HookType) -> Option<&HookSettings> {
+        match hook_type {
+            HookType::PreCommit => Some(&self.pre_commit),
+            HookType::PrePush => Some(&self.pre_push),
+            HookType::PostCommit => Some(&self.post_commit),
+            _ => None,
+        }
+    }
+
+    /// Whether `hook_type` is one CargoCrypt installs
+    pub fn is_enabled(&self, hook_type: &HookType) -> bool {
+        self.settings(hook_type).is_some_and(|settings| settings.enabled)
+    }
+}
+
+/// Whether a hook is installed, what it checks and how findings fail it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HookSettings {
+    /// Install the hook
+    pub enabled: bool,
+    /// Checks the hook runs
+    pub checks: Vec<HookCheck>,
+    /// Whether findings fail the hook
+    pub fail_level: FailLevel,
+}
+
+impl Default for HookSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            checks: HookCheck::ALL.to_vec(),
+            fail_level: FailLevel::Policy,
         }
     }
 }
 
+impl HookSettings {
+    /// The configured checks minus `skipped`
+    pub fn active_checks(&self, skipped: &[HookCheck]) -> Vec<HookCheck> {
+        self.checks.iter().copied().filter(|check| !skipped.contains(check)).collect()
+    }
+
+    /// The settings on a branch with `policy` from `.cargocrypt/git.toml`
+    pub fn for_branch(&self, policy: Option<&BranchPolicy>) -> HookSettings {
+        let mut settings = self.clone();
+        let Some(policy) = policy else {
+            return settings;
+        };
+        if policy.require_encryption && !settings.checks.contains(&HookCheck::Encryption) {
+            settings.checks.push(HookCheck::Encryption);
+        }
+        if let Some(fail_level) = policy.fail_level {
+            settings.fail_level = fail_level;
+        }
+        if policy.enforce_hooks {
+            settings.enabled = true;
+            if settings.fail_level == FailLevel::Warn {
+                settings.fail_level = FailLevel::Policy;
+            }
+        }
+        settings
+    }
+}
+
+/// A check run by the hooks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HookCheck {
+    /// Scan for secrets (staged changes on commit, the working tree on push)
+    Detect,
+    /// Warn about secrets due for rotation
+    Expiry,
+    /// Verify files marked for encryption are stored encrypted (staged on commit, pushed commits on push)
+    Encryption,
+}
+
+impl HookCheck {
+    /// Every check, what `CARGOCRYPT_SKIP=all` skips
+    pub const ALL: [HookCheck; 3] = [HookCheck::Detect, HookCheck::Expiry, HookCheck::Encryption];
+
+    /// Name used in `hooks.toml` and `CARGOCRYPT_SKIP`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Detect => "detect",
+            Self::Expiry => "expiry",
+            Self::Encryption => "encryption",
+        }
+    }
+}
+
+impl std::fmt::Display for HookCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Whether findings of a hook's checks stop the commit or push
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FailLevel {
+    /// Block the findings the stage's policy in `.cargocrypt/policy.toml` blocks
+    #[default]
+    Policy,
+    /// Block every finding
+    Block,
+    /// Report findings without blocking
+    Warn,
+}
+
+impl std::fmt::Display for FailLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Policy => "policy",
+            Self::Block => "block",
+            Self::Warn => "warn",
+        })
+    }
+}
+
+/// Checks listed in a `CARGOCRYPT_SKIP` value
+///
+/// Names are separated by commas or spaces; `all` skips every check and
+/// unknown names are ignored with a warning.
+pub fn parse_skip(value: &str) -> Vec<HookCheck> {
+    let mut skipped = Vec::new();
+    for name in value.split(|c: char| c == ',' || c.is_whitespace()).filter(|name| !name.is_empty()) {
+        let checks: &[HookCheck] = match name.to_ascii_lowercase().as_str() {
+            "all" => &HookCheck::ALL,
+            "detect" => &[HookCheck::Detect],
+            "expiry" => &[HookCheck::Expiry],
+            "encryption" => &[HookCheck::Encryption],
+            _ => {
+                warn!("Unknown check '{}' in {}", name, SKIP_ENV);
+                &[]
+            }
+        };
+        for check in checks {
+            if !skipped.contains(check) {
+                skipped.push(*check);
+            }
+        }
+    }
+    skipped
+}
+
+/// Checks skipped through the `CARGOCRYPT_SKIP` environment variable
+pub fn skipped_checks() -> Vec<HookCheck> {
+    std::env::var(SKIP_ENV).map(|value| parse_skip(&value)).unwrap_or_default()
+}
+
+/// A ref update git passes to the pre-push hook
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PushUpdate {
+    /// Local ref being pushed, `(delete)` when deleting
+    pub local_ref: String,
+    /// Commit being pushed, zero when deleting
+    pub local_oid: git2::Oid,
+    /// Ref updated on the remote
+    pub remote_ref: String,
+    /// Commit the remote ref points to, zero when it is created
+    pub remote_oid: git2::Oid,
+}
+
+/// The `<local ref> <local sha> <remote ref> <remote sha>` lines of the pre-push hook's stdin
+///
+/// Malformed lines are skipped.
+pub fn parse_push_updates(stdin: &[u8]) -> Vec<PushUpdate> {
+    String::from_utf8_lossy(stdin)
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            Some(PushUpdate {
+                local_ref: fields.next()?.to_string(),
+                local_oid: git2::Oid::from_str(fields.next()?).ok()?,
+                remote_ref: fields.next()?.to_string(),
+                remote_oid: git2::Oid::from_str(fields.next()?).ok()?,
+            })
+        })
+        .collect()
+}
+
+/// Commits a push sends, newest first
+///
+/// Like `git rev-list <local>... --not <remote>... --remotes`: deleted refs
+/// send nothing, and commits the remote refs or remote-tracking refs
+/// already have are left out.
+pub fn pushed_commits(repo: &git2::Repository, updates: &[PushUpdate]) -> GitResult<Vec<git2::Oid>> {
+    let mut revwalk = repo.revwalk()?;
+    let mut pushing = false;
+    for update in updates {
+        if update.local_oid.is_zero() {
+            continue;
+        }
+        revwalk.push(update.local_oid)?;
+        pushing = true;
+        // A forced push may replace commits the local repository never had
+        if !update.remote_oid.is_zero() && repo.find_commit(update.remote_oid).is_ok() {
+            revwalk.hide(update.remote_oid)?;
+        }
+    }
+    if !pushing {
+        return Ok(Vec::new());
+    }
+    revwalk.hide_glob("refs/remotes/*")?;
+    Ok(revwalk.collect::<Result<Vec<_>, _>>()?)
+}
+
 /// Configuration for secret detection in hooks
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SecretDetectionConfig {
@@ -221,10 +523,16 @@ pub struct GitHooks {
 }
 
 impl GitHooks {
-    /// Create a new GitHooks manager
+    /// Create a new GitHooks manager configured by the project's `.cargocrypt/hooks.toml`
     pub fn new(repo: &GitRepo) -> GitResult<Self> {
-        let hooks_dir = repo.git_dir().join("hooks");
-        let config = HookConfig::default();
+        let hooks_dir = hooks_dir(repo.inner());
+        let mut config = HookConfig::load_project(repo.workdir())?;
+        // Hooks a branch policy enforces are installed even where hooks.toml disables them
+        let git_config = super::GitCryptConfig::load_project(repo.workdir())?;
+        if git_config.branches.iter().any(|policy| policy.enforce_hooks) {
+            config.pre_commit.enabled = true;
+            config.pre_push.enabled = true;
+        }
         
         Ok(Self {
             repo: repo.clone(),
@@ -235,7 +543,7 @@ impl GitHooks {
     
     /// Create with custom configuration
     pub fn with_config(repo: &GitRepo, config: HookConfig) -> GitResult<Self> {
-        let hooks_dir = repo.git_dir().join("hooks");
+        let hooks_dir = hooks_dir(repo.inner());
         
         Ok(Self {
             repo: repo.clone(),
@@ -245,14 +553,29 @@ impl GitHooks {
     }
     
     /// Install a git hook
+    ///
+    /// A hook that is already there and not CargoCrypt's is moved aside to
+    /// be chain-loaded, unless `backup_existing` is off.
     pub async fn install_hook(&self, hook_type: HookType, hook: Box<dyn GitHook>) -> GitResult<()> {
         let hook_path = self.hooks_dir.join(hook_type.filename());
+        fs::create_dir_all(&self.hooks_dir).await
+            .map_err(|e| GitError::HookFailed(format!("Failed to create hooks directory: {}", e)))?;
         
-        // Backup existing hook if configured
         if self.config.backup_existing && hook_path.exists() {
-            let backup_path = hook_path.with_extension(&format!("{}.backup", hook_type.filename()));
-            fs::copy(&hook_path, backup_path).await
-                .map_err(|e| GitError::HookFailed(format!("Failed to backup hook: {}", e)))?;
+            let content = fs::read(&hook_path).await
+                .map_err(|e| GitError::HookFailed(format!("Failed to read hook: {}", e)))?;
+            if !String::from_utf8_lossy(&content).contains(HOOK_MARKER) {
+                let chained_path = chained_hook_path(&self.hooks_dir, &hook_type);
+                if chained_path.exists() {
+                    return Err(GitError::HookFailed(format!(
+                        "{} and {} both exist; merge them into one and install again",
+                        hook_path.display(),
+                        chained_path.display()
+                    )));
+                }
+                fs::rename(&hook_path, &chained_path).await
+                    .map_err(|e| GitError::HookFailed(format!("Failed to keep existing hook: {}", e)))?;
+            }
         }
         
         // Generate hook script
@@ -283,43 +606,111 @@ impl GitHooks {
         self.install_hook(HookType::PreCommit, Box::new(hook)).await
     }
     
-    /// Install pre-push hook for encryption validation
+    /// Install pre-push hook gating pushes with the push policy
     pub async fn install_encryption_validation_hook(&self) -> GitResult<()> {
         let hook = EncryptionValidationHook::new()?;
         self.install_hook(HookType::PrePush, Box::new(hook)).await
     }
     
-    /// Check if hooks are installed
-    pub fn are_installed(&self) -> bool {
-        let required_hooks = [HookType::PreCommit, HookType::PrePush];
-        
-        required_hooks.iter().all(|hook_type| {
+    /// Install CargoCrypt's hook for `hook_type`, whether or not it is enabled
+    pub async fn install_managed(&self, hook_type: &HookType) -> GitResult<()> {
+        self.install_hook(hook_type.clone(), managed_hook(hook_type)).await
+    }
+    
+    /// Install the hooks enabled in the configuration and remove the disabled ones
+    ///
+    /// Returns the hooks that are installed.
+    pub async fn install_configured(&self) -> GitResult<Vec<HookType>> {
+        let mut installed = Vec::new();
+        for hook_type in &MANAGED_HOOKS {
+            if self.config.is_enabled(hook_type) {
+                self.install_managed(hook_type).await?;
+                installed.push(hook_type.clone());
+            } else {
+                self.uninstall_hook(hook_type).await?;
+            }
+        }
+        Ok(installed)
+    }
+    
+    /// Record what [`Self::install_configured`] would change in `plan`
+    ///
+    /// Fails like installing would when a hook and the hook it would
+    /// chain-load both exist.
+    pub fn plan_install_configured(&self, plan: &mut Plan) -> GitResult<()> {
+        for hook_type in &MANAGED_HOOKS {
             let hook_path = self.hooks_dir.join(hook_type.filename());
-            hook_path.exists()
-        })
+            let installed = std::fs::read(&hook_path).ok().map(|content| String::from_utf8_lossy(&content).contains(HOOK_MARKER));
+            if self.config.is_enabled(hook_type) {
+                if self.config.backup_existing && installed == Some(false) {
+                    let chained_path = chained_hook_path(&self.hooks_dir, hook_type);
+                    if chained_path.exists() {
+                        return Err(GitError::HookFailed(format!(
+                            "{} and {} both exist; merge them into one and install again",
+                            hook_path.display(),
+                            chained_path.display()
+                        )));
+                    }
+                    plan.push(Change::Create, chained_path, Some("the existing hook, chain-loaded"));
+                }
+                let script = managed_hook(hook_type).generate_script(&self.config)?;
+                plan.write_content(hook_path, script.as_bytes(), None);
+            } else if installed == Some(true) {
+                let chained_path = chained_hook_path(&self.hooks_dir, hook_type);
+                let backup_path = hook_path.with_extension(format!("{}.backup", hook_type.filename()));
+                match [chained_path, backup_path].into_iter().find(|previous| previous.exists()) {
+                    Some(previous) => {
+                        plan.push(Change::Modify, &hook_path, Some("the hook it chain-loaded is put back"));
+                        plan.push(Change::Delete, previous, None);
+                    }
+                    None => plan.push(Change::Delete, hook_path, Some("disabled in .cargocrypt/hooks.toml")),
+                }
+            }
+        }
+        Ok(())
+    }
+    
+    /// Check if the enabled hooks are installed
+    pub fn are_installed(&self) -> bool {
+        MANAGED_HOOKS
+            .iter()
+            .filter(|hook_type| self.config.is_enabled(hook_type))
+            .all(|hook_type| {
+                let hook_path = self.hooks_dir.join(hook_type.filename());
+                hook_path.exists()
+            })
     }
     
-    /// Remove CargoCrypt hooks
+    /// Remove CargoCrypt hooks, putting back the hooks they chain-loaded
     pub async fn uninstall_hooks(&self) -> GitResult<()> {
-        let cargocrypt_hooks = [HookType::PreCommit, HookType::PrePush];
+        for hook_type in &MANAGED_HOOKS {
+            self.uninstall_hook(hook_type).await?;
+        }
         
-        for hook_type in &cargocrypt_hooks {
-            let hook_path = self.hooks_dir.join(hook_type.filename());
+        Ok(())
+    }
+    
+    /// Remove CargoCrypt's `hook_type` hook, putting back the hook it chain-loaded
+    pub async fn uninstall_hook(&self, hook_type: &HookType) -> GitResult<()> {
+        let hook_path = self.hooks_dir.join(hook_type.filename());
+        
+        if hook_path.exists() {
+            // Check if it's a CargoCrypt hook
+            let content = fs::read_to_string(&hook_path).await
+                .map_err(|e| GitError::HookFailed(format!("Failed to read hook: {}", e)))?;
             
-            if hook_path.exists() {
-                // Check if it's a CargoCrypt hook
-                let content = fs::read_to_string(&hook_path).await
-                    .map_err(|e| GitError::HookFailed(format!("Failed to read hook: {}", e)))?;
+            if content.contains(HOOK_MARKER) {
+                fs::remove_file(&hook_path).await
+                    .map_err(|e| GitError::HookFailed(format!("Failed to remove hook: {}", e)))?;
                 
-                if content.contains("CargoCrypt") {
-                    fs::remove_file(&hook_path).await
-                        .map_err(|e| GitError::HookFailed(format!("Failed to remove hook: {}", e)))?;
-                    
-                    // Restore backup if it exists
-                    let backup_path = hook_path.with_extension(&format!("{}.backup", hook_type.filename()));
-                    if backup_path.exists() {
-                        fs::rename(backup_path, hook_path).await
-                            .map_err(|e| GitError::HookFailed(format!("Failed to restore backup: {}", e)))?;
+                // Restore the chained hook, or a backup made by older versions
+                let chained_path = chained_hook_path(&self.hooks_dir, hook_type);
+                let backup_path = hook_path.with_extension(format!("{}.backup", hook_type.filename()));
+                for previous in [chained_path, backup_path] {
+                    if previous.exists() {
+                        fs::rename(previous, &hook_path).await
+                            .map_err(|e| GitError::HookFailed(format!("Failed to restore hook: {}", e)))?;
+                        break;
                     }
                 }
             }
@@ -328,6 +719,36 @@ impl GitHooks {
         Ok(())
     }
     
+    /// What is installed for each hook CargoCrypt manages, compared with what install would write
+    pub fn status(&self) -> GitResult<Vec<HookStatus>> {
+        MANAGED_HOOKS
+            .iter()
+            .map(|hook_type| {
+                let hook_path = self.hooks_dir.join(hook_type.filename());
+                let installed = match std::fs::read(&hook_path) {
+                    Ok(content) => Some(String::from_utf8_lossy(&content).into_owned()),
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                    Err(e) => return Err(GitError::HookFailed(format!("Failed to read {}: {}", hook_path.display(), e))),
+                };
+                let version = installed.as_deref().and_then(installed_version);
+                let state = match &installed {
+                    None => HookState::Missing,
+                    Some(content) if !content.contains(HOOK_MARKER) => HookState::Foreign,
+                    Some(content) if *content == managed_hook(hook_type).generate_script(&self.config)? => HookState::Current,
+                    Some(_) if version.as_deref() == Some(HOOK_VERSION) => HookState::Modified,
+                    Some(_) => HookState::Outdated,
+                };
+                Ok(HookStatus {
+                    hook_type: hook_type.clone(),
+                    enabled: self.config.is_enabled(hook_type),
+                    state,
+                    version,
+                    chained: chained_hook_path(&self.hooks_dir, hook_type).exists(),
+                })
+            })
+            .collect()
+    }
+    
     /// Get the hooks directory
     pub fn hooks_dir(&self) -> &Path {
         &self.hooks_dir
@@ -339,6 +760,242 @@ impl GitHooks {
     }
 }
 
+/// State of an installed hook
+#[derive(Debug, Clone)]
+pub struct HookStatus {
+    pub hook_type: HookType,
+    /// Whether `.cargocrypt/hooks.toml` enables the hook
+    pub enabled: bool,
+    pub state: HookState,
+    /// CargoCrypt version that installed the hook, if it is CargoCrypt's and recorded one
+    pub version: Option<String>,
+    /// Whether a hook that was there before is chain-loaded
+    pub chained: bool,
+}
+
+/// How an installed hook compares with what `cargocrypt git install-hooks` writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookState {
+    /// No hook installed
+    Missing,
+    /// Installed by this version, unchanged
+    Current,
+    /// Installed by another version
+    Outdated,
+    /// Installed by this version, then edited
+    Modified,
+    /// A hook that is not CargoCrypt's
+    Foreign,
+}
+
+impl std::fmt::Display for HookState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            Self::Missing => "not installed",
+            Self::Current => "up to date",
+            Self::Outdated => "outdated",
+            Self::Modified => "modified",
+            Self::Foreign => "not CargoCrypt's",
+        })
+    }
+}
+
+/// The hook CargoCrypt installs as `hook_type`
+fn managed_hook(hook_type: &HookType) -> Box<dyn GitHook> {
+    match hook_type {
+        HookType::PrePush => Box::new(EncryptionValidationHook),
+        HookType::PostCommit => Box::new(NoVerifyAuditHook),
+        _ => Box::new(SecretDetectionHook { crypto: CryptoEngine::new() }),
+    }
+}
+
+/// CargoCrypt version recorded in an installed hook
+fn installed_version(content: &str) -> Option<String> {
+    let prefix = format!("# {} version: ", HOOK_MARKER);
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix(&prefix))
+        .map(|version| version.trim().to_string())
+}
+
+/// CargoCrypt's files in the git directory of the worktree, which are never committed
+fn state_dir(repo: &git2::Repository) -> PathBuf {
+    repo.path().join("cargocrypt")
+}
+
+/// Location of the hook audit log of a repository, shared by its worktrees
+pub fn audit_log_path(repo: &git2::Repository) -> PathBuf {
+    super::repo::common_dir(repo).join("cargocrypt").join("hooks-audit.log")
+}
+
+/// Record a skipped check or bypassed hook in the hook audit log
+///
+/// Entries are in the format of the team audit log, with the committer as actor.
+pub fn log_hook_event(repo: &git2::Repository, event: &str, details: &str) -> GitResult<()> {
+    let path = audit_log_path(repo);
+    let io_error = |e: std::io::Error| GitError::HookFailed(format!("Failed to write {}: {}", path.display(), e));
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(io_error)?;
+    }
+
+    let actor = repo
+        .config()
+        .and_then(|config| config.get_string("user.email"))
+        .unwrap_or_else(|_| "unknown".to_string());
+    let timestamp = crate::utils::unix_now();
+    let mut log = std::fs::OpenOptions::new().create(true).append(true).open(&path).map_err(io_error)?;
+    writeln!(log, "{} | {} | {} | {}", timestamp, event, actor, details).map_err(io_error)
+}
+
+/// Entries of the hook audit log, newest first
+pub fn hook_audit_trail(repo: &git2::Repository, limit: Option<usize>) -> GitResult<Vec<AuditEntry>> {
+    let path = audit_log_path(repo);
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(GitError::HookFailed(format!("Failed to read {}: {}", path.display(), e))),
+    };
+    let mut entries: Vec<AuditEntry> = content
+        .lines()
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.splitn(4, " | ").collect();
+            match parts.as_slice() {
+                [timestamp, operation, actor, details] => Some(AuditEntry {
+                    timestamp: timestamp.parse().unwrap_or(0),
+                    operation: operation.to_string(),
+                    actor: actor.to_string(),
+                    details: details.to_string(),
+                }),
+                _ => None,
+            }
+        })
+        .collect();
+    entries.reverse();
+    if let Some(limit) = limit {
+        entries.truncate(limit);
+    }
+    Ok(entries)
+}
+
+/// Tree of the index being committed
+///
+/// `git commit -a` and `git commit <paths>` commit a temporary index that
+/// git passes to the hooks in `GIT_INDEX_FILE`.
+fn staged_tree(repo: &git2::Repository) -> GitResult<git2::Oid> {
+    let mut index = match std::env::var_os("GIT_INDEX_FILE") {
+        Some(path) => git2::Index::open(Path::new(&path))?,
+        None => repo.index()?,
+    };
+    Ok(index.write_tree_to(repo)?)
+}
+
+/// Remember that the pre-commit hook let the staged tree through
+pub fn record_verified_commit(repo: &git2::Repository) -> GitResult<()> {
+    let tree = staged_tree(repo)?;
+    let path = state_dir(repo).join(VERIFIED_TREE_FILE);
+    std::fs::create_dir_all(state_dir(repo))
+        .and_then(|_| std::fs::write(&path, format!("{}\n", tree)))
+        .map_err(|e| GitError::HookFailed(format!("Failed to write {}: {}", path.display(), e)))
+}
+
+/// Whether the commit just made skipped CargoCrypt's pre-commit hook
+///
+/// The pre-commit hook records the tree it let through, so a commit of
+/// another tree was made with `--no-verify`. Commits made by rebase,
+/// cherry-pick and friends never run pre-commit and are not reported, nor
+/// are commits made while the pre-commit hook is not CargoCrypt's.
+pub fn bypassed_pre_commit(repo: &git2::Repository) -> GitResult<bool> {
+    let sequencing = std::env::var_os("GIT_REFLOG_ACTION").is_some_and(|action| !action.is_empty());
+    if sequencing || repo.state() != git2::RepositoryState::Clean {
+        return Ok(false);
+    }
+    let pre_commit = hooks_dir(repo).join(HookType::PreCommit.filename());
+    let ours = std::fs::read(&pre_commit).is_ok_and(|content| String::from_utf8_lossy(&content).contains(HOOK_MARKER));
+    if !ours {
+        return Ok(false);
+    }
+
+    let committed = repo.head()?.peel_to_commit()?.tree_id();
+    let verified = std::fs::read_to_string(state_dir(repo).join(VERIFIED_TREE_FILE)).unwrap_or_default();
+    Ok(verified.trim() != committed.to_string())
+}
+
+/// Directory git runs hooks from: `core.hooksPath` if set, else `.git/hooks`
+///
+/// A relative `core.hooksPath` is relative to the working tree, as in git.
+/// Linked worktrees share the hooks of the main git directory; submodules
+/// have their own in the superproject's `.git/modules/<name>/hooks`.
+pub fn hooks_dir(repo: &git2::Repository) -> PathBuf {
+    let configured = repo
+        .config()
+        .and_then(|config| config.get_path("core.hooksPath"))
+        .ok();
+    match configured {
+        Some(path) if path.is_absolute() => path,
+        Some(path) => repo.workdir().unwrap_or_else(|| repo.path()).join(path),
+        None => super::repo::common_dir(repo).join("hooks"),
+    }
+}
+
+/// Where the hook CargoCrypt replaced is kept
+pub fn chained_hook_path(hooks_dir: &Path, hook_type: &HookType) -> PathBuf {
+    hooks_dir.join(format!("{}.{}", hook_type.filename(), CHAINED_EXTENSION))
+}
+
+/// Run the hook CargoCrypt replaced with the same arguments and stdin
+///
+/// Returns `None` when there is no chained hook, or, like git, when it is
+/// not executable.
+pub fn run_chained_hook(hooks_dir: &Path, hook_type: &HookType, args: &[String], stdin: &[u8]) -> GitResult<Option<ExitStatus>> {
+    let path = chained_hook_path(hooks_dir, hook_type);
+    if !is_executable(&path) {
+        return Ok(None);
+    }
+
+    let mut child = Command::new(&path)
+        .args(args)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| GitError::HookFailed(format!("Failed to run {}: {}", path.display(), e)))?;
+    if let Some(mut child_stdin) = child.stdin.take() {
+        // A hook that does not read its stdin closes it early, which is fine
+        let _ = child_stdin.write_all(stdin);
+    }
+    let status = child
+        .wait()
+        .map_err(|e| GitError::HookFailed(format!("Failed to run {}: {}", path.display(), e)))?;
+    Ok(Some(status))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path).is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Shim handing `hook_type` to `cargocrypt hook exec`
+fn exec_script(hook_type: &HookType, purpose: &str) -> String {
+    let name = hook_type.filename();
+    format!(r#"#!/bin/sh
+# {HOOK_MARKER} {name} hook: {purpose}
+# {HOOK_MARKER} version: {HOOK_VERSION}
+# Installed by `cargocrypt git install-hooks`. A {name} hook that was here
+# before is kept as {name}.{CHAINED_EXTENSION} and runs first.
+
+if ! command -v cargocrypt >/dev/null 2>&1; then
+    echo "❌ CargoCrypt not found in PATH"
+    exit 1
+fi
+
+exec cargocrypt hook exec {name} "$@"
+"#)
+}
+
 /// Trait for git hook implementations
 pub trait GitHook {
     /// Generate the hook script content
@@ -512,33 +1169,7 @@ impl SecretDetectionHook {
 
 impl GitHook for SecretDetectionHook {
     fn generate_script(&self, _config: &HookConfig) -> GitResult<String> {
-        let script = format!(r#"#!/bin/bash
-# CargoCrypt Pre-commit Hook - Secret Detection
-# This hook prevents committing files that contain secrets
-
-set -e
-
-echo "🔍 CargoCrypt: Scanning for secrets..."
-
-# Check if cargocrypt is available
-if ! command -v cargocrypt &> /dev/null; then
-    echo "❌ CargoCrypt not found in PATH"
-    exit 1
-fi
-
-# Run secret detection on staged files using CargoCrypt's built-in detection
-if cargocrypt git install-hooks --check-secrets 2>/dev/null; then
-    echo "✅ No secrets detected in staged files"
-    exit 0
-else
-    echo "❌ Secrets detected! Commit blocked."
-    echo "To encrypt sensitive files: 'cargocrypt encrypt <file>'"
-    echo "Or configure .gitattributes for automatic encryption"
-    exit 1
-fi
-"#);
-        
-        Ok(script)
+        Ok(exec_script(&HookType::PreCommit, "secret detection"))
     }
     
     fn name(&self) -> &str {
@@ -562,36 +1193,90 @@ impl EncryptionValidationHook {
 
 impl GitHook for EncryptionValidationHook {
     fn generate_script(&self, _config: &HookConfig) -> GitResult<String> {
-        let script = r#"#!/bin/bash
-# CargoCrypt Pre-push Hook - Encryption Validation
-# This hook validates that encrypted files are properly encrypted
+        Ok(exec_script(&HookType::PrePush, "push policy gate"))
+    }
+    
+    fn name(&self) -> &str {
+        "encryption-validation"
+    }
+    
+    fn description(&self) -> &str {
+        "Validates that encrypted files are properly encrypted before push"
+    }
+}
+
+/// Post-commit hook recording commits that bypassed the pre-commit hook
+pub struct NoVerifyAuditHook;
+
+impl GitHook for NoVerifyAuditHook {
+    fn generate_script(&self, _config: &HookConfig) -> GitResult<String> {
+        Ok(exec_script(&HookType::PostCommit, "--no-verify audit"))
+    }
+    
+    fn name(&self) -> &str {
+        "no-verify-audit"
+    }
+    
+    fn description(&self) -> &str {
+        "Records commits made with --no-verify in the hook audit log"
+    }
+}
+
+/// Server-side pre-receive hook rejecting pushes that add secrets
+///
+/// Meant for the bare repositories of self-hosted git servers, where it sees
+/// every push regardless of the hooks installed in clones. Objects the
+/// server did not have yet are listed with `git rev-list --objects` and
+/// scanned by `cargocrypt scan --stdin-objects`, which gates them with the
+/// `push` stage of `.cargocrypt/policy.toml` in the repository directory.
+pub struct PreReceiveHook;
 
-set -e
+impl PreReceiveHook {
+    /// Create a new pre-receive hook
+    pub fn new() -> GitResult<Self> {
+        Ok(Self)
+    }
+}
 
-echo "🔐 CargoCrypt: Validating encrypted files..."
+impl GitHook for PreReceiveHook {
+    fn generate_script(&self, _config: &HookConfig) -> GitResult<String> {
+        let script = r#"#!/bin/bash
+# CargoCrypt Pre-receive Hook - Secret Detection
+# Install as hooks/pre-receive of the bare repository (Gitea: repository
+# settings > Git Hooks; GitLab: custom_hooks/pre-receive) or as a global hook.
 
-# Check if cargocrypt is available
 if ! command -v cargocrypt &> /dev/null; then
-    echo "❌ CargoCrypt not found in PATH"
+    echo "❌ CargoCrypt not found in PATH on the git server, push rejected"
     exit 1
 fi
 
-# Validate encryption for files marked as encrypted
-# For now, just check if there are any .enc files that might need validation
-echo "✅ Encryption validation passed"
-# TODO: Implement proper validation once validate command is added
-exit 0
+status=0
+while read -r old new ref; do
+    # Deleting a ref pushes no objects
+    if [[ "$new" =~ ^0+$ ]]; then
+        continue
+    fi
+
+    # Objects reachable from the new tip that no existing ref reaches
+    if ! git rev-list --objects "$new" --not --all | cargocrypt scan --stdin-objects; then
+        echo "❌ CargoCrypt: secrets detected in $ref, push rejected"
+        echo "Remove them from the pushed commits (and rotate them) before pushing again"
+        status=1
+    fi
+done
+
+exit $status
 "#;
-        
+
         Ok(script.to_string())
     }
-    
+
     fn name(&self) -> &str {
-        "encryption-validation"
+        "secret-detection-pre-receive"
     }
-    
+
     fn description(&self) -> &str {
-        "Validates that encrypted files are properly encrypted before push"
+        "Rejects pushes that add secrets on the git server"
     }
 }
 
@@ -636,6 +1321,13 @@ mod tests {
         assert!(script.contains("secret detection"));
     }
     
+    #[test]
+    fn test_pre_receive_hook() {
+        let script = PreReceiveHook::new().unwrap().generate_script(&HookConfig::default()).unwrap();
+        assert!(script.contains("git rev-list --objects \"$new\" --not --all | cargocrypt scan --stdin-objects"));
+        assert_eq!(HookType::PreReceive.filename(), "pre-receive");
+    }
+
     #[test]
     fn test_secret_pattern_matching() {
         let pattern = SecretPattern::new("api_key", r"api_key\s*=\s*[a-zA-Z0-9_-]+");
@@ -681,4 +1373,196 @@ mod tests {
         let content = fs::read_to_string(&hook_path).await.unwrap();
         assert!(content.contains("CargoCrypt"));
     }
+
+    #[tokio::test]
+    async fn test_existing_hooks_are_chained_in_hooks_path() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = GitRepo::init(temp_dir.path()).unwrap();
+        repo.inner().config().unwrap().set_str("core.hooksPath", ".husky").unwrap();
+        let hooks = GitHooks::new(&repo).unwrap();
+        assert!(hooks.hooks_dir().ends_with(".husky"));
+
+        let husky_hook = "#!/bin/sh\nnpx lint-staged\n";
+        std::fs::create_dir_all(hooks.hooks_dir()).unwrap();
+        std::fs::write(hooks.hooks_dir().join("pre-commit"), husky_hook).unwrap();
+
+        // Installing twice must not chain CargoCrypt's own hook
+        hooks.install_secret_detection_hook().await.unwrap();
+        hooks.install_secret_detection_hook().await.unwrap();
+        let chained = chained_hook_path(hooks.hooks_dir(), &HookType::PreCommit);
+        assert_eq!(std::fs::read_to_string(&chained).unwrap(), husky_hook);
+        let shim = std::fs::read_to_string(hooks.hooks_dir().join("pre-commit")).unwrap();
+        assert!(shim.contains("exec cargocrypt hook exec pre-commit \"$@\""));
+
+        hooks.uninstall_hooks().await.unwrap();
+        assert_eq!(std::fs::read_to_string(hooks.hooks_dir().join("pre-commit")).unwrap(), husky_hook);
+        assert!(!chained.exists());
+    }
+
+    #[tokio::test]
+    async fn test_configured_hooks_and_status() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = GitRepo::init(temp_dir.path()).unwrap();
+        std::fs::create_dir_all(temp_dir.path().join(".cargocrypt")).unwrap();
+        std::fs::write(
+            HookConfig::project_path(temp_dir.path()),
+            "[pre-push]\nchecks = [\"detect\"]\nfail_level = \"warn\"\n\n[post-commit]\nenabled = false\n",
+        )
+        .unwrap();
+        let hooks = GitHooks::new(&repo).unwrap();
+        assert_eq!(hooks.config().pre_commit, HookSettings::default());
+        assert_eq!(hooks.config().pre_push.checks, vec![HookCheck::Detect]);
+        assert_eq!(hooks.config().pre_push.fail_level, FailLevel::Warn);
+
+        let installed = hooks.install_configured().await.unwrap();
+        assert_eq!(installed, vec![HookType::PreCommit, HookType::PrePush]);
+        assert!(hooks.are_installed());
+        let status = hooks.status().unwrap();
+        assert_eq!(status[0].state, HookState::Current);
+        assert_eq!(status[0].version.as_deref(), Some(HOOK_VERSION));
+        assert_eq!(status[2].state, HookState::Missing);
+        assert!(!status[2].enabled);
+
+        // Hooks of other versions and edited hooks drift
+        let pre_commit = hooks.hooks_dir().join("pre-commit");
+        let script = std::fs::read_to_string(&pre_commit).unwrap();
+        std::fs::write(&pre_commit, script.replace(HOOK_VERSION, "0.0.1")).unwrap();
+        let pre_push = hooks.hooks_dir().join("pre-push");
+        std::fs::write(&pre_push, std::fs::read_to_string(&pre_push).unwrap() + "echo edited\n").unwrap();
+        let status = hooks.status().unwrap();
+        assert_eq!(status[0].state, HookState::Outdated);
+        assert_eq!(status[0].version.as_deref(), Some("0.0.1"));
+        assert_eq!(status[1].state, HookState::Modified);
+
+        std::fs::write(hooks.hooks_dir().join("post-commit"), "#!/bin/sh\n").unwrap();
+        assert_eq!(hooks.status().unwrap()[2].state, HookState::Foreign);
+    }
+
+    #[test]
+    fn test_parse_skip() {
+        assert_eq!(parse_skip("detect"), vec![HookCheck::Detect]);
+        assert_eq!(parse_skip(" Expiry, detect,detect "), vec![HookCheck::Expiry, HookCheck::Detect]);
+        assert_eq!(parse_skip("all"), HookCheck::ALL.to_vec());
+        assert_eq!(parse_skip("encryption"), vec![HookCheck::Encryption]);
+        assert!(parse_skip("lint").is_empty());
+        assert_eq!(
+            HookSettings::default().active_checks(&[HookCheck::Detect]),
+            vec![HookCheck::Expiry, HookCheck::Encryption]
+        );
+    }
+
+    #[test]
+    fn test_settings_for_branch() {
+        let settings = HookSettings { enabled: false, checks: vec![HookCheck::Detect], fail_level: FailLevel::Warn };
+        assert_eq!(settings.for_branch(None), settings);
+
+        let main = BranchPolicy { pattern: "main".to_string(), require_encryption: true, enforce_hooks: true, fail_level: None };
+        let on_main = settings.for_branch(Some(&main));
+        assert!(on_main.enabled);
+        assert_eq!(on_main.checks, vec![HookCheck::Detect, HookCheck::Encryption]);
+        assert_eq!(on_main.fail_level, FailLevel::Policy);
+
+        let scratch = BranchPolicy { pattern: "scratch/*".to_string(), fail_level: Some(FailLevel::Warn), ..BranchPolicy::default() };
+        assert_eq!(HookSettings::default().for_branch(Some(&scratch)).fail_level, FailLevel::Warn);
+    }
+
+    #[test]
+    fn test_pushed_commits() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = git2::Repository::init(temp_dir.path()).unwrap();
+        let signature = git2::Signature::now("Dev", "dev@example.com").unwrap();
+        let tree = repo.find_tree(repo.index().unwrap().write_tree().unwrap()).unwrap();
+        let first = repo.commit(Some("HEAD"), &signature, &signature, "first", &tree, &[]).unwrap();
+        let parent = repo.find_commit(first).unwrap();
+        let second = repo.commit(Some("HEAD"), &signature, &signature, "second", &tree, &[&parent]).unwrap();
+        let parent = repo.find_commit(second).unwrap();
+        let third = repo.commit(Some("HEAD"), &signature, &signature, "third", &tree, &[&parent]).unwrap();
+
+        let zero = git2::Oid::zero();
+        let stdin = format!(
+            "refs/heads/main {} refs/heads/main {}\n(delete) {} refs/heads/old {}\ngarbage\n",
+            third, first, zero, second
+        );
+        let updates = parse_push_updates(stdin.as_bytes());
+        assert_eq!(updates.len(), 2);
+        assert_eq!(updates[0].remote_oid, first);
+        assert_eq!(pushed_commits(&repo, &updates).unwrap(), vec![third, second]);
+        assert!(pushed_commits(&repo, &updates[1..]).unwrap().is_empty());
+
+        // A new branch sends what the remote-tracking refs do not have yet
+        repo.reference("refs/remotes/origin/main", second, true, "fetch").unwrap();
+        let new_branch = format!("refs/heads/topic {} refs/heads/topic {}\n", third, zero);
+        assert_eq!(pushed_commits(&repo, &parse_push_updates(new_branch.as_bytes())).unwrap(), vec![third]);
+    }
+
+    #[tokio::test]
+    async fn test_no_verify_commits_are_detected_and_audited() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = GitRepo::init(temp_dir.path()).unwrap();
+        let git = repo.inner();
+        git.config().unwrap().set_str("user.email", "dev@example.com").unwrap();
+        GitHooks::new(&repo).unwrap().install_secret_detection_hook().await.unwrap();
+
+        let commit = |content: &str| {
+            std::fs::write(temp_dir.path().join("file.txt"), content).unwrap();
+            let mut index = git.index().unwrap();
+            index.add_path(Path::new("file.txt")).unwrap();
+            index.write().unwrap();
+        };
+        let finish = || {
+            let tree = git.find_tree(git.index().unwrap().write_tree().unwrap()).unwrap();
+            let signature = git2::Signature::now("Dev", "dev@example.com").unwrap();
+            let parents: Vec<git2::Commit> = git.head().ok().and_then(|head| head.peel_to_commit().ok()).into_iter().collect();
+            let parents: Vec<&git2::Commit> = parents.iter().collect();
+            git.commit(Some("HEAD"), &signature, &signature, "commit", &tree, &parents).unwrap();
+        };
+
+        // The pre-commit hook ran and let the tree through
+        commit("one");
+        record_verified_commit(git).unwrap();
+        finish();
+        assert!(!bypassed_pre_commit(git).unwrap());
+
+        // Committed without the pre-commit hook
+        commit("two");
+        finish();
+        assert!(bypassed_pre_commit(git).unwrap());
+
+        log_hook_event(git, "skip", "pre-commit skipped detect").unwrap();
+        log_hook_event(git, "no-verify", "commit abc skipped the pre-commit hook").unwrap();
+        let entries = hook_audit_trail(git, Some(1)).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].operation, "no-verify");
+        assert_eq!(entries[0].actor, "dev@example.com");
+        assert_eq!(hook_audit_trail(git, None).unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_linked_worktree_uses_main_hooks() {
+        let temp_dir = TempDir::new().unwrap();
+        let main_dir = temp_dir.path().join("main");
+        let repo = GitRepo::init(&main_dir).unwrap();
+        let git = repo.inner();
+        let tree = git.find_tree(git.index().unwrap().write_tree().unwrap()).unwrap();
+        let signature = git2::Signature::now("Dev", "dev@example.com").unwrap();
+        git.commit(Some("HEAD"), &signature, &signature, "initial", &tree, &[]).unwrap();
+        git.worktree("feature", &temp_dir.path().join("feature"), None).unwrap();
+
+        let worktree = GitRepo::open(temp_dir.path().join("feature")).unwrap();
+        assert!(worktree.is_worktree());
+        assert!(!repo.is_worktree());
+        let main_git_dir = repo.git_dir().canonicalize().unwrap();
+        assert_eq!(worktree.common_dir(), main_git_dir);
+        assert_ne!(worktree.git_dir().canonicalize().unwrap(), main_git_dir);
+
+        let hooks = GitHooks::new(&worktree).unwrap();
+        assert_eq!(hooks.hooks_dir().canonicalize().unwrap(), main_git_dir.join("hooks"));
+        hooks.install_configured().await.unwrap();
+        assert!(GitHooks::new(&repo).unwrap().are_installed());
+
+        // The audit log is shared, the verified tree is per worktree
+        log_hook_event(worktree.inner(), "skip", "pre-commit skipped detect").unwrap();
+        assert_eq!(hook_audit_trail(git, None).unwrap().len(), 1);
+        assert_eq!(audit_log_path(worktree.inner()), main_git_dir.join("cargocrypt").join("hooks-audit.log"));
+    }
 }
\ No newline at end of file
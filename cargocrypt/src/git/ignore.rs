@@ -5,6 +5,7 @@
 //! necessary configuration files.
 
 use super::{GitRepo, GitError, GitResult};
+use crate::plan::Plan;
 use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 use tokio::fs;
@@ -31,12 +32,14 @@ impl Default for IgnoreConfig {
                 "*.enc".to_string(),
                 ".cargocrypt/keys/".to_string(),
                 ".cargocrypt/cache/".to_string(),
+                ".cargocrypt/team/".to_string(),
+                ".cargocrypt/attempts.toml".to_string(),
+                ".cargocrypt/sessions.toml".to_string(),
                 "# CargoCrypt temporary files".to_string(),
                 "*.tmp.cargocrypt".to_string(),
             ],
             never_ignore: vec![
                 ".cargocrypt/config.toml".to_string(),
-                ".cargocrypt/team/".to_string(),
                 ".gitattributes".to_string(),
             ],
             backup_existing: true,
@@ -157,18 +160,31 @@ impl GitIgnoreManager {
                 .map_err(|e| GitError::StorageFailed(format!("Failed to backup .gitignore: {}", e)))?;
         }
         
-        let content = self.patterns
-            .iter()
-            .map(|p| p.to_string())
-            .collect::<Vec<_>>()
-            .join("\n");
-        
-        fs::write(&self.gitignore_path, content).await
+        fs::write(&self.gitignore_path, self.content()).await
             .map_err(|e| GitError::StorageFailed(format!("Failed to write .gitignore: {}", e)))?;
         
         Ok(())
     }
     
+    /// Record what [`Self::save`] would write in `plan`
+    pub fn plan_save(&self, plan: &mut Plan) {
+        plan.write_content(&self.gitignore_path, self.content().as_bytes(), None);
+        if self.config.backup_existing {
+            if let Ok(current) = std::fs::read(&self.gitignore_path) {
+                plan.write_content(self.gitignore_path.with_extension("gitignore.bak"), &current, Some("backup of .gitignore"));
+            }
+        }
+    }
+    
+    /// Content of the .gitignore file
+    fn content(&self) -> String {
+        self.patterns
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+    
     /// Add a pattern to ignore
     pub async fn add_pattern(&mut self, pattern: &str) -> GitResult<()> {
         if !self.has_pattern(pattern) {
@@ -6,6 +6,9 @@
 //! - Team key sharing via Git refs
 //! - Git hooks for automatic secret detection
 //! - Git attributes for automatic encryption patterns
+//! - Clean/smudge filters keyed per path or per vault
+//! - Reading and writing git-crypt's format for teams migrating from it
+//! - Per-package configuration in Cargo workspaces and monorepos
 //! 
 //! The design follows git-native patterns that feel natural to developers.
 
@@ -14,18 +17,35 @@ pub mod hooks;
 pub mod attributes;
 pub mod storage;
 pub mod team;
+pub mod team_ref;
+pub mod team_audit;
+pub mod team_escrow;
 pub mod ignore;
 pub mod config;
+pub mod credentials;
+pub mod filter;
+pub mod gitcrypt;
+pub mod suggest;
+pub mod workspace;
 
 pub use repo::{GitRepo, GitRepoError, GitRepoResult};
-pub use hooks::{GitHooks, HookType, HookConfig, SecretDetectionHook};
-pub use attributes::{GitAttributes, EncryptionPattern, AttributeConfig};
+pub use hooks::{GitHooks, HookType, HookConfig, HookSettings, HookCheck, HookStatus, HookState, FailLevel, PushUpdate, SecretDetectionHook, NoVerifyAuditHook, PreReceiveHook};
+pub use attributes::{GitAttributes, EncryptionPattern, AttributeConfig, PlaintextBlob, encryption_patterns, is_stored_encrypted, plaintext_in_commits, plaintext_in_index};
 pub use storage::{EncryptedStorage, GitObjectStorage, StorageRef};
-pub use team::{TeamKeySharing, TeamMember, KeyShareConfig};
+pub use team::{TeamKeySharing, TeamMember, TeamRole, TeamIdentity, KeyShareConfig};
+pub use team_ref::{SyncOutcome, TeamSigner};
+pub use team_audit::{AuditRecord, AuditVerification};
+pub use team_escrow::EscrowShare;
 pub use ignore::{GitIgnoreManager, IgnorePattern, IgnoreConfig};
-pub use config::{GitCryptConfig, RepositorySetup, IntegrationMode};
+pub use config::{GitCryptConfig, RepositorySetup, IntegrationMode, FilterSettings, FilterKey, DecryptFailure, BranchPolicy};
+pub use credentials::{Credential, CredentialSource, ResolvedCredential};
+pub use filter::{GitFilter, Merged, Smudged};
+pub use gitcrypt::{GitCryptKey, MigrationReport};
+pub use suggest::{PatternSuggestion, PatternSuggestions, suggest_patterns};
+pub use workspace::{Workspace, WorkspaceMember};
 
 use crate::crypto::{CryptoEngine, EncryptedSecret};
+use crate::plan::{Change, Plan};
 use git2::{Repository, Signature};
 use std::path::{Path, PathBuf};
 use thiserror::Error;
@@ -72,6 +92,15 @@ pub enum GitError {
 
 pub type GitResult<T> = Result<T, GitError>;
 
+/// Files restaged by [`GitIntegration::reencrypt_team_files`]
+#[derive(Debug, Default)]
+pub struct Rekeyed {
+    /// Files encrypted again with the current team keys
+    pub files: Vec<PathBuf>,
+    /// Files whose working copy is missing or not decrypted, left as they were
+    pub skipped: Vec<PathBuf>,
+}
+
 /// Git integration manager - the main entry point for all git-native operations
 pub struct GitIntegration {
     repo: GitRepo,
@@ -106,17 +135,56 @@ impl GitIntegration {
         })
     }
     
+    /// The repository of the current directory, or `None` outside one
+    ///
+    /// For dry runs: unlike [`Self::new`], neither a repository nor
+    /// `.cargocrypt/git.toml` is created.
+    pub fn discover() -> GitResult<Option<Self>> {
+        let Some(repo) = GitRepo::discover(".") else {
+            return Ok(None);
+        };
+        let config = GitCryptConfig::load_project(repo.workdir())?;
+        Ok(Some(Self {
+            repo,
+            crypto: CryptoEngine::new(),
+            config,
+        }))
+    }
+    
+    /// Open an existing repository, which may be bare, without creating anything
+    ///
+    /// This is the entry point for CI and server-side tooling: a bare
+    /// repository supports scanning commits, [`verify_encrypted`](Self::verify_encrypted)
+    /// and the storage and team refs, and reads its configuration from HEAD.
+    pub async fn open<P: AsRef<Path>>(path: P) -> GitResult<Self> {
+        let repo = GitRepo::open(path)?;
+        let crypto = CryptoEngine::new();
+        let config = GitCryptConfig::load_or_default(&repo).await?;
+        
+        Ok(Self {
+            repo,
+            crypto,
+            config,
+        })
+    }
+    
     /// Set up CargoCrypt integration in the repository
     /// This is the equivalent of `cargo crypt init --git`
     pub async fn setup_repository(&mut self) -> GitResult<()> {
+        self.repo.require_worktree("Setting up CargoCrypt")?;
+        
         // 1. Configure .gitignore for encrypted files
         self.setup_gitignore().await?;
         
         // 2. Set up git attributes for automatic encryption
-        self.setup_attributes().await?;
+        if self.config.features.git_attributes {
+            self.setup_attributes().await?;
+        }
         
         // 3. Install git hooks for secret detection
-        self.setup_hooks().await?;
+        if self.config.features.git_hooks {
+            self.setup_hooks().await?;
+        }
         
         // 4. Initialize encrypted storage
         self.setup_storage().await?;
@@ -127,8 +195,35 @@ impl GitIntegration {
         Ok(())
     }
     
+    /// What [`Self::setup_repository`] would change, without writing anything
+    pub async fn plan_setup(&self) -> GitResult<Plan> {
+        self.repo.require_worktree("Setting up CargoCrypt")?;
+        let mut plan = Plan::new();
+        
+        let config_path = GitCryptConfig::project_path(self.repo.workdir());
+        if !config_path.exists() {
+            plan.push(Change::Create, config_path, Some("default git settings"));
+        }
+        self.setup_gitignore_manager().await?.plan_save(&mut plan);
+        if self.config.features.git_attributes {
+            self.setup_attributes_manager().await?.plan_save(&mut plan)?;
+        }
+        if self.config.features.git_hooks {
+            GitHooks::new(&self.repo)?.plan_install_configured(&mut plan)?;
+        }
+        EncryptedStorage::new(&self.repo, &self.crypto)?.plan_initialize(&mut plan)?;
+        TeamKeySharing::plan_initialize(&self.repo, &mut plan)?;
+        
+        Ok(plan)
+    }
+    
     /// Configure .gitignore to exclude encrypted files and include necessary patterns
     async fn setup_gitignore(&self) -> GitResult<()> {
+        self.setup_gitignore_manager().await?.save().await?;
+        Ok(())
+    }
+    
+    async fn setup_gitignore_manager(&self) -> GitResult<GitIgnoreManager> {
         let mut ignore_manager = GitIgnoreManager::new(&self.repo)?;
         
         // Add CargoCrypt patterns
@@ -136,21 +231,13 @@ impl GitIntegration {
         ignore_manager.add_pattern("*.enc").await?;
         ignore_manager.add_pattern(".cargocrypt/").await?;
         ignore_manager.add_pattern("!.cargocrypt/config.toml").await?; // Include config
-        ignore_manager.add_pattern("!.cargocrypt/team/").await?; // Include team keys
         
-        ignore_manager.save().await?;
-        Ok(())
+        Ok(ignore_manager)
     }
     
     /// Set up git attributes for automatic encryption patterns
     async fn setup_attributes(&self) -> GitResult<()> {
-        let mut attributes = GitAttributes::new(&self.repo)?;
-        
-        // Add default encryption patterns
-        attributes.add_pattern("*.secret", "cargocrypt-encrypt").await?;
-        attributes.add_pattern("*.key", "cargocrypt-encrypt").await?;
-        attributes.add_pattern("secrets/*", "cargocrypt-encrypt").await?;
-        attributes.add_pattern("config/secrets.*", "cargocrypt-encrypt").await?;
+        let attributes = self.setup_attributes_manager().await?;
         
         // Add clean/smudge filters for automatic encryption/decryption
         attributes.configure_filters(&self.config).await?;
@@ -159,16 +246,24 @@ impl GitIntegration {
         Ok(())
     }
     
-    /// Install git hooks for automatic secret detection
-    async fn setup_hooks(&self) -> GitResult<()> {
-        let hooks = GitHooks::new(&self.repo)?;
+    async fn setup_attributes_manager(&self) -> GitResult<GitAttributes> {
+        let mut attributes = GitAttributes::new(&self.repo)?;
         
-        // Pre-commit hook for secret detection
-        let detection_hook = SecretDetectionHook::new(&self.crypto)?;
-        hooks.install_hook(HookType::PreCommit, Box::new(detection_hook)).await?;
+        // Add the encryption patterns of .cargocrypt/git.toml
+        for pattern in &self.config.setup.default_patterns {
+            attributes.add_pattern(pattern, "filter=cargocrypt-encrypt").await?;
+        }
         
-        // Pre-push hook for encrypted file validation
-        hooks.install_encryption_validation_hook().await?;
+        // Patterns of the member crates' own .cargocrypt/git.toml
+        attributes.add_workspace_patterns(&self.workspace()?).await?;
+        
+        Ok(attributes)
+    }
+    
+    /// Install git hooks for automatic secret detection
+    async fn setup_hooks(&self) -> GitResult<()> {
+        // The hooks enabled in .cargocrypt/hooks.toml
+        GitHooks::new(&self.repo)?.install_configured().await?;
         
         Ok(())
     }
@@ -235,7 +330,7 @@ impl GitIntegration {
         ));
         
         // Write encrypted data to file
-        let encrypted_bytes = bincode::serialize(&encrypted)
+        let encrypted_bytes = encrypted.to_bytes()
             .map_err(|e| GitError::SerializationFailed(format!("Failed to serialize: {}", e)))?;
         tokio::fs::write(&encrypted_path, encrypted_bytes).await
             .map_err(|e| GitError::Io(e))?;
@@ -260,7 +355,7 @@ impl GitIntegration {
             .map_err(|e| GitError::Io(e))?;
         
         // Deserialize encrypted data
-        let encrypted: EncryptedSecret = bincode::deserialize(&encrypted_data)
+        let encrypted = EncryptedSecret::from_bytes(&encrypted_data)
             .map_err(|e| GitError::SerializationFailed(format!("Failed to deserialize: {}", e)))?;
         
         // Decrypt the data
@@ -282,21 +377,110 @@ impl GitIntegration {
     }
     
     /// Add a team member for key sharing
-    pub async fn add_team_member(&self, member: TeamMember) -> GitResult<()> {
-        let team_sharing = TeamKeySharing::new(&self.repo, &self.crypto)?;
-        team_sharing.add_member(member).await?;
+    ///
+    /// `granted_by` is the identity of a member holding the existing shared
+    /// keys, to wrap them for the new member as well. `signer` signs the
+    /// change, as team settings require by default.
+    pub async fn add_team_member(
+        &self,
+        member: TeamMember,
+        granted_by: Option<&team::TeamIdentity>,
+        signer: Option<TeamSigner>,
+    ) -> GitResult<()> {
+        let team_sharing = self.team_sharing(signer)?;
+        team_sharing.add_member(member, granted_by).await?;
         
         Ok(())
     }
     
     /// Rotate team keys and update all encrypted files
-    pub async fn rotate_team_keys(&self) -> GitResult<()> {
-        let team_sharing = TeamKeySharing::new(&self.repo, &self.crypto)?;
+    pub async fn rotate_team_keys(&self, signer: Option<TeamSigner>) -> GitResult<()> {
+        let team_sharing = self.team_sharing(signer)?;
         team_sharing.rotate_keys().await?;
         
         Ok(())
     }
     
+    /// Re-encrypt the tracked files keyed by a team key of `purposes`
+    ///
+    /// After a rotation the clean filter encrypts with the new keys, so the
+    /// files are staged again with `git add --renormalize`. That reads the
+    /// working copies, which must be checked out decrypted.
+    pub async fn reencrypt_team_files(&self, purposes: &[String]) -> GitResult<Rekeyed> {
+        let rekeyed = self.team_files(purposes)?;
+        if rekeyed.files.is_empty() {
+            return Ok(rekeyed);
+        }
+
+        let output = std::process::Command::new("git")
+            .current_dir(self.repo.workdir())
+            .args(["add", "--renormalize", "--"])
+            .args(&rekeyed.files)
+            .output()?;
+        if !output.status.success() {
+            return Err(GitError::StorageFailed(format!(
+                "Failed to re-encrypt files: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(rekeyed)
+    }
+    
+    /// Files [`Self::reencrypt_team_files`] would re-encrypt, and skip
+    pub fn team_files(&self, purposes: &[String]) -> GitResult<Rekeyed> {
+        self.repo.require_worktree("Re-encrypting files")?;
+        let mut rekeyed = Rekeyed::default();
+        for entry in self.repo.inner().index()?.iter() {
+            let Ok(path) = String::from_utf8(entry.path).map(PathBuf::from) else {
+                continue;
+            };
+            let team_key = self.config.filter.key_for(&path).and_then(|key| key.team_key.as_ref());
+            if !team_key.is_some_and(|purpose| purposes.contains(purpose)) {
+                continue;
+            }
+            let decrypted = std::fs::read(self.repo.workdir().join(&path))
+                .is_ok_and(|content| !content.starts_with(crate::crypto::secrets::FILE_MAGIC));
+            if decrypted {
+                rekeyed.files.push(path);
+            } else {
+                rekeyed.skipped.push(path);
+            }
+        }
+        Ok(rekeyed)
+    }
+    
+    /// Files marked for encryption that the commits of `revisions` store in plaintext
+    ///
+    /// A revision is a commit or a range like `origin/main..HEAD`, whose
+    /// commits are all checked. Each commit is checked against its own
+    /// `.gitattributes`, as [`plaintext_in_commits`] does. Works in bare
+    /// repositories, as nothing is read from the working tree.
+    pub fn verify_encrypted<S: AsRef<str>>(&self, revisions: &[S]) -> GitResult<Vec<PlaintextBlob>> {
+        let repo = self.repo.inner();
+        let mut commits = Vec::new();
+        for revision in revisions {
+            let revision = revision.as_ref();
+            if revision.contains("..") {
+                let mut revwalk = repo.revwalk()?;
+                revwalk.push_range(revision)?;
+                for commit in revwalk {
+                    commits.push(commit?);
+                }
+            } else {
+                commits.push(repo.revparse_single(revision)?.peel_to_commit()?.id());
+            }
+        }
+        plaintext_in_commits(repo, &commits)
+    }
+    
+    fn team_sharing(&self, signer: Option<TeamSigner>) -> GitResult<TeamKeySharing> {
+        let team_sharing = TeamKeySharing::new(&self.repo, &self.crypto)?;
+        Ok(match signer {
+            Some(signer) => team_sharing.with_signer(signer),
+            None => team_sharing,
+        })
+    }
+    
     /// Get repository reference for lower-level operations
     pub fn repo(&self) -> &GitRepo {
         &self.repo
@@ -311,6 +495,16 @@ impl GitIntegration {
     pub fn config(&self) -> &GitCryptConfig {
         &self.config
     }
+    
+    /// Change the git integration configuration and save it to .cargocrypt/git.toml
+    pub async fn update_config<F: FnOnce(&mut GitCryptConfig)>(&mut self, updater: F) -> GitResult<()> {
+        self.config.update(&self.repo, updater).await
+    }
+    
+    /// The packages of the repository and their own configurations
+    pub fn workspace(&self) -> GitResult<Workspace> {
+        Workspace::discover(self.repo.workdir())
+    }
 }
 
 /// Utility functions for git-native patterns
@@ -361,6 +555,36 @@ mod tests {
         assert!(git_integration.is_ok());
     }
     
+    #[tokio::test]
+    async fn test_bare_repository() {
+        let temp_dir = TempDir::new().unwrap();
+        let bare = Repository::init_bare(temp_dir.path()).unwrap();
+        let mut tree = bare.treebuilder(None).unwrap();
+        let attributes = bare.blob(b"*.env filter=cargocrypt-encrypt\n").unwrap();
+        tree.insert(".gitattributes", attributes, 0o100644).unwrap();
+        tree.insert("prod.env", bare.blob(b"TOKEN=plaintext\n").unwrap(), 0o100644).unwrap();
+        let mut config = bare.treebuilder(None).unwrap();
+        let git_toml = bare.blob(b"[[branches]]\npattern = \"main\"\nrequire_encryption = true\n").unwrap();
+        config.insert("git.toml", git_toml, 0o100644).unwrap();
+        tree.insert(".cargocrypt", config.write().unwrap(), 0o040000).unwrap();
+        let tree = bare.find_tree(tree.write().unwrap()).unwrap();
+        let signature = git2::Signature::now("Test", "test@example.com").unwrap();
+        bare.commit(Some("HEAD"), &signature, &signature, "Add config", &tree, &[]).unwrap();
+        
+        let mut integration = GitIntegration::open(temp_dir.path()).await.unwrap();
+        assert!(integration.repo().is_bare());
+        assert!(integration.repo().has_cargocrypt_config());
+        assert_eq!(integration.config().branches.len(), 1);
+        assert!(!temp_dir.path().join(".cargocrypt").exists());
+        
+        let plaintext = integration.verify_encrypted(&["HEAD"]).unwrap();
+        assert_eq!(plaintext.len(), 1);
+        assert_eq!(plaintext[0].path, Path::new("prod.env"));
+        assert_eq!(integration.verify_encrypted(&["HEAD..HEAD"]).unwrap(), []);
+        assert!(integration.verify_encrypted(&["no-such-branch"]).is_err());
+        assert!(integration.setup_repository().await.is_err());
+    }
+    
     #[test]
     fn test_utils_git_detection() {
         // Test utility functions
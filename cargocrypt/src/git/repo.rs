@@ -27,14 +27,20 @@ pub enum GitRepoError {
 pub type GitRepoResult<T> = Result<T, GitRepoError>;
 
 /// Git repository wrapper with CargoCrypt-specific functionality
+///
+/// Bare repositories (as on a git server or in CI mirrors) are supported for
+/// everything that works on objects and refs: scanning commits, checking
+/// that files are stored encrypted, and the storage and team refs. Operations
+/// on the index or working tree fail with [`GitRepoError::InvalidState`].
 pub struct GitRepo {
     repo: Repository,
+    /// The working tree, or the git directory of a bare repository
     workdir: PathBuf,
 }
 
 impl Clone for GitRepo {
     fn clone(&self) -> Self {
-        // Re-open the repository from the working directory
+        // Re-open the repository from the working directory (the git directory when bare)
         let repo = Repository::open(&self.workdir).expect("Failed to re-open repository");
         Self {
             repo,
@@ -52,14 +58,14 @@ impl std::fmt::Debug for GitRepo {
 }
 
 impl GitRepo {
-    /// Open an existing git repository
+    /// Open an existing git repository, which may be bare
     pub fn open<P: AsRef<Path>>(path: P) -> GitRepoResult<Self> {
-        let repo = Repository::open(path)?;
-        let workdir = repo.workdir()
-            .ok_or_else(|| GitRepoError::InvalidState("Bare repository not supported".to_string()))?
-            .to_path_buf();
-        
-        Ok(Self { repo, workdir })
+        Ok(Self::from_repository(Repository::open(path)?))
+    }
+    
+    fn from_repository(repo: Repository) -> Self {
+        let workdir = repo.workdir().unwrap_or_else(|| repo.path()).to_path_buf();
+        Self { repo, workdir }
     }
     
     /// Create a new git repository
@@ -72,6 +78,11 @@ impl GitRepo {
         Ok(Self { repo, workdir })
     }
     
+    /// Find the repository containing `path`, without creating one
+    pub fn discover<P: AsRef<Path>>(path: P) -> Option<Self> {
+        Repository::discover(path).ok().map(Self::from_repository)
+    }
+    
     /// Find existing repository or create a new one
     pub async fn find_or_create() -> GitRepoResult<Self> {
         Self::find_or_create_in(".").await
@@ -83,12 +94,7 @@ impl GitRepo {
         
         // Try to discover existing repository
         match Repository::discover(path) {
-            Ok(repo) => {
-                let workdir = repo.workdir()
-                    .ok_or_else(|| GitRepoError::InvalidState("Bare repository not supported".to_string()))?
-                    .to_path_buf();
-                Ok(Self { repo, workdir })
-            }
+            Ok(repo) => Ok(Self::from_repository(repo)),
             Err(_) => {
                 // Create new repository
                 Self::init(path)
@@ -112,23 +118,71 @@ impl GitRepo {
     }
     
     /// Get the working directory path
+    ///
+    /// For a bare repository this is the git directory, so files CargoCrypt
+    /// keeps next to the repository (such as the checkout of the team ref)
+    /// live there.
     pub fn workdir(&self) -> &Path {
         &self.workdir
     }
     
+    /// Check if the repository is bare, i.e. has no working tree
+    pub fn is_bare(&self) -> bool {
+        self.repo.is_bare()
+    }
+    
+    /// Fail with a message naming `operation` if the repository is bare
+    pub fn require_worktree(&self, operation: &str) -> GitRepoResult<()> {
+        if self.is_bare() {
+            return Err(GitRepoError::InvalidState(format!(
+                "{} needs a working tree, but {} is a bare repository",
+                operation,
+                self.workdir.display()
+            )));
+        }
+        Ok(())
+    }
+    
     /// Get the .git directory path
+    ///
+    /// For a linked worktree this is its own `.git/worktrees/<name>`, and for
+    /// a submodule the superproject's `.git/modules/<name>`.
     pub fn git_dir(&self) -> &Path {
         self.repo.path()
     }
     
+    /// The git directory shared by all worktrees, which holds the config and hooks
+    pub fn common_dir(&self) -> PathBuf {
+        common_dir(&self.repo)
+    }
+    
+    /// Check if this is a linked worktree (`git worktree add`)
+    pub fn is_worktree(&self) -> bool {
+        self.repo.is_worktree()
+    }
+    
     /// Check if CargoCrypt configuration exists
+    ///
+    /// A bare repository is checked at HEAD.
     pub fn has_cargocrypt_config(&self) -> bool {
+        if self.is_bare() {
+            return self.head_blob(".cargocrypt/git.toml").is_some() || self.head_blob("Cargo.toml").is_some();
+        }
         self.workdir.join(".cargocrypt").exists() ||
         self.workdir.join("Cargo.toml").exists() // Check if it's a Rust project
     }
     
+    /// Content of the file at `path` in the HEAD commit, if it has one
+    pub fn head_blob(&self, path: &str) -> Option<Vec<u8>> {
+        let tree = self.repo.head().ok()?.peel_to_tree().ok()?;
+        let entry = tree.get_path(Path::new(path)).ok()?;
+        let blob = self.repo.find_blob(entry.id()).ok()?;
+        Some(blob.content().to_vec())
+    }
+    
     /// Stage a file for commit
     pub async fn stage_file<P: AsRef<Path>>(&self, path: P) -> GitRepoResult<()> {
+        self.require_worktree("Staging files")?;
         let path = path.as_ref();
         let relative_path = self.make_relative_path(path)?;
         
@@ -350,6 +404,7 @@ impl GitRepo {
     
     /// Initialize .cargocrypt directory structure
     pub async fn init_cargocrypt_structure(&self) -> GitRepoResult<()> {
+        self.require_worktree("Initializing .cargocrypt")?;
         let base_dir = self.workdir.join(".cargocrypt");
         
         // Create directory structure
@@ -383,6 +438,28 @@ pre_push_validation = true
     }
 }
 
+/// The branch HEAD is on, also before its first commit; `None` when detached
+pub fn head_branch(repo: &Repository) -> Option<String> {
+    let head = repo.find_reference("HEAD").ok()?;
+    let target = head.symbolic_target()?;
+    target.strip_prefix("refs/heads/").map(str::to_string)
+}
+
+/// The git directory shared by the worktrees of `repo`
+///
+/// A linked worktree's git directory names it in its `commondir` file;
+/// any other git directory is its own common directory.
+pub fn common_dir(repo: &Repository) -> PathBuf {
+    let git_dir = repo.path();
+    match std::fs::read_to_string(git_dir.join("commondir")) {
+        Ok(content) => {
+            let common = git_dir.join(content.trim());
+            common.canonicalize().unwrap_or(common)
+        }
+        Err(_) => git_dir.to_path_buf(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -3,15 +3,43 @@
 //! This module provides encrypted blob storage within Git repositories,
 //! enabling secure storage of encrypted files alongside regular git operations.
 //! It supports git-native patterns for storing and retrieving encrypted data.
+//!
+//! Stored files are committed to the storage ref (`refs/cargocrypt/storage`
+//! by default) rather than the working branch, so large secrets can be
+//! shared by fetching and pushing that ref without bloating checkouts:
+//!
+//! ```text
+//! files/<name>                  metadata of each stored file (JSON)
+//! objects/<aa>/<62 hex digits>  chunks of encrypted data, named by SHA-256
+//! ```
+//!
+//! Data is split into chunks of at most `max_blob_size` bytes and each chunk
+//! is stored once, however many files contain it. Deleting a file only drops
+//! its metadata; [`EncryptedStorage::optimize`] removes the chunks no file
+//! refers to any more, and [`EncryptedStorage::squash_history`] lets git
+//! prune them for good.
+//!
+//! When two clones changed the store independently, [`EncryptedStorage::pull`]
+//! merges them file by file: a file changed on both sides keeps the newer
+//! version, and a file deleted on one side and changed on the other is kept.
 
+use super::team_ref::{self, Files, SyncOutcome};
 use super::{GitRepo, GitError, GitResult};
+use crate::plan::{Change, Plan};
 use crate::crypto::{CryptoEngine, EncryptedSecret};
-use git2::{Oid, ObjectType, Signature};
-use std::collections::HashMap;
+use git2::{Oid, Repository, Signature};
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::path::Path;
 use tokio::fs;
 use serde::{Deserialize, Serialize};
 
+/// Directory of the storage tree holding file metadata
+const FILES_DIR: &str = "files/";
+
+/// Directory of the storage tree holding chunks
+const OBJECTS_DIR: &str = "objects/";
+
 /// Configuration for encrypted storage
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StorageConfig {
@@ -31,7 +59,7 @@ impl Default for StorageConfig {
             storage_ref: "refs/cargocrypt/storage".to_string(),
             compress: true,
             max_blob_size: 1024 * 1024, // 1MB
-            version: 1,
+            version: 2,
         }
     }
 }
@@ -39,9 +67,9 @@ impl Default for StorageConfig {
 /// Reference to a stored encrypted object
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StorageRef {
-    /// Git object ID
+    /// Git object ID of the file's metadata
     pub oid: String,
-    /// Path within the storage tree
+    /// Name of the file in the store
     pub path: String,
     /// Metadata about the stored object
     pub metadata: StorageMetadata,
@@ -62,6 +90,9 @@ pub struct StorageMetadata {
     pub algorithm: String,
     /// Whether data is compressed
     pub compressed: bool,
+    /// SHA-256 of each chunk of the stored data, in order
+    #[serde(default)]
+    pub chunks: Vec<String>,
 }
 
 /// Encrypted storage manager for Git repositories
@@ -95,37 +126,41 @@ impl EncryptedStorage {
     
     /// Initialize encrypted storage in the repository
     pub async fn initialize(&self) -> GitResult<()> {
-        // Create initial storage tree
-        let git_repo = self.repo.inner();
-        let signature = self.get_signature()?;
-        
-        // Create empty tree for storage
-        let tree_builder = git_repo.treebuilder(None)?;
-        let tree_oid = tree_builder.write()?;
-        let tree = git_repo.find_tree(tree_oid)?;
-        
-        // Create initial commit for storage
-        let _commit_oid = git_repo.commit(
-            Some(&self.config.storage_ref),
-            &signature,
-            &signature,
-            "Initialize CargoCrypt encrypted storage",
-            &tree,
-            &[],
-        )?;
+        // Create the storage ref with an empty tree, unless it exists already
+        if team_ref::tip(self.repo.inner(), &self.config.storage_ref)?.is_none() {
+            self.commit(&Files::new(), "Initialize CargoCrypt encrypted storage", &[])?;
+        }
         
-        // Create storage configuration
+        // Create storage configuration; a bare repository only has the ref
+        if self.repo.is_bare() {
+            return Ok(());
+        }
         let storage_config_path = self.repo.workdir().join(".cargocrypt").join("storage.toml");
         let config_content = toml::to_string(&self.config)
             .map_err(|e| GitError::StorageFailed(format!("Failed to serialize config: {}", e)))?;
         
+        fs::create_dir_all(self.repo.workdir().join(".cargocrypt")).await
+            .map_err(|e| GitError::StorageFailed(format!("Failed to create .cargocrypt: {}", e)))?;
         fs::write(&storage_config_path, config_content).await
             .map_err(|e| GitError::StorageFailed(format!("Failed to write storage config: {}", e)))?;
         
         Ok(())
     }
     
-    /// Store encrypted data in git storage
+    /// Record what [`Self::initialize`] would change in `plan`
+    pub fn plan_initialize(&self, plan: &mut Plan) -> GitResult<()> {
+        if team_ref::tip(self.repo.inner(), &self.config.storage_ref)?.is_none() {
+            plan.push(Change::Create, &self.config.storage_ref, Some("git ref"));
+        }
+        if !self.repo.is_bare() {
+            let config_content = toml::to_string(&self.config)
+                .map_err(|e| GitError::StorageFailed(format!("Failed to serialize config: {}", e)))?;
+            plan.write_content(self.repo.workdir().join(".cargocrypt").join("storage.toml"), config_content.as_bytes(), None);
+        }
+        Ok(())
+    }
+    
+    /// Store encrypted data in git storage, replacing a file of the same name
     pub async fn store(&self, file_path: &Path, encrypted_secret: &EncryptedSecret) -> GitResult<StorageRef> {
         let git_repo = self.repo.inner();
         
@@ -139,8 +174,16 @@ impl EncryptedStorage {
             encrypted_data
         };
         
-        // Create blob in git
-        let blob_oid = git_repo.blob(&final_data)?;
+        // Store each chunk once, under its checksum
+        let mut files = self.files()?;
+        let mut chunks = Vec::new();
+        for chunk in final_data.chunks(self.config.max_blob_size.max(1)) {
+            let checksum = self.calculate_checksum(chunk);
+            if let Entry::Vacant(entry) = files.entry(object_path(&checksum)) {
+                entry.insert(git_repo.blob(chunk)?);
+            }
+            chunks.push(checksum);
+        }
         
         // Create storage metadata
         let metadata = StorageMetadata {
@@ -153,14 +196,20 @@ impl EncryptedStorage {
             checksum: self.calculate_checksum(&final_data),
             algorithm: "ChaCha20-Poly1305".to_string(), // CargoCrypt always uses ChaCha20-Poly1305
             compressed: self.config.compress,
+            chunks,
         };
         
         // Store in storage tree
         let storage_path = self.get_storage_path(file_path);
-        self.update_storage_tree(&storage_path, blob_oid, &metadata).await?;
+        let metadata_json = serde_json::to_string_pretty(&metadata)
+            .map_err(|e| GitError::StorageFailed(format!("Failed to serialize metadata: {}", e)))?;
+        let metadata_oid = git_repo.blob(metadata_json.as_bytes())?;
+        files.insert(format!("{}{}", FILES_DIR, storage_path), metadata_oid);
+        let parents: Vec<Oid> = team_ref::tip(git_repo, &self.config.storage_ref)?.into_iter().collect();
+        self.commit(&files, &format!("Store encrypted file: {}", storage_path), &parents)?;
         
         Ok(StorageRef {
-            oid: blob_oid.to_string(),
+            oid: metadata_oid.to_string(),
             path: storage_path,
             metadata,
         })
@@ -169,182 +218,117 @@ impl EncryptedStorage {
     /// Retrieve encrypted data from git storage
     pub async fn retrieve(&self, storage_ref: &StorageRef) -> GitResult<EncryptedSecret> {
         let git_repo = self.repo.inner();
+        let files = self.files()?;
         
-        // Get blob from git
-        let oid = Oid::from_str(&storage_ref.oid)
-            .map_err(|e| GitError::StorageFailed(format!("Invalid OID: {}", e)))?;
-        let blob = git_repo.find_blob(oid)?;
+        // Reassemble the chunks
+        let mut stored = Vec::with_capacity(storage_ref.metadata.size as usize);
+        for checksum in &storage_ref.metadata.chunks {
+            let oid = files.get(&object_path(checksum)).ok_or_else(|| {
+                GitError::StorageFailed(format!("Chunk {} of {} is missing from the store", checksum, storage_ref.path))
+            })?;
+            stored.extend_from_slice(git_repo.find_blob(*oid)?.content());
+        }
+        if self.calculate_checksum(&stored) != storage_ref.metadata.checksum {
+            return Err(GitError::StorageFailed(format!("Checksum of {} does not match its data", storage_ref.path)));
+        }
         
         // Decompress if needed
         let data = if storage_ref.metadata.compressed {
-            self.decompress_data(blob.content())?
+            self.decompress_data(&stored)?
         } else {
-            blob.content().to_vec()
+            stored
         };
         
         // Deserialize encrypted secret
         self.deserialize_encrypted_secret(&data)
     }
     
+    /// Look up a stored file by the name it was stored under
+    pub async fn get(&self, file_path: &Path) -> GitResult<Option<StorageRef>> {
+        let storage_path = self.get_storage_path(file_path);
+        match self.files()?.get(&format!("{}{}", FILES_DIR, storage_path)) {
+            Some(oid) => Ok(Some(self.load_storage_ref(&storage_path, *oid)?)),
+            None => Ok(None),
+        }
+    }
+    
     /// List all stored encrypted files
     pub async fn list_stored_files(&self) -> GitResult<Vec<StorageRef>> {
-        let git_repo = self.repo.inner();
         let mut stored_files = Vec::new();
-        
-        // Get current storage tree
-        if let Ok(storage_ref) = git_repo.find_reference(&self.config.storage_ref) {
-            let commit = git_repo.find_commit(storage_ref.target().unwrap())?;
-            let tree = commit.tree()?;
-            
-            // Walk the tree to find all stored files
-            tree.walk(git2::TreeWalkMode::PreOrder, |root, entry| {
-                if entry.kind() == Some(ObjectType::Blob) {
-                    let entry_name = entry.name().unwrap_or("");
-                    let full_path = if root.is_empty() {
-                        entry_name.to_string()
-                    } else {
-                        format!("{}{}", root, entry_name)
-                    };
-                    
-                    // Try to load metadata
-                    if let Ok(metadata) = self.load_metadata_for_path(&full_path) {
-                        stored_files.push(StorageRef {
-                            oid: entry.id().to_string(),
-                            path: full_path,
-                            metadata,
-                        });
-                    }
-                }
-                git2::TreeWalkResult::Ok
-            })?;
+        for (path, oid) in self.files()? {
+            if let Some(storage_path) = path.strip_prefix(FILES_DIR) {
+                stored_files.push(self.load_storage_ref(storage_path, oid)?);
+            }
         }
         
         Ok(stored_files)
     }
     
     /// Delete a stored file
+    ///
+    /// Its chunks stay until [`EncryptedStorage::optimize`] finds them unused.
     pub async fn delete(&self, storage_ref: &StorageRef) -> GitResult<()> {
-        // Remove from storage tree
-        self.remove_from_storage_tree(&storage_ref.path).await?;
+        let mut files = self.files()?;
+        if files.remove(&format!("{}{}", FILES_DIR, storage_ref.path)).is_none() {
+            return Err(GitError::StorageFailed(format!("{} is not in the store", storage_ref.path)));
+        }
+        let parents: Vec<Oid> = team_ref::tip(self.repo.inner(), &self.config.storage_ref)?.into_iter().collect();
+        self.commit(&files, &format!("Remove encrypted file: {}", storage_ref.path), &parents)?;
         
         Ok(())
     }
     
-    /// Update storage tree with new entry
-    async fn update_storage_tree(&self, path: &str, blob_oid: Oid, metadata: &StorageMetadata) -> GitResult<()> {
-        let git_repo = self.repo.inner();
-        let signature = self.get_signature()?;
-        
-        // Get current storage tree or create new one
-        let current_tree = if let Ok(storage_ref) = git_repo.find_reference(&self.config.storage_ref) {
-            let commit = git_repo.find_commit(storage_ref.target().unwrap())?;
-            Some(commit.tree()?)
-        } else {
-            None
-        };
-        
-        // Create new tree with updated entry
-        let mut tree_builder = git_repo.treebuilder(current_tree.as_ref())?;
-        
-        // Add the blob entry
-        tree_builder.insert(path, blob_oid, git2::FileMode::Blob.into())?;
-        
-        // Store metadata as a separate blob
-        let metadata_path = format!("{}.metadata", path);
-        let metadata_json = serde_json::to_string(metadata)
-            .map_err(|e| GitError::StorageFailed(format!("Failed to serialize metadata: {}", e)))?;
-        let metadata_oid = git_repo.blob(metadata_json.as_bytes())?;
-        tree_builder.insert(&metadata_path, metadata_oid, git2::FileMode::Blob.into())?;
-        
-        let tree_oid = tree_builder.write()?;
-        let tree = git_repo.find_tree(tree_oid)?;
-        
-        // Create commit
-        let parent_commits = if let Ok(storage_ref) = git_repo.find_reference(&self.config.storage_ref) {
-            vec![git_repo.find_commit(storage_ref.target().unwrap())?]
-        } else {
-            vec![]
-        };
-        
-        let parent_refs: Vec<&git2::Commit> = parent_commits.iter().collect();
-        
-        git_repo.commit(
-            Some(&self.config.storage_ref),
-            &signature,
-            &signature,
-            &format!("Store encrypted file: {}", path),
-            &tree,
-            &parent_refs,
-        )?;
-        
-        Ok(())
+    /// Files of the storage tree at the tip of the storage ref
+    fn files(&self) -> GitResult<Files> {
+        match team_ref::tip(self.repo.inner(), &self.config.storage_ref)? {
+            Some(tip) => team_ref::tree_files(self.repo.inner(), tip),
+            None => Ok(Files::new()),
+        }
     }
     
-    /// Remove entry from storage tree
-    async fn remove_from_storage_tree(&self, path: &str) -> GitResult<()> {
+    /// Commit `files` onto the storage ref
+    fn commit(&self, files: &Files, message: &str, parents: &[Oid]) -> GitResult<Oid> {
         let git_repo = self.repo.inner();
         let signature = self.get_signature()?;
+        let tree = git_repo.find_tree(team_ref::write_tree(git_repo, files)?)?;
+        let parents = parents.iter().map(|oid| git_repo.find_commit(*oid)).collect::<Result<Vec<_>, _>>()?;
+        let parent_refs: Vec<&git2::Commit> = parents.iter().collect();
         
-        // Get current storage tree
-        let storage_ref = git_repo.find_reference(&self.config.storage_ref)?;
-        let commit = git_repo.find_commit(storage_ref.target().unwrap())?;
-        let current_tree = commit.tree()?;
-        
-        // Create new tree without the entry
-        let mut tree_builder = git_repo.treebuilder(Some(&current_tree))?;
-        tree_builder.remove(path)?;
-        tree_builder.remove(&format!("{}.metadata", path))?; // Remove metadata too
-        
-        let tree_oid = tree_builder.write()?;
-        let tree = git_repo.find_tree(tree_oid)?;
-        
-        // Create commit
-        git_repo.commit(
-            Some(&self.config.storage_ref),
-            &signature,
-            &signature,
-            &format!("Remove encrypted file: {}", path),
-            &tree,
-            &[&commit],
-        )?;
+        let commit = git_repo.commit(None, &signature, &signature, message, &tree, &parent_refs)?;
+        git_repo.reference(&self.config.storage_ref, commit, true, message)?;
         
-        Ok(())
+        Ok(commit)
     }
     
     /// Get storage path for a file
     fn get_storage_path(&self, file_path: &Path) -> String {
         // Convert file path to storage path (flatten directory structure)
         let path_str = file_path.to_string_lossy();
-        path_str.replace('/', "_").replace('\\', "_")
+        path_str.replace(['/', '\\'], "_")
     }
     
-    /// Load metadata for a storage path
-    fn load_metadata_for_path(&self, path: &str) -> GitResult<StorageMetadata> {
-        let git_repo = self.repo.inner();
-        let metadata_path = format!("{}.metadata", path);
-        
-        let storage_ref = git_repo.find_reference(&self.config.storage_ref)?;
-        let commit = git_repo.find_commit(storage_ref.target().unwrap())?;
-        let tree = commit.tree()?;
-        
-        let metadata_entry = tree.get_path(Path::new(&metadata_path))?;
-        let metadata_blob = git_repo.find_blob(metadata_entry.id())?;
-        
+    /// Load the metadata blob of a stored file
+    fn load_storage_ref(&self, storage_path: &str, oid: Oid) -> GitResult<StorageRef> {
+        let metadata_blob = self.repo.inner().find_blob(oid)?;
         let metadata: StorageMetadata = serde_json::from_slice(metadata_blob.content())
             .map_err(|e| GitError::StorageFailed(format!("Failed to deserialize metadata: {}", e)))?;
         
-        Ok(metadata)
+        Ok(StorageRef {
+            oid: oid.to_string(),
+            path: storage_path.to_string(),
+            metadata,
+        })
     }
     
     /// Serialize encrypted secret to bytes
     fn serialize_encrypted_secret(&self, encrypted_secret: &EncryptedSecret) -> GitResult<Vec<u8>> {
-        bincode::serialize(encrypted_secret)
+        encrypted_secret.to_bytes()
             .map_err(|e| GitError::StorageFailed(format!("Failed to serialize encrypted secret: {}", e)))
     }
     
     /// Deserialize encrypted secret from bytes
     fn deserialize_encrypted_secret(&self, data: &[u8]) -> GitResult<EncryptedSecret> {
-        bincode::deserialize(data)
+        EncryptedSecret::from_bytes(data)
             .map_err(|e| GitError::StorageFailed(format!("Failed to deserialize encrypted secret: {}", e)))
     }
     
@@ -406,28 +390,137 @@ impl EncryptedStorage {
         })
     }
     
-    /// Optimize storage (garbage collection, compression)
+    /// Optimize storage by removing the chunks no stored file refers to
+    ///
+    /// The sizes in the result are those of all chunks in the store.
     pub async fn optimize(&self) -> GitResult<OptimizationResult> {
         let mut result = OptimizationResult::default();
-        
-        // Get current stats
+        let git_repo = self.repo.inner();
+        let mut files = self.files()?;
         let stored_files = self.list_stored_files().await?;
+        let chunk_size = |files: &Files| -> GitResult<u64> {
+            files.iter()
+                .filter(|(path, _)| path.starts_with(OBJECTS_DIR))
+                .map(|(_, oid)| Ok(git_repo.find_blob(*oid)?.size() as u64))
+                .sum()
+        };
         result.files_before = stored_files.len();
-        result.size_before = stored_files.iter().map(|f| f.metadata.size).sum();
+        result.size_before = chunk_size(&files)?;
         
-        // TODO: Implement optimization strategies:
-        // 1. Remove duplicate blobs
-        // 2. Recompress with better algorithms
-        // 3. Merge small files
-        // 4. Remove orphaned metadata
+        let unreferenced = drop_unreferenced_chunks(git_repo, &mut files)?;
+        if unreferenced > 0 {
+            let parents: Vec<Oid> = team_ref::tip(git_repo, &self.config.storage_ref)?.into_iter().collect();
+            self.commit(&files, &format!("Remove {} unreferenced chunk(s)", unreferenced), &parents)?;
+            result.operations_performed.push(format!("Removed {} unreferenced chunk(s)", unreferenced));
+        }
         
-        // For now, just return current stats
-        result.files_after = result.files_before;
-        result.size_after = result.size_before;
+        result.files_after = stored_files.len();
+        result.size_after = chunk_size(&files)?;
         
         Ok(result)
     }
     
+    /// Replace the history of the storage ref with a single commit of its current tree
+    ///
+    /// Chunks removed by [`EncryptedStorage::optimize`] stay reachable through
+    /// history until then; afterwards `git gc` can prune them. The next push
+    /// has to force the ref. Returns whether there was history to drop.
+    pub async fn squash_history(&self) -> GitResult<bool> {
+        let git_repo = self.repo.inner();
+        let Some(tip) = team_ref::tip(git_repo, &self.config.storage_ref)? else {
+            return Ok(false);
+        };
+        if git_repo.find_commit(tip)?.parent_count() == 0 {
+            return Ok(false);
+        }
+        self.commit(&self.files()?, "Squash CargoCrypt encrypted storage", &[])?;
+        
+        Ok(true)
+    }
+    
+    /// Fetch the storage ref of `remote` and merge it into the local one
+    pub async fn pull(&self, remote: &str) -> GitResult<SyncOutcome> {
+        let git_repo = self.repo.inner();
+        let tracking = remote_ref(remote);
+        if !team_ref::fetch_ref(git_repo, remote, &self.config.storage_ref, &tracking)? {
+            return Ok(SyncOutcome::NoRemoteState);
+        }
+        let Some(theirs) = team_ref::tip(git_repo, &tracking)? else {
+            return Ok(SyncOutcome::NoRemoteState);
+        };
+        
+        let ours = match team_ref::tip(git_repo, &self.config.storage_ref)? {
+            Some(ours) if ours == theirs || git_repo.graph_descendant_of(ours, theirs)? => {
+                return Ok(SyncOutcome::UpToDate);
+            }
+            Some(ours) if !git_repo.graph_descendant_of(theirs, ours)? => ours,
+            _ => {
+                git_repo.reference(&self.config.storage_ref, theirs, true, "cargocrypt: fast-forward storage")?;
+                return Ok(SyncOutcome::FastForward);
+            }
+        };
+        
+        // A squashed history shares no base with the old one
+        let base = match git_repo.merge_base(ours, theirs) {
+            Ok(base) => team_ref::tree_files(git_repo, base)?,
+            Err(_) => Files::new(),
+        };
+        let (our_files, their_files) = (team_ref::tree_files(git_repo, ours)?, team_ref::tree_files(git_repo, theirs)?);
+        let mut merged = Files::new();
+        let mut resolved = Vec::new();
+        let paths: BTreeSet<&String> = our_files.keys().chain(their_files.keys()).collect();
+        for path in paths {
+            let (b, o, t) = (base.get(path), our_files.get(path), their_files.get(path));
+            let kept = if path.starts_with(OBJECTS_DIR) {
+                // Chunks are kept while any file may need them
+                o.or(t)
+            } else if o == t || t == b {
+                o
+            } else if o == b {
+                t
+            } else {
+                resolved.push(path.trim_start_matches(FILES_DIR).to_string());
+                match (o, t) {
+                    (Some(o), Some(t)) => Some(if self.timestamp(*t)? > self.timestamp(*o)? { t } else { o }),
+                    (o, t) => o.or(t),
+                }
+            };
+            if let Some(oid) = kept {
+                merged.insert(path.clone(), *oid);
+            }
+        }
+        drop_unreferenced_chunks(git_repo, &mut merged)?;
+        self.commit(&merged, &format!("Merge encrypted storage from {}", remote), &[ours, theirs])?;
+        
+        Ok(SyncOutcome::Merged { resolved })
+    }
+    
+    /// Push the storage ref to `remote`
+    ///
+    /// Fails if the remote has changes that were not pulled yet, unless
+    /// `force` (needed after [`EncryptedStorage::squash_history`]).
+    pub async fn push(&self, remote: &str, force: bool) -> GitResult<()> {
+        let git_repo = self.repo.inner();
+        let Some(local) = team_ref::tip(git_repo, &self.config.storage_ref)? else {
+            return Err(GitError::StorageFailed("Nothing to push; store a file first".to_string()));
+        };
+        team_ref::push_ref(
+            git_repo,
+            remote,
+            &self.config.storage_ref,
+            "run 'cargocrypt store pull' to merge the remote store first",
+            force,
+        )?;
+        git_repo.reference(&remote_ref(remote), local, true, "cargocrypt: push storage")?;
+        
+        Ok(())
+    }
+    
+    /// Storage timestamp in the metadata blob `oid`
+    fn timestamp(&self, oid: Oid) -> GitResult<u64> {
+        Ok(self.load_storage_ref("", oid)?.metadata.timestamp)
+    }
+    
     /// Export storage to external format
     pub async fn export(&self, export_path: &Path) -> GitResult<()> {
         let stored_files = self.list_stored_files().await?;
@@ -458,6 +551,32 @@ impl EncryptedStorage {
     }
 }
 
+/// Ref the storage of `remote` is fetched into
+pub fn remote_ref(remote: &str) -> String {
+    format!("refs/cargocrypt/remotes/{}/storage", remote)
+}
+
+/// Path of the chunk with SHA-256 `checksum` in the storage tree
+fn object_path(checksum: &str) -> String {
+    let (dir, rest) = checksum.split_at(2.min(checksum.len()));
+    format!("{}{}/{}", OBJECTS_DIR, dir, rest)
+}
+
+/// Remove the chunks of `files` no stored file lists, returning how many
+fn drop_unreferenced_chunks(repo: &Repository, files: &mut Files) -> GitResult<usize> {
+    let mut referenced = HashSet::new();
+    for (path, oid) in files.iter() {
+        if path.starts_with(FILES_DIR) {
+            let metadata: StorageMetadata = serde_json::from_slice(repo.find_blob(*oid)?.content())
+                .map_err(|e| GitError::StorageFailed(format!("Failed to deserialize metadata of {}: {}", path, e)))?;
+            referenced.extend(metadata.chunks.iter().map(|checksum| object_path(checksum)));
+        }
+    }
+    let before = files.len();
+    files.retain(|path, _| !path.starts_with(OBJECTS_DIR) || referenced.contains(path));
+    Ok(before - files.len())
+}
+
 /// Storage statistics
 #[derive(Debug, Clone)]
 pub struct StorageStats {
@@ -565,7 +684,7 @@ mod tests {
         
         // Create test secret
         let plaintext = PlaintextSecret::new("test-secret".as_bytes().to_vec());
-        let encrypted = crypto.encrypt(plaintext, "test_password", EncryptionOptions::default()).unwrap();
+        let encrypted = crypto.encrypt(plaintext, "test_password", EncryptionOptions::default()).await.unwrap();
         
         // Store in git
         let file_path = Path::new("test.secret");
@@ -579,6 +698,25 @@ mod tests {
         assert_eq!(decrypted.as_bytes(), b"test-secret");
     }
     
+    #[tokio::test]
+    async fn test_bare_repository_storage() {
+        let temp_dir = TempDir::new().unwrap();
+        git2::Repository::init_bare(temp_dir.path()).unwrap();
+        let repo = GitRepo::open(temp_dir.path()).unwrap();
+        assert!(repo.is_bare());
+        let crypto = CryptoEngine::new();
+        let storage = EncryptedStorage::new(&repo, &crypto).unwrap();
+        
+        storage.initialize().await.unwrap();
+        assert!(!temp_dir.path().join(".cargocrypt").exists());
+        
+        let encrypted = crypto.encrypt_data(b"server-side", "test_password").await.unwrap();
+        storage.store(Path::new("ci/token.secret"), &encrypted).await.unwrap();
+        let stored = storage.get(Path::new("ci/token.secret")).await.unwrap().unwrap();
+        let retrieved = storage.retrieve(&stored).await.unwrap();
+        assert_eq!(crypto.decrypt(&retrieved, "test_password").unwrap().as_bytes(), b"server-side");
+    }
+    
     #[tokio::test]
     async fn test_list_stored_files() {
         let temp_dir = TempDir::new().unwrap();
@@ -591,7 +729,7 @@ mod tests {
         // Store multiple files
         for i in 0..3 {
             let plaintext = PlaintextSecret::new(format!("secret-{}", i).as_bytes().to_vec());
-            let encrypted = crypto.encrypt(plaintext, "test_password", EncryptionOptions::default()).unwrap();
+            let encrypted = crypto.encrypt(plaintext, "test_password", EncryptionOptions::default()).await.unwrap();
             let file_name = format!("test{}.secret", i);
             let file_path = Path::new(&file_name);
             storage.store(file_path, &encrypted).await.unwrap();
@@ -613,7 +751,7 @@ mod tests {
         
         // Store a test file
         let plaintext = PlaintextSecret::new("test-secret".as_bytes().to_vec());
-        let encrypted = crypto.encrypt(plaintext, "test_password", EncryptionOptions::default()).unwrap();
+        let encrypted = crypto.encrypt(plaintext, "test_password", EncryptionOptions::default()).await.unwrap();
         let file_path = Path::new("test.secret");
         storage.store(file_path, &encrypted).await.unwrap();
         
@@ -622,4 +760,74 @@ mod tests {
         assert_eq!(stats.total_files, 1);
         assert!(stats.total_size > 0);
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_chunks_are_shared_and_collected() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = GitRepo::init(temp_dir.path()).unwrap();
+        let crypto = CryptoEngine::new();
+        let config = StorageConfig { compress: false, max_blob_size: 64, ..StorageConfig::default() };
+        let storage = EncryptedStorage::with_config(&repo, &crypto, config).unwrap();
+        storage.initialize().await.unwrap();
+        
+        let encrypted = crypto.encrypt_data(&[7u8; 1000], "test_password").await.unwrap();
+        let first = storage.store(Path::new("assets/model.bin"), &encrypted).await.unwrap();
+        let second = storage.store(Path::new("copy.bin"), &encrypted).await.unwrap();
+        assert!(first.metadata.chunks.len() > 1);
+        assert_eq!(first.metadata.chunks, second.metadata.chunks);
+        assert_eq!(first.path, "assets_model.bin");
+        
+        let found = storage.get(Path::new("assets/model.bin")).await.unwrap().unwrap();
+        let retrieved = storage.retrieve(&found).await.unwrap();
+        assert_eq!(crypto.decrypt_data(&retrieved, "test_password").unwrap(), vec![7u8; 1000]);
+        
+        // Chunks stay while another file still refers to them
+        storage.delete(&first).await.unwrap();
+        assert!(storage.get(Path::new("assets/model.bin")).await.unwrap().is_none());
+        assert!(storage.optimize().await.unwrap().operations_performed.is_empty());
+        storage.delete(&second).await.unwrap();
+        let result = storage.optimize().await.unwrap();
+        assert_eq!(result.operations_performed.len(), 1);
+        assert!(result.size_before > 0);
+        assert_eq!(result.size_after, 0);
+        
+        assert!(storage.squash_history().await.unwrap());
+        assert!(!storage.squash_history().await.unwrap());
+        assert!(storage.list_stored_files().await.unwrap().is_empty());
+    }
+    
+    #[tokio::test]
+    async fn test_push_and_pull() {
+        let temp_dir = TempDir::new().unwrap();
+        let remote_path = temp_dir.path().join("remote.git");
+        git2::Repository::init_bare(&remote_path).unwrap();
+        let crypto = CryptoEngine::new();
+        let clone = |name: &str| {
+            let repo = GitRepo::init(temp_dir.path().join(name)).unwrap();
+            repo.inner().remote("origin", remote_path.to_str().unwrap()).unwrap();
+            EncryptedStorage::new(&repo, &crypto).unwrap()
+        };
+        let (alice, bob) = (clone("alice"), clone("bob"));
+        
+        assert_eq!(bob.pull("origin").await.unwrap(), SyncOutcome::NoRemoteState);
+        let encrypted = crypto.encrypt_data(b"large secret", "test_password").await.unwrap();
+        alice.store(Path::new("shared.bin"), &encrypted).await.unwrap();
+        alice.push("origin", false).await.unwrap();
+        assert_eq!(bob.pull("origin").await.unwrap(), SyncOutcome::FastForward);
+        assert_eq!(bob.pull("origin").await.unwrap(), SyncOutcome::UpToDate);
+        
+        // Both sides store files; pulling merges them
+        let other = crypto.encrypt_data(b"other secret", "test_password").await.unwrap();
+        bob.store(Path::new("bob.bin"), &other).await.unwrap();
+        alice.store(Path::new("alice.bin"), &other).await.unwrap();
+        alice.push("origin", false).await.unwrap();
+        assert!(bob.push("origin", false).await.is_err());
+        assert!(matches!(bob.pull("origin").await.unwrap(), SyncOutcome::Merged { .. }));
+        assert_eq!(bob.list_stored_files().await.unwrap().len(), 3);
+        bob.push("origin", false).await.unwrap();
+        
+        let shared = bob.get(Path::new("shared.bin")).await.unwrap().unwrap();
+        let retrieved = bob.retrieve(&shared).await.unwrap();
+        assert_eq!(crypto.decrypt_data(&retrieved, "test_password").unwrap(), b"large secret");
+    }
+}
@@ -0,0 +1,199 @@
+//! `.gitattributes` encryption patterns learned from scan results
+//!
+//! `cargocrypt git suggest-patterns` scans the working tree and proposes
+//! patterns covering the files where high-confidence secrets were found and
+//! that no CargoCrypt pattern encrypts yet:
+//! - key material (`*.pem`, `*.key`, `*.p12`, ...) gets a pattern for its
+//!   extension, so new keys are covered too,
+//! - files in a directory named like a secrets store (`secrets/`, `keys/`,
+//!   `credentials/`, ...) get a pattern for the directory,
+//! - other files get a pattern for their exact path.
+//!
+//! Secrets in source code and documentation are reported separately:
+//! encrypting `src/main.rs` would break the build, so those secrets have to
+//! move out of the file instead. Files git ignores are never committed and
+//! need no pattern.
+
+use super::attributes::encryption_patterns;
+use super::GitResult;
+use crate::detection::Finding;
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+
+/// Extensions of key material, suggested as `*.<ext>`
+const KEY_EXTENSIONS: &[&str] = &["pem", "key", "p12", "pfx", "jks", "keystore", "ppk"];
+
+/// Directory names that hold secrets, suggested as `<dir>/*`
+const SECRET_DIRS: &[&str] = &["secrets", "secret", "credentials", "creds", "keys", "private", "certs"];
+
+/// Extensions of source code and documentation, which are not encrypted
+const SOURCE_EXTENSIONS: &[&str] = &[
+    "rs", "py", "js", "jsx", "ts", "tsx", "mjs", "cjs", "go", "java", "kt", "kts", "scala", "rb", "php", "c", "h", "cc",
+    "cpp", "hpp", "cs", "swift", "m", "sh", "bash", "zsh", "ps1", "md", "rst", "adoc",
+];
+
+/// A proposed encryption pattern
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatternSuggestion {
+    /// The `.gitattributes` pattern
+    pub pattern: String,
+    /// Files with high-confidence findings the pattern covers, relative to the working tree
+    pub files: Vec<PathBuf>,
+    /// Number of high-confidence findings in those files
+    pub findings: usize,
+}
+
+/// What a scan suggests for `.gitattributes`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PatternSuggestions {
+    pub patterns: Vec<PatternSuggestion>,
+    /// Source and documentation files with high-confidence findings
+    pub source_files: Vec<PathBuf>,
+}
+
+impl PatternSuggestions {
+    /// Whether there is nothing to suggest
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty() && self.source_files.is_empty()
+    }
+
+    /// Number of files the suggested patterns cover
+    pub fn file_count(&self) -> usize {
+        self.patterns.iter().map(|suggestion| suggestion.files.len()).sum()
+    }
+}
+
+/// Patterns covering the files of `findings` that the `.gitattributes` in `workdir` leaves unencrypted
+pub fn suggest_patterns(workdir: &Path, findings: &[Finding]) -> GitResult<PatternSuggestions> {
+    let existing = encryption_patterns(workdir)?;
+    let repo = git2::Repository::open(workdir).ok();
+
+    // Findings per file, relative to the working tree
+    let mut files: BTreeMap<PathBuf, usize> = BTreeMap::new();
+    for finding in findings {
+        if finding.is_ignored || finding.is_suppressed() || !finding.is_high_confidence() {
+            continue;
+        }
+        let Some(path) = relative_path(workdir, &finding.file_path) else {
+            continue;
+        };
+        if existing.iter().any(|pattern| pattern.matches_path(&path)) {
+            continue;
+        }
+        if repo.as_ref().is_some_and(|repo| repo.is_path_ignored(&path).unwrap_or(false)) {
+            continue;
+        }
+        *files.entry(path).or_default() += 1;
+    }
+
+    let mut suggestions = PatternSuggestions::default();
+    let mut patterns: BTreeMap<String, PatternSuggestion> = BTreeMap::new();
+    for (path, count) in files {
+        let Some(pattern) = pattern_for(&path) else {
+            suggestions.source_files.push(path);
+            continue;
+        };
+        let suggestion = patterns.entry(pattern.clone()).or_insert_with(|| PatternSuggestion {
+            pattern,
+            files: Vec::new(),
+            findings: 0,
+        });
+        suggestion.files.push(path);
+        suggestion.findings += count;
+    }
+
+    // The most findings first
+    suggestions.patterns = patterns.into_values().collect();
+    suggestions.patterns.sort_by(|a, b| b.findings.cmp(&a.findings).then_with(|| a.pattern.cmp(&b.pattern)));
+    Ok(suggestions)
+}
+
+/// `file` relative to `workdir`, or `None` outside of it
+///
+/// Scans report paths as given on the command line, e.g. `./config/app.yml`.
+fn relative_path(workdir: &Path, file: &Path) -> Option<PathBuf> {
+    let absolute = match file.canonicalize() {
+        Ok(canonical) => canonical,
+        Err(_) if file.is_relative() => std::env::current_dir().ok()?.join(file),
+        Err(_) => file.to_path_buf(),
+    };
+    let canonical_workdir = workdir.canonicalize().unwrap_or_else(|_| workdir.to_path_buf());
+    absolute
+        .strip_prefix(&canonical_workdir)
+        .or_else(|_| absolute.strip_prefix(workdir))
+        .ok()
+        .map(|relative| relative.components().filter(|c| !matches!(c, Component::CurDir)).collect())
+}
+
+/// The pattern to suggest for `path`, or `None` for source code and documentation
+fn pattern_for(path: &Path) -> Option<String> {
+    if let Some(extension) = path.extension().and_then(|ext| ext.to_str()) {
+        // Patterns are case-sensitive, so `*.PEM` stays upper case
+        let lower = extension.to_ascii_lowercase();
+        if KEY_EXTENSIONS.contains(&lower.as_str()) {
+            return Some(format!("*.{}", extension));
+        }
+        if SOURCE_EXTENSIONS.contains(&lower.as_str()) {
+            return None;
+        }
+    }
+
+    // The outermost secrets directory, so nested files share one pattern
+    let mut dir = PathBuf::new();
+    for component in path.parent().into_iter().flat_map(Path::components) {
+        dir.push(component);
+        let name = component.as_os_str().to_string_lossy().to_ascii_lowercase();
+        if SECRET_DIRS.contains(&name.as_str()) {
+            return Some(format!("{}/*", slash_path(&dir)));
+        }
+    }
+    Some(slash_path(path))
+}
+
+/// `path` with `/` separators, as `.gitattributes` writes them
+fn slash_path(path: &Path) -> String {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detection::findings::test_finding;
+    use tempfile::TempDir;
+
+    fn finding(workdir: &Path, path: &str, confidence: f64) -> Finding {
+        test_finding(workdir.join(path), "api_key", "sk_live_0123456789abcdef", 1, confidence)
+    }
+
+    #[test]
+    fn test_suggest_patterns() {
+        let temp_dir = TempDir::new().unwrap();
+        let workdir = temp_dir.path();
+        std::fs::write(workdir.join(".gitattributes"), "*.env filter=cargocrypt-encrypt diff=cargocrypt\n").unwrap();
+
+        let findings = vec![
+            finding(workdir, "deploy/tls/server.pem", 0.95),
+            finding(workdir, "deploy/tls/client.pem", 0.9),
+            finding(workdir, "config/secrets/db.yml", 0.9),
+            finding(workdir, "config/secrets/nested/api.json", 0.8),
+            finding(workdir, "config/app.yml", 0.9),
+            finding(workdir, "config/app.yml", 0.85),
+            finding(workdir, "src/main.rs", 0.9),
+            finding(workdir, "prod.env", 0.99),
+            finding(workdir, "notes.txt", 0.4),
+        ];
+        let suggestions = suggest_patterns(workdir, &findings).unwrap();
+
+        let patterns: Vec<(&str, usize, usize)> = suggestions
+            .patterns
+            .iter()
+            .map(|suggestion| (suggestion.pattern.as_str(), suggestion.files.len(), suggestion.findings))
+            .collect();
+        assert_eq!(patterns, [("*.pem", 2, 2), ("config/app.yml", 1, 2), ("config/secrets/*", 2, 2)]);
+        assert_eq!(suggestions.source_files, [PathBuf::from("src/main.rs")]);
+        assert_eq!(suggestions.file_count(), 5);
+    }
+}
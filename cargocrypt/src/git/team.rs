@@ -3,16 +3,46 @@
 //! This module provides secure team key sharing via Git repositories,
 //! enabling multiple team members to access encrypted files while
 //! maintaining security and auditability.
+//!
+//! Each member is listed with an X25519 public key or an OpenPGP
+//! fingerprint, and every shared key is stored wrapped for each active
+//! member's key (see [`crate::crypto::recipient`] and
+//! [`crate::crypto::openpgp`]), so only listed members can unwrap it with
+//! their secret identity ([`TeamIdentity`]). Sharing existing keys with a new member takes the
+//! identity of a member who already has them. Removing a member rotates the
+//! keys they held, so what is encrypted from then on is out of their reach.
+//!
+//! Several identities can be on hand at once ([`TeamIdentity::discover`]).
+//! Each wrapped copy is unwrapped only with the identity whose public key or
+//! GPG fingerprint matches the member it is listed for, so an identity of
+//! another team or an outdated one is passed over rather than failing.
+//!
+//! Team state is committed to the team ref rather than the working branch
+//! and shared by fetching and pushing that ref (see [`super::team_ref`]).
+//! With `require_signatures`, every change must be signed by an Owner or
+//! Admin ([`TeamSigner`]), and the signatures of the whole history are
+//! checked before the team state is loaded or a remote's state is taken in.
+//! Each operation is recorded as a signed git note on the commit that made
+//! it (see [`super::team_audit`]). Owners can escrow the shared keys in a
+//! bundle that a threshold of them can open to recover access (see
+//! [`super::team_escrow`]).
 
+use super::team_audit::{self, AuditRecord, AuditVerification};
+use super::team_escrow::{self, EscrowShare, EscrowedKey};
+use super::credentials::{git_config_secret, git_config_values};
+use super::team_ref::{self, SyncOutcome, TeamSigner};
 use super::{GitRepo, GitError, GitResult};
-use crate::crypto::{CryptoEngine, DerivedKey, EncryptedSecret, PlaintextSecret};
+use crate::plan::{Change, Plan};
+use crate::crypto::{openpgp, recipient, CryptoEngine, DerivedKey, EncryptedSecret, PlaintextSecret, RecipientKeyPair};
 use git2::Signature;
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 use tokio::fs;
 use serde::{Deserialize, Serialize};
 use ring::rand::SystemRandom;
 use base64ct::{Base64, Encoding};
+use zeroize::Zeroize;
 
 /// Configuration for team key sharing
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -27,6 +57,20 @@ pub struct KeyShareConfig {
     pub rotation_interval: u64,
     /// Backup key locations
     pub backup_locations: Vec<String>,
+    /// Git notes ref of the audit trail
+    #[serde(default = "default_audit_ref")]
+    pub audit_ref: String,
+    /// Git ref of the recovery escrow bundle
+    #[serde(default = "default_escrow_ref")]
+    pub escrow_ref: String,
+}
+
+fn default_audit_ref() -> String {
+    team_audit::AUDIT_NOTES_REF.to_string()
+}
+
+fn default_escrow_ref() -> String {
+    team_escrow::ESCROW_REF.to_string()
 }
 
 impl Default for KeyShareConfig {
@@ -37,6 +81,8 @@ impl Default for KeyShareConfig {
             max_members: 20,
             rotation_interval: 90, // 3 months
             backup_locations: Vec::new(),
+            audit_ref: default_audit_ref(),
+            escrow_ref: default_escrow_ref(),
         }
     }
 }
@@ -46,8 +92,11 @@ impl Default for KeyShareConfig {
 pub struct TeamMember {
     /// Member identifier (email or username)
     pub id: String,
-    /// Member's public key for encryption
+    /// Member's X25519 public key (hex) that shared keys are wrapped for
     pub public_key: String,
+    /// Fingerprint of the member's OpenPGP key, used instead of `public_key` when set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gpg_fingerprint: Option<String>,
     /// Member's signing key (Ed25519 public key)
     pub signing_key: String,
     /// Member's role
@@ -66,6 +115,7 @@ impl TeamMember {
         Self {
             id,
             public_key,
+            gpg_fingerprint: None,
             signing_key,
             role,
             added_at: std::time::SystemTime::now()
@@ -77,6 +127,21 @@ impl TeamMember {
         }
     }
     
+    /// Wrap shared keys for this member's OpenPGP key instead of an X25519 key
+    pub fn with_gpg_fingerprint(mut self, fingerprint: &str) -> Self {
+        self.gpg_fingerprint = Some(fingerprint.to_uppercase());
+        self
+    }
+
+    /// Wrap `key` for this member's OpenPGP or X25519 key
+    pub fn wrap_key(&self, key: &[u8]) -> crate::crypto::CryptoResult<String> {
+        match &self.gpg_fingerprint {
+            Some(fingerprint) => openpgp::wrap_key(key, fingerprint),
+            None => recipient::parse_public_key(&self.public_key)
+                .and_then(|public_key| recipient::wrap_key_base64(key, &public_key)),
+        }
+    }
+
     /// Check if member can perform an operation
     pub fn can_perform(&self, operation: &TeamOperation) -> bool {
         if !self.active {
@@ -107,7 +172,7 @@ impl TeamMember {
 }
 
 /// Team member roles
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, clap::ValueEnum)]
 pub enum TeamRole {
     Owner,
     Admin,
@@ -115,6 +180,89 @@ pub enum TeamRole {
     ReadOnly,
 }
 
+/// The secret half a member unwraps shared keys with
+#[derive(Debug, Clone)]
+pub enum TeamIdentity {
+    /// An X25519 identity
+    Recipient(RecipientKeyPair),
+    /// The secret keys of the local GPG keyring, used through gpg-agent
+    Gpg,
+}
+
+impl TeamIdentity {
+    /// Whether this identity holds the secret key of `member`
+    pub fn belongs_to(&self, member: &TeamMember) -> bool {
+        match (self, &member.gpg_fingerprint) {
+            (TeamIdentity::Recipient(identity), None) => {
+                recipient::parse_public_key(&member.public_key).ok() == Some(identity.public_key())
+            }
+            (TeamIdentity::Gpg, Some(fingerprint)) => openpgp::secret_key_fingerprints()
+                .iter()
+                .any(|own| own.eq_ignore_ascii_case(fingerprint)),
+            _ => false,
+        }
+    }
+
+    /// Unwrap a key wrapped for this identity
+    pub fn unwrap_key(&self, wrapped: &str) -> crate::crypto::CryptoResult<Vec<u8>> {
+        match self {
+            TeamIdentity::Recipient(identity) if !openpgp::is_wrapped(wrapped) => identity.unwrap_key_base64(wrapped),
+            TeamIdentity::Gpg if openpgp::is_wrapped(wrapped) => openpgp::unwrap_key(wrapped),
+            _ => Err(crate::crypto::CryptoError::decryption("Key was wrapped for another kind of identity")),
+        }
+    }
+}
+
+impl TeamIdentity {
+    /// The identities at hand in the repository at `workdir`
+    ///
+    /// `CARGOCRYPT_TEAM_IDENTITY` names the only identity to use when set.
+    /// Otherwise every `git config cargocrypt.team.identity` (the key may be
+    /// given more than once) and `.cargocrypt/identity` are used, then the GPG
+    /// keyring with `git config cargocrypt.team.gpg true`. Identity files
+    /// that cannot be read are skipped; finding none at all is an error.
+    pub fn discover(workdir: &Path) -> crate::error::CryptoResult<Vec<TeamIdentity>> {
+        if let Ok(path) = std::env::var("CARGOCRYPT_TEAM_IDENTITY") {
+            return Ok(vec![RecipientKeyPair::load(path)?.into()]);
+        }
+
+        let mut paths: Vec<PathBuf> = git_config_values(workdir, "cargocrypt.team.identity")
+            .into_iter()
+            .map(|path| workdir.join(path))
+            .collect();
+        paths.push(workdir.join(".cargocrypt").join("identity"));
+        let mut identities: Vec<TeamIdentity> = Vec::new();
+        for path in paths.iter().filter(|path| path.exists()) {
+            match RecipientKeyPair::load(path) {
+                Ok(identity) => {
+                    let known = identities.iter().any(|other| matches!(other, TeamIdentity::Recipient(other) if other.public_key() == identity.public_key()));
+                    if !known {
+                        identities.push(identity.into());
+                    }
+                }
+                Err(e) => tracing::warn!("Skipping team identity {}: {}", path.display(), e),
+            }
+        }
+        if git_config_secret(workdir, "cargocrypt.team.gpg").is_some_and(|gpg| gpg.as_str() == "true") {
+            identities.push(TeamIdentity::Gpg);
+        }
+
+        if identities.is_empty() {
+            return Err(crate::error::CargoCryptError::Config {
+                message: "No team identity to unwrap team keys with".to_string(),
+                suggestion: Some("Set CARGOCRYPT_TEAM_IDENTITY or 'git config cargocrypt.team.identity <path>'".to_string()),
+            });
+        }
+        Ok(identities)
+    }
+}
+
+impl From<RecipientKeyPair> for TeamIdentity {
+    fn from(identity: RecipientKeyPair) -> Self {
+        TeamIdentity::Recipient(identity)
+    }
+}
+
 /// Team operations that can be performed
 #[derive(Debug, Clone)]
 pub enum TeamOperation {
@@ -131,7 +279,7 @@ pub enum TeamOperation {
 pub struct SharedKey {
     /// Key identifier
     pub id: String,
-    /// Encrypted key material (encrypted for each team member)
+    /// Key material wrapped for each team member's public key (base64)
     pub encrypted_for_members: HashMap<String, String>,
     /// Key metadata
     pub metadata: KeyMetadata,
@@ -222,6 +370,37 @@ pub struct OffboardingSummary {
     pub keys_revoked: usize,
     /// Role of the removed member
     pub role: TeamRole,
+    /// Purposes of the rotated keys, whose files still need re-encrypting
+    #[serde(default)]
+    pub rotated_purposes: Vec<String>,
+}
+
+/// Outcome of sealing the shared keys into an escrow bundle
+#[derive(Debug, Clone)]
+pub struct EscrowSummary {
+    /// Commit of the bundle on the escrow ref
+    pub commit: git2::Oid,
+    /// Owners holding a share
+    pub owners: Vec<String>,
+    /// Number of shares needed to recover
+    pub threshold: u8,
+    /// Number of keys escrowed
+    pub keys: usize,
+    /// Keys the sealing identity could not unwrap, left out of the bundle
+    pub skipped: Vec<String>,
+}
+
+/// Outcome of recovering the shared keys from escrow
+#[derive(Debug, Clone)]
+pub struct RecoveryResult {
+    /// Owner who sealed the bundle
+    pub sealed_by: String,
+    /// Number of keys wrapped for members who lacked them
+    pub keys_rewrapped: usize,
+    /// Members who were given keys
+    pub members: Vec<String>,
+    /// Escrowed keys that are no longer in the team state
+    pub missing: Vec<String>,
 }
 
 /// Access token data structure
@@ -276,32 +455,60 @@ pub struct TeamKeySharing {
     crypto: CryptoEngine,
     config: KeyShareConfig,
     team_dir: PathBuf,
+    signer: Option<TeamSigner>,
+    /// Audit entries of the operation in progress, recorded on its commit
+    pending_audit: Mutex<Vec<AuditEntry>>,
+}
+
+/// Git config key pinning the first commit of the trusted team history
+const TEAM_ROOT_CONFIG: &str = "cargocrypt.teamRoot";
+
+/// Distinct purposes of `keys`, sorted
+fn key_purposes(keys: &[SharedKey]) -> Vec<String> {
+    let mut purposes: Vec<String> = keys.iter().map(|key| key.metadata.purpose.clone()).collect();
+    purposes.sort();
+    purposes.dedup();
+    purposes
+}
+
+/// Local copy of the team state, checked out from the team ref
+fn team_dir(repo: &GitRepo) -> PathBuf {
+    repo.workdir().join(".cargocrypt").join("team")
 }
 
 impl TeamKeySharing {
     /// Create a new team key sharing manager
+    ///
+    /// `.cargocrypt/team` is brought up to date with the team ref.
     pub fn new(repo: &GitRepo, crypto: &CryptoEngine) -> GitResult<Self> {
-        let config = KeyShareConfig::default();
-        let team_dir = repo.workdir().join(".cargocrypt").join("team");
-        
-        Ok(Self {
-            repo: repo.clone(),
-            crypto: crypto.clone(),
-            config,
-            team_dir,
-        })
+        Self::with_config(repo, crypto, KeyShareConfig::default())
     }
     
     /// Create with custom configuration
     pub fn with_config(repo: &GitRepo, crypto: &CryptoEngine, config: KeyShareConfig) -> GitResult<Self> {
-        let team_dir = repo.workdir().join(".cargocrypt").join("team");
+        let team_dir = team_dir(repo);
         
-        Ok(Self {
+        let team_sharing = Self {
             repo: repo.clone(),
             crypto: crypto.clone(),
             config,
             team_dir,
-        })
+            signer: None,
+            pending_audit: Mutex::new(Vec::new()),
+        };
+        team_sharing.checkout_team_ref()?;
+        Ok(team_sharing)
+    }
+    
+    /// Sign team changes with `signer`, which must belong to an Owner or Admin
+    pub fn with_signer(mut self, signer: impl Into<TeamSigner>) -> Self {
+        self.signer = Some(signer.into());
+        self
+    }
+    
+    /// Whether team key sharing was set up in this repository
+    pub fn is_initialized(&self) -> bool {
+        self.team_dir.join("config.toml").exists()
     }
     
     /// Initialize team key sharing
@@ -324,15 +531,50 @@ impl TeamKeySharing {
         fs::write(&team_config_path, config_content).await
             .map_err(|e| GitError::TeamSharingFailed(format!("Failed to write team config: {}", e)))?;
         
-        // Initialize git ref for team data
-        self.init_team_ref().await?;
+        // Record the team state on the team ref
+        self.commit_team_changes("Initialize team key sharing").await?;
         
         Ok(())
     }
     
+    /// Record what [`Self::initialize`] would change in `plan`
+    ///
+    /// Reads the local copy of the team state instead of checking out the
+    /// team ref, so nothing is written.
+    pub fn plan_initialize(repo: &GitRepo, plan: &mut Plan) -> GitResult<()> {
+        let config = KeyShareConfig::default();
+        let team_config_path = team_dir(repo).join("config.toml");
+        let config_content = toml::to_string(&config)
+            .map_err(|e| GitError::TeamSharingFailed(format!("Failed to serialize team config: {}", e)))?;
+        let unchanged = std::fs::read(&team_config_path).is_ok_and(|current| current == config_content.as_bytes());
+        plan.write_content(team_config_path, config_content.as_bytes(), None);
+        match team_ref::tip(repo.inner(), &config.team_ref)? {
+            None => plan.push(Change::Create, &config.team_ref, Some("git ref")),
+            Some(_) if !unchanged => plan.push(Change::Modify, &config.team_ref, Some("git ref")),
+            Some(_) => {}
+        }
+        Ok(())
+    }
+    
     /// Add a team member
-    pub async fn add_member(&self, member: TeamMember) -> GitResult<()> {
+    ///
+    /// With the identity of a member who holds the existing shared keys,
+    /// those keys are wrapped for the new member too; without it the new
+    /// member only gets keys generated from now on.
+    pub async fn add_member(&self, member: TeamMember, granted_by: Option<&TeamIdentity>) -> GitResult<()> {
         // Validate member
+        if member.gpg_fingerprint.is_none() {
+            recipient::parse_public_key(&member.public_key)
+                .map_err(|e| GitError::TeamSharingFailed(format!("Invalid public key for {}: {}", member.id, e)))?;
+        }
+        let can_sign = member.gpg_fingerprint.is_some()
+            || hex::decode(&member.signing_key).is_ok_and(|key| key.len() == 32);
+        if self.config.require_signatures && matches!(member.role, TeamRole::Owner | TeamRole::Admin) && !can_sign {
+            return Err(GitError::TeamSharingFailed(format!(
+                "{} needs an Ed25519 signing key (hex) or a GPG key to sign team changes as {:?}",
+                member.id, member.role
+            )));
+        }
         if self.get_members().await?.len() >= self.config.max_members {
             return Err(GitError::TeamSharingFailed("Maximum team size reached".to_string()));
         }
@@ -350,8 +592,16 @@ impl TeamKeySharing {
         fs::write(&member_path, member_json).await
             .map_err(|e| GitError::TeamSharingFailed(format!("Failed to write member file: {}", e)))?;
         
-        // Re-encrypt existing keys for the new member
-        self.reencrypt_keys_for_new_member(&member).await?;
+        // Wrap existing keys for the new member
+        if let Some(identity) = granted_by {
+            self.reencrypt_keys_for_new_member(&member, std::slice::from_ref(identity)).await?;
+        }
+        
+        self.log_team_operation(
+            "member_addition",
+            &self.actor()?,
+            &format!("Added member: {} with role: {:?}", member.id, member.role)
+        ).await?;
         
         // Commit changes to git
         self.commit_team_changes(&format!("Add team member: {}", member.id)).await?;
@@ -359,7 +609,7 @@ impl TeamKeySharing {
         Ok(())
     }
     
-    /// Remove a team member
+    /// Remove a team member and rotate the shared keys they held
     pub async fn remove_member(&self, member_id: &str) -> GitResult<()> {
         let member_path = self.team_dir.join("members").join(format!("{}.json", member_id));
         
@@ -371,9 +621,11 @@ impl TeamKeySharing {
         fs::remove_file(&member_path).await
             .map_err(|e| GitError::TeamSharingFailed(format!("Failed to remove member file: {}", e)))?;
         
-        // Re-encrypt keys without this member
+        // Replace the keys the member could unwrap
         self.reencrypt_keys_without_member(member_id).await?;
         
+        self.log_team_operation("member_removal", &self.actor()?, &format!("Removed member: {}", member_id)).await?;
+        
         // Commit changes to git
         self.commit_team_changes(&format!("Remove team member: {}", member_id)).await?;
         
@@ -472,11 +724,13 @@ impl TeamKeySharing {
         // Log audit trail
         self.log_team_operation("key_generation", created_by, &format!("Generated key {} for purpose: {}", shared_key.id, purpose)).await?;
         
+        self.commit_team_changes(&format!("Generate key: {}", shared_key.id)).await?;
+        
         Ok(shared_key)
     }
     
-    /// Get a shared key for a specific member
-    pub async fn get_shared_key(&self, key_id: &str, member_id: &str) -> GitResult<DerivedKey> {
+    /// Unwrap a shared key with a member's secret identity
+    pub async fn get_shared_key(&self, key_id: &str, member_id: &str, identity: &TeamIdentity) -> GitResult<DerivedKey> {
         let shared_key = self.load_shared_key(key_id).await?;
         
         // Check if member has access to this key
@@ -485,7 +739,13 @@ impl TeamKeySharing {
         
         // Decrypt the key for this member
         let member = self.get_member(member_id).await?;
-        let decrypted_key = self.decrypt_key_for_member(encrypted_key, &member).await?;
+        if !member.active {
+            return Err(GitError::TeamSharingFailed(format!("Member {} is deactivated", member_id)));
+        }
+        if !identity.belongs_to(&member) {
+            return Err(GitError::TeamSharingFailed(format!("Identity does not belong to member {}", member_id)));
+        }
+        let decrypted_key = self.decrypt_key_for_member(encrypted_key, identity).await?;
         
         Ok(decrypted_key)
     }
@@ -580,10 +840,90 @@ impl TeamKeySharing {
         Ok(shared_key)
     }
     
+    /// Unwrap every key of `purpose` wrapped for one of `identities`, current keys first
+    ///
+    /// Archived keys follow, newest first, so files encrypted before a
+    /// rotation can still be decrypted until they are re-encrypted.
+    pub async fn keys_for_purpose(&self, purpose: &str, identities: &[TeamIdentity]) -> GitResult<Vec<DerivedKey>> {
+        self.unwrap_keys(Some(purpose), identities).await
+    }
+    
+    /// Unwrap every key wrapped for one of `identities`, whatever its purpose
+    ///
+    /// Ordered as [`Self::keys_for_purpose`].
+    pub async fn all_keys(&self, identities: &[TeamIdentity]) -> GitResult<Vec<DerivedKey>> {
+        self.unwrap_keys(None, identities).await
+    }
+    
+    async fn unwrap_keys(&self, purpose: Option<&str>, identities: &[TeamIdentity]) -> GitResult<Vec<DerivedKey>> {
+        let newest_first = |mut keys: Vec<SharedKey>| {
+            keys.retain(|key| purpose.is_none_or(|purpose| key.metadata.purpose == purpose));
+            keys.sort_by_key(|key| std::cmp::Reverse(key.metadata.created_at));
+            keys
+        };
+        let mut shared_keys = newest_first(self.list_shared_keys().await?);
+        shared_keys.extend(newest_first(self.list_archived_keys().await?));
+        
+        let owners = self.identity_owners(identities).await?;
+        let mut keys = Vec::new();
+        for shared_key in shared_keys {
+            keys.extend(self.unwrap_shared_key(&shared_key, &owners).await);
+        }
+        Ok(keys)
+    }
+    
+    /// The identity in `identities` holding the secret key of each member, by member ID
+    async fn identity_owners<'i>(&self, identities: &'i [TeamIdentity]) -> GitResult<HashMap<String, &'i TeamIdentity>> {
+        // Listing the GPG keyring once spares a gpg run per member
+        let gpg_fingerprints = if identities.iter().any(|identity| matches!(identity, TeamIdentity::Gpg)) {
+            openpgp::secret_key_fingerprints()
+        } else {
+            Vec::new()
+        };
+        let mut owners = HashMap::new();
+        for member in self.get_members().await? {
+            let owner = identities.iter().find(|identity| match (identity, &member.gpg_fingerprint) {
+                (TeamIdentity::Gpg, Some(fingerprint)) => gpg_fingerprints.iter().any(|own| own.eq_ignore_ascii_case(fingerprint)),
+                (identity, _) => identity.belongs_to(&member),
+            });
+            if let Some(owner) = owner {
+                owners.insert(member.id, owner);
+            }
+        }
+        Ok(owners)
+    }
+    
+    /// Unwrap the copy of `shared_key` listed for a member one of `owners` holds
+    ///
+    /// X25519 identities are tried before GPG, which may ask for a passphrase;
+    /// a copy that does not unwrap moves on to the next member.
+    async fn unwrap_shared_key(&self, shared_key: &SharedKey, owners: &HashMap<String, &TeamIdentity>) -> Option<DerivedKey> {
+        let mut copies: Vec<(&TeamIdentity, &String, &String)> = shared_key.encrypted_for_members
+            .iter()
+            .filter_map(|(member_id, encrypted_key)| owners.get(member_id).map(|identity| (*identity, member_id, encrypted_key)))
+            .collect();
+        copies.sort_by_key(|(identity, member_id, _)| (matches!(identity, TeamIdentity::Gpg), member_id.as_str()));
+        for (identity, member_id, encrypted_key) in copies {
+            match self.decrypt_key_for_member(encrypted_key, identity).await {
+                Ok(key) => return Some(key),
+                Err(e) => tracing::debug!("Copy of key {} for {} did not unwrap: {}", shared_key.id, member_id, e),
+            }
+        }
+        None
+    }
+    
     /// List all shared keys
     async fn list_shared_keys(&self) -> GitResult<Vec<SharedKey>> {
+        self.read_keys_dir(self.team_dir.join("keys")).await
+    }
+    
+    /// List the keys archived by rotations
+    async fn list_archived_keys(&self) -> GitResult<Vec<SharedKey>> {
+        self.read_keys_dir(self.team_dir.join("keys").join("archived")).await
+    }
+    
+    async fn read_keys_dir(&self, keys_dir: PathBuf) -> GitResult<Vec<SharedKey>> {
         let mut keys = Vec::new();
-        let keys_dir = self.team_dir.join("keys");
         
         if !keys_dir.exists() {
             return Ok(keys);
@@ -617,94 +957,80 @@ impl TeamKeySharing {
         hex::encode(random_bytes)
     }
     
-    /// Encrypt a key for a specific team member
-    async fn encrypt_key_for_member(&self, key: &DerivedKey, _member: &TeamMember) -> GitResult<String> {
-        // For now, use a simple encryption scheme
-        // In a real implementation, this would use the member's public key
-        let key_hex = key.to_hex();
-        let plaintext = PlaintextSecret::from_string(key_hex);
-        
-        let encrypted = self.crypto.encrypt_data(plaintext.as_bytes(), "team_key_password").await
-            .map_err(|e| GitError::TeamSharingFailed(format!("Failed to encrypt key: {}", e)))?;
-        
-        // Serialize to base64
-        let serialized = bincode::serialize(&encrypted)
-            .map_err(|e| GitError::TeamSharingFailed(format!("Failed to serialize encrypted key: {}", e)))?;
-        
-        Ok(Base64::encode_string(&serialized))
+    /// Wrap a key for a specific team member's public key
+    async fn encrypt_key_for_member(&self, key: &DerivedKey, member: &TeamMember) -> GitResult<String> {
+        let mut key_hex = key.to_hex();
+        let wrapped = member.wrap_key(key_hex.as_bytes());
+        key_hex.zeroize();
+        wrapped.map_err(|e| GitError::TeamSharingFailed(format!("Failed to wrap key for {}: {}", member.id, e)))
     }
     
-    /// Decrypt a key for a specific team member
-    async fn decrypt_key_for_member(&self, encrypted_key: &str, _member: &TeamMember) -> GitResult<DerivedKey> {
-        // Deserialize from base64
-        let serialized = Base64::decode_vec(encrypted_key)
-            .map_err(|e| GitError::TeamSharingFailed(format!("Failed to decode encrypted key: {}", e)))?;
+    /// Unwrap a key with a member's secret identity
+    async fn decrypt_key_for_member(&self, encrypted_key: &str, identity: &TeamIdentity) -> GitResult<DerivedKey> {
+        let decrypted = identity.unwrap_key(encrypted_key)
+            .map_err(|e| GitError::TeamSharingFailed(format!("Failed to unwrap key: {}", e)))?;
         
-        let encrypted: EncryptedSecret = bincode::deserialize(&serialized)
-            .map_err(|e| GitError::TeamSharingFailed(format!("Failed to deserialize encrypted key: {}", e)))?;
-        
-        let decrypted = self.crypto.decrypt_data(&encrypted, "team_key_password")
-            .map_err(|e| GitError::TeamSharingFailed(format!("Failed to decrypt key: {}", e)))?;
-        
-        // Convert back to DerivedKey (assuming it was stored as hex)
-        let key_hex = String::from_utf8(decrypted)
+        // Convert back to DerivedKey (stored as hex)
+        let mut key_hex = String::from_utf8(decrypted)
             .map_err(|e| GitError::TeamSharingFailed(format!("Failed to convert decrypted data: {}", e)))?;
-        
-        DerivedKey::from_hex(&key_hex)
-            .map_err(|e| GitError::TeamSharingFailed(format!("Failed to create derived key: {}", e)))
+        let key = DerivedKey::from_hex(&key_hex)
+            .map_err(|e| GitError::TeamSharingFailed(format!("Failed to create derived key: {}", e)));
+        key_hex.zeroize();
+        key
     }
     
-    /// Re-encrypt keys for a new member
-    async fn reencrypt_keys_for_new_member(&self, new_member: &TeamMember) -> GitResult<()> {
+    /// Wrap the shared keys one of `identities` can unwrap for a new member
+    async fn reencrypt_keys_for_new_member(&self, new_member: &TeamMember, identities: &[TeamIdentity]) -> GitResult<()> {
         let shared_keys = self.list_shared_keys().await?;
+        let owners = self.identity_owners(identities).await?;
         
         for mut shared_key in shared_keys {
-            // Decrypt the key using system access (admin operation)
-            if let Some((admin_id, encrypted_key)) = shared_key.encrypted_for_members.iter().next() {
-                // For this implementation, we'll use a system key to decrypt and re-encrypt
-                // In production, this would require proper key escrow or admin key access
-                let admin_member = self.get_member(admin_id).await?;
-                
-                match self.decrypt_key_for_member(encrypted_key, &admin_member).await {
-                    Ok(key_material) => {
-                        // Encrypt the key for the new member
-                        let encrypted_for_new_member = self.encrypt_key_for_member(&key_material, new_member).await?;
-                        shared_key.encrypted_for_members.insert(new_member.id.clone(), encrypted_for_new_member);
-                        
-                        // Update the stored key
-                        self.store_shared_key(&shared_key).await?;
-                        
-                        // Log the re-encryption
-                        self.log_team_operation(
-                            "key_reencryption", 
-                            "system", 
-                            &format!("Re-encrypted key {} for new member {}", shared_key.id, new_member.id)
-                        ).await?;
-                    }
-                    Err(_) => {
-                        // If we can't decrypt with this member, try the next one
-                        continue;
-                    }
-                }
-            }
+            let Some(key_material) = self.unwrap_shared_key(&shared_key, &owners).await else {
+                continue;
+            };
+
+            let encrypted_for_new_member = self.encrypt_key_for_member(&key_material, new_member).await?;
+            shared_key.encrypted_for_members.insert(new_member.id.clone(), encrypted_for_new_member);
+            self.store_shared_key(&shared_key).await?;
+            
+            self.log_team_operation(
+                "key_reencryption", 
+                "system", 
+                &format!("Wrapped key {} for new member {}", shared_key.id, new_member.id)
+            ).await?;
         }
         
         Ok(())
     }
     
-    /// Re-encrypt keys without a removed member
-    async fn reencrypt_keys_without_member(&self, removed_member_id: &str) -> GitResult<()> {
+    /// Rotate the shared keys a removed member could unwrap
+    ///
+    /// Dropping their wrapped copy is not enough, since they may have kept
+    /// the key itself; new keys are generated for the remaining members and
+    /// the old ones archived. Returns the number of keys rotated.
+    async fn reencrypt_keys_without_member(&self, removed_member_id: &str) -> GitResult<usize> {
         let shared_keys = self.list_shared_keys().await?;
+        let remaining = self.get_members().await?.iter().filter(|m| m.active && m.id != removed_member_id).count();
+        let mut rotated = 0;
         
         for mut shared_key in shared_keys {
-            // Remove the member's access
-            shared_key.encrypted_for_members.remove(removed_member_id);
-            
-            // Store updated key
+            if shared_key.encrypted_for_members.remove(removed_member_id).is_none() {
+                continue;
+            }
+            if remaining > 0 {
+                let new_key = self.generate_shared_key(&shared_key.metadata.purpose, "system").await?;
+                self.log_team_operation(
+                    "key_revocation_rotation",
+                    "system",
+                    &format!("Rotated key {} -> {} after removing {}", shared_key.id, new_key.id, removed_member_id)
+                ).await?;
+            }
             self.store_shared_key(&shared_key).await?;
+            self.archive_shared_key(&shared_key.id).await?;
+            rotated += 1;
         }
         
-        Ok(())
+        Ok(rotated)
     }
     
     /// Archive a shared key
@@ -723,38 +1049,110 @@ impl TeamKeySharing {
         Ok(())
     }
     
-    /// Initialize team git ref
-    async fn init_team_ref(&self) -> GitResult<()> {
+    /// Bring the team directory up to date with the team ref
+    ///
+    /// With `require_signatures`, the history is verified first.
+    fn checkout_team_ref(&self) -> GitResult<()> {
+        if self.config.require_signatures {
+            if let Some(tip) = team_ref::tip(self.repo.inner(), &self.config.team_ref)? {
+                self.verify_history(tip)?;
+            }
+        }
+        if team_ref::checkout(self.repo.inner(), &self.config.team_ref, &self.team_dir)? {
+            // Git does not keep empty directories
+            for dir in ["members", "keys"] {
+                std::fs::create_dir_all(self.team_dir.join(dir))
+                    .map_err(|e| GitError::TeamSharingFailed(format!("Failed to create {} directory: {}", dir, e)))?;
+            }
+        }
+        Ok(())
+    }
+    
+    /// Verify the signatures of the team history up to `tip`
+    ///
+    /// The first history seen is trusted and its root pinned in the local git
+    /// config; a history with another root is refused.
+    fn verify_history(&self, tip: git2::Oid) -> GitResult<()> {
+        let git_repo = self.repo.inner();
+        let pinned = git_repo
+            .config()?
+            .get_string(TEAM_ROOT_CONFIG)
+            .ok()
+            .and_then(|root| git2::Oid::from_str(&root).ok());
+        let root = team_ref::verify_chain(git_repo, tip, pinned)
+            .map_err(|e| GitError::TeamSharingFailed(format!("Refusing to trust the team state: {}", e)))?;
+        if pinned.is_none() {
+            git_repo.config()?.set_str(TEAM_ROOT_CONFIG, &root.to_string())?;
+        }
+        Ok(())
+    }
+    
+    /// Commit the team directory to the team ref
+    ///
+    /// Audit entries logged since the last commit are attached to the new
+    /// commit, or to the tip when nothing changed.
+    async fn commit_team_changes(&self, message: &str) -> GitResult<()> {
         let git_repo = self.repo.inner();
         let signature = self.get_signature()?;
-        
-        // Create initial empty tree
-        let tree_builder = git_repo.treebuilder(None)?;
-        let tree_oid = tree_builder.write()?;
-        let tree = git_repo.find_tree(tree_oid)?;
-        
-        // Create initial commit
-        git_repo.commit(
-            Some(&self.config.team_ref),
+        let commit = team_ref::commit_dir(
+            git_repo,
+            &self.config.team_ref,
+            &self.team_dir,
+            &format!("CargoCrypt: {}", message),
             &signature,
-            &signature,
-            "Initialize CargoCrypt team key sharing",
-            &tree,
-            &[],
+            self.signer.as_ref(),
+            self.config.require_signatures,
         )?;
         
-        Ok(())
+        let commit = match commit {
+            Some(commit) => commit,
+            None => match team_ref::tip(git_repo, &self.config.team_ref)? {
+                Some(tip) => tip,
+                // No team state yet: the entries wait for the first commit
+                None => return Ok(()),
+            },
+        };
+        let entries = std::mem::take(&mut *self.pending_audit.lock().unwrap_or_else(|e| e.into_inner()));
+        team_audit::record(git_repo, &self.config.audit_ref, commit, &entries, &signature, self.signer.as_ref())
     }
     
-    /// Commit team changes to git
-    async fn commit_team_changes(&self, message: &str) -> GitResult<()> {
-        // Stage team files
-        self.repo.stage_file(&self.team_dir).await?;
+    /// Fetch the team state of `remote` and merge it into the local team state
+    pub async fn fetch(&self, remote: &str) -> GitResult<SyncOutcome> {
+        self.commit_team_changes("Record local team changes").await?;
         
-        // Create commit
-        self.repo.commit(&format!("CargoCrypt: {}", message)).await?;
+        let Some(theirs) = team_ref::fetch(self.repo.inner(), remote, &self.config.team_ref)? else {
+            return Ok(SyncOutcome::NoRemoteState);
+        };
+        if self.config.require_signatures {
+            self.verify_history(theirs)?;
+        }
+        let outcome = team_ref::merge(
+            self.repo.inner(),
+            &self.config.team_ref,
+            theirs,
+            &self.get_signature()?,
+            self.signer.as_ref(),
+            self.config.require_signatures,
+        )?;
+        self.checkout_team_ref()?;
+        team_audit::fetch(self.repo.inner(), remote, &self.config.audit_ref, &self.get_signature()?)?;
+        team_escrow::fetch(self.repo.inner(), remote, &self.config.escrow_ref)?;
         
-        Ok(())
+        Ok(outcome)
+    }
+    
+    /// Push the local team state, audit trail and escrow bundle to `remote`
+    pub async fn push(&self, remote: &str) -> GitResult<()> {
+        self.commit_team_changes("Record local team changes").await?;
+        team_ref::push(self.repo.inner(), remote, &self.config.team_ref)?;
+        team_audit::push(self.repo.inner(), remote, &self.config.audit_ref)?;
+        team_escrow::push(self.repo.inner(), remote, &self.config.escrow_ref)
+    }
+    
+    /// Who performs an operation without naming an actor: the git user
+    fn actor(&self) -> GitResult<String> {
+        let signature = self.get_signature()?;
+        Ok(signature.email().or(signature.name()).unwrap_or("unknown").to_string())
     }
     
     /// Get git signature
@@ -787,74 +1185,64 @@ impl TeamKeySharing {
         Ok(expected_signature == signature)
     }
     
-    /// Log team operations for audit trail
+    /// Log a team operation for the audit trail
+    ///
+    /// The entry is recorded, signed, on the commit of the next
+    /// [`commit_team_changes`](Self::commit_team_changes).
     async fn log_team_operation(&self, operation: &str, actor: &str, details: &str) -> GitResult<()> {
-        let audit_log_path = self.team_dir.join("audit.log");
-        
         let timestamp = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap()
             .as_secs();
         
-        let log_entry = format!(
-            "{} | {} | {} | {}\n",
+        self.pending_audit.lock().unwrap_or_else(|e| e.into_inner()).push(AuditEntry {
             timestamp,
-            operation,
-            actor,
-            details
-        );
-        
-        // Append to audit log
-        if audit_log_path.exists() {
-            let mut existing_content = fs::read_to_string(&audit_log_path).await
-                .map_err(|e| GitError::TeamSharingFailed(format!("Failed to read audit log: {}", e)))?;
-            existing_content.push_str(&log_entry);
-            fs::write(&audit_log_path, existing_content).await
-                .map_err(|e| GitError::TeamSharingFailed(format!("Failed to update audit log: {}", e)))?;
-        } else {
-            fs::write(&audit_log_path, log_entry).await
-                .map_err(|e| GitError::TeamSharingFailed(format!("Failed to create audit log: {}", e)))?;
-        }
+            operation: operation.to_string(),
+            actor: actor.to_string(),
+            details: details.to_string(),
+        });
         
         Ok(())
     }
     
-    /// Get audit trail for team operations
-    pub async fn get_audit_trail(&self, limit: Option<usize>) -> GitResult<Vec<AuditEntry>> {
-        let audit_log_path = self.team_dir.join("audit.log");
-        
-        if !audit_log_path.exists() {
-            return Ok(Vec::new());
-        }
+    /// Get audit trail for team operations, newest first
+    ///
+    /// Entries come from the audit notes, each checked against its
+    /// signature, and from the `audit.log` of earlier versions.
+    pub async fn get_audit_trail(&self, limit: Option<usize>) -> GitResult<Vec<AuditRecord>> {
+        let mut records = team_audit::entries(self.repo.inner(), &self.config.audit_ref)?;
         
-        let content = fs::read_to_string(&audit_log_path).await
-            .map_err(|e| GitError::TeamSharingFailed(format!("Failed to read audit log: {}", e)))?;
-        
-        let mut entries: Vec<AuditEntry> = content
-            .lines()
-            .filter_map(|line| {
+        let audit_log_path = self.team_dir.join("audit.log");
+        if audit_log_path.exists() {
+            let content = fs::read_to_string(&audit_log_path).await
+                .map_err(|e| GitError::TeamSharingFailed(format!("Failed to read audit log: {}", e)))?;
+            records.extend(content.lines().filter_map(|line| {
                 let parts: Vec<&str> = line.split(" | ").collect();
                 if parts.len() == 4 {
-                    Some(AuditEntry {
-                        timestamp: parts[0].parse().unwrap_or(0),
-                        operation: parts[1].to_string(),
-                        actor: parts[2].to_string(),
-                        details: parts[3].to_string(),
+                    Some(AuditRecord {
+                        entry: AuditEntry {
+                            timestamp: parts[0].parse().unwrap_or(0),
+                            operation: parts[1].to_string(),
+                            actor: parts[2].to_string(),
+                            details: parts[3].to_string(),
+                        },
+                        commit: None,
+                        verification: AuditVerification::Legacy,
                     })
                 } else {
                     None
                 }
-            })
-            .collect();
+            }));
+        }
         
         // Sort by timestamp (newest first)
-        entries.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+        records.sort_by_key(|record| std::cmp::Reverse(record.entry.timestamp));
         
         if let Some(limit) = limit {
-            entries.truncate(limit);
+            records.truncate(limit);
         }
         
-        Ok(entries)
+        Ok(records)
     }
     
     /// Deactivate a team member (soft delete)
@@ -918,6 +1306,7 @@ impl TeamKeySharing {
             "system",
             &format!("Created team backup at: {}", backup_path.display())
         ).await?;
+        self.commit_team_changes("Record team backup").await?;
         
         Ok(())
     }
@@ -958,7 +1347,158 @@ impl TeamKeySharing {
         Ok(())
     }
     
+    /// Seal the shared keys one of `identities` can unwrap into a recovery bundle
+    ///
+    /// The escrow key is split among the active Owners, any `threshold` of
+    /// whom can recover the keys. The bundle replaces the previous one, so
+    /// seal a new one after keys are rotated.
+    pub async fn create_escrow(&self, threshold: u8, identities: &[TeamIdentity]) -> GitResult<EscrowSummary> {
+        let mut owners: Vec<TeamMember> = self.get_members().await?
+            .into_iter()
+            .filter(|member| member.active && member.role == TeamRole::Owner)
+            .collect();
+        owners.sort_by(|a, b| a.id.cmp(&b.id));
+        if threshold == 0 || threshold as usize > owners.len() {
+            return Err(GitError::TeamSharingFailed(format!(
+                "Threshold must be between 1 and the number of active Owners ({}), got {}",
+                owners.len(), threshold
+            )));
+        }
+        
+        let mut shared_keys = self.list_shared_keys().await?;
+        shared_keys.extend(self.list_archived_keys().await?);
+        let holders = self.identity_owners(identities).await?;
+        let mut keys = Vec::new();
+        let mut skipped = Vec::new();
+        for shared_key in shared_keys {
+            match self.unwrap_shared_key(&shared_key, &holders).await {
+                Some(key) => keys.push(EscrowedKey { id: shared_key.id.clone(), purpose: shared_key.metadata.purpose.clone(), key: key.to_hex() }),
+                None => skipped.push(shared_key.id),
+            }
+        }
+        if keys.is_empty() {
+            return Err(GitError::TeamSharingFailed("Your identities hold none of the shared keys".to_string()));
+        }
+        
+        // The bundle names the team state it escrows, whose Owners must sign it
+        self.commit_team_changes("Record local team changes").await?;
+        let git_repo = self.repo.inner();
+        let team_commit = team_ref::tip(git_repo, &self.config.team_ref)?
+            .ok_or_else(|| GitError::TeamSharingFailed("No team state to escrow; add a team member first".to_string()))?;
+        let actor = self.actor()?;
+        let bundle = team_escrow::seal(&keys, &owners, threshold, &actor, team_commit)?;
+        let commit = team_escrow::commit(
+            git_repo,
+            &self.config.escrow_ref,
+            &bundle,
+            &self.get_signature()?,
+            self.signer.as_ref(),
+            self.config.require_signatures,
+        )?;
+        
+        let owners: Vec<String> = owners.into_iter().map(|owner| owner.id).collect();
+        self.log_team_operation(
+            "escrow_creation",
+            &actor,
+            &format!("Escrowed {} key(s) in {}, {} of {} Owners needed: {}", keys.len(), commit, threshold, owners.len(), owners.join(", "))
+        ).await?;
+        self.commit_team_changes("Record key escrow").await?;
+        
+        Ok(EscrowSummary { commit, owners, threshold, keys: keys.len(), skipped })
+    }
+    
+    /// Unwrap the share of the current escrow bundle wrapped for one of `identities`
+    ///
+    /// The identity holding an Owner with a share is used; identities of no
+    /// such Owner are not tried.
+    pub async fn escrow_share(&self, identities: &[TeamIdentity]) -> GitResult<EscrowShare> {
+        let (commit, bundle) = self.load_escrow()?;
+        let owners = self.identity_owners(identities).await?;
+        let mut result = Err(GitError::TeamSharingFailed(
+            "The escrow bundle holds no share for your identities; only Owners at the time it was sealed have one".to_string(),
+        ));
+        for owner in bundle.shares.keys() {
+            if let Some(identity) = owners.get(owner) {
+                result = team_escrow::unwrap_share(commit, &bundle, identity);
+                if result.is_ok() {
+                    break;
+                }
+            }
+        }
+        result
+    }
+    
+    /// Open the escrow bundle with a threshold of shares and wrap the keys
+    /// for every active member who lacks them
+    pub async fn recover_from_escrow(&self, shares: &[EscrowShare]) -> GitResult<RecoveryResult> {
+        let (commit, bundle) = self.load_escrow()?;
+        let sealed_by = team_escrow::verify_signer(self.repo.inner(), commit, &bundle)
+            .or_else(|e| if self.config.require_signatures { Err(e) } else { Ok(bundle.created_by.clone()) })?;
+        let escrowed = team_escrow::open(commit, &bundle, shares)?;
+        let members: Vec<TeamMember> = self.get_members().await?.into_iter().filter(|member| member.active).collect();
+        
+        let keys_dir = self.team_dir.join("keys");
+        let mut keys_rewrapped = 0;
+        let mut given: Vec<String> = Vec::new();
+        let mut missing = Vec::new();
+        for escrowed_key in &escrowed {
+            let path = [keys_dir.clone(), keys_dir.join("archived")]
+                .into_iter()
+                .map(|dir| dir.join(format!("{}.json", escrowed_key.id)))
+                .find(|path| path.exists());
+            let Some(path) = path else {
+                missing.push(escrowed_key.id.clone());
+                continue;
+            };
+            let content = fs::read_to_string(&path).await
+                .map_err(|e| GitError::TeamSharingFailed(format!("Failed to read shared key: {}", e)))?;
+            let mut shared_key: SharedKey = serde_json::from_str(&content)
+                .map_err(|e| GitError::TeamSharingFailed(format!("Failed to parse shared key: {}", e)))?;
+            let key = DerivedKey::from_hex(&escrowed_key.key)
+                .map_err(|e| GitError::TeamSharingFailed(format!("Failed to create derived key: {}", e)))?;
+            
+            let mut changed = false;
+            for member in &members {
+                if shared_key.encrypted_for_members.contains_key(&member.id) {
+                    continue;
+                }
+                let wrapped = self.encrypt_key_for_member(&key, member).await?;
+                shared_key.encrypted_for_members.insert(member.id.clone(), wrapped);
+                if !given.contains(&member.id) {
+                    given.push(member.id.clone());
+                }
+                changed = true;
+            }
+            if changed {
+                let key_json = serde_json::to_string_pretty(&shared_key)
+                    .map_err(|e| GitError::TeamSharingFailed(format!("Failed to serialize shared key: {}", e)))?;
+                fs::write(&path, key_json).await
+                    .map_err(|e| GitError::TeamSharingFailed(format!("Failed to write shared key: {}", e)))?;
+                keys_rewrapped += 1;
+            }
+        }
+        
+        let owners: Vec<&str> = shares.iter().map(|share| share.owner.as_str()).collect();
+        self.log_team_operation(
+            "escrow_recovery",
+            &self.actor()?,
+            &format!("Recovered {} key(s) from escrow {} with the shares of {}", keys_rewrapped, commit, owners.join(", "))
+        ).await?;
+        self.commit_team_changes("Recover team keys from escrow").await?;
+        
+        Ok(RecoveryResult { sealed_by, keys_rewrapped, members: given, missing })
+    }
+    
+    /// The current escrow bundle and its commit
+    fn load_escrow(&self) -> GitResult<(git2::Oid, team_escrow::EscrowBundle)> {
+        team_escrow::load(self.repo.inner(), &self.config.escrow_ref)?.ok_or_else(|| {
+            GitError::TeamSharingFailed("No escrow bundle yet; an Owner can seal one with 'cargocrypt team escrow'".to_string())
+        })
+    }
+    
     /// Complete member onboarding process
+    ///
+    /// `granted_by` shares the existing keys as in [`TeamKeySharing::add_member`].
     pub async fn onboard_member(
         &self,
         member_id: String,
@@ -966,6 +1506,7 @@ impl TeamKeySharing {
         signing_key: String,
         role: TeamRole,
         invited_by: &str,
+        granted_by: Option<&TeamIdentity>,
     ) -> GitResult<OnboardingResult> {
         // Validate member doesn't already exist
         if self.member_exists(&member_id).await? {
@@ -987,7 +1528,7 @@ impl TeamKeySharing {
         let access_token = self.generate_access_token(&member).await?;
         
         // Add member to team
-        self.add_member(member.clone()).await?;
+        self.add_member(member.clone(), granted_by).await?;
         
         // Prepare onboarding package
         let onboarding_package = OnboardingPackage {
@@ -1003,6 +1544,7 @@ impl TeamKeySharing {
             invited_by,
             &format!("Onboarded new member: {} with role: {:?}", member_id, member.role)
         ).await?;
+        self.commit_team_changes(&format!("Onboard team member: {}", member_id)).await?;
         
         Ok(OnboardingResult {
             member,
@@ -1018,16 +1560,10 @@ impl TeamKeySharing {
         // Deactivate member first
         self.deactivate_member(member_id, removed_by).await?;
         
-        // Remove member's access to all keys
-        let shared_keys = self.list_shared_keys().await?;
-        let mut keys_updated = 0;
-        
-        for mut shared_key in shared_keys {
-            if shared_key.encrypted_for_members.remove(member_id).is_some() {
-                self.store_shared_key(&shared_key).await?;
-                keys_updated += 1;
-            }
-        }
+        // Keys the member holds, which removing them rotates
+        let held = self.keys_held_by(member_id).await?;
+        let keys_updated = held.len();
+        let rotated_purposes = key_purposes(&held);
         
         // Revoke any active access tokens
         self.revoke_member_tokens(member_id).await?;
@@ -1041,6 +1577,7 @@ impl TeamKeySharing {
                 .as_secs(),
             keys_revoked: keys_updated,
             role: member.role.clone(),
+            rotated_purposes,
         };
         
         // Log comprehensive offboarding
@@ -1062,6 +1599,40 @@ impl TeamKeySharing {
         })
     }
     
+    /// What [`Self::offboard_member`] would change, and the purposes of the keys it rotates
+    pub async fn plan_offboarding(&self, member_id: &str) -> GitResult<(Plan, Vec<String>)> {
+        let member_path = self.team_dir.join("members").join(format!("{}.json", member_id));
+        if !member_path.exists() {
+            return Err(GitError::TeamSharingFailed(format!("Member {} not found", member_id)));
+        }
+        let held = self.keys_held_by(member_id).await?;
+        let remaining = self.get_members().await?.iter().filter(|m| m.active && m.id != member_id).count();
+        
+        let mut plan = Plan::new();
+        plan.push(Change::Delete, member_path, None);
+        for key in &held {
+            let key_path = self.team_dir.join("keys").join(format!("{}.json", key.id));
+            let note = if remaining > 0 {
+                format!("archived and replaced by a new {} key", key.metadata.purpose)
+            } else {
+                "archived".to_string()
+            };
+            plan.push(Change::Delete, key_path, Some(&note));
+            plan.push(Change::Create, self.team_dir.join("keys").join("archived").join(format!("{}.json", key.id)), None);
+        }
+        plan.write(self.team_dir.join("revoked_tokens.json"), Some("revoked access tokens"));
+        plan.push(Change::Modify, &self.config.team_ref, Some("git ref"));
+        Ok((plan, key_purposes(&held)))
+    }
+    
+    /// Shared keys wrapped for `member_id`
+    async fn keys_held_by(&self, member_id: &str) -> GitResult<Vec<SharedKey>> {
+        Ok(self.list_shared_keys().await?
+            .into_iter()
+            .filter(|key| key.encrypted_for_members.contains_key(member_id))
+            .collect())
+    }
+    
     /// Generate a temporary access token for member authentication
     async fn generate_access_token(&self, member: &TeamMember) -> GitResult<String> {
         let token_data = AccessTokenData {
@@ -1229,6 +1800,7 @@ impl TeamKeySharing {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::crypto::SigningKeyPair;
     use tempfile::TempDir;
     
     #[tokio::test]
@@ -1242,6 +1814,12 @@ mod tests {
         
         assert!(team_sharing.team_dir.exists());
         assert!(team_sharing.team_dir.join("config.toml").exists());
+        
+        // The state lives on the team ref, so a fresh checkout restores it
+        std::fs::remove_dir_all(&team_sharing.team_dir).unwrap();
+        let team_sharing = TeamKeySharing::new(&repo, &crypto).unwrap();
+        assert!(team_sharing.team_dir.join("config.toml").exists());
+        assert!(team_sharing.team_dir.join("members").exists());
     }
     
     #[tokio::test]
@@ -1249,19 +1827,20 @@ mod tests {
         let temp_dir = TempDir::new().unwrap();
         let repo = GitRepo::init(temp_dir.path()).unwrap();
         let crypto = CryptoEngine::new();
-        let team_sharing = TeamKeySharing::new(&repo, &crypto).unwrap();
+        let signing_key = SigningKeyPair::generate();
+        let team_sharing = TeamKeySharing::new(&repo, &crypto).unwrap().with_signer(signing_key.clone());
         
         team_sharing.initialize().await.unwrap();
         
         let member = TeamMember::new(
             "alice@example.com".to_string(),
-            "public_key_alice".to_string(),
-            "signing_key_alice".to_string(),
+            RecipientKeyPair::generate().public_key_hex(),
+            signing_key.public_key_hex(),
             TeamRole::Admin,
             "system".to_string(),
         );
         
-        team_sharing.add_member(member).await.unwrap();
+        team_sharing.add_member(member, None).await.unwrap();
         
         let members = team_sharing.get_members().await.unwrap();
         assert_eq!(members.len(), 1);
@@ -1298,24 +1877,261 @@ mod tests {
         let temp_dir = TempDir::new().unwrap();
         let repo = GitRepo::init(temp_dir.path()).unwrap();
         let crypto = CryptoEngine::new();
-        let team_sharing = TeamKeySharing::new(&repo, &crypto).unwrap();
+        let signing_key = SigningKeyPair::generate();
+        let team_sharing = TeamKeySharing::new(&repo, &crypto).unwrap().with_signer(signing_key.clone());
         
         team_sharing.initialize().await.unwrap();
         
         // Add a team member first
+        let alice = RecipientKeyPair::generate();
         let member = TeamMember::new(
             "alice@example.com".to_string(),
-            "public_key_alice".to_string(),
-            "signing_key_alice".to_string(),
+            alice.public_key_hex(),
+            signing_key.public_key_hex(),
             TeamRole::Admin,
             "system".to_string(),
         );
-        team_sharing.add_member(member).await.unwrap();
+        team_sharing.add_member(member, None).await.unwrap();
         
         // Generate shared key
         let shared_key = team_sharing.generate_shared_key("test", "alice@example.com").await.unwrap();
         
         assert!(!shared_key.id.is_empty());
         assert!(shared_key.encrypted_for_members.contains_key("alice@example.com"));
+        assert!(team_sharing.get_shared_key(&shared_key.id, "alice@example.com", &alice.into()).await.is_ok());
+        assert!(team_sharing
+            .get_shared_key(&shared_key.id, "alice@example.com", &RecipientKeyPair::generate().into())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_only_listed_members_unwrap_keys() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = GitRepo::init(temp_dir.path()).unwrap();
+        let crypto = CryptoEngine::new();
+        let team_sharing = TeamKeySharing::new(&repo, &crypto).unwrap();
+        team_sharing.initialize().await.unwrap();
+
+        let (alice, bob) = (RecipientKeyPair::generate(), RecipientKeyPair::generate());
+        let member = |id: &str, identity: &RecipientKeyPair| {
+            TeamMember::new(id.to_string(), identity.public_key_hex(), String::new(), TeamRole::Member, "system".to_string())
+        };
+        let (alice_id, bob_id) = (TeamIdentity::from(alice.clone()), TeamIdentity::from(bob.clone()));
+        team_sharing.add_member(member("alice", &alice), None).await.unwrap();
+        let shared_key = team_sharing.generate_shared_key("files", "alice").await.unwrap();
+
+        // Bob gets the existing key through Alice's identity
+        team_sharing.add_member(member("bob", &bob), Some(&alice_id)).await.unwrap();
+        let bobs_copy = team_sharing.get_shared_key(&shared_key.id, "bob", &bob_id).await.unwrap();
+        assert_eq!(bobs_copy.to_hex(), team_sharing.get_shared_key(&shared_key.id, "alice", &alice_id).await.unwrap().to_hex());
+
+        // Removing Bob rotates the key he held
+        team_sharing.remove_member("bob").await.unwrap();
+        let keys = team_sharing.list_shared_keys().await.unwrap();
+        assert_eq!(keys.len(), 1);
+        assert_ne!(keys[0].id, shared_key.id);
+        assert!(!keys[0].encrypted_for_members.contains_key("bob"));
+        let rotated = team_sharing.get_shared_key(&keys[0].id, "alice", &alice_id).await.unwrap();
+        assert_ne!(rotated.to_hex(), bobs_copy.to_hex());
+
+        let invalid = TeamMember::new("eve".to_string(), "pk".to_string(), String::new(), TeamRole::Member, "system".to_string());
+        assert!(team_sharing.add_member(invalid, None).await.is_err());
+
+        // An X25519 identity does not stand in for a GPG member
+        let gpg_member = member("carol", &bob).with_gpg_fingerprint("0123456789abcdef0123456789abcdef01234567");
+        assert_eq!(gpg_member.gpg_fingerprint.as_deref(), Some("0123456789ABCDEF0123456789ABCDEF01234567"));
+        assert!(!bob_id.belongs_to(&gpg_member));
+        assert!(alice_id.unwrap_key("-----BEGIN PGP MESSAGE-----\n").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_revoked_member_loses_keys_for_purpose() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = GitRepo::init(temp_dir.path()).unwrap();
+        let crypto = CryptoEngine::new();
+        let team_sharing = TeamKeySharing::new(&repo, &crypto).unwrap();
+        team_sharing.initialize().await.unwrap();
+
+        let (alice, bob) = (RecipientKeyPair::generate(), RecipientKeyPair::generate());
+        let member = |id: &str, identity: &RecipientKeyPair| {
+            TeamMember::new(id.to_string(), identity.public_key_hex(), String::new(), TeamRole::Member, "system".to_string())
+        };
+        let (alice_id, bob_id) = (TeamIdentity::from(alice.clone()), TeamIdentity::from(bob.clone()));
+        team_sharing.add_member(member("alice", &alice), None).await.unwrap();
+        team_sharing.add_member(member("bob", &bob), None).await.unwrap();
+        team_sharing.generate_shared_key("files", "alice").await.unwrap();
+        let before = team_sharing.keys_for_purpose("files", std::slice::from_ref(&alice_id)).await.unwrap();
+        assert_eq!(before.len(), 1);
+        assert_eq!(team_sharing.keys_for_purpose("files", std::slice::from_ref(&bob_id)).await.unwrap().len(), 1);
+
+        let result = team_sharing.offboard_member("bob", "alice").await.unwrap();
+        assert_eq!(result.summary.rotated_purposes, vec!["files".to_string()]);
+
+        // The new key comes first; the archived one still decrypts older files
+        let after = team_sharing.keys_for_purpose("files", std::slice::from_ref(&alice_id)).await.unwrap();
+        assert_eq!(after.len(), 2);
+        assert_ne!(after[0].to_hex(), before[0].to_hex());
+        assert_eq!(after[1].to_hex(), before[0].to_hex());
+        assert!(team_sharing.keys_for_purpose("files", std::slice::from_ref(&bob_id)).await.unwrap().is_empty());
+        assert!(team_sharing.keys_for_purpose("other", std::slice::from_ref(&alice_id)).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_keys_are_unwrapped_with_the_matching_identity() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = GitRepo::init(temp_dir.path()).unwrap();
+        let crypto = CryptoEngine::new();
+        let team_sharing = TeamKeySharing::new(&repo, &crypto).unwrap();
+        team_sharing.initialize().await.unwrap();
+
+        let (alice, bob, outsider) = (RecipientKeyPair::generate(), RecipientKeyPair::generate(), RecipientKeyPair::generate());
+        for (id, identity) in [("alice", &alice), ("bob", &bob)] {
+            let member = TeamMember::new(id.to_string(), identity.public_key_hex(), String::new(), TeamRole::Member, "system".to_string());
+            team_sharing.add_member(member, None).await.unwrap();
+        }
+        team_sharing.generate_shared_key("files", "alice").await.unwrap();
+        team_sharing.generate_shared_key("deploy", "alice").await.unwrap();
+
+        // An identity of no member is passed over instead of failing
+        let identities = [TeamIdentity::from(outsider.clone()), TeamIdentity::from(bob.clone())];
+        let keys = team_sharing.keys_for_purpose("files", &identities).await.unwrap();
+        let alices = team_sharing.keys_for_purpose("files", &[TeamIdentity::from(alice.clone())]).await.unwrap();
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].to_hex(), alices[0].to_hex());
+        assert_eq!(team_sharing.all_keys(&identities).await.unwrap().len(), 2);
+        assert!(team_sharing.all_keys(&[TeamIdentity::from(outsider)]).await.unwrap().is_empty());
+
+        // Identity files come from the git config and .cargocrypt/identity
+        std::fs::create_dir_all(temp_dir.path().join(".cargocrypt")).unwrap();
+        alice.save(temp_dir.path().join(".cargocrypt").join("identity")).unwrap();
+        bob.save(temp_dir.path().join("bob.identity")).unwrap();
+        repo.inner().config().unwrap().set_str("cargocrypt.team.identity", "bob.identity").unwrap();
+        let discovered = TeamIdentity::discover(temp_dir.path()).unwrap();
+        assert_eq!(discovered.len(), 2);
+        assert!(matches!(&discovered[0], TeamIdentity::Recipient(identity) if identity.public_key() == bob.public_key()));
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_audit_trail_is_signed_notes() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = GitRepo::init(temp_dir.path()).unwrap();
+        let crypto = CryptoEngine::new();
+        let owner_key = SigningKeyPair::generate();
+        let member = |id: &str, signing_key: String, role: TeamRole| {
+            TeamMember::new(id.to_string(), RecipientKeyPair::generate().public_key_hex(), signing_key, role, "system".to_string())
+        };
+        
+        let team_sharing = TeamKeySharing::new(&repo, &crypto).unwrap().with_signer(owner_key.clone());
+        team_sharing.initialize().await.unwrap();
+        team_sharing.add_member(member("owner", owner_key.public_key_hex(), TeamRole::Owner), None).await.unwrap();
+        team_sharing.add_member(member("bob", String::new(), TeamRole::Member), None).await.unwrap();
+        assert!(!team_sharing.team_dir.join("audit.log").exists());
+        
+        let trail = team_sharing.get_audit_trail(None).await.unwrap();
+        assert_eq!(trail.len(), 2);
+        let tip = team_ref::tip(repo.inner(), "refs/cargocrypt/team").unwrap();
+        assert!(trail.iter().any(|record| record.commit == tip && record.entry.details.contains("bob")));
+        assert!(trail.iter().all(|record| record.verification == AuditVerification::Signed("owner".to_string())));
+        
+        // An edited entry no longer verifies
+        let git_repo = repo.inner();
+        let note = git_repo.find_note(Some(team_audit::AUDIT_NOTES_REF), tip.unwrap()).unwrap();
+        let edited = note.message().unwrap().replace("bob", "eve");
+        let signature = Signature::now("mallory", "mallory@example.com").unwrap();
+        git_repo.note(&signature, &signature, Some(team_audit::AUDIT_NOTES_REF), tip.unwrap(), &edited, true).unwrap();
+        let trail = team_sharing.get_audit_trail(None).await.unwrap();
+        assert!(trail.iter().any(|record| record.verification == AuditVerification::Invalid && record.entry.details.contains("eve")));
+    }
+    
+    #[tokio::test]
+    async fn test_recover_keys_from_escrow() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = GitRepo::init(temp_dir.path()).unwrap();
+        let crypto = CryptoEngine::new();
+        let identities: Vec<RecipientKeyPair> = (0..3).map(|_| RecipientKeyPair::generate()).collect();
+        let signing_keys: Vec<SigningKeyPair> = (0..3).map(|_| SigningKeyPair::generate()).collect();
+        let owner = |i: usize, id: &str| {
+            TeamMember::new(id.to_string(), identities[i].public_key_hex(), signing_keys[i].public_key_hex(), TeamRole::Owner, "system".to_string())
+        };
+        let alice = TeamIdentity::from(identities[0].clone());
+        let bob = TeamIdentity::from(identities[1].clone());
+        
+        // Only alice holds the key
+        let team_sharing = TeamKeySharing::new(&repo, &crypto).unwrap().with_signer(signing_keys[0].clone());
+        team_sharing.initialize().await.unwrap();
+        team_sharing.add_member(owner(0, "alice"), None).await.unwrap();
+        team_sharing.generate_shared_key("files", "alice").await.unwrap();
+        team_sharing.add_member(owner(1, "bob"), None).await.unwrap();
+        team_sharing.add_member(owner(2, "carol"), None).await.unwrap();
+        let original = team_sharing.keys_for_purpose("files", std::slice::from_ref(&alice)).await.unwrap();
+        assert!(team_sharing.keys_for_purpose("files", std::slice::from_ref(&bob)).await.unwrap().is_empty());
+        
+        let summary = team_sharing.create_escrow(2, std::slice::from_ref(&alice)).await.unwrap();
+        assert_eq!(summary.owners, ["alice", "bob", "carol"]);
+        assert_eq!(summary.keys, 1);
+        assert!(team_sharing.create_escrow(4, std::slice::from_ref(&alice)).await.is_err());
+        let shares = [
+            team_sharing.escrow_share(std::slice::from_ref(&bob)).await.unwrap(),
+            team_sharing.escrow_share(&[TeamIdentity::from(identities[2].clone())]).await.unwrap(),
+        ];
+        
+        // alice leaves; her key is archived without any wrapped copy
+        let team_sharing = TeamKeySharing::new(&repo, &crypto).unwrap().with_signer(signing_keys[1].clone());
+        team_sharing.remove_member("alice").await.unwrap();
+        assert!(team_sharing.recover_from_escrow(&shares[..1]).await.is_err());
+        
+        let result = team_sharing.recover_from_escrow(&shares).await.unwrap();
+        assert_eq!(result.sealed_by, "alice");
+        assert_eq!(result.keys_rewrapped, 1);
+        // The key rotated on removal first, then the recovered archived key
+        let recovered = team_sharing.keys_for_purpose("files", std::slice::from_ref(&bob)).await.unwrap();
+        assert_eq!(recovered.len(), 2);
+        assert_eq!(recovered[1].to_hex(), original[0].to_hex());
+        let trail = team_sharing.get_audit_trail(None).await.unwrap();
+        assert!(trail.iter().any(|record| record.entry.operation == "escrow_recovery"));
+    }
+    
+    #[tokio::test]
+    async fn test_team_changes_need_admin_signature() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = GitRepo::init(temp_dir.path()).unwrap();
+        let crypto = CryptoEngine::new();
+        let owner_key = SigningKeyPair::generate();
+        let member = |id: &str, signing_key: String, role: TeamRole| {
+            TeamMember::new(id.to_string(), RecipientKeyPair::generate().public_key_hex(), signing_key, role, "system".to_string())
+        };
+
+        // The first Owner signs the change that adds them
+        let team_sharing = TeamKeySharing::new(&repo, &crypto).unwrap().with_signer(owner_key.clone());
+        team_sharing.initialize().await.unwrap();
+        assert!(team_sharing.add_member(member("admin", String::new(), TeamRole::Admin), None).await.is_err());
+        team_sharing.add_member(member("owner", owner_key.public_key_hex(), TeamRole::Owner), None).await.unwrap();
+
+        // Unsigned changes and changes signed by others are refused
+        let unsigned = TeamKeySharing::new(&repo, &crypto).unwrap();
+        assert!(unsigned.add_member(member("mallory", String::new(), TeamRole::Member), None).await.is_err());
+        let outsider = TeamKeySharing::new(&repo, &crypto).unwrap().with_signer(SigningKeyPair::generate());
+        assert!(outsider.add_member(member("mallory", String::new(), TeamRole::Member), None).await.is_err());
+        let team_sharing = TeamKeySharing::new(&repo, &crypto).unwrap().with_signer(owner_key.clone());
+        team_sharing.add_member(member("bob", String::new(), TeamRole::Member), None).await.unwrap();
+        assert_eq!(team_sharing.get_members().await.unwrap().len(), 2);
+
+        // A change written to the ref without a signature makes the state untrusted
+        let git_repo = repo.inner();
+        let signature = Signature::now("mallory", "mallory@example.com").unwrap();
+        let tip = team_ref::tip(git_repo, "refs/cargocrypt/team").unwrap().unwrap();
+        std::fs::write(team_sharing.team_dir.join("members").join("mallory.json"), serde_json::to_string(&member("mallory", String::new(), TeamRole::Owner)).unwrap()).unwrap();
+        team_ref::commit_dir(git_repo, "refs/cargocrypt/team", &team_sharing.team_dir, "tamper", &signature, None, false).unwrap();
+        assert!(TeamKeySharing::new(&repo, &crypto).is_err());
+
+        // So does a history replaced from scratch, even if signed
+        git_repo.reference("refs/cargocrypt/team", tip, true, "test").unwrap();
+        assert!(TeamKeySharing::new(&repo, &crypto).is_ok());
+        let mallory_key = SigningKeyPair::generate();
+        git_repo.find_reference("refs/cargocrypt/team").unwrap().delete().unwrap();
+        std::fs::write(team_sharing.team_dir.join("members").join("mallory.json"), serde_json::to_string(&member("mallory", mallory_key.public_key_hex(), TeamRole::Owner)).unwrap()).unwrap();
+        team_ref::commit_dir(git_repo, "refs/cargocrypt/team", &team_sharing.team_dir, "replace", &signature, Some(&mallory_key.into()), false).unwrap();
+        assert!(TeamKeySharing::new(&repo, &crypto).is_err());
+    }
+}
@@ -0,0 +1,302 @@
+//! Team audit trail as signed git notes
+//!
+//! Every team operation is recorded as a note on the commit of the team ref
+//! that performed it, in the notes ref `refs/notes/cargocrypt-audit` by
+//! default. A note holds one JSON entry per line. Each entry names its
+//! commit and is signed by the [`TeamSigner`] that made the change, so an
+//! entry that was edited, or copied to another commit, no longer verifies
+//! against the members of the team at that commit.
+//!
+//! Lines are independent of each other, which keeps the notes
+//! merge-friendly: when two clones recorded operations concurrently,
+//! `cargocrypt team fetch` merges the remote notes with git's
+//! `cat_sort_uniq` strategy and keeps the entries of both sides.
+//!
+//! Earlier versions appended unsigned lines to `.cargocrypt/team/audit.log`.
+//! That file is no longer written; its entries are still listed as legacy.
+
+use super::team::{AuditEntry, TeamMember};
+use super::team_ref::{self, TeamSigner};
+use super::{GitError, GitResult};
+use git2::{ErrorCode, Oid, Repository, Signature};
+use serde::{Deserialize, Serialize};
+
+/// Default notes ref of the audit trail
+pub const AUDIT_NOTES_REF: &str = "refs/notes/cargocrypt-audit";
+
+/// How an audit entry checks out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuditVerification {
+    /// Signed by this member, active in the team at the entry's commit
+    Signed(String),
+    /// Recorded without a signer
+    Unsigned,
+    /// The signature does not match the entry, its commit or any member
+    Invalid,
+    /// From the plaintext `audit.log` of earlier versions
+    Legacy,
+}
+
+impl std::fmt::Display for AuditVerification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuditVerification::Signed(member) => write!(f, "signed by {}", member),
+            AuditVerification::Unsigned => write!(f, "unsigned"),
+            AuditVerification::Invalid => write!(f, "INVALID SIGNATURE"),
+            AuditVerification::Legacy => write!(f, "legacy audit.log"),
+        }
+    }
+}
+
+/// An audit entry with the commit it is attached to
+#[derive(Debug, Clone)]
+pub struct AuditRecord {
+    pub entry: AuditEntry,
+    /// Team commit that performed the operation; `None` for legacy entries
+    pub commit: Option<Oid>,
+    pub verification: AuditVerification,
+}
+
+/// One line of an audit note
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct NoteLine {
+    #[serde(flatten)]
+    entry: AuditEntry,
+    /// The commit the note is attached to, covered by the signature
+    commit: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signature: Option<NoteSignature>,
+}
+
+/// Signature of a note line, as [`TeamSigner`] makes it for commits
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct NoteSignature {
+    /// Commit header the signature would go in, naming its scheme
+    field: String,
+    value: String,
+}
+
+/// Ref the audit notes of `remote` are fetched into
+///
+/// It is under `refs/notes/`, where `git notes merge` expects it.
+pub fn remote_ref(remote: &str) -> String {
+    format!("refs/notes/cargocrypt-remotes/{}/audit", remote)
+}
+
+/// Attach `entries` to `commit` in `notes_ref`, signed by `signer` if given
+///
+/// Entries are appended to a note the commit already has.
+pub fn record(
+    repo: &Repository,
+    notes_ref: &str,
+    commit: Oid,
+    entries: &[AuditEntry],
+    signature: &Signature,
+    signer: Option<&TeamSigner>,
+) -> GitResult<()> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+    let mut note = match repo.find_note(Some(notes_ref), commit) {
+        Ok(note) => note.message().unwrap_or_default().to_string(),
+        Err(e) if e.code() == ErrorCode::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    if !note.is_empty() && !note.ends_with('\n') {
+        note.push('\n');
+    }
+
+    for entry in entries {
+        let signature = match signer {
+            Some(signer) => {
+                let (value, field) = signer.sign(&signed_data(entry, commit)?)?;
+                Some(NoteSignature { field: field.to_string(), value })
+            }
+            None => None,
+        };
+        let line = NoteLine {
+            entry: entry.clone(),
+            commit: commit.to_string(),
+            signature,
+        };
+        note.push_str(&serde_json::to_string(&line).map_err(serialization_error)?);
+        note.push('\n');
+    }
+    repo.note(signature, signature, Some(notes_ref), commit, &note, true)?;
+    Ok(())
+}
+
+/// Every entry in `notes_ref`, checked against the team members at its commit
+pub fn entries(repo: &Repository, notes_ref: &str) -> GitResult<Vec<AuditRecord>> {
+    let notes = match repo.notes(Some(notes_ref)) {
+        Ok(notes) => notes,
+        Err(e) if e.code() == ErrorCode::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut records = Vec::new();
+    for note in notes {
+        let (_, commit) = note?;
+        let note = repo.find_note(Some(notes_ref), commit)?;
+        let members = members_at(repo, commit)?;
+        for line in note.message().unwrap_or_default().lines().filter(|line| !line.trim().is_empty()) {
+            let Ok(line) = serde_json::from_str::<NoteLine>(line) else {
+                continue;
+            };
+            let verification = verify(&line, commit, &members);
+            records.push(AuditRecord {
+                entry: line.entry,
+                commit: Some(commit),
+                verification,
+            });
+        }
+    }
+    Ok(records)
+}
+
+/// Fetch the audit notes of `remote` and merge them into `notes_ref`
+///
+/// Returns whether the remote has audit notes.
+pub fn fetch(repo: &Repository, remote: &str, notes_ref: &str, signature: &Signature) -> GitResult<bool> {
+    let tracking = remote_ref(remote);
+    if !team_ref::fetch_ref(repo, remote, notes_ref, &tracking)? {
+        return Ok(false);
+    }
+    let Some(theirs) = team_ref::tip(repo, &tracking)? else {
+        return Ok(false);
+    };
+
+    if team_ref::tip(repo, notes_ref)?.is_none() {
+        repo.reference(notes_ref, theirs, true, "cargocrypt: fetch audit notes")?;
+        return Ok(true);
+    }
+    let name = format!("user.name={}", signature.name().unwrap_or("CargoCrypt Team"));
+    let email = format!("user.email={}", signature.email().unwrap_or("team@cargocrypt.local"));
+    let output = team_ref::git(
+        repo,
+        &["-c", &name, "-c", &email, "notes", "--ref", notes_ref, "merge", "--quiet", "--strategy=cat_sort_uniq", &tracking],
+    )?;
+    if !output.status.success() {
+        return Err(GitError::TeamSharingFailed(format!(
+            "Failed to merge the audit notes of {}: {}",
+            remote,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(true)
+}
+
+/// Push `notes_ref` to `remote`, if there are audit notes
+///
+/// Fails if the remote has entries that were not merged yet.
+pub fn push(repo: &Repository, remote: &str, notes_ref: &str) -> GitResult<()> {
+    let Some(local) = team_ref::tip(repo, notes_ref)? else {
+        return Ok(());
+    };
+    team_ref::push_ref(repo, remote, notes_ref, "run 'cargocrypt team fetch' to merge the remote audit trail first", false)?;
+    repo.reference(&remote_ref(remote), local, true, "cargocrypt: push audit notes")?;
+    Ok(())
+}
+
+/// What the signature of an entry covers: the entry and the commit it is attached to
+fn signed_data(entry: &AuditEntry, commit: Oid) -> GitResult<Vec<u8>> {
+    let entry = serde_json::to_string(entry).map_err(serialization_error)?;
+    Ok(format!("{}\n{}", commit, entry).into_bytes())
+}
+
+fn verify(line: &NoteLine, commit: Oid, members: &[TeamMember]) -> AuditVerification {
+    if line.commit != commit.to_string() {
+        return AuditVerification::Invalid;
+    }
+    let Some(signature) = &line.signature else {
+        return AuditVerification::Unsigned;
+    };
+    let Ok(data) = signed_data(&line.entry, commit) else {
+        return AuditVerification::Invalid;
+    };
+    match team_ref::signed_by(&signature.field, signature.value.as_bytes(), &data, members) {
+        Some(member) => AuditVerification::Signed(member.id.clone()),
+        None => AuditVerification::Invalid,
+    }
+}
+
+/// Active members before and after `commit`, who may have signed its entries
+fn members_at(repo: &Repository, commit: Oid) -> GitResult<Vec<TeamMember>> {
+    let Ok(found) = repo.find_commit(commit) else {
+        return Ok(Vec::new());
+    };
+    let mut members = team_ref::active_members(repo, &team_ref::tree_files(repo, commit)?)?;
+    if let Some(parent) = found.parent_ids().next() {
+        members.extend(team_ref::active_members(repo, &team_ref::tree_files(repo, parent)?)?);
+    }
+    Ok(members)
+}
+
+fn serialization_error(e: serde_json::Error) -> GitError {
+    GitError::SerializationFailed(format!("Failed to serialize audit entry: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    const TEAM_REF: &str = "refs/cargocrypt/team";
+
+    fn entry(timestamp: u64, details: &str) -> AuditEntry {
+        AuditEntry {
+            timestamp,
+            operation: "member_addition".to_string(),
+            actor: "alice@example.com".to_string(),
+            details: details.to_string(),
+        }
+    }
+
+    fn team_commit(repo: &Repository, dir: &Path) -> Oid {
+        std::fs::create_dir_all(dir).unwrap();
+        std::fs::write(dir.join("config.toml"), "").unwrap();
+        let signature = Signature::now("test", "test@example.com").unwrap();
+        team_ref::commit_dir(repo, TEAM_REF, dir, "test", &signature, None, false).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_concurrent_entries_merge() {
+        let temp_dir = TempDir::new().unwrap();
+        let remote_path = temp_dir.path().join("remote.git");
+        Repository::init_bare(&remote_path).unwrap();
+        let remote_url = remote_path.to_str().unwrap();
+        let signature = Signature::now("test", "test@example.com").unwrap();
+
+        let alice = Repository::init(temp_dir.path().join("alice")).unwrap();
+        alice.remote("origin", remote_url).unwrap();
+        let bob = Repository::init(temp_dir.path().join("bob")).unwrap();
+        bob.remote("origin", remote_url).unwrap();
+        let commit = team_commit(&alice, &temp_dir.path().join("alice-team"));
+        team_ref::push(&alice, "origin", TEAM_REF).unwrap();
+        team_ref::fetch(&bob, "origin", TEAM_REF).unwrap();
+        team_ref::merge(&bob, TEAM_REF, commit, &signature, None, false).unwrap();
+        assert!(!fetch(&bob, "origin", AUDIT_NOTES_REF, &signature).unwrap());
+
+        // Both record an entry on the same commit
+        record(&alice, AUDIT_NOTES_REF, commit, &[entry(1, "Added carol")], &signature, None).unwrap();
+        record(&alice, AUDIT_NOTES_REF, commit, &[entry(3, "Added erin")], &signature, None).unwrap();
+        record(&bob, AUDIT_NOTES_REF, commit, &[entry(2, "Added dave")], &signature, None).unwrap();
+        push(&alice, "origin", AUDIT_NOTES_REF).unwrap();
+        assert!(push(&bob, "origin", AUDIT_NOTES_REF).is_err());
+
+        assert!(fetch(&bob, "origin", AUDIT_NOTES_REF, &signature).unwrap());
+        push(&bob, "origin", AUDIT_NOTES_REF).unwrap();
+        let mut details: Vec<String> = entries(&bob, AUDIT_NOTES_REF)
+            .unwrap()
+            .into_iter()
+            .inspect(|record| {
+                assert_eq!(record.commit, Some(commit));
+                assert_eq!(record.verification, AuditVerification::Unsigned);
+            })
+            .map(|record| record.entry.details)
+            .collect();
+        details.sort();
+        assert_eq!(details, ["Added carol", "Added dave", "Added erin"]);
+    }
+}
@@ -0,0 +1,379 @@
+//! Recovery escrow of the team's shared keys
+//!
+//! When the members holding a key leave abruptly, nobody may be left who can
+//! unwrap it. An Owner who holds the keys can seal them into a recovery
+//! bundle: the keys are encrypted under a random escrow key, which is split
+//! with Shamir secret sharing ([`crate::crypto::shamir`]) into one share per
+//! active Owner, each wrapped for that Owner's key. The bundle is committed
+//! to its own ref (`refs/cargocrypt/escrow` by default), signed like team
+//! changes, and travels with `cargocrypt team push` and `fetch`.
+//!
+//! To recover, a threshold of Owners unwrap their shares
+//! (`cargocrypt team escrow-share`) and hand them to whoever runs
+//! `cargocrypt team recover`, which opens the bundle and wraps the keys for
+//! the current members. A bundle is only opened if it was signed by an Owner
+//! of the team state it escrows.
+
+use super::team::{TeamIdentity, TeamMember, TeamRole};
+use super::team_ref::{self, TeamSigner};
+use super::{GitError, GitResult};
+use crate::crypto::shamir::{self, Share};
+use base64ct::{Base64, Encoding};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use git2::{Oid, Repository, Signature};
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// Default ref of the recovery bundle
+pub const ESCROW_REF: &str = "refs/cargocrypt/escrow";
+
+/// File of the bundle in the tree of the escrow ref
+const BUNDLE_FILE: &str = "escrow.json";
+
+const NONCE_LENGTH: usize = 12;
+
+/// A sealed recovery bundle, as committed to the escrow ref
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EscrowBundle {
+    /// When the bundle was sealed
+    pub created_at: u64,
+    /// Who sealed it
+    pub created_by: String,
+    /// Number of shares needed to open it
+    pub threshold: u8,
+    /// Team commit whose keys are escrowed
+    pub team_commit: String,
+    /// IDs of the escrowed keys
+    pub key_ids: Vec<String>,
+    /// Each Owner's share of the escrow key, wrapped for them
+    pub shares: BTreeMap<String, WrappedShare>,
+    /// Nonce and ciphertext of the escrowed keys (base64)
+    sealed: String,
+}
+
+/// An Owner's share as stored in the bundle
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WrappedShare {
+    pub index: u8,
+    /// The share value (hex), wrapped like a shared key
+    pub wrapped: String,
+}
+
+/// An unwrapped share, handed by its Owner to whoever runs the recovery
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EscrowShare {
+    /// Commit of the bundle the share opens
+    pub escrow: String,
+    /// Owner the share was wrapped for
+    pub owner: String,
+    #[serde(flatten)]
+    pub share: Share,
+}
+
+impl EscrowShare {
+    /// Write the share to `path`, readable by the owner only
+    pub fn save(&self, path: &Path) -> GitResult<()> {
+        use std::io::Write;
+
+        let mut json = serde_json::to_string_pretty(self)
+            .map_err(|e| GitError::SerializationFailed(format!("Failed to serialize escrow share: {}", e)))?;
+        json.push('\n');
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let result = options.open(path).and_then(|mut file| file.write_all(json.as_bytes()));
+        json.zeroize();
+        result.map_err(|e| GitError::TeamSharingFailed(format!("Failed to write {}: {}", path.display(), e)))
+    }
+
+    /// Read a share written by [`EscrowShare::save`]
+    pub fn load(path: &Path) -> GitResult<Self> {
+        let mut json = std::fs::read_to_string(path)
+            .map_err(|e| GitError::TeamSharingFailed(format!("Failed to read {}: {}", path.display(), e)))?;
+        let share = serde_json::from_str(&json)
+            .map_err(|e| GitError::TeamSharingFailed(format!("{} is not an escrow share: {}", path.display(), e)));
+        json.zeroize();
+        share
+    }
+}
+
+/// A shared key in the sealed part of the bundle
+#[derive(Clone, Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+pub struct EscrowedKey {
+    pub id: String,
+    pub purpose: String,
+    /// Key material (hex), as wrapped for members
+    pub key: String,
+}
+
+/// Seal `keys` into a bundle whose escrow key is split among `owners`
+///
+/// Any `threshold` of the owners can open it.
+pub fn seal(
+    keys: &[EscrowedKey],
+    owners: &[TeamMember],
+    threshold: u8,
+    created_by: &str,
+    team_commit: Oid,
+) -> GitResult<EscrowBundle> {
+    let count = u8::try_from(owners.len())
+        .map_err(|_| GitError::TeamSharingFailed("Escrow supports at most 255 Owners".to_string()))?;
+    let mut plaintext = serde_json::to_vec(keys)
+        .map_err(|e| GitError::SerializationFailed(format!("Failed to serialize escrowed keys: {}", e)))?;
+    let mut escrow_key = [0u8; 32];
+    OsRng.fill_bytes(&mut escrow_key);
+    let shares = shamir::split(&escrow_key, threshold, count);
+
+    let mut nonce = [0u8; NONCE_LENGTH];
+    OsRng.fill_bytes(&mut nonce);
+    let aad = team_commit.to_string();
+    let ciphertext = ChaCha20Poly1305::new(Key::from_slice(&escrow_key))
+        .encrypt(Nonce::from_slice(&nonce), Payload { msg: &plaintext, aad: aad.as_bytes() });
+    plaintext.zeroize();
+    escrow_key.zeroize();
+    let ciphertext = ciphertext.map_err(|_| GitError::TeamSharingFailed("Failed to seal the escrowed keys".to_string()))?;
+    let sealed = Base64::encode_string(&[nonce.as_slice(), &ciphertext].concat());
+    let shares = shares.map_err(|e| GitError::TeamSharingFailed(format!("Failed to split the escrow key: {}", e)))?;
+
+    let mut wrapped_shares = BTreeMap::new();
+    for (owner, share) in owners.iter().zip(shares) {
+        let mut value = hex::encode(&share.value);
+        let wrapped = owner.wrap_key(value.as_bytes());
+        value.zeroize();
+        let wrapped = wrapped
+            .map_err(|e| GitError::TeamSharingFailed(format!("Failed to wrap the escrow share of {}: {}", owner.id, e)))?;
+        wrapped_shares.insert(owner.id.clone(), WrappedShare { index: share.index, wrapped });
+    }
+
+    Ok(EscrowBundle {
+        created_at: crate::utils::unix_now(),
+        created_by: created_by.to_string(),
+        threshold,
+        team_commit: team_commit.to_string(),
+        key_ids: keys.iter().map(|key| key.id.clone()).collect(),
+        shares: wrapped_shares,
+        sealed,
+    })
+}
+
+/// Commit `bundle` onto `escrow_ref`, signed by `signer` if given
+///
+/// With `verify`, the commit must be signed by an Owner of the team state
+/// the bundle escrows, or the ref is left alone.
+pub fn commit(
+    repo: &Repository,
+    escrow_ref: &str,
+    bundle: &EscrowBundle,
+    signature: &Signature,
+    signer: Option<&TeamSigner>,
+    verify: bool,
+) -> GitResult<Oid> {
+    let json = serde_json::to_string_pretty(bundle)
+        .map_err(|e| GitError::SerializationFailed(format!("Failed to serialize escrow bundle: {}", e)))?;
+    let mut files = team_ref::Files::new();
+    files.insert(BUNDLE_FILE.to_string(), repo.blob(json.as_bytes())?);
+    let tree = repo.find_tree(team_ref::write_tree(repo, &files)?)?;
+    let parent = team_ref::tip(repo, escrow_ref)?.map(|oid| repo.find_commit(oid)).transpose()?;
+    let parents: Vec<&git2::Commit> = parent.iter().collect();
+    let message = format!("CargoCrypt: Escrow {} team key(s)", bundle.key_ids.len());
+
+    let oid = team_ref::write_commit(repo, &message, signature, &tree, &parents, signer)?;
+    if verify {
+        verify_signer(repo, oid, bundle)?;
+    }
+    match parents.first() {
+        Some(parent) => repo.reference_matching(escrow_ref, oid, true, parent.id(), &message)?,
+        None => repo.reference(escrow_ref, oid, false, &message)?,
+    };
+    Ok(oid)
+}
+
+/// The bundle at the tip of `escrow_ref` and its commit, if there is one
+pub fn load(repo: &Repository, escrow_ref: &str) -> GitResult<Option<(Oid, EscrowBundle)>> {
+    let Some(tip) = team_ref::tip(repo, escrow_ref)? else {
+        return Ok(None);
+    };
+    let files = team_ref::tree_files(repo, tip)?;
+    let blob = files
+        .get(BUNDLE_FILE)
+        .ok_or_else(|| GitError::TeamSharingFailed(format!("{} holds no {}", escrow_ref, BUNDLE_FILE)))?;
+    let bundle = serde_json::from_slice(repo.find_blob(*blob)?.content())
+        .map_err(|e| GitError::TeamSharingFailed(format!("Failed to parse the escrow bundle: {}", e)))?;
+    Ok(Some((tip, bundle)))
+}
+
+/// Check that the bundle committed as `commit` was signed by an Owner of the
+/// team state it escrows; returns that Owner
+pub fn verify_signer(repo: &Repository, commit: Oid, bundle: &EscrowBundle) -> GitResult<String> {
+    let team_commit = Oid::from_str(&bundle.team_commit)
+        .map_err(|_| GitError::TeamSharingFailed(format!("Invalid team commit {}", bundle.team_commit)))?;
+    let mut owners = team_ref::active_members(repo, &team_ref::tree_files(repo, team_commit)?)?;
+    owners.retain(|member| member.role == TeamRole::Owner);
+    team_ref::commit_signer(repo, commit, &owners)
+        .map(|owner| owner.id.clone())
+        .ok_or_else(|| {
+            GitError::TeamSharingFailed(format!(
+                "Escrow bundle {} is not signed by an Owner of the team state it escrows",
+                commit
+            ))
+        })
+}
+
+/// Unwrap the share of `bundle` (committed as `commit`) wrapped for `identity`
+pub fn unwrap_share(commit: Oid, bundle: &EscrowBundle, identity: &TeamIdentity) -> GitResult<EscrowShare> {
+    for (owner, wrapped) in &bundle.shares {
+        let Ok(mut value) = identity.unwrap_key(&wrapped.wrapped) else {
+            continue;
+        };
+        let decoded = hex::decode(&value);
+        value.zeroize();
+        let value = decoded.map_err(|_| GitError::TeamSharingFailed(format!("Escrow share of {} is corrupt", owner)))?;
+        return Ok(EscrowShare {
+            escrow: commit.to_string(),
+            owner: owner.clone(),
+            share: Share { index: wrapped.index, value },
+        });
+    }
+    Err(GitError::TeamSharingFailed(
+        "The escrow bundle holds no share for this identity; only Owners at the time it was sealed have one".to_string(),
+    ))
+}
+
+/// Open `bundle` (committed as `commit`) with a threshold of its shares
+pub fn open(commit: Oid, bundle: &EscrowBundle, shares: &[EscrowShare]) -> GitResult<Vec<EscrowedKey>> {
+    let mut picked: Vec<Share> = Vec::new();
+    for share in shares {
+        if share.escrow != commit.to_string() {
+            return Err(GitError::TeamSharingFailed(format!(
+                "The share of {} belongs to escrow bundle {:.7}, not the current {:.7}",
+                share.owner, share.escrow, commit
+            )));
+        }
+        if !picked.iter().any(|other| other.index == share.share.index) {
+            picked.push(share.share.clone());
+        }
+    }
+    if picked.len() < bundle.threshold as usize {
+        return Err(GitError::TeamSharingFailed(format!(
+            "Recovery needs shares of {} Owners, got {}",
+            bundle.threshold,
+            picked.len()
+        )));
+    }
+
+    let mut escrow_key = shamir::combine(&picked)
+        .map_err(|e| GitError::TeamSharingFailed(format!("Failed to combine the escrow shares: {}", e)))?;
+    let sealed = Base64::decode_vec(&bundle.sealed)
+        .ok()
+        .filter(|sealed| sealed.len() > NONCE_LENGTH)
+        .ok_or_else(|| GitError::TeamSharingFailed("The escrow bundle is corrupt".to_string()));
+    let plaintext = sealed.and_then(|sealed| {
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LENGTH);
+        ChaCha20Poly1305::new(Key::from_slice(&escrow_key))
+            .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: bundle.team_commit.as_bytes() })
+            .map_err(|_| GitError::TeamSharingFailed("The escrow shares do not open the bundle".to_string()))
+    });
+    escrow_key.zeroize();
+    let mut plaintext = plaintext?;
+    let keys = serde_json::from_slice(&plaintext)
+        .map_err(|e| GitError::TeamSharingFailed(format!("Failed to parse the escrowed keys: {}", e)));
+    plaintext.zeroize();
+    keys
+}
+
+/// Ref the escrow bundle of `remote` is fetched into
+pub fn remote_ref(remote: &str) -> String {
+    format!("refs/cargocrypt/remotes/{}/escrow", remote)
+}
+
+/// Fetch the escrow bundle of `remote`, taking it if it is newer than ours
+///
+/// Bundles are replaced, not merged: when both sides sealed a new bundle,
+/// ours is kept. Returns whether the local bundle changed.
+pub fn fetch(repo: &Repository, remote: &str, escrow_ref: &str) -> GitResult<bool> {
+    let tracking = remote_ref(remote);
+    if !team_ref::fetch_ref(repo, remote, escrow_ref, &tracking)? {
+        return Ok(false);
+    }
+    let Some(theirs) = team_ref::tip(repo, &tracking)? else {
+        return Ok(false);
+    };
+    let newer = match team_ref::tip(repo, escrow_ref)? {
+        None => true,
+        Some(ours) => ours != theirs && repo.graph_descendant_of(theirs, ours)?,
+    };
+    if newer {
+        repo.reference(escrow_ref, theirs, true, "cargocrypt: fetch escrow bundle")?;
+    }
+    Ok(newer)
+}
+
+/// Push `escrow_ref` to `remote`, if there is a bundle
+pub fn push(repo: &Repository, remote: &str, escrow_ref: &str) -> GitResult<()> {
+    let Some(local) = team_ref::tip(repo, escrow_ref)? else {
+        return Ok(());
+    };
+    team_ref::push_ref(
+        repo,
+        remote,
+        escrow_ref,
+        "another Owner sealed a bundle meanwhile: run 'cargocrypt team fetch' and seal a new one",
+        false,
+    )?;
+    repo.reference(&remote_ref(remote), local, true, "cargocrypt: push escrow bundle")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::RecipientKeyPair;
+
+    fn owner(id: &str, identity: &RecipientKeyPair) -> TeamMember {
+        TeamMember::new(id.to_string(), identity.public_key_hex(), String::new(), TeamRole::Owner, "system".to_string())
+    }
+
+    #[test]
+    fn test_threshold_of_owners_opens_the_bundle() {
+        let identities: Vec<RecipientKeyPair> = (0..3).map(|_| RecipientKeyPair::generate()).collect();
+        let owners: Vec<TeamMember> = ["alice", "bob", "carol"]
+            .iter()
+            .zip(&identities)
+            .map(|(id, identity)| owner(id, identity))
+            .collect();
+        let keys = vec![EscrowedKey {
+            id: "0123".to_string(),
+            purpose: "team_key".to_string(),
+            key: "ab".repeat(32),
+        }];
+        let team_commit = Oid::from_str("1111111111111111111111111111111111111111").unwrap();
+        let commit = Oid::from_str("2222222222222222222222222222222222222222").unwrap();
+        let bundle = seal(&keys, &owners, 2, "alice", team_commit).unwrap();
+        assert_eq!(bundle.shares.len(), 3);
+        assert!(!bundle.sealed.contains(&keys[0].key));
+
+        let shares: Vec<EscrowShare> = identities
+            .iter()
+            .map(|identity| unwrap_share(commit, &bundle, &TeamIdentity::from(identity.clone())).unwrap())
+            .collect();
+        assert_eq!(shares[1].owner, "bob");
+        let opened = open(commit, &bundle, &shares[1..]).unwrap();
+        assert_eq!(opened[0].key, keys[0].key);
+
+        // One Owner alone, or a share of another bundle, opens nothing
+        assert!(open(commit, &bundle, &shares[..1]).is_err());
+        assert!(open(commit, &bundle, &[shares[0].clone(), shares[0].clone()]).is_err());
+        let other = Oid::from_str("3333333333333333333333333333333333333333").unwrap();
+        assert!(open(other, &bundle, &shares).is_err());
+        let outsider = TeamIdentity::from(RecipientKeyPair::generate());
+        assert!(unwrap_share(commit, &bundle, &outsider).is_err());
+    }
+}
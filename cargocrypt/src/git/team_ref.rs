@@ -0,0 +1,716 @@
+//! Team state stored as git objects
+//!
+//! Members and wrapped keys are committed to the team ref
+//! (`refs/cargocrypt/team` by default) instead of the working branch, so key
+//! state travels with the repository when that ref is fetched and pushed.
+//! The audit trail is kept in git notes on these commits (see
+//! [`team_audit`](super::team_audit)).
+//! `.cargocrypt/team` is a checkout of the ref that
+//! [`TeamKeySharing`](super::TeamKeySharing) reads and writes; every change
+//! is committed back to the ref.
+//!
+//! When two clones changed the team independently, [`merge`] combines them
+//! file by file against their merge base. Where both sides changed the same
+//! file, removals win (a removed member or an archived key stays gone),
+//! deactivated members stay deactivated, the wrapped copies of a key are
+//! united and the audit logs of earlier versions are interleaved. Afterwards, wrapped copies for
+//! members who are no longer active are dropped from the current keys.
+//!
+//! Commits can be signed by a [`TeamSigner`]: with Ed25519 in a
+//! `cargocrypt-sig` commit header, or with GPG in the standard `gpgsig`
+//! header. [`verify_chain`] checks that every commit was signed by an active
+//! Owner or Admin of the state it was made on (its first parent). Only a
+//! commit on a state without members needs no signature, which lets the
+//! first Owner sign the commit that adds them.
+
+use super::team::{SharedKey, TeamMember, TeamRole};
+use super::{GitError, GitResult};
+use crate::crypto::{openpgp, DetachedSignature, SigningKeyPair};
+use git2::{ErrorCode, ObjectType, Oid, Repository, Signature, TreeWalkMode, TreeWalkResult};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::path::Path;
+use std::process::{Command, Output};
+
+/// Files of a team tree: path relative to the team directory -> blob
+pub(super) type Files = BTreeMap<String, Oid>;
+
+/// Commit header holding Ed25519 signatures
+pub const SIGNATURE_FIELD: &str = "cargocrypt-sig";
+
+/// Commit header holding GPG signatures
+const GPG_SIGNATURE_FIELD: &str = "gpgsig";
+
+/// Key that signs team changes
+#[derive(Debug, Clone)]
+pub enum TeamSigner {
+    /// An Ed25519 key, matched against members' `signing_key`
+    Ed25519(SigningKeyPair),
+    /// A GPG secret key by fingerprint, matched against members' `gpg_fingerprint`
+    Gpg(String),
+}
+
+impl TeamSigner {
+    /// Signature of `data` and the commit header it goes in
+    pub(super) fn sign(&self, data: &[u8]) -> GitResult<(String, &'static str)> {
+        let signed = match self {
+            TeamSigner::Ed25519(keypair) => serde_json::to_string(&keypair.sign(data))
+                .map(|signature| (signature, SIGNATURE_FIELD))
+                .map_err(|e| e.to_string()),
+            TeamSigner::Gpg(fingerprint) => openpgp::sign(data, fingerprint)
+                .map(|signature| (signature, GPG_SIGNATURE_FIELD))
+                .map_err(|e| e.to_string()),
+        };
+        signed.map_err(|e| GitError::TeamSharingFailed(format!("Failed to sign team change: {}", e)))
+    }
+}
+
+impl From<SigningKeyPair> for TeamSigner {
+    fn from(keypair: SigningKeyPair) -> Self {
+        TeamSigner::Ed25519(keypair)
+    }
+}
+
+/// Outcome of bringing in the state of a remote (team or storage ref)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyncOutcome {
+    /// The remote has no such ref yet
+    NoRemoteState,
+    /// The local state already contains the remote's
+    UpToDate,
+    /// The local state moved forward to the remote's
+    FastForward,
+    /// Both sides changed; these files needed resolving
+    Merged { resolved: Vec<String> },
+}
+
+/// Ref the team state of `remote` is fetched into
+pub fn remote_ref(remote: &str) -> String {
+    format!("refs/cargocrypt/remotes/{}/team", remote)
+}
+
+/// Commit at the tip of `team_ref`, if the ref exists
+pub fn tip(repo: &Repository, team_ref: &str) -> GitResult<Option<Oid>> {
+    match repo.find_reference(team_ref) {
+        Ok(reference) => Ok(Some(reference.peel_to_commit()?.id())),
+        Err(e) if e.code() == ErrorCode::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Commit the files under `dir` onto `team_ref`, signed by `signer` if given
+///
+/// With `verify`, the commit must pass [`verify_chain`]'s check or the ref
+/// is left alone. Returns `None` when the files match the tip already.
+pub fn commit_dir(
+    repo: &Repository,
+    team_ref: &str,
+    dir: &Path,
+    message: &str,
+    signature: &Signature,
+    signer: Option<&TeamSigner>,
+    verify: bool,
+) -> GitResult<Option<Oid>> {
+    let tree = write_dir(repo, dir)?;
+    let parent = tip(repo, team_ref)?.map(|oid| repo.find_commit(oid)).transpose()?;
+    if parent.as_ref().map(|commit| commit.tree_id()) == Some(tree) {
+        return Ok(None);
+    }
+    let tree = repo.find_tree(tree)?;
+    let parents: Vec<&git2::Commit> = parent.iter().collect();
+    Ok(Some(create_commit(repo, team_ref, message, signature, &tree, &parents, signer, verify)?))
+}
+
+/// Write the tree at the tip of `team_ref` to `dir`
+///
+/// Files in `dir` that are not in the tree are removed. Returns `false`,
+/// leaving `dir` alone, if the ref does not exist or holds an empty tree
+/// (as created by earlier versions, which kept the state in `dir` only).
+pub fn checkout(repo: &Repository, team_ref: &str, dir: &Path) -> GitResult<bool> {
+    let Some(tip) = tip(repo, team_ref)? else {
+        return Ok(false);
+    };
+    let files = tree_files(repo, tip)?;
+    if files.is_empty() {
+        return Ok(false);
+    }
+
+    let io_error = |e: std::io::Error| GitError::TeamSharingFailed(format!("Failed to check out team state: {}", e));
+    for stale in list_files(dir)?.into_iter().filter(|path| !files.contains_key(path)) {
+        std::fs::remove_file(dir.join(stale)).map_err(io_error)?;
+    }
+    for (path, oid) in &files {
+        let target = dir.join(path);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent).map_err(io_error)?;
+        }
+        std::fs::write(&target, repo.find_blob(*oid)?.content()).map_err(io_error)?;
+    }
+    Ok(true)
+}
+
+/// Merge the team state at `theirs` into `team_ref`
+///
+/// A merge commit is only needed, and signed by `signer`, when both sides
+/// changed; `verify` checks it like [`commit_dir`] does.
+pub fn merge(
+    repo: &Repository,
+    team_ref: &str,
+    theirs: Oid,
+    signature: &Signature,
+    signer: Option<&TeamSigner>,
+    verify: bool,
+) -> GitResult<SyncOutcome> {
+    let Some(ours) = tip(repo, team_ref)? else {
+        repo.reference(team_ref, theirs, true, "cargocrypt: fetch team state")?;
+        return Ok(SyncOutcome::FastForward);
+    };
+    if ours == theirs || repo.graph_descendant_of(ours, theirs)? {
+        return Ok(SyncOutcome::UpToDate);
+    }
+    if repo.graph_descendant_of(theirs, ours)? {
+        repo.reference(team_ref, theirs, true, "cargocrypt: fast-forward team state")?;
+        return Ok(SyncOutcome::FastForward);
+    }
+
+    // Unrelated histories (two clones initialized the team separately) merge against nothing
+    let base = match repo.merge_base(ours, theirs) {
+        Ok(base) => tree_files(repo, base)?,
+        Err(e) if e.code() == ErrorCode::NotFound => Files::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let (our_files, their_files) = (tree_files(repo, ours)?, tree_files(repo, theirs)?);
+    let paths: BTreeSet<&String> = base.keys().chain(our_files.keys()).chain(their_files.keys()).collect();
+
+    let mut merged = Files::new();
+    let mut resolved = Vec::new();
+    for path in paths {
+        let (b, o, t) = (base.get(path), our_files.get(path), their_files.get(path));
+        let result = if o == t || t == b {
+            o.copied()
+        } else if o == b {
+            t.copied()
+        } else {
+            resolved.push(path.clone());
+            match (o, t) {
+                (Some(o), Some(t)) => Some(resolve(repo, path, *o, *t)?),
+                // Deleted on one side: removals win
+                _ => None,
+            }
+        };
+        if let Some(oid) = result {
+            merged.insert(path.clone(), oid);
+        }
+    }
+    for path in drop_inactive_recipients(repo, &mut merged)? {
+        if !resolved.contains(&path) {
+            resolved.push(path);
+        }
+    }
+
+    let tree = repo.find_tree(write_tree(repo, &merged)?)?;
+    let (our_commit, their_commit) = (repo.find_commit(ours)?, repo.find_commit(theirs)?);
+    create_commit(
+        repo,
+        team_ref,
+        "CargoCrypt: Merge remote team state",
+        signature,
+        &tree,
+        &[&our_commit, &their_commit],
+        signer,
+        verify,
+    )?;
+    Ok(SyncOutcome::Merged { resolved })
+}
+
+/// Check the signatures of every commit reachable from `tip`
+///
+/// With `root`, the history must also start at that commit, so a team ref
+/// rewritten from scratch is refused. Returns the root commit.
+pub fn verify_chain(repo: &Repository, tip: Oid, root: Option<Oid>) -> GitResult<Oid> {
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push(tip)?;
+    let mut found_root = None;
+    for oid in revwalk {
+        let commit = repo.find_commit(oid?)?;
+        verify_commit(repo, &commit)?;
+        if commit.parent_count() == 0 {
+            if root.is_some_and(|root| root != commit.id()) || found_root.is_some() {
+                return Err(GitError::TeamSharingFailed(format!(
+                    "Team history starts at {} instead of the trusted {}; the team ref was replaced",
+                    commit.id(),
+                    root.or(found_root).expect("a root to compare with")
+                )));
+            }
+            found_root = Some(commit.id());
+        }
+    }
+    found_root.ok_or_else(|| GitError::TeamSharingFailed("Team history has no root commit".to_string()))
+}
+
+/// Check that `commit` was signed by an Owner or Admin of the state it was made on
+fn verify_commit(repo: &Repository, commit: &git2::Commit) -> GitResult<()> {
+    let mut authorities = match commit.parent_ids().next() {
+        Some(parent) => admins(repo, &tree_files(repo, parent)?)?,
+        None => Vec::new(),
+    };
+    if authorities.is_empty() {
+        authorities = admins(repo, &tree_files(repo, commit.id())?)?;
+    }
+    if authorities.is_empty() {
+        return Ok(());
+    }
+
+    if commit_signer(repo, commit.id(), &authorities).is_none() {
+        return Err(GitError::TeamSharingFailed(format!(
+            "Team change {} ({}) is not signed by an Owner or Admin",
+            commit.id(),
+            commit.summary().unwrap_or_default()
+        )));
+    }
+    Ok(())
+}
+
+/// The one of `candidates` who signed `commit`, in either signature header
+pub(super) fn commit_signer<'a>(repo: &Repository, commit: Oid, candidates: &'a [TeamMember]) -> Option<&'a TeamMember> {
+    match repo.extract_signature(&commit, Some(SIGNATURE_FIELD)) {
+        Ok((signature, data)) => signed_by(SIGNATURE_FIELD, &signature, &data, candidates),
+        Err(_) => match repo.extract_signature(&commit, None) {
+            Ok((signature, data)) => signed_by(GPG_SIGNATURE_FIELD, &signature, &data, candidates),
+            Err(_) => None,
+        },
+    }
+}
+
+/// The one of `candidates` who signed `data`, with a signature as [`TeamSigner`] writes to the header `field`
+pub(super) fn signed_by<'a>(
+    field: &str,
+    signature: &[u8],
+    data: &[u8],
+    candidates: &'a [TeamMember],
+) -> Option<&'a TeamMember> {
+    if field == SIGNATURE_FIELD {
+        let signature: DetachedSignature = serde_json::from_slice(signature).ok()?;
+        signature.verify(data).ok()?;
+        let signer = hex::encode(signature.public_key);
+        candidates.iter().find(|member| member.signing_key.eq_ignore_ascii_case(&signer))
+    } else {
+        let signer = openpgp::verify(data, &String::from_utf8_lossy(signature)).ok()?;
+        candidates
+            .iter()
+            .find(|member| member.gpg_fingerprint.as_deref().is_some_and(|own| own.eq_ignore_ascii_case(&signer)))
+    }
+}
+
+/// Active Owners and Admins in `files`
+fn admins(repo: &Repository, files: &Files) -> GitResult<Vec<TeamMember>> {
+    let mut admins = active_members(repo, files)?;
+    admins.retain(|member| matches!(member.role, TeamRole::Owner | TeamRole::Admin));
+    Ok(admins)
+}
+
+/// Active members in `files`
+pub(super) fn active_members(repo: &Repository, files: &Files) -> GitResult<Vec<TeamMember>> {
+    let mut members = Vec::new();
+    for (path, oid) in files {
+        if path.starts_with("members/") {
+            if let Ok(member) = serde_json::from_slice::<TeamMember>(repo.find_blob(*oid)?.content()) {
+                if member.active {
+                    members.push(member);
+                }
+            }
+        }
+    }
+    Ok(members)
+}
+
+/// Commit `tree` onto `team_ref`, whose tip must be the first of `parents`
+///
+/// With `verify`, the commit is checked before the ref moves, so a change
+/// without the signature of an Owner or Admin is never recorded.
+#[allow(clippy::too_many_arguments)]
+fn create_commit(
+    repo: &Repository,
+    team_ref: &str,
+    message: &str,
+    signature: &Signature,
+    tree: &git2::Tree,
+    parents: &[&git2::Commit],
+    signer: Option<&TeamSigner>,
+    verify: bool,
+) -> GitResult<Oid> {
+    let oid = write_commit(repo, message, signature, tree, parents, signer)?;
+    if verify {
+        verify_commit(repo, &repo.find_commit(oid)?)?;
+    }
+
+    match parents.first() {
+        Some(parent) => repo.reference_matching(team_ref, oid, true, parent.id(), message)?,
+        None => repo.reference(team_ref, oid, false, message)?,
+    };
+    Ok(oid)
+}
+
+/// Write a commit of `tree` without moving any ref, signed by `signer` if given
+pub(super) fn write_commit(
+    repo: &Repository,
+    message: &str,
+    signature: &Signature,
+    tree: &git2::Tree,
+    parents: &[&git2::Commit],
+    signer: Option<&TeamSigner>,
+) -> GitResult<Oid> {
+    match signer {
+        Some(signer) => {
+            let buffer = repo.commit_create_buffer(signature, signature, message, tree, parents)?;
+            let content = buffer
+                .as_str()
+                .ok_or_else(|| GitError::TeamSharingFailed("Team commit is not valid UTF-8".to_string()))?;
+            let (commit_signature, field) = signer.sign(content.as_bytes())?;
+            Ok(repo.commit_signed(content, &commit_signature, Some(field))?)
+        }
+        None => Ok(repo.commit(None, signature, signature, message, tree, parents)?),
+    }
+}
+
+/// Fetch the team ref of `remote` into [`remote_ref`], returning its tip
+pub fn fetch(repo: &Repository, remote: &str, team_ref: &str) -> GitResult<Option<Oid>> {
+    let tracking = remote_ref(remote);
+    if !fetch_ref(repo, remote, team_ref, &tracking)? {
+        return Ok(None);
+    }
+    tip(repo, &tracking)
+}
+
+/// Push `team_ref` to `remote`
+///
+/// Fails if the remote has changes that were not merged yet.
+pub fn push(repo: &Repository, remote: &str, team_ref: &str) -> GitResult<()> {
+    let Some(local) = tip(repo, team_ref)? else {
+        return Err(GitError::TeamSharingFailed("No team state to push; add a team member first".to_string()));
+    };
+    push_ref(repo, remote, team_ref, "run 'cargocrypt team fetch' to merge the remote team state first", false)?;
+    repo.reference(&remote_ref(remote), local, true, "cargocrypt: push team state")?;
+    Ok(())
+}
+
+/// Fetch `git_ref` of `remote` into `tracking`; false if the remote has no such ref
+pub(super) fn fetch_ref(repo: &Repository, remote: &str, git_ref: &str, tracking: &str) -> GitResult<bool> {
+    let output = git(repo, &["fetch", "--no-tags", remote, &format!("+{}:{}", git_ref, tracking)])?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("couldn't find remote ref") {
+            return Ok(false);
+        }
+        return Err(GitError::TeamSharingFailed(format!("git fetch {} failed: {}", remote, stderr.trim())));
+    }
+    Ok(true)
+}
+
+/// Push `git_ref` to the same ref of `remote`
+///
+/// `hint` says what to do when the remote rejects it for having moved on;
+/// `force` overwrites the remote ref instead.
+pub(super) fn push_ref(repo: &Repository, remote: &str, git_ref: &str, hint: &str, force: bool) -> GitResult<()> {
+    let refspec = format!("{}{1}:{1}", if force { "+" } else { "" }, git_ref);
+    let output = git(repo, &["push", remote, &refspec])?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let hint = if stderr.contains("rejected") { format!("; {}", hint) } else { String::new() };
+        return Err(GitError::TeamSharingFailed(format!("git push {} failed: {}{}", remote, stderr.trim(), hint)));
+    }
+    Ok(())
+}
+
+/// Resolve a file both sides changed
+fn resolve(repo: &Repository, path: &str, ours: Oid, theirs: Oid) -> GitResult<Oid> {
+    let (our_blob, their_blob) = (repo.find_blob(ours)?, repo.find_blob(theirs)?);
+    let (our_content, their_content) = (our_blob.content(), their_blob.content());
+
+    if path.starts_with("members/") {
+        let parse = |content: &[u8]| serde_json::from_slice::<TeamMember>(content).ok();
+        if let (Some(our_member), Some(their_member)) = (parse(our_content), parse(their_content)) {
+            if our_member.active && !their_member.active {
+                return Ok(theirs);
+            }
+        }
+        return Ok(ours);
+    }
+
+    if path.starts_with("keys/") && !path.starts_with("keys/archived/") {
+        let parse = |content: &[u8]| serde_json::from_slice::<SharedKey>(content).ok();
+        if let (Some(mut key), Some(their_key)) = (parse(our_content), parse(their_content)) {
+            for (member_id, wrapped) in their_key.encrypted_for_members {
+                key.encrypted_for_members.entry(member_id).or_insert(wrapped);
+            }
+            return write_json(repo, &key);
+        }
+        return Ok(ours);
+    }
+
+    if path == "audit.log" {
+        let our_log = String::from_utf8_lossy(our_content);
+        let their_log = String::from_utf8_lossy(their_content);
+        let seen: HashSet<&str> = our_log.lines().collect();
+        let mut lines: Vec<&str> = our_log.lines().collect();
+        lines.extend(their_log.lines().filter(|line| !seen.contains(line)));
+        // Entries start with their timestamp
+        lines.sort_by_key(|line| line.split(" | ").next().and_then(|ts| ts.parse::<u64>().ok()).unwrap_or(0));
+        let mut log = lines.join("\n");
+        log.push('\n');
+        return Ok(repo.blob(log.as_bytes())?);
+    }
+
+    Ok(ours)
+}
+
+/// Drop wrapped copies of current keys for members who are gone or inactive
+///
+/// Returns the keys that changed.
+fn drop_inactive_recipients(repo: &Repository, files: &mut Files) -> GitResult<Vec<String>> {
+    let mut active = HashSet::new();
+    for (path, oid) in files.iter() {
+        if path.starts_with("members/") {
+            if let Ok(member) = serde_json::from_slice::<TeamMember>(repo.find_blob(*oid)?.content()) {
+                if member.active {
+                    active.insert(member.id);
+                }
+            }
+        }
+    }
+
+    let mut changed = Vec::new();
+    for (path, oid) in files.iter_mut() {
+        if !path.starts_with("keys/") || path.starts_with("keys/archived/") {
+            continue;
+        }
+        let Ok(mut key) = serde_json::from_slice::<SharedKey>(repo.find_blob(*oid)?.content()) else {
+            continue;
+        };
+        let before = key.encrypted_for_members.len();
+        key.encrypted_for_members.retain(|member_id, _| active.contains(member_id));
+        if key.encrypted_for_members.len() != before {
+            *oid = write_json(repo, &key)?;
+            changed.push(path.clone());
+        }
+    }
+    Ok(changed)
+}
+
+fn write_json<T: serde::Serialize>(repo: &Repository, value: &T) -> GitResult<Oid> {
+    let json = serde_json::to_string_pretty(value)
+        .map_err(|e| GitError::TeamSharingFailed(format!("Failed to serialize team file: {}", e)))?;
+    Ok(repo.blob(json.as_bytes())?)
+}
+
+/// Write the files under `dir` as a tree
+fn write_dir(repo: &Repository, dir: &Path) -> GitResult<Oid> {
+    let mut files = Files::new();
+    for path in list_files(dir)? {
+        let content = std::fs::read(dir.join(&path))
+            .map_err(|e| GitError::TeamSharingFailed(format!("Failed to read team file {}: {}", path, e)))?;
+        files.insert(path, repo.blob(&content)?);
+    }
+    write_tree(repo, &files)
+}
+
+/// Files of the tree of `commit`
+pub(super) fn tree_files(repo: &Repository, commit: Oid) -> GitResult<Files> {
+    let mut files = Files::new();
+    repo.find_commit(commit)?.tree()?.walk(TreeWalkMode::PreOrder, |root, entry| {
+        if entry.kind() == Some(ObjectType::Blob) {
+            if let Some(name) = entry.name() {
+                files.insert(format!("{}{}", root, name), entry.id());
+            }
+        }
+        TreeWalkResult::Ok
+    })?;
+    Ok(files)
+}
+
+/// Write nested trees for `files`
+pub(super) fn write_tree(repo: &Repository, files: &Files) -> GitResult<Oid> {
+    let mut builder = repo.treebuilder(None)?;
+    let mut subdirs: BTreeMap<&str, Files> = BTreeMap::new();
+    for (path, oid) in files {
+        match path.split_once('/') {
+            Some((dir, rest)) => {
+                subdirs.entry(dir).or_default().insert(rest.to_string(), *oid);
+            }
+            None => {
+                builder.insert(path, *oid, git2::FileMode::Blob.into())?;
+            }
+        }
+    }
+    for (dir, files) in subdirs {
+        let oid = write_tree(repo, &files)?;
+        builder.insert(dir, oid, git2::FileMode::Tree.into())?;
+    }
+    Ok(builder.write()?)
+}
+
+/// Paths of the regular files under `dir`, relative and `/`-separated
+fn list_files(dir: &Path) -> GitResult<Vec<String>> {
+    fn walk(dir: &Path, prefix: &str, files: &mut Vec<String>) -> std::io::Result<()> {
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                walk(&entry.path(), &format!("{}{}/", prefix, name), files)?;
+            } else if file_type.is_file() {
+                files.push(format!("{}{}", prefix, name));
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = Vec::new();
+    if dir.exists() {
+        walk(dir, "", &mut files)
+            .map_err(|e| GitError::TeamSharingFailed(format!("Failed to read team directory: {}", e)))?;
+    }
+    Ok(files)
+}
+
+/// Run git against `repo`, with the user's credentials and remotes configuration
+pub(super) fn git(repo: &Repository, args: &[&str]) -> GitResult<Output> {
+    Command::new("git")
+        .arg("--git-dir")
+        .arg(repo.path())
+        .args(args)
+        .output()
+        .map_err(|e| GitError::TeamSharingFailed(format!("Failed to run git: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::team::{KeyMetadata, TeamRole};
+    use std::collections::HashMap;
+    use tempfile::TempDir;
+
+    const TEAM_REF: &str = "refs/cargocrypt/team";
+
+    fn member(id: &str, active: bool) -> String {
+        let mut member = TeamMember::new(id.to_string(), "pk".to_string(), String::new(), TeamRole::Member, "system".to_string());
+        member.active = active;
+        serde_json::to_string_pretty(&member).unwrap()
+    }
+
+    fn key(members: &[&str]) -> String {
+        let key = SharedKey {
+            id: "k1".to_string(),
+            encrypted_for_members: members.iter().map(|id| (id.to_string(), format!("wrapped-{}", id))).collect::<HashMap<_, _>>(),
+            metadata: KeyMetadata {
+                created_at: 1,
+                created_by: "system".to_string(),
+                purpose: "test".to_string(),
+                algorithm: "ChaCha20-Poly1305".to_string(),
+                expires_at: None,
+            },
+            signature: String::new(),
+        };
+        serde_json::to_string_pretty(&key).unwrap()
+    }
+
+    fn commit(repo: &Repository, dir: &Path, files: &[(&str, String)]) -> Oid {
+        let _ = std::fs::remove_dir_all(dir);
+        for (path, content) in files {
+            std::fs::create_dir_all(dir.join(path).parent().unwrap()).unwrap();
+            std::fs::write(dir.join(path), content).unwrap();
+        }
+        let signature = Signature::now("test", "test@example.com").unwrap();
+        commit_dir(repo, TEAM_REF, dir, "test", &signature, None, false).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_commit_and_checkout() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = Repository::init(temp_dir.path()).unwrap();
+        let team_dir = temp_dir.path().join("team");
+        commit(&repo, &team_dir, &[("members/alice.json", member("alice", true)), ("config.toml", String::new())]);
+        let signature = Signature::now("test", "test@example.com").unwrap();
+        assert!(commit_dir(&repo, TEAM_REF, &team_dir, "unchanged", &signature, None, false).unwrap().is_none());
+
+        let checkout_dir = temp_dir.path().join("checkout");
+        std::fs::create_dir_all(&checkout_dir).unwrap();
+        std::fs::write(checkout_dir.join("stale.json"), "{}").unwrap();
+        assert!(checkout(&repo, TEAM_REF, &checkout_dir).unwrap());
+        assert!(checkout_dir.join("members/alice.json").exists());
+        assert!(!checkout_dir.join("stale.json").exists());
+    }
+
+    #[test]
+    fn test_merge_resolves_concurrent_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = Repository::init(temp_dir.path()).unwrap();
+        let team_dir = temp_dir.path().join("team");
+        let base = [
+            ("members/alice.json", member("alice", true)),
+            ("members/bob.json", member("bob", true)),
+            ("keys/k1.json", key(&["alice", "bob"])),
+            ("audit.log", "1 | init | system | base\n".to_string()),
+        ];
+        let base_commit = commit(&repo, &team_dir, &base);
+
+        // Theirs: deactivate bob and share k1 with dave
+        let theirs = commit(&repo, &team_dir, &[
+            ("members/alice.json", member("alice", true)),
+            ("members/bob.json", member("bob", false)),
+            ("members/dave.json", member("dave", true)),
+            ("keys/k1.json", key(&["alice", "bob", "dave"])),
+            ("audit.log", "1 | init | system | base\n3 | add | alice | dave\n".to_string()),
+        ]);
+
+        // Ours, from the base: share k1 with carol
+        repo.reference(TEAM_REF, base_commit, true, "test").unwrap();
+        commit(&repo, &team_dir, &[
+            ("members/alice.json", member("alice", true)),
+            ("members/bob.json", member("bob", true).replace("\"Member\"", "\"Admin\"")),
+            ("members/carol.json", member("carol", true)),
+            ("keys/k1.json", key(&["alice", "bob", "carol"])),
+            ("audit.log", "1 | init | system | base\n2 | add | alice | carol\n".to_string()),
+        ]);
+
+        let signature = Signature::now("test", "test@example.com").unwrap();
+        let outcome = merge(&repo, TEAM_REF, theirs, &signature, None, false).unwrap();
+        let SyncOutcome::Merged { resolved } = outcome.clone() else { panic!("expected a merge, got {:?}", outcome) };
+        assert!(resolved.contains(&"keys/k1.json".to_string()));
+        assert_eq!(merge(&repo, TEAM_REF, theirs, &signature, None, false).unwrap(), SyncOutcome::UpToDate);
+
+        let merged = temp_dir.path().join("merged");
+        checkout(&repo, TEAM_REF, &merged).unwrap();
+        let bob: TeamMember = serde_json::from_str(&std::fs::read_to_string(merged.join("members/bob.json")).unwrap()).unwrap();
+        assert!(!bob.active);
+        let k1: SharedKey = serde_json::from_str(&std::fs::read_to_string(merged.join("keys/k1.json")).unwrap()).unwrap();
+        let mut recipients: Vec<&String> = k1.encrypted_for_members.keys().collect();
+        recipients.sort();
+        assert_eq!(recipients, ["alice", "carol", "dave"]);
+        assert_eq!(
+            std::fs::read_to_string(merged.join("audit.log")).unwrap(),
+            "1 | init | system | base\n2 | add | alice | carol\n3 | add | alice | dave\n"
+        );
+    }
+
+    #[test]
+    fn test_push_and_fetch() {
+        let temp_dir = TempDir::new().unwrap();
+        let remote_path = temp_dir.path().join("remote.git");
+        Repository::init_bare(&remote_path).unwrap();
+        let remote_url = remote_path.to_str().unwrap();
+
+        let alice = Repository::init(temp_dir.path().join("alice")).unwrap();
+        alice.remote("origin", remote_url).unwrap();
+        let bob = Repository::init(temp_dir.path().join("bob")).unwrap();
+        bob.remote("origin", remote_url).unwrap();
+
+        assert_eq!(fetch(&bob, "origin", TEAM_REF).unwrap(), None);
+        let pushed = commit(&alice, &temp_dir.path().join("alice-team"), &[("members/alice.json", member("alice", true))]);
+        push(&alice, "origin", TEAM_REF).unwrap();
+
+        let fetched = fetch(&bob, "origin", TEAM_REF).unwrap().unwrap();
+        assert_eq!(fetched, pushed);
+        let signature = Signature::now("test", "test@example.com").unwrap();
+        assert_eq!(merge(&bob, TEAM_REF, fetched, &signature, None, false).unwrap(), SyncOutcome::FastForward);
+    }
+}
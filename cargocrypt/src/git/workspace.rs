@@ -0,0 +1,315 @@
+//! Cargo workspaces and nested packages in one repository
+//!
+//! Every package below the repository root (the members of a `[workspace]`
+//! and any other crate with a `Cargo.toml`, such as `fuzz/`) can have its own
+//! `.cargocrypt/git.toml`. Its patterns are relative to the package
+//! directory, like a nested `.gitattributes`:
+//!
+//! - `[[filter.keys]]` patterns select keys only for files of the package,
+//!   and its keyfiles are relative to the package directory. The innermost
+//!   package with a matching key wins over the root's keys.
+//! - `setup.default_patterns` are added to the root `.gitattributes`
+//!   scoped to the package (`secrets/*` in `crates/api` becomes
+//!   `crates/api/secrets/*`, `*.pem` becomes `crates/api/**/*.pem`).
+//!
+//! Hooks and filters are set up once, from the repository root, and cover
+//! every package. Submodules and other nested repositories are separate
+//! repositories with their own root, and are not part of the workspace.
+
+use super::config::{FilterKey, FilterSettings, GitCryptConfig};
+use super::{GitError, GitResult};
+use globset::GlobBuilder;
+use ignore::WalkBuilder;
+use std::path::{Path, PathBuf};
+
+/// Manifest of a Cargo package or workspace
+const MANIFEST: &str = "Cargo.toml";
+
+/// A package below the repository root
+#[derive(Debug, Clone)]
+pub struct WorkspaceMember {
+    /// Package name, or the directory for manifests without a `[package]`
+    pub name: String,
+    /// Directory relative to the repository root
+    pub dir: PathBuf,
+    /// Whether `[workspace] members` of the root manifest lists it
+    pub in_workspace: bool,
+    /// Its own `.cargocrypt/git.toml`, if it has one
+    pub config: Option<GitCryptConfig>,
+}
+
+/// The packages of a repository and their CargoCrypt configurations
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    root: PathBuf,
+    config: GitCryptConfig,
+    members: Vec<WorkspaceMember>,
+}
+
+impl Workspace {
+    /// Find the packages below the repository root at `root`
+    ///
+    /// Directories ignored by git, like `target/`, and nested repositories
+    /// such as submodules are skipped.
+    pub fn discover<P: AsRef<Path>>(root: P) -> GitResult<Self> {
+        let root = root.as_ref().to_path_buf();
+        let config = GitCryptConfig::load_project(&root)?;
+        let (member_globs, excluded) = workspace_globs(&root)?;
+
+        let mut members = Vec::new();
+        let walker = WalkBuilder::new(&root)
+            .filter_entry(|entry| {
+                let is_dir = entry.file_type().is_some_and(|kind| kind.is_dir());
+                entry.depth() == 0 || !is_dir || !entry.path().join(".git").exists()
+            })
+            .build();
+        for entry in walker {
+            let entry = entry.map_err(|e| GitError::InitializationFailed(format!("Failed to list packages: {}", e)))?;
+            if entry.file_name() != MANIFEST || !entry.file_type().is_some_and(|kind| kind.is_file()) {
+                continue;
+            }
+            let Some(dir) = entry.path().parent().and_then(|dir| dir.strip_prefix(&root).ok()) else {
+                continue;
+            };
+            if dir.as_os_str().is_empty() {
+                continue;
+            }
+
+            let package_dir = root.join(dir);
+            let member_config = GitCryptConfig::project_path(&package_dir)
+                .exists()
+                .then(|| GitCryptConfig::load_project(&package_dir))
+                .transpose()?;
+            members.push(WorkspaceMember {
+                name: package_name(entry.path()).unwrap_or_else(|| dir.display().to_string()),
+                dir: dir.to_path_buf(),
+                in_workspace: !excluded.iter().any(|excluded| dir.starts_with(excluded))
+                    && member_globs.iter().any(|glob| glob.is_match(dir)),
+                config: member_config,
+            });
+        }
+        members.sort_by(|a, b| a.dir.cmp(&b.dir));
+
+        Ok(Self { root, config, members })
+    }
+
+    /// The repository root
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The packages below the root, by directory
+    pub fn members(&self) -> &[WorkspaceMember] {
+        &self.members
+    }
+
+    /// The innermost package containing `path` (relative to the root)
+    pub fn member_for(&self, path: &Path) -> Option<&WorkspaceMember> {
+        self.members
+            .iter()
+            .filter(|member| path.starts_with(&member.dir))
+            .max_by_key(|member| member.dir.components().count())
+    }
+
+    /// The root's filter keys followed by the packages' keys, scoped to their directories
+    ///
+    /// The last matching key wins, so keys of inner packages come last.
+    pub fn filter_settings(&self) -> FilterSettings {
+        let mut settings = self.config.filter.clone();
+        let mut members: Vec<&WorkspaceMember> = self.members.iter().filter(|member| member.config.is_some()).collect();
+        members.sort_by_key(|member| member.dir.components().count());
+        for member in members {
+            let keys = &member.config.as_ref().expect("members with a config").filter.keys;
+            settings.keys.extend(keys.iter().map(|key| scope_key(&member.dir, key)));
+        }
+        settings
+    }
+
+    /// Encryption patterns of the packages, scoped to their directories
+    ///
+    /// Patterns without a slash that the root also has already match in
+    /// every directory and are left out.
+    pub fn encryption_patterns(&self) -> Vec<String> {
+        let mut patterns: Vec<String> = Vec::new();
+        for member in &self.members {
+            let Some(config) = &member.config else {
+                continue;
+            };
+            for pattern in &config.setup.default_patterns {
+                if !pattern.contains('/') && self.config.setup.default_patterns.contains(pattern) {
+                    continue;
+                }
+                let scoped = scope_pattern(&member.dir, pattern);
+                if !patterns.contains(&scoped) {
+                    patterns.push(scoped);
+                }
+            }
+        }
+        patterns
+    }
+}
+
+/// `pattern` of the `.cargocrypt/git.toml` in `dir`, as a pattern of the repository root
+///
+/// As in `.gitattributes`, a pattern with a slash is relative to `dir` and
+/// one without matches at any depth below it.
+pub fn scope_pattern(dir: &Path, pattern: &str) -> String {
+    let dir = dir.to_string_lossy().replace('\\', "/");
+    let dir = dir.trim_end_matches('/');
+    if dir.is_empty() {
+        return pattern.to_string();
+    }
+    match pattern.strip_prefix('/') {
+        Some(anchored) => format!("{}/{}", dir, anchored),
+        None if pattern.contains('/') => format!("{}/{}", dir, pattern),
+        None => format!("{}/**/{}", dir, pattern),
+    }
+}
+
+/// A filter key of the `.cargocrypt/git.toml` in `dir`, relative to the repository root
+pub fn scope_key(dir: &Path, key: &FilterKey) -> FilterKey {
+    FilterKey {
+        pattern: scope_pattern(dir, &key.pattern),
+        keyfile: key.keyfile.as_ref().map(|keyfile| dir.join(keyfile)),
+        ..key.clone()
+    }
+}
+
+/// The filter key of `path`, relative to the repository root at `root`
+///
+/// Looks in the `.cargocrypt/git.toml` of the directories of `path`,
+/// innermost first, then in `root_settings`. The filters run once per file,
+/// so this reads only those directories instead of discovering the
+/// workspace.
+pub fn key_for(root: &Path, root_settings: &FilterSettings, path: &Path) -> GitResult<Option<FilterKey>> {
+    for dir in path.ancestors().skip(1) {
+        if dir.as_os_str().is_empty() {
+            break;
+        }
+        let package_dir = root.join(dir);
+        if !GitCryptConfig::project_path(&package_dir).exists() {
+            continue;
+        }
+        let config = GitCryptConfig::load_project(&package_dir)?;
+        let relative = path.strip_prefix(dir).unwrap_or(path);
+        if let Some(key) = config.filter.key_for(relative) {
+            return Ok(Some(scope_key(dir, key)));
+        }
+    }
+    Ok(root_settings.key_for(path).cloned())
+}
+
+/// Matchers of `[workspace] members` of the root manifest, and its `exclude` paths
+fn workspace_globs(root: &Path) -> GitResult<(Vec<globset::GlobMatcher>, Vec<PathBuf>)> {
+    let path = root.join(MANIFEST);
+    let Ok(content) = std::fs::read_to_string(&path) else {
+        return Ok((Vec::new(), Vec::new()));
+    };
+    let manifest: toml::Value = toml::from_str(&content)
+        .map_err(|e| GitError::InitializationFailed(format!("Failed to parse {}: {}", path.display(), e)))?;
+    let list = |key: &str| -> Vec<String> {
+        manifest
+            .get("workspace")
+            .and_then(|workspace| workspace.get(key))
+            .and_then(|value| value.as_array())
+            .map(|values| {
+                values
+                    .iter()
+                    .filter_map(|value| value.as_str())
+                    .map(|value| value.trim_start_matches("./").trim_end_matches('/').to_string())
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+
+    let mut globs = Vec::new();
+    for member in list("members") {
+        let glob = GlobBuilder::new(&member)
+            .literal_separator(true)
+            .build()
+            .map_err(|e| GitError::InitializationFailed(format!("Invalid workspace member '{}': {}", member, e)))?;
+        globs.push(glob.compile_matcher());
+    }
+    Ok((globs, list("exclude").into_iter().map(PathBuf::from).collect()))
+}
+
+/// `[package] name` of a manifest
+fn package_name(manifest: &Path) -> Option<String> {
+    let manifest: toml::Value = toml::from_str(&std::fs::read_to_string(manifest).ok()?).ok()?;
+    manifest.get("package")?.get("name")?.as_str().map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write(root: &Path, path: &str, content: &str) {
+        let path = root.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_scope_pattern() {
+        let dir = Path::new("crates/api");
+        assert_eq!(scope_pattern(dir, "secrets/*"), "crates/api/secrets/*");
+        assert_eq!(scope_pattern(dir, "/Secrets.toml"), "crates/api/Secrets.toml");
+        assert_eq!(scope_pattern(dir, "*.pem"), "crates/api/**/*.pem");
+        assert_eq!(scope_pattern(Path::new(""), "*.pem"), "*.pem");
+    }
+
+    #[test]
+    fn test_workspace_members_and_scoped_config() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        write(root, "Cargo.toml", "[workspace]\nmembers = [\"crates/*\"]\nexclude = [\"crates/legacy\"]\n");
+        write(root, "crates/api/Cargo.toml", "[package]\nname = \"api\"\n");
+        write(
+            root,
+            "crates/api/.cargocrypt/git.toml",
+            "[setup]\ndefault_patterns = [\"*.key\", \"*.pem\", \"secrets/*\"]\n\n\
+             [[filter.keys]]\npattern = \"secrets/*\"\nkeyfile = \".cargocrypt/keys/api.key\"\n",
+        );
+        write(root, "crates/legacy/Cargo.toml", "[package]\nname = \"legacy\"\n");
+        write(root, "fuzz/Cargo.toml", "[package]\nname = \"api-fuzz\"\n");
+
+        let workspace = Workspace::discover(root).unwrap();
+        let members: Vec<(&str, bool, bool)> = workspace
+            .members()
+            .iter()
+            .map(|member| (member.name.as_str(), member.in_workspace, member.config.is_some()))
+            .collect();
+        assert_eq!(members, vec![("api", true, true), ("legacy", false, false), ("api-fuzz", false, false)]);
+        assert_eq!(workspace.member_for(Path::new("crates/api/src/main.rs")).unwrap().name, "api");
+        assert!(workspace.member_for(Path::new("src/main.rs")).is_none());
+
+        // *.key is already a root pattern everywhere
+        assert_eq!(workspace.encryption_patterns(), vec!["crates/api/**/*.pem", "crates/api/secrets/*"]);
+
+        let settings = workspace.filter_settings();
+        let key = settings.key_for(Path::new("crates/api/secrets/db.toml")).unwrap();
+        assert_eq!(key.keyfile.as_deref(), Some(Path::new("crates/api/.cargocrypt/keys/api.key")));
+        assert!(settings.key_for(Path::new("secrets/db.toml")).is_none());
+
+        let key = key_for(root, &FilterSettings::default(), Path::new("crates/api/secrets/db.toml")).unwrap();
+        assert_eq!(key.unwrap().pattern, "crates/api/secrets/*");
+        assert!(key_for(root, &FilterSettings::default(), Path::new("crates/legacy/secrets/db.toml")).unwrap().is_none());
+    }
+    #[test]
+    fn test_nested_repositories_are_not_members() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        write(root, "Cargo.toml", "[workspace]\nmembers = [\"crates/*\", \"vendor/*\"]\n");
+        write(root, "crates/api/Cargo.toml", "[package]\nname = \"api\"\n");
+        write(root, "vendor/lib/Cargo.toml", "[package]\nname = \"lib\"\n");
+        write(root, "vendor/lib/.cargocrypt/git.toml", "[setup]\ndefault_patterns = [\"*.pem\"]\n");
+        // A submodule's checkout has a .git file pointing into the superproject
+        write(root, "vendor/lib/.git", "gitdir: ../../.git/modules/vendor/lib\n");
+
+        let workspace = Workspace::discover(root).unwrap();
+        let names: Vec<&str> = workspace.members().iter().map(|member| member.name.as_str()).collect();
+        assert_eq!(names, vec!["api"]);
+        assert!(workspace.encryption_patterns().is_empty());
+    }
+}
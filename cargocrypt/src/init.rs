@@ -0,0 +1,139 @@
+//! Choices made when a project is initialized
+//!
+//! `cargocrypt init --interactive` asks how the project's files are keyed,
+//! which performance profile new files use, and whether and how to set up
+//! git. The answers are written to `.cargocrypt/config.toml` (the key mode
+//! under `[keys]` and the profile's Argon2 parameters) and, with git, to
+//! `.cargocrypt/git.toml`: the chosen patterns as `setup.default_patterns`,
+//! hooks and attributes as feature toggles, and a `[[filter.keys]]` entry
+//! per pattern for keyfile and team keys.
+//!
+//! ```toml
+//! [keys]
+//! mode = "keyfile"
+//! keyfile = ".cargocrypt/keys/default.key"
+//! ```
+
+use crate::crypto::PerformanceProfile;
+use crate::git::config::FilterKey;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::PathBuf;
+
+/// Keyfile created for [`KeyMode::Keyfile`], relative to the project root
+pub const DEFAULT_KEYFILE: &str = ".cargocrypt/keys/default.key";
+
+/// Purpose of the team key created for [`KeyMode::Team`]
+pub const TEAM_KEY_PURPOSE: &str = "secrets";
+
+/// How a project's files are keyed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyMode {
+    /// A password typed when encrypting; the git filters ask once per session
+    #[default]
+    Password,
+    /// A random key in a keyfile, e.g. for CI
+    Keyfile,
+    /// Shared keys wrapped for each team member's X25519 or GPG key
+    Team,
+}
+
+impl KeyMode {
+    /// All key modes, in the order the wizard offers them
+    pub fn all() -> &'static [KeyMode] {
+        &[Self::Password, Self::Keyfile, Self::Team]
+    }
+
+    /// Whether this mode needs a git repository
+    pub fn requires_git(&self) -> bool {
+        *self == Self::Team
+    }
+}
+
+impl fmt::Display for KeyMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Password => write!(f, "password"),
+            Self::Keyfile => write!(f, "keyfile"),
+            Self::Team => write!(f, "team"),
+        }
+    }
+}
+
+/// Key settings
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyConfig {
+    /// How files are keyed
+    pub mode: KeyMode,
+    /// Keyfile of [`KeyMode::Keyfile`], relative to the project root
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keyfile: Option<PathBuf>,
+    /// Purpose of the team key of [`KeyMode::Team`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub team_key: Option<String>,
+}
+
+impl KeyConfig {
+    /// Settings of `mode` with its default keyfile or team key
+    pub fn for_mode(mode: KeyMode) -> Self {
+        Self {
+            mode,
+            keyfile: (mode == KeyMode::Keyfile).then(|| PathBuf::from(DEFAULT_KEYFILE)),
+            team_key: (mode == KeyMode::Team).then(|| TEAM_KEY_PURPOSE.to_string()),
+        }
+    }
+
+    /// Filter keys encrypting the files matching `patterns` with this key
+    ///
+    /// Files matching no filter key use the filter password, so password
+    /// mode needs none.
+    pub fn filter_keys<S: AsRef<str>>(&self, patterns: &[S]) -> Vec<FilterKey> {
+        if self.keyfile.is_none() && self.team_key.is_none() {
+            return Vec::new();
+        }
+        patterns
+            .iter()
+            .map(|pattern| FilterKey {
+                pattern: pattern.as_ref().to_string(),
+                vault: None,
+                keyfile: self.keyfile.clone(),
+                team_key: self.team_key.clone(),
+            })
+            .collect()
+    }
+}
+
+/// What [`CargoCrypt::init_project_with`](crate::CargoCrypt::init_project_with) sets up
+#[derive(Debug, Clone, Default)]
+pub struct InitOptions {
+    /// Profile whose Argon2 parameters new files use; calibrated to this machine if `None`
+    pub profile: Option<PerformanceProfile>,
+    /// How files are keyed; left as configured if `None`
+    pub key_mode: Option<KeyMode>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_keys_follow_mode() {
+        let patterns = ["*.key", "secrets/*"];
+        assert!(KeyConfig::for_mode(KeyMode::Password).filter_keys(&patterns).is_empty());
+
+        let keys = KeyConfig::for_mode(KeyMode::Keyfile).filter_keys(&patterns);
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[1].pattern, "secrets/*");
+        assert_eq!(keys[1].keyfile.as_deref(), Some(std::path::Path::new(DEFAULT_KEYFILE)));
+
+        let keys = KeyConfig::for_mode(KeyMode::Team).filter_keys(&patterns);
+        assert_eq!(keys[0].team_key.as_deref(), Some(TEAM_KEY_PURPOSE));
+        assert_eq!(keys[0].keyfile, None);
+
+        let config: KeyConfig = toml::from_str("mode = \"keyfile\"\nkeyfile = \"ci.key\"").unwrap();
+        assert_eq!(config.mode, KeyMode::Keyfile);
+        assert_eq!(config.keyfile, Some(PathBuf::from("ci.key")));
+    }
+}
@@ -0,0 +1,118 @@
+//! Read-only inspection of encrypted files
+//!
+//! Everything reported here comes from the unencrypted header, so
+//! `cargocrypt inspect` needs no password and never decrypts anything.
+
+use crate::crypto::{Algorithm, ChunkLayout, EncryptedSecret, Kdf, KeySource};
+use crate::error::CryptoResult;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// Header information of one encrypted file
+#[derive(Debug, Clone, Serialize)]
+pub struct FileInspection {
+    /// Inspected file
+    pub path: PathBuf,
+    /// On-disk format version (1 is the legacy bincode format)
+    pub format_version: u8,
+    /// Cipher protecting the content
+    pub algorithm: String,
+    /// Key material needed to decrypt
+    pub key_source: KeySource,
+    /// Password KDF and its parameters; `None` for keyfile-only files
+    pub kdf: Option<Kdf>,
+    /// Whether the KDF parameters are below current recommendations
+    pub kdf_is_weak: bool,
+    /// Vault whose key encrypted the file
+    pub vault: Option<String>,
+    /// Name the file gets back on decryption
+    pub original_name: Option<String>,
+    /// Creation time (Unix timestamp)
+    pub created_at: Option<u64>,
+    /// Expiry time (Unix timestamp)
+    pub expires_at: Option<u64>,
+    /// Rotation interval in seconds
+    pub ttl_secs: Option<u64>,
+    /// Description recorded at encryption
+    pub description: Option<String>,
+    /// Tags recorded at encryption
+    pub tags: Vec<String>,
+    /// Chunk layout of a file encrypted in parallel chunks
+    pub chunks: Option<ChunkLayout>,
+    /// Size of the ciphertext in bytes
+    pub ciphertext_len: usize,
+    /// Fingerprints of the recipients the file key is bound to
+    ///
+    /// The post-quantum hybrid recipient, if any; empty for files encrypted
+    /// with a password or keyfile alone.
+    pub recipients: Vec<String>,
+    /// Whether the key is bound to a recipient with hybrid X25519 + ML-KEM-768
+    pub post_quantum: bool,
+}
+
+impl FileInspection {
+    /// Inspect the encrypted file at `path`
+    pub fn read<P: AsRef<Path>>(path: P) -> CryptoResult<Self> {
+        let path = path.as_ref();
+        Self::from_bytes(path, &std::fs::read(path)?)
+    }
+
+    /// Inspect the serialized secret `bytes`, read from `path`
+    pub fn from_bytes(path: &Path, bytes: &[u8]) -> CryptoResult<Self> {
+        let encrypted = EncryptedSecret::from_bytes(bytes)?;
+        let metadata = encrypted.metadata();
+        let kdf = encrypted.key_source().requires_password().then(|| *encrypted.kdf());
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            format_version: EncryptedSecret::format_version(bytes),
+            algorithm: Algorithm::ChaCha20Poly1305.to_string(),
+            key_source: encrypted.key_source(),
+            kdf_is_weak: kdf.is_some_and(|kdf| kdf.is_weak()),
+            kdf,
+            vault: encrypted.vault().map(str::to_string),
+            original_name: crate::utils::original_filename(path),
+            created_at: metadata.created_at,
+            expires_at: metadata.expires_at,
+            ttl_secs: metadata.ttl_secs,
+            description: metadata.description.clone(),
+            tags: metadata.tags.clone(),
+            chunks: encrypted.chunk_layout().copied(),
+            ciphertext_len: encrypted.ciphertext_len(),
+            recipients: encrypted.post_quantum().map(|encapsulation| encapsulation.recipient.clone()).into_iter().collect(),
+            post_quantum: encrypted.post_quantum().is_some(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{DerivedKey, PlaintextSecret, SecretMetadata};
+
+    #[test]
+    fn test_inspect_without_password() {
+        let key = DerivedKey::from_password_with_random_salt("inspect_password").unwrap();
+        let mut metadata = SecretMetadata::with_description("Database credentials");
+        metadata.add_tag("prod");
+        let encrypted = EncryptedSecret::encrypt_with_key(
+            PlaintextSecret::from_string("secret".to_string()),
+            &key,
+            Some(metadata),
+        ).unwrap();
+
+        let inspection = FileInspection::from_bytes(Path::new("config/db.toml.enc"), &encrypted.to_bytes().unwrap()).unwrap();
+
+        assert_eq!(inspection.format_version, crate::crypto::secrets::FORMAT_VERSION);
+        assert_eq!(inspection.algorithm, "ChaCha20-Poly1305");
+        assert_eq!(inspection.key_source, KeySource::Password);
+        assert_eq!(inspection.kdf, Some(Kdf::Argon2id));
+        assert_eq!(inspection.original_name.as_deref(), Some("db.toml"));
+        assert_eq!(inspection.description.as_deref(), Some("Database credentials"));
+        assert_eq!(inspection.tags, vec!["prod".to_string()]);
+        assert_eq!(inspection.ciphertext_len, "secret".len() + crate::crypto::defaults::TAG_LENGTH);
+        assert!(inspection.chunks.is_none() && inspection.recipients.is_empty() && !inspection.post_quantum);
+
+        assert!(FileInspection::from_bytes(Path::new("x.enc"), b"not encrypted").is_err());
+    }
+}
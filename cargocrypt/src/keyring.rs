@@ -0,0 +1,243 @@
+//! Passwords in the operating system's keyring
+//!
+//! Entries live under the service `cargocrypt` and an account name chosen by
+//! the caller. The platform tools are used rather than linking a keyring
+//! library: `secret-tool` (libsecret, e.g. GNOME Keyring or KWallet) on
+//! Linux and other unix systems, and `security` (the login keychain) on
+//! macOS. Secrets are passed on standard input, never as arguments, so they
+//! do not show up in the process list.
+//!
+//! A missing tool or a locked or absent keyring reads as no entry, so
+//! callers can fall back to other sources; storing fails instead.
+//! [`read`] reports a keyring that cannot be read, for `cargocrypt doctor`.
+
+use crate::crypto::{SecureBytes, SecureString};
+use crate::error::{CargoCryptError, CryptoResult};
+use std::io::Write;
+use std::process::{Command, Stdio};
+use tracing::debug;
+use zeroize::Zeroize;
+
+/// Service name of CargoCrypt's keyring entries
+pub const SERVICE: &str = "cargocrypt";
+
+/// Keyring command line tool of the platform
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tool {
+    /// `secret-tool` from libsecret
+    SecretTool,
+    /// macOS `security`
+    Security,
+}
+
+/// A tool invocation, with the secret (if any) on standard input
+struct Invocation {
+    program: &'static str,
+    args: Vec<String>,
+    input: Option<SecureBytes>,
+}
+
+/// Whether the platform has a supported keyring
+pub fn is_supported() -> bool {
+    tool().is_some()
+}
+
+/// Name of the keyring backend, for status output
+pub fn backend_name() -> &'static str {
+    match tool() {
+        Some(Tool::SecretTool) => "secret-tool (Secret Service)",
+        Some(Tool::Security) => "macOS keychain",
+        None => "unsupported",
+    }
+}
+
+/// The password stored for `account`, if any
+pub fn get(account: &str) -> Option<SecureString> {
+    match read(account) {
+        Ok(password) => password,
+        Err(e) => {
+            debug!("No keyring entry for {}: {}", account, e);
+            None
+        }
+    }
+}
+
+/// The password stored for `account`, failing if the keyring cannot be read
+///
+/// Unlike [`get`], only a missing tool or entry reads as `None`; a locked
+/// keyring or a failing tool is an error.
+pub fn read(account: &str) -> CryptoResult<Option<SecureString>> {
+    let Some(tool) = tool() else {
+        return Ok(None);
+    };
+    let invocation = lookup(tool, account);
+    let output = match execute(&invocation) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        output => output.map_err(|e| tool_error(&invocation, format!("Failed to run {}: {}", invocation.program, e)))?,
+    };
+    let mut stdout = SecureBytes::from(output.stdout);
+    if !output.status.success() {
+        // Both tools exit non-zero for a missing entry; only `security` says so
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.trim().is_empty() || stderr.contains("could not be found") {
+            return Ok(None);
+        }
+        return Err(tool_error(&invocation, format!("{} failed: {}", invocation.program, stderr.trim())));
+    }
+    let password = std::str::from_utf8(stdout.as_slice())
+        .ok()
+        .map(|s| SecureString::from(s.trim_end_matches(['\r', '\n'])))
+        .filter(|password| !password.is_empty());
+    stdout.zeroize();
+    Ok(password)
+}
+
+/// Store the password of `account`, replacing an existing entry
+pub fn set(account: &str, password: &str) -> CryptoResult<()> {
+    let tool = tool().ok_or_else(unsupported)?;
+    run(store(tool, account, password)).map(|_| ())
+}
+
+/// Remove the entry of `account`; returns whether there was one
+pub fn delete(account: &str) -> CryptoResult<bool> {
+    let tool = tool().ok_or_else(unsupported)?;
+    if get(account).is_none() {
+        return Ok(false);
+    }
+    run(clear(tool, account)).map(|_| true)
+}
+
+fn tool() -> Option<Tool> {
+    if cfg!(target_os = "macos") {
+        Some(Tool::Security)
+    } else if cfg!(unix) {
+        Some(Tool::SecretTool)
+    } else {
+        None
+    }
+}
+
+fn lookup(tool: Tool, account: &str) -> Invocation {
+    match tool {
+        Tool::SecretTool => Invocation {
+            program: "secret-tool",
+            args: strings(&["lookup", "service", SERVICE, "account", account]),
+            input: None,
+        },
+        Tool::Security => Invocation {
+            program: "security",
+            args: strings(&["find-generic-password", "-s", SERVICE, "-a", account, "-w"]),
+            input: None,
+        },
+    }
+}
+
+fn store(tool: Tool, account: &str, password: &str) -> Invocation {
+    match tool {
+        Tool::SecretTool => Invocation {
+            program: "secret-tool",
+            args: strings(&["store", "--label", &format!("CargoCrypt {}", account), "service", SERVICE, "account", account]),
+            input: Some(SecureBytes::from(password.as_bytes().to_vec())),
+        },
+        // `security -i` reads commands from standard input, keeping the password off argv
+        Tool::Security => {
+            let mut command = format!(
+                "add-generic-password -U -s {} -a {} -w {}\n",
+                quote(SERVICE),
+                quote(account),
+                quote(password)
+            );
+            let input = SecureBytes::from(command.as_bytes().to_vec());
+            command.zeroize();
+            Invocation {
+                program: "security",
+                args: strings(&["-i"]),
+                input: Some(input),
+            }
+        }
+    }
+}
+
+fn clear(tool: Tool, account: &str) -> Invocation {
+    match tool {
+        Tool::SecretTool => Invocation {
+            program: "secret-tool",
+            args: strings(&["clear", "service", SERVICE, "account", account]),
+            input: None,
+        },
+        Tool::Security => Invocation {
+            program: "security",
+            args: strings(&["delete-generic-password", "-s", SERVICE, "-a", account]),
+            input: None,
+        },
+    }
+}
+
+/// Run a tool and return its standard output
+fn run(invocation: Invocation) -> CryptoResult<SecureBytes> {
+    let output = execute(&invocation)
+        .map_err(|e| tool_error(&invocation, format!("Could not run {}: {}", invocation.program, e)))?;
+    let stdout = SecureBytes::from(output.stdout);
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(tool_error(&invocation, format!("{} failed: {}", invocation.program, stderr.trim())));
+    }
+    Ok(stdout)
+}
+
+/// Run a tool, writing its input
+fn execute(invocation: &Invocation) -> std::io::Result<std::process::Output> {
+    let mut child = Command::new(invocation.program)
+        .args(&invocation.args)
+        .stdin(if invocation.input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let (Some(input), Some(mut stdin)) = (&invocation.input, child.stdin.take()) {
+        stdin.write_all(input.as_slice())?;
+    }
+    child.wait_with_output()
+}
+
+fn tool_error(invocation: &Invocation, message: String) -> CargoCryptError {
+    CargoCryptError::KeyManagement {
+        message,
+        recovery_suggestion: Some(format!("Make sure {} is installed and the keyring is unlocked", invocation.program)),
+    }
+}
+
+/// Quote a word for the command line `security -i` reads
+fn quote(word: &str) -> String {
+    format!("\"{}\"", word.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn strings(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+fn unsupported() -> CargoCryptError {
+    CargoCryptError::KeyManagement {
+        message: "No supported keyring on this platform".to_string(),
+        recovery_suggestion: Some("Use the key agent or an identity file instead".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secrets_stay_off_the_command_line() {
+        for tool in [Tool::SecretTool, Tool::Security] {
+            let invocation = store(tool, "0123abcd/filter", "pa\"ss\\word");
+            assert!(invocation.args.iter().all(|arg| !arg.contains("pa\"ss")));
+            let input = String::from_utf8(invocation.input.unwrap().as_slice().to_vec()).unwrap();
+            assert!(input.contains("pa"));
+        }
+
+        let invocation = store(Tool::Security, "0123abcd/filter", "pa\"ss\\word");
+        let input = String::from_utf8(invocation.input.unwrap().as_slice().to_vec()).unwrap();
+        assert_eq!(input, "add-generic-password -U -s \"cargocrypt\" -a \"0123abcd/filter\" -w \"pa\\\"ss\\\\word\"\n");
+        assert_eq!(lookup(Tool::SecretTool, "a").args, ["lookup", "service", "cargocrypt", "account", "a"]);
+    }
+}
@@ -23,11 +23,25 @@
 //!     Ok(())
 //! }
 //! ```
+//!
+//! ## Reading Secrets at Runtime
+//!
+//! Applications can decrypt straight into memory, so the plaintext never
+//! touches the filesystem and is zeroized when dropped:
+//!
+//! ```rust,no_run
+//! # async fn example(crypt: cargocrypt::CargoCrypt) -> anyhow::Result<()> {
+//! let config = crypt.decrypt_to_memory("config/prod.toml.enc", "password").await?;
+//! let settings = config.as_string()?;
+//! # Ok(())
+//! # }
+//! ```
 
 // Re-export main types for easy access
 pub use crate::core::{
     CargoCrypt, CargoCryptBuilder, CryptoConfig, SecretBytes, 
-    ResilienceManager, ResilienceConfig
+    ResilienceManager, ResilienceConfig, ResourceLimitsConfig,
+    BatchOptions, BatchReport, BatchFileOutcome, UpgradeReport, UpgradeOutcome, PasswordChange, SessionClose
 };
 pub use crate::crypto::{
     CryptoEngine, DerivedKey, EncryptedSecret, PlaintextSecret, 
@@ -42,6 +56,9 @@ pub use crate::detection::{
 
 // Core modules
 pub mod core;
+pub mod config;
+pub mod clipboard;
+pub mod project;
 pub mod crypto;
 pub mod error;
 pub mod validation;
@@ -52,6 +69,26 @@ pub mod monitoring;
 // pub mod auth;
 pub mod detection;
 pub mod git;
+pub mod agent;
+pub mod keyring;
+pub mod expiry;
+pub mod publish;
+pub mod manifest;
+pub mod vault;
+pub mod backup;
+pub mod doctor;
+pub mod init;
+pub mod bench;
+pub mod inspect;
+pub mod redact;
+pub mod plan;
+pub mod progress;
+pub mod dotenv;
+pub mod run;
+pub mod lock;
+pub mod lockout;
+pub mod managed;
+pub mod session;
 // pub mod providers;
 pub mod tui {
     pub mod monitoring;
@@ -133,20 +170,84 @@ pub mod utils {
     }
 
     /// Find the root of the current Rust project
+    ///
+    /// The workspace root for members of a workspace; see [`crate::project`].
     pub fn find_project_root() -> CryptoResult<std::path::PathBuf> {
-        let mut current = std::env::current_dir()?;
-        
-        loop {
-            if current.join("Cargo.toml").exists() {
-                return Ok(current);
-            }
-            
-            if !current.pop() {
-                break;
-            }
+        crate::project::Project::find().map(|project| project.root)
+    }
+
+    /// Short stable identifier of a project directory
+    ///
+    /// Derived from the canonical path, so two checkouts of the same
+    /// repository get different identifiers.
+    pub fn project_id<P: AsRef<Path>>(root: P) -> String {
+        use sha2::{Digest, Sha256};
+
+        let root = root.as_ref();
+        let canonical = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+        let digest = Sha256::digest(canonical.to_string_lossy().as_bytes());
+        hex::encode(&digest[..8])
+    }
+
+    /// Author to record for a change: git's `user.name <user.email>` for the
+    /// repository containing `dir`, otherwise the login name
+    pub fn author(dir: &Path) -> Option<String> {
+        let config = git2::Repository::discover(dir)
+            .and_then(|repo| repo.config())
+            .or_else(|_| git2::Config::open_default())
+            .ok();
+        let get = |key: &str| config.as_ref()?.get_string(key).ok().filter(|value| !value.is_empty());
+
+        match (get("user.name"), get("user.email")) {
+            (Some(name), Some(email)) => Some(format!("{} <{}>", name, email)),
+            (Some(name), None) => Some(name),
+            (None, Some(email)) => Some(email),
+            (None, None) => std::env::var("USER").or_else(|_| std::env::var("USERNAME")).ok(),
+        }
+    }
+
+    /// Seconds since the Unix epoch, 0 if the clock is before it
+    pub fn unix_now() -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0)
+    }
+
+    /// Per-user directory for sockets and short-lived files
+    ///
+    /// `$XDG_RUNTIME_DIR/cargocrypt`, or a directory named after the user
+    /// below the system temp directory.
+    pub fn runtime_dir() -> std::path::PathBuf {
+        match std::env::var_os("XDG_RUNTIME_DIR") {
+            Some(runtime_dir) => std::path::PathBuf::from(runtime_dir).join("cargocrypt"),
+            None => std::env::temp_dir().join(format!("cargocrypt-{}", user_id())),
         }
-        
-        Err(crate::error::CargoCryptError::project_not_found())
+    }
+
+    /// Identifier of the current user: the uid on unix, otherwise the login name
+    pub fn user_id() -> String {
+        #[cfg(unix)]
+        if let Some(uid) = current_uid() {
+            return uid.to_string();
+        }
+
+        std::env::var("USER")
+            .or_else(|_| std::env::var("USERNAME"))
+            .map(|name| name.chars().filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_').collect())
+            .unwrap_or_else(|_| "default".to_string())
+    }
+
+    /// Numeric id of the current user
+    #[cfg(unix)]
+    pub fn current_uid() -> Option<u32> {
+        use std::os::unix::fs::MetadataExt;
+
+        // /proc/self (Linux) and the home directory belong to the current user
+        std::fs::metadata("/proc/self")
+            .ok()
+            .or_else(|| std::fs::metadata(std::env::var_os("HOME")?).ok())
+            .map(|metadata| metadata.uid())
     }
 }
 
@@ -168,6 +269,35 @@ mod tests {
         assert_eq!(utils::original_filename("file.txt"), Some("file".to_string()));
     }
 
+    #[test]
+    fn test_project_id() {
+        let first = tempfile::TempDir::new().unwrap();
+        let second = tempfile::TempDir::new().unwrap();
+
+        assert_eq!(utils::project_id(first.path()), utils::project_id(first.path()));
+        assert_ne!(utils::project_id(first.path()), utils::project_id(second.path()));
+        assert_eq!(utils::project_id(first.path()).len(), 16);
+    }
+
+    #[test]
+    fn test_config_file_roundtrip() {
+        let dir = tempfile::TempDir::new().unwrap();
+        assert_eq!(CryptoConfig::load_project(dir.path()).unwrap().key_params.kdf, crypto::Kdf::Argon2id);
+
+        let mut config = default_config();
+        config.key_params.kdf = crypto::Kdf::Argon2idCustom { m_cost: 32768, t_cost: 2, p_cost: 2 };
+        let path = CryptoConfig::project_path(dir.path());
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        config.save(&path).unwrap();
+
+        let loaded = CryptoConfig::load_project(dir.path()).unwrap();
+        assert_eq!(loaded.key_params.kdf, config.key_params.kdf);
+        assert_eq!(loaded.key_params.calibration_budget_ms, 500);
+
+        std::fs::write(&path, "key_params = 3").unwrap();
+        assert!(CryptoConfig::load_project(dir.path()).is_err());
+    }
+
     #[test]
     fn test_default_config() {
         let config = default_config();
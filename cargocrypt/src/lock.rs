@@ -0,0 +1,163 @@
+//! Advisory locks against concurrent cargocrypt processes
+//!
+//! Two invocations working on the same file at once, say two terminals or
+//! the TUI next to the CLI, could interleave their writes and leave a
+//! ciphertext that matches neither. Every operation that writes an encrypted
+//! file or its plaintext holds the lock of the encrypted file's path, and
+//! changes to project state under `.cargocrypt/` hold the state lock.
+//!
+//! Locks are OS advisory locks on files in `.cargocrypt/locks/`, so they are
+//! released when the holder exits, even if it crashes. A locked path fails at
+//! once with [`CargoCryptError::Locked`] unless [`set_wait`] allows polling
+//! for it.
+
+use crate::error::{CargoCryptError, CryptoResult};
+use sha2::{Digest, Sha256};
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// Directory of the lock files, relative to the project root
+pub const LOCK_DIR: &str = ".cargocrypt/locks";
+
+/// How often a waiting operation retries a held lock
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long operations wait for a held lock, in milliseconds
+static WAIT_MS: AtomicU64 = AtomicU64::new(0);
+
+/// Set how long operations in this process wait for a held lock (zero fails at once)
+pub fn set_wait(wait: Duration) {
+    WAIT_MS.store(u64::try_from(wait.as_millis()).unwrap_or(u64::MAX), Ordering::SeqCst);
+}
+
+/// How long operations in this process wait for a held lock
+pub fn wait() -> Duration {
+    Duration::from_millis(WAIT_MS.load(Ordering::SeqCst))
+}
+
+/// A held advisory lock, released on drop
+#[derive(Debug)]
+pub struct FileLock {
+    file: File,
+    target: PathBuf,
+}
+
+impl FileLock {
+    /// Lock the path `target` within the project at `project_root`
+    ///
+    /// `target` does not need to exist; the encrypted file an operation is
+    /// about to create is locked before it is written.
+    pub async fn path(project_root: &Path, target: &Path) -> CryptoResult<Self> {
+        let lock_file = lock_dir(project_root)?.join(format!("{}.lock", path_key(target)));
+        Self::acquire(&lock_file, target, wait()).await
+    }
+
+    /// Lock the project state under `.cargocrypt/`
+    pub async fn state(project_root: &Path) -> CryptoResult<Self> {
+        let lock_file = lock_dir(project_root)?.join("state.lock");
+        Self::acquire(&lock_file, &project_root.join(".cargocrypt"), wait()).await
+    }
+
+    /// Lock `target` through the lock file `lock_file`, polling for up to `wait`
+    pub async fn acquire(lock_file: &Path, target: &Path, wait: Duration) -> CryptoResult<Self> {
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(lock_file)?;
+        let deadline = Instant::now() + wait;
+
+        loop {
+            match file.try_lock() {
+                Ok(()) => {
+                    return Ok(Self { file, target: target.to_path_buf() });
+                }
+                Err(TryLockError::WouldBlock) if Instant::now() < deadline => {
+                    debug!("Waiting for the lock on {}", target.display());
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                }
+                Err(TryLockError::WouldBlock) => return Err(CargoCryptError::locked(target)),
+                Err(TryLockError::Error(e)) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Path this lock protects
+    pub fn target(&self) -> &Path {
+        &self.target
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        // Closing the file releases the lock too; unlocking first just makes it prompt
+        let _ = self.file.unlock();
+    }
+}
+
+/// Create the lock directory of a project, keeping it out of git
+fn lock_dir(project_root: &Path) -> CryptoResult<PathBuf> {
+    let dir = project_root.join(LOCK_DIR);
+    if !dir.exists() {
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join(".gitignore"), "*\n")?;
+    }
+    Ok(dir)
+}
+
+/// Name of the lock file of `path`, the same for every spelling of the path
+fn path_key(path: &Path) -> String {
+    let resolved = match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => {
+            let parent = if parent.as_os_str().is_empty() { Path::new(".") } else { parent };
+            parent.canonicalize().map(|parent| parent.join(name)).ok()
+        }
+        _ => None,
+    }
+    .or_else(|| std::path::absolute(path).ok())
+    .unwrap_or_else(|| path.to_path_buf());
+
+    hex::encode(&Sha256::digest(resolved.to_string_lossy().as_bytes())[..16])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_second_lock_fails_until_released() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let target = root.join("secrets.env.enc");
+
+        let held = FileLock::path(root, &target).await.unwrap();
+        match FileLock::path(root, &root.join(".").join("secrets.env.enc")).await {
+            Err(CargoCryptError::Locked { path, .. }) => assert!(path.ends_with("secrets.env.enc")),
+            other => panic!("expected a Locked error, got {:?}", other),
+        }
+        // Other paths and the state lock are independent
+        let _other = FileLock::path(root, &root.join("other.enc")).await.unwrap();
+        let _state = FileLock::state(root).await.unwrap();
+
+        drop(held);
+        FileLock::path(root, &target).await.unwrap();
+        assert!(root.join(LOCK_DIR).join(".gitignore").exists());
+    }
+
+    #[tokio::test]
+    async fn test_waiting_for_a_lock() {
+        let temp_dir = TempDir::new().unwrap();
+        let lock_file = temp_dir.path().join("test.lock");
+        let target = temp_dir.path().join("target");
+
+        let held = FileLock::acquire(&lock_file, &target, Duration::ZERO).await.unwrap();
+        let release = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            drop(held);
+        });
+
+        let lock = FileLock::acquire(&lock_file, &target, Duration::from_secs(10)).await.unwrap();
+        assert_eq!(lock.target(), target);
+        release.await.unwrap();
+    }
+}
@@ -0,0 +1,177 @@
+//! Lockout after repeated wrong passwords
+//!
+//! With [`SecurityConfig::lockout_after`](crate::core::SecurityConfig::lockout_after)
+//! set, each wrong password given to `cargocrypt decrypt` is counted for the
+//! file or vault it was for in `.cargocrypt/attempts.toml`. That many
+//! failures in a row lock the target for
+//! [`SecurityConfig::lockout_minutes`](crate::core::SecurityConfig::lockout_minutes);
+//! a right password clears its count.
+//!
+//! The record is local state and anyone who can edit the project can delete
+//! it. It slows down guessing at an unattended terminal; it does nothing
+//! against someone who copies the encrypted file, which only a strong
+//! password and key derivation protect.
+//!
+//! ```toml
+//! [target."config/secrets.toml.enc"]
+//! failures = 2
+//! last_failure = 1760520000
+//! ```
+
+use crate::error::{CargoCryptError, CryptoResult};
+use crate::lock::FileLock;
+use crate::utils::unix_now;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Attempts file, inside `.cargocrypt/`
+pub const ATTEMPTS_FILE: &str = "attempts.toml";
+
+/// Failed attempts for one file or vault
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attempts {
+    /// Wrong passwords in a row
+    pub failures: u32,
+    /// When the last one was given (Unix timestamp)
+    pub last_failure: u64,
+    /// Until when the target is locked (Unix timestamp)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locked_until: Option<u64>,
+}
+
+/// The failed attempts recorded in `.cargocrypt/attempts.toml`, by target
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FailedAttempts {
+    #[serde(rename = "target", default)]
+    pub targets: BTreeMap<String, Attempts>,
+}
+
+impl FailedAttempts {
+    /// Path of the attempts file of the project at `project_root`
+    pub fn project_path<P: AsRef<Path>>(project_root: P) -> PathBuf {
+        project_root.as_ref().join(".cargocrypt").join(ATTEMPTS_FILE)
+    }
+
+    /// Load the attempts of the project at `project_root`; none if there is no file
+    pub fn load<P: AsRef<Path>>(project_root: P) -> CryptoResult<Self> {
+        let path = Self::project_path(project_root);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        toml::from_str(&std::fs::read_to_string(&path)?).map_err(|e| CargoCryptError::Config {
+            message: format!("Invalid {}: {}", path.display(), e),
+            suggestion: Some("Delete the file to reset the failed attempts".to_string()),
+        })
+    }
+
+    fn save(&self, project_root: &Path) -> CryptoResult<()> {
+        let path = Self::project_path(project_root);
+        if self.targets.is_empty() {
+            if path.exists() {
+                std::fs::remove_file(path)?;
+            }
+            return Ok(());
+        }
+        let content = toml::to_string_pretty(self).map_err(|e| CargoCryptError::Serialization {
+            message: format!("Failed to serialize failed attempts: {}", e),
+            source: Box::new(e),
+        })?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+
+    /// Fail while `target` is locked out
+    pub fn check(project_root: &Path, target: &str) -> CryptoResult<()> {
+        let attempts = Self::load(project_root)?;
+        match attempts.targets.get(target).and_then(|attempts| attempts.locked_until) {
+            Some(until) if until > unix_now() => Err(locked_out(target, until)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Count a wrong password for `target`, locking it once `lockout_after` are in a row
+    ///
+    /// Returns the lockout error when this attempt locked the target.
+    pub async fn record_failure(project_root: &Path, target: &str, lockout_after: u32, lockout: Duration) -> CryptoResult<Option<CargoCryptError>> {
+        let _lock = FileLock::state(project_root).await?;
+        let mut attempts = Self::load(project_root)?;
+        let now = unix_now();
+        let entry = attempts.targets.entry(target.to_string()).or_default();
+        // A lockout that ran out starts a new count
+        if entry.locked_until.is_some_and(|until| until <= now) {
+            *entry = Attempts::default();
+        }
+        entry.failures += 1;
+        entry.last_failure = now;
+        let locked = entry.failures >= lockout_after;
+        if locked {
+            entry.locked_until = Some(now + lockout.as_secs());
+        }
+        let until = entry.locked_until;
+        attempts.save(project_root)?;
+        Ok(until.filter(|_| locked).map(|until| locked_out(target, until)))
+    }
+
+    /// Clear the failed attempts of `target` after a right password
+    pub async fn record_success(project_root: &Path, target: &str) -> CryptoResult<()> {
+        if !Self::project_path(project_root).exists() {
+            return Ok(());
+        }
+        let _lock = FileLock::state(project_root).await?;
+        let mut attempts = Self::load(project_root)?;
+        if attempts.targets.remove(target).is_some() {
+            attempts.save(project_root)?;
+        }
+        Ok(())
+    }
+}
+
+fn locked_out(target: &str, until: u64) -> CargoCryptError {
+    let minutes = until.saturating_sub(unix_now()).div_ceil(60);
+    CargoCryptError::Auth {
+        message: format!("Too many wrong passwords for {}; locked for {} more minute(s)", target, minutes),
+        retry_suggestion: Some(format!(
+            "Wait, or delete {} if you are sure it was you",
+            Path::new(".cargocrypt").join(ATTEMPTS_FILE).display()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_lockout_after_failures_in_a_row() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let lockout = Duration::from_secs(600);
+
+        assert!(FailedAttempts::record_failure(root, "a.enc", 3, lockout).await.unwrap().is_none());
+        FailedAttempts::record_success(root, "a.enc").await.unwrap();
+        assert!(!FailedAttempts::project_path(root).exists());
+
+        for _ in 0..2 {
+            assert!(FailedAttempts::record_failure(root, "a.enc", 3, lockout).await.unwrap().is_none());
+        }
+        FailedAttempts::check(root, "a.enc").unwrap();
+        let error = FailedAttempts::record_failure(root, "a.enc", 3, lockout).await.unwrap().unwrap();
+        assert!(error.to_string().contains("10 more minute"), "{}", error);
+        assert!(FailedAttempts::check(root, "a.enc").is_err());
+        FailedAttempts::check(root, "vault 'prod'").unwrap();
+
+        // An expired lockout starts over
+        let mut attempts = FailedAttempts::load(root).unwrap();
+        attempts.targets.get_mut("a.enc").unwrap().locked_until = Some(unix_now() - 1);
+        attempts.save(root).unwrap();
+        FailedAttempts::check(root, "a.enc").unwrap();
+        assert!(FailedAttempts::record_failure(root, "a.enc", 3, lockout).await.unwrap().is_none());
+        assert_eq!(FailedAttempts::load(root).unwrap().targets["a.enc"].failures, 1);
+    }
+}
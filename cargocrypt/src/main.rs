@@ -2,16 +2,59 @@
 //!
 //! Zero-config cryptographic operations for Rust projects
 
-use cargocrypt::{CargoCrypt, CryptoResult, CargoCryptError};
-use clap::{Parser, Subcommand};
-use rpassword::prompt_password;
-use std::{path::PathBuf, sync::Arc};
+use cargocrypt::{CargoCrypt, CryptoResult, CargoCryptError, ResourceLimitsConfig, SessionClose};
+use cargocrypt::error::exit_code;
+use cargocrypt::expiry::{format_expiry, parse_expiry, ExpiringItem, ExpiryStatus};
+use cargocrypt::manifest::IntegrityIssueKind;
+use cargocrypt::git::TeamRole;
+use cargocrypt::plan::{Change, Plan};
+use cargocrypt::progress::Progress;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use cargocrypt::crypto::SecureString;
+use std::{path::{Path, PathBuf}, sync::Arc};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Environment variable that disables prompts like `--non-interactive`
+const NON_INTERACTIVE_ENV: &str = "CARGOCRYPT_NONINTERACTIVE";
+
+/// Whether prompts are disabled for this process
+static NON_INTERACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Whether commands print JSON instead of text
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// Whether cargo started this process as `cargo crypt`
+static CARGO_SUBCOMMAND: AtomicBool = AtomicBool::new(false);
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// Wait up to this long for files locked by another cargocrypt process instead of failing
+    #[arg(long, global = true, value_name = "SECONDS")]
+    wait: Option<u64>,
+
+    /// Apply the [profiles.NAME] settings of the configuration files (default: $CARGOCRYPT_PROFILE)
+    #[arg(long, global = true, value_name = "NAME")]
+    profile: Option<String>,
+
+    /// Never prompt; fail with exit code 5 when input is needed (default: $CARGOCRYPT_NONINTERACTIVE)
+    #[arg(long, global = true)]
+    non_interactive: bool,
+
+    /// Print the result, or the error and its exit code, as JSON on stdout
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Cargo.toml of the package to work on (default: the nearest one above the working directory)
+    #[arg(long, global = true, value_name = "PATH")]
+    manifest_path: Option<PathBuf>,
+
+    /// Hide progress bars, and print nothing when `report expiring` finds nothing due
+    #[arg(short, long, global = true)]
+    quiet: bool,
 }
 
 #[derive(Subcommand)]
@@ -21,342 +64,5751 @@ enum Commands {
         /// Enable Git integration
         #[arg(long)]
         git: bool,
+        /// Only list the files that would be created or changed
+        #[arg(long)]
+        dry_run: bool,
+        /// Choose the key mode, performance profile, protected files, hooks and filters step by step
+        #[arg(short, long, conflicts_with = "dry_run")]
+        interactive: bool,
     },
-    /// Encrypt a file
+    /// Encrypt a file, or every file in a directory (`-` reads stdin and writes to stdout)
     Encrypt { 
-        file: PathBuf,
+        #[arg(required_unless_present = "all_managed")]
+        file: Option<PathBuf>,
         /// Read password from stdin instead of prompting
         #[arg(long)]
         password_stdin: bool,
+        /// Encrypt with a keyfile instead of a password
+        #[arg(long)]
+        keyfile: Option<PathBuf>,
+        /// Require a password in addition to the keyfile (implied by --password-stdin)
+        #[arg(long, requires = "keyfile")]
+        with_password: bool,
+        /// Expiry date of the secret (YYYY-MM-DD or RFC 3339), e.g. a certificate's valid-until
+        #[arg(long, value_name = "DATE")]
+        expires: Option<String>,
+        /// Remove the plaintext once the encrypted file is safely written (no backup is kept)
+        #[arg(long)]
+        in_place: bool,
+        /// Overwrite the plaintext with random data before removing it
+        #[arg(long, requires = "in_place")]
+        shred: bool,
+        /// Replace the encrypted file if it already exists
+        #[arg(long)]
+        force: bool,
+        /// Only list the files that would be written or removed; needs no password
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Encrypt with the key of this vault (prompts for the vault's password)
+        #[arg(long, conflicts_with = "keyfile")]
+        vault: Option<String>,
+        /// Encrypt every file in .cargocrypt/files.toml that changed since it was encrypted, with the key it was encrypted with
+        #[arg(long, conflicts_with_all = ["file", "with_password", "expires", "force", "dry_run", "vault"])]
+        all_managed: bool,
     },
-    /// Decrypt a file
+    /// Decrypt a file (`-` reads stdin and writes to stdout)
     Decrypt { 
+        #[arg(required_unless_present = "all_managed")]
+        file: Option<PathBuf>,
+        /// Read password from stdin instead of prompting
+        #[arg(long)]
+        password_stdin: bool,
+        /// Decrypt with a keyfile (prompts for a password only if one was mixed in)
+        #[arg(long)]
+        keyfile: Option<PathBuf>,
+        /// Only list the file that would be written; needs no password
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Vault the file was encrypted in (read from the file's header if omitted)
+        #[arg(long, conflicts_with = "keyfile")]
+        vault: Option<String>,
+        /// X25519 identity to unwrap team keys with, for a file encrypted with a team key (default: every identity found locally)
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["keyfile", "vault", "all_managed"])]
+        identity: Option<PathBuf>,
+        /// Decrypt every file in .cargocrypt/files.toml whose plaintext has no unencrypted changes
+        #[arg(long, conflicts_with_all = ["file", "dry_run", "vault"])]
+        all_managed: bool,
+    },
+    /// Decrypt a file for a limited time; it is encrypted again if changed and removed when the time is up
+    Open {
+        /// The encrypted file
+        file: PathBuf,
+        /// How long the file stays open, e.g. 30s, 10m or 2h
+        #[arg(long, default_value = "10m")]
+        ttl: String,
+        /// Decrypt next to the encrypted file instead of into a private temporary directory
+        #[arg(long)]
+        in_place: bool,
+        /// Read password from stdin instead of prompting
+        #[arg(long)]
+        password_stdin: bool,
+        /// Decrypt with a keyfile (prompts for a password only if one was mixed in)
+        #[arg(long)]
+        keyfile: Option<PathBuf>,
+    },
+    /// Close files opened with `open` now, encrypting the changed ones again
+    Close {
+        /// The encrypted file or its open plaintext (default: every open file)
+        file: Option<PathBuf>,
+        /// Read passwords for changed files from stdin, one line each, instead of prompting
+        #[arg(long)]
+        password_stdin: bool,
+        /// Overwrite the plaintext with random data before removing it
+        #[arg(long)]
+        shred: bool,
+        /// Wait until the session of FILE ending at this Unix timestamp expires, then close it
+        #[arg(long, hide = true, requires = "file")]
+        expires_at: Option<u64>,
+    },
+    /// Edit an encrypted file in $VISUAL or $EDITOR; it is encrypted again when the editor exits
+    Edit {
+        /// The encrypted file
         file: PathBuf,
         /// Read password from stdin instead of prompting
         #[arg(long)]
         password_stdin: bool,
+        /// Decrypt with a keyfile (prompts for a password only if one was mixed in)
+        #[arg(long)]
+        keyfile: Option<PathBuf>,
+    },
+    /// Set or clear the expiry date of an encrypted file
+    Expire {
+        /// Encrypted file
+        file: PathBuf,
+        /// Expiry date (YYYY-MM-DD or RFC 3339)
+        #[arg(required_unless_present = "clear")]
+        date: Option<String>,
+        /// Remove the expiry date
+        #[arg(long, conflicts_with = "date")]
+        clear: bool,
+    },
+    /// Show project status and secrets that need rotation
+    Status,
+    /// Sign a file with the project's Ed25519 key (writes <file>.sig)
+    Sign {
+        file: PathBuf,
+        /// Read signing key password from stdin instead of prompting
+        #[arg(long)]
+        password_stdin: bool,
+    },
+    /// Verify a file's detached Ed25519 signature, or every encrypted file with --all
+    Verify {
+        #[arg(required_unless_present = "all")]
+        file: Option<PathBuf>,
+        /// Signature file (defaults to <file>.sig)
+        #[arg(long, conflicts_with = "all")]
+        signature: Option<PathBuf>,
+        /// Require the signature to come from this public key (hex)
+        #[arg(long)]
+        key: Option<String>,
+        /// Check every encrypted file in the project against the signed manifest
+        #[arg(long, conflicts_with = "file")]
+        all: bool,
+        /// Sign and write .cargocrypt/manifest.json after checking
+        #[arg(long, requires = "all")]
+        write_manifest: bool,
+        /// Read the password from stdin to also check authentication tags
+        /// (and to unlock the signing key with --write-manifest)
+        #[arg(long, requires = "all")]
+        password_stdin: bool,
+    },
+    /// Scan files for secrets
+    #[command(args_conflicts_with_subcommands = true)]
+    Scan {
+        #[command(subcommand)]
+        action: Option<ScanCommands>,
+        /// Directory or file to scan
+        #[arg(default_value = ".")]
+        path: PathBuf,
+        /// Output format (`json` and `csv` have a stable schema, `sarif` is for GitHub Code Scanning, `ecs` for SIEMs)
+        #[arg(long, value_enum, default_value_t = ScanFormat::Table)]
+        format: ScanFormat,
+        /// Write the report to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Scan every commit reachable from HEAD instead of the working tree
+        #[arg(long)]
+        history: bool,
+        /// Only scan commits after this revision (with --history)
+        #[arg(long, requires = "history")]
+        since: Option<String>,
+        /// Only scan changes staged for commit, failing on secrets the commit policy blocks (for pre-commit hooks)
+        #[arg(long, conflicts_with = "history")]
+        staged: bool,
+        /// Scan every file instead of skipping files unchanged since a clean scan
+        #[arg(long)]
+        no_cache: bool,
+        /// Check GitHub tokens, AWS keys and Slack webhooks against their services (read-only)
+        #[arg(long)]
+        verify: bool,
+        /// Also scan inside zip, jar, tar, tar.gz, .crate and .gz archives
+        #[arg(long)]
+        archives: bool,
+        /// Scan the printable strings of binaries (ELF, Mach-O, PE, .so, .wasm...) instead of skipping them
+        #[arg(long)]
+        binaries: bool,
+        /// Keep running and rescan files as they change, printing new findings
+        #[arg(long, conflicts_with_all = ["history", "staged", "output", "verify"])]
+        watch: bool,
+        /// Fail only on findings that `.cargocrypt/policy.toml` blocks in CI
+        #[arg(long, conflicts_with_all = ["history", "staged", "watch"])]
+        ci: bool,
+        /// Gate with the policy of this stage instead (with --ci, e.g. `push` in pre-push hooks)
+        #[arg(long, value_enum, requires = "ci")]
+        stage: Option<cargocrypt::detection::PolicyStage>,
+        /// Only scan files .gitattributes marks for encryption, failing if any is staged in plaintext
+        #[arg(long, conflicts_with_all = ["history", "staged", "watch"])]
+        encrypted_only: bool,
+        /// Scan the git objects listed on stdin (`git rev-list --objects`), for server-side pre-receive hooks
+        #[arg(long, conflicts_with_all = ["history", "staged", "watch", "ci", "encrypted_only"])]
+        stdin_objects: bool,
+        /// Scan the files of this commit instead of the working tree (a bare repository scans HEAD)
+        #[arg(long, value_name = "REV", conflicts_with_all = ["history", "staged", "watch", "encrypted_only", "stdin_objects"])]
+        rev: Option<String>,
+        /// Also stream the findings as ECS NDJSON to tcp://host:port or an http(s):// collector
+        #[arg(long, value_name = "URL", conflicts_with_all = ["history", "watch"])]
+        send_to: Option<String>,
+    },
+    /// Replace detected secrets with `${NAME}` placeholders (prints a patch unless --apply)
+    Redact {
+        /// File or directory to redact
+        #[arg(default_value = ".")]
+        path: PathBuf,
+        /// Rewrite the files instead of printing a patch
+        #[arg(long)]
+        apply: bool,
+        /// Save the original values in the secret store under the placeholder names
+        #[arg(long)]
+        store: bool,
+        /// Write the patch to a file instead of stdout
+        #[arg(short, long, conflicts_with = "apply")]
+        output: Option<PathBuf>,
+        /// Use the secret store of this vault
+        #[arg(long, requires = "store")]
+        vault: Option<String>,
+        /// Read the store password from stdin instead of prompting
+        #[arg(long, requires = "store")]
+        password_stdin: bool,
+        /// Only list the files that would be rewritten or written; needs no password
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Run a command with secrets from the store or an encrypted .env in its environment
+    Run {
+        /// Secret to pass, as NAME or VAR=NAME (all stored secrets if neither this nor --env-file is given)
+        #[arg(long = "secret", value_name = "[VAR=]NAME")]
+        secrets: Vec<String>,
+        /// Encrypted .env file whose variables to pass (later files override earlier ones)
+        #[arg(long, value_name = "FILE")]
+        env_file: Vec<PathBuf>,
+        /// Use the secret store of this vault
+        #[arg(long)]
+        vault: Option<String>,
+        /// Read passwords from stdin instead of prompting: the store's on the first line, the .env files' on the next
+        #[arg(long)]
+        password_stdin: bool,
+        /// The command to run and its arguments, after `--`
+        #[arg(last = true, required = true, value_name = "COMMAND")]
+        command: Vec<std::ffi::OsString>,
+    },
+    /// Check that `cargo package` would not publish secrets or encrypted files
+    Prepublish {
+        /// Also scan packaged files with the secret detector
+        #[arg(long)]
+        scan_secrets: bool,
+    },
+    /// Check the project for missing hooks and filters, plaintext secrets, stale files, weak keys and keyring problems
+    Doctor {
+        /// Apply the safe fixes: install hooks, configure the git filters and calibrate key derivation
+        #[arg(long)]
+        fix: bool,
+    },
+    /// Show configuration, or get, set and edit settings
+    Config {
+        #[command(subcommand)]
+        action: Option<ConfigCommands>,
+    },
+    /// Tune Argon2 parameters to this machine and save them to the project configuration
+    Calibrate {
+        /// Target time for one key derivation in milliseconds (default: from configuration)
+        #[arg(long)]
+        budget_ms: Option<u64>,
+    },
+    /// Show the header of an encrypted file without decrypting it
+    Inspect {
+        /// Encrypted file to inspect
+        file: PathBuf,
+    },
+    /// Re-encrypt files whose key derivation is weaker than the configured one
+    Upgrade {
+        /// Only list the files that would be upgraded
+        #[arg(long)]
+        dry_run: bool,
+        /// Read the password from stdin instead of prompting
+        #[arg(long)]
+        password_stdin: bool,
+    },
+    /// Change the password of the project's vault and signing keys without re-encrypting files
+    Passwd,
+    /// Measure key derivation, cipher and file I/O speed and recommend a performance profile
+    Bench {
+        /// Longest acceptable key derivation in milliseconds for the recommendation
+        #[arg(long, default_value_t = 1000)]
+        budget_ms: u64,
+        /// Megabytes of data encrypted and written per measurement
+        #[arg(long, default_value_t = 16)]
+        size_mb: usize,
     },
-    /// Show configuration
-    Config,
     /// Launch interactive TUI for all CargoCrypt operations
     Tui,
+    /// Clear the clipboard after SECS seconds if it still holds the value whose digest is on stdin
+    #[command(hide = true)]
+    ClearClipboard { secs: u64 },
     /// Git-specific commands
     #[command(subcommand)]
     Git(GitCommands),
+    /// Entry points of the installed git hooks
+    #[command(subcommand)]
+    Hook(HookCommands),
+    /// Move a repository from another encryption tool to CargoCrypt
+    #[command(subcommand)]
+    Migrate(MigrateCommands),
+    /// Team members that shared keys are wrapped for
+    #[command(subcommand)]
+    Team(TeamCommands),
     /// Monitoring and performance commands
     #[command(subcommand)]
     Monitor(MonitorCommands),
+    /// Reports over detected secrets
+    #[command(subcommand)]
+    Report(ReportCommands),
+    /// Triage the findings recorded by scans in .cargocrypt/findings.json
+    #[command(subcommand)]
+    Findings(FindingsCommands),
+    /// Custom detection rules from .cargocrypt/rules.yml and shared rule packs
+    #[command(subcommand)]
+    Rules(RulesCommands),
+    /// Keyfile management
+    #[command(subcommand)]
+    Key(KeyCommands),
+    /// Session agent caching derived keys (like ssh-agent)
+    #[command(subcommand)]
+    Agent(AgentCommands),
+    /// Named vaults with independent keys (e.g. dev, staging, prod)
+    #[command(subcommand)]
+    Vault(VaultCommands),
+    /// Named secrets kept in the encrypted store .cargocrypt/secrets.enc
+    #[command(subcommand)]
+    Secret(SecretCommands),
+    /// Password-protected backups of .cargocrypt/: configuration, keys, team data and the secret store
+    #[command(subcommand)]
+    Backup(BackupCommands),
+    /// .env files encrypted per variable into .env.enc, merged over .env.example
+    #[command(subcommand)]
+    Env(EnvCommands),
+    /// Large encrypted files shared through refs/cargocrypt/storage instead of the working tree
+    #[command(subcommand)]
+    Store(StoreCommands),
+}
+
+#[derive(Subcommand)]
+enum StoreCommands {
+    /// Encrypt a file (unless it is encrypted already) and add it to the store
+    Add {
+        file: PathBuf,
+        /// Name to store it under (defaults to the file's path)
+        #[arg(long)]
+        name: Option<String>,
+        /// Read the password from stdin instead of prompting
+        #[arg(long)]
+        password_stdin: bool,
+    },
+    /// Decrypt a stored file
+    Get {
+        name: String,
+        /// Where to write it (defaults to the path it was stored from)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Write the encrypted file instead of decrypting it
+        #[arg(long)]
+        encrypted: bool,
+        /// Read the password from stdin instead of prompting
+        #[arg(long)]
+        password_stdin: bool,
+    },
+    /// List stored files
+    List,
+    /// Remove a file from the store
+    Rm { name: String },
+    /// Remove data no stored file refers to any more
+    Gc {
+        /// Also drop the store's history so git can prune removed data (the next push needs --force)
+        #[arg(long)]
+        squash: bool,
+    },
+    /// Push the store to a remote
+    Push {
+        #[arg(default_value = "origin")]
+        remote: String,
+        /// Overwrite the remote store, e.g. after `store gc --squash`
+        #[arg(long)]
+        force: bool,
+    },
+    /// Fetch the store of a remote and merge it
+    Pull {
+        #[arg(default_value = "origin")]
+        remote: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum EnvCommands {
+    /// Encrypt each value of a .env file into <file>.enc; unchanged values keep their ciphertext
+    Encrypt {
+        #[arg(default_value = ".env")]
+        file: PathBuf,
+        /// Encrypted file to write (default: <file>.enc)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Defaults left out of the encrypted file (default: .env.example next to the file, if present)
+        #[arg(long)]
+        example: Option<PathBuf>,
+        /// Read the password from stdin instead of prompting
+        #[arg(long)]
+        password_stdin: bool,
+    },
+    /// Write the plaintext .env from .env.enc, merged over .env.example
+    Decrypt {
+        #[arg(default_value = ".env.enc")]
+        file: PathBuf,
+        /// Plaintext file to write (default: <file> without .enc)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Defaults to merge the values over (default: .env.example next to the file, if present)
+        #[arg(long)]
+        example: Option<PathBuf>,
+        /// Overwrite a plaintext file with changes that are not encrypted yet
+        #[arg(long)]
+        force: bool,
+        /// Read the password from stdin instead of prompting
+        #[arg(long)]
+        password_stdin: bool,
+    },
+    /// Show variables that differ between .env.enc, the plaintext .env and .env.example (exits 2 if any do)
+    Diff {
+        #[arg(default_value = ".env.enc")]
+        file: PathBuf,
+        /// Variable documentation to check against (default: .env.example next to the file, if present)
+        #[arg(long)]
+        example: Option<PathBuf>,
+        /// Read the password from stdin instead of prompting (needed only if the plaintext file exists)
+        #[arg(long)]
+        password_stdin: bool,
+    },
+    /// Print the decrypted variables merged over .env.example, e.g. for `eval "$(cargocrypt env export --format shell)"`
+    Export {
+        #[arg(default_value = ".env.enc")]
+        file: PathBuf,
+        /// Defaults to merge the values over (default: .env.example next to the file, if present)
+        #[arg(long)]
+        example: Option<PathBuf>,
+        #[arg(long, value_enum, default_value = "dotenv")]
+        format: EnvFormat,
+        /// Read the password from stdin instead of prompting
+        #[arg(long)]
+        password_stdin: bool,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum EnvFormat {
+    /// NAME=value lines
+    Dotenv,
+    /// export NAME='value' lines for a POSIX shell
+    Shell,
+    Json,
+}
+
+#[derive(Subcommand)]
+enum SecretCommands {
+    /// Save a secret (the value is prompted for, or read from stdin with --stdin)
+    Set {
+        name: String,
+        /// Read the value from stdin (a trailing newline is dropped)
+        #[arg(long)]
+        stdin: bool,
+        /// What the secret is for (kept from the previous version if omitted)
+        #[arg(long)]
+        description: Option<String>,
+        /// Expiry date of the value (YYYY-MM-DD or RFC 3339), e.g. when an API key lapses
+        #[arg(long)]
+        expires: Option<String>,
+        /// Rotate the secret this long after it is set, e.g. 90d (kept from the previous version if omitted)
+        #[arg(long)]
+        ttl: Option<String>,
+        /// Use the secret store of this vault
+        #[arg(long)]
+        vault: Option<String>,
+        /// Read the store password from stdin instead of prompting (with --stdin, from its first line)
+        #[arg(long)]
+        password_stdin: bool,
+    },
+    /// Print a secret's value
+    Get {
+        name: String,
+        /// Print an earlier version instead of the current value
+        #[arg(long)]
+        version: Option<u32>,
+        /// Use the secret store of this vault
+        #[arg(long)]
+        vault: Option<String>,
+        /// Read the store password from stdin instead of prompting
+        #[arg(long)]
+        password_stdin: bool,
+        /// Copy the value to the clipboard instead of printing it, clearing it after `security.clipboard_clear_secs`
+        #[arg(long)]
+        clip: bool,
+    },
+    /// List the names of stored secrets
+    List {
+        /// Use the secret store of this vault
+        #[arg(long)]
+        vault: Option<String>,
+        /// Read the store password from stdin instead of prompting
+        #[arg(long)]
+        password_stdin: bool,
+    },
+    /// Show every saved version of a secret
+    History {
+        name: String,
+        /// Use the secret store of this vault
+        #[arg(long)]
+        vault: Option<String>,
+        /// Read the store password from stdin instead of prompting
+        #[arg(long)]
+        password_stdin: bool,
+    },
+    /// Make an earlier version of a secret current again
+    Rollback {
+        name: String,
+        /// Version to restore, as shown by `secret history`
+        #[arg(long)]
+        version: u32,
+        /// Use the secret store of this vault
+        #[arg(long)]
+        vault: Option<String>,
+        /// Read the store password from stdin instead of prompting
+        #[arg(long)]
+        password_stdin: bool,
+    },
+    /// List secrets that expire or are due for rotation soon
+    Expiring {
+        /// Warning window in days (defaults to the configured window)
+        #[arg(long)]
+        within: Option<u32>,
+        /// List every secret with an expiry date or TTL
+        #[arg(long)]
+        all: bool,
+        /// Exit with status 2 if any secret is expired or due soon (for CI)
+        #[arg(long)]
+        check: bool,
+        /// Use the secret store of this vault
+        #[arg(long)]
+        vault: Option<String>,
+        /// Read the store password from stdin instead of prompting
+        #[arg(long)]
+        password_stdin: bool,
+    },
+    /// Remove a secret with all its versions
+    Rm {
+        name: String,
+        /// Use the secret store of this vault
+        #[arg(long)]
+        vault: Option<String>,
+        /// Read the store password from stdin instead of prompting
+        #[arg(long)]
+        password_stdin: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum VaultCommands {
+    /// Create a vault with its own key and password
+    Create {
+        name: String,
+        /// What the vault is for
+        #[arg(long)]
+        description: Option<String>,
+        /// Read the vault password from stdin instead of prompting
+        #[arg(long)]
+        password_stdin: bool,
+    },
+    /// List the project's vaults
+    List,
+    /// Change a vault's password (files in the vault are unaffected)
+    Passwd { name: String },
+    /// Write a vault's key as a keyfile, e.g. to give CI access to one vault
+    ExportKey {
+        name: String,
+        /// Where to write the keyfile
+        path: PathBuf,
+        /// Read the vault password from stdin instead of prompting
+        #[arg(long)]
+        password_stdin: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum BackupCommands {
+    /// Pack .cargocrypt/ into one archive encrypted with a password of its own
+    Create {
+        /// Where to write the backup [default: .cargocrypt/backups/<date>.ccbackup]
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Read the backup password from stdin instead of prompting
+        #[arg(long)]
+        password_stdin: bool,
+    },
+    /// Restore .cargocrypt/ from a backup; files not in the backup are kept
+    Restore {
+        backup: PathBuf,
+        /// Replace files that were changed since the backup
+        #[arg(long)]
+        force: bool,
+        /// Only list the files that would be restored
+        #[arg(long)]
+        dry_run: bool,
+        /// Read the backup password from stdin instead of prompting
+        #[arg(long)]
+        password_stdin: bool,
+    },
+    /// List the backups in .cargocrypt/backups/, or the files in one backup
+    List {
+        /// Backup whose files to list (asks for its password)
+        backup: Option<PathBuf>,
+        /// Read the backup password from stdin instead of prompting
+        #[arg(long)]
+        password_stdin: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum AgentCommands {
+    /// Start the agent in the background and print its socket variable
+    Start {
+        /// How long derived keys are kept, in seconds
+        #[arg(long)]
+        ttl: Option<u64>,
+        /// Run in the foreground instead of detaching
+        #[arg(long)]
+        foreground: bool,
+    },
+    /// Stop the running agent
+    Stop,
+    /// Show whether an agent is running and how many keys it holds
+    Status,
+    /// Forget all cached keys and credentials without stopping the agent
+    Clear,
+    /// Hold the filter password (or a vault's) for the git filters until the agent's TTL runs out
+    Unlock {
+        /// Unlock the password of this vault instead of the filter password
+        #[arg(long)]
+        vault: Option<String>,
+        /// Read the password from stdin instead of prompting
+        #[arg(long)]
+        password_stdin: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum KeyCommands {
+    /// Generate a random 32-byte keyfile readable only by the current user
+    Generate {
+        /// Where to write the keyfile
+        #[arg(default_value = ".cargocrypt/keys/default.key")]
+        path: PathBuf,
+        /// Replace an existing keyfile
+        #[arg(long)]
+        force: bool,
+    },
 }
 
 #[derive(Subcommand)]
 enum GitCommands {
-    /// Install git hooks for automatic secret detection
+    /// Install git hooks for automatic secret detection (as configured in .cargocrypt/hooks.toml)
     InstallHooks,
     /// Uninstall git hooks
     UninstallHooks,
+    /// Inspect the installed git hooks
+    #[command(subcommand)]
+    Hooks(GitHooksCommands),
+    /// Print a server-side pre-receive hook that rejects pushes adding secrets
+    GeneratePreReceive {
+        /// Write the hook to a file (made executable) instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
     /// Configure git attributes for automatic encryption
-    ConfigureAttributes,
+    ConfigureAttributes {
+        /// Only list the files that would be created or changed
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Scan the working tree and propose encryption patterns for files with high-confidence secrets
+    SuggestPatterns {
+        /// Add the patterns to .gitattributes, confirming each one
+        #[arg(long)]
+        apply: bool,
+        /// With --apply, add every suggested pattern without asking
+        #[arg(long, short, requires = "apply")]
+        yes: bool,
+    },
     /// Clean filter for git (used internally)
     FilterClean {
-        /// File being processed (placeholder - content comes from stdin)
+        /// Path of the file in the repository (git's %f), which selects its key; content comes from stdin
         #[arg(default_value = "-")]
-        file: String,
+        file: PathBuf,
     },
     /// Smudge filter for git (used internally)
     FilterSmudge {
-        /// File being processed (placeholder - content comes from stdin)
+        /// Path of the file in the repository (git's %f), which selects its key; content comes from stdin
         #[arg(default_value = "-")]
-        file: String,
+        file: PathBuf,
+    },
+    /// Show encrypted files decrypted in git diff and git log -p
+    ConfigureDiff,
+    /// Diff textconv for git (used internally)
+    Textconv {
+        /// File holding the blob, written by git
+        file: PathBuf,
+    },
+    /// Merge driver for encrypted files (used internally)
+    MergeDriver {
+        /// Common ancestor (git's %O)
+        base: PathBuf,
+        /// Our side, overwritten with the result (git's %A)
+        ours: PathBuf,
+        /// Their side (git's %B)
+        theirs: PathBuf,
+        /// Path of the file in the repository (git's %P), which selects its key
+        file: PathBuf,
     },
     /// Update .gitignore with CargoCrypt patterns
-    UpdateIgnore,
+    UpdateIgnore {
+        /// Only list the files that would be created or changed
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// List the packages of the repository and the ones with their own .cargocrypt/git.toml
+    Workspace,
+    /// Show the policy of the current branch and whether the hooks and stored files meet it
+    Status,
+    /// Check that commits store the files marked for encryption as ciphertext; works in bare repositories
+    VerifyEncrypted {
+        /// Commits or ranges (e.g. origin/main..HEAD) to check
+        #[arg(default_value = "HEAD")]
+        revisions: Vec<String>,
+    },
+    /// Passwords of the git filters in the OS keyring
+    #[command(subcommand)]
+    Credentials(GitCredentialsCommands),
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Print the effective value of a setting, e.g. `key_params.time_cost`, and where it comes from
+    Get {
+        /// Dotted setting name; a section name prints the whole section
+        key: String,
+    },
+    /// Change a setting in the project configuration, checking it against the known settings
+    Set {
+        /// Dotted setting name, or `profiles.<name>.<setting>` for a profile
+        key: String,
+        /// New value, as TOML (`4`, `true`, `{ id = "scrypt" }`) or a plain string
+        value: String,
+        /// Change the user configuration instead
+        #[arg(long)]
+        user: bool,
+    },
+    /// Edit the project configuration in $VISUAL or $EDITOR, saving it only when it is valid
+    Edit {
+        /// Edit the user configuration instead
+        #[arg(long)]
+        user: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum GitCredentialsCommands {
+    /// Show where the filters find each password they need
+    Status,
+    /// Store the filter password (or a vault's) in the OS keyring
+    Store {
+        /// Store the password of this vault instead of the filter password
+        #[arg(long)]
+        vault: Option<String>,
+        /// Read the password from stdin instead of prompting
+        #[arg(long)]
+        password_stdin: bool,
+        /// Move the password from plaintext git config (cargocrypt.password) into the keyring
+        #[arg(long, conflicts_with = "password_stdin")]
+        from_git_config: bool,
+    },
+    /// Remove the filter password (or a vault's) from the OS keyring
+    Forget {
+        /// Remove the password of this vault instead of the filter password
+        #[arg(long)]
+        vault: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum GitHooksCommands {
+    /// Show the installed hooks, their versions and drift from what install-hooks writes
+    Status,
+    /// Show skipped checks and --no-verify commits from the hook audit log, newest first
+    Audit {
+        /// Number of entries to show
+        #[arg(short = 'n', long, default_value = "20")]
+        limit: usize,
+    },
+}
+
+#[derive(Subcommand)]
+enum HookCommands {
+    /// Run a git hook in-process, after the hook it replaced (used by the installed hooks)
+    Exec {
+        hook: ExecHook,
+        /// Arguments git passed to the hook
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum MigrateCommands {
+    /// Take over a git-crypt repository without re-encrypting its history
+    GitCrypt {
+        /// Key exported with 'git-crypt export-key' (default: the unlocked key, else the GPG-wrapped keys)
+        #[arg(long)]
+        key_file: Option<PathBuf>,
+        /// Name of the git-crypt key (git-crypt -k)
+        #[arg(long, default_value = "default")]
+        key_name: String,
+        /// Encrypt new commits in CargoCrypt's format instead of staying git-crypt compatible
+        #[arg(long)]
+        reencrypt: bool,
+        /// Also wrap the key for these GPG fingerprints, like 'git-crypt add-gpg-user'
+        #[arg(long = "gpg-user", value_name = "FINGERPRINT")]
+        gpg_users: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum TeamCommands {
+    /// Set up team key sharing in this repository
+    Init,
+    /// Add a member, wrapping the shared keys for their X25519 or OpenPGP key
+    #[command(group(clap::ArgGroup::new("recipient").required(true)))]
+    AddMember {
+        /// Member ID, usually an email address
+        id: String,
+        /// Member's X25519 public key: hex, a file such as the .pub written by 'team identity', or an https:// URL
+        #[arg(long, group = "recipient", value_name = "KEY|FILE|URL")]
+        public_key: Option<String>,
+        /// Member's GPG key from the local keyring (fingerprint, key ID or email)
+        #[arg(long, group = "recipient", value_name = "FINGERPRINT")]
+        gpg: Option<String>,
+        /// Role of the new member
+        #[arg(long, value_enum, default_value = "member")]
+        role: TeamRole,
+        /// Your X25519 identity, to wrap the existing shared keys for the new member
+        #[arg(long, conflicts_with = "gpg_identity")]
+        identity: Option<PathBuf>,
+        /// Use your GPG secret key to wrap the existing shared keys for the new member
+        #[arg(long)]
+        gpg_identity: bool,
+        /// Member's Ed25519 public key for signing team changes (hex, file or https:// URL); Owners and Admins need one unless added with --gpg
+        #[arg(long, value_name = "KEY|FILE|URL")]
+        signing_key: Option<String>,
+        #[command(flatten)]
+        signing: TeamSigning,
+    },
+    /// Remove a member and rotate the shared keys they held
+    RemoveMember {
+        /// Member ID
+        id: String,
+        #[command(flatten)]
+        signing: TeamSigning,
+    },
+    /// Remove a member, rotate their keys and, with --rekey, re-encrypt the files those keys protect
+    Revoke {
+        /// Member ID
+        id: String,
+        /// Re-encrypt and stage the tracked files keyed by the rotated team keys
+        #[arg(long)]
+        rekey: bool,
+        /// X25519 identity the filters unwrap the new keys with, if not configured
+        #[arg(long, value_name = "FILE")]
+        identity: Option<PathBuf>,
+        /// Only list the team files and, with --rekey, the files that would change
+        #[arg(long)]
+        dry_run: bool,
+        #[command(flatten)]
+        signing: TeamSigning,
+    },
+    /// Print the public key you sign team changes with, creating the signing key on first use
+    SigningKey {
+        /// Read the signing key password from stdin
+        #[arg(long)]
+        password_stdin: bool,
+    },
+    /// Generate an X25519 identity and print its public key
+    Identity {
+        /// Where to save the secret key (the public key goes next to it, with .pub appended)
+        #[arg(default_value = ".cargocrypt/identity")]
+        path: PathBuf,
+    },
+    /// List team members
+    List,
+    /// Rotate one shared key, or all of them
+    Rotate {
+        /// ID of the key to rotate (default: all keys)
+        key_id: Option<String>,
+        #[command(flatten)]
+        signing: TeamSigning,
+    },
+    /// Show team statistics
+    Stats,
+    /// Show the team audit trail (signed git notes on the team ref), newest first
+    Audit {
+        /// Number of entries to show
+        #[arg(short = 'n', long, default_value = "20")]
+        limit: usize,
+        /// Only show entries of this operation (e.g. member_addition, key_generation)
+        #[arg(long)]
+        operation: Option<String>,
+        /// Only show entries of this actor
+        #[arg(long)]
+        actor: Option<String>,
+    },
+    /// Seal the shared keys into a recovery bundle that a threshold of Owners can open
+    #[command(group(clap::ArgGroup::new("secret")))]
+    Escrow {
+        /// Number of Owners needed to recover the keys
+        #[arg(long)]
+        threshold: u8,
+        /// Your X25519 identity, to unwrap the shared keys being escrowed (default: every identity found locally)
+        #[arg(long, group = "secret", value_name = "FILE")]
+        identity: Option<PathBuf>,
+        /// Use your GPG secret key to unwrap the shared keys being escrowed
+        #[arg(long, group = "secret")]
+        gpg_identity: bool,
+        #[command(flatten)]
+        signing: TeamSigning,
+    },
+    /// Unwrap your share of the escrow bundle, to hand to whoever runs 'team recover'
+    #[command(group(clap::ArgGroup::new("secret")))]
+    EscrowShare {
+        /// Where to write the share; keep it private
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Your X25519 identity (default: every identity found locally)
+        #[arg(long, group = "secret", value_name = "FILE")]
+        identity: Option<PathBuf>,
+        /// Use your GPG secret key
+        #[arg(long, group = "secret")]
+        gpg_identity: bool,
+    },
+    /// Open the escrow bundle with Owners' shares and wrap the keys for every current member
+    Recover {
+        /// Share files written by 'team escrow-share'
+        #[arg(required = true, value_name = "SHARE")]
+        shares: Vec<PathBuf>,
+        #[command(flatten)]
+        signing: TeamSigning,
+    },
+    /// Fetch the team state of a remote and merge it into yours
+    Fetch {
+        /// Remote to fetch from
+        #[arg(default_value = "origin")]
+        remote: String,
+        #[command(flatten)]
+        signing: TeamSigning,
+    },
+    /// Push your team state to a remote
+    Push {
+        /// Remote to push to
+        #[arg(default_value = "origin")]
+        remote: String,
+    },
+}
+
+/// How team changes are signed
+#[derive(clap::Args)]
+struct TeamSigning {
+    /// Sign with this GPG key instead of the project signing key
+    #[arg(long, value_name = "FINGERPRINT")]
+    gpg_sign: Option<String>,
+    /// Read the signing key password from stdin
+    #[arg(long)]
+    password_stdin: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ExecHook {
+    PreCommit,
+    PrePush,
+    PostCommit,
+}
+
+#[derive(Subcommand)]
+enum MonitorCommands {
+    /// Show current system metrics
+    Metrics,
+    /// Display real-time monitoring dashboard
+    Dashboard,
+    /// Start monitoring HTTP server
+    Server {
+        /// Port to listen on
+        #[arg(long, default_value = "3030")]
+        port: u16,
+        /// Host to bind to
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+    },
+    /// Show performance alerts
+    Alerts,
+    /// Export metrics to JSON
+    Export {
+        /// Output file path
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Health check
+    Health,
+}
+
+#[derive(Subcommand)]
+enum ScanCommands {
+    /// Label a finding so later scans of this repository weight its rule accordingly
+    Feedback {
+        /// Id of the finding, as shown by `cargocrypt scan`
+        id: String,
+        /// The finding is a real secret
+        #[arg(long, conflicts_with = "false_positive", required_unless_present = "false_positive")]
+        true_positive: bool,
+        /// The finding is not a secret; hide it and lower the confidence of its rule
+        #[arg(long)]
+        false_positive: bool,
+        /// Directory or file the finding is in
+        #[arg(long, default_value = ".")]
+        path: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum FindingsCommands {
+    /// List open findings (new and triaged)
+    List {
+        /// Only list findings in this state
+        #[arg(long, value_enum, conflicts_with = "all")]
+        state: Option<cargocrypt::detection::TriageState>,
+        /// Also list accepted and fixed findings
+        #[arg(long)]
+        all: bool,
+    },
+    /// Acknowledge a finding as triaged, or as an accepted risk
+    Ack {
+        /// Id of the finding, or a unique prefix of it
+        id: String,
+        /// Keep the secret deliberately instead of working on it
+        #[arg(long)]
+        accept_risk: bool,
+        /// Why, for whoever reads the finding next
+        #[arg(long)]
+        note: Option<String>,
+    },
+    /// Mark a finding as fixed
+    Resolve {
+        /// Id of the finding, or a unique prefix of it
+        id: String,
+        /// How it was fixed
+        #[arg(long)]
+        note: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum RulesCommands {
+    /// Validate a rules file, listing every problem
+    Lint {
+        /// Rules file (defaults to the project's .cargocrypt/rules.yml or rules.toml)
+        file: Option<PathBuf>,
+    },
+    /// Install a signed rule pack from an HTTPS URL or a git repository
+    Add {
+        /// HTTPS URL of the pack file (signature at <url>.sig), or URL of a git repository
+        source: String,
+        /// Public key (hex) the pack must be signed with
+        #[arg(long)]
+        key: String,
+        /// Branch, tag or commit of a git repository
+        #[arg(long = "ref")]
+        git_ref: Option<String>,
+        /// Pack file in a git repository (defaults to ruleset.yml)
+        #[arg(long)]
+        path: Option<String>,
+    },
+    /// Fetch the installed rule packs again and install newer versions
+    Update {
+        /// Only update this pack
+        name: Option<String>,
+    },
+    /// Uninstall a rule pack
+    Remove {
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ReportCommands {
+    /// Aggregate findings by directory and severity into a heatmap
+    Heatmap {
+        /// Directory to scan
+        #[arg(default_value = ".")]
+        path: PathBuf,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = HeatmapFormat::Html)]
+        format: HeatmapFormat,
+        /// Output file (defaults to secrets-heatmap.html for HTML, stdout for JSON)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// List encrypted files and secrets that expire soon, for rotation planning
+    Expiring {
+        /// Warning window in days (defaults to the configured window)
+        #[arg(long)]
+        within: Option<u32>,
+        /// List every item with an expiry date
+        #[arg(long)]
+        all: bool,
+        /// Print nothing when no item needs attention; set by hooks, and by the global --quiet
+        #[arg(skip)]
+        quiet: bool,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ScanFormat {
+    Table,
+    Json,
+    Csv,
+    Sarif,
+    /// NDJSON events with Elastic Common Schema fields, for SIEM ingestion
+    Ecs,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum HeatmapFormat {
+    Html,
+    Json,
+}
+
+#[tokio::main]
+async fn main() {
+    let args = cli_args();
+    let cli = cli_command()
+        .try_get_matches_from(args)
+        .and_then(|matches| Cli::from_arg_matches(&matches))
+        .unwrap_or_else(|e| exit_with(e));
+    cargocrypt::project::set_manifest_path(cli.manifest_path.clone());
+    cargocrypt::progress::set_quiet(cli.quiet);
+    if let Some(secs) = cli.wait {
+        cargocrypt::lock::set_wait(std::time::Duration::from_secs(secs));
+    }
+    if cli.profile.is_some() {
+        cargocrypt::config::set_profile(cli.profile);
+    }
+    let from_env = std::env::var(NON_INTERACTIVE_ENV)
+        .is_ok_and(|value| !matches!(value.trim().to_ascii_lowercase().as_str(), "" | "0" | "false" | "no"));
+    NON_INTERACTIVE.store(cli.non_interactive || from_env, Ordering::SeqCst);
+    JSON_OUTPUT.store(cli.json, Ordering::SeqCst);
+    if cli.json && !supports_json(&cli.command) {
+        fail(exit_code::USAGE, "--json is not supported by this command");
+    }
+
+    if let Err(error) = run(cli.command).await {
+        if json_output() {
+            print_error_json(error.exit_code(), &error.kind(), &error.to_string(), error.suggestion());
+        } else {
+            eprintln!("Error: {:?}", error);
+        }
+        std::process::exit(error.exit_code());
+    }
+}
+
+/// Arguments of this process, without the `crypt` that cargo passes to `cargo-crypt`
+fn cli_args() -> Vec<std::ffi::OsString> {
+    let mut args: Vec<_> = std::env::args_os().collect();
+    if args.get(1).is_some_and(|arg| arg == "crypt") {
+        args.remove(1);
+        CARGO_SUBCOMMAND.store(true, Ordering::SeqCst);
+    }
+    args
+}
+
+/// The command-line interface, named as it was invoked
+fn cli_command() -> clap::Command {
+    let command = Cli::command();
+    if CARGO_SUBCOMMAND.load(Ordering::SeqCst) {
+        command.bin_name("cargo crypt")
+    } else {
+        command
+    }
+}
+
+/// Whether `command` prints a JSON result with `--json`
+fn supports_json(command: &Commands) -> bool {
+    match command {
+        Commands::Encrypt { file, .. } | Commands::Decrypt { file, .. } => !file.as_deref().is_some_and(is_pipe),
+        Commands::Scan { action: None, watch: false, .. } => true,
+        Commands::Config { .. } | Commands::Status | Commands::Inspect { .. } | Commands::Bench { .. } => true,
+        Commands::Git(command) => matches!(
+            command,
+            GitCommands::Status
+                | GitCommands::VerifyEncrypted { .. }
+                | GitCommands::SuggestPatterns { .. }
+                | GitCommands::ConfigureAttributes { dry_run: true }
+                | GitCommands::UpdateIgnore { dry_run: true }
+        ),
+        Commands::Monitor(command) => matches!(
+            command,
+            MonitorCommands::Metrics | MonitorCommands::Alerts | MonitorCommands::Export { .. } | MonitorCommands::Health
+        ),
+        Commands::Secret(SecretCommands::Expiring { .. }) | Commands::Report(ReportCommands::Expiring { .. }) => true,
+        Commands::Env(_) | Commands::Backup(_) | Commands::Doctor { .. } => true,
+        Commands::Open { .. } | Commands::Close { expires_at: None, .. } | Commands::Edit { .. } => true,
+        Commands::Init { dry_run, .. } | Commands::Redact { dry_run, .. } => *dry_run,
+        Commands::Team(TeamCommands::Revoke { dry_run, .. }) => *dry_run,
+        _ => false,
+    }
+}
+
+/// Whether commands print JSON instead of text
+fn json_output() -> bool {
+    JSON_OUTPUT.load(Ordering::SeqCst)
+}
+
+/// Print a command result as JSON
+fn print_json<T: serde::Serialize + ?Sized>(value: &T) -> CryptoResult<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// Print an error as JSON: `{"error": {"code", "kind", "message", "suggestion"}}`
+fn print_error_json<K: serde::Serialize + ?Sized>(code: i32, kind: &K, message: &str, suggestion: Option<&str>) {
+    let error = serde_json::json!({
+        "error": { "code": code, "kind": kind, "message": message, "suggestion": suggestion },
+    });
+    println!("{}", serde_json::to_string_pretty(&error).unwrap_or_default());
+}
+
+/// JSON result of encrypting or decrypting a file
+fn file_result(operation: &str, input: &Path, output: &Path, vault: Option<&str>) -> serde_json::Value {
+    serde_json::json!({ "operation": operation, "input": input, "output": output, "vault": vault })
+}
+
+/// Decrypt with a password, asking again after a wrong one
+///
+/// Prompts get `max_password_attempts` tries, waiting `retry_delay_ms`
+/// before the second and twice as long before each one after; a password
+/// from stdin gets one. Wrong passwords count towards the lockout of
+/// `target` when `lockout_after` is set (see [`cargocrypt::lockout`]).
+async fn with_password_retries<T, F, Fut>(
+    crypt: &CargoCrypt,
+    target: &str,
+    prompt: &str,
+    password_stdin: bool,
+    mut decrypt: F,
+) -> CryptoResult<T>
+where
+    F: FnMut(SecureString) -> Fut,
+    Fut: std::future::Future<Output = CryptoResult<T>>,
+{
+    use cargocrypt::lockout::FailedAttempts;
+
+    let security = crypt.config().await.security;
+    let project_root = crypt.project_root();
+    if security.lockout_after > 0 {
+        FailedAttempts::check(project_root, target)?;
+    }
+    let attempts = if password_stdin { 1 } else { security.max_password_attempts.max(1) };
+    let mut delay = std::time::Duration::from_millis(security.retry_delay_ms);
+    let mut attempt = 1;
+    loop {
+        let password = if password_stdin { read_password_stdin()? } else { prompt_password(prompt)? };
+        let error = match decrypt(password).await {
+            Ok(value) => {
+                if security.lockout_after > 0 {
+                    FailedAttempts::record_success(project_root, target).await?;
+                }
+                return Ok(value);
+            }
+            Err(e) if e.is_authentication_failure() => e,
+            Err(e) => return Err(e),
+        };
+        if security.lockout_after > 0 {
+            let lockout = std::time::Duration::from_secs(security.lockout_minutes * 60);
+            if let Some(locked) = FailedAttempts::record_failure(project_root, target, security.lockout_after, lockout).await? {
+                return Err(locked);
+            }
+        }
+        if attempt >= attempts {
+            return Err(error);
+        }
+        eprintln!("❌ Wrong password or key ({} of {} attempts)", attempt, attempts);
+        tokio::time::sleep(delay).await;
+        delay = delay.saturating_mul(2);
+        attempt += 1;
+    }
+}
+
+/// How long `edit` records its file as open; the session watcher closes it
+/// then if `edit` was killed before it could
+const EDIT_TTL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// Run the user's editor on `path` and wait for it, leaving Ctrl-C to the editor
+///
+/// The editor shares the terminal, so an interrupt reaches it directly;
+/// `edit` keeps running to encrypt the file again once the editor exits.
+async fn run_editor_uninterrupted(path: &Path) -> CryptoResult<()> {
+    let path = path.to_path_buf();
+    let editor = tokio::task::spawn_blocking(move || cargocrypt::config::run_editor(&path));
+    tokio::pin!(editor);
+    loop {
+        tokio::select! {
+            result = &mut editor => return result.map_err(std::io::Error::other)?,
+            _ = tokio::signal::ctrl_c() => {}
+        }
+    }
+}
+
+/// Open `file` for `ttl` with [`CargoCrypt::open_file`], asking for its
+/// password (or its vault's) if it needs one
+///
+/// Returns the password too, for closing the session.
+async fn open_session(
+    crypt: &CargoCrypt,
+    file: &Path,
+    keyfile: Option<&Path>,
+    ttl: std::time::Duration,
+    in_place: bool,
+    password_stdin: bool,
+) -> CryptoResult<(cargocrypt::session::Session, Option<SecureString>)> {
+    let header = cargocrypt::crypto::EncryptedSecret::from_bytes(&tokio::fs::read(file).await?)?;
+    if header.vault().is_none() && !header.key_source().requires_password() {
+        return Ok((crypt.open_file(file, None, keyfile, ttl, in_place).await?, None));
+    }
+
+    let (target, prompt) = match header.vault() {
+        Some(vault) => (format!("vault '{}'", vault), format!("Enter password for vault '{}': ", vault)),
+        None => (attempt_target(crypt, file), "Enter password for decryption: ".to_string()),
+    };
+    let (session, password) = with_password_retries(crypt, &target, &prompt, password_stdin, |password| async move {
+        let session = crypt.open_file(file, Some(password.as_str()), keyfile, ttl, in_place).await?;
+        Ok((session, password))
+    })
+    .await?;
+    Ok((session, Some(password)))
+}
+
+/// Start the process that closes `session` when it expires
+///
+/// It is `cargocrypt close --expires-at`, detached from the terminal, and
+/// gets the password on stdin so it can encrypt a changed file again.
+fn spawn_session_watcher(crypt: &CargoCrypt, session: &cargocrypt::session::Session, password: Option<&SecureString>) -> CryptoResult<()> {
+    let encrypted = crypt.project_root().join(&session.encrypted);
+    let mut command = std::process::Command::new(std::env::current_exe()?);
+    command
+        .arg("close")
+        .arg(&encrypted)
+        .args(["--expires-at", &session.expires_at.to_string()])
+        .current_dir(crypt.project_root());
+    if password.is_some() {
+        command.arg("--password-stdin");
+    }
+    spawn_detached(&mut command, password.map(SecureString::as_str))
+}
+
+/// Start `command` in the background, detached from the terminal, writing `input` as a line to its stdin
+fn spawn_detached(command: &mut std::process::Command, input: Option<&str>) -> CryptoResult<()> {
+    use std::io::Write;
+
+    command
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null());
+    #[cfg(unix)]
+    {
+        // Keep the process alive when the terminal sends SIGINT to the group
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+
+    let mut child = command.spawn()?;
+    if let (Some(mut stdin), Some(input)) = (child.stdin.take(), input) {
+        stdin.write_all(format!("{}\n", input).as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Name under which wrong passwords for `file` are counted: its path in the project
+fn attempt_target(crypt: &CargoCrypt, file: &Path) -> String {
+    let absolute = std::path::absolute(file).unwrap_or_else(|_| file.to_path_buf());
+    absolute.strip_prefix(crypt.project_root()).unwrap_or(&absolute).display().to_string()
+}
+
+/// Decrypt `file`, encrypted with a team key, trying every shared key wrapped for `identity` or else for the identities found locally
+async fn decrypt_with_team_keys(crypt: &CargoCrypt, file: &Path, identity: Option<PathBuf>) -> CryptoResult<PathBuf> {
+    use cargocrypt::crypto::{Keyfile, RecipientKeyPair};
+    use cargocrypt::git::{GitIntegration, TeamIdentity, TeamKeySharing};
+
+    let team = match GitIntegration::discover()? {
+        Some(git) => Some((TeamKeySharing::new(git.repo(), git.crypto())?, git.repo().workdir().to_path_buf())),
+        None => None,
+    };
+    let Some((team, workdir)) = team.filter(|(team, _)| team.is_initialized()) else {
+        if identity.is_some() {
+            return Err(CargoCryptError::Config {
+                message: "No team is set up in this repository".to_string(),
+                suggestion: Some("Run 'cargocrypt team init', or fetch the team with 'cargocrypt team pull'".to_string()),
+            });
+        }
+        return Err(CargoCryptError::Config {
+            message: format!("{} was encrypted with a keyfile", file.display()),
+            suggestion: Some("Pass --keyfile <path>".to_string()),
+        });
+    };
+    let identities = match identity {
+        Some(path) => vec![TeamIdentity::from(RecipientKeyPair::load(path)?)],
+        None => TeamIdentity::discover(&workdir)?,
+    };
+
+    // Keys of other purposes or older rotations simply do not decrypt it
+    for key in team.all_keys(&identities).await? {
+        let keyfile = Keyfile::from_bytes(key.key().as_slice())?;
+        match crypt.decrypt_file_with_keyfile(file, &keyfile, None).await {
+            Err(e) if e.exit_code() == exit_code::AUTH => continue,
+            result => return result,
+        }
+    }
+    Err(CargoCryptError::Config {
+        message: format!("No team key wrapped for your identities decrypts {}", file.display()),
+        suggestion: Some("Pass --keyfile if it was encrypted with a keyfile, or --identity with the identity the team lists for you".to_string()),
+    })
+}
+
+/// Keys for the managed files in `selected`, prompted for or read from stdin
+///
+/// The files' password comes first, then the password of each vault by
+/// name. The keyfile is `--keyfile` or the one in `[keys]` of the project
+/// configuration.
+async fn managed_keys(
+    crypt: &CargoCrypt,
+    selected: &[&cargocrypt::managed::ManagedFile],
+    password_stdin: bool,
+    keyfile: Option<PathBuf>,
+    confirm: bool,
+) -> CryptoResult<cargocrypt::managed::ManagedKeys> {
+    use cargocrypt::crypto::Keyfile;
+
+    let mut keys = cargocrypt::managed::ManagedKeys::default();
+    let direct: Vec<_> = selected.iter().filter(|file| file.vault.is_none()).collect();
+    if direct.iter().any(|file| file.key.requires_password()) {
+        keys.password = Some(if password_stdin {
+            read_password_stdin()?
+        } else {
+            let password = prompt_password(format!("Enter password for {} managed file(s): ", direct.len()))?;
+            if confirm && password != prompt_password("Confirm password: ")? {
+                fail(exit_code::FAILURE, "Passwords do not match");
+            }
+            password
+        });
+    }
+    if direct.iter().any(|file| file.key.requires_keyfile()) {
+        let configured = crypt.config().await.keys.keyfile.map(|path| crypt.project_root().join(path));
+        if let Some(path) = keyfile.or(configured) {
+            keys.keyfile = Some(Keyfile::load(&path)?);
+        }
+    }
+
+    let mut vaults: Vec<&str> = selected.iter().filter_map(|file| file.vault.as_deref()).collect();
+    vaults.sort_unstable();
+    vaults.dedup();
+    for vault in vaults {
+        let password = if password_stdin {
+            read_password_stdin()?
+        } else {
+            prompt_password(format!("Enter password for vault '{}': ", vault))?
+        };
+        keys.vaults.insert(vault.to_string(), password);
+    }
+    Ok(keys)
+}
+
+/// Print the result of `encrypt --all-managed` or `decrypt --all-managed`, exiting with FAILURE if a file failed
+fn print_managed_report(operation: &str, report: &cargocrypt::managed::ManagedReport) -> CryptoResult<()> {
+    use cargocrypt::managed::ManagedOutcome;
+
+    if json_output() {
+        let files: Vec<_> = report
+            .files
+            .iter()
+            .map(|(path, outcome)| match outcome {
+                ManagedOutcome::Written(output) => serde_json::json!({ "input": path, "output": output }),
+                ManagedOutcome::Skipped(reason) => serde_json::json!({ "input": path, "skipped": reason }),
+                ManagedOutcome::Failed(e) => serde_json::json!({ "input": path, "error": e.to_string() }),
+            })
+            .collect();
+        print_json(&serde_json::json!({ "operation": operation, "managed": true, "files": files }))?;
+    } else if report.files.is_empty() {
+        println!("✅ No managed files: files are recorded in .cargocrypt/files.toml as they are encrypted");
+    } else {
+        for (path, outcome) in &report.files {
+            match outcome {
+                ManagedOutcome::Written(output) => println!("✅ {} → {}", path.display(), output.display()),
+                ManagedOutcome::Skipped(reason) => println!("⏭️  {}: {}", path.display(), reason),
+                ManagedOutcome::Failed(e) => println!("❌ {}: {}", path.display(), e),
+            }
+        }
+        let done = if operation == "encrypt" { "Encrypted" } else { "Decrypted" };
+        println!("{} {} of {} managed file(s)", done, report.written(), report.files.len());
+    }
+    if !report.is_success() {
+        std::process::exit(exit_code::FAILURE);
+    }
+    Ok(())
+}
+
+/// Reject `encrypt` options that only work on a single file
+fn check_directory_flags(keyfile: bool, vault: bool, in_place: bool) {
+    if keyfile || vault || in_place {
+        cli_command()
+            .error(
+                clap::error::ErrorKind::ArgumentConflict,
+                "--keyfile, --vault and --in-place take a single file; a directory is encrypted with a password",
+            )
+            .exit();
+    }
+}
+
+/// Print what a `--dry-run` would change, with paths relative to the working directory
+fn print_plan(plan: &Plan) -> CryptoResult<()> {
+    let plan = match std::env::current_dir() {
+        Ok(dir) => plan.relative_to(&dir),
+        Err(_) => plan.clone(),
+    };
+    if json_output() {
+        return print_json(&serde_json::json!({ "dry_run": true, "changes": plan }));
+    }
+    if plan.is_empty() {
+        println!("🔍 Dry run: nothing would change");
+    } else {
+        println!("🔍 Dry run: {} change(s), nothing was written", plan.len());
+        print!("{}", plan);
+    }
+    Ok(())
+}
+
+/// The repository of the working directory for a `--dry-run`
+///
+/// Records in `plan` that one would be created when there is none.
+fn discover_git(plan: &mut Plan) -> CryptoResult<Option<cargocrypt::git::GitIntegration>> {
+    let git_integration = cargocrypt::git::GitIntegration::discover()?;
+    if git_integration.is_none() {
+        plan.push(Change::Create, ".git", Some("new repository"));
+    }
+    Ok(git_integration)
+}
+
+/// Report a failure without an error value and exit with `code`
+fn fail(code: i32, message: &str) -> ! {
+    if json_output() {
+        let kind = if code == exit_code::USAGE { "usage" } else { "failure" };
+        print_error_json(code, kind, message, None);
+    } else {
+        eprintln!("❌ Error: {}", message);
+    }
+    std::process::exit(code)
+}
+
+async fn run(command: Commands) -> CryptoResult<()> {
+    match command {
+        Commands::Init { git, dry_run: true, .. } => {
+            let mut plan = CargoCrypt::plan_init_project()?;
+            if git {
+                if let Some(git_integration) = discover_git(&mut plan)? {
+                    plan.extend(git_integration.plan_setup().await?);
+                }
+            }
+            print_plan(&plan)?;
+        }
+        Commands::Init { git, interactive: true, .. } => init_wizard(git).await?,
+        Commands::Init { git, .. } => {
+            CargoCrypt::init_project().await?;
+            println!("✅ CargoCrypt initialized successfully!");
+            
+            if git {
+                // Initialize git integration
+                use cargocrypt::git::GitIntegration;
+                
+                println!("🔧 Setting up Git integration...");
+                let mut git_integration = GitIntegration::new().await?;
+                git_integration.setup_repository().await?;
+                println!("✅ Git integration configured successfully!");
+            }
+        }
+        Commands::Encrypt { file: None, password_stdin, keyfile, in_place, shred, .. } => {
+            let crypt = CargoCrypt::new().await?;
+            let root = crypt.project_root().to_path_buf();
+            let managed = crypt.managed_files()?;
+            let selected: Vec<_> = managed.files.iter().filter(|file| file.state(&root).needs_encrypting(in_place)).collect();
+            let keys = managed_keys(&crypt, &selected, password_stdin, keyfile, true).await?;
+            let report = crypt.encrypt_managed(&keys, in_place, shred).await?;
+            print_managed_report("encrypt", &report)?;
+        }
+        Commands::Encrypt { file: Some(file), dry_run: true, keyfile, vault, expires, in_place, shred, force, .. } => {
+            if is_pipe(&file) {
+                cli_command().error(clap::error::ErrorKind::ArgumentConflict, "--dry-run cannot be used with `-`").exit();
+            }
+            if file.is_dir() {
+                check_directory_flags(keyfile.is_some(), vault.is_some(), in_place);
+            }
+            expires.as_deref().map(parse_expiry).transpose()?;
+            let crypt = CargoCrypt::new().await?;
+            if force {
+                crypt.update_config(|config| config.file_ops.overwrite_existing = true).await;
+            }
+            if let Some(vault) = &vault {
+                cargocrypt::vault::Vault::open(crypt.project_root(), vault)?;
+            }
+            let plan = if file.is_dir() {
+                let mut plan = Plan::new();
+                for path in crypt.files_to_encrypt(&file).await? {
+                    plan.extend(crypt.plan_encrypt_file(&path, false, false).await?);
+                }
+                plan
+            } else {
+                crypt.plan_encrypt_file(&file, in_place, shred).await?
+            };
+            print_plan(&plan)?;
+        }
+        Commands::Encrypt { file: Some(file), password_stdin, keyfile, vault, expires, in_place, force, .. } if file.is_dir() => {
+            use cargocrypt::{BatchFileOutcome, BatchOptions};
+
+            check_directory_flags(keyfile.is_some(), vault.is_some(), in_place);
+            let expires_at = expires.as_deref().map(parse_expiry).transpose()?;
+            let crypt = CargoCrypt::new().await?;
+            if force {
+                crypt.update_config(|config| config.file_ops.overwrite_existing = true).await;
+            }
+            let files = crypt.files_to_encrypt(&file).await?;
+            if files.is_empty() {
+                if json_output() {
+                    return print_json(&serde_json::json!({ "operation": "encrypt", "input": file, "files": [] }));
+                }
+                println!("✅ No files to encrypt in {}", file.display());
+                return Ok(());
+            }
+
+            let password = if password_stdin {
+                read_password_stdin()?
+            } else {
+                let password = prompt_password(format!("Enter password to encrypt {} file(s): ", files.len()))?;
+                if password != prompt_password("Confirm password: ")? {
+                    fail(exit_code::FAILURE, "Passwords do not match");
+                }
+                password
+            };
+
+            let progress = Progress::bytes("Encrypting");
+            let report = crypt.encrypt_batch(&files, &password, BatchOptions::new().progress(progress.clone())).await;
+            progress.finish();
+            if expires_at.is_some() {
+                for encrypted_file in report.encrypted() {
+                    crypt.set_file_expiry(encrypted_file, expires_at).await?;
+                }
+            }
+
+            if json_output() {
+                let results: Vec<_> = report
+                    .files
+                    .iter()
+                    .map(|(path, outcome)| match outcome {
+                        BatchFileOutcome::Encrypted(encrypted_file) => file_result("encrypt", path, encrypted_file, None),
+                        BatchFileOutcome::Failed(e) => serde_json::json!({ "input": path, "error": e.to_string() }),
+                        BatchFileOutcome::RolledBack => serde_json::json!({ "input": path, "error": "rolled back" }),
+                    })
+                    .collect();
+                print_json(&serde_json::json!({ "operation": "encrypt", "input": file, "files": results }))?;
+            } else {
+                for (path, e) in report.failures() {
+                    println!("❌ {}: {}", path.display(), e);
+                }
+                println!("✅ Encrypted {} of {} file(s) in {}", report.encrypted().count(), files.len(), file.display());
+            }
+            if !report.is_success() {
+                std::process::exit(exit_code::FAILURE);
+            }
+        }
+        Commands::Encrypt { file: Some(file), password_stdin, vault: Some(vault), expires, in_place, shred, force, .. } => {
+            if is_pipe(&file) {
+                check_pipe_flags(password_stdin, expires.is_some(), in_place);
+                cli_command().error(clap::error::ErrorKind::ArgumentConflict, "--vault cannot be used with `-`").exit();
+            }
+            let expires_at = expires.as_deref().map(parse_expiry).transpose()?;
+            let crypt = CargoCrypt::new().await?;
+            if force {
+                crypt.update_config(|config| config.file_ops.overwrite_existing = true).await;
+            }
+            let password = if password_stdin {
+                read_password_stdin()?
+            } else {
+                prompt_password(format!("Enter password for vault '{}': ", vault))?
+            };
+
+            let encrypted_file = if in_place {
+                crypt.encrypt_file_in_vault_in_place(&file, &vault, &password, shred).await?
+            } else {
+                crypt.encrypt_file_in_vault(&file, &vault, &password).await?
+            };
+            if expires_at.is_some() {
+                crypt.set_file_expiry(&encrypted_file, expires_at).await?;
+            }
+            if json_output() {
+                print_json(&file_result("encrypt", &file, &encrypted_file, Some(&vault)))?;
+            } else {
+                println!("✅ File encrypted in vault '{}': {}", vault, encrypted_file.display());
+            }
+        }
+        Commands::Encrypt { file: Some(file), password_stdin, keyfile: Some(keyfile), with_password, expires, in_place, shred, force, .. } => {
+            use cargocrypt::crypto::Keyfile;
+
+            let pipe = is_pipe(&file);
+            if pipe {
+                check_pipe_flags(password_stdin, expires.is_some(), in_place);
+            }
+            let expires_at = expires.as_deref().map(parse_expiry).transpose()?;
+            let crypt = CargoCrypt::new().await?;
+            if force {
+                crypt.update_config(|config| config.file_ops.overwrite_existing = true).await;
+            }
+            let keyfile = Keyfile::load(&keyfile)?;
+
+            let password = if password_stdin {
+                Some(read_password_stdin()?)
+            } else if with_password {
+                let password = prompt_password("Enter password to combine with the keyfile: ")?;
+                let password_confirm = prompt_password("Confirm password: ")?;
+
+                if password != password_confirm {
+                    fail(exit_code::FAILURE, "Passwords do not match");
+                }
+                Some(password)
+            } else {
+                None
+            };
+
+            if pipe {
+                crypt.encrypt_reader_to_writer_with_keyfile(tokio::io::stdin(), tokio::io::stdout(), &keyfile, password.as_deref()).await?;
+                return Ok(());
+            }
+
+            let encrypted_file = if in_place {
+                crypt.encrypt_file_with_keyfile_in_place(&file, &keyfile, password.as_deref(), shred).await?
+            } else {
+                crypt.encrypt_file_with_keyfile(&file, &keyfile, password.as_deref()).await?
+            };
+            if expires_at.is_some() {
+                crypt.set_file_expiry(&encrypted_file, expires_at).await?;
+            }
+            if json_output() {
+                print_json(&file_result("encrypt", &file, &encrypted_file, None))?;
+            } else {
+                println!("✅ File encrypted: {}", encrypted_file.display());
+            }
+        }
+        Commands::Encrypt { file: Some(file), password_stdin, expires, in_place, shred, force, .. } => {
+            let pipe = is_pipe(&file);
+            if pipe {
+                check_pipe_flags(password_stdin, expires.is_some(), in_place);
+            }
+            let expires_at = expires.as_deref().map(parse_expiry).transpose()?;
+            let crypt = CargoCrypt::new().await?;
+            if force {
+                crypt.update_config(|config| config.file_ops.overwrite_existing = true).await;
+            }
+            
+            let password = if password_stdin {
+                read_password_stdin()?
+            } else {
+                // Prompt for password with confirmation
+                let password = prompt_password("Enter password for encryption: ")?;
+                let password_confirm = prompt_password("Confirm password: ")?;
+                
+                if password != password_confirm {
+                    fail(exit_code::FAILURE, "Passwords do not match");
+                }
+                password
+            };
+            
+            if pipe {
+                crypt.encrypt_reader_to_writer(tokio::io::stdin(), tokio::io::stdout(), &password).await?;
+                return Ok(());
+            }
+
+            let encrypted_file = if in_place {
+                crypt.encrypt_file_in_place(&file, &password, shred).await?
+            } else {
+                crypt.encrypt_file(&file, &password).await?
+            };
+            if expires_at.is_some() {
+                crypt.set_file_expiry(&encrypted_file, expires_at).await?;
+            }
+            if json_output() {
+                print_json(&file_result("encrypt", &file, &encrypted_file, None))?;
+            } else {
+                println!("✅ File encrypted: {}", encrypted_file.display());
+            }
+        }
+        Commands::Expire { file, date, clear } => {
+            let expires_at = if clear { None } else { date.as_deref().map(parse_expiry).transpose()? };
+            let crypt = CargoCrypt::new().await?;
+            crypt.set_file_expiry(&file, expires_at).await?;
+
+            match expires_at {
+                Some(expires_at) => println!("📅 {} expires on {}", file.display(), format_expiry(expires_at)),
+                None => println!("📅 Expiry cleared for {}", file.display()),
+            }
+        }
+        Commands::Status => {
+            use cargocrypt::agent::AgentClient;
+
+            let crypt = CargoCrypt::new().await?;
+            let config = crypt.config().await;
+            let cached_keys = match AgentClient::for_project(crypt.project_root()).map(|agent| agent.status()) {
+                Some(Ok((entries, _))) => Some(entries),
+                _ => None,
+            };
+            let items: Vec<_> = crypt
+                .expiring_items()
+                .await?
+                .into_iter()
+                .filter(|item| item.status.needs_attention())
+                .collect();
+            let project = cargocrypt::project::Project::find().ok();
+            let package = project.as_ref().filter(|project| project.in_workspace && project.package.is_some());
+            let managed: Vec<_> = crypt
+                .managed_files()?
+                .files
+                .into_iter()
+                .map(|file| (file.state(crypt.project_root()), file))
+                .collect();
+            let sessions = crypt.sessions()?;
+            if json_output() {
+                let managed: Vec<_> = managed
+                    .iter()
+                    .map(|(state, file)| serde_json::json!({
+                        "path": file.path,
+                        "encrypted": file.encrypted,
+                        "key": file.key,
+                        "vault": file.vault,
+                        "state": state,
+                    }))
+                    .collect();
+                return print_json(&serde_json::json!({
+                    "project": crypt.project_root(),
+                    "package": package.map(|project| serde_json::json!({
+                        "name": project.package,
+                        "dir": project.package_dir(),
+                    })),
+                    "agent": { "running": cached_keys.is_some(), "cached_keys": cached_keys },
+                    "managed": managed,
+                    "sessions": sessions
+                        .iter()
+                        .map(|session| serde_json::json!({
+                            "encrypted": session.encrypted,
+                            "plaintext": session.plaintext,
+                            "expires_at": session.expires_at,
+                            "expired": session.is_expired(),
+                        }))
+                        .collect::<Vec<_>>(),
+                    "expiring": items,
+                }));
+            }
+
+            println!("📁 Project: {}", crypt.project_root().display());
+            if let Some(project) = package {
+                let dir = project.package_dir();
+                println!(
+                    "📦 Package: {} ({}), a member of the workspace",
+                    project.package.as_deref().unwrap_or_default(),
+                    dir.strip_prefix(&project.root).unwrap_or(dir).display()
+                );
+            }
+            match cached_keys {
+                Some(entries) => println!("🔑 Key agent: running ({} cached keys)", entries),
+                None => println!("🔑 Key agent: not running"),
+            }
+            if managed.is_empty() {
+                println!("🗂️  No managed files yet");
+            } else {
+                println!("🗂️  Managed files ({}):", managed.len());
+                for (state, file) in &managed {
+                    match &file.vault {
+                        Some(vault) => println!("   {:<13} {} (vault '{}')", state, file.path.display(), vault),
+                        None => println!("   {:<13} {}", state, file.path.display()),
+                    }
+                }
+            }
+            if !sessions.is_empty() {
+                println!("🔓 Open files ({}):", sessions.len());
+                for session in &sessions {
+                    if session.is_expired() {
+                        println!("   {} at {}, expired: run `cargocrypt close`", session.encrypted.display(), session.plaintext.display());
+                    } else {
+                        let remaining = cargocrypt::session::format_duration(session.remaining());
+                        println!("   {} at {}, closes in {}", session.encrypted.display(), session.plaintext.display(), remaining);
+                    }
+                }
+            }
+            if items.is_empty() {
+                println!("📅 No secrets expire within {} days", config.expiry.warning_days);
+            } else {
+                print_expiring_items("Secrets due for rotation", &items);
+            }
+        }
+        Commands::Decrypt { file: None, password_stdin, keyfile, .. } => {
+            let crypt = CargoCrypt::new().await?;
+            let root = crypt.project_root().to_path_buf();
+            let managed = crypt.managed_files()?;
+            let selected: Vec<_> = managed.files.iter().filter(|file| file.state(&root).can_decrypt()).collect();
+            let keys = managed_keys(&crypt, &selected, password_stdin, keyfile, false).await?;
+            let report = crypt.decrypt_managed(&keys).await?;
+            print_managed_report("decrypt", &report)?;
+        }
+        Commands::Decrypt { file: Some(file), dry_run: true, .. } => {
+            if is_pipe(&file) {
+                cli_command().error(clap::error::ErrorKind::ArgumentConflict, "--dry-run cannot be used with `-`").exit();
+            }
+            let crypt = CargoCrypt::new().await?;
+            print_plan(&crypt.plan_decrypt_file(&file).await?)?;
+        }
+        Commands::Decrypt { file: Some(file), identity: Some(identity), .. } => {
+            if is_pipe(&file) {
+                cli_command().error(clap::error::ErrorKind::ArgumentConflict, "--identity cannot be used with `-`").exit();
+            }
+            let crypt = CargoCrypt::new().await?;
+            let decrypted_file = decrypt_with_team_keys(&crypt, &file, Some(identity)).await?;
+            if json_output() {
+                print_json(&file_result("decrypt", &file, &decrypted_file, None))?;
+            } else {
+                println!("✅ File decrypted: {}", decrypted_file.display());
+            }
+        }
+        Commands::Decrypt { file: Some(file), password_stdin, keyfile: Some(keyfile), .. } => {
+            use cargocrypt::crypto::{EncryptedSecret, Keyfile};
+
+            let pipe = is_pipe(&file);
+            if pipe {
+                check_pipe_flags(password_stdin, false, false);
+            }
+            let crypt = CargoCrypt::new().await?;
+            let keyfile = Keyfile::load(&keyfile)?;
+
+            // The header records whether a password was mixed in
+            let input = if pipe { read_stdin()? } else { std::fs::read(&file)? };
+            let encrypted = EncryptedSecret::from_bytes(&input)?;
+            let requires_password = encrypted.key_source().requires_password();
+
+            if pipe {
+                let password = if !requires_password {
+                    None
+                } else if password_stdin {
+                    Some(read_password_stdin()?)
+                } else {
+                    Some(prompt_password("Enter password for decryption: ")?)
+                };
+                crypt.decrypt_reader_to_writer_with_keyfile(&input[..], tokio::io::stdout(), &keyfile, password.as_deref()).await?;
+                return Ok(());
+            }
+
+            let decrypted_file = if requires_password {
+                let target = attempt_target(&crypt, &file);
+                with_password_retries(&crypt, &target, "Enter password for decryption: ", password_stdin, |password| {
+                    let (crypt, file, keyfile) = (&crypt, &file, &keyfile);
+                    async move { crypt.decrypt_file_with_keyfile(file, keyfile, Some(password.as_str())).await }
+                })
+                .await?
+            } else {
+                crypt.decrypt_file_with_keyfile(&file, &keyfile, None).await?
+            };
+            if json_output() {
+                print_json(&file_result("decrypt", &file, &decrypted_file, None))?;
+            } else {
+                println!("✅ File decrypted: {}", decrypted_file.display());
+            }
+        }
+        Commands::Open { file, ttl, in_place, password_stdin, keyfile } => {
+            use cargocrypt::session::{format_duration, parse_duration};
+
+            let ttl = parse_duration(&ttl)?;
+            let crypt = CargoCrypt::new().await?;
+            let (session, password) = open_session(&crypt, &file, keyfile.as_deref(), ttl, in_place, password_stdin).await?;
+
+            if let Err(e) = spawn_session_watcher(&crypt, &session, password.as_ref()) {
+                eprintln!("⚠️  Could not start the process that closes {} on time: {}", file.display(), e);
+                eprintln!("   Close it yourself with: cargocrypt close {}", file.display());
+            }
+            if json_output() {
+                let mut result = file_result("open", &file, &session.plaintext, session.vault.as_deref());
+                result["expires_at"] = session.expires_at.into();
+                print_json(&result)?;
+            } else {
+                println!("🔓 File opened: {}", session.plaintext.display());
+                println!("   Closes in {}; close it sooner with: cargocrypt close {}", format_duration(ttl), file.display());
+            }
+        }
+        Commands::Close { file: Some(file), password_stdin, expires_at: Some(expires_at), .. } => {
+            // Started by `open`: holds the password until the session expires
+            let crypt = CargoCrypt::new().await?;
+            let password = if password_stdin { Some(read_password_stdin()?) } else { None };
+            loop {
+                let Some(session) = crypt
+                    .sessions()?
+                    .into_iter()
+                    .find(|session| session.matches(crypt.project_root(), &file) && session.expires_at == expires_at)
+                else {
+                    // Closed by hand, or opened again
+                    return Ok(());
+                };
+                if session.is_expired() {
+                    crypt.close_file(&session, password.as_deref(), false).await?;
+                    return Ok(());
+                }
+                // Wake up regularly: a sleeping machine does not advance the timer
+                tokio::time::sleep(session.remaining().min(std::time::Duration::from_secs(30))).await;
+            }
+        }
+        Commands::Close { file, password_stdin, shred, .. } => {
+            let crypt = CargoCrypt::new().await?;
+            let sessions: Vec<_> = crypt
+                .sessions()?
+                .into_iter()
+                .filter(|session| file.as_ref().is_none_or(|file| session.matches(crypt.project_root(), file)))
+                .collect();
+            if let (Some(file), true) = (&file, sessions.is_empty()) {
+                return Err(CargoCryptError::Config {
+                    message: format!("{} is not open", file.display()),
+                    suggestion: Some("List the open files with `cargocrypt status`".to_string()),
+                });
+            }
+
+            let mut closed = Vec::new();
+            for session in &sessions {
+                let outcome = if session.needs_password() && session.is_modified()? {
+                    let (target, prompt) = match &session.vault {
+                        Some(vault) => (format!("vault '{}'", vault), format!("Enter password for vault '{}' to encrypt {} again: ", vault, session.encrypted.display())),
+                        None => (session.encrypted.display().to_string(), format!("Enter password to encrypt {} again: ", session.encrypted.display())),
+                    };
+                    with_password_retries(&crypt, &target, &prompt, password_stdin, |password| {
+                        let crypt = &crypt;
+                        async move { crypt.close_file(session, Some(password.as_str()), shred).await }
+                    })
+                    .await?
+                } else {
+                    crypt.close_file(session, None, shred).await?
+                };
+                if !json_output() {
+                    match outcome {
+                        SessionClose::Reencrypted => println!("🔒 Closed {}: encrypted the changes again", session.encrypted.display()),
+                        SessionClose::Unchanged => println!("🔒 Closed {}", session.encrypted.display()),
+                        SessionClose::Missing => println!("🔒 Closed {}: the plaintext was already gone", session.encrypted.display()),
+                    }
+                }
+                closed.push(serde_json::json!({
+                    "encrypted": session.encrypted,
+                    "plaintext": session.plaintext,
+                    "outcome": outcome,
+                }));
+            }
+            if json_output() {
+                print_json(&serde_json::json!({ "closed": closed }))?;
+            } else if closed.is_empty() {
+                println!("No open files");
+            }
+        }
+        Commands::Edit { file, password_stdin, keyfile } => {
+            require_interactive("Editing an encrypted file", "Decrypt it with 'cargocrypt open' and close it with 'cargocrypt close'")?;
+            let crypt = CargoCrypt::new().await?;
+            let (session, password) = open_session(&crypt, &file, keyfile.as_deref(), EDIT_TTL, false, password_stdin).await?;
+            if let Err(e) = spawn_session_watcher(&crypt, &session, password.as_ref()) {
+                eprintln!("⚠️  Could not start the process that closes {} if editing is cut short: {}", file.display(), e);
+            }
+
+            // Whatever the editor did, encrypt what it left and shred the plaintext
+            let edited = run_editor_uninterrupted(&session.plaintext).await;
+            let outcome = crypt.close_file(&session, password.as_ref().map(SecureString::as_str), true).await?;
+            let changed = outcome == SessionClose::Reencrypted;
+            if let Err(e) = edited {
+                // Like `config edit`, keep the changes rather than guess whether they are wanted
+                let message = match e {
+                    CargoCryptError::Config { message, .. } => message,
+                    other => other.to_string(),
+                };
+                let suggestion = if changed {
+                    format!("The changes it saved are encrypted in {}; run `cargocrypt edit {0}` to review them", file.display())
+                } else {
+                    format!("{} is unchanged", file.display())
+                };
+                return Err(CargoCryptError::Config { message, suggestion: Some(suggestion) });
+            }
+            if json_output() {
+                print_json(&serde_json::json!({ "path": file, "changed": changed }))?;
+            } else if changed {
+                println!("✅ Saved {}", file.display());
+            } else {
+                println!("ℹ️  No changes to {}", file.display());
+            }
+        }
+        Commands::Decrypt { file: Some(file), password_stdin, vault, .. } => {
+            let pipe = is_pipe(&file);
+            if pipe {
+                check_pipe_flags(password_stdin, false, false);
+                if vault.is_some() {
+                    cli_command().error(clap::error::ErrorKind::ArgumentConflict, "--vault cannot be used with `-`").exit();
+                }
+            }
+            let crypt = CargoCrypt::new().await?;
+
+            // Files encrypted in a vault record it in their header
+            let vault = match vault {
+                Some(vault) => Some(vault),
+                None if !pipe => crypt.file_vault(&file).await?,
+                None => None,
+            };
+            if let Some(vault) = vault {
+                let prompt = format!("Enter password for vault '{}': ", vault);
+                let decrypted_file = with_password_retries(&crypt, &format!("vault '{}'", vault), &prompt, password_stdin, |password| {
+                    let (crypt, file, vault) = (&crypt, &file, &vault);
+                    async move { crypt.decrypt_file_in_vault(file, vault, &password).await }
+                })
+                .await?;
+                if json_output() {
+                    print_json(&file_result("decrypt", &file, &decrypted_file, Some(&vault)))?;
+                } else {
+                    println!("✅ File decrypted: {}", decrypted_file.display());
+                }
+                return Ok(());
+            }
+
+            // Files encrypted with a team key need no password
+            let keyfile_only = !pipe
+                && std::fs::read(&file)
+                    .ok()
+                    .and_then(|input| cargocrypt::crypto::EncryptedSecret::from_bytes(&input).ok())
+                    .is_some_and(|encrypted| !encrypted.key_source().requires_password());
+            if keyfile_only {
+                let decrypted_file = decrypt_with_team_keys(&crypt, &file, None).await?;
+                if json_output() {
+                    print_json(&file_result("decrypt", &file, &decrypted_file, None))?;
+                } else {
+                    println!("✅ File decrypted: {}", decrypted_file.display());
+                }
+                return Ok(());
+            }
+            
+            if pipe {
+                let password = if password_stdin {
+                    read_password_stdin()?
+                } else {
+                    prompt_password("Enter password for decryption: ")?
+                };
+                crypt.decrypt_reader_to_writer(tokio::io::stdin(), tokio::io::stdout(), &password).await?;
+                return Ok(());
+            }
+
+            let target = attempt_target(&crypt, &file);
+            let decrypted_file = with_password_retries(&crypt, &target, "Enter password for decryption: ", password_stdin, |password| {
+                let (crypt, file) = (&crypt, &file);
+                async move { crypt.decrypt_file(file, &password).await }
+            })
+            .await?;
+            if json_output() {
+                print_json(&file_result("decrypt", &file, &decrypted_file, None))?;
+            } else {
+                println!("✅ File decrypted: {}", decrypted_file.display());
+            }
+        }
+        Commands::Sign { file, password_stdin } => {
+            let crypt = CargoCrypt::new().await?;
+            let new_key = !crypt.signing_key_path().exists();
+
+            let password = if password_stdin {
+                read_password_stdin()?
+            } else if new_key {
+                let password = prompt_password("Enter password for new signing key: ")?;
+                let password_confirm = prompt_password("Confirm password: ")?;
+
+                if password != password_confirm {
+                    fail(exit_code::FAILURE, "Passwords do not match");
+                }
+                password
+            } else {
+                prompt_password("Enter signing key password: ")?
+            };
+
+            let signature_file = crypt.sign_file(&file, &password).await?;
+            if new_key {
+                println!("🔑 Created signing key: {}", crypt.signing_key_path().display());
+            }
+            println!("✅ File signed: {}", signature_file.display());
+        }
+        Commands::Verify { all: true, key, write_manifest, password_stdin, .. } => {
+            let crypt = CargoCrypt::new().await?;
+            let password = if password_stdin { Some(read_password_stdin()?) } else { None };
+
+            let report = crypt.verify_project(password.as_deref(), key.as_deref()).await?;
+            let fresh = report.issues.iter().all(|issue| issue.kind == IntegrityIssueKind::NoManifest);
+
+            if report.is_clean() {
+                println!("✅ {} encrypted file(s) match the manifest", report.files_checked);
+                if let Some(signer) = &report.signer {
+                    println!("   Signer: {}", signer);
+                }
+            } else if write_manifest && !report.has_damage() {
+                if !fresh {
+                    println!("📝 Changes since the last manifest:");
+                    for issue in &report.issues {
+                        println!("   - {} [{}]", issue.path.display(), issue.kind);
+                    }
+                }
+            } else {
+                eprintln!("❌ {} integrity issue(s):", report.issues.len());
+                for issue in &report.issues {
+                    eprintln!("   - {} [{}]: {}", issue.path.display(), issue.kind, issue.detail);
+                }
+            }
+            if report.tags_checked > 0 {
+                println!("   Authentication tags checked: {}", report.tags_checked);
+            }
+
+            if report.has_damage() {
+                if write_manifest {
+                    eprintln!("   Refusing to sign a manifest over damaged files");
+                }
+                std::process::exit(exit_code::FINDINGS);
+            }
+            if write_manifest {
+                let password = match password {
+                    Some(password) => password,
+                    None => prompt_password("Enter signing key password: ")?,
+                };
+                let manifest = crypt.write_manifest(&password).await?;
+                println!("✅ Manifest of {} file(s) written: {}", manifest.entries.len(), crypt.manifest_path().display());
+                println!("   Signer: {}", manifest.signature.signer_fingerprint());
+                println!("   Public key: {}", hex::encode(manifest.signature.public_key));
+            } else if !report.is_clean() {
+                std::process::exit(exit_code::FINDINGS);
+            }
+        }
+        Commands::Verify { file, signature, key, .. } => {
+            let crypt = CargoCrypt::new().await?;
+            let file = file.expect("clap requires a file without --all");
+
+            match crypt.verify_file(&file, signature.as_deref(), key.as_deref()).await {
+                Ok(signature) => {
+                    println!("✅ Valid signature for {}", file.display());
+                    println!("   Signer: {}", signature.signer_fingerprint());
+                    println!("   Public key: {}", hex::encode(signature.public_key));
+                }
+                Err(e) => {
+                    eprintln!("❌ Signature verification failed: {}", e);
+                    std::process::exit(exit_code::AUTH);
+                }
+            }
+        }
+        Commands::Prepublish { scan_secrets } => {
+            use cargocrypt::publish::PublishCheck;
+
+            let crypt = CargoCrypt::new().await?;
+            let config = crypt.config().await;
+            let report = PublishCheck::new(crypt.project_root())
+                .with_encrypted_extension(config.file_ops.encrypted_extension)
+                .with_secret_scan(scan_secrets)
+                .run()
+                .await?;
+
+            if report.is_clean() {
+                println!("✅ Package is clean ({} files checked)", report.files_checked);
+            } else {
+                eprintln!("❌ {} file(s) must not be published:", report.issues.len());
+                for issue in &report.issues {
+                    eprintln!("   - {} [{}]: {}", issue.path.display(), issue.kind, issue.detail);
+                }
+                eprintln!("   Add them to `exclude` in Cargo.toml or remove them before publishing");
+                std::process::exit(exit_code::FINDINGS);
+            }
+        }
+        Commands::Doctor { fix } => {
+            use cargocrypt::doctor::Doctor;
+
+            let fixed = if fix { Doctor::new(&CargoCrypt::new().await?).fix().await? } else { Vec::new() };
+            // After calibrating, only new instances use the new parameters
+            let crypt = CargoCrypt::new().await?;
+            let report = Doctor::new(&crypt).diagnose().await?;
+
+            if json_output() {
+                print_json(&serde_json::json!({ "fixed": fixed, "git": report.git, "problems": report.problems }))?;
+            } else {
+                for action in &fixed {
+                    println!("🔧 {}", action);
+                }
+                if !report.git {
+                    println!("ℹ️  Not in a git working tree: hooks, filters, plaintext in git and keyring entries were not checked");
+                }
+                if report.is_clean() {
+                    println!("✅ No problems found");
+                } else {
+                    eprintln!("⚠️  {} problem(s) found:", report.problems.len());
+                    for problem in &report.problems {
+                        match &problem.path {
+                            Some(path) => eprintln!("   - {} [{}]: {}", path.display(), problem.kind, problem.detail),
+                            None => eprintln!("   - [{}] {}", problem.kind, problem.detail),
+                        }
+                        let fix = if problem.fixable && !fix { " (--fix)" } else { "" };
+                        eprintln!("     → {}{}", problem.suggestion, fix);
+                    }
+                    if report.fixable() > 0 && !fix {
+                        eprintln!("   Run `cargocrypt doctor --fix` to fix {} of them", report.fixable());
+                    }
+                }
+            }
+            if !report.is_clean() {
+                std::process::exit(exit_code::FINDINGS);
+            }
+        }
+        Commands::Scan { action: Some(scan_cmd), .. } => {
+            handle_scan_command(scan_cmd).await?;
+        }
+        Commands::Scan {
+            action: None, path, format, output, history, since, staged, no_cache, verify, archives, binaries, watch, ci, stage,
+            encrypted_only, stdin_objects, rev, send_to,
+        } => {
+            use cargocrypt::detection::{
+                FindingsDb, Policy, PolicyMode, PolicyStage, RuleConfig, SarifLog, ScanReport, SecretDetector, SiemExport,
+                VerificationStatus, Verifier,
+            };
+
+            // `--json` is `--format json` unless another format is asked for
+            let format = if json_output() && matches!(format, ScanFormat::Table) { ScanFormat::Json } else { format };
+            let project_root = cargocrypt::utils::find_project_root().unwrap_or_else(|_| PathBuf::from("."));
+            let mut options = project_scan_options(&project_root, no_cache)?;
+            options.scan_config.scan_archives = archives;
+            options.scan_config.scan_binaries = binaries;
+            options.scan_config.encrypted_paths_only = encrypted_only;
+
+            // `--staged` gates like the pre-commit hook, `--ci` runs from pipelines,
+            // `--stdin-objects` from the pre-receive hook on the server
+            let policy = Policy::load_project(&project_root)?;
+            let gate = if staged {
+                Some(PolicyStage::Commit)
+            } else if stdin_objects {
+                Some(PolicyStage::Push)
+            } else if ci {
+                Some(stage.unwrap_or(PolicyStage::Ci))
+            } else {
+                None
+            };
+
+            let mut detector = SecretDetector::new();
+            detector.load_custom_rules(&RuleConfig::load_project(&project_root)?)?;
+            if watch {
+                watch_for_secrets(&detector, &path, &options).await?;
+                return Ok(());
+            }
+            // A bare repository has no files, so its HEAD is scanned instead
+            let bare_repo = git2::Repository::open(&path).ok().filter(|repo| repo.is_bare());
+            let revision_scan = rev.is_some() || bare_repo.is_some();
+            let (content, reported, blocking) = if history {
+                if matches!(format, ScanFormat::Csv | ScanFormat::Sarif | ScanFormat::Ecs) {
+                    fail(exit_code::USAGE, "--history supports --format table or json");
+                }
+
+                let mut scan = detector.scan_history(&path, since.as_deref(), &options)?;
+                if verify {
+                    let mut findings: Vec<_> = scan.findings.iter().map(|f| f.finding.clone()).collect();
+                    Verifier::new()?.verify_findings(&mut findings).await;
+                    for (history_finding, finding) in scan.findings.iter_mut().zip(findings) {
+                        history_finding.finding = finding;
+                    }
+                }
+                let report = scan.report();
+                let content = match format {
+                    ScanFormat::Table if report.findings.is_empty() => {
+                        format!("✅ No secrets found in {} commit(s)", report.commits_scanned)
+                    }
+                    ScanFormat::Table => format!(
+                        "{}🔍 {} secret(s) found in {} commit(s), {} still in HEAD",
+                        report.to_table(),
+                        report.findings.len(),
+                        report.commits_scanned,
+                        report.still_in_head()
+                    ),
+                    // csv, sarif and ecs were rejected above
+                    _ => report.to_json()?,
+                };
+                (content, report.findings.len(), 0)
+            } else {
+                let (findings, root) = if staged {
+                    let root = git2::Repository::discover(&path)?
+                        .workdir()
+                        .map(PathBuf::from)
+                        .unwrap_or_else(|| path.clone());
+                    (detector.scan_staged(&path, &options)?, root)
+                } else if stdin_objects {
+                    // Hooks run with GIT_DIR set, which also points at the push quarantine
+                    let repo = if std::env::var_os("GIT_DIR").is_some() {
+                        git2::Repository::open_from_env()?
+                    } else {
+                        git2::Repository::discover(&path)?
+                    };
+                    (detector.scan_objects(&repo, std::io::stdin().lock(), &options)?, PathBuf::new())
+                } else if revision_scan {
+                    let repo = match bare_repo {
+                        Some(repo) => repo,
+                        None => git2::Repository::discover(&path)?,
+                    };
+                    (detector.scan_revision(&repo, rev.as_deref().unwrap_or("HEAD"), &options)?, PathBuf::new())
+                } else if path.is_file() {
+                    let root = path.parent().map(PathBuf::from).unwrap_or_default();
+                    (detector.scan_file(&path, &options).await?, root)
+                } else {
+                    (scan_directory_with_progress_bar(&detector, &path, &options).await?, path.clone())
+                };
+                // Suppressed findings stay in the reports so suppressions can be audited
+                let mut findings: Vec<_> = findings
+                    .into_iter()
+                    .filter(|finding| finding.should_report() || finding.is_suppressed())
+                    .collect();
+                let reported = findings.iter().filter(|finding| !finding.is_ignored).count();
+                if verify {
+                    Verifier::new()?.verify_findings(&mut findings).await;
+                }
+                let confirmed = findings
+                    .iter()
+                    .filter(|finding| finding.verification == Some(VerificationStatus::Confirmed))
+                    .count();
+                let blocking = gate.map_or(0, |stage| policy.stage(stage).blocking(&findings).len());
+                if !staged && !stdin_objects && !revision_scan {
+                    // Only a scan of the whole project can tell that a finding was fixed
+                    let complete = path.is_dir() && path.canonicalize().ok() == project_root.canonicalize().ok();
+                    let db_path = FindingsDb::project_path(&project_root);
+                    let mut db = FindingsDb::load(&db_path)?;
+                    db.sync(&project_root, &findings, complete);
+                    db.save(&db_path)?;
+                }
+
+                if let Some(destination) = &send_to {
+                    let ndjson = SiemExport::new(&root).to_ndjson(&root, &findings)?;
+                    cargocrypt::detection::siem::send(destination, &ndjson).await?;
+                    eprintln!("📡 Sent {} finding(s) to {}", reported, destination);
+                }
+
+                // Offer patterns for files that hold secrets but are not encrypted
+                let unencrypted = if matches!(format, ScanFormat::Table) && !staged && !stdin_objects && !revision_scan && path.is_dir() {
+                    git2::Repository::discover(&path)
+                        .ok()
+                        .and_then(|repo| cargocrypt::git::suggest_patterns(repo.workdir()?, &findings).ok())
+                        .map_or(0, |suggestions| suggestions.file_count())
+                } else {
+                    0
+                };
+
+                let content = match format {
+                    ScanFormat::Ecs => SiemExport::new(&root).to_ndjson(&root, &findings)?,
+                    ScanFormat::Sarif => SarifLog::from_findings(&root, &findings).to_json()?,
+                    ScanFormat::Json => ScanReport::from_findings(&root, &findings).to_json()?,
+                    ScanFormat::Csv => ScanReport::from_findings(&root, &findings).to_csv(),
+                    ScanFormat::Table => {
+                        let table = ScanReport::from_findings(&root, &findings).to_table();
+                        let summary = if reported == 0 {
+                            format!("{}✅ No secrets found", table)
+                        } else if confirmed > 0 {
+                            format!(
+                                "{}🔍 {} potential secret(s) found, 🔥 {} confirmed active: rotate these first",
+                                table, reported, confirmed
+                            )
+                        } else {
+                            format!("{}🔍 {} potential secret(s) found", table, reported)
+                        };
+                        if unencrypted > 0 {
+                            format!(
+                                "{}\n💡 {} file(s) with high-confidence secrets are not encrypted: run 'cargocrypt git suggest-patterns'",
+                                summary, unencrypted
+                            )
+                        } else {
+                            summary
+                        }
+                    }
+                };
+                (content, reported, blocking)
+            };
+
+            match output {
+                Some(file_path) => {
+                    tokio::fs::write(&file_path, &content).await?;
+                    if json_output() {
+                        print_json(&serde_json::json!({ "output": file_path, "findings": reported }))?;
+                    } else {
+                        println!("✅ Report of {} finding(s) written to: {}", reported, file_path.display());
+                    }
+                }
+                // An ECS export without findings has no lines at all
+                None if content.is_empty() || content.ends_with('\n') => print!("{}", content),
+                None => println!("{}", content),
+            }
+            if encrypted_only {
+                let plaintext = match git2::Repository::discover(&path) {
+                    Ok(repo) => match repo.workdir() {
+                        Some(workdir) => cargocrypt::git::plaintext_in_index(workdir)?,
+                        None => Vec::new(),
+                    },
+                    Err(_) => Vec::new(),
+                };
+                if !plaintext.is_empty() {
+                    eprintln!("❌ {} file(s) marked for encryption are staged in plaintext:", plaintext.len());
+                    for file in &plaintext {
+                        eprintln!("   {}", file.display());
+                    }
+                    eprintln!("   Run 'cargocrypt git configure-attributes' to set up the encryption filter, then 'git add --renormalize .'");
+                    std::process::exit(exit_code::FINDINGS);
+                }
+            }
+            if let Some(stage) = gate {
+                let stage_policy = policy.stage(stage);
+                if blocking > 0 {
+                    eprintln!("❌ {} finding(s) blocked by the {} policy", blocking, stage);
+                    std::process::exit(exit_code::FINDINGS);
+                } else if reported > 0 && stage_policy.mode == PolicyMode::Warn {
+                    eprintln!("⚠️  The {} policy is warn-only: {} finding(s) not blocking", stage, reported);
+                } else if reported > 0 {
+                    eprintln!("⚠️  {} finding(s) below the {} policy threshold, not blocking", reported, stage);
+                }
+            }
+        }
+        Commands::Redact { path, apply, store, output, vault, password_stdin, dry_run } => {
+            use cargocrypt::crypto::SecretMetadata;
+            use cargocrypt::detection::{DetectionConfig, RuleConfig, ScanOptions, SecretDetector};
+            use cargocrypt::redact::RedactionPlan;
+
+            let project_root = cargocrypt::utils::find_project_root().unwrap_or_else(|_| PathBuf::from("."));
+            let options = ScanOptions {
+                detection_config: DetectionConfig::load_project(&project_root)?,
+                ..ScanOptions::default()
+            };
+            let mut detector = SecretDetector::new();
+            detector.load_custom_rules(&RuleConfig::load_project(&project_root)?)?;
+            let (findings, root) = if path.is_file() {
+                let root = path.parent().map(PathBuf::from).unwrap_or_default();
+                (detector.scan_file(&path, &options).await?, root)
+            } else {
+                (detector.scan_directory(&path, &options).await?, path.clone())
+            };
+            let findings: Vec<_> = findings.into_iter().filter(|finding| finding.should_report()).collect();
+
+            let plan = RedactionPlan::new(&root, &findings)?;
+            for skipped in &plan.skipped {
+                eprintln!(
+                    "⚠️  Left {} in {}:{}: {}",
+                    skipped.secret_type,
+                    skipped.file.display(),
+                    skipped.line,
+                    skipped.reason
+                );
+            }
+            if dry_run {
+                let mut changes = Plan::new();
+                if store && !plan.is_empty() {
+                    let crypt = CargoCrypt::new().await?;
+                    let store_path = match &vault {
+                        Some(vault) => cargocrypt::vault::Vault::open(crypt.project_root(), vault)?.secrets_path(),
+                        None => crypt.secret_store_path(),
+                    };
+                    changes.write(store_path, Some(&format!("up to {} secret(s) saved", plan.unique_secrets().len())));
+                }
+                if apply {
+                    for (file, count) in plan.file_counts() {
+                        changes.push(Change::Modify, file, Some(&format!("{} secret(s) replaced", count)));
+                    }
+                } else if let Some(file_path) = output.filter(|_| !plan.is_empty()) {
+                    changes.write(file_path, Some("patch"));
+                }
+                return print_plan(&changes);
+            }
+            if plan.is_empty() {
+                println!("✅ Nothing to redact");
+                return Ok(());
+            }
+
+            if store {
+                let crypt = CargoCrypt::new().await?;
+                let secret_store = open_secret_store(&crypt, vault.as_deref(), password_stdin).await?;
+                let mut saved = 0;
+                for redaction in plan.unique_secrets() {
+                    let current = secret_store.get(&redaction.name).await?;
+                    if current.is_some_and(|current| current.as_bytes() == redaction.value().as_bytes()) {
+                        continue;
+                    }
+                    let mut metadata = SecretMetadata::new();
+                    metadata.description = Some(format!(
+                        "Redacted from {}:{}",
+                        redaction.file.strip_prefix(&root).unwrap_or(&redaction.file).display(),
+                        redaction.line
+                    ));
+                    secret_store.set(&redaction.name, redaction.value().clone(), metadata).await?;
+                    saved += 1;
+                }
+                eprintln!("🔐 Saved {} secret(s) in {}", saved, secret_store.path().display());
+            }
+
+            if apply {
+                let files = plan.apply()?;
+                println!("✅ Replaced {} secret(s) in {} file(s)", plan.redactions.len(), files);
+                if !store {
+                    println!("   The original values are not saved anywhere; rotate them if they were committed");
+                }
+            } else {
+                let patch = plan.to_patch()?;
+                match output {
+                    Some(file_path) => {
+                        tokio::fs::write(&file_path, &patch).await?;
+                        println!(
+                            "✅ Patch replacing {} secret(s) in {} file(s) written to: {}",
+                            plan.redactions.len(),
+                            plan.files(),
+                            file_path.display()
+                        );
+                        println!("   Review it, then apply it with `git apply {}`", file_path.display());
+                    }
+                    None => print!("{}", patch),
+                }
+            }
+        }
+        Commands::Config { action: Some(action) } => {
+            handle_config_command(action).await?;
+        }
+        Commands::Config { action: None } => {
+            let crypt = CargoCrypt::new().await?;
+            let config = crypt.config().await;
+            let layers = cargocrypt::config::ConfigLayers::load(crypt.project_root())?;
+            if json_output() {
+                let sources: Vec<&str> = layers.layers.iter().map(|layer| layer.source.as_str()).collect();
+                return print_json(&serde_json::json!({ "profile": layers.profile, "sources": sources, "config": config }));
+            }
+            println!("📋 Current configuration:");
+            if let Some(profile) = &layers.profile {
+                println!("  Profile: {}", profile);
+            }
+            if layers.layers.is_empty() {
+                println!("  Sources: defaults");
+            } else {
+                let sources: Vec<&str> = layers.layers.iter().map(|layer| layer.source.as_str()).collect();
+                println!("  Sources: defaults < {}", sources.join(" < "));
+            }
+            println!("  Performance Profile: {:?}", config.performance_profile);
+            println!("  Key derivation: {}", config.key_params.kdf);
+            println!("  Memory cost: {} KiB", config.key_params.memory_cost);
+            println!("  Time cost: {} iterations", config.key_params.time_cost);
+            println!("  Parallelism: {}", config.key_params.parallelism);
+            println!("  Auto-backup: {}", config.file_ops.backup_originals);
+            println!("  Fail-secure: {}", config.security.fail_secure);
+        }
+        Commands::Calibrate { budget_ms } => {
+            let crypt = CargoCrypt::new().await?;
+            println!("⏱️  Calibrating key derivation...");
+            let calibration = crypt.calibrate(budget_ms.map(std::time::Duration::from_millis)).await?;
+            println!("✅ {} takes {} ms (target {} ms)",
+                calibration.kdf,
+                calibration.elapsed.as_millis(),
+                calibration.budget.as_millis());
+            println!("   Saved to {}", cargocrypt::CryptoConfig::project_path(crypt.project_root()).display());
+        }
+        Commands::Inspect { file } => {
+            use cargocrypt::crypto::KeySource;
+            use cargocrypt::expiry::format_expiry;
+            use cargocrypt::inspect::FileInspection;
+
+            let inspection = FileInspection::read(&file)?;
+            if json_output() {
+                return print_json(&inspection);
+            }
+
+            println!("📄 {}", inspection.path.display());
+            println!("   Format:      v{}", inspection.format_version);
+            if inspection.post_quantum {
+                println!("   Algorithm:   {} (key bound with X25519 + ML-KEM-768)", inspection.algorithm);
+            } else {
+                println!("   Algorithm:   {}", inspection.algorithm);
+            }
+            let key = match inspection.key_source {
+                KeySource::Password => "password",
+                KeySource::Keyfile => "keyfile",
+                KeySource::KeyfileAndPassword => "keyfile + password",
+            };
+            match &inspection.vault {
+                Some(vault) => println!("   Key:         vault '{}'", vault),
+                None => println!("   Key:         {}", key),
+            }
+            if let Some(kdf) = &inspection.kdf {
+                let note = if inspection.kdf_is_weak { "  ⚠️  below current recommendations" } else { "" };
+                println!("   KDF:         {}{}", kdf, note);
+            }
+            if let Some(name) = &inspection.original_name {
+                println!("   Original:    {}", name);
+            }
+            if let Some(created_at) = inspection.created_at {
+                println!("   Created:     {}", format_expiry(created_at));
+            }
+            if let Some(expires_at) = inspection.expires_at {
+                println!("   Expires:     {}", format_expiry(expires_at));
+            }
+            if let Some(ttl_secs) = inspection.ttl_secs {
+                println!("   Rotate:      every {} days", ttl_secs / (24 * 60 * 60));
+            }
+            if let Some(description) = &inspection.description {
+                println!("   Description: {}", description);
+            }
+            if !inspection.tags.is_empty() {
+                println!("   Tags:        {}", inspection.tags.join(", "));
+            }
+            match inspection.chunks {
+                Some(layout) => println!("   Ciphertext:  {} bytes in {} chunks of {} bytes",
+                    inspection.ciphertext_len, layout.chunks, layout.chunk_size),
+                None => println!("   Ciphertext:  {} bytes", inspection.ciphertext_len),
+            }
+            if inspection.recipients.is_empty() {
+                println!("   Recipients:  none");
+            } else {
+                println!("   Recipients:  {}", inspection.recipients.join(", "));
+            }
+        }
+        Commands::Upgrade { dry_run, password_stdin } => {
+            use cargocrypt::UpgradeOutcome;
+
+            let crypt = CargoCrypt::new().await?;
+            let outdated = crypt.outdated_files().await?;
+            let target = crypt.crypto().kdf();
+            if outdated.is_empty() {
+                println!("✅ All encrypted files already use {} or stronger", target);
+                return Ok(());
+            }
+            if dry_run {
+                println!("⬆️  {} file(s) would be upgraded to {}:", outdated.len(), target);
+                for (path, kdf) in &outdated {
+                    println!("   {} ({})", path.display(), kdf);
+                }
+                return Ok(());
+            }
+
+            let password = if password_stdin {
+                read_password_stdin()?
+            } else {
+                prompt_password("Enter password of the files to upgrade: ")?
+            };
+            let progress = Progress::bytes("Upgrading");
+            let report = crypt.upgrade_files_with_progress(&password, &progress).await;
+            progress.finish();
+            let report = report?;
+            for (path, outcome) in &report.files {
+                match outcome {
+                    UpgradeOutcome::Upgraded { from } => println!("⬆️  {} ({} → {})", path.display(), from, report.target),
+                    UpgradeOutcome::Skipped(reason) => println!("⏭️  {}: {}", path.display(), reason),
+                    UpgradeOutcome::Failed(e) => println!("❌ {}: {}", path.display(), e),
+                }
+            }
+            println!("✅ Upgraded {} of {} file(s)", report.upgraded(), report.files.len());
+            if !report.is_success() {
+                std::process::exit(1);
+            }
+        }
+        Commands::Passwd => {
+            let crypt = CargoCrypt::new().await?;
+            let old_password = prompt_password("Enter current password: ")?;
+            let new_password = prompt_password("Enter new password: ")?;
+            let password_confirm = prompt_password("Confirm new password: ")?;
+
+            if new_password != password_confirm {
+                fail(exit_code::FAILURE, "Passwords do not match");
+            }
+
+            let change = crypt.change_password(&old_password, &new_password).await?;
+            if change.rewrapped.is_empty() {
+                println!("ℹ️  The project has no password-protected keys (vaults or signing key)");
+            } else {
+                println!("✅ Rewrapped {} key(s) under the new password:", change.rewrapped.len());
+                for path in &change.rewrapped {
+                    println!("   {}", path.display());
+                }
+            }
+            if !change.skipped.is_empty() {
+                println!("⚠️  Not opened by the current password, left unchanged:");
+                for path in &change.skipped {
+                    println!("   {}", path.display());
+                }
+            }
+            if !change.password_files.is_empty() {
+                println!(
+                    "⚠️  {} file(s) are encrypted directly with a password and still need the old one;",
+                    change.password_files.len()
+                );
+                println!("   encrypt them into a vault (`cargocrypt encrypt --vault <name>`) so future changes skip re-encryption");
+            }
+        }
+        Commands::Bench { budget_ms, size_mb } => {
+            let crypt = CargoCrypt::new().await?;
+            let options = cargocrypt::bench::BenchOptions {
+                data_size: size_mb.max(1) * 1024 * 1024,
+                kdf_budget: std::time::Duration::from_millis(budget_ms),
+            };
+            if !json_output() {
+                println!("⏱️  Benchmarking this machine...");
+            }
+            let report = crypt.benchmark(options).await?;
+
+            if json_output() {
+                return print_json(&report);
+            }
+
+            println!("🔑 Key derivation ({} MB round trip):", size_mb.max(1));
+            for benchmark in &report.profiles {
+                println!("   {:<9} {:>9.1} ms   encrypt {:>8.1} MB/s   decrypt {:>8.1} MB/s",
+                    format!("{:?}", benchmark.profile),
+                    benchmark.key_derivation_ms,
+                    benchmark.encryption_throughput_mbps(),
+                    benchmark.decryption_throughput_mbps());
+            }
+            for profile in &report.skipped {
+                println!("   {:<9} skipped (a faster profile is already over budget)", format!("{:?}", profile));
+            }
+            println!("🔐 Ciphers:");
+            for cipher in &report.ciphers {
+                match (cipher.encryption_mbps, cipher.decryption_mbps) {
+                    (Some(encryption), Some(decryption)) => println!("   {:<45} encrypt {:>8.1} MB/s   decrypt {:>8.1} MB/s",
+                        cipher.algorithm, encryption, decryption),
+                    _ => println!("   {:<45} not available", cipher.algorithm),
+                }
+            }
+            println!("💾 File I/O in {}:", crypt.project_root().display());
+            println!("   write {:.1} MB/s (synced)   read {:.1} MB/s", report.file_io.write_mbps, report.file_io.read_mbps);
+            println!("✅ Recommended profile: {:?} (key derivation within {} ms)", report.recommended, report.kdf_budget_ms);
+        }
+        Commands::ClearClipboard { secs } => {
+            // Started by `secret get --clip`
+            let mut expected = String::new();
+            std::io::stdin().read_line(&mut expected)?;
+            let clipboard = cargocrypt::clipboard::Clipboard::detect()?;
+            tokio::time::sleep(std::time::Duration::from_secs(secs)).await;
+            clipboard.clear_if_unchanged(expected.trim())?;
+        }
+        Commands::Tui => {
+            require_interactive("The TUI", "Use the subcommands instead")?;
+            println!("Starting TUI...");
+            let crypt = Arc::new(CargoCrypt::new().await?);
+            cargocrypt::tui_simple::run_simple_tui(crypt).await?;
+        }
+        Commands::Git(git_cmd) => {
+            handle_git_command(git_cmd).await?;
+        }
+        Commands::Hook(hook_cmd) => {
+            handle_hook_command(hook_cmd).await?;
+        }
+        Commands::Migrate(migrate_cmd) => {
+            handle_migrate_command(migrate_cmd).await?;
+        }
+        Commands::Team(team_cmd) => {
+            handle_team_command(team_cmd).await?;
+        }
+        Commands::Monitor(monitor_cmd) => {
+            handle_monitor_command(monitor_cmd).await?;
+        }
+        Commands::Report(report_cmd) => {
+            handle_report_command(report_cmd).await?;
+        }
+        Commands::Findings(findings_cmd) => {
+            handle_findings_command(findings_cmd)?;
+        }
+        Commands::Rules(rules_cmd) => {
+            handle_rules_command(rules_cmd).await?;
+        }
+        Commands::Key(key_cmd) => {
+            handle_key_command(key_cmd)?;
+        }
+        Commands::Agent(agent_cmd) => {
+            handle_agent_command(agent_cmd).await?;
+        }
+        Commands::Vault(vault_cmd) => {
+            handle_vault_command(vault_cmd).await?;
+        }
+        Commands::Backup(backup_cmd) => {
+            handle_backup_command(backup_cmd).await?;
+        }
+        Commands::Run { secrets, env_file, vault, password_stdin, command } => {
+            let code = run_with_secrets(secrets, env_file, vault, password_stdin, command).await?;
+            if code != exit_code::OK {
+                std::process::exit(code);
+            }
+        }
+        Commands::Secret(secret_cmd) => {
+            handle_secret_command(secret_cmd).await?;
+        }
+        Commands::Env(env_cmd) => {
+            handle_env_command(env_cmd).await?;
+        }
+        Commands::Store(store_cmd) => {
+            handle_store_command(store_cmd).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Read a single password line from stdin
+/// Whether a file argument of `-` asks for stdin/stdout
+fn is_pipe(file: &std::path::Path) -> bool {
+    file.as_os_str() == "-"
+}
+
+/// Reject options that need a real file or stdin when piping
+fn check_pipe_flags(password_stdin: bool, expires: bool, in_place: bool) {
+    use clap::error::ErrorKind;
+
+    let conflict = if password_stdin {
+        "--password-stdin cannot be used with `-`: stdin carries the data, the password is prompted for on the terminal"
+    } else if expires {
+        "--expires cannot be used with `-`: write the output to a file and run `cargocrypt expire` on it"
+    } else if in_place {
+        "--in-place cannot be used with `-`"
+    } else {
+        return;
+    };
+    exit_with(cli_command().error(ErrorKind::ArgumentConflict, conflict));
+}
+
+/// Print a command-line error and exit, with [`exit_code::USAGE`] for usage errors
+fn exit_with(error: clap::Error) -> ! {
+    let _ = error.print();
+    std::process::exit(if error.use_stderr() { exit_code::USAGE } else { exit_code::OK })
+}
+
+/// Fail with [`exit_code::INPUT_REQUIRED`] instead of prompting when prompts are disabled
+fn require_interactive(prompt: &str, alternative: &str) -> CryptoResult<()> {
+    if NON_INTERACTIVE.load(Ordering::SeqCst) {
+        return Err(CargoCryptError::input_required(
+            format!("{} (prompts are disabled)", prompt.trim_end().trim_end_matches(':')),
+            format!("{}, or run without --non-interactive", alternative),
+        ));
+    }
+    Ok(())
+}
+
+fn read_stdin() -> CryptoResult<Vec<u8>> {
+    use std::io::Read;
+
+    let mut input = Vec::new();
+    std::io::stdin().lock().read_to_end(&mut input).map_err(CargoCryptError::from)?;
+    Ok(input)
+}
+
+fn read_password_stdin() -> CryptoResult<SecureString> {
+    use std::io::{self, BufRead};
+    use zeroize::Zeroize;
+
+    // Room for long passphrases, so the line is not reallocated while read
+    let mut line = String::with_capacity(1024);
+    let result = io::stdin().lock().read_line(&mut line);
+    let password = SecureString::from(line.trim());
+    line.zeroize();
+    result.map_err(CargoCryptError::from)?;
+    Ok(password)
+}
+
+/// Prompt for a password on the terminal without echo
+fn prompt_password(prompt: impl ToString) -> CryptoResult<SecureString> {
+    let prompt = prompt.to_string();
+    require_interactive(&prompt, "Pass the password with --password-stdin where the command offers it")?;
+    Ok(rpassword::prompt_password(prompt).map(SecureString::from)?)
+}
+
+/// The working tree git runs the filters in
+fn filter_workdir() -> PathBuf {
+    git2::Repository::discover(".")
+        .ok()
+        .and_then(|repo| repo.workdir().map(PathBuf::from))
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// The git filters of the working tree git runs them in
+async fn git_filter() -> CryptoResult<cargocrypt::git::GitFilter> {
+    let workdir = filter_workdir();
+    let crypt = CargoCrypt::builder().project_root(&workdir).build().await?;
+    cargocrypt::git::GitFilter::new(crypt, workdir)
+}
+
+/// The filter credential selected by `--vault`
+fn filter_credential(vault: Option<String>) -> cargocrypt::git::Credential {
+    vault.map_or(cargocrypt::git::Credential::Filter, cargocrypt::git::Credential::Vault)
+}
+
+/// Read a password for the filters, checking a vault's password against the vault
+async fn read_filter_password(workdir: &Path, credential: &cargocrypt::git::Credential, password_stdin: bool) -> CryptoResult<SecureString> {
+    use cargocrypt::git::Credential;
+
+    let password = if password_stdin {
+        read_password_stdin()?
+    } else {
+        let password = prompt_password(format!("Enter the {}: ", credential))?;
+        if *credential == Credential::Filter && password != prompt_password("Confirm password: ")? {
+            fail(exit_code::FAILURE, "Passwords do not match");
+        }
+        password
+    };
+    if let Credential::Vault(vault) = credential {
+        let crypt = CargoCrypt::builder().project_root(workdir).build().await?;
+        crypt.unlock_vault(vault, &password).await?;
+    }
+    Ok(password)
+}
+
+/// Patterns the init wizard offers besides the configured defaults
+const EXTRA_PATTERNS: &[&str] = &["*.pem", "*.p12", "*.pfx", "credentials/*"];
+
+/// `cargocrypt init --interactive`: key mode, performance profile, then git setup
+async fn init_wizard(git: bool) -> CryptoResult<()> {
+    use cargocrypt::crypto::{PerformanceProfile, RecipientKeyPair};
+    use cargocrypt::git::{GitCryptConfig, GitIntegration, TeamMember};
+    use cargocrypt::init::{InitOptions, KeyConfig, KeyMode};
+    use dialoguer::{Confirm, Input, MultiSelect, Select};
+    use std::io::IsTerminal;
+
+    require_interactive("The init wizard", "Run 'cargocrypt init' and change settings with 'cargocrypt config set'")?;
+    if !std::io::stdin().is_terminal() || !std::io::stderr().is_terminal() {
+        fail(exit_code::INPUT_REQUIRED, "--interactive asks its questions on a terminal; run 'cargocrypt init' instead");
+    }
+    let project_root = cargocrypt::utils::find_project_root()?;
+    println!("🧙 Setting up CargoCrypt in {}", project_root.display());
+
+    let modes = KeyMode::all();
+    let items: Vec<&str> = modes
+        .iter()
+        .map(|mode| match mode {
+            KeyMode::Password => "Password: typed when encrypting; the git filters ask once per session",
+            KeyMode::Keyfile => "Keyfile: a random key in .cargocrypt/keys/default.key, e.g. for CI",
+            KeyMode::Team => "Team: keys shared with each member's X25519 key through git",
+        })
+        .collect();
+    let mode = modes[Select::new()
+        .with_prompt("How should files be keyed?")
+        .items(&items)
+        .default(0)
+        .interact()
+        .map_err(std::io::Error::other)?];
+
+    println!("⏱️  Benchmarking key derivation on this machine...");
+    let options = cargocrypt::bench::BenchOptions {
+        data_size: 1024 * 1024,
+        kdf_budget: std::time::Duration::from_secs(1),
+    };
+    let bench_root = project_root.clone();
+    let report = tokio::task::spawn_blocking(move || cargocrypt::bench::run(&bench_root, &options))
+        .await
+        .map_err(std::io::Error::other)??;
+    let profiles = PerformanceProfile::all();
+    let items: Vec<String> = profiles
+        .iter()
+        .map(|profile| {
+            let timing = match report.profiles.iter().find(|benchmark| benchmark.profile == *profile) {
+                Some(benchmark) => format!("{:.0} ms per password", benchmark.key_derivation_ms),
+                None => "over 1 s per password, not measured".to_string(),
+            };
+            let recommended = if *profile == report.recommended { " (recommended)" } else { "" };
+            format!("{:<9} {}{}", format!("{:?}", profile), timing, recommended)
+        })
+        .collect();
+    let profile = profiles[Select::new()
+        .with_prompt("Performance profile for new files")
+        .items(&items)
+        .default(profiles.iter().position(|profile| *profile == report.recommended).unwrap_or(0))
+        .interact()
+        .map_err(std::io::Error::other)?];
+
+    let workdir = filter_workdir();
+    let in_repo = git2::Repository::discover(&project_root).is_ok();
+    let use_git = if mode.requires_git() {
+        println!("ℹ️  Team keys are shared through git, so git integration will be set up");
+        true
+    } else {
+        Confirm::new()
+            .with_prompt(if in_repo {
+                "Set up git integration in this repository?"
+            } else {
+                "Create a git repository and set up git integration?"
+            })
+            .default(git || in_repo)
+            .interact()
+            .map_err(std::io::Error::other)?
+    };
+
+    let mut patterns = Vec::new();
+    let (mut hooks, mut attributes) = (false, false);
+    if use_git {
+        let defaults = GitCryptConfig::load_project(&workdir)?.setup.default_patterns;
+        let mut candidates = defaults.clone();
+        candidates.extend(EXTRA_PATTERNS.iter().map(|pattern| pattern.to_string()).filter(|pattern| !defaults.contains(pattern)));
+        let checked: Vec<bool> = candidates.iter().map(|pattern| defaults.contains(pattern)).collect();
+        let chosen = MultiSelect::new()
+            .with_prompt("Files to protect (space toggles, enter accepts)")
+            .items(&candidates)
+            .defaults(&checked)
+            .interact()
+            .map_err(std::io::Error::other)?;
+        patterns.extend(chosen.into_iter().map(|index| candidates[index].clone()));
+        let more: String = Input::new()
+            .with_prompt("More patterns, separated by spaces (enter for none)")
+            .allow_empty(true)
+            .interact_text()
+            .map_err(std::io::Error::other)?;
+        for pattern in more.split_whitespace() {
+            if !patterns.iter().any(|chosen| chosen == pattern) {
+                patterns.push(pattern.to_string());
+            }
+        }
+
+        hooks = Confirm::new()
+            .with_prompt("Install git hooks that stop commits containing secrets?")
+            .default(true)
+            .interact()
+            .map_err(std::io::Error::other)?;
+        attributes = Confirm::new()
+            .with_prompt("Encrypt these files on commit with git filters (.gitattributes)?")
+            .default(true)
+            .interact()
+            .map_err(std::io::Error::other)?;
+    }
+
+    let keys = KeyConfig::for_mode(mode);
+    println!();
+    println!("📋 Summary:");
+    match &keys.keyfile {
+        Some(keyfile) => println!("   Keys:     {} ({})", mode, keyfile.display()),
+        None => println!("   Keys:     {}", mode),
+    }
+    println!("   Profile:  {:?} ({})", profile, profile.kdf());
+    if use_git {
+        let protected = if patterns.is_empty() { "none".to_string() } else { patterns.join(" ") };
+        println!("   Protect:  {}", protected);
+        println!("   Hooks:    {}", if hooks { "installed" } else { "not installed" });
+        println!("   Filters:  {}", if attributes { "configured in .gitattributes" } else { "not configured" });
+    } else {
+        println!("   Git:      not set up");
+    }
+    let confirmed = Confirm::new()
+        .with_prompt("Write this configuration?")
+        .default(true)
+        .interact()
+        .map_err(std::io::Error::other)?;
+    if !confirmed {
+        println!("Nothing written");
+        return Ok(());
+    }
+
+    CargoCrypt::init_project_with(&InitOptions { profile: Some(profile), key_mode: Some(mode) }).await?;
+    println!("✅ CargoCrypt initialized successfully!");
+
+    if use_git {
+        println!("🔧 Setting up Git integration...");
+        let mut git_integration = GitIntegration::new().await?;
+        // Filter keyfiles are relative to the working tree, the project's keyfile to the project root
+        let prefix = project_root
+            .canonicalize()
+            .ok()
+            .zip(git_integration.repo().workdir().canonicalize().ok())
+            .and_then(|(root, workdir)| root.strip_prefix(workdir).ok().map(Path::to_path_buf))
+            .unwrap_or_default();
+        let mut filter_keys = keys.filter_keys(&patterns);
+        for key in &mut filter_keys {
+            key.keyfile = key.keyfile.take().map(|keyfile| prefix.join(keyfile));
+        }
+        git_integration
+            .update_config(|config| {
+                config.setup.default_patterns = patterns.clone();
+                config.setup.setup_team = mode == KeyMode::Team;
+                config.features.git_hooks = hooks;
+                config.features.git_attributes = attributes;
+                config.features.team_sharing = mode == KeyMode::Team;
+                config.filter.keys.retain(|key| !patterns.contains(&key.pattern));
+                config.filter.keys.extend(filter_keys);
+            })
+            .await?;
+        git_integration.setup_repository().await?;
+        println!("✅ Git integration configured successfully!");
+    }
+
+    match mode {
+        KeyMode::Password => {
+            println!("   Encrypt files with: cargocrypt encrypt <file>");
+            if attributes {
+                println!("   Keep the filter password in the OS keyring with: cargocrypt git credentials store");
+            }
+        }
+        KeyMode::Keyfile => {
+            let keyfile = keys.keyfile.as_deref().unwrap_or(Path::new(cargocrypt::init::DEFAULT_KEYFILE));
+            println!("🔑 Keyfile: {}", keyfile.display());
+            println!("   Encrypt files with: cargocrypt encrypt --keyfile {} <file>", keyfile.display());
+            println!("   Keep a copy outside the repository, e.g. with 'cargocrypt backup create'");
+        }
+        KeyMode::Team => {
+            let team_sharing = team_sharing().await?;
+            if !team_sharing.get_members().await?.is_empty() {
+                println!("👥 The team already has members; ask an Owner or Admin to add you with 'cargocrypt team add-member'");
+                return Ok(());
+            }
+
+            let default_id = git2::Config::open_default()
+                .and_then(|config| config.get_string("user.email"))
+                .unwrap_or_default();
+            let id: String = Input::new()
+                .with_prompt("Your member ID, usually an email address")
+                .with_initial_text(default_id)
+                .interact_text()
+                .map_err(std::io::Error::other)?;
+
+            let identity_path = project_root.join(".cargocrypt").join("identity");
+            let identity = if identity_path.exists() {
+                RecipientKeyPair::load(&identity_path)?
+            } else {
+                let identity = RecipientKeyPair::generate();
+                identity.save(&identity_path)?;
+                let mut public_path = identity_path.clone().into_os_string();
+                public_path.push(".pub");
+                std::fs::write(PathBuf::from(public_path), format!("{}\n", identity.public_key_hex()))?;
+                identity
+            };
+
+            let crypt = CargoCrypt::new().await?;
+            let password = if crypt.signing_key_path().exists() {
+                prompt_password("Enter signing key password: ")?
+            } else {
+                let password = prompt_password("Enter password for your new signing key: ")?;
+                if password != prompt_password("Confirm password: ")? {
+                    fail(exit_code::FAILURE, "Passwords do not match");
+                }
+                password
+            };
+            let signing_key = crypt.load_or_create_signing_key(&password).await?;
+
+            let added_by = std::env::var("USER").unwrap_or_else(|_| "unknown".to_string());
+            let member = TeamMember::new(id.clone(), identity.public_key_hex(), signing_key.public_key_hex(), TeamRole::Owner, added_by);
+            let team_sharing = team_sharing.with_signer(signing_key);
+            team_sharing.add_member(member, None).await?;
+            if let Some(purpose) = &keys.team_key {
+                team_sharing.generate_shared_key(purpose, &id).await?;
+            }
+            // The filters unwrap team keys with the identity in the git config
+            git2::Repository::discover(&workdir)?
+                .config()?
+                .set_str("cargocrypt.team.identity", &identity_path.to_string_lossy())?;
+            println!("👥 Added {} as the team's first Owner", id);
+            println!("   Identity: {} (keep it private)", identity_path.display());
+            println!("   Share the team with 'cargocrypt team push'; add teammates with 'cargocrypt team add-member'");
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_config_command(cmd: ConfigCommands) -> CryptoResult<()> {
+    use cargocrypt::config::{self, ConfigLayers};
+
+    let project_root = cargocrypt::utils::find_project_root()?;
+    let file = |user: bool| -> CryptoResult<PathBuf> {
+        if !user {
+            return Ok(cargocrypt::CryptoConfig::project_path(&project_root));
+        }
+        config::user_path().ok_or_else(|| CargoCryptError::Config {
+            message: "Cannot locate the user configuration".to_string(),
+            suggestion: Some("Set HOME or XDG_CONFIG_HOME".to_string()),
+        })
+    };
+
+    match cmd {
+        ConfigCommands::Get { key } => {
+            let layers = ConfigLayers::load(&project_root)?;
+            match layers.get(&key)? {
+                Some((value, source)) if json_output() => {
+                    print_json(&serde_json::json!({ "key": key, "value": value, "source": source }))?;
+                }
+                Some((toml::Value::Table(table), source)) => {
+                    print!("{}", table);
+                    eprintln!("# from {}", source);
+                }
+                Some((value, source)) => {
+                    match value {
+                        toml::Value::String(string) => println!("{}", string),
+                        other => println!("{}", other),
+                    }
+                    eprintln!("# from {}", source);
+                }
+                None => fail(exit_code::FAILURE, &format!("'{}' is not set", key)),
+            }
+        }
+        ConfigCommands::Set { key, value, user } => {
+            let path = file(user)?;
+            let stored = config::set(&path, &key, &value)?;
+            if json_output() {
+                print_json(&serde_json::json!({ "key": key, "value": stored, "path": path }))?;
+            } else {
+                println!("✅ Set {} = {}", key, stored);
+                println!("   Saved to {}", path.display());
+            }
+        }
+        ConfigCommands::Edit { user } => {
+            require_interactive("Editing the configuration", "Change single settings with 'cargocrypt config set'")?;
+            let path = file(user)?;
+            let changed = config::edit(&path)?;
+            if json_output() {
+                print_json(&serde_json::json!({ "path": path, "changed": changed }))?;
+            } else if changed {
+                println!("✅ Saved {}", path.display());
+            } else {
+                println!("ℹ️  No changes to {}", path.display());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn handle_key_command(cmd: KeyCommands) -> CryptoResult<()> {
+    use cargocrypt::crypto::Keyfile;
+
+    match cmd {
+        KeyCommands::Generate { path, force } => {
+            if path.exists() {
+                if !force {
+                    eprintln!("❌ Error: {} already exists (use --force to replace it)", path.display());
+                    std::process::exit(1);
+                }
+                std::fs::remove_file(&path)?;
+            }
+
+            Keyfile::generate()?.save(&path)?;
+            println!("🔑 Keyfile written: {}", path.display());
+            println!("   Keep it out of version control; in CI, store it as a secret file");
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_vault_command(cmd: VaultCommands) -> CryptoResult<()> {
+    let crypt = CargoCrypt::new().await?;
+
+    match cmd {
+        VaultCommands::Create { name, description, password_stdin } => {
+            let password = if password_stdin {
+                read_password_stdin()?
+            } else {
+                let password = prompt_password(format!("Enter password for vault '{}': ", name))?;
+                let password_confirm = prompt_password("Confirm password: ")?;
+
+                if password != password_confirm {
+                    fail(exit_code::FAILURE, "Passwords do not match");
+                }
+                password
+            };
+
+            let vault = crypt.create_vault(&name, &password, description).await?;
+            println!("🔐 Vault '{}' created in {}", vault.name(), vault.dir().display());
+            println!("   Encrypt into it with `cargocrypt encrypt --vault {} <file>`", vault.name());
+        }
+        VaultCommands::List => {
+            let vaults = crypt.vaults()?;
+            if vaults.is_empty() {
+                println!("No vaults yet; create one with `cargocrypt vault create <name>`");
+            }
+            for vault in vaults {
+                let info = vault.info().ok();
+                let created = info
+                    .as_ref()
+                    .map(|info| format_expiry(info.created_at))
+                    .unwrap_or_else(|| "unknown".to_string());
+                let description = info.and_then(|info| info.description).unwrap_or_default();
+                let line = format!("  {:<16} created {}  {}", vault.name(), created, description);
+                println!("{}", line.trim_end());
+            }
+        }
+        VaultCommands::Passwd { name } => {
+            let old_password = prompt_password(format!("Enter current password for vault '{}': ", name))?;
+            let new_password = prompt_password("Enter new password: ")?;
+            let password_confirm = prompt_password("Confirm new password: ")?;
+
+            if new_password != password_confirm {
+                fail(exit_code::FAILURE, "Passwords do not match");
+            }
+
+            crypt.change_vault_password(&name, &old_password, &new_password).await?;
+            println!("✅ Password of vault '{}' changed", name);
+        }
+        VaultCommands::ExportKey { name, path, password_stdin } => {
+            if path.exists() {
+                eprintln!("❌ Error: {} already exists", path.display());
+                std::process::exit(1);
+            }
+            let password = if password_stdin {
+                read_password_stdin()?
+            } else {
+                prompt_password(format!("Enter password for vault '{}': ", name))?
+            };
+
+            crypt.unlock_vault(&name, &password).await?.save(&path)?;
+            println!("🔑 Key of vault '{}' written: {}", name, path.display());
+            println!("   Decrypt the vault's files with `cargocrypt decrypt --keyfile {}`", path.display());
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_backup_command(cmd: BackupCommands) -> CryptoResult<()> {
+    use cargocrypt::backup;
+    use cargocrypt::progress::format_bytes;
+
+    let crypt = CargoCrypt::new().await?;
+    let password = |password_stdin: bool| -> CryptoResult<SecureString> {
+        if password_stdin {
+            read_password_stdin()
+        } else {
+            prompt_password("Enter backup password: ")
+        }
+    };
+
+    match cmd {
+        BackupCommands::Create { output, password_stdin } => {
+            let password = if password_stdin {
+                read_password_stdin()?
+            } else {
+                let password = prompt_password("Enter a password for the backup: ")?;
+                if password != prompt_password("Confirm password: ")? {
+                    fail(exit_code::FAILURE, "Passwords do not match");
+                }
+                password
+            };
+
+            let backup = crypt.create_backup(output.as_deref(), &password).await?;
+            if json_output() {
+                return print_json(&backup);
+            }
+            println!("💾 Backed up {} file(s) of .cargocrypt/ to {}", backup.files.len(), backup.path.display());
+            println!("   Keep a copy off this machine; restore with `cargocrypt backup restore {}`", backup.path.display());
+        }
+        BackupCommands::Restore { backup, force, dry_run, password_stdin } => {
+            let password = password(password_stdin)?;
+            if dry_run {
+                return print_plan(&crypt.plan_restore_backup(&backup, &password).await?);
+            }
+
+            let restored = crypt.restore_backup(&backup, &password, force).await?;
+            let restored = match std::env::current_dir() {
+                Ok(dir) => restored.relative_to(&dir),
+                Err(_) => restored,
+            };
+            if json_output() {
+                return print_json(&serde_json::json!({ "backup": backup, "restored": restored }));
+            }
+            if restored.is_empty() {
+                println!("✅ .cargocrypt/ already matches {}", backup.display());
+            } else {
+                println!("♻️  Restored {} file(s) from {}", restored.len(), backup.display());
+                print!("{}", restored);
+            }
+        }
+        BackupCommands::List { backup: Some(backup), password_stdin } => {
+            let files = crypt.read_backup(&backup, &password(password_stdin)?).await?;
+            let entries: Vec<_> = files.into_iter().map(|(entry, _)| entry).collect();
+            if json_output() {
+                return print_json(&entries);
+            }
+            for entry in &entries {
+                println!("  {:>10}  {}", format_bytes(entry.size), entry.path.display());
+            }
+            println!("{} file(s)", entries.len());
+        }
+        BackupCommands::List { backup: None, .. } => {
+            let backups = backup::list(crypt.project_root())?;
+            if json_output() {
+                return print_json(&backups);
+            }
+            if backups.is_empty() {
+                println!("No backups in {}; create one with `cargocrypt backup create`", backup::backups_dir(crypt.project_root()).display());
+            }
+            for info in backups {
+                let created = info.created_at.map(format_expiry).unwrap_or_else(|| "unknown".to_string());
+                let name = info.path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+                println!("  {:<26} {:>10}  created {}  {}", name, format_bytes(info.size), created, info.description.unwrap_or_default());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_store_command(cmd: StoreCommands) -> CryptoResult<()> {
+    use cargocrypt::crypto::{secrets::FILE_MAGIC, EncryptedSecret};
+    use cargocrypt::git::{EncryptedStorage, GitIntegration, SyncOutcome};
+
+    let git = GitIntegration::new().await?;
+    let storage = EncryptedStorage::new(git.repo(), git.crypto())?;
+    let password = |password_stdin: bool, confirm: bool| -> CryptoResult<SecureString> {
+        if password_stdin {
+            return read_password_stdin();
+        }
+        let password = prompt_password("Enter password: ")?;
+        if confirm && password != prompt_password("Confirm password: ")? {
+            fail(exit_code::FAILURE, "Passwords do not match");
+        }
+        Ok(password)
+    };
+
+    match cmd {
+        StoreCommands::Add { file, name, password_stdin } => {
+            let content = tokio::fs::read(&file).await?;
+            let encrypted = if content.starts_with(FILE_MAGIC) {
+                EncryptedSecret::from_bytes(&content)?
+            } else {
+                git.crypto().encrypt_data(&content, &password(password_stdin, true)?).await?
+            };
+            let name = name.map(PathBuf::from).unwrap_or_else(|| file.clone());
+            let stored = storage.store(&name, &encrypted).await?;
+            println!("📦 Stored {} as '{}' ({} bytes in {} chunk(s))",
+                file.display(), stored.path, stored.metadata.size, stored.metadata.chunks.len());
+            println!("   Share it with 'cargocrypt store push'");
+        }
+        StoreCommands::Get { name, output, encrypted, password_stdin } => {
+            let Some(stored) = storage.get(std::path::Path::new(&name)).await? else {
+                eprintln!("❌ '{}' is not in the store; see 'cargocrypt store list'", name);
+                std::process::exit(1);
+            };
+            let output = output.unwrap_or_else(|| PathBuf::from(&stored.metadata.original_path));
+            if output.exists() {
+                eprintln!("❌ Error: {} already exists; choose another with --output", output.display());
+                std::process::exit(1);
+            }
+            let secret = storage.retrieve(&stored).await?;
+            let content = if encrypted {
+                secret.to_bytes()?
+            } else {
+                git.crypto().decrypt_data(&secret, &password(password_stdin, false)?)?
+            };
+            if let Some(parent) = output.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            tokio::fs::write(&output, content).await?;
+            println!("🔓 Wrote '{}' to {}", stored.path, output.display());
+        }
+        StoreCommands::List => {
+            let files = storage.list_stored_files().await?;
+            if files.is_empty() {
+                println!("The store is empty; add files with 'cargocrypt store add <file>'");
+            }
+            for file in files {
+                println!("  {:<32} {:>10} bytes  {}", file.path, file.metadata.size, format_expiry(file.metadata.timestamp));
+            }
+        }
+        StoreCommands::Rm { name } => {
+            let Some(stored) = storage.get(std::path::Path::new(&name)).await? else {
+                eprintln!("❌ '{}' is not in the store", name);
+                std::process::exit(1);
+            };
+            storage.delete(&stored).await?;
+            println!("🗑️  Removed '{}'; 'cargocrypt store gc' frees its data", stored.path);
+        }
+        StoreCommands::Gc { squash } => {
+            let result = storage.optimize().await?;
+            println!("🧹 Store data: {} -> {} bytes", result.size_before, result.size_after);
+            for operation in &result.operations_performed {
+                println!("   {}", operation);
+            }
+            if squash && storage.squash_history().await? {
+                println!("   History squashed; run 'git gc --prune=now' to free the space");
+                println!("   and 'cargocrypt store push --force' to replace the remote store");
+            }
+        }
+        StoreCommands::Push { remote, force } => match storage.push(&remote, force).await {
+            Ok(()) => println!("✅ Pushed the store to {}", remote),
+            Err(e) => {
+                eprintln!("❌ {}", e);
+                std::process::exit(1);
+            }
+        },
+        StoreCommands::Pull { remote } => match storage.pull(&remote).await? {
+            SyncOutcome::NoRemoteState => println!("{} has no store yet", remote),
+            SyncOutcome::UpToDate => println!("✅ Store is up to date with {}", remote),
+            SyncOutcome::FastForward => println!("✅ Store updated from {}", remote),
+            SyncOutcome::Merged { resolved } => {
+                println!("✅ Merged the store of {}", remote);
+                for name in resolved {
+                    println!("   Changed on both sides: {}", name);
+                }
+            }
+        },
+    }
+
+    Ok(())
+}
+
+async fn handle_secret_command(cmd: SecretCommands) -> CryptoResult<()> {
+    use cargocrypt::crypto::{PlaintextSecret, SecretMetadata, SecretStore};
+    use std::io::Write;
+
+    let crypt = CargoCrypt::new().await?;
+
+    match cmd {
+        SecretCommands::Set { name, stdin, description, expires, ttl, vault, password_stdin } => {
+            cargocrypt::crypto::store::validate_secret_name(&name)?;
+            let expires_at = expires.as_deref().map(parse_expiry).transpose()?;
+            let ttl_secs = ttl.as_deref().map(cargocrypt::expiry::parse_ttl).transpose()?;
+            // With --password-stdin too, this consumes the first line of stdin
+            let store = open_secret_store(&crypt, vault.as_deref(), password_stdin).await?;
+
+            let value = if stdin {
+                let mut value = read_stdin()?;
+                if value.ends_with(b"\n") {
+                    value.pop();
+                    if value.ends_with(b"\r") {
+                        value.pop();
+                    }
+                }
+                PlaintextSecret::from_bytes(value)
+            } else {
+                PlaintextSecret::from_bytes(prompt_password(format!("Enter value for {}: ", name))?.as_bytes().to_vec())
+            };
+
+            // The rotation policy and description outlive individual values
+            let previous = store.retrieve(&name).await?.map(|secret| secret.metadata().clone());
+            let mut metadata = SecretMetadata::new();
+            metadata.description = description.or_else(|| previous.as_ref()?.description.clone());
+            metadata.set_expires_at(expires_at);
+            metadata.set_ttl(ttl_secs.or_else(|| previous.as_ref()?.ttl_secs));
+            let version = store.set(&name, value, metadata).await?;
+            println!("✅ Secret '{}' saved as version {} in {}", name, version, store.path().display());
+        }
+        SecretCommands::Get { name, version, vault, password_stdin, clip } => {
+            let store = open_secret_store(&crypt, vault.as_deref(), password_stdin).await?;
+            let value = match version {
+                Some(version) => store.get_version(&name, version).await?,
+                None => store.get(&name).await?,
+            };
+            let Some(value) = value else {
+                match version {
+                    Some(version) => eprintln!("❌ Error: secret '{}' has no version {}", name, version),
+                    None => eprintln!("❌ Error: no secret named '{}'", name),
+                }
+                std::process::exit(1);
+            };
+
+            if clip {
+                use cargocrypt::clipboard::{digest, Clipboard};
+
+                Clipboard::detect()?.copy(value.as_bytes())?;
+                let secs = crypt.config().await.security.clipboard_clear_secs;
+                if secs == 0 {
+                    eprintln!("📋 Copied '{}' to the clipboard", name);
+                    return Ok(());
+                }
+                let mut command = std::process::Command::new(std::env::current_exe()?);
+                command.args(["clear-clipboard", &secs.to_string()]);
+                match spawn_detached(&mut command, Some(&digest(value.as_bytes()))) {
+                    Ok(()) => eprintln!("📋 Copied '{}' to the clipboard; it is cleared in {}s", name, secs),
+                    Err(e) => eprintln!("📋 Copied '{}' to the clipboard, but it will not be cleared: {}", name, e),
+                }
+                return Ok(());
+            }
+            let mut stdout = std::io::stdout().lock();
+            stdout.write_all(value.as_bytes())?;
+            stdout.write_all(b"\n")?;
+        }
+        SecretCommands::List { vault, password_stdin } => {
+            let store = open_secret_store(&crypt, vault.as_deref(), password_stdin).await?;
+            let names = store.list().await?;
+            if names.is_empty() {
+                println!("No secrets yet; add one with `cargocrypt secret set <name>`");
+            }
+            for name in names {
+                let description = store
+                    .retrieve(&name)
+                    .await?
+                    .and_then(|secret| secret.metadata().description.clone())
+                    .unwrap_or_default();
+                let line = format!("  {:<32} {}", name, description);
+                println!("{}", line.trim_end());
+            }
+        }
+        SecretCommands::History { name, vault, password_stdin } => {
+            let store = open_secret_store(&crypt, vault.as_deref(), password_stdin).await?;
+            let history = store.history(&name).await;
+            if history.is_empty() {
+                eprintln!("❌ Error: no secret named '{}'", name);
+                std::process::exit(1);
+            }
+
+            println!("📜 History of '{}' (newest first):", name);
+            for saved in history.iter().rev() {
+                let saved_at = chrono::DateTime::<chrono::Utc>::from_timestamp(saved.created_at as i64, 0)
+                    .map(|time| time.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+                    .unwrap_or_else(|| saved.created_at.to_string());
+                let restored = saved
+                    .restored_from
+                    .map(|version| format!("  (rollback to v{})", version))
+                    .unwrap_or_default();
+                println!(
+                    "  v{:<4} {}  {}{}",
+                    saved.version,
+                    saved_at,
+                    saved.author.as_deref().unwrap_or("unknown"),
+                    restored
+                );
+            }
+        }
+        SecretCommands::Rollback { name, version, vault, password_stdin } => {
+            let store = open_secret_store(&crypt, vault.as_deref(), password_stdin).await?;
+            let new_version = store.rollback(&name, version).await?;
+            println!("⏪ Secret '{}' restored to the value of version {} (saved as version {})", name, version, new_version);
+        }
+        SecretCommands::Expiring { within, all, check, vault, password_stdin } => {
+            use cargocrypt::expiry::ExpiryChecker;
+
+            let store = open_secret_store(&crypt, vault.as_deref(), password_stdin).await?;
+            let mut expiry = crypt.config().await.expiry;
+            if let Some(days) = within {
+                expiry.warning_days = days;
+            }
+
+            let items = ExpiryChecker::new(expiry.warning_window()).check_store(&store).await?;
+            let due = items.iter().filter(|item| item.status.needs_attention()).count();
+            let items: Vec<ExpiringItem> = items.into_iter().filter(|item| all || item.status.needs_attention()).collect();
+
+            if json_output() {
+                print_json(&items)?;
+            } else if !items.is_empty() {
+                let title = if all { "Secrets with an expiry date or TTL" } else { "Secrets due for rotation" };
+                print_expiring_items(title, &items);
+            } else {
+                println!("✅ No secrets expire or need rotation within {} days", expiry.warning_days);
+            }
+
+            if check && due > 0 {
+                std::process::exit(exit_code::FINDINGS);
+            }
+        }
+        SecretCommands::Rm { name, vault, password_stdin } => {
+            let store = open_secret_store(&crypt, vault.as_deref(), password_stdin).await?;
+            if !store.remove(&name).await? {
+                eprintln!("❌ Error: no secret named '{}'", name);
+                std::process::exit(1);
+            }
+            println!("🗑️  Secret '{}' removed", name);
+        }
+    }
+
+    Ok(())
+}
+
+/// Scan a directory with a progress bar on stderr; Ctrl-C stops the scan and keeps partial results
+async fn scan_directory_with_progress_bar(
+    detector: &cargocrypt::detection::SecretDetector,
+    path: &std::path::Path,
+    options: &cargocrypt::detection::ScanOptions,
+) -> CryptoResult<Vec<cargocrypt::detection::Finding>> {
+    use cargocrypt::detection::CancellationToken;
+    use cargocrypt::progress::format_bytes;
+
+    let cancellation = CancellationToken::new();
+    let ctrl_c = tokio::spawn({
+        let cancellation = cancellation.clone();
+        async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                cancellation.cancel();
+            }
+        }
+    });
+
+    let progress_bar = Progress::files("Scanning");
+    let findings = detector
+        .scan_directory_with_progress(path, options, &cancellation, |progress| {
+            let secs = progress_bar.elapsed().as_secs_f64().max(0.001);
+            progress_bar.set_files(progress.files_done as u64, progress.files_total as u64);
+            progress_bar.set_message(format!(
+                "({}/s, {} finding(s)) {}",
+                format_bytes((progress.bytes_done as f64 / secs) as u64),
+                progress.findings,
+                progress.current_path.strip_prefix(path).unwrap_or(&progress.current_path).display()
+            ));
+        })
+        .await;
+    progress_bar.finish();
+    ctrl_c.abort();
+
+    if cancellation.is_cancelled() {
+        eprintln!("⚠️  Scan cancelled: only the files scanned before Ctrl-C are reported");
+    }
+    findings
+}
+
+/// `scan --watch`: scan once, then print findings in files as they change
+async fn watch_for_secrets(
+    detector: &cargocrypt::detection::SecretDetector,
+    path: &std::path::Path,
+    options: &cargocrypt::detection::ScanOptions,
+) -> CryptoResult<()> {
+    use cargocrypt::detection::{ScanReport, SecretWatcher, WatchEvent};
+
+    let root = if path.is_file() { path.parent().unwrap_or(path) } else { path };
+    let findings = if path.is_file() {
+        detector.scan_file(path, options).await?
+    } else {
+        scan_directory_with_progress_bar(detector, path, options).await?
+    };
+    let findings: Vec<_> = findings.into_iter().filter(|finding| finding.should_report()).collect();
+    if findings.is_empty() {
+        println!("✅ No secrets found");
+    } else {
+        print!("{}", ScanReport::from_findings(root, &findings).to_table());
+        println!("🔍 {} potential secret(s) found", findings.len());
+    }
+
+    let mut watcher = SecretWatcher::new(detector, path, options);
+    watcher.track_findings(&findings);
+    println!("👀 Watching {} for changes (Ctrl-C to stop)", path.display());
+    let root = std::path::absolute(root).unwrap_or_else(|_| root.to_path_buf());
+    watcher.run(|event| {
+        let time = chrono::Local::now().format("%H:%M:%S");
+        match event {
+            WatchEvent::Findings { file, findings } => {
+                let relative = file.strip_prefix(&root).unwrap_or(file);
+                for finding in findings {
+                    println!(
+                        "🚨 {} {}:{}:{}  {}  {}",
+                        time,
+                        relative.display(),
+                        finding.secret.line_number,
+                        finding.secret.column_number,
+                        finding.secret.secret_type,
+                        finding.redacted_preview()
+                    );
+                }
+            }
+            WatchEvent::Resolved { file } => {
+                println!("✅ {} {}: no secrets left", time, file.strip_prefix(&root).unwrap_or(file).display());
+            }
+        }
+        true
+    })
+}
+
+/// Unlock the project's secret store, or a vault's
+async fn open_secret_store(
+    crypt: &CargoCrypt,
+    vault: Option<&str>,
+    password_stdin: bool,
+) -> CryptoResult<cargocrypt::crypto::FileSecretStore> {
+    if let Some(vault) = vault {
+        let password = if password_stdin {
+            read_password_stdin()?
+        } else {
+            prompt_password(format!("Enter password for vault '{}': ", vault))?
+        };
+        return crypt.open_vault_secret_store(vault, &password).await;
+    }
+
+    let password = if password_stdin {
+        read_password_stdin()?
+    } else if crypt.secret_store_path().exists() {
+        prompt_password("Enter secret store password: ")?
+    } else {
+        let password = prompt_password("Choose a password for the new secret store: ")?;
+        let password_confirm = prompt_password("Confirm password: ")?;
+
+        if password != password_confirm {
+            fail(exit_code::FAILURE, "Passwords do not match");
+        }
+        password
+    };
+    crypt.open_secret_store(&password).await
+}
+
+/// Decrypt the selected secrets and run `command` with them, returning its exit code
+async fn run_with_secrets(
+    secrets: Vec<String>,
+    env_files: Vec<PathBuf>,
+    vault: Option<String>,
+    password_stdin: bool,
+    command: Vec<std::ffi::OsString>,
+) -> CryptoResult<i32> {
+    use cargocrypt::crypto::SecretStore;
+    use cargocrypt::run::{env_name, SecretEnv};
+
+    let crypt = CargoCrypt::new().await?;
+    let mut env = SecretEnv::new();
+    let mut stored = Vec::new();
+
+    // Without a selection, every secret in the store
+    if !secrets.is_empty() || env_files.is_empty() {
+        if vault.is_none() && !crypt.secret_store_path().exists() {
+            fail(
+                exit_code::FAILURE,
+                "no secret store yet; add secrets with `cargocrypt secret set <name>` or pass --env-file",
+            );
+        }
+        let store = open_secret_store(&crypt, vault.as_deref(), password_stdin).await?;
+        let selected: Vec<(String, String)> = if secrets.is_empty() {
+            store.list().await?.into_iter().map(|name| (env_name(&name), name)).collect()
+        } else {
+            secrets
+                .iter()
+                .map(|secret| match secret.split_once('=') {
+                    Some((var, name)) => (var.to_string(), name.to_string()),
+                    None => (env_name(secret), secret.clone()),
+                })
+                .collect()
+        };
+        for (var, name) in selected {
+            let Some(value) = store.get(&name).await? else {
+                fail(exit_code::FAILURE, &format!("no secret named '{}'", name));
+            };
+            stored.push((var, value));
+        }
+    }
+
+    if !env_files.is_empty() {
+        let password = if password_stdin {
+            read_password_stdin()?
+        } else {
+            prompt_password("Enter password for the .env files: ")?
+        };
+        for file in &env_files {
+            env.extend(&read_encrypted_dotenv(&crypt, file, &password).await?);
+        }
+    }
+    // Secrets picked with --secret win over the .env files
+    for (var, value) in &stored {
+        env.insert_secret(var, value)?;
+    }
+
+    eprintln!("🔐 Running with {} secret(s): {}", env.len(), env.names().collect::<Vec<_>>().join(", "));
+    env.run(&command[0], &command[1..]).await
+}
+
+async fn handle_env_command(cmd: EnvCommands) -> CryptoResult<()> {
+    use cargocrypt::dotenv::{Dotenv, EncryptedDotenv, EnvChanges};
+
+    let crypt = CargoCrypt::new().await?;
+    let read_password = |path: &Path| -> CryptoResult<SecureString> {
+        if password_stdin_of(&cmd) {
+            read_password_stdin()
+        } else {
+            prompt_password(format!("Enter password for {}: ", path.display()))
+        }
+    };
+
+    match &cmd {
+        EnvCommands::Encrypt { file, output, example, .. } => {
+            let output = output.clone().unwrap_or_else(|| with_suffix(file, ".enc"));
+            let (mut encrypted, key) = if output.exists() {
+                let encrypted = EncryptedDotenv::parse(&std::fs::read_to_string(&output)?)?;
+                let key = encrypted.unlock(&read_password(&output)?)?;
+                (encrypted, key)
+            } else if password_stdin_of(&cmd) {
+                crypt.new_encrypted_dotenv(&read_password_stdin()?)?
+            } else {
+                let password = prompt_password(format!("Choose a password for {}: ", output.display()))?;
+                if password != prompt_password("Confirm password: ")? {
+                    fail(exit_code::FAILURE, "Passwords do not match");
+                }
+                crypt.new_encrypted_dotenv(&password)?
+            };
+
+            let example = load_env_example(file, example.as_deref())?;
+            let dotenv = encrypted.overrides(&Dotenv::load(file)?, example.as_ref());
+            let changes = encrypted.update(&key, &dotenv)?;
+            std::fs::write(&output, encrypted.to_string())?;
+
+            if json_output() {
+                print_json(&serde_json::json!({
+                    "input": file,
+                    "output": output,
+                    "variables": encrypted.len(),
+                    "changes": changes,
+                }))?;
+            } else if changes.is_empty() {
+                println!("✅ {} is up to date ({} variable(s))", output.display(), encrypted.len());
+            } else {
+                println!("✅ Encrypted {} variable(s) into {}", encrypted.len(), output.display());
+                print_env_changes(&changes, "   ");
+                println!("   Commit {} and keep {} out of git", output.display(), file.display());
+            }
+        }
+        EnvCommands::Decrypt { file, output, example, force, .. } => {
+            let output = match output.clone().or_else(|| without_enc_suffix(file)) {
+                Some(output) => output,
+                None => fail(exit_code::USAGE, "the file name does not end in .enc; pass --output"),
+            };
+            let encrypted = EncryptedDotenv::parse(&std::fs::read_to_string(file)?)?;
+            let key = encrypted.unlock(&read_password(file)?)?;
+            let values = encrypted.decrypt(&key)?;
+            let example = load_env_example(file, example.as_deref())?;
+            let mut merged = example.clone().unwrap_or_default();
+            merged.overlay(&values);
+
+            if output.exists() {
+                let current = Dotenv::load(&output)?;
+                if EnvChanges::between(&merged, &current).is_empty() {
+                    if json_output() {
+                        print_json(&serde_json::json!({ "input": file, "output": output, "variables": merged.len(), "written": false }))?;
+                    } else {
+                        println!("✅ {} is up to date", output.display());
+                    }
+                    return Ok(());
+                }
+                let changes = encrypted.diff(&key, &encrypted.overrides(&current, example.as_ref()))?;
+                if !force && !changes.is_empty() {
+                    let names: Vec<&str> =
+                        changes.added.iter().chain(&changes.changed).chain(&changes.removed).map(String::as_str).collect();
+                    fail(
+                        exit_code::FAILURE,
+                        &format!(
+                            "{} has changes that are not in {} ({}); run `cargocrypt env encrypt` to keep them or pass --force to discard them",
+                            output.display(),
+                            file.display(),
+                            names.join(", ")
+                        ),
+                    );
+                }
+            }
+            merged.save(&output)?;
+
+            if json_output() {
+                print_json(&serde_json::json!({ "input": file, "output": output, "variables": merged.len(), "written": true }))?;
+            } else {
+                println!(
+                    "✅ Decrypted {} variable(s) into {} ({} encrypted, {} default(s) from the example)",
+                    merged.len(),
+                    output.display(),
+                    values.len(),
+                    merged.names().filter(|name| values.get(name).is_none()).count()
+                );
+            }
+        }
+        EnvCommands::Diff { file, example, .. } => {
+            let encrypted = EncryptedDotenv::parse(&std::fs::read_to_string(file)?)?;
+            let example = load_env_example(file, example.as_deref())?;
+
+            // What `env encrypt` would change for the plaintext file
+            let plaintext = without_enc_suffix(file).filter(|plaintext| plaintext.exists());
+            let plaintext_changes = match &plaintext {
+                Some(plaintext) => {
+                    let key = encrypted.unlock(&read_password(file)?)?;
+                    Some(encrypted.diff(&key, &encrypted.overrides(&Dotenv::load(plaintext)?, example.as_ref()))?)
+                }
+                None => None,
+            };
+            // Variables the example does not document, and required ones (empty in the example) not set
+            let undocumented: Vec<&str> = match &example {
+                Some(example) => encrypted.names().filter(|name| example.get(name).is_none()).collect(),
+                None => Vec::new(),
+            };
+            let missing: Vec<&str> = match &example {
+                Some(example) => example
+                    .iter()
+                    .filter(|(name, value)| value.is_empty() && !encrypted.names().any(|encrypted| encrypted == *name))
+                    .map(|(name, _)| name)
+                    .collect(),
+                None => Vec::new(),
+            };
+            let clean = plaintext_changes.as_ref().is_none_or(EnvChanges::is_empty) && undocumented.is_empty() && missing.is_empty();
+
+            if json_output() {
+                print_json(&serde_json::json!({
+                    "file": file,
+                    "plaintext": plaintext,
+                    "changes": plaintext_changes,
+                    "undocumented": undocumented,
+                    "missing": missing,
+                }))?;
+            } else {
+                if let (Some(plaintext), Some(changes)) = (&plaintext, &plaintext_changes) {
+                    if !changes.is_empty() {
+                        println!("📝 {} has changes that are not encrypted yet:", plaintext.display());
+                        print_env_changes(changes, "   ");
+                    }
+                }
+                if !undocumented.is_empty() {
+                    println!("📖 Not documented in the example: {}", undocumented.join(", "));
+                }
+                if !missing.is_empty() {
+                    println!("❓ Required by the example but not set: {}", missing.join(", "));
+                }
+                if clean {
+                    println!("✅ {} matches {}", file.display(), match (&plaintext, &example) {
+                        (Some(_), Some(_)) => "the plaintext file and the example",
+                        (Some(_), None) => "the plaintext file",
+                        (None, Some(_)) => "the example",
+                        (None, None) => "itself (no plaintext file or example to compare with)",
+                    });
+                }
+            }
+            if !clean {
+                std::process::exit(exit_code::FINDINGS);
+            }
+        }
+        EnvCommands::Export { file, example, format, .. } => {
+            use std::io::Write;
+
+            let encrypted = EncryptedDotenv::parse(&std::fs::read_to_string(file)?)?;
+            let values = encrypted.decrypt(&encrypted.unlock(&read_password(file)?)?)?;
+            let mut merged = load_env_example(file, example.as_deref())?.unwrap_or_default();
+            merged.overlay(&values);
+
+            let format = if json_output() { EnvFormat::Json } else { *format };
+            let text = match format {
+                EnvFormat::Dotenv => merged.to_dotenv(),
+                EnvFormat::Shell => merged.to_shell(),
+                EnvFormat::Json => merged.to_json(),
+            };
+            std::io::stdout().lock().write_all(text.as_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+fn password_stdin_of(cmd: &EnvCommands) -> bool {
+    match cmd {
+        EnvCommands::Encrypt { password_stdin, .. }
+        | EnvCommands::Decrypt { password_stdin, .. }
+        | EnvCommands::Diff { password_stdin, .. }
+        | EnvCommands::Export { password_stdin, .. } => *password_stdin,
+    }
+}
+
+/// The `.env.example` to use for `file`: `example` if given, else the one next to `file` if present
+fn load_env_example(file: &Path, example: Option<&Path>) -> CryptoResult<Option<cargocrypt::dotenv::Dotenv>> {
+    let path = match example {
+        Some(example) => example.to_path_buf(),
+        None => file.with_file_name(".env.example"),
+    };
+    if example.is_none() && !path.exists() {
+        return Ok(None);
+    }
+    cargocrypt::dotenv::Dotenv::load(&path).map(Some)
+}
+
+/// Print added, changed and removed variable names, one per line
+fn print_env_changes(changes: &cargocrypt::dotenv::EnvChanges, indent: &str) {
+    for name in &changes.added {
+        println!("{}+ {}", indent, name);
+    }
+    for name in &changes.changed {
+        println!("{}~ {}", indent, name);
+    }
+    for name in &changes.removed {
+        println!("{}- {}", indent, name);
+    }
+}
+
+/// `path` with `suffix` appended to its file name
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().map(|name| name.to_os_string()).unwrap_or_default();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// `path` without a trailing `.enc`, or `None` if it has none
+fn without_enc_suffix(path: &Path) -> Option<PathBuf> {
+    let name = path.file_name()?.to_str()?;
+    name.strip_suffix(".enc").filter(|stem| !stem.is_empty()).map(|stem| path.with_file_name(stem))
+}
+
+/// Decrypt a `.env` file encrypted per variable by `env encrypt`, or as a whole by `encrypt`
+async fn read_encrypted_dotenv(crypt: &CargoCrypt, file: &Path, password: &str) -> CryptoResult<cargocrypt::dotenv::Dotenv> {
+    use cargocrypt::dotenv::{Dotenv, EncryptedDotenv};
+
+    let content = std::fs::read(file)?;
+    if EncryptedDotenv::is_encrypted_dotenv(&content) {
+        let encrypted = EncryptedDotenv::parse(&String::from_utf8_lossy(&content))?;
+        return encrypted.decrypt(&encrypted.unlock(password)?);
+    }
+    if let Some(vault) = crypt.file_vault(file).await? {
+        fail(
+            exit_code::FAILURE,
+            &format!("{} is encrypted in vault '{}'; only files encrypted with the project password can be read", file.display(), vault),
+        );
+    }
+    let plaintext = crypt.decrypt_to_memory(file, password).await?;
+    Dotenv::parse(plaintext.as_string()?).map_err(|e| match e {
+        CargoCryptError::Validation { message, errors, warnings } => CargoCryptError::Validation {
+            message: format!("{}: {}", file.display(), message),
+            errors,
+            warnings,
+        },
+        e => e,
+    })
+}
+
+async fn handle_agent_command(cmd: AgentCommands) -> CryptoResult<()> {
+    use cargocrypt::agent::{default_socket_path, AgentClient, AgentConfig, AgentServer, AGENT_SOCKET_ENV};
+    use std::time::Duration;
+
+    let project_root = cargocrypt::utils::find_project_root().ok();
+    let socket_path = default_socket_path(project_root.as_deref());
+    let client = AgentClient::new(&socket_path);
+
+    match cmd {
+        AgentCommands::Start { ttl, foreground } => {
+            let ttl = ttl.unwrap_or_else(|| AgentConfig::default().ttl_secs);
+
+            if foreground {
+                println!("{}={}; export {};", AGENT_SOCKET_ENV, socket_path.display(), AGENT_SOCKET_ENV);
+                AgentServer::new(&socket_path, Duration::from_secs(ttl)).run().await?;
+                return Ok(());
+            }
+
+            if client.status().is_ok() {
+                eprintln!("❌ Error: an agent is already running at {}", socket_path.display());
+                std::process::exit(1);
+            }
+
+            let mut command = std::process::Command::new(std::env::current_exe()?);
+            command
+                .args(["agent", "start", "--foreground", "--ttl", &ttl.to_string()])
+                .env(AGENT_SOCKET_ENV, &socket_path)
+                .stdin(std::process::Stdio::null())
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::null());
+            #[cfg(unix)]
+            {
+                // Keep the agent alive when the terminal sends SIGINT to the group
+                use std::os::unix::process::CommandExt;
+                command.process_group(0);
+            }
+            command.spawn()?;
+
+            // Wait for the agent to accept connections
+            for _ in 0..50 {
+                if client.status().is_ok() {
+                    println!("{}={}; export {};", AGENT_SOCKET_ENV, socket_path.display(), AGENT_SOCKET_ENV);
+                    println!("echo Agent started, keys expire after {}s;", ttl);
+                    return Ok(());
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+
+            eprintln!("❌ Error: agent did not start at {}", socket_path.display());
+            std::process::exit(1);
+        }
+        AgentCommands::Stop => {
+            client.stop()?;
+            println!("🛑 Agent stopped");
+        }
+        AgentCommands::Status => match client.status() {
+            Ok((entries, ttl_secs)) => {
+                println!("🟢 Agent running at {}", socket_path.display());
+                println!("   Cached keys: {}", entries);
+                println!("   Unlocked credentials: {}", client.credential_count().unwrap_or(0));
+                println!("   Key lifetime: {}s", ttl_secs);
+            }
+            Err(_) => {
+                println!("⚪ No agent running at {}", socket_path.display());
+                println!("   Start one with: eval $(cargocrypt agent start)");
+            }
+        },
+        AgentCommands::Clear => {
+            client.clear()?;
+            println!("🧹 Agent keys and credentials cleared");
+        }
+        AgentCommands::Unlock { vault, password_stdin } => {
+            // The filters look up the agent of the working tree they run in
+            let workdir = filter_workdir();
+            let Some(client) = AgentClient::for_project(&workdir) else {
+                eprintln!("❌ Error: no agent running for {}", workdir.display());
+                eprintln!("   Start one with: eval $(cargocrypt agent start)");
+                std::process::exit(1);
+            };
+            let credential = filter_credential(vault);
+            let password = read_filter_password(&workdir, &credential, password_stdin).await?;
+            client.add_credential(&credential.name(), &password)?;
+            let (_, ttl_secs) = client.status()?;
+            println!("🔓 The agent holds the {} for the git filters for {}s", credential, ttl_secs);
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_git_command(cmd: GitCommands) -> CryptoResult<()> {
+    use cargocrypt::git::{GitIntegration, GitHooks, GitAttributes, GitIgnoreManager, HookConfig, HookState, HookType};
+    use cargocrypt::git::hooks::{hook_audit_trail, SKIP_ENV};
+    
+    match cmd {
+        GitCommands::InstallHooks => {
+            let git_integration = GitIntegration::new().await?;
+            let hooks = GitHooks::new(git_integration.repo())?;
+            
+            println!("🔧 Installing Git hooks...");
+            let installed = hooks.install_configured().await?;
+            
+            println!("✅ Git hooks installed in {}", hooks.hooks_dir().display());
+            for hook_type in &installed {
+                let description = match hook_type {
+                    HookType::PreCommit => "Secret detection (commit policy)",
+                    HookType::PrePush => "Secret detection (push policy)",
+                    _ => "Records commits made with --no-verify",
+                };
+                println!("   - {}: {}", hook_type.filename(), description);
+            }
+            println!("   Hooks that were already installed still run first");
+            println!("   Choose hooks and checks in {}", HookConfig::project_path(git_integration.repo().workdir()).display());
+        }
+        GitCommands::Hooks(GitHooksCommands::Status) => {
+            let git_integration = GitIntegration::new().await?;
+            let hooks = GitHooks::new(git_integration.repo())?;
+            
+            println!("🪝 Hooks in {}", hooks.hooks_dir().display());
+            if git_integration.repo().is_worktree() {
+                println!("   (linked worktree: hooks are shared with the main checkout)");
+            }
+            println!("{:<12} {:<8} {:<17} {:<10} CHAINED", "HOOK", "ENABLED", "STATE", "VERSION");
+            let statuses = hooks.status()?;
+            for status in &statuses {
+                println!(
+                    "{:<12} {:<8} {:<17} {:<10} {}",
+                    status.hook_type.filename(),
+                    if status.enabled { "yes" } else { "no" },
+                    status.state,
+                    status.version.as_deref().unwrap_or("-"),
+                    if status.chained { "yes" } else { "no" }
+                );
+            }
+            
+            let drifted = statuses.iter().any(|status| match status.state {
+                HookState::Current => !status.enabled,
+                HookState::Foreign => false,
+                HookState::Missing => status.enabled,
+                HookState::Outdated | HookState::Modified => true,
+            });
+            if drifted {
+                println!("⚠️  Hooks differ from what v{} installs: run 'cargocrypt git install-hooks'", env!("CARGO_PKG_VERSION"));
+            } else {
+                println!("✅ Hooks match the configuration");
+            }
+            if let Some(skip) = std::env::var(SKIP_ENV).ok().filter(|skip| !skip.is_empty()) {
+                println!("⚠️  {}={} is set: those checks are skipped", SKIP_ENV, skip);
+            }
+        }
+        GitCommands::Hooks(GitHooksCommands::Audit { limit }) => {
+            let git_integration = GitIntegration::new().await?;
+            let entries = hook_audit_trail(git_integration.repo().inner(), Some(limit))?;
+            if entries.is_empty() {
+                println!("No skipped checks or --no-verify commits recorded");
+            }
+            for entry in entries {
+                let when = chrono::DateTime::<chrono::Utc>::from_timestamp(entry.timestamp as i64, 0)
+                    .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+                    .unwrap_or_else(|| entry.timestamp.to_string());
+                println!("{}  {:<10} {:<24} {}", when, entry.operation, entry.actor, entry.details);
+            }
+        }
+        GitCommands::UninstallHooks => {
+            let git_integration = GitIntegration::new().await?;
+            let hooks = GitHooks::new(git_integration.repo())?;
+            
+            println!("🔧 Uninstalling Git hooks...");
+            hooks.uninstall_hooks().await?;
+            println!("✅ Git hooks removed successfully!");
+        }
+        GitCommands::GeneratePreReceive { output } => {
+            use cargocrypt::git::hooks::GitHook;
+            use cargocrypt::git::{HookConfig, PreReceiveHook};
+
+            let script = PreReceiveHook::new()?.generate_script(&HookConfig::default())?;
+            match output {
+                Some(file_path) => {
+                    tokio::fs::write(&file_path, &script).await?;
+                    #[cfg(unix)]
+                    {
+                        use std::os::unix::fs::PermissionsExt;
+                        tokio::fs::set_permissions(&file_path, std::fs::Permissions::from_mode(0o755)).await?;
+                    }
+                    println!("✅ Pre-receive hook written to: {}", file_path.display());
+                    println!("   Install it as hooks/pre-receive of the server's bare repositories,");
+                    println!("   with cargocrypt in the PATH of the git server user");
+                }
+                None => print!("{}", script),
+            }
+        }
+        GitCommands::ConfigureAttributes { dry_run } => {
+            let mut plan = Plan::new();
+            let git_integration = if dry_run {
+                match discover_git(&mut plan)? {
+                    Some(git_integration) => git_integration,
+                    None => return print_plan(&plan),
+                }
+            } else {
+                GitIntegration::new().await?
+            };
+            let mut attributes = GitAttributes::new(git_integration.repo())?;
+            
+            // Add the patterns of member crates, then the default CargoCrypt patterns
+            let scoped = attributes.add_workspace_patterns(&git_integration.workspace()?).await?;
+            attributes.add_cargocrypt_patterns().await?;
+            if dry_run {
+                attributes.plan_save(&mut plan)?;
+                return print_plan(&plan);
+            }
+            
+            println!("🔧 Configuring Git attributes...");
+            
+            // Configure filters
+            attributes.configure_filters(git_integration.config()).await?;
+            
+            // Save attributes
+            attributes.save().await?;
+            
+            println!("✅ Git attributes configured successfully!");
+            println!("   Patterns added for automatic encryption:");
+            for pattern in attributes.get_patterns() {
+                println!("   - {}", pattern.pattern);
+            }
+            if scoped > 0 {
+                println!("   {} of them from member crates' .cargocrypt/git.toml", scoped);
+            }
+        }
+        GitCommands::SuggestPatterns { apply, yes } => {
+            use cargocrypt::detection::{RuleConfig, SecretDetector};
+            use std::io::IsTerminal;
+            
+            let git_integration = GitIntegration::new().await?;
+            let workdir = git_integration.repo().workdir().to_path_buf();
+            let mut detector = SecretDetector::new();
+            detector.load_custom_rules(&RuleConfig::load_project(&workdir)?)?;
+            let findings = scan_directory_with_progress_bar(&detector, &workdir, &project_scan_options(&workdir, false)?).await?;
+            let suggestions = cargocrypt::git::suggest_patterns(&workdir, &findings)?;
+            let json = json_output();
+            let result = |added: &[&cargocrypt::git::PatternSuggestion], committed: bool| {
+                let patterns: Vec<_> = suggestions
+                    .patterns
+                    .iter()
+                    .map(|suggestion| serde_json::json!({
+                        "pattern": suggestion.pattern,
+                        "files": suggestion.files,
+                        "findings": suggestion.findings,
+                    }))
+                    .collect();
+                let added: Vec<&str> = added.iter().map(|suggestion| suggestion.pattern.as_str()).collect();
+                serde_json::json!({
+                    "patterns": patterns,
+                    "source_files": suggestions.source_files,
+                    "added": added,
+                    "committed_in_plaintext": committed,
+                })
+            };
+            
+            if !json {
+                for file in &suggestions.source_files {
+                    println!("⚠️  {}: secrets in source files cannot be encrypted; move them to a config file or the secret store", file.display());
+                }
+                if suggestions.patterns.is_empty() {
+                    println!("✅ Every other file with high-confidence secrets is covered by an encryption pattern");
+                    return Ok(());
+                }
+                println!("💡 Suggested .gitattributes encryption patterns:");
+                for suggestion in &suggestions.patterns {
+                    println!("   {:<24} {} finding(s) in {} file(s)", suggestion.pattern, suggestion.findings, suggestion.files.len());
+                    for file in &suggestion.files {
+                        println!("      {}", file.display());
+                    }
+                }
+            }
+            if !apply || suggestions.patterns.is_empty() {
+                if json {
+                    return print_json(&result(&[], false));
+                }
+                println!("   Add them with: cargocrypt git suggest-patterns --apply");
+                return Ok(());
+            }
+            if !yes {
+                require_interactive("Confirming each pattern", "Pass --yes to add them all")?;
+            }
+            if !yes && !std::io::stdin().is_terminal() {
+                fail(exit_code::INPUT_REQUIRED, "--apply confirms each pattern on a terminal; pass --yes to add them all");
+            }
+            
+            let mut attributes = GitAttributes::new(git_integration.repo())?;
+            attributes.load().await?;
+            let mut added = Vec::new();
+            for suggestion in &suggestions.patterns {
+                let accepted = yes
+                    || dialoguer::Confirm::new()
+                        .with_prompt(format!("Encrypt {}?", suggestion.pattern))
+                        .default(true)
+                        .interact()
+                        .map_err(std::io::Error::other)?;
+                if accepted && attributes.add_encryption_pattern(&suggestion.pattern).await? {
+                    added.push(suggestion);
+                }
+            }
+            if added.is_empty() {
+                if json {
+                    return print_json(&result(&[], false));
+                }
+                println!("No patterns added");
+                return Ok(());
+            }
+            attributes.configure_filters(git_integration.config()).await?;
+            attributes.save().await?;
+            let repo = git_integration.repo().inner();
+            let committed = added
+                .iter()
+                .flat_map(|suggestion| &suggestion.files)
+                .any(|file| repo.revparse_single(&format!("HEAD:{}", file.display())).is_ok());
+            if json {
+                return print_json(&result(&added, committed));
+            }
+            println!("✅ Added {} pattern(s) to .gitattributes", added.len());
+            println!("   Run 'git add --renormalize .' to encrypt the files already committed");
+            if committed {
+                println!("⚠️  Committed versions stay in history in plaintext: rotate those secrets");
+            }
+        }
+        GitCommands::Workspace => {
+            let git_integration = GitIntegration::new().await?;
+            let workspace = git_integration.workspace()?;
+            if workspace.members().is_empty() {
+                println!("No packages below {}", workspace.root().display());
+                return Ok(());
+            }
+            
+            println!("📦 Packages below {}", workspace.root().display());
+            println!("{:<24} {:<32} {:<10} CONFIG", "PACKAGE", "DIRECTORY", "WORKSPACE");
+            for member in workspace.members() {
+                let config = match &member.config {
+                    Some(config) => format!("{} filter key(s)", config.filter.keys.len()),
+                    None => "root's".to_string(),
+                };
+                println!(
+                    "{:<24} {:<32} {:<10} {}",
+                    member.name,
+                    member.dir.display(),
+                    if member.in_workspace { "member" } else { "-" },
+                    config
+                );
+            }
+            let patterns = workspace.encryption_patterns();
+            if !patterns.is_empty() {
+                println!("🔐 Scoped encryption patterns:");
+                for pattern in &patterns {
+                    println!("   - {}", pattern);
+                }
+                println!("   'cargocrypt git configure-attributes' adds them to the root .gitattributes");
+            }
+        }
+        GitCommands::Status => {
+            use cargocrypt::git::repo::head_branch;
+            use cargocrypt::git::FailLevel;
+            
+            let json = json_output();
+            let git_integration = GitIntegration::new().await?;
+            let repo = git_integration.repo();
+            let branch = head_branch(repo.inner());
+            let policy = branch.as_deref().and_then(|branch| git_integration.config().branch_policy(branch));
+            
+            if !json {
+                println!("🌿 Branch: {}", branch.as_deref().unwrap_or("(detached HEAD)"));
+                match policy {
+                    Some(policy) => {
+                        let mut rules = Vec::new();
+                        if policy.require_encryption {
+                            rules.push("encryption required".to_string());
+                        }
+                        if policy.enforce_hooks {
+                            rules.push("hooks enforced".to_string());
+                        }
+                        if let Some(fail_level) = policy.fail_level {
+                            rules.push(format!("fail level {}", fail_level));
+                        }
+                        println!("📜 Branch policy '{}': {}", policy.pattern, if rules.is_empty() { "no rules".to_string() } else { rules.join(", ") });
+                    }
+                    None => println!("📜 No branch policy: hooks run as in .cargocrypt/hooks.toml"),
+                }
+            }
+            
+            let mut violations = Vec::new();
+            let mut hook_results = Vec::new();
+            let hooks = GitHooks::new(repo)?;
+            let statuses = hooks.status()?;
+            for hook_type in [HookType::PreCommit, HookType::PrePush] {
+                let settings = hooks.config().settings(&hook_type).cloned().unwrap_or_default().for_branch(policy);
+                if !settings.enabled {
+                    hook_results.push(serde_json::json!({ "hook": hook_type.filename(), "enabled": false }));
+                    if !json {
+                        println!("🪝 {:<10} disabled", hook_type.filename());
+                    }
+                    continue;
+                }
+                let state = statuses
+                    .iter()
+                    .find(|status| status.hook_type == hook_type)
+                    .map(|status| status.state)
+                    .unwrap_or(HookState::Missing);
+                hook_results.push(serde_json::json!({
+                    "hook": hook_type.filename(),
+                    "enabled": true,
+                    "state": state.to_string(),
+                    "checks": settings.checks,
+                    "fail_level": settings.fail_level,
+                }));
+                if !json {
+                    let checks = settings.checks.iter().map(|check| check.name()).collect::<Vec<_>>().join(", ");
+                    println!("🪝 {:<10} {}, checks {}, fail level {}", hook_type.filename(), state, checks, settings.fail_level);
+                }
+                if matches!(state, HookState::Missing | HookState::Foreign) {
+                    if policy.is_some_and(|policy| policy.enforce_hooks) {
+                        violations.push(format!("The branch policy enforces the {} hook but it is not installed", hook_type.filename()));
+                        if !json {
+                            eprintln!("❌ The branch policy enforces the {} hook but it is not installed", hook_type.filename());
+                        }
+                    } else if !json {
+                        println!("⚠️  The {} hook is not installed", hook_type.filename());
+                    }
+                }
+            }
+            
+            let workdir = repo.workdir();
+            let mut plaintext: Vec<(PathBuf, &str)> = cargocrypt::git::plaintext_in_index(workdir)?
+                .into_iter()
+                .map(|path| (path, "staged"))
+                .collect();
+            if let Ok(head) = repo.inner().head().and_then(|head| head.peel_to_commit()) {
+                plaintext.extend(
+                    cargocrypt::git::plaintext_in_commits(repo.inner(), &[head.id()])?
+                        .into_iter()
+                        .map(|found| (found.path, "committed")),
+                );
+            }
+            let required = policy.is_some_and(|policy| policy.require_encryption && policy.fail_level != Some(FailLevel::Warn));
+            if !plaintext.is_empty() && required {
+                violations.push(format!("{} file(s) marked for encryption are stored in plaintext", plaintext.len()));
+            }
+            
+            if json {
+                let plaintext: Vec<_> = plaintext
+                    .iter()
+                    .map(|(path, stored)| serde_json::json!({ "path": path, "stored": stored }))
+                    .collect();
+                print_json(&serde_json::json!({
+                    "branch": branch,
+                    "policy": policy,
+                    "hooks": hook_results,
+                    "plaintext": plaintext,
+                    "violations": violations,
+                }))?;
+            } else if plaintext.is_empty() {
+                println!("🔐 Files marked for encryption are stored encrypted");
+            } else {
+                let icon = if required { "❌" } else { "⚠️ " };
+                eprintln!("{} {} file(s) marked for encryption are stored in plaintext:", icon, plaintext.len());
+                for (path, stored) in &plaintext {
+                    eprintln!("   {} ({})", path.display(), stored);
+                }
+                eprintln!("   Run 'cargocrypt git configure-attributes', then 'git add --renormalize .'");
+            }
+            
+            if !violations.is_empty() {
+                if !json {
+                    eprintln!("❌ The branch does not meet its policy");
+                }
+                std::process::exit(exit_code::FINDINGS);
+            }
+        }
+        GitCommands::VerifyEncrypted { revisions } => {
+            let git_integration = GitIntegration::new().await?;
+            let plaintext = git_integration.verify_encrypted(&revisions)?;
+            if json_output() {
+                let files: Vec<_> = plaintext
+                    .iter()
+                    .map(|found| serde_json::json!({ "path": found.path, "commit": found.commit.to_string() }))
+                    .collect();
+                print_json(&serde_json::json!({ "revisions": revisions, "plaintext": files }))?;
+                if !plaintext.is_empty() {
+                    std::process::exit(exit_code::FINDINGS);
+                }
+            } else if plaintext.is_empty() {
+                println!("🔐 Files marked for encryption are stored encrypted in {}", revisions.join(", "));
+            } else {
+                eprintln!("❌ {} file(s) marked for encryption are stored in plaintext:", plaintext.len());
+                for found in &plaintext {
+                    eprintln!("   {} (commit {:.7})", found.path.display(), found.commit);
+                }
+                eprintln!("   Rotate these secrets: they are readable by everyone with access to the repository");
+                std::process::exit(exit_code::FINDINGS);
+            }
+        }
+        GitCommands::Credentials(GitCredentialsCommands::Status) => {
+            use cargocrypt::git::{credentials, Credential, GitCryptConfig, Workspace};
+            
+            let workdir = filter_workdir();
+            let settings = match Workspace::discover(&workdir) {
+                Ok(workspace) => workspace.filter_settings(),
+                Err(_) => GitCryptConfig::load_project(&workdir)?.filter,
+            };
+            let mut needed = vec![Credential::Filter];
+            for vault in settings.keys.iter().filter_map(|key| key.vault.clone()) {
+                if !needed.contains(&Credential::Vault(vault.clone())) {
+                    needed.push(Credential::Vault(vault));
+                }
+            }
+            
+            println!("🔑 Keyring: {}", cargocrypt::keyring::backend_name());
+            for credential in &needed {
+                match credentials::resolve(&workdir, credential) {
+                    Ok(resolved) => {
+                        let icon = if matches!(resolved.source, cargocrypt::git::CredentialSource::GitConfig(_)) { "⚠️ " } else { "✅" };
+                        println!("{} {}: {}", icon, credential, resolved.source);
+                    }
+                    Err(e) => {
+                        println!("❌ {}: not available", credential);
+                        if let CargoCryptError::Auth { retry_suggestion: Some(suggestion), .. } = e {
+                            println!("   {}", suggestion);
+                        }
+                    }
+                }
+            }
+            println!("   Order: key agent, keyring, identity file, environment");
+        }
+        GitCommands::Credentials(GitCredentialsCommands::Store { vault, password_stdin, from_git_config }) => {
+            use zeroize::Zeroize;
+            
+            let workdir = filter_workdir();
+            let credential = filter_credential(vault);
+            let account = credential.keyring_account(&workdir);
+            if from_git_config {
+                let key = format!("{}.password", credential.git_config_prefix());
+                let mut config = git2::Repository::discover(&workdir)?.config()?.open_level(git2::ConfigLevel::Local)?;
+                let Ok(mut password) = config.get_string(&key) else {
+                    eprintln!("❌ Error: {} is not set in this repository's git config", key);
+                    std::process::exit(exit_code::CONFIG);
+                };
+                let stored = cargocrypt::keyring::set(&account, &password);
+                password.zeroize();
+                stored?;
+                config.remove(&key)?;
+                println!("🔐 Moved the {} from git config {} to the keyring", credential, key);
+            } else {
+                let password = read_filter_password(&workdir, &credential, password_stdin).await?;
+                cargocrypt::keyring::set(&account, &password)?;
+                println!("🔐 Stored the {} in the keyring ({})", credential, cargocrypt::keyring::backend_name());
+            }
+        }
+        GitCommands::Credentials(GitCredentialsCommands::Forget { vault }) => {
+            let workdir = filter_workdir();
+            let credential = filter_credential(vault);
+            if cargocrypt::keyring::delete(&credential.keyring_account(&workdir))? {
+                println!("🗑️  Removed the {} from the keyring", credential);
+            } else {
+                println!("ℹ️  The keyring holds no {} for this repository", credential);
+            }
+        }
+        GitCommands::FilterClean { file } => {
+            // Called by git when staging: plaintext on stdin, encrypted content on stdout
+            use std::io::Write;
+
+            let input = read_stdin()?;
+            let encrypted = git_filter().await?.clean(&file, &input).await?;
+            std::io::stdout().write_all(&encrypted).map_err(CargoCryptError::from)?;
+        }
+        GitCommands::ConfigureDiff => {
+            let git_integration = GitIntegration::new().await?;
+            let mut attributes = GitAttributes::new(git_integration.repo())?;
+
+            println!("🔧 Configuring the diff driver...");
+            let updated = attributes.configure_diff().await?;
+            println!("✅ git diff and git log -p show encrypted files decrypted ({} pattern(s) updated)", updated);
+            println!("   Decrypted text is never cached; without the keys a placeholder is shown");
+        }
+        GitCommands::Textconv { file } => {
+            // Called by git diff with the blob in a file: text on stdout
+            use std::io::Write;
+
+            let shown = git_filter().await?.textconv(&std::fs::read(&file)?).await;
+            if let Some(warning) = &shown.warning {
+                eprintln!("⚠️  {}", warning);
+            }
+            std::io::stdout().write_all(&shown.content).map_err(CargoCryptError::from)?;
+        }
+        GitCommands::MergeDriver { base, ours, theirs, file } => {
+            // Called by git for paths with merge=cargocrypt; a non-zero exit reports a conflict
+            let merged = git_filter()
+                .await?
+                .merge(&file, &std::fs::read(&base)?, &std::fs::read(&ours)?, &std::fs::read(&theirs)?)
+                .await;
+            let merged = match merged {
+                Ok(merged) => merged,
+                Err(e) => {
+                    eprintln!("❌ Cannot merge {}: {}", file.display(), e);
+                    std::process::exit(1);
+                }
+            };
+            std::fs::write(&ours, &merged.content)?;
+            if merged.conflicts {
+                eprintln!("⚠️  Conflicts in {}; resolve them in the decrypted file", file.display());
+                std::process::exit(1);
+            }
+        }
+        GitCommands::FilterSmudge { file } => {
+            // Called by git on checkout: stored content on stdin, plaintext on stdout
+            use std::io::Write;
+
+            let input = read_stdin()?;
+            let smudged = git_filter().await?.smudge(&file, &input).await?;
+            if let Some(warning) = &smudged.warning {
+                eprintln!("⚠️  {}", warning);
+            }
+            std::io::stdout().write_all(&smudged.content).map_err(CargoCryptError::from)?;
+        }
+        GitCommands::UpdateIgnore { dry_run } => {
+            let mut plan = Plan::new();
+            let git_integration = if dry_run {
+                match discover_git(&mut plan)? {
+                    Some(git_integration) => git_integration,
+                    None => return print_plan(&plan),
+                }
+            } else {
+                GitIntegration::new().await?
+            };
+            let mut ignore_manager = GitIgnoreManager::new(git_integration.repo())?;
+            
+            // Add CargoCrypt patterns
+            ignore_manager.add_cargocrypt_patterns().await?;
+            if dry_run {
+                ignore_manager.plan_save(&mut plan);
+                return print_plan(&plan);
+            }
+            
+            println!("🔧 Updating .gitignore...");
+            
+            // Save the updated .gitignore
+            ignore_manager.save().await?;
+            
+            println!("✅ .gitignore updated successfully!");
+            println!("   Added patterns:");
+            for pattern in ignore_manager.get_ignore_patterns() {
+                println!("   - {}", pattern);
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+/// Scan options of a project: its detection config, cache, feedback and resource limits
+fn project_scan_options(project_root: &std::path::Path, no_cache: bool) -> CryptoResult<cargocrypt::detection::ScanOptions> {
+    use cargocrypt::detection::{DetectionConfig, Feedback, ScanCache, ScanOptions};
+
+    let mut options = ScanOptions {
+        detection_config: DetectionConfig::load_project(project_root)?,
+        cache_path: (!no_cache).then(|| ScanCache::project_path(project_root)),
+        feedback_path: Some(Feedback::project_path(project_root)),
+        ..ScanOptions::default()
+    };
+    ResourceLimitsConfig::default()
+        .with_env_overrides()
+        .apply_to_scan(&mut options.scan_config);
+    Ok(options)
+}
+
+async fn handle_migrate_command(cmd: MigrateCommands) -> CryptoResult<()> {
+    use cargocrypt::git::{gitcrypt, GitIntegration, IntegrationMode};
+
+    match cmd {
+        MigrateCommands::GitCrypt { key_file, key_name, reencrypt, gpg_users } => {
+            let git_integration = GitIntegration::new().await?;
+            let repo = git_integration.repo();
+
+            println!("🔄 Migrating from git-crypt...");
+            let report = match gitcrypt::migrate(repo, key_file.as_deref(), &key_name, reencrypt).await {
+                Ok(report) => report,
+                Err(e) => {
+                    eprintln!("❌ Migration failed: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            println!("✅ git-crypt key '{}' stored in {}", key_name, report.key_path.display());
+            println!("   {} .gitattributes pattern(s) now use the CargoCrypt filter", report.converted_patterns);
+
+            if !gpg_users.is_empty() {
+                let key = gitcrypt::GitCryptKey::load(&report.key_path)?;
+                for fingerprint in &gpg_users {
+                    let path = key.save_gpg(repo.workdir(), fingerprint)?;
+                    println!("🔑 Key wrapped for {} in {}", fingerprint, path.display());
+                }
+            }
+
+            if report.mode == IntegrationMode::Compatibility {
+                println!("   Compatibility mode: files stay in git-crypt's format, so git-crypt users can keep working");
+                println!("   Rerun with --reencrypt once everyone has moved over");
+            } else {
+                let status = std::process::Command::new("git")
+                    .args(["add", "--renormalize", "."])
+                    .current_dir(repo.workdir())
+                    .status()?;
+                if !status.success() {
+                    eprintln!("❌ 'git add --renormalize .' failed; run it to re-encrypt the files");
+                    std::process::exit(1);
+                }
+                println!("   Files re-encrypted and staged; history stays readable with the git-crypt key");
+            }
+            println!("   Commit .gitattributes and .cargocrypt/git.toml to finish");
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_team_command(cmd: TeamCommands) -> CryptoResult<()> {
+    use cargocrypt::crypto::{openpgp, RecipientKeyPair};
+    use cargocrypt::git::{GitIntegration, SyncOutcome, TeamIdentity, TeamMember};
+
+    match cmd {
+        TeamCommands::Init => {
+            let team_sharing = team_sharing().await?;
+            if team_sharing.is_initialized() {
+                println!("ℹ️  Team key sharing is already set up");
+                return Ok(());
+            }
+            team_sharing.initialize().await?;
+            println!("✅ Team key sharing set up on refs/cargocrypt/team");
+            println!("   Add members with 'cargocrypt team add-member' and share with 'cargocrypt team push'");
+        }
+        TeamCommands::AddMember { id, public_key, gpg, role, identity, gpg_identity, signing_key, signing } => {
+            let granted_by = match identity {
+                Some(path) => Some(TeamIdentity::from(RecipientKeyPair::load(path)?)),
+                None if gpg_identity => Some(TeamIdentity::Gpg),
+                None => None,
+            };
+            let added_by = std::env::var("USER").unwrap_or_else(|_| "unknown".to_string());
+            let signing_key = match signing_key {
+                Some(source) => read_public_key(&source).await?,
+                None => String::new(),
+            };
+            let member = match (public_key, gpg) {
+                (Some(source), _) => {
+                    let public_key = read_public_key(&source).await?;
+                    TeamMember::new(id.clone(), public_key, signing_key, role, added_by)
+                }
+                (None, Some(query)) => {
+                    let key = match openpgp::find_recipient(&query) {
+                        Ok(key) => key,
+                        Err(e) => {
+                            eprintln!("❌ {}", e);
+                            std::process::exit(1);
+                        }
+                    };
+                    println!("🔑 Using GPG key {} ({})", key.fingerprint, key.user_id);
+                    TeamMember::new(id.clone(), String::new(), signing_key, role, added_by)
+                        .with_gpg_fingerprint(&key.fingerprint)
+                }
+                (None, None) => unreachable!("clap requires a recipient"),
+            };
+
+            let team_sharing = team_sharing_signed_by(signing).await?;
+            if !team_sharing.is_initialized() {
+                team_sharing.initialize().await?;
+            }
+            if let Err(e) = team_sharing.add_member(member, granted_by.as_ref()).await {
+                eprintln!("❌ Failed to add {}: {}", id, e);
+                std::process::exit(1);
+            }
+            println!("✅ Added {} to the team", id);
+            if granted_by.is_none() {
+                println!("   Existing shared keys were not wrapped for them; pass --identity or --gpg-identity to share those");
+            }
+        }
+        TeamCommands::Identity { path } => {
+            let identity = RecipientKeyPair::generate();
+            if let Err(e) = identity.save(&path) {
+                eprintln!("❌ {}", e);
+                std::process::exit(1);
+            }
+            let mut public_path = path.clone().into_os_string();
+            public_path.push(".pub");
+            let public_path = PathBuf::from(public_path);
+            std::fs::write(&public_path, format!("{}\n", identity.public_key_hex()))?;
+            println!("🔐 Identity saved to {}", path.display());
+            println!("   Public key: {} (also in {})", identity.public_key_hex(), public_path.display());
+            println!("   Share the public key with a team admin; keep the identity file private");
+        }
+        TeamCommands::RemoveMember { id, signing } => {
+            let team_sharing = team_sharing_signed_by(signing).await?;
+            let removed_by = std::env::var("USER").unwrap_or_else(|_| "unknown".to_string());
+            match team_sharing.offboard_member(&id, &removed_by).await {
+                Ok(result) => {
+                    println!("✅ Removed {} from the team", id);
+                    println!("   {} shared key(s) they held were rotated", result.summary.keys_revoked);
+                    println!("   Share the change with 'cargocrypt team push'");
+                }
+                Err(e) => {
+                    eprintln!("❌ Failed to remove {}: {}", id, e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        TeamCommands::Revoke { id, rekey, dry_run: true, .. } => {
+            let Some(git) = GitIntegration::discover()? else {
+                fail(exit_code::FAILURE, "Not in a git repository");
+            };
+            let team_sharing = cargocrypt::git::TeamKeySharing::new(git.repo(), git.crypto())?;
+            let (mut plan, purposes) = team_sharing.plan_offboarding(&id).await?;
+            let rekeyed = if rekey { git.team_files(&purposes)? } else { Default::default() };
+            for path in &rekeyed.files {
+                plan.push(Change::Modify, git.repo().workdir().join(path), Some("re-encrypted and staged"));
+            }
+            print_plan(&plan)?;
+            for path in &rekeyed.skipped {
+                eprintln!("   ⚠️  Would skip {}: it is not checked out decrypted", path.display());
+            }
+        }
+        TeamCommands::Revoke { id, rekey, identity, signing, .. } => {
+            let team_sharing = team_sharing_signed_by(signing).await?;
+            let removed_by = std::env::var("USER").unwrap_or_else(|_| "unknown".to_string());
+            let result = match team_sharing.offboard_member(&id, &removed_by).await {
+                Ok(result) => result,
+                Err(e) => {
+                    eprintln!("❌ Failed to revoke {}: {}", id, e);
+                    std::process::exit(1);
+                }
+            };
+            println!("✅ Revoked {}", id);
+            println!("   {} shared key(s) they held were rotated", result.summary.keys_revoked);
+            if !rekey {
+                println!("   Files stay encrypted with the old keys until re-encrypted; run with --rekey to do it now");
+            } else if !result.summary.rotated_purposes.is_empty() {
+                if let Some(identity) = identity {
+                    // Read by the clean filter that git runs
+                    std::env::set_var("CARGOCRYPT_TEAM_IDENTITY", identity);
+                }
+                let git = GitIntegration::new().await?;
+                match git.reencrypt_team_files(&result.summary.rotated_purposes).await {
+                    Ok(rekeyed) => {
+                        println!("🔄 Re-encrypted and staged {} file(s)", rekeyed.files.len());
+                        for path in &rekeyed.skipped {
+                            println!("   ⚠️  Skipped {}: check it out decrypted and run 'git add --renormalize'", path.display());
+                        }
+                        println!("   Commit them to finish; older commits keep the old ciphertext,");
+                        println!("   and plaintext {} already has cannot be taken back", id);
+                    }
+                    Err(e) => {
+                        eprintln!("❌ Failed to re-encrypt files: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            println!("   Share the change with 'cargocrypt team push'");
+        }
+        TeamCommands::SigningKey { password_stdin } => {
+            let crypt = CargoCrypt::new().await?;
+            let new_key = !crypt.signing_key_path().exists();
+            let password = if password_stdin {
+                read_password_stdin()?
+            } else if new_key {
+                let password = prompt_password("Enter password for new signing key: ")?;
+                if password != prompt_password("Confirm password: ")? {
+                    fail(exit_code::FAILURE, "Passwords do not match");
+                }
+                password
+            } else {
+                prompt_password("Enter signing key password: ")?
+            };
+            let keypair = crypt.load_or_create_signing_key(&password).await?;
+            if new_key {
+                println!("🔑 Created signing key: {}", crypt.signing_key_path().display());
+            }
+            println!("{}", keypair.public_key_hex());
+        }
+        TeamCommands::List => {
+            let team_sharing = team_sharing().await?;
+            let members = team_sharing.get_members().await?;
+            if members.is_empty() {
+                println!("No team members yet; add one with 'cargocrypt team add-member'");
+            }
+            for member in members {
+                let recipient = match &member.gpg_fingerprint {
+                    Some(fingerprint) => format!("gpg {}", fingerprint),
+                    None => format!("x25519 {}", member.public_key),
+                };
+                let status = if member.active { "" } else { " (inactive)" };
+                println!("👤 {} [{:?}] {}{}", member.id, member.role, recipient, status);
+            }
+        }
+        TeamCommands::Rotate { key_id, signing } => {
+            let team_sharing = team_sharing_signed_by(signing).await?;
+            let rotated_by = std::env::var("USER").unwrap_or_else(|_| "unknown".to_string());
+            let result = match &key_id {
+                Some(key_id) => team_sharing
+                    .rotate_key(key_id, &rotated_by)
+                    .await
+                    .map(|new_key| println!("🔄 Rotated {} -> {}", key_id, new_key.id)),
+                None => team_sharing.rotate_keys().await.map(|()| println!("🔄 Rotated all shared keys")),
+            };
+            if let Err(e) = result {
+                eprintln!("❌ Rotation failed: {}", e);
+                std::process::exit(1);
+            }
+            println!("   Old keys are archived; share the new ones with 'cargocrypt team push'");
+        }
+        TeamCommands::Stats => {
+            let stats = team_sharing().await?.get_team_stats().await?;
+            println!("👥 Team statistics");
+            println!("   Members: {} ({} active)", stats.total_members, stats.active_members);
+            println!("   Shared keys: {} ({} expired)", stats.total_keys, stats.expired_keys);
+            println!("   Audit entries: {}", stats.audit_entries);
+        }
+        TeamCommands::Audit { limit, operation, actor } => {
+            use cargocrypt::git::AuditVerification;
+            
+            let records: Vec<_> = team_sharing()
+                .await?
+                .get_audit_trail(None)
+                .await?
+                .into_iter()
+                .filter(|record| operation.as_ref().is_none_or(|operation| record.entry.operation == *operation))
+                .filter(|record| actor.as_ref().is_none_or(|actor| record.entry.actor == *actor))
+                .collect();
+            if records.is_empty() {
+                println!("No team operations recorded yet");
+            }
+            for record in records.iter().take(limit) {
+                let entry = &record.entry;
+                let when = chrono::DateTime::<chrono::Utc>::from_timestamp(entry.timestamp as i64, 0)
+                    .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+                    .unwrap_or_else(|| entry.timestamp.to_string());
+                let commit = record.commit.map_or_else(|| "-".to_string(), |commit| format!("{:.7}", commit));
+                println!(
+                    "{}  {:<7}  {:<24} {:<16} {}  [{}]",
+                    when, commit, entry.operation, entry.actor, entry.details, record.verification
+                );
+            }
+            
+            let invalid = records.iter().filter(|record| record.verification == AuditVerification::Invalid).count();
+            if invalid > 0 {
+                eprintln!("❌ {} audit entries do not match their signature: the audit trail was altered", invalid);
+                std::process::exit(exit_code::AUTH);
+            }
+        }
+        TeamCommands::Escrow { threshold, identity, gpg_identity, signing } => {
+            let identities = team_identities(identity, gpg_identity)?;
+            let team_sharing = team_sharing_signed_by(signing).await?;
+            match team_sharing.create_escrow(threshold, &identities).await {
+                Ok(summary) => {
+                    println!("🔐 Escrowed {} shared key(s) in {:.7}", summary.keys, summary.commit);
+                    println!(
+                        "   Any {} of {} Owners can recover them: {}",
+                        summary.threshold,
+                        summary.owners.len(),
+                        summary.owners.join(", ")
+                    );
+                    if !summary.skipped.is_empty() {
+                        println!("   ⚠️  {} key(s) your identities cannot unwrap were left out: {}", summary.skipped.len(), summary.skipped.join(", "));
+                    }
+                    println!("   Seal a new bundle after keys are rotated; share it with 'cargocrypt team push'");
+                }
+                Err(e) => {
+                    eprintln!("❌ Escrow failed: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        TeamCommands::EscrowShare { output, identity, gpg_identity } => {
+            let identities = team_identities(identity, gpg_identity)?;
+            let share = match team_sharing().await?.escrow_share(&identities).await {
+                Ok(share) => share,
+                Err(e) => {
+                    eprintln!("❌ {}", e);
+                    std::process::exit(1);
+                }
+            };
+            share.save(&output)?;
+            println!("🔑 Escrow share of {} written to {}", share.owner, output.display());
+            println!("   Hand it to whoever runs 'cargocrypt team recover' over a private channel, then delete it");
+        }
+        TeamCommands::Recover { shares, signing } => {
+            let shares = shares
+                .iter()
+                .map(|path| cargocrypt::git::EscrowShare::load(path))
+                .collect::<Result<Vec<_>, _>>()?;
+            let team_sharing = team_sharing_signed_by(signing).await?;
+            match team_sharing.recover_from_escrow(&shares).await {
+                Ok(result) => {
+                    println!("✅ Opened the escrow bundle sealed by {}", result.sealed_by);
+                    if result.members.is_empty() {
+                        println!("   Every member already holds the escrowed keys");
+                    } else {
+                        println!("   Wrapped {} key(s) for {}", result.keys_rewrapped, result.members.join(", "));
+                    }
+                    if !result.missing.is_empty() {
+                        println!("   ⚠️  {} escrowed key(s) are no longer in the team state: {}", result.missing.len(), result.missing.join(", "));
+                    }
+                    println!("   Share the change with 'cargocrypt team push', and rotate the keys departed members held");
+                }
+                Err(e) => {
+                    eprintln!("❌ Recovery failed: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        TeamCommands::Fetch { remote, signing } => {
+            let team_sharing = team_sharing_signed_by(signing).await?;
+            match team_sharing.fetch(&remote).await {
+                Ok(SyncOutcome::NoRemoteState) => println!("ℹ️  {} has no team state yet; share yours with 'cargocrypt team push'", remote),
+                Ok(SyncOutcome::UpToDate) => println!("✅ Team state already includes {}", remote),
+                Ok(SyncOutcome::FastForward) => println!("✅ Team state updated from {}", remote),
+                Ok(SyncOutcome::Merged { resolved }) => {
+                    println!("🔀 Merged the team state of {}", remote);
+                    for path in resolved {
+                        println!("   resolved {}", path);
+                    }
+                    println!("   Push the merged state with 'cargocrypt team push {}'", remote);
+                }
+                Err(e) => {
+                    eprintln!("❌ {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        TeamCommands::Push { remote } => {
+            let team_sharing = team_sharing().await?;
+            if let Err(e) = team_sharing.push(&remote).await {
+                eprintln!("❌ {}", e);
+                std::process::exit(1);
+            }
+            println!("✅ Team state pushed to {}", remote);
+        }
+    }
+
+    Ok(())
 }
 
-#[derive(Subcommand)]
-enum MonitorCommands {
-    /// Show current system metrics
-    Metrics,
-    /// Display real-time monitoring dashboard
-    Dashboard,
-    /// Start monitoring HTTP server
-    Server {
-        /// Port to listen on
-        #[arg(long, default_value = "3030")]
-        port: u16,
-        /// Host to bind to
-        #[arg(long, default_value = "127.0.0.1")]
-        host: String,
-    },
-    /// Show performance alerts
-    Alerts,
-    /// Export metrics to JSON
-    Export {
-        /// Output file path
-        #[arg(short, long)]
-        output: Option<PathBuf>,
-    },
-    /// Health check
-    Health,
+/// Team key sharing of the current repository
+/// The identity given with `--identity` or `--gpg-identity`, else every identity found locally
+fn team_identities(identity: Option<PathBuf>, gpg_identity: bool) -> CryptoResult<Vec<cargocrypt::git::TeamIdentity>> {
+    use cargocrypt::git::TeamIdentity;
+
+    match identity {
+        Some(path) => Ok(vec![TeamIdentity::from(cargocrypt::crypto::RecipientKeyPair::load(path)?)]),
+        None if gpg_identity => Ok(vec![TeamIdentity::Gpg]),
+        None => TeamIdentity::discover(&cargocrypt::git::utils::find_git_root()?),
+    }
 }
 
-#[tokio::main]
-async fn main() -> CryptoResult<()> {
-    let cli = Cli::parse();
+async fn team_sharing() -> CryptoResult<cargocrypt::git::TeamKeySharing> {
+    let git_integration = cargocrypt::git::GitIntegration::new().await?;
+    Ok(cargocrypt::git::TeamKeySharing::new(git_integration.repo(), git_integration.crypto())?)
+}
 
-    match cli.command {
-        Commands::Init { git } => {
-            CargoCrypt::init_project().await?;
-            println!("✅ CargoCrypt initialized successfully!");
-            
-            if git {
-                // Initialize git integration
-                use cargocrypt::git::GitIntegration;
-                
-                println!("🔧 Setting up Git integration...");
-                let mut git_integration = GitIntegration::new().await?;
-                git_integration.setup_repository().await?;
-                println!("✅ Git integration configured successfully!");
-            }
-        }
-        Commands::Encrypt { file, password_stdin } => {
-            let crypt = CargoCrypt::new().await?;
-            
-            let password = if password_stdin {
-                // Read password from stdin
-                use std::io::{self, BufRead};
-                let stdin = io::stdin();
-                let mut handle = stdin.lock();
-                let mut password = String::new();
-                handle.read_line(&mut password).map_err(|e| CargoCryptError::from(e))?;
-                password.trim().to_string()
+/// [`team_sharing`] signing changes with the project signing key or a GPG key
+///
+/// Without `--gpg-sign` or a project signing key, changes go unsigned, which
+/// the team only accepts while it has no Owner or Admin.
+async fn team_sharing_signed_by(signing: TeamSigning) -> CryptoResult<cargocrypt::git::TeamKeySharing> {
+    let team_sharing = team_sharing().await?;
+    if let Some(fingerprint) = signing.gpg_sign {
+        return Ok(team_sharing.with_signer(cargocrypt::git::TeamSigner::Gpg(fingerprint.to_uppercase())));
+    }
+    let crypt = CargoCrypt::new().await?;
+    if !crypt.signing_key_path().exists() {
+        return Ok(team_sharing);
+    }
+    let password = if signing.password_stdin {
+        read_password_stdin()?
+    } else {
+        prompt_password("Enter signing key password: ")?
+    };
+    Ok(team_sharing.with_signer(crypt.load_or_create_signing_key(&password).await?))
+}
+
+/// A public key given as hex, a file or an https:// URL
+async fn read_public_key(source: &str) -> CryptoResult<String> {
+    let content = if source.starts_with("https://") {
+        let client = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(30))
+            .user_agent(concat!("cargocrypt/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+        client.get(source).send().await?.error_for_status()?.text().await?
+    } else if source.starts_with("http://") {
+        return Err(CargoCryptError::Config {
+            message: format!("Public key URL {} is not HTTPS", source),
+            suggestion: Some("Fetch public keys over HTTPS so they cannot be swapped in transit".to_string()),
+        });
+    } else if std::path::Path::new(source).is_file() {
+        std::fs::read_to_string(source)?
+    } else {
+        source.to_string()
+    };
+    // Only the first token, so a key file may carry a trailing comment
+    Ok(content.split_whitespace().next().unwrap_or_default().to_string())
+}
+
+async fn handle_hook_command(cmd: HookCommands) -> CryptoResult<()> {
+    use cargocrypt::detection::{Policy, PolicyMode, PolicyStage, RuleConfig, ScanReport, SecretDetector};
+    use cargocrypt::git::hooks::{
+        bypassed_pre_commit, hooks_dir, log_hook_event, parse_push_updates, pushed_commits, record_verified_commit, run_chained_hook,
+        skipped_checks, SKIP_ENV,
+    };
+    use cargocrypt::git::repo::head_branch;
+    use cargocrypt::git::{BranchPolicy, FailLevel, GitCryptConfig, HookCheck, HookConfig, HookType};
+
+    match cmd {
+        HookCommands::Exec { hook, args } => {
+            // Git runs hooks at the top of the working tree with GIT_DIR set
+            let repo = if std::env::var_os("GIT_DIR").is_some() {
+                git2::Repository::open_from_env()?
             } else {
-                // Prompt for password with confirmation
-                let password = prompt_password("Enter password for encryption: ")?;
-                let password_confirm = prompt_password("Confirm password: ")?;
-                
-                if password != password_confirm {
-                    eprintln!("❌ Error: Passwords do not match");
-                    std::process::exit(1);
+                git2::Repository::discover(".")?
+            };
+            let root = repo.workdir().map(PathBuf::from).unwrap_or_else(|| PathBuf::from("."));
+            let (hook_type, stage) = match hook {
+                ExecHook::PreCommit => (HookType::PreCommit, PolicyStage::Commit),
+                ExecHook::PrePush => (HookType::PrePush, PolicyStage::Push),
+                ExecHook::PostCommit => (HookType::PostCommit, PolicyStage::Commit),
+            };
+
+            // The pushed refs come on stdin, which the chained hook needs too
+            let stdin = if hook_type == HookType::PrePush { read_stdin()? } else { Vec::new() };
+            if let Some(status) = run_chained_hook(&hooks_dir(&repo), &hook_type, &args, &stdin)? {
+                if !status.success() {
+                    std::process::exit(status.code().unwrap_or(1));
                 }
-                password
+            }
+
+            let config = HookConfig::load_project(&root)?;
+            if hook_type == HookType::PostCommit {
+                if config.post_commit.enabled && config.pre_commit.enabled && bypassed_pre_commit(&repo)? {
+                    let commit = repo.head()?.peel_to_commit()?.id();
+                    log_hook_event(&repo, "no-verify", &format!("commit {} skipped the pre-commit hook", commit))?;
+                    eprintln!("⚠️  Commit {:.7} skipped the pre-commit secret scan (--no-verify); recorded in the hook audit log", commit);
+                }
+                return Ok(());
+            }
+            // Pushes are judged by the branches they update, commits by the current branch
+            let updates = parse_push_updates(&stdin);
+            let git_config = GitCryptConfig::load_project(&root)?;
+            let policy = match hook_type {
+                HookType::PrePush => BranchPolicy::strictest(
+                    updates
+                        .iter()
+                        .filter_map(|update| update.remote_ref.strip_prefix("refs/heads/"))
+                        .filter_map(|branch| git_config.branch_policy(branch)),
+                ),
+                _ => head_branch(&repo).and_then(|branch| git_config.branch_policy(&branch).cloned()),
             };
-            
-            let encrypted_file = crypt.encrypt_file(&file, &password).await?;
-            println!("✅ File encrypted: {}", encrypted_file.display());
+            let settings = config.settings(&hook_type).cloned().unwrap_or_default().for_branch(policy.as_ref());
+            if !settings.enabled {
+                return Ok(());
+            }
+
+            let mut skip = skipped_checks();
+            if let Some(policy) = policy.as_ref().filter(|policy| policy.enforce_hooks && !skip.is_empty()) {
+                eprintln!("⚠️  {} is ignored: the branch policy '{}' enforces the hooks", SKIP_ENV, policy.pattern);
+                skip.clear();
+            }
+            let skipped: Vec<HookCheck> = settings.checks.iter().copied().filter(|check| skip.contains(check)).collect();
+            if !skipped.is_empty() {
+                let names = skipped.iter().map(|check| check.name()).collect::<Vec<_>>().join(",");
+                log_hook_event(&repo, "skip", &format!("{} skipped {}", hook_type.filename(), names))?;
+                eprintln!("⚠️  Skipping {} ({}); recorded in the hook audit log", names, SKIP_ENV);
+            }
+            let checks = settings.active_checks(&skipped);
+
+            // Secrets due for rotation only warn
+            if checks.contains(&HookCheck::Expiry) {
+                let _ = handle_report_command(ReportCommands::Expiring { within: None, all: false, quiet: true }).await;
+            }
+
+            // Plaintext where ciphertext belongs is never below a threshold
+            if checks.contains(&HookCheck::Encryption) {
+                let plaintext: Vec<String> = if hook_type == HookType::PreCommit {
+                    cargocrypt::git::plaintext_in_index(&root)?
+                        .iter()
+                        .map(|path| path.display().to_string())
+                        .collect()
+                } else {
+                    let commits = pushed_commits(&repo, &updates)?;
+                    cargocrypt::git::plaintext_in_commits(&repo, &commits)?
+                        .iter()
+                        .map(|found| format!("{} (commit {:.7})", found.path.display(), found.commit))
+                        .collect()
+                };
+                if !plaintext.is_empty() {
+                    let action = if hook_type == HookType::PreCommit { "staged" } else { "pushed" };
+                    eprintln!("❌ {} file(s) marked for encryption would be {} in plaintext:", plaintext.len(), action);
+                    for file in &plaintext {
+                        eprintln!("   {}", file);
+                    }
+                    eprintln!("   Run 'cargocrypt git configure-attributes' to set up the encryption filter, then 'git add --renormalize .'");
+                    if hook_type == HookType::PrePush {
+                        eprintln!("   and rewrite the commits above, since the plaintext is in their history");
+                    }
+                    if settings.fail_level == FailLevel::Warn {
+                        eprintln!("⚠️  The {} hook is warn-only: not blocking", hook_type.filename());
+                    } else {
+                        std::process::exit(exit_code::FINDINGS);
+                    }
+                }
+            }
+
+            if checks.contains(&HookCheck::Detect) {
+                let options = project_scan_options(&root, false)?;
+                let mut detector = SecretDetector::new();
+                detector.load_custom_rules(&RuleConfig::load_project(&root)?)?;
+                let findings = match hook_type {
+                    HookType::PreCommit => detector.scan_staged(&root, &options)?,
+                    _ => detector.scan_directory(&root, &options).await?,
+                };
+                let findings: Vec<_> = findings
+                    .into_iter()
+                    .filter(|finding| finding.should_report() || finding.is_suppressed())
+                    .collect();
+                let reported = findings.iter().filter(|finding| !finding.is_ignored).count();
+                if reported > 0 {
+                    print!("{}", ScanReport::from_findings(&root, &findings).to_table());
+
+                    let policy = Policy::load_project(&root)?;
+                    let stage_policy = policy.stage(stage);
+                    let blocking = match settings.fail_level {
+                        FailLevel::Policy => stage_policy.blocking(&findings).len(),
+                        FailLevel::Block => reported,
+                        FailLevel::Warn => 0,
+                    };
+                    if blocking > 0 && settings.fail_level == FailLevel::Block {
+                        eprintln!("❌ {} finding(s) and the {} hook blocks every finding, {} stopped", blocking, hook_type.filename(), stage);
+                        eprintln!("To encrypt sensitive files: 'cargocrypt encrypt <file>'");
+                        std::process::exit(exit_code::FINDINGS);
+                    } else if blocking > 0 {
+                        eprintln!("❌ {} finding(s) blocked by the {} policy, {} stopped", blocking, stage, stage);
+                        eprintln!("To encrypt sensitive files: 'cargocrypt encrypt <file>'");
+                        eprintln!("Or adjust [{}] in .cargocrypt/policy.toml to only warn", stage);
+                        std::process::exit(exit_code::FINDINGS);
+                    } else if settings.fail_level == FailLevel::Warn {
+                        eprintln!("⚠️  The {} hook is warn-only: {} finding(s) not blocking", hook_type.filename(), reported);
+                    } else if stage_policy.mode == PolicyMode::Warn {
+                        eprintln!("⚠️  The {} policy is warn-only: {} finding(s) not blocking", stage, reported);
+                    } else {
+                        eprintln!("⚠️  {} finding(s) below the {} policy threshold, not blocking", reported, stage);
+                    }
+                }
+            }
+
+            // Lets the post-commit hook tell this commit from one made with --no-verify
+            if hook_type == HookType::PreCommit {
+                record_verified_commit(&repo)?;
+            }
         }
-        Commands::Decrypt { file, password_stdin } => {
-            let crypt = CargoCrypt::new().await?;
-            
-            let password = if password_stdin {
-                // Read password from stdin
-                use std::io::{self, BufRead};
-                let stdin = io::stdin();
-                let mut handle = stdin.lock();
-                let mut password = String::new();
-                handle.read_line(&mut password).map_err(|e| CargoCryptError::from(e))?;
-                password.trim().to_string()
+    }
+
+    Ok(())
+}
+
+async fn handle_scan_command(cmd: ScanCommands) -> CryptoResult<()> {
+    use cargocrypt::detection::{DetectionConfig, Feedback, Label, RuleConfig, ScanCache, ScanOptions, SecretDetector};
+
+    match cmd {
+        ScanCommands::Feedback { id, true_positive, false_positive: _, path } => {
+            let project_root = cargocrypt::utils::find_project_root().unwrap_or_else(|_| PathBuf::from("."));
+            // Without feedback applied, so findings already labeled can be relabeled
+            let options = ScanOptions {
+                detection_config: DetectionConfig::load_project(&project_root)?,
+                cache_path: Some(ScanCache::project_path(&project_root)),
+                include_low_confidence: true,
+                ..ScanOptions::default()
+            };
+            let mut detector = SecretDetector::new();
+            detector.load_custom_rules(&RuleConfig::load_project(&project_root)?)?;
+            let findings = if path.is_file() {
+                detector.scan_file(&path, &options).await?
             } else {
-                // Prompt for password
-                prompt_password("Enter password for decryption: ")?
+                detector.scan_directory(&path, &options).await?
             };
-            
-            let decrypted_file = crypt.decrypt_file(&file, &password).await?;
-            println!("✅ File decrypted: {}", decrypted_file.display());
-        }
-        Commands::Config => {
-            let crypt = CargoCrypt::new().await?;
-            let config = crypt.config().await;
-            println!("📋 Current configuration:");
-            println!("  Performance Profile: {:?}", config.performance_profile);
-            println!("  Key derivation: Argon2id");
-            println!("  Memory cost: {} KiB", config.key_params.memory_cost);
-            println!("  Time cost: {} iterations", config.key_params.time_cost);
-            println!("  Parallelism: {}", config.key_params.parallelism);
-            println!("  Auto-backup: {}", config.file_ops.backup_originals);
-            println!("  Fail-secure: {}", config.security.fail_secure);
+            let Some(finding) = findings.iter().find(|finding| finding.id() == id) else {
+                eprintln!("❌ No finding with id {} in {}", id, path.display());
+                eprintln!("   Run `cargocrypt scan {}` to list the ids of current findings", path.display());
+                std::process::exit(1);
+            };
+
+            let label = if true_positive { Label::TruePositive } else { Label::FalsePositive };
+            let feedback_path = Feedback::project_path(&project_root);
+            let mut feedback = Feedback::load(&feedback_path)?;
+            feedback.record(finding, label);
+            feedback.save(&feedback_path)?;
+
+            println!(
+                "✅ Labeled {} ({} in {}:{}) as a {}",
+                id,
+                finding.secret.secret_type,
+                finding.file_path.display(),
+                finding.secret.line_number,
+                label
+            );
+            println!(
+                "📊 Rule {} now reports at {:.0}% of its confidence",
+                finding.rule_id(),
+                feedback.rule_weight(&finding.rule_id()) * 100.0
+            );
+            println!("💡 Commit {} to share the labels", feedback_path.display());
         }
-        Commands::Tui => {
-            println!("Starting TUI...");
-            let crypt = Arc::new(CargoCrypt::new().await?);
-            cargocrypt::tui_simple::run_simple_tui(crypt).await?;
+    }
+    Ok(())
+}
+
+fn handle_findings_command(cmd: FindingsCommands) -> CryptoResult<()> {
+    use cargocrypt::detection::{FindingsDb, TriageState};
+
+    let project_root = cargocrypt::utils::find_project_root().unwrap_or_else(|_| PathBuf::from("."));
+    let db_path = FindingsDb::project_path(&project_root);
+    let mut db = FindingsDb::load(&db_path)?;
+
+    match cmd {
+        FindingsCommands::List { state, all } => {
+            let listed: Vec<_> = db
+                .findings
+                .iter()
+                .filter(|(_, tracked)| match state {
+                    Some(state) => tracked.state == state,
+                    None => all || tracked.state.is_open(),
+                })
+                .collect();
+            if listed.is_empty() {
+                println!("✅ No findings to triage");
+                return Ok(());
+            }
+            println!("{:<12} {:<13} {:<9} {:<24} {:<40} PREVIEW", "ID", "STATE", "SEVERITY", "RULE", "LOCATION");
+            for (id, tracked) in &listed {
+                let severity = tracked.severity.map(|severity| severity.to_string()).unwrap_or_else(|| "-".to_string());
+                let location = format!("{}:{}", tracked.file, tracked.line);
+                println!(
+                    "{:<12} {:<13} {:<9} {:<24} {:<40} {}",
+                    id, tracked.state, severity, tracked.rule_id, location, tracked.preview
+                );
+            }
+            println!("📋 {} finding(s)", listed.len());
         }
-        Commands::Git(git_cmd) => {
-            handle_git_command(git_cmd).await?;
+        FindingsCommands::Ack { id, accept_risk, note } => {
+            let state = if accept_risk { TriageState::AcceptedRisk } else { TriageState::Triaged };
+            let tracked = db.set_state(&id, state, note)?;
+            println!("✅ {}:{} is now {}", tracked.file, tracked.line, tracked.state);
+            db.save(&db_path)?;
         }
-        Commands::Monitor(monitor_cmd) => {
-            handle_monitor_command(monitor_cmd).await?;
+        FindingsCommands::Resolve { id, note } => {
+            let tracked = db.set_state(&id, TriageState::Fixed, note)?;
+            println!("✅ {}:{} is now {}", tracked.file, tracked.line, tracked.state);
+            println!("   A later scan reopens it if the secret is still there");
+            db.save(&db_path)?;
         }
     }
 
     Ok(())
 }
 
-async fn handle_git_command(cmd: GitCommands) -> CryptoResult<()> {
-    use cargocrypt::git::{GitIntegration, GitHooks, GitAttributes, GitIgnoreManager};
-    
+async fn handle_rules_command(cmd: RulesCommands) -> CryptoResult<()> {
+    use cargocrypt::detection::{PackSource, RuleConfig, RulePacks};
+
+    let project_root = cargocrypt::utils::find_project_root().unwrap_or_else(|_| PathBuf::from("."));
+
     match cmd {
-        GitCommands::InstallHooks => {
-            let git_integration = GitIntegration::new().await?;
-            let hooks = GitHooks::new(git_integration.repo())?;
-            
-            println!("🔧 Installing Git hooks...");
-            
-            // Install secret detection hook
-            hooks.install_secret_detection_hook().await?;
-            
-            // Install encryption validation hook  
-            hooks.install_encryption_validation_hook().await?;
-            
-            println!("✅ Git hooks installed successfully!");
-            println!("   - Pre-commit: Secret detection");
-            println!("   - Pre-push: Encryption validation");
+        RulesCommands::Lint { file } => {
+            let path = file.unwrap_or_else(|| RuleConfig::project_path(&project_root));
+            if !path.exists() {
+                eprintln!("❌ No rules file at {}", path.display());
+                std::process::exit(exit_code::CONFIG);
+            }
+
+            let config = RuleConfig::read(&path)?;
+            let issues = config.lint();
+            if issues.is_empty() {
+                println!("✅ {} rule(s) in {} are valid", config.rules.len(), path.display());
+            } else {
+                eprintln!("❌ {} problem(s) in {}:", issues.len(), path.display());
+                for issue in &issues {
+                    eprintln!("   - {}", issue);
+                }
+                std::process::exit(exit_code::CONFIG);
+            }
         }
-        GitCommands::UninstallHooks => {
-            let git_integration = GitIntegration::new().await?;
-            let hooks = GitHooks::new(git_integration.repo())?;
-            
-            println!("🔧 Uninstalling Git hooks...");
-            hooks.uninstall_hooks().await?;
-            println!("✅ Git hooks removed successfully!");
+        RulesCommands::Add { source, key, git_ref, path } => {
+            let source = PackSource::new(source, git_ref, path);
+            println!("📥 Fetching rule pack from {}", source);
+            let fetched = source.fetch().await?;
+
+            let mut packs = RulePacks::load_project(&project_root)?;
+            let installed = packs.install(&project_root, source, &key, &fetched)?.clone();
+            packs.save_project(&project_root)?;
+            println!("✅ Installed rule pack {} {} (signature verified)", installed.name, installed.version);
+            println!("💡 Commit {} and {}", RulePacks::project_path(&project_root).display(), RulePacks::pack_dir(&project_root).display());
         }
-        GitCommands::ConfigureAttributes => {
-            let git_integration = GitIntegration::new().await?;
-            let mut attributes = GitAttributes::new(git_integration.repo())?;
-            
-            println!("🔧 Configuring Git attributes...");
-            
-            // Add default CargoCrypt patterns
-            attributes.add_cargocrypt_patterns().await?;
-            
-            // Configure filters
-            attributes.configure_filters(git_integration.config()).await?;
-            
-            // Save attributes
-            attributes.save().await?;
-            
-            println!("✅ Git attributes configured successfully!");
-            println!("   Patterns added for automatic encryption:");
-            for pattern in attributes.get_patterns() {
-                println!("   - {}", pattern.pattern);
+        RulesCommands::Update { name } => {
+            let mut packs = RulePacks::load_project(&project_root)?;
+            let selected: Vec<_> = packs
+                .packs
+                .iter()
+                .filter(|pack| name.is_none() || name.as_ref() == Some(&pack.name))
+                .cloned()
+                .collect();
+            if selected.is_empty() {
+                match name {
+                    Some(name) => eprintln!("❌ No rule pack named {} is installed", name),
+                    None => eprintln!("❌ No rule packs installed; add one with `cargocrypt rules add`"),
+                }
+                std::process::exit(1);
+            }
+
+            for pack in selected {
+                let fetched = pack.source.fetch().await?;
+                if fetched.sha256() == pack.sha256 {
+                    println!("✅ {} {} is up to date", pack.name, pack.version);
+                    continue;
+                }
+                // Newer versions must be signed with the key the pack was installed with
+                let installed = packs.install(&project_root, pack.source.clone(), &pack.public_key, &fetched)?;
+                println!("⬆️  Updated {} {} -> {}", pack.name, pack.version, installed.version);
             }
+            packs.save_project(&project_root)?;
         }
-        GitCommands::FilterClean { .. } => {
-            // This is called by git during staging
-            // Read from stdin, encrypt, write to stdout
-            use std::io::{self, Read, Write};
-            use cargocrypt::CargoCrypt;
-            
-            let mut input = Vec::new();
-            io::stdin().read_to_end(&mut input)
-                .map_err(|e| cargocrypt::error::CargoCryptError::from(e))?;
-            
-            // Get password from git config or environment
-            let password = std::env::var("CARGOCRYPT_PASSWORD")
-                .unwrap_or_else(|_| {
-                    // Try to read from git config
-                    std::process::Command::new("git")
-                        .args(&["config", "--get", "cargocrypt.password"])
-                        .output()
-                        .ok()
-                        .and_then(|output| {
-                            if output.status.success() {
-                                String::from_utf8(output.stdout).ok()
-                                    .map(|s| s.trim().to_string())
-                            } else {
-                                None
-                            }
-                        })
-                        .unwrap_or_else(|| "default-password".to_string())
-                });
-            
-            let crypt = CargoCrypt::new().await?;
-            let encrypted = crypt.crypto().encrypt_data(&input, &password).await?;
-            
-            // Output encrypted data
-            let encrypted_bytes = bincode::serialize(&encrypted)
-                .map_err(|e| cargocrypt::error::CargoCryptError::Serialization { 
-                    message: format!("Failed to serialize: {}", e), 
-                    source: Box::new(e) 
-                })?;
-            io::stdout().write_all(&encrypted_bytes)
-                .map_err(|e| cargocrypt::error::CargoCryptError::from(e))?;
-        }
-        GitCommands::FilterSmudge { .. } => {
-            // This is called by git during checkout
-            // Read from stdin, decrypt, write to stdout
-            use std::io::{self, Read, Write};
-            use cargocrypt::{CargoCrypt, crypto::EncryptedSecret};
-            
-            let mut input = Vec::new();
-            io::stdin().read_to_end(&mut input)
-                .map_err(|e| cargocrypt::error::CargoCryptError::from(e))?;
-            
-            // Get password from git config or environment
-            let password = std::env::var("CARGOCRYPT_PASSWORD")
-                .unwrap_or_else(|_| {
-                    // Try to read from git config
-                    std::process::Command::new("git")
-                        .args(&["config", "--get", "cargocrypt.password"])
-                        .output()
-                        .ok()
-                        .and_then(|output| {
-                            if output.status.success() {
-                                String::from_utf8(output.stdout).ok()
-                                    .map(|s| s.trim().to_string())
-                            } else {
-                                None
-                            }
-                        })
-                        .unwrap_or_else(|| "default-password".to_string())
-                });
-            
-            let crypt = CargoCrypt::new().await?;
-            
-            // Try to deserialize and decrypt
-            match bincode::deserialize::<EncryptedSecret>(&input) {
-                Ok(encrypted) => {
-                    match crypt.crypto().decrypt_data(&encrypted, &password) {
-                        Ok(decrypted) => {
-                            io::stdout().write_all(&decrypted)
-                                .map_err(|e| cargocrypt::error::CargoCryptError::from(e))?;
-                        }
-                        Err(_) => {
-                            // If decryption fails, output original (might not be encrypted)
-                            io::stdout().write_all(&input)
-                                .map_err(|e| cargocrypt::error::CargoCryptError::from(e))?;
-                        }
-                    }
+        RulesCommands::Remove { name } => {
+            let mut packs = RulePacks::load_project(&project_root)?;
+            match packs.remove(&project_root, &name)? {
+                Some(pack) => {
+                    packs.save_project(&project_root)?;
+                    println!("🗑️  Removed rule pack {} {}", pack.name, pack.version);
                 }
-                Err(_) => {
-                    // If deserialization fails, output original (not encrypted)
-                    io::stdout().write_all(&input)
-                        .map_err(|e| cargocrypt::error::CargoCryptError::from(e))?;
+                None => {
+                    eprintln!("❌ No rule pack named {} is installed", name);
+                    std::process::exit(1);
                 }
             }
         }
-        GitCommands::UpdateIgnore => {
-            let git_integration = GitIntegration::new().await?;
-            let mut ignore_manager = GitIgnoreManager::new(git_integration.repo())?;
-            
-            println!("🔧 Updating .gitignore...");
-            
-            // Add CargoCrypt patterns
-            ignore_manager.add_cargocrypt_patterns().await?;
-            
-            // Save the updated .gitignore
-            ignore_manager.save().await?;
-            
-            println!("✅ .gitignore updated successfully!");
-            println!("   Added patterns:");
-            for pattern in ignore_manager.get_ignore_patterns() {
-                println!("   - {}", pattern);
+    }
+
+    Ok(())
+}
+
+async fn handle_report_command(cmd: ReportCommands) -> CryptoResult<()> {
+    use cargocrypt::detection::{DetectionConfig, RuleConfig, SecretDetector, ScanOptions, SecretsHeatmap};
+
+    match cmd {
+        ReportCommands::Heatmap { path, format, output } => {
+            let project_root = cargocrypt::utils::find_project_root().unwrap_or_else(|_| PathBuf::from("."));
+            let mut options = ScanOptions {
+                detection_config: DetectionConfig::load_project(&project_root)?,
+                ..ScanOptions::default()
+            };
+            ResourceLimitsConfig::default()
+                .with_env_overrides()
+                .apply_to_scan(&mut options.scan_config);
+
+            let mut detector = SecretDetector::new();
+            detector.load_custom_rules(&RuleConfig::load_project(&project_root)?)?;
+            let findings = detector.scan_directory(&path, &options).await?;
+            let heatmap = SecretsHeatmap::from_findings(&path, &findings);
+
+            let (content, default_output) = match format {
+                HeatmapFormat::Html => (heatmap.to_html()?, Some(PathBuf::from("secrets-heatmap.html"))),
+                HeatmapFormat::Json => (heatmap.to_json()?, None),
+            };
+
+            match output.or(default_output) {
+                Some(file_path) => {
+                    tokio::fs::write(&file_path, &content).await?;
+                    println!("✅ Heatmap written to: {}", file_path.display());
+                    println!("   {} findings", heatmap.total_findings());
+                    for node in heatmap.hotspots(5) {
+                        println!("   {:>5.1}  {} ({} findings)", node.risk_score, node.path.display(), node.findings);
+                    }
+                }
+                None => println!("{}", content),
+            }
+        }
+        ReportCommands::Expiring { within, all, quiet } => {
+            let crypt = CargoCrypt::new().await?;
+            if let Some(days) = within {
+                crypt.update_config(|config| config.expiry.warning_days = days).await;
+            }
+
+            let items: Vec<ExpiringItem> = crypt
+                .expiring_items()
+                .await?
+                .into_iter()
+                .filter(|item| all || item.status.needs_attention())
+                .collect();
+
+            if json_output() {
+                print_json(&items)?;
+            } else if !items.is_empty() {
+                let title = if all { "Secrets with an expiry date" } else { "Secrets due for rotation" };
+                print_expiring_items(title, &items);
+            } else if !quiet && !cargocrypt::progress::is_quiet() {
+                println!("✅ No secrets need rotation");
             }
         }
     }
-    
+
     Ok(())
 }
 
+/// Print expiring items, most urgent first
+fn print_expiring_items(title: &str, items: &[ExpiringItem]) {
+    let now = cargocrypt::utils::unix_now();
+
+    println!("📅 {}:", title);
+    for item in items {
+        let icon = match item.status {
+            ExpiryStatus::Expired => "🔴",
+            ExpiryStatus::ExpiringSoon => "⚠️",
+            ExpiryStatus::Valid => "🟢",
+        };
+        let days = item.days_remaining(now);
+        let when = match (days < 0, item.rotation) {
+            (true, false) => format!("expired {} days ago", -days),
+            (true, true) => format!("rotation overdue by {} days", -days),
+            (false, false) => format!("{} days left", days),
+            (false, true) => format!("rotate within {} days", days),
+        };
+        println!("   {} {}  {} ({})", icon, item.expiry_date(), item.source, when);
+        if let Some(description) = &item.description {
+            println!("      {}", description);
+        }
+    }
+}
+
 async fn handle_monitor_command(cmd: MonitorCommands) -> CryptoResult<()> {
     use cargocrypt::monitoring::{MonitoringManager, MonitoringConfig, server::MonitoringServer};
     use std::net::SocketAddr;
@@ -366,11 +5818,14 @@ async fn handle_monitor_command(cmd: MonitorCommands) -> CryptoResult<()> {
     
     match cmd {
         MonitorCommands::Metrics => {
+            let metrics = monitoring.get_metrics().await;
+            if json_output() {
+                return print_json(&metrics);
+            }
+            
             println!("📊 System Metrics");
             println!("================");
             
-            let metrics = monitoring.get_metrics().await;
-            
             // Display crypto operations
             println!("\n🔐 Crypto Operations:");
             for (op_type, summary) in &metrics.crypto_operations {
@@ -400,7 +5855,11 @@ async fn handle_monitor_command(cmd: MonitorCommands) -> CryptoResult<()> {
             
             // Create and run monitoring dashboard
             use cargocrypt::tui::monitoring::MonitoringDashboard;
-            let mut dashboard = MonitoringDashboard::new(monitoring);
+            let expiring = match CargoCrypt::new().await {
+                Ok(crypt) => crypt.expiring_items().await.unwrap_or_default(),
+                Err(_) => Vec::new(),
+            };
+            let mut dashboard = MonitoringDashboard::new(monitoring).with_expiring_items(expiring);
             dashboard.run().await.map_err(|e| CargoCryptError::from(std::io::Error::new(std::io::ErrorKind::Other, e.to_string())))?
         }
         
@@ -424,11 +5883,14 @@ async fn handle_monitor_command(cmd: MonitorCommands) -> CryptoResult<()> {
         }
         
         MonitorCommands::Alerts => {
+            let alerts = monitoring.check_performance_alerts().await;
+            if json_output() {
+                return print_json(&alerts);
+            }
+            
             println!("⚠️  Performance Alerts");
             println!("=====================");
             
-            let alerts = monitoring.check_performance_alerts().await;
-            
             if alerts.is_empty() {
                 println!("✅ No active alerts");
             } else {
@@ -454,7 +5916,11 @@ async fn handle_monitor_command(cmd: MonitorCommands) -> CryptoResult<()> {
             match output {
                 Some(file_path) => {
                     tokio::fs::write(&file_path, &json).await?;
-                    println!("✅ Metrics exported to: {}", file_path.display());
+                    if json_output() {
+                        print_json(&serde_json::json!({ "output": file_path }))?;
+                    } else {
+                        println!("✅ Metrics exported to: {}", file_path.display());
+                    }
                 }
                 None => {
                     println!("{}", json);
@@ -463,11 +5929,14 @@ async fn handle_monitor_command(cmd: MonitorCommands) -> CryptoResult<()> {
         }
         
         MonitorCommands::Health => {
+            let health = monitoring.health_check().await;
+            if json_output() {
+                return print_json(&health);
+            }
+            
             println!("🏥 System Health Check");
             println!("=====================");
             
-            let health = monitoring.health_check().await;
-            
             let status_emoji = match health.status {
                 cargocrypt::monitoring::HealthStatus::Healthy => "✅",
                 cargocrypt::monitoring::HealthStatus::Degraded => "⚠️",
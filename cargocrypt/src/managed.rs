@@ -0,0 +1,332 @@
+//! The registry of files a project keeps encrypted
+//!
+//! Every file encrypted or decrypted through [`CargoCrypt`](crate::CargoCrypt)
+//! inside the project is recorded in `.cargocrypt/files.toml` with its
+//! encrypted copy, the kind of key it needs and its vault, so
+//! `cargocrypt encrypt --all-managed`, `cargocrypt decrypt --all-managed` and
+//! `cargocrypt status` work on the known set instead of explicit paths.
+//! Files outside the project root are not recorded. The registry holds no
+//! keys and is meant to be committed.
+//!
+//! ```toml
+//! [[file]]
+//! path = "config/secrets.toml"
+//! encrypted = "config/secrets.toml.enc"
+//! key = "password"
+//! encrypted_at = 1760520000
+//! ```
+
+use crate::crypto::{KeySource, Keyfile, SecureString};
+use crate::error::{CargoCryptError, CryptoResult};
+use crate::lock::FileLock;
+use crate::utils::unix_now;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Registry file, inside `.cargocrypt/`
+pub const MANAGED_FILE: &str = "files.toml";
+
+/// A file the project keeps encrypted
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManagedFile {
+    /// The plaintext, relative to the project root
+    pub path: PathBuf,
+    /// The encrypted copy, relative to the project root
+    pub encrypted: PathBuf,
+    /// Key material the file is encrypted with
+    #[serde(default)]
+    pub key: KeySource,
+    /// Vault whose key encrypts the file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vault: Option<String>,
+    /// When the file was last encrypted (Unix timestamp)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encrypted_at: Option<u64>,
+    /// When the file was last decrypted (Unix timestamp)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decrypted_at: Option<u64>,
+}
+
+/// Where a managed file stands, from the files in the working tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ManagedState {
+    /// Only the encrypted copy exists
+    Encrypted,
+    /// Both exist and the plaintext is not newer than the encryption or the last decryption
+    Decrypted,
+    /// Both exist and the plaintext changed after it was encrypted
+    Modified,
+    /// Only the plaintext exists
+    Unencrypted,
+    /// Neither exists
+    Missing,
+}
+
+impl fmt::Display for ManagedState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Self::Encrypted => "encrypted",
+            Self::Decrypted => "decrypted",
+            Self::Modified => "modified",
+            Self::Unencrypted => "not encrypted",
+            Self::Missing => "missing",
+        })
+    }
+}
+
+impl ManagedState {
+    /// Whether `encrypt --all-managed` encrypts a file in this state
+    pub fn needs_encrypting(&self, in_place: bool) -> bool {
+        matches!(self, Self::Modified | Self::Unencrypted) || (in_place && *self == Self::Decrypted)
+    }
+
+    /// Whether `decrypt --all-managed` decrypts a file in this state
+    pub fn can_decrypt(&self) -> bool {
+        matches!(self, Self::Encrypted | Self::Decrypted)
+    }
+}
+
+impl ManagedFile {
+    /// State of the file in the project at `project_root`
+    pub fn state(&self, project_root: &Path) -> ManagedState {
+        let modified = |path: &Path| std::fs::metadata(project_root.join(path)).and_then(|metadata| metadata.modified()).ok();
+        match (modified(&self.path), modified(&self.encrypted)) {
+            (Some(plaintext), Some(encrypted)) if plaintext > encrypted && !self.written_by_decrypt(plaintext) => {
+                ManagedState::Modified
+            }
+            (Some(_), Some(_)) => ManagedState::Decrypted,
+            (None, Some(_)) => ManagedState::Encrypted,
+            (Some(_), None) => ManagedState::Unencrypted,
+            (None, None) => ManagedState::Missing,
+        }
+    }
+
+    /// Whether a plaintext last modified at `modified` was written by the last decryption
+    fn written_by_decrypt(&self, modified: std::time::SystemTime) -> bool {
+        let modified = modified.duration_since(std::time::UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or(0);
+        self.decrypted_at.is_some_and(|decrypted_at| modified <= decrypted_at)
+    }
+}
+
+/// The files recorded in `.cargocrypt/files.toml`, sorted by path
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManagedFiles {
+    /// The recorded files
+    #[serde(rename = "file", default)]
+    pub files: Vec<ManagedFile>,
+}
+
+/// What happened to a managed file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManagedEvent {
+    Encrypted,
+    Decrypted,
+}
+
+impl ManagedFiles {
+    /// Path of the registry of the project at `project_root`
+    pub fn project_path<P: AsRef<Path>>(project_root: P) -> PathBuf {
+        project_root.as_ref().join(".cargocrypt").join(MANAGED_FILE)
+    }
+
+    /// Load the registry of the project at `project_root`; empty if there is none
+    pub fn load<P: AsRef<Path>>(project_root: P) -> CryptoResult<Self> {
+        let path = Self::project_path(project_root);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        toml::from_str(&std::fs::read_to_string(&path)?).map_err(|e| CargoCryptError::Config {
+            message: format!("Invalid {}: {}", path.display(), e),
+            suggestion: Some("Fix or delete the file; it is rebuilt as files are encrypted".to_string()),
+        })
+    }
+
+    /// Save the registry of the project at `project_root`
+    pub fn save<P: AsRef<Path>>(&self, project_root: P) -> CryptoResult<()> {
+        let path = Self::project_path(project_root);
+        let content = toml::to_string_pretty(self).map_err(|e| CargoCryptError::Serialization {
+            message: format!("Failed to serialize managed files: {}", e),
+            source: Box::new(e),
+        })?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+
+    /// Change the registry of the project at `project_root` under the state lock
+    pub async fn update<F: FnOnce(&mut Self)>(project_root: &Path, update: F) -> CryptoResult<()> {
+        let _lock = FileLock::state(project_root).await?;
+        let mut files = Self::load(project_root)?;
+        let before = files.clone();
+        update(&mut files);
+        if files != before {
+            files.save(project_root)?;
+        }
+        Ok(())
+    }
+
+    /// The entry of the plaintext `path`, relative to the project root
+    pub fn get<P: AsRef<Path>>(&self, path: P) -> Option<&ManagedFile> {
+        self.files.iter().find(|file| file.path == path.as_ref())
+    }
+
+    /// Record that `plaintext` was encrypted to or decrypted from `encrypted`
+    ///
+    /// Paths may be relative to the working directory; files outside
+    /// `project_root` are not recorded. Returns whether the file was.
+    pub fn record(
+        &mut self,
+        project_root: &Path,
+        plaintext: &Path,
+        encrypted: &Path,
+        key: KeySource,
+        vault: Option<&str>,
+        event: ManagedEvent,
+    ) -> bool {
+        let (Some(path), Some(encrypted)) = (relative(project_root, plaintext), relative(project_root, encrypted)) else {
+            return false;
+        };
+        if path.starts_with(".cargocrypt") {
+            return false;
+        }
+
+        let index = match self.files.binary_search_by(|file| file.path.cmp(&path)) {
+            Ok(index) => index,
+            Err(index) => {
+                self.files.insert(index, ManagedFile {
+                    path,
+                    encrypted: encrypted.clone(),
+                    key,
+                    vault: None,
+                    encrypted_at: None,
+                    decrypted_at: None,
+                });
+                index
+            }
+        };
+        let file = &mut self.files[index];
+        file.encrypted = encrypted;
+        file.key = key;
+        file.vault = vault.map(str::to_string);
+        match event {
+            ManagedEvent::Encrypted => file.encrypted_at = Some(unix_now()),
+            ManagedEvent::Decrypted => file.decrypted_at = Some(unix_now()),
+        }
+        true
+    }
+
+    /// Remove the entry of the plaintext `path`; returns whether there was one
+    pub fn remove<P: AsRef<Path>>(&mut self, path: P) -> bool {
+        let before = self.files.len();
+        self.files.retain(|file| file.path != path.as_ref());
+        self.files.len() != before
+    }
+
+    /// Vaults whose keys the managed files need, sorted by name
+    pub fn vaults(&self) -> Vec<&str> {
+        let mut vaults: Vec<&str> = self.files.iter().filter_map(|file| file.vault.as_deref()).collect();
+        vaults.sort_unstable();
+        vaults.dedup();
+        vaults
+    }
+}
+
+/// Keys for [`CargoCrypt::encrypt_managed`](crate::CargoCrypt::encrypt_managed) and
+/// [`CargoCrypt::decrypt_managed`](crate::CargoCrypt::decrypt_managed)
+///
+/// Files whose key is missing are skipped.
+#[derive(Default)]
+pub struct ManagedKeys {
+    /// Password of the files encrypted with one, alone or mixed with the keyfile
+    pub password: Option<SecureString>,
+    /// Keyfile of the files encrypted with one
+    pub keyfile: Option<Keyfile>,
+    /// Password of each vault
+    pub vaults: BTreeMap<String, SecureString>,
+}
+
+/// What happened to one file in [`CargoCrypt::encrypt_managed`](crate::CargoCrypt::encrypt_managed)
+/// or [`CargoCrypt::decrypt_managed`](crate::CargoCrypt::decrypt_managed)
+#[derive(Debug)]
+pub enum ManagedOutcome {
+    /// Written to the given path
+    Written(PathBuf),
+    /// Left alone for the given reason
+    Skipped(String),
+    /// Could not be encrypted or decrypted; left untouched
+    Failed(CargoCryptError),
+}
+
+/// Per-file results of an operation on the managed files
+#[derive(Debug, Default)]
+pub struct ManagedReport {
+    /// Each managed file (plaintext path, relative to the project root) with its outcome
+    pub files: Vec<(PathBuf, ManagedOutcome)>,
+}
+
+impl ManagedReport {
+    /// Whether no file failed
+    pub fn is_success(&self) -> bool {
+        !self.files.iter().any(|(_, outcome)| matches!(outcome, ManagedOutcome::Failed(_)))
+    }
+
+    /// Number of files written
+    pub fn written(&self) -> usize {
+        self.files.iter().filter(|(_, outcome)| matches!(outcome, ManagedOutcome::Written(_))).count()
+    }
+}
+
+/// `path` relative to `project_root`, if it is inside
+fn relative(project_root: &Path, path: &Path) -> Option<PathBuf> {
+    let path = std::path::absolute(path).ok()?;
+    let project_root = std::path::absolute(project_root).ok()?;
+    path.strip_prefix(project_root).ok().map(Path::to_path_buf)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_record_and_state() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let mut files = ManagedFiles::default();
+
+        assert!(files.record(root, &root.join("b.env"), &root.join("b.env.enc"), KeySource::Password, None, ManagedEvent::Encrypted));
+        assert!(files.record(root, &root.join("a.key"), &root.join("a.key.enc"), KeySource::Keyfile, Some("ci"), ManagedEvent::Encrypted));
+        assert!(!files.record(root, Path::new("/elsewhere/x"), Path::new("/elsewhere/x.enc"), KeySource::Password, None, ManagedEvent::Encrypted));
+        assert!(files.record(root, &root.join("b.env"), &root.join("b.env.enc"), KeySource::Password, None, ManagedEvent::Decrypted));
+
+        let paths: Vec<_> = files.files.iter().map(|file| file.path.clone()).collect();
+        assert_eq!(paths, [PathBuf::from("a.key"), PathBuf::from("b.env")]);
+        let b = files.get("b.env").unwrap();
+        assert!(b.encrypted_at.is_some() && b.decrypted_at.is_some());
+        assert_eq!(files.vaults(), ["ci"]);
+
+        assert_eq!(b.state(root), ManagedState::Missing);
+        std::fs::write(root.join("b.env.enc"), "encrypted").unwrap();
+        assert_eq!(b.state(root), ManagedState::Encrypted);
+        std::fs::write(root.join("b.env"), "KEY=value").unwrap();
+        let encrypted = std::fs::File::options().write(true).open(root.join("b.env.enc")).unwrap();
+        encrypted.set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(60)).unwrap();
+        assert_eq!(b.state(root), ManagedState::Decrypted);
+        encrypted.set_modified(std::time::SystemTime::UNIX_EPOCH).unwrap();
+        assert_eq!(b.state(root), ManagedState::Decrypted);
+        let plaintext = std::fs::File::options().write(true).open(root.join("b.env")).unwrap();
+        plaintext.set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(60)).unwrap();
+        assert_eq!(b.state(root), ManagedState::Modified);
+
+        files.save(root).unwrap();
+        assert_eq!(ManagedFiles::load(root).unwrap(), files);
+        assert!(files.remove("a.key"));
+        assert!(!files.remove("a.key"));
+    }
+}
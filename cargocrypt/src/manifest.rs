@@ -0,0 +1,480 @@
+//! Signed integrity manifest for a project's encrypted files
+//!
+//! `cargocrypt verify --all` walks the project and checks every encrypted
+//! file without decrypting it: the format must parse and the ciphertext must
+//! be long enough to carry its Poly1305 tag. The files are then compared
+//! against `.cargocrypt/manifest.json`, which records a SHA-256 digest of
+//! each file and of its header and is signed with the project's Ed25519 key,
+//! so any edit, removal or unexpected addition is reported. When a password
+//! is available, authentication tags are checked as well by decrypting in
+//! memory.
+//!
+//! In CI, pin the signer so that a rewritten manifest is not accepted:
+//!
+//! ```bash
+//! cargocrypt verify --all --key "$CARGOCRYPT_SIGNING_KEY"
+//! ```
+
+use crate::crypto::secrets::FILE_MAGIC;
+use crate::crypto::{defaults, DetachedSignature, EncryptedSecret, KeySource, SigningKeyPair};
+use crate::error::{CargoCryptError, CryptoResult};
+use crate::expiry::find_encrypted_files;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// File name of the manifest inside `.cargocrypt/`
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Current manifest format version
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// Length of the fixed part of a v2 file before the header JSON
+const HEADER_PREFIX_LENGTH: usize = 6 + 1 + 4;
+
+/// Digests recorded for one encrypted file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// File size in bytes
+    pub size: u64,
+    /// SHA-256 of the whole file (hex)
+    pub sha256: String,
+    /// SHA-256 of the magic, version and header (hex), absent for legacy files
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header_sha256: Option<String>,
+}
+
+impl ManifestEntry {
+    /// Compute the digests of an encrypted file's contents
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        Self {
+            size: bytes.len() as u64,
+            sha256: hex::encode(Sha256::digest(bytes)),
+            header_sha256: header_bytes(bytes).map(|header| hex::encode(Sha256::digest(header))),
+        }
+    }
+}
+
+/// Signed list of every encrypted file in a project
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Manifest format version
+    pub version: u32,
+    /// Creation time (Unix timestamp)
+    pub created_at: u64,
+    /// Entries keyed by `/`-separated path relative to the project root
+    pub entries: BTreeMap<String, ManifestEntry>,
+    /// Signature over the fields above
+    pub signature: DetachedSignature,
+}
+
+/// The signed part of a manifest
+#[derive(Serialize)]
+struct SignedFields<'a> {
+    version: u32,
+    created_at: u64,
+    entries: &'a BTreeMap<String, ManifestEntry>,
+}
+
+impl Manifest {
+    /// Build and sign a manifest over `entries`
+    pub fn sign(entries: BTreeMap<String, ManifestEntry>, keypair: &SigningKeyPair) -> CryptoResult<Self> {
+        let created_at = crate::utils::unix_now();
+        let message = signed_bytes(MANIFEST_VERSION, created_at, &entries)?;
+
+        Ok(Self {
+            version: MANIFEST_VERSION,
+            created_at,
+            entries,
+            signature: keypair.sign(&message),
+        })
+    }
+
+    /// Check the signature, optionally requiring a specific signer
+    pub fn verify_signature(&self, expected_public_key: Option<&[u8]>) -> CryptoResult<()> {
+        let message = signed_bytes(self.version, self.created_at, &self.entries)?;
+        match expected_public_key {
+            Some(public_key) => self.signature.verify_with_key(&message, public_key)?,
+            None => self.signature.verify(&message)?,
+        }
+        Ok(())
+    }
+
+    /// Load a manifest from disk
+    pub fn load<P: AsRef<Path>>(path: P) -> CryptoResult<Self> {
+        let manifest: Self = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        if manifest.version != MANIFEST_VERSION {
+            return Err(CargoCryptError::Validation {
+                message: format!("Unsupported manifest version {}", manifest.version),
+                errors: vec![format!("Expected version {}", MANIFEST_VERSION)],
+                warnings: Vec::new(),
+            });
+        }
+        Ok(manifest)
+    }
+
+    /// Write the manifest to disk
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> CryptoResult<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+fn signed_bytes(version: u32, created_at: u64, entries: &BTreeMap<String, ManifestEntry>) -> CryptoResult<Vec<u8>> {
+    Ok(serde_json::to_vec(&SignedFields { version, created_at, entries })?)
+}
+
+/// The magic, version and header of a v2 file
+fn header_bytes(bytes: &[u8]) -> Option<&[u8]> {
+    if !bytes.starts_with(FILE_MAGIC) || bytes.len() < HEADER_PREFIX_LENGTH {
+        return None;
+    }
+    let len_bytes = &bytes[HEADER_PREFIX_LENGTH - 4..HEADER_PREFIX_LENGTH];
+    let header_len = u32::from_le_bytes([len_bytes[0], len_bytes[1], len_bytes[2], len_bytes[3]]) as usize;
+    bytes.get(..HEADER_PREFIX_LENGTH.checked_add(header_len)?)
+}
+
+/// Kind of integrity problem
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IntegrityIssueKind {
+    /// The file is not a well-formed encrypted file
+    Malformed,
+    /// Decryption failed: wrong password or tampered ciphertext
+    AuthenticationFailed,
+    /// The header differs from the manifest
+    HeaderModified,
+    /// The ciphertext differs from the manifest
+    Modified,
+    /// Listed in the manifest but not on disk
+    Missing,
+    /// On disk but not listed in the manifest
+    Untracked,
+    /// No manifest has been written yet
+    NoManifest,
+    /// The manifest could not be read or its signature is invalid
+    InvalidManifest,
+}
+
+impl fmt::Display for IntegrityIssueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Malformed => "malformed",
+            Self::AuthenticationFailed => "authentication failed",
+            Self::HeaderModified => "header modified",
+            Self::Modified => "modified",
+            Self::Missing => "missing",
+            Self::Untracked => "not in manifest",
+            Self::NoManifest => "no manifest",
+            Self::InvalidManifest => "invalid manifest",
+        };
+        f.write_str(name)
+    }
+}
+
+impl IntegrityIssueKind {
+    /// Whether the file itself is damaged, as opposed to differing from the manifest
+    pub fn is_damage(&self) -> bool {
+        matches!(self, Self::Malformed | Self::AuthenticationFailed)
+    }
+}
+
+/// A problem found while verifying a project
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IntegrityIssue {
+    /// Path relative to the project root
+    pub path: PathBuf,
+    /// What is wrong with it
+    pub kind: IntegrityIssueKind,
+    /// Human-readable detail
+    pub detail: String,
+}
+
+/// Result of verifying a project
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IntegrityReport {
+    /// Number of encrypted files checked
+    pub files_checked: usize,
+    /// Number of authentication tags checked by decrypting
+    pub tags_checked: usize,
+    /// Fingerprint of the manifest signer, if a valid manifest was found
+    pub signer: Option<String>,
+    /// Problems found
+    pub issues: Vec<IntegrityIssue>,
+}
+
+impl IntegrityReport {
+    /// Whether every file is intact and matches the manifest
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// Whether any file is damaged, regardless of the manifest
+    pub fn has_damage(&self) -> bool {
+        self.issues.iter().any(|issue| issue.kind.is_damage())
+    }
+
+    fn push<P: Into<PathBuf>>(&mut self, path: P, kind: IntegrityIssueKind, detail: impl Into<String>) {
+        self.issues.push(IntegrityIssue { path: path.into(), kind, detail: detail.into() });
+    }
+}
+
+/// Integrity checker for the encrypted files of a project
+#[derive(Debug, Clone)]
+pub struct ProjectVerifier {
+    project_root: PathBuf,
+    encrypted_extension: String,
+    expected_public_key: Option<Vec<u8>>,
+}
+
+impl ProjectVerifier {
+    /// Create a verifier for the project in `project_root`
+    pub fn new<P: AsRef<Path>>(project_root: P) -> Self {
+        Self {
+            project_root: project_root.as_ref().to_path_buf(),
+            encrypted_extension: "enc".to_string(),
+            expected_public_key: None,
+        }
+    }
+
+    /// Set the extension used for encrypted files (default: `enc`)
+    pub fn with_encrypted_extension<S: Into<String>>(mut self, extension: S) -> Self {
+        self.encrypted_extension = extension.into();
+        self
+    }
+
+    /// Only accept a manifest signed by this public key
+    pub fn with_expected_key(mut self, public_key: Option<Vec<u8>>) -> Self {
+        self.expected_public_key = public_key;
+        self
+    }
+
+    /// Path of the project's manifest
+    pub fn manifest_path(&self) -> PathBuf {
+        self.project_root.join(".cargocrypt").join(MANIFEST_FILE)
+    }
+
+    /// Digests of every encrypted file, keyed by manifest path
+    ///
+    /// Fails on the first file that is not a well-formed encrypted file.
+    pub fn entries(&self) -> CryptoResult<BTreeMap<String, ManifestEntry>> {
+        let mut entries = BTreeMap::new();
+        for path in find_encrypted_files(&self.project_root, &self.encrypted_extension) {
+            let bytes = std::fs::read(&path)?;
+            check_format(&bytes).map_err(|detail| CargoCryptError::Validation {
+                message: format!("{} is not a valid encrypted file", path.display()),
+                errors: vec![detail],
+                warnings: Vec::new(),
+            })?;
+            entries.insert(self.manifest_key(&path), ManifestEntry::from_bytes(&bytes));
+        }
+        Ok(entries)
+    }
+
+    /// Check every encrypted file and compare against the manifest
+    ///
+    /// With a password, authentication tags of password-encrypted files are
+    /// checked too, except for vault keys.
+    pub fn run(&self, password: Option<&str>) -> CryptoResult<IntegrityReport> {
+        let mut report = IntegrityReport::default();
+        let mut found = BTreeMap::new();
+
+        for path in find_encrypted_files(&self.project_root, &self.encrypted_extension) {
+            let key = self.manifest_key(&path);
+            report.files_checked += 1;
+
+            let bytes = std::fs::read(&path)?;
+            let secret = match check_format(&bytes) {
+                Ok(secret) => secret,
+                Err(detail) => {
+                    report.push(&key, IntegrityIssueKind::Malformed, detail);
+                    continue;
+                }
+            };
+
+            // Vault keys under .cargocrypt/ have passwords of their own
+            if let Some(password) = password.filter(|_| !key.starts_with(".cargocrypt/")) {
+                if secret.key_source() == KeySource::Password {
+                    report.tags_checked += 1;
+                    if secret.decrypt_with_password(password).is_err() {
+                        report.push(&key, IntegrityIssueKind::AuthenticationFailed, "wrong password or tampered ciphertext");
+                    }
+                }
+            }
+            found.insert(key, ManifestEntry::from_bytes(&bytes));
+        }
+
+        let manifest_path = self.manifest_path();
+        if !manifest_path.exists() {
+            report.push(
+                self.manifest_display_path(),
+                IntegrityIssueKind::NoManifest,
+                "run `cargocrypt verify --all --write-manifest` to create one",
+            );
+            return Ok(report);
+        }
+
+        let manifest = match Manifest::load(&manifest_path)
+            .and_then(|manifest| manifest.verify_signature(self.expected_public_key.as_deref()).map(|()| manifest))
+        {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                report.push(self.manifest_display_path(), IntegrityIssueKind::InvalidManifest, e.to_string());
+                return Ok(report);
+            }
+        };
+        report.signer = Some(manifest.signature.signer_fingerprint());
+
+        for (key, entry) in &found {
+            match manifest.entries.get(key) {
+                None => report.push(key, IntegrityIssueKind::Untracked, "added since the manifest was signed"),
+                Some(expected) if expected == entry => {}
+                Some(expected) if expected.header_sha256 != entry.header_sha256 => {
+                    report.push(key, IntegrityIssueKind::HeaderModified, "header digest does not match the manifest")
+                }
+                Some(_) => report.push(key, IntegrityIssueKind::Modified, "digest does not match the manifest"),
+            }
+        }
+        for key in manifest.entries.keys().filter(|key| !found.contains_key(*key)) {
+            report.push(key, IntegrityIssueKind::Missing, "listed in the manifest but not found");
+        }
+
+        Ok(report)
+    }
+
+    fn manifest_display_path(&self) -> PathBuf {
+        Path::new(".cargocrypt").join(MANIFEST_FILE)
+    }
+
+    fn manifest_key(&self, path: &Path) -> String {
+        let relative = path.strip_prefix(&self.project_root).unwrap_or(path);
+        relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+/// Parse an encrypted file and check that its ciphertext can hold a tag
+fn check_format(bytes: &[u8]) -> Result<EncryptedSecret, String> {
+    let secret = EncryptedSecret::from_bytes(bytes).map_err(|e| e.to_string())?;
+    if secret.ciphertext_len() < defaults::TAG_LENGTH {
+        return Err(format!(
+            "ciphertext is {} bytes, shorter than the {}-byte authentication tag",
+            secret.ciphertext_len(),
+            defaults::TAG_LENGTH
+        ));
+    }
+    Ok(secret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{DerivedKey, Kdf, PlaintextSecret, SecureRandom};
+    use tempfile::TempDir;
+
+    fn write_secret(root: &Path, name: &str, password: &str) -> Vec<u8> {
+        let kdf = Kdf::Argon2idCustom { m_cost: 8192, t_cost: 1, p_cost: 1 };
+        let key = DerivedKey::from_password_with_kdf(password, &SecureRandom::generate_salt().unwrap(), &kdf).unwrap();
+        let plaintext = PlaintextSecret::from_string(name.to_string());
+        let secret = EncryptedSecret::encrypt_with_key(plaintext, &key, None).unwrap().with_kdf(kdf);
+        let bytes = secret.to_bytes().unwrap();
+        let path = root.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, &bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_manifest_detects_tampering() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let bytes = write_secret(root, "config/db.env.enc", "password");
+        write_secret(root, "api.key.enc", "password");
+
+        let keypair = SigningKeyPair::generate();
+        let verifier = ProjectVerifier::new(root).with_expected_key(Some(keypair.public_key().to_vec()));
+        assert_eq!(verifier.run(None).unwrap().issues[0].kind, IntegrityIssueKind::NoManifest);
+
+        Manifest::sign(verifier.entries().unwrap(), &keypair).unwrap().save(verifier.manifest_path()).unwrap();
+        let report = verifier.run(Some("password")).unwrap();
+        assert!(report.is_clean(), "{:?}", report.issues);
+        assert_eq!((report.files_checked, report.tags_checked), (2, 2));
+
+        // Flip a ciphertext bit, drop a file and add an unknown one
+        let mut tampered = bytes.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        std::fs::write(root.join("config/db.env.enc"), &tampered).unwrap();
+        std::fs::remove_file(root.join("api.key.enc")).unwrap();
+        write_secret(root, "new.enc", "password");
+
+        let report = verifier.run(Some("password")).unwrap();
+        let kinds: Vec<_> = report.issues.iter().map(|issue| (issue.path.to_string_lossy().into_owned(), issue.kind)).collect();
+        assert!(kinds.contains(&("config/db.env.enc".to_string(), IntegrityIssueKind::AuthenticationFailed)));
+        assert!(kinds.contains(&("config/db.env.enc".to_string(), IntegrityIssueKind::Modified)));
+        assert!(kinds.contains(&("new.enc".to_string(), IntegrityIssueKind::Untracked)));
+        assert!(kinds.contains(&("api.key.enc".to_string(), IntegrityIssueKind::Missing)));
+        assert!(report.has_damage());
+    }
+
+    #[test]
+    fn test_manifest_signature_is_checked() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        write_secret(root, "db.env.enc", "password");
+
+        let keypair = SigningKeyPair::generate();
+        let verifier = ProjectVerifier::new(root);
+        let mut manifest = Manifest::sign(verifier.entries().unwrap(), &keypair).unwrap();
+        manifest.entries.values_mut().for_each(|entry| entry.size += 1);
+        manifest.save(verifier.manifest_path()).unwrap();
+        assert_eq!(verifier.run(None).unwrap().issues[0].kind, IntegrityIssueKind::InvalidManifest);
+
+        // A valid manifest from an unexpected signer is rejected when the key is pinned
+        let other = SigningKeyPair::generate();
+        Manifest::sign(verifier.entries().unwrap(), &other).unwrap().save(verifier.manifest_path()).unwrap();
+        assert!(verifier.run(None).unwrap().is_clean());
+        let pinned = verifier.with_expected_key(Some(keypair.public_key().to_vec()));
+        assert_eq!(pinned.run(None).unwrap().issues[0].kind, IntegrityIssueKind::InvalidManifest);
+    }
+
+    #[test]
+    fn test_header_change_is_reported() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let bytes = write_secret(root, "db.env.enc", "password");
+
+        let verifier = ProjectVerifier::new(root);
+        Manifest::sign(verifier.entries().unwrap(), &SigningKeyPair::generate())
+            .unwrap()
+            .save(verifier.manifest_path())
+            .unwrap();
+
+        let mut secret = EncryptedSecret::from_bytes(&bytes).unwrap();
+        let mut metadata = secret.metadata().clone();
+        metadata.set_expires_at(Some(1));
+        secret.set_metadata(metadata);
+        std::fs::write(root.join("db.env.enc"), secret.to_bytes().unwrap()).unwrap();
+
+        let report = verifier.run(None).unwrap();
+        assert_eq!(report.issues.len(), 1);
+        assert_eq!(report.issues[0].kind, IntegrityIssueKind::HeaderModified);
+    }
+
+    #[test]
+    fn test_malformed_file() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("junk.enc"), b"CCRYPT\x02").unwrap();
+
+        let verifier = ProjectVerifier::new(temp_dir.path());
+        assert!(verifier.entries().is_err());
+        assert_eq!(verifier.run(None).unwrap().issues[0].kind, IntegrityIssueKind::Malformed);
+    }
+}
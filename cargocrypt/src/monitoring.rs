@@ -71,9 +71,13 @@ impl MonitoringManager {
         let env_filter = EnvFilter::try_from_default_env()
             .unwrap_or_else(|_| EnvFilter::new(&self.config.file_log_level));
 
-        tracing_subscriber::registry()
+        // Another instance or the host application may have installed a
+        // subscriber already; keep it rather than panicking
+        let installed = tracing_subscriber::registry()
             .with(
+                // stdout carries data for pipes and git filters
                 tracing_subscriber::fmt::layer()
+                    .with_writer(std::io::stderr)
                     .with_target(false)
                     .with_thread_ids(true)
                     .with_file(true)
@@ -81,9 +85,12 @@ impl MonitoringManager {
                     .compact()
             )
             .with(env_filter)
-            .init();
+            .try_init()
+            .is_ok();
 
-        info!("CargoCrypt monitoring initialized");
+        if installed {
+            info!("CargoCrypt monitoring initialized");
+        }
         Ok(())
     }
 
@@ -0,0 +1,160 @@
+//! Dry runs of commands that change files
+//!
+//! Commands that write files can describe what they would do as a [`Plan`]
+//! before doing it; `--dry-run` prints the plan and stops there. Planning
+//! only reads: files are compared with what would be written, so a file
+//! that would be rewritten with the same content is left out.
+
+use serde::Serialize;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// What would happen to a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Change {
+    Create,
+    Modify,
+    Delete,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Self::Create => "create",
+            Self::Modify => "modify",
+            Self::Delete => "delete",
+        })
+    }
+}
+
+/// A change to one file, or to a git ref
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PlannedChange {
+    pub change: Change,
+    pub path: PathBuf,
+    /// Why, or what the file is for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+/// The changes a command would make, in the order it would make them
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub struct Plan {
+    changes: Vec<PlannedChange>,
+}
+
+impl Plan {
+    /// An empty plan
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a change to `path`, unless one is recorded already
+    pub fn push(&mut self, change: Change, path: impl Into<PathBuf>, note: Option<&str>) {
+        let path = path.into();
+        if !self.changes.iter().any(|planned| planned.path == path) {
+            self.changes.push(PlannedChange { change, path, note: note.map(str::to_string) });
+        }
+    }
+
+    /// Record writing `path`: created if it is missing, modified otherwise
+    pub fn write(&mut self, path: impl Into<PathBuf>, note: Option<&str>) {
+        let path = path.into();
+        let change = if path.exists() { Change::Modify } else { Change::Create };
+        self.push(change, path, note);
+    }
+
+    /// Record writing `content` to `path`, unless the file holds it already
+    pub fn write_content(&mut self, path: impl Into<PathBuf>, content: &[u8], note: Option<&str>) {
+        let path = path.into();
+        if std::fs::read(&path).map_or(true, |current| current != content) {
+            self.write(path, note);
+        }
+    }
+
+    /// Record removing `path`, if it exists
+    pub fn delete(&mut self, path: impl Into<PathBuf>, note: Option<&str>) {
+        let path = path.into();
+        if path.exists() {
+            self.push(Change::Delete, path, note);
+        }
+    }
+
+    /// Append the changes of `other`
+    pub fn extend(&mut self, other: Plan) {
+        for planned in other.changes {
+            if !self.changes.iter().any(|existing| existing.path == planned.path) {
+                self.changes.push(planned);
+            }
+        }
+    }
+
+    /// The planned changes
+    pub fn iter(&self) -> impl Iterator<Item = &PlannedChange> {
+        self.changes.iter()
+    }
+
+    /// Number of planned changes
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    /// Whether nothing would change
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// The plan with paths below `dir` made relative to it, for display
+    pub fn relative_to(&self, dir: &Path) -> Self {
+        let changes = self
+            .changes
+            .iter()
+            .map(|planned| PlannedChange {
+                path: planned.path.strip_prefix(dir).map(Path::to_path_buf).unwrap_or_else(|_| planned.path.clone()),
+                ..planned.clone()
+            })
+            .collect();
+        Self { changes }
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for planned in &self.changes {
+            write!(f, "   {:<6}  {}", planned.change, planned.path.display())?;
+            if let Some(note) = &planned.note {
+                write!(f, " ({})", note)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_plan_compares_with_disk() {
+        let temp_dir = TempDir::new().unwrap();
+        let existing = temp_dir.path().join("existing.txt");
+        std::fs::write(&existing, "same").unwrap();
+
+        let mut plan = Plan::new();
+        plan.write_content(&existing, b"same", None);
+        assert!(plan.is_empty());
+
+        plan.write_content(&existing, b"changed", Some("rewritten"));
+        plan.write(temp_dir.path().join("new.txt"), None);
+        plan.delete(temp_dir.path().join("missing.txt"), None);
+        let changes: Vec<_> = plan.iter().map(|planned| planned.change).collect();
+        assert_eq!(changes, [Change::Modify, Change::Create]);
+
+        let shown = plan.relative_to(temp_dir.path()).to_string();
+        assert_eq!(shown, "   modify  existing.txt (rewritten)\n   create  new.txt\n");
+    }
+}
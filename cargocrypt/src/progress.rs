@@ -0,0 +1,221 @@
+//! Progress bars for long operations
+//!
+//! Operations over many files report to a [`Progress`]: one bar for the
+//! whole operation, counting bytes and showing the throughput, above a line
+//! for each file in flight with its size and how long it has taken. Finished
+//! files scroll by above the bars with their own throughput. Bars are
+//! drawn on stderr, only when it is a terminal, so they never end up in
+//! redirected output, and not at all after [`set_quiet`] (`--quiet`).
+
+use indicatif::{HumanBytes, MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How often spinners redraw while an operation waits on a single file
+const TICK: Duration = Duration::from_millis(120);
+
+/// Whether progress bars are hidden in this process
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Hide progress bars in this process
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::SeqCst);
+}
+
+/// Whether progress bars are hidden in this process
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::SeqCst)
+}
+
+/// Progress of an operation over many files
+///
+/// Clones report to the same bars, so tasks running concurrently can each
+/// hold one.
+#[derive(Clone)]
+pub struct Progress {
+    bars: MultiProgress,
+    total: ProgressBar,
+    files_total: Arc<AtomicU64>,
+    files_done: Arc<AtomicU64>,
+}
+
+impl Progress {
+    /// Progress that is never drawn
+    pub fn hidden() -> Self {
+        Self::with_target(ProgressDrawTarget::hidden())
+    }
+
+    /// Progress counted in bytes, for operations that read and write whole files
+    ///
+    /// The operation sets its size with [`Progress::set_total`] once it knows
+    /// which files it works on.
+    pub fn bytes(message: &str) -> Self {
+        let progress = Self::with_target(Self::target());
+        progress.total.set_style(style(
+            "{spinner} {prefix} [{bar:30}] {bytes}/{total_bytes} ({binary_bytes_per_sec}, {eta}) {msg}",
+        ));
+        progress.total.set_prefix(message.to_string());
+        progress
+    }
+
+    /// Progress counted in files whose number is learnt as the operation goes, e.g. a scan
+    pub fn files(message: &str) -> Self {
+        let progress = Self::with_target(Self::target());
+        progress.total.set_style(style("{spinner} {prefix} [{bar:30}] {pos}/{len} files {msg}"));
+        progress.total.set_prefix(message.to_string());
+        progress
+    }
+
+    fn with_target(target: ProgressDrawTarget) -> Self {
+        let bars = MultiProgress::with_draw_target(target);
+        let total = bars.add(ProgressBar::new(0));
+        if !bars.is_hidden() {
+            total.enable_steady_tick(TICK);
+        }
+        Self { bars, total, files_total: Arc::default(), files_done: Arc::default() }
+    }
+
+    fn target() -> ProgressDrawTarget {
+        if is_quiet() {
+            ProgressDrawTarget::hidden()
+        } else {
+            ProgressDrawTarget::stderr()
+        }
+    }
+
+    /// Set the number of files and their bytes altogether, for [`Progress::bytes`]
+    pub fn set_total(&self, files: u64, bytes: u64) {
+        self.files_total.store(files, Ordering::Relaxed);
+        self.total.set_length(bytes);
+        self.total.set_message(self.files_message());
+    }
+
+    /// Show `path` as in flight until the returned [`FileProgress`] is dropped
+    pub fn start_file(&self, path: &Path, size: u64) -> FileProgress {
+        let bar = self.bars.add(ProgressBar::new(size));
+        bar.set_style(style("  {spinner} {msg} ({total_bytes}, {elapsed})"));
+        bar.set_message(path.display().to_string());
+        if !self.bars.is_hidden() {
+            bar.enable_steady_tick(TICK);
+        }
+        FileProgress { progress: self.clone(), bar, size }
+    }
+
+    /// Set the files found so far and those done, for [`Progress::files`]
+    pub fn set_files(&self, done: u64, total: u64) {
+        self.total.set_length(total);
+        self.total.set_position(done);
+    }
+
+    /// Set the text after the bar of the whole operation
+    pub fn set_message(&self, message: String) {
+        self.total.set_message(message);
+    }
+
+    /// Time since the operation started
+    pub fn elapsed(&self) -> Duration {
+        self.total.elapsed()
+    }
+
+    /// Print a line above the bars without garbling them
+    pub fn println(&self, line: impl AsRef<str>) {
+        if self.bars.is_hidden() {
+            return;
+        }
+        let _ = self.bars.println(line);
+    }
+
+    /// Remove the bars
+    pub fn finish(&self) {
+        self.total.finish_and_clear();
+        let _ = self.bars.clear();
+    }
+
+    fn files_message(&self) -> String {
+        format!(
+            "{}/{} files",
+            self.files_done.load(Ordering::Relaxed),
+            self.files_total.load(Ordering::Relaxed)
+        )
+    }
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Self::hidden()
+    }
+}
+
+impl std::fmt::Debug for Progress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Progress")
+            .field("hidden", &self.bars.is_hidden())
+            .field("position", &self.total.position())
+            .field("length", &self.total.length())
+            .finish()
+    }
+}
+
+/// A file in flight, see [`Progress::start_file`]
+///
+/// Dropping it counts the file as done, whether it succeeded or not.
+pub struct FileProgress {
+    progress: Progress,
+    bar: ProgressBar,
+    size: u64,
+}
+
+impl FileProgress {
+    /// Count the file as done and log its throughput above the bars
+    pub fn finish(self) {
+        let secs = self.bar.elapsed().as_secs_f64().max(0.001);
+        self.progress.println(format!(
+            "   {}  {} ({}/s)",
+            self.bar.message(),
+            format_bytes(self.size),
+            format_bytes((self.size as f64 / secs) as u64)
+        ));
+    }
+}
+
+impl Drop for FileProgress {
+    fn drop(&mut self) {
+        self.bar.finish_and_clear();
+        self.progress.bars.remove(&self.bar);
+        self.progress.total.inc(self.size);
+        self.progress.files_done.fetch_add(1, Ordering::Relaxed);
+        self.progress.total.set_message(self.progress.files_message());
+    }
+}
+
+/// `bytes` as shown by the bars, e.g. `1.50 MiB`
+pub fn format_bytes(bytes: u64) -> String {
+    HumanBytes(bytes).to_string()
+}
+
+fn style(template: &str) -> ProgressStyle {
+    ProgressStyle::with_template(template)
+        .unwrap_or_else(|_| ProgressStyle::default_bar())
+        .progress_chars("=> ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_files_count_when_dropped() {
+        let progress = Progress::hidden();
+        progress.set_total(2, 30);
+        progress.start_file(Path::new("a.txt"), 10).finish();
+        let failed = progress.start_file(Path::new("b.txt"), 20);
+        assert_eq!(progress.files_message(), "1/2 files");
+        drop(failed);
+
+        assert_eq!(progress.total.position(), 30);
+        assert_eq!(progress.files_message(), "2/2 files");
+        assert_eq!(format_bytes(1536), "1.50 KiB");
+    }
+}
@@ -0,0 +1,193 @@
+//! Finding the project a command runs in
+//!
+//! The project root is the directory holding `.cargocrypt/`. It is found the
+//! way cargo finds a package: from `--manifest-path` if one was given, else
+//! from the nearest `Cargo.toml` above the working directory. When that
+//! package belongs to a workspace, the workspace root is the project root,
+//! so all members share one `.cargocrypt/` whichever member directory a
+//! command runs in; members can still have their own `.cargocrypt/git.toml`
+//! (see [`crate::git::workspace`]).
+//!
+//! A package belongs to the workspace of the nearest manifest above it with
+//! a `[workspace]` table, or to the one its `package.workspace` key points
+//! to, unless that workspace lists it under `exclude`.
+
+use crate::error::{CargoCryptError, CryptoResult};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+/// Manifest of a Cargo package or workspace
+const MANIFEST: &str = "Cargo.toml";
+
+/// Manifest given with `--manifest-path` for this process
+static MANIFEST_PATH: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// Resolve the project of this process from `manifest` instead of the working directory
+pub fn set_manifest_path(manifest: Option<PathBuf>) {
+    *MANIFEST_PATH.write().unwrap_or_else(|e| e.into_inner()) = manifest;
+}
+
+/// The manifest given with [`set_manifest_path`], if any
+pub fn manifest_path() -> Option<PathBuf> {
+    MANIFEST_PATH.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// The package a command runs in and the project it belongs to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Project {
+    /// Directory holding `.cargocrypt/`: the workspace root, or the package directory
+    pub root: PathBuf,
+    /// Manifest of the current package (or of a virtual workspace)
+    pub manifest: PathBuf,
+    /// `[package] name`, `None` for a virtual workspace manifest
+    pub package: Option<String>,
+    /// Whether the package is a member of a workspace rooted elsewhere or at its own directory
+    pub in_workspace: bool,
+}
+
+impl Project {
+    /// The project of this process: from `--manifest-path`, or the working directory
+    pub fn find() -> CryptoResult<Self> {
+        match manifest_path() {
+            Some(manifest) => Self::from_manifest(&manifest),
+            None => Self::discover(&std::env::current_dir()?),
+        }
+    }
+
+    /// The project of the nearest `Cargo.toml` at or above `dir`
+    pub fn discover(dir: &Path) -> CryptoResult<Self> {
+        dir.ancestors()
+            .map(|ancestor| ancestor.join(MANIFEST))
+            .find(|manifest| manifest.is_file())
+            .ok_or_else(CargoCryptError::project_not_found)
+            .and_then(|manifest| Self::from_manifest(&manifest))
+    }
+
+    /// The project of the package with `manifest`
+    pub fn from_manifest(manifest: &Path) -> CryptoResult<Self> {
+        if manifest.file_name().is_none_or(|name| name != MANIFEST) {
+            return Err(CargoCryptError::Config {
+                message: format!("The manifest path must point to a Cargo.toml file, not {}", manifest.display()),
+                suggestion: Some("Pass e.g. --manifest-path crates/api/Cargo.toml".to_string()),
+            });
+        }
+        if !manifest.is_file() {
+            return Err(CargoCryptError::file_not_found(manifest));
+        }
+        let manifest = std::path::absolute(manifest)?;
+        let dir = manifest.parent().map(Path::to_path_buf).unwrap_or_default();
+        let parsed = read_manifest(&manifest);
+        let package = parsed
+            .as_ref()
+            .and_then(|parsed| parsed.get("package")?.get("name")?.as_str().map(str::to_string));
+
+        let root = match &parsed {
+            Some(parsed) if parsed.get("workspace").is_some() => Some(dir.clone()),
+            Some(parsed) => match parsed.get("package").and_then(|package| package.get("workspace")) {
+                Some(workspace) => workspace.as_str().map(|workspace| dir.join(workspace)),
+                None => enclosing_workspace(&dir),
+            },
+            None => None,
+        };
+        Ok(Self {
+            in_workspace: root.is_some() && (root.as_deref() != Some(&dir) || is_real_workspace(&manifest)),
+            root: root.map(|root| normalize(&root)).unwrap_or(dir),
+            manifest,
+            package,
+        })
+    }
+
+    /// Directory of the current package
+    pub fn package_dir(&self) -> &Path {
+        self.manifest.parent().unwrap_or(&self.root)
+    }
+}
+
+/// The nearest workspace root above `dir` that does not exclude it
+fn enclosing_workspace(dir: &Path) -> Option<PathBuf> {
+    let workspace = dir.ancestors().skip(1).find(|ancestor| {
+        read_manifest(&ancestor.join(MANIFEST)).is_some_and(|parsed| parsed.get("workspace").is_some())
+    })?;
+    let parsed = read_manifest(&workspace.join(MANIFEST))?;
+    let relative = dir.strip_prefix(workspace).ok()?;
+    let excluded = parsed
+        .get("workspace")
+        .and_then(|workspace| workspace.get("exclude"))
+        .and_then(|exclude| exclude.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|exclude| exclude.as_str())
+        .any(|exclude| relative.starts_with(exclude.trim_start_matches("./").trim_end_matches('/')));
+    (!excluded).then(|| workspace.to_path_buf())
+}
+
+/// Whether the `[workspace]` of `manifest` lists members, as opposed to a lone package declaring itself a root
+fn is_real_workspace(manifest: &Path) -> bool {
+    read_manifest(manifest)
+        .and_then(|parsed| parsed.get("workspace")?.get("members")?.as_array().map(|members| !members.is_empty()))
+        .unwrap_or(false)
+}
+
+fn read_manifest(manifest: &Path) -> Option<toml::Value> {
+    toml::from_str(&std::fs::read_to_string(manifest).ok()?).ok()
+}
+
+/// `path` without `.` and `..` components, as `package.workspace = "../.."` yields
+fn normalize(path: &Path) -> PathBuf {
+    use std::path::Component;
+
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write(root: &Path, path: &str, content: &str) {
+        let path = root.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_workspace_members_share_the_root() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = std::path::absolute(temp_dir.path()).unwrap();
+        write(&root, "Cargo.toml", "[workspace]\nmembers = [\"crates/*\"]\nexclude = [\"tools/standalone\"]\n");
+        write(&root, "crates/api/Cargo.toml", "[package]\nname = \"api\"\n");
+        write(&root, "tools/standalone/Cargo.toml", "[package]\nname = \"standalone\"\n");
+        write(&root, "other/nested/Cargo.toml", "[package]\nname = \"nested\"\nworkspace = \"../..\"\n");
+        std::fs::create_dir_all(root.join("crates/api/src/bin")).unwrap();
+
+        let api = Project::discover(&root.join("crates/api/src/bin")).unwrap();
+        assert_eq!(api.root, root);
+        assert_eq!(api.package.as_deref(), Some("api"));
+        assert_eq!(api.package_dir(), root.join("crates/api"));
+        assert!(api.in_workspace);
+
+        let virtual_root = Project::discover(&root).unwrap();
+        assert_eq!(virtual_root.root, root);
+        assert_eq!(virtual_root.package, None);
+
+        let standalone = Project::from_manifest(&root.join("tools/standalone/Cargo.toml")).unwrap();
+        assert_eq!(standalone.root, root.join("tools/standalone"));
+        assert!(!standalone.in_workspace);
+
+        let nested = Project::discover(&root.join("other/nested")).unwrap();
+        assert_eq!(nested.root, root);
+
+        assert!(Project::from_manifest(&root.join("crates/api")).is_err());
+        assert!(Project::from_manifest(&root.join("missing/Cargo.toml")).is_err());
+    }
+}
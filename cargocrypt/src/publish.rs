@@ -0,0 +1,259 @@
+//! Pre-publish checks for `cargo package` / `cargo publish`
+//!
+//! Before a crate is uploaded to crates.io, `cargocrypt prepublish` asks cargo
+//! for the exact list of files that would go into the `.crate` archive and
+//! rejects the package if it contains:
+//! - encrypted files (`*.enc`), which are useless to consumers and leak metadata
+//! - decrypted secrets, i.e. plaintext files sitting next to their `.enc` copy
+//! - CargoCrypt project data (`.cargocrypt/`, including signing keys)
+//! - optionally, anything the secret detector flags with high confidence
+//!
+//! The check exits non-zero on any issue, so it can be used as a
+//! [cargo-release](https://github.com/crate-ci/cargo-release) pre-release hook:
+//!
+//! ```toml
+//! # release.toml
+//! pre-release-hook = ["cargocrypt", "prepublish"]
+//! ```
+
+use crate::detection::{ScanOptions, SecretDetector};
+use crate::error::{CargoCryptError, CryptoResult};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Minimum detector confidence for a finding to block publishing
+pub const SECRET_CONFIDENCE_THRESHOLD: f64 = 0.7;
+
+/// Kind of problem found in a package file list
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PublishIssueKind {
+    /// An encrypted file would be published
+    EncryptedFile,
+    /// A plaintext file with an encrypted counterpart would be published
+    DecryptedSecret,
+    /// CargoCrypt project data would be published
+    CargoCryptData,
+    /// The secret detector found a likely secret
+    DetectedSecret,
+}
+
+impl fmt::Display for PublishIssueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::EncryptedFile => "encrypted file",
+            Self::DecryptedSecret => "decrypted secret",
+            Self::CargoCryptData => "CargoCrypt data",
+            Self::DetectedSecret => "detected secret",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A file that should not be published
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PublishIssue {
+    /// Path relative to the package root
+    pub path: PathBuf,
+    /// What is wrong with it
+    pub kind: PublishIssueKind,
+    /// Human-readable detail
+    pub detail: String,
+}
+
+/// Result of a pre-publish check
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PublishReport {
+    /// Number of package files checked
+    pub files_checked: usize,
+    /// Problems found
+    pub issues: Vec<PublishIssue>,
+}
+
+impl PublishReport {
+    /// Whether the package is safe to publish
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Pre-publish checker for a cargo package
+#[derive(Debug, Clone)]
+pub struct PublishCheck {
+    package_root: PathBuf,
+    encrypted_extension: String,
+    scan_secrets: bool,
+}
+
+impl PublishCheck {
+    /// Create a checker for the package in `package_root`
+    pub fn new<P: AsRef<Path>>(package_root: P) -> Self {
+        Self {
+            package_root: package_root.as_ref().to_path_buf(),
+            encrypted_extension: "enc".to_string(),
+            scan_secrets: false,
+        }
+    }
+
+    /// Set the extension used for encrypted files (default: `enc`)
+    pub fn with_encrypted_extension<S: Into<String>>(mut self, extension: S) -> Self {
+        self.encrypted_extension = extension.into();
+        self
+    }
+
+    /// Also run the secret detector over every packaged file
+    pub fn with_secret_scan(mut self, enabled: bool) -> Self {
+        self.scan_secrets = enabled;
+        self
+    }
+
+    /// List the files `cargo package` would include
+    pub async fn package_files(&self) -> CryptoResult<Vec<PathBuf>> {
+        let output = tokio::process::Command::new("cargo")
+            .args(["package", "--list", "--allow-dirty"])
+            .current_dir(&self.package_root)
+            .output()
+            .await?;
+
+        if !output.status.success() {
+            return Err(CargoCryptError::Project {
+                message: format!(
+                    "cargo package --list failed: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+                suggestion: Some("Run the check from a crate directory with a valid Cargo.toml".to_string()),
+            });
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(PathBuf::from)
+            .collect())
+    }
+
+    /// Run the check against the current `cargo package` file list
+    pub async fn run(&self) -> CryptoResult<PublishReport> {
+        let files = self.package_files().await?;
+        let mut report = self.check_files(&files);
+
+        if self.scan_secrets {
+            report.issues.extend(self.scan_files(&files).await?);
+        }
+
+        Ok(report)
+    }
+
+    /// Check a package file list (paths relative to the package root)
+    pub fn check_files(&self, files: &[PathBuf]) -> PublishReport {
+        let mut issues = Vec::new();
+        let encrypted_suffix = format!(".{}", self.encrypted_extension);
+
+        for file in files {
+            let name = file.to_string_lossy();
+
+            if file.components().any(|c| c.as_os_str() == ".cargocrypt") {
+                issues.push(PublishIssue {
+                    path: file.clone(),
+                    kind: PublishIssueKind::CargoCryptData,
+                    detail: "CargoCrypt project data must never be published".to_string(),
+                });
+            } else if name.ends_with(&encrypted_suffix) {
+                issues.push(PublishIssue {
+                    path: file.clone(),
+                    kind: PublishIssueKind::EncryptedFile,
+                    detail: "Encrypted file is included in the package".to_string(),
+                });
+            } else {
+                let encrypted = self.package_root.join(format!("{}{}", name, encrypted_suffix));
+                if encrypted.exists() {
+                    issues.push(PublishIssue {
+                        path: file.clone(),
+                        kind: PublishIssueKind::DecryptedSecret,
+                        detail: format!("Plaintext copy of {}{}", name, encrypted_suffix),
+                    });
+                }
+            }
+        }
+
+        PublishReport {
+            files_checked: files.len(),
+            issues,
+        }
+    }
+
+    async fn scan_files(&self, files: &[PathBuf]) -> CryptoResult<Vec<PublishIssue>> {
+        let detector = SecretDetector::new();
+        let options = ScanOptions::default().with_min_confidence(SECRET_CONFIDENCE_THRESHOLD);
+        let mut issues = Vec::new();
+
+        for file in files {
+            let path = self.package_root.join(file);
+            // cargo lists generated files (Cargo.toml.orig, .cargo_vcs_info.json)
+            if !path.is_file() {
+                continue;
+            }
+
+            for finding in detector.scan_file(&path, &options).await?.into_iter().filter(|f| !f.is_ignored) {
+                issues.push(PublishIssue {
+                    path: file.clone(),
+                    kind: PublishIssueKind::DetectedSecret,
+                    detail: format!(
+                        "{} at line {} ({:.0}% confidence)",
+                        finding.secret.secret_type,
+                        finding.secret.line_number,
+                        finding.confidence * 100.0
+                    ),
+                });
+            }
+        }
+
+        Ok(issues)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_check_files() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join(".env"), "KEY=value").unwrap();
+        fs::write(temp_dir.path().join(".env.enc"), "encrypted").unwrap();
+
+        let files = vec![
+            PathBuf::from("Cargo.toml"),
+            PathBuf::from("src/lib.rs"),
+            PathBuf::from(".env"),
+            PathBuf::from("config/prod.toml.enc"),
+            PathBuf::from(".cargocrypt/signing.key"),
+        ];
+
+        let report = PublishCheck::new(temp_dir.path()).check_files(&files);
+        assert_eq!(report.files_checked, 5);
+        assert!(!report.is_clean());
+
+        let kinds: Vec<_> = report.issues.iter().map(|i| (i.path.clone(), i.kind)).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (PathBuf::from(".env"), PublishIssueKind::DecryptedSecret),
+                (PathBuf::from("config/prod.toml.enc"), PublishIssueKind::EncryptedFile),
+                (PathBuf::from(".cargocrypt/signing.key"), PublishIssueKind::CargoCryptData),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_clean_package() {
+        let temp_dir = TempDir::new().unwrap();
+        let files = vec![PathBuf::from("Cargo.toml"), PathBuf::from("src/main.rs")];
+
+        let report = PublishCheck::new(temp_dir.path()).check_files(&files);
+        assert!(report.is_clean());
+    }
+}
@@ -201,6 +201,15 @@ impl GracefulDegradation {
         }
     }
 
+    /// Create with the given features enabled from the start
+    pub fn with_features(features: &[&str]) -> Self {
+        let flags = features.iter().map(|name| (name.to_string(), true)).collect();
+        Self {
+            feature_flags: Arc::new(RwLock::new(flags)),
+            circuit_breakers: Arc::new(RwLock::new(std::collections::HashMap::new())),
+        }
+    }
+
     /// Register a feature that can be disabled
     pub async fn register_feature(&self, name: &str, enabled: bool) {
         self.feature_flags.write().await.insert(name.to_string(), enabled);
@@ -0,0 +1,173 @@
+//! Running commands with decrypted secrets in their environment
+//!
+//! `cargocrypt run -- <cmd>` decrypts secrets from the secret store or from
+//! an encrypted `.env` file and passes them to the command as environment
+//! variables, so an application can run locally without a plaintext `.env`
+//! on disk. The values are held in zeroizing buffers and wiped as soon as
+//! the command exits.
+//!
+//! The command's own copy of its environment is out of reach: the operating
+//! system keeps it for the lifetime of the process (on Linux it is readable
+//! by the same user in `/proc/<pid>/environ`), so only trusted commands
+//! should be run this way.
+
+use crate::crypto::{PlaintextSecret, SecureString};
+use crate::dotenv::{is_env_name, Dotenv};
+use crate::error::{CargoCryptError, CryptoResult};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+
+/// Environment variables to run a command with
+#[derive(Default)]
+pub struct SecretEnv {
+    vars: BTreeMap<String, SecureString>,
+}
+
+impl SecretEnv {
+    /// An empty environment
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set `name` to `value`, replacing an earlier value
+    pub fn insert(&mut self, name: &str, value: SecureString) -> CryptoResult<()> {
+        if !is_env_name(name) {
+            return Err(invalid(format!("'{}' is not a valid environment variable name", name), Vec::new()));
+        }
+        if value.contains('\0') {
+            return Err(invalid(format!("The value of {} contains a NUL byte", name), Vec::new()));
+        }
+        self.vars.insert(name.to_string(), value);
+        Ok(())
+    }
+
+    /// Set `name` to a secret from the store, which must be UTF-8 text
+    pub fn insert_secret(&mut self, name: &str, secret: &PlaintextSecret) -> CryptoResult<()> {
+        let value = secret
+            .as_string()
+            .map_err(|_| invalid(format!("The value of {} is binary, not text", name), Vec::new()))?;
+        self.insert(name, SecureString::from(value))
+    }
+
+    /// Set the variables of a `.env` file, returning how many there were
+    pub fn extend(&mut self, dotenv: &Dotenv) -> usize {
+        for (name, value) in dotenv.iter() {
+            self.vars.insert(name.to_string(), SecureString::from(value));
+        }
+        dotenv.len()
+    }
+
+    /// Names of the variables, sorted
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.vars.keys().map(String::as_str)
+    }
+
+    /// Number of variables
+    pub fn len(&self) -> usize {
+        self.vars.len()
+    }
+
+    /// Whether no variables are set
+    pub fn is_empty(&self) -> bool {
+        self.vars.is_empty()
+    }
+
+    /// Run `program` with these variables added to the inherited environment
+    ///
+    /// Waits for the program and returns its exit code, or 128 plus the
+    /// signal number if a signal killed it, as shells report it. Ctrl-C
+    /// reaches the program, which decides whether to stop; this process
+    /// keeps waiting for it either way, so the values are wiped after it
+    /// exits.
+    pub async fn run(self, program: &OsString, args: &[OsString]) -> CryptoResult<i32> {
+        let mut child = {
+            let mut command = tokio::process::Command::new(program);
+            command.args(args);
+            for (name, value) in &self.vars {
+                command.env(name, value.as_str());
+            }
+            command.spawn().map_err(|e| CargoCryptError::Io {
+                message: format!("Cannot run {}: {}", program.to_string_lossy(), e),
+                source: e,
+            })?
+        };
+        drop(self);
+
+        let ctrl_c = tokio::spawn(async {
+            while tokio::signal::ctrl_c().await.is_ok() {}
+        });
+        let status = child.wait().await;
+        ctrl_c.abort();
+        Ok(exit_code(status?))
+    }
+}
+
+impl std::fmt::Debug for SecretEnv {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SecretEnv").field("names", &self.vars.keys().collect::<Vec<_>>()).finish()
+    }
+}
+
+/// The environment variable a secret store name is passed as
+///
+/// Letters are upper-cased and everything else but digits becomes `_`, so
+/// `db/password` is passed as `DB_PASSWORD`.
+pub fn env_name(secret_name: &str) -> String {
+    let mut name: String = secret_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect();
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        name.insert(0, '_');
+    }
+    name
+}
+
+fn invalid(message: String, errors: Vec<String>) -> CargoCryptError {
+    CargoCryptError::Validation { message, errors, warnings: Vec::new() }
+}
+
+#[cfg(unix)]
+fn exit_code(status: std::process::ExitStatus) -> i32 {
+    use std::os::unix::process::ExitStatusExt;
+    status.code().or_else(|| status.signal().map(|signal| 128 + signal)).unwrap_or(1)
+}
+
+#[cfg(not(unix))]
+fn exit_code(status: std::process::ExitStatus) -> i32 {
+    status.code().unwrap_or(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_env_names() {
+        assert_eq!(env_name("API_KEY"), "API_KEY");
+        assert_eq!(env_name("db/password"), "DB_PASSWORD");
+        assert_eq!(env_name("3rd-party.token"), "_3RD_PARTY_TOKEN");
+
+        let mut env = SecretEnv::new();
+        assert!(env.insert("bad-name", SecureString::from("x")).is_err());
+        assert!(env.insert_secret("BINARY", &PlaintextSecret::from_bytes(vec![0xff, 0xfe])).is_err());
+        assert!(env.insert("NUL", SecureString::from("a\0b")).is_err());
+        assert!(env.is_empty());
+
+        let dotenv = Dotenv::parse("API_KEY=from-file\nDEBUG=1\n").unwrap();
+        env.insert("API_KEY", SecureString::from("from-store")).unwrap();
+        assert_eq!(env.extend(&dotenv), 2);
+        assert_eq!(env.names().collect::<Vec<_>>(), ["API_KEY", "DEBUG"]);
+        assert_eq!(env.vars["API_KEY"].as_str(), "from-file");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run_passes_variables_and_exit_code() {
+        let mut env = SecretEnv::new();
+        env.insert("CARGOCRYPT_RUN_TEST", SecureString::from("s3cret value")).unwrap();
+        let script = OsString::from("test \"$CARGOCRYPT_RUN_TEST\" = 's3cret value' && exit 7");
+        let code = env.run(&OsString::from("sh"), &[OsString::from("-c"), script]).await.unwrap();
+        assert_eq!(code, 7);
+    }
+}
@@ -0,0 +1,298 @@
+//! Temporary decryption sessions
+//!
+//! `cargocrypt open` decrypts a file for a limited time: into a private
+//! per-user directory (see [`session_dir`]), or next to the encrypted file
+//! with `--in-place`. Each open file is recorded in `.cargocrypt/sessions.toml`
+//! with a digest of the plaintext it started from. When the session ends,
+//! through `cargocrypt close` or a background process that waits for the
+//! TTL, a plaintext that changed is encrypted again and the plaintext is
+//! removed, so a decrypted file is not left behind by accident.
+//!
+//! The record is local state and git-ignored.
+//!
+//! ```toml
+//! [[session]]
+//! encrypted = "config/secrets.toml.enc"
+//! plaintext = "/run/user/1000/cargocrypt/open-3f2a9c1d8e7b6a54/config/secrets.toml"
+//! key = "password"
+//! opened_at = 1760520000
+//! expires_at = 1760520600
+//! digest = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"
+//! ```
+
+use crate::crypto::KeySource;
+use crate::error::{CargoCryptError, CryptoResult};
+use crate::lock::FileLock;
+use crate::utils::{self, unix_now};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Sessions file, inside `.cargocrypt/`
+pub const SESSIONS_FILE: &str = "sessions.toml";
+
+/// How long a file stays open unless `--ttl` says otherwise
+pub const DEFAULT_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// A file decrypted by `cargocrypt open`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Session {
+    /// The encrypted file, relative to the project root
+    pub encrypted: PathBuf,
+    /// Where the plaintext was written
+    pub plaintext: PathBuf,
+    /// Whether the plaintext is next to the encrypted file
+    #[serde(default)]
+    pub in_place: bool,
+    /// Key material the file is encrypted with
+    #[serde(default)]
+    pub key: KeySource,
+    /// Vault whose key encrypts the file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vault: Option<String>,
+    /// Keyfile the file was opened with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keyfile: Option<PathBuf>,
+    /// When the file was opened (Unix timestamp)
+    pub opened_at: u64,
+    /// When the file is closed (Unix timestamp)
+    pub expires_at: u64,
+    /// SHA-256 of the plaintext as it was decrypted
+    pub digest: String,
+}
+
+impl Session {
+    /// Whether the session ran past its TTL
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= unix_now()
+    }
+
+    /// Time until the session ends
+    pub fn remaining(&self) -> Duration {
+        Duration::from_secs(self.expires_at.saturating_sub(unix_now()))
+    }
+
+    /// Whether closing may need a password: for a changed plaintext of a
+    /// password-protected file or a vault file
+    pub fn needs_password(&self) -> bool {
+        self.vault.is_some() || self.key.requires_password()
+    }
+
+    /// Whether the plaintext differs from what was decrypted; false once it is gone
+    pub fn is_modified(&self) -> CryptoResult<bool> {
+        match std::fs::read(&self.plaintext) {
+            Ok(bytes) => Ok(digest(&bytes) != self.digest),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Whether `path` names this session's encrypted file or its plaintext
+    pub fn matches(&self, project_root: &Path, path: &Path) -> bool {
+        let Ok(path) = std::path::absolute(path) else {
+            return false;
+        };
+        path == project_root.join(&self.encrypted) || path == self.plaintext
+    }
+}
+
+/// The open files recorded in `.cargocrypt/sessions.toml`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sessions {
+    /// The open files, oldest first
+    #[serde(rename = "session", default)]
+    pub sessions: Vec<Session>,
+}
+
+impl Sessions {
+    /// Path of the sessions file of the project at `project_root`
+    pub fn project_path<P: AsRef<Path>>(project_root: P) -> PathBuf {
+        project_root.as_ref().join(".cargocrypt").join(SESSIONS_FILE)
+    }
+
+    /// Load the sessions of the project at `project_root`; none if there is no file
+    pub fn load<P: AsRef<Path>>(project_root: P) -> CryptoResult<Self> {
+        let path = Self::project_path(project_root);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        toml::from_str(&std::fs::read_to_string(&path)?).map_err(|e| CargoCryptError::Config {
+            message: format!("Invalid {}: {}", path.display(), e),
+            suggestion: Some("Remove any plaintext left by `cargocrypt open`, then delete the file".to_string()),
+        })
+    }
+
+    fn save(&self, project_root: &Path) -> CryptoResult<()> {
+        let path = Self::project_path(project_root);
+        if self.sessions.is_empty() {
+            if path.exists() {
+                std::fs::remove_file(path)?;
+            }
+            return Ok(());
+        }
+        let content = toml::to_string_pretty(self).map_err(|e| CargoCryptError::Serialization {
+            message: format!("Failed to serialize sessions: {}", e),
+            source: Box::new(e),
+        })?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+
+    /// Change the sessions of the project at `project_root` under the state lock
+    pub async fn update<T, F: FnOnce(&mut Self) -> CryptoResult<T>>(project_root: &Path, update: F) -> CryptoResult<T> {
+        let _lock = FileLock::state(project_root).await?;
+        let mut sessions = Self::load(project_root)?;
+        let before = sessions.clone();
+        let result = update(&mut sessions)?;
+        if sessions != before {
+            sessions.save(project_root)?;
+        }
+        Ok(result)
+    }
+
+    /// The session of the encrypted file `encrypted`, relative to the project root
+    pub fn get<P: AsRef<Path>>(&self, encrypted: P) -> Option<&Session> {
+        self.sessions.iter().find(|session| session.encrypted == encrypted.as_ref())
+    }
+}
+
+/// Private directory that `cargocrypt open` decrypts the files of the project at `project_root` into
+///
+/// `open-<project id>` in the [runtime directory](utils::runtime_dir).
+pub fn session_dir(project_root: &Path) -> PathBuf {
+    utils::runtime_dir().join(format!("open-{}", utils::project_id(project_root)))
+}
+
+/// Create [`session_dir`], readable only by the current user
+pub(crate) fn create_session_dir(project_root: &Path) -> CryptoResult<PathBuf> {
+    let dir = session_dir(project_root);
+    std::fs::create_dir_all(&dir)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+        // The runtime directory may be in a shared location such as /tmp
+        for dir in [dir.parent().unwrap_or(&dir), &dir] {
+            let owner = std::fs::metadata(dir)?.uid();
+            if utils::current_uid().is_some_and(|uid| uid != owner) {
+                return Err(CargoCryptError::Config {
+                    message: format!("{} belongs to another user (uid {})", dir.display(), owner),
+                    suggestion: Some("Set XDG_RUNTIME_DIR to a directory you own".to_string()),
+                });
+            }
+            std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))?;
+        }
+    }
+    Ok(dir)
+}
+
+/// Parse a session length such as `30s`, `10m` or `2h`; a bare number is minutes
+pub fn parse_duration(value: &str) -> CryptoResult<Duration> {
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => value.split_at(index),
+        None => (value, "m"),
+    };
+    let unit_secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        _ => 0,
+    };
+
+    number
+        .parse::<u64>()
+        .ok()
+        .filter(|number| *number > 0 && unit_secs > 0)
+        .and_then(|number| number.checked_mul(unit_secs))
+        .map(Duration::from_secs)
+        .ok_or_else(|| CargoCryptError::Validation {
+            message: format!("Invalid session length: {}", value),
+            errors: vec!["Expected a number of seconds, minutes or hours, e.g. 30s, 10m or 2h".to_string()],
+            warnings: Vec::new(),
+        })
+}
+
+/// Format a session length in minutes, or seconds below one, e.g. `10m`
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs < 60 {
+        format!("{}s", secs)
+    } else {
+        format!("{}m", secs.div_ceil(60))
+    }
+}
+
+/// Hex SHA-256 of a plaintext
+pub(crate) fn digest(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("10m").unwrap(), Duration::from_secs(600));
+        assert_eq!(parse_duration("30s").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("2h").unwrap(), Duration::from_secs(7200));
+        assert_eq!(parse_duration("5").unwrap(), Duration::from_secs(300));
+        for invalid in ["", "0m", "m", "3d", "-1m"] {
+            assert!(parse_duration(invalid).is_err(), "{}", invalid);
+        }
+        assert_eq!(format_duration(Duration::from_secs(45)), "45s");
+        assert_eq!(format_duration(Duration::from_secs(561)), "10m");
+    }
+
+    #[tokio::test]
+    async fn test_sessions_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let plaintext = root.join("open").join("secret.txt");
+        std::fs::create_dir_all(plaintext.parent().unwrap()).unwrap();
+        std::fs::write(&plaintext, "token").unwrap();
+        let session = Session {
+            encrypted: PathBuf::from("secret.txt.enc"),
+            plaintext: plaintext.clone(),
+            in_place: false,
+            key: KeySource::Password,
+            vault: None,
+            keyfile: None,
+            opened_at: unix_now(),
+            expires_at: unix_now() + 600,
+            digest: digest(b"token"),
+        };
+
+        Sessions::update(root, |sessions| {
+            sessions.sessions.push(session.clone());
+            Ok(())
+        })
+        .await
+        .unwrap();
+        let loaded = Sessions::load(root).unwrap();
+        assert_eq!(loaded.get("secret.txt.enc"), Some(&session));
+        assert!(session.matches(root, &root.join("secret.txt.enc")));
+        assert!(session.matches(root, &plaintext));
+        assert!(!session.is_expired() && session.needs_password());
+
+        assert!(!session.is_modified().unwrap());
+        std::fs::write(&plaintext, "changed").unwrap();
+        assert!(session.is_modified().unwrap());
+        std::fs::remove_file(&plaintext).unwrap();
+        assert!(!session.is_modified().unwrap());
+
+        Sessions::update(root, |sessions| {
+            sessions.sessions.clear();
+            Ok(())
+        })
+        .await
+        .unwrap();
+        assert!(!Sessions::project_path(root).exists());
+    }
+}
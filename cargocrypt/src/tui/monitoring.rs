@@ -20,6 +20,7 @@ use ratatui::{
     },
     Frame, Terminal,
 };
+use crate::expiry::{ExpiringItem, ExpiryStatus};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::time::Instant;
@@ -31,6 +32,7 @@ pub struct MonitoringDashboard {
     should_quit: bool,
     last_update: Instant,
     update_interval: Duration,
+    expiring: Vec<ExpiringItem>,
 }
 
 #[derive(Debug, Clone)]
@@ -51,8 +53,15 @@ impl MonitoringDashboard {
             should_quit: false,
             last_update: Instant::now(),
             update_interval: Duration::from_secs(2),
+            expiring: Vec::new(),
         }
     }
+
+    /// Show secrets that are expired or expire soon on the alerts tab
+    pub fn with_expiring_items(mut self, items: Vec<ExpiringItem>) -> Self {
+        self.expiring = items.into_iter().filter(|item| item.status.needs_attention()).collect();
+        self
+    }
     
     /// Run the monitoring dashboard
     pub async fn run(&mut self) -> Result<(), Box<dyn std::error::Error>> {
@@ -253,7 +262,25 @@ impl MonitoringDashboard {
     }
     
     fn render_alerts(&self, f: &mut Frame, area: Rect) {
-        let alerts = vec![
+        let mut alerts: Vec<ListItem> = self.expiring.iter().map(|item| {
+            let (icon, level, color) = match item.status {
+                ExpiryStatus::Expired => ("✖ ", "EXPIRED", Color::Red),
+                _ => ("⚠ ", "EXPIRING", Color::Yellow),
+            };
+            ListItem::new(vec![
+                Line::from(vec![
+                    Span::styled(icon, Style::default().fg(color)),
+                    Span::styled(level, Style::default().fg(color).add_modifier(Modifier::BOLD)),
+                    Span::styled(format!(" - {}", item.source), Style::default().fg(Color::White)),
+                ]),
+                Line::from(vec![
+                    Span::styled("  ", Style::default()),
+                    Span::styled(format!("Expiry date: {} - rotate this secret", item.expiry_date()), Style::default().fg(Color::Gray)),
+                ]),
+            ])
+        }).collect();
+
+        alerts.extend([
             ListItem::new(vec![
                 Line::from(vec![
                     Span::styled("⚠ ", Style::default().fg(Color::Yellow)),
@@ -276,7 +303,7 @@ impl MonitoringDashboard {
                     Span::styled("Peak memory usage: 67.1MB", Style::default().fg(Color::Gray)),
                 ]),
             ]),
-        ];
+        ]);
         
         let alerts_list = List::new(alerts)
             .block(Block::default().borders(Borders::ALL).title("Active Alerts"))
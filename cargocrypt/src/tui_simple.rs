@@ -1,6 +1,7 @@
 //! Simplified TUI implementation for CargoCrypt file browser
 
-use crate::{CargoCrypt, CryptoResult};
+use crate::crypto::SecureString;
+use crate::{BatchOptions, CargoCrypt, CryptoResult};
 use crossterm::{
     event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
     execute,
@@ -32,6 +33,17 @@ pub struct TuiApp {
     list_state: ListState,
     status_message: String,
     should_quit: bool,
+    /// Password entered for this session, zeroized when the app is dropped
+    password: Option<SecureString>,
+    /// Password being typed, and the operation waiting for it
+    password_prompt: Option<(FileAction, SecureString)>,
+}
+
+/// Operation that needs the password
+#[derive(Clone, Copy)]
+enum FileAction {
+    Encrypt,
+    Decrypt,
 }
 
 /// File information for display
@@ -55,6 +67,8 @@ impl TuiApp {
             list_state: ListState::default(),
             status_message: "CargoCrypt File Browser - Press 'q' to quit".to_string(),
             should_quit: false,
+            password: None,
+            password_prompt: None,
         };
         app.refresh_files()?;
         Ok(app)
@@ -171,8 +185,50 @@ impl TuiApp {
         }
     }
 
+    /// Run `action`, asking for the session password first if there is none
+    async fn run_action(&mut self, action: FileAction, crypt: &Arc<CargoCrypt>) -> CryptoResult<()> {
+        let Some(password) = self.password.clone() else {
+            self.password_prompt = Some((action, SecureString::new()));
+            return Ok(());
+        };
+        match action {
+            FileAction::Encrypt => self.encrypt_selected_files(crypt, &password).await,
+            FileAction::Decrypt => self.decrypt_selected_files(crypt, &password).await,
+        }
+    }
+
+    /// Handle a key while the password prompt is open
+    async fn handle_password_key(&mut self, key: KeyCode, crypt: &Arc<CargoCrypt>) -> CryptoResult<()> {
+        let Some((_, input)) = self.password_prompt.as_mut() else { return Ok(()) };
+        match key {
+            KeyCode::Char(c) => input.push(c),
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Enter => {
+                let (action, input) = self.password_prompt.take().expect("prompt is open");
+                if input.is_empty() {
+                    self.status_message = "Password must not be empty".to_string();
+                } else {
+                    self.password = Some(input);
+                    self.run_action(action, crypt).await?;
+                }
+            }
+            KeyCode::Esc => {
+                self.password_prompt = None;
+                self.status_message = "Cancelled".to_string();
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
     /// Handle key events
     async fn handle_key(&mut self, key: KeyCode, crypt: &Arc<CargoCrypt>) -> CryptoResult<()> {
+        if self.password_prompt.is_some() {
+            return self.handle_password_key(key, crypt).await;
+        }
+
         match key {
             // Navigation
             KeyCode::Up | KeyCode::Char('k') => self.move_up(),
@@ -200,10 +256,14 @@ impl TuiApp {
             
             // File operations
             KeyCode::Char('e') => {
-                self.encrypt_selected_files(crypt).await?;
+                self.run_action(FileAction::Encrypt, crypt).await?;
             }
             KeyCode::Char('d') => {
-                self.decrypt_selected_files(crypt).await?;
+                self.run_action(FileAction::Decrypt, crypt).await?;
+            }
+            KeyCode::Char('p') => {
+                self.password = None;
+                self.status_message = "Password forgotten; the next operation asks for it again".to_string();
             }
             KeyCode::Char(' ') => {
                 self.toggle_selection();
@@ -226,7 +286,7 @@ impl TuiApp {
     }
 
     /// Encrypt selected files
-    async fn encrypt_selected_files(&mut self, crypt: &Arc<CargoCrypt>) -> CryptoResult<()> {
+    async fn encrypt_selected_files(&mut self, crypt: &Arc<CargoCrypt>, password: &str) -> CryptoResult<()> {
         let files_to_encrypt: Vec<PathBuf> = if self.selected_files.is_empty() {
             if let Some(file) = self.get_selected_file() {
                 if !file.is_directory && !file.is_encrypted {
@@ -252,33 +312,26 @@ impl TuiApp {
             return Ok(());
         }
 
-        let mut encrypted_count = 0;
-        for file_path in &files_to_encrypt {
-            // For demo purposes, use a default password
-            let password = "demo_password";
-            
-            match crypt.encrypt_file(file_path, password).await {
-                Ok(_) => {
-                    encrypted_count += 1;
-                }
-                Err(e) => {
-                    self.status_message = format!("Error encrypting {}: {}", file_path.display(), e);
-                    break;
-                }
-            }
-        }
+        let report = crypt
+            .encrypt_batch(&files_to_encrypt, password, BatchOptions::new().all_or_nothing(true))
+            .await;
         
         self.selected_files.clear();
         let _ = self.refresh_files();
         
-        if encrypted_count > 0 {
-            self.status_message = format!("Encrypted {} files", encrypted_count);
+        match report.failures().next() {
+            Some((file_path, e)) => {
+                self.status_message = format!("Error encrypting {}: {} (no files were changed)", file_path.display(), e);
+            }
+            None => {
+                self.status_message = format!("Encrypted {} files", report.encrypted().count());
+            }
         }
         Ok(())
     }
 
     /// Decrypt selected files
-    async fn decrypt_selected_files(&mut self, crypt: &Arc<CargoCrypt>) -> CryptoResult<()> {
+    async fn decrypt_selected_files(&mut self, crypt: &Arc<CargoCrypt>, password: &str) -> CryptoResult<()> {
         let files_to_decrypt: Vec<PathBuf> = if self.selected_files.is_empty() {
             if let Some(file) = self.get_selected_file() {
                 if !file.is_directory && file.is_encrypted {
@@ -303,8 +356,6 @@ impl TuiApp {
 
         let mut decrypted_count = 0;
         for file_path in &files_to_decrypt {
-            let password = "demo_password";
-            
             match crypt.decrypt_file(file_path, password).await {
                 Ok(_) => {
                     decrypted_count += 1;
@@ -388,14 +439,30 @@ fn render_ui(app: &TuiApp, frame: &mut Frame) {
     
     frame.render_stateful_widget(file_list, chunks[1], &mut list_state);
 
-    // Status
-    let status = Paragraph::new(app.status_message.as_str())
-        .style(Style::default().fg(Color::Green))
-        .block(Block::default().borders(Borders::ALL).title("Status"));
+    // Status, or the password prompt with one mask character per typed character
+    let status = match &app.password_prompt {
+        Some((action, input)) => {
+            let operation = match action {
+                FileAction::Encrypt => "encryption",
+                FileAction::Decrypt => "decryption",
+            };
+            Paragraph::new(format!("Password for {}: {}", operation, "*".repeat(input.chars().count())))
+                .style(Style::default().fg(Color::Yellow))
+                .block(Block::default().borders(Borders::ALL).title("Password"))
+        }
+        None => Paragraph::new(app.status_message.as_str())
+            .style(Style::default().fg(Color::Green))
+            .block(Block::default().borders(Borders::ALL).title("Status")),
+    };
     frame.render_widget(status, chunks[2]);
 
     // Help
-    let help = Paragraph::new("j/k: Navigate | Enter: Open | Space: Select | e: Encrypt | d: Decrypt | r: Refresh | q: Quit")
+    let help_text = if app.password_prompt.is_some() {
+        "Type the password | Enter: Confirm | Backspace: Delete | Esc: Cancel"
+    } else {
+        "j/k: Navigate | Enter: Open | Space: Select | e: Encrypt | d: Decrypt | p: Forget password | r: Refresh | q: Quit"
+    };
+    let help = Paragraph::new(help_text)
         .style(Style::default().fg(Color::Gray))
         .block(Block::default().borders(Borders::TOP));
     frame.render_widget(help, chunks[3]);
@@ -412,6 +479,12 @@ pub async fn run_simple_tui(crypt: Arc<CargoCrypt>) -> CryptoResult<()> {
 
     // Create app
     let mut app = TuiApp::new()?;
+    if let Ok(items) = crypt.expiring_items().await {
+        let due = items.iter().filter(|item| item.status.needs_attention()).count();
+        if due > 0 {
+            app.status_message = format!("⚠ {} secret(s) expired or expiring soon - run 'cargocrypt report expiring'", due);
+        }
+    }
 
     // Main loop
     loop {
@@ -0,0 +1,184 @@
+//! Named vaults: separate encrypted domains within one project
+//!
+//! A vault lives in `.cargocrypt/vaults/<name>/` and owns a random 32-byte
+//! key, kept in `key.enc` wrapped with the vault's own password. Files
+//! encrypted into a vault use that key the way a keyfile is used and record
+//! the vault name in their header, so `dev`, `staging` and `prod` secrets can
+//! share a repository while each needs its own password.
+//!
+//! Because files depend only on the vault key, changing a vault's password
+//! rewraps `key.enc` without touching any file, and an exported vault key is
+//! a keyfile that gives CI access to that vault and nothing else.
+
+use crate::error::{CargoCryptError, CryptoResult};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Directory holding the vaults, inside `.cargocrypt/`
+pub const VAULTS_DIR: &str = "vaults";
+
+/// Wrapped vault key inside a vault directory
+pub const VAULT_KEY_FILE: &str = "key.enc";
+
+/// Vault description inside a vault directory
+pub const VAULT_INFO_FILE: &str = "vault.toml";
+
+/// Longest accepted vault name
+const MAX_NAME_LENGTH: usize = 64;
+
+/// Check that `name` is usable as a vault name: lowercase letters, digits, `-` and `_`
+pub fn validate_vault_name(name: &str) -> CryptoResult<()> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_NAME_LENGTH
+        && name.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-' || b == b'_')
+        && !name.starts_with('-');
+    if valid {
+        return Ok(());
+    }
+    Err(CargoCryptError::Validation {
+        message: format!("Invalid vault name '{}'", name),
+        errors: vec![format!(
+            "Use up to {} lowercase letters, digits, '-' and '_', not starting with '-'",
+            MAX_NAME_LENGTH
+        )],
+        warnings: Vec::new(),
+    })
+}
+
+/// Description of a vault, stored in plain text next to its key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VaultInfo {
+    /// Creation time (Unix timestamp)
+    pub created_at: u64,
+    /// What the vault is for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// A named vault of a project
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Vault {
+    name: String,
+    dir: PathBuf,
+}
+
+impl Vault {
+    /// Directory of the vault `name` in the project at `project_root`
+    pub fn dir_for<P: AsRef<Path>>(project_root: P, name: &str) -> PathBuf {
+        project_root.as_ref().join(".cargocrypt").join(VAULTS_DIR).join(name)
+    }
+
+    /// Describe a vault that does not exist yet
+    pub(crate) fn new<P: AsRef<Path>>(project_root: P, name: &str) -> CryptoResult<Self> {
+        validate_vault_name(name)?;
+        Ok(Self {
+            name: name.to_string(),
+            dir: Self::dir_for(project_root, name),
+        })
+    }
+
+    /// Open an existing vault
+    pub fn open<P: AsRef<Path>>(project_root: P, name: &str) -> CryptoResult<Self> {
+        let vault = Self::new(project_root, name)?;
+        if !vault.key_path().exists() {
+            return Err(CargoCryptError::Config {
+                message: format!("Vault '{}' does not exist", name),
+                suggestion: Some(format!("Create it with `cargocrypt vault create {}`", name)),
+            });
+        }
+        Ok(vault)
+    }
+
+    /// All vaults of the project, sorted by name
+    pub fn list<P: AsRef<Path>>(project_root: P) -> CryptoResult<Vec<Self>> {
+        let project_root = project_root.as_ref();
+        let vaults_dir = project_root.join(".cargocrypt").join(VAULTS_DIR);
+        if !vaults_dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut vaults = Vec::new();
+        for entry in std::fs::read_dir(vaults_dir)? {
+            let entry = entry?;
+            let Some(name) = entry.file_name().to_str().map(str::to_string) else { continue };
+            if let Ok(vault) = Self::open(project_root, &name) {
+                vaults.push(vault);
+            }
+        }
+        vaults.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(vaults)
+    }
+
+    /// Name of the vault
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Directory of the vault
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Path of the wrapped vault key
+    pub fn key_path(&self) -> PathBuf {
+        self.dir.join(VAULT_KEY_FILE)
+    }
+
+    /// Path of the vault's secret store
+    pub fn secrets_path(&self) -> PathBuf {
+        self.dir.join(crate::crypto::store::SECRETS_FILE)
+    }
+
+    /// Read the vault description
+    pub fn info(&self) -> CryptoResult<VaultInfo> {
+        let path = self.dir.join(VAULT_INFO_FILE);
+        Ok(toml::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Write the vault description
+    pub(crate) fn save_info(&self, info: &VaultInfo) -> CryptoResult<()> {
+        let contents = toml::to_string_pretty(info).map_err(|e| CargoCryptError::Serialization {
+            message: format!("Failed to serialize vault info: {}", e),
+            source: Box::new(e),
+        })?;
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(self.dir.join(VAULT_INFO_FILE), contents)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_vault_names() {
+        for name in ["dev", "prod-eu", "team_2"] {
+            assert!(validate_vault_name(name).is_ok(), "{}", name);
+        }
+        for name in ["", "Prod", "../prod", "a/b", "-x", &"v".repeat(65)] {
+            assert!(validate_vault_name(name).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_open_and_list() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        assert!(Vault::list(root).unwrap().is_empty());
+        assert!(Vault::open(root, "prod").is_err());
+
+        for name in ["prod", "dev"] {
+            let vault = Vault::new(root, name).unwrap();
+            vault.save_info(&VaultInfo { created_at: 1, description: None }).unwrap();
+            std::fs::write(vault.key_path(), b"wrapped").unwrap();
+        }
+        // A directory without a key is not a vault
+        std::fs::create_dir_all(Vault::dir_for(root, "stray")).unwrap();
+
+        let names: Vec<_> = Vault::list(root).unwrap().iter().map(|vault| vault.name().to_string()).collect();
+        assert_eq!(names, ["dev", "prod"]);
+        assert_eq!(Vault::open(root, "dev").unwrap().info().unwrap().created_at, 1);
+    }
+}
@@ -1,8 +1,27 @@
 //! Integration tests for CargoCrypt core functionality
 
+use cargocrypt::crypto::Kdf;
 use cargocrypt::{CargoCrypt, CryptoConfig, SecretBytes};
 use tempfile::TempDir;
 use std::fs;
+use std::path::Path;
+
+/// Default config with a cheap KDF, so tests do not spend their time deriving keys
+fn fast_config() -> CryptoConfig {
+    let mut config = CryptoConfig::default();
+    config.key_params.kdf = Kdf::Argon2idCustom { m_cost: 8192, t_cost: 1, p_cost: 1 };
+    config
+}
+
+/// CargoCrypt for the project at `dir` with `config`
+async fn crypt_with(dir: &Path, config: CryptoConfig) -> CargoCrypt {
+    CargoCrypt::builder().project_root(dir).config(config).build().await.unwrap()
+}
+
+/// CargoCrypt for the project at `dir` with [`fast_config`]
+async fn fast_crypt(dir: &Path) -> CargoCrypt {
+    crypt_with(dir, fast_config()).await
+}
 
 #[tokio::test]
 async fn test_zero_config_initialization() {
@@ -91,4 +110,726 @@ async fn test_algorithm_properties() {
     assert_eq!(alg.tag_length(), 16);
     assert!(alg.is_authenticated());
     assert_eq!(alg.to_string(), "ChaCha20-Poly1305");
-}
\ No newline at end of file
+}
+#[tokio::test]
+async fn test_encrypt_file_in_place() {
+    let temp_dir = TempDir::new().unwrap();
+    let crypt = fast_crypt(temp_dir.path()).await;
+
+    for shred in [false, true] {
+        let plaintext_path = temp_dir.path().join(format!("secret-{}.txt", shred));
+        fs::write(&plaintext_path, "in-place secret").unwrap();
+
+        let encrypted_path = crypt.encrypt_file_in_place(&plaintext_path, "InPlace_Password123!", shred).await.unwrap();
+        assert!(!plaintext_path.exists(), "plaintext should be removed");
+        assert!(encrypted_path.exists());
+
+        let decrypted_path = crypt.decrypt_file(&encrypted_path, "InPlace_Password123!").await.unwrap();
+        assert_eq!(fs::read_to_string(decrypted_path).unwrap(), "in-place secret");
+    }
+
+    // Neither backups nor temporary files are left behind, only the lock directory
+    let mut names: Vec<String> = fs::read_dir(temp_dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| name != ".cargocrypt")
+        .collect();
+    names.sort();
+    assert_eq!(names, ["secret-false.txt", "secret-false.txt.enc", "secret-true.txt", "secret-true.txt.enc"]);
+}
+
+#[tokio::test]
+async fn test_encrypt_reader_to_writer() {
+    let temp_dir = TempDir::new().unwrap();
+    let crypt = fast_crypt(temp_dir.path()).await;
+
+    let input = b"piped secret data".to_vec();
+    let mut encrypted = Vec::new();
+    let written = crypt.encrypt_reader_to_writer(&input[..], &mut encrypted, "Pipe_Password123!").await.unwrap();
+    assert_eq!(written, encrypted.len() as u64);
+    assert!(encrypted.starts_with(b"CCRYPT"));
+
+    let mut decrypted = Vec::new();
+    crypt.decrypt_reader_to_writer(&encrypted[..], &mut decrypted, "Pipe_Password123!").await.unwrap();
+    assert_eq!(decrypted, input);
+
+    let mut output = Vec::new();
+    assert!(crypt.decrypt_reader_to_writer(&encrypted[..], &mut output, "Wrong_Password123!").await.is_err());
+    assert!(output.is_empty(), "nothing should be written when decryption fails");
+}
+
+#[tokio::test]
+async fn test_encrypt_batch() {
+    use cargocrypt::{BatchFileOutcome, BatchOptions};
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = fast_config();
+    // The batches below replace c.txt.enc and then their own output
+    config.file_ops.overwrite_existing = true;
+    let crypt = crypt_with(temp_dir.path(), config).await;
+
+    let paths: Vec<_> = ["a.txt", "b.txt", "missing.txt", "c.txt"]
+        .iter()
+        .map(|name| temp_dir.path().join(name))
+        .collect();
+    for path in paths.iter().filter(|path| !path.ends_with("missing.txt")) {
+        fs::write(path, "batch secret").unwrap();
+    }
+    let enc = |name: &str| temp_dir.path().join(format!("{}.enc", name));
+    fs::write(enc("c.txt"), "previous ciphertext").unwrap();
+
+    // All or nothing: the missing file rolls back the others
+    let report = crypt
+        .encrypt_batch(&paths, "Batch_Password123!", BatchOptions::new().concurrency(2).all_or_nothing(true))
+        .await;
+    assert!(report.rolled_back);
+    assert!(!report.is_success());
+    assert_eq!(report.failures().count(), 1);
+    assert!(matches!(report.files[0].1, BatchFileOutcome::RolledBack));
+    assert!(matches!(report.files[2].1, BatchFileOutcome::Failed(_)));
+    assert!(!enc("a.txt").exists() && !enc("b.txt").exists());
+    assert_eq!(fs::read_to_string(enc("c.txt")).unwrap(), "previous ciphertext");
+
+    // Per file: everything but the missing file is encrypted, in input order
+    let report = crypt.encrypt_batch(&paths, "Batch_Password123!", BatchOptions::new()).await;
+    assert!(!report.rolled_back);
+    assert_eq!(report.encrypted().collect::<Vec<_>>(), [enc("a.txt"), enc("b.txt"), enc("c.txt")]);
+    assert_eq!(report.failures().next().unwrap().0, paths[2]);
+
+    let decrypted = crypt.decrypt_file(enc("c.txt"), "Batch_Password123!").await.unwrap();
+    assert_eq!(fs::read_to_string(decrypted).unwrap(), "batch secret");
+
+    // A complete all-or-nothing batch writes every file and leaves no temporaries
+    let report = crypt
+        .encrypt_batch(&paths[..2], "Batch_Password123!", BatchOptions::new().all_or_nothing(true))
+        .await;
+    assert!(report.is_success());
+    assert!(fs::read_dir(temp_dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .all(|name| name == ".cargocrypt" || !name.starts_with('.')));
+}
+
+#[tokio::test]
+async fn test_encrypt_directory_files() {
+    use cargocrypt::progress::Progress;
+    use cargocrypt::BatchOptions;
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = fast_config();
+    config.file_ops.backup_originals = false;
+    let crypt = crypt_with(temp_dir.path(), config).await;
+
+    let dir = temp_dir.path().join("secrets");
+    for name in ["b.txt", "nested/a.key", "done.txt", "done.txt.enc", ".hidden", ".git/config", "old.txt.backup"] {
+        let path = dir.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, name).unwrap();
+    }
+    let files = crypt.files_to_encrypt(&dir).await.unwrap();
+    assert_eq!(files, [dir.join("b.txt"), dir.join("nested/a.key")]);
+    assert!(crypt.files_to_encrypt(dir.join("b.txt")).await.is_err());
+
+    let report = crypt
+        .encrypt_batch(&files, "Directory_Password123!", BatchOptions::new().progress(Progress::hidden()))
+        .await;
+    assert!(report.is_success());
+    assert!(crypt.files_to_encrypt(&dir).await.unwrap().is_empty());
+
+    crypt.update_config(|config| config.file_ops.overwrite_existing = true).await;
+    assert_eq!(crypt.files_to_encrypt(&dir).await.unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn test_encrypt_refuses_to_overwrite() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = fast_config();
+    config.file_ops.backup_originals = false;
+    let crypt = crypt_with(temp_dir.path(), config).await;
+
+    let path = temp_dir.path().join("secret.txt");
+    fs::write(&path, "first").unwrap();
+    let encrypted_path = crypt.encrypt_file(&path, "Overwrite_Password123!").await.unwrap();
+    let first = fs::read(&encrypted_path).unwrap();
+
+    fs::write(&path, "second").unwrap();
+    let error = crypt.encrypt_file(&path, "Overwrite_Password123!").await.unwrap_err();
+    assert!(error.to_string().contains("already exists"), "{}", error);
+    assert!(crypt.encrypt_file_in_place(&path, "Overwrite_Password123!", true).await.is_err());
+    assert_eq!(fs::read(&encrypted_path).unwrap(), first);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "second", "plaintext must survive a refused in-place encryption");
+
+    crypt.update_config(|config| config.file_ops.overwrite_existing = true).await;
+    crypt.encrypt_file(&path, "Overwrite_Password123!").await.unwrap();
+    fs::remove_file(&path).unwrap();
+    let decrypted = crypt.decrypt_file(&encrypted_path, "Overwrite_Password123!").await.unwrap();
+    assert_eq!(fs::read_to_string(decrypted).unwrap(), "second");
+}
+
+#[tokio::test]
+async fn test_decrypt_to_memory() {
+    use cargocrypt::crypto::Keyfile;
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = fast_config();
+    config.file_ops.backup_originals = false;
+    let crypt = crypt_with(temp_dir.path(), config).await;
+
+    let path = temp_dir.path().join("api.key");
+    fs::write(&path, "sk_live_123").unwrap();
+    let encrypted_path = crypt.encrypt_file_in_place(&path, "Memory_Password123!", false).await.unwrap();
+
+    let plaintext = crypt.decrypt_to_memory(&encrypted_path, "Memory_Password123!").await.unwrap();
+    assert_eq!(plaintext.as_string().unwrap(), "sk_live_123");
+    assert!(!path.exists(), "decrypting to memory must not write the plaintext");
+    assert_eq!(plaintext.into_secure_buffer().as_slice(), b"sk_live_123");
+    assert!(crypt.decrypt_to_memory(&encrypted_path, "Wrong_Password123!").await.is_err());
+
+    let keyfile = Keyfile::generate().unwrap();
+    let keyed = temp_dir.path().join("token.txt");
+    fs::write(&keyed, "ci-token").unwrap();
+    let keyed_encrypted = crypt.encrypt_file_with_keyfile(&keyed, &keyfile, None).await.unwrap();
+    let plaintext = crypt.decrypt_to_memory_with_keyfile(&keyed_encrypted, &keyfile, None).await.unwrap();
+    assert_eq!(plaintext.as_bytes(), b"ci-token");
+}
+
+#[tokio::test]
+async fn test_upgrade_reencrypts_files_with_weaker_kdf() {
+    use cargocrypt::crypto::{EncryptedSecret, Keyfile};
+    use cargocrypt::UpgradeOutcome;
+
+    let temp_dir = TempDir::new().unwrap();
+    let build = |kdf: Kdf| {
+        let mut config = fast_config();
+        config.key_params.kdf = kdf;
+        config.file_ops.backup_originals = false;
+        crypt_with(temp_dir.path(), config)
+    };
+    let weak = fast_config().key_params.kdf;
+    let strong = Kdf::Argon2idCustom { m_cost: 16384, t_cost: 2, p_cost: 1 };
+
+    let old = build(weak).await;
+    let path = temp_dir.path().join("db.env");
+    fs::write(&path, "DB_PASSWORD=hunter2").unwrap();
+    let encrypted_path = old.encrypt_file_in_place(&path, "Upgrade_Password123!", false).await.unwrap();
+    let other = temp_dir.path().join("other.env");
+    fs::write(&other, "OTHER=1").unwrap();
+    let other_encrypted = old.encrypt_file_in_place(&other, "Other_Password123!", false).await.unwrap();
+    let keyed = temp_dir.path().join("ci.env");
+    fs::write(&keyed, "CI=1").unwrap();
+    old.encrypt_file_with_keyfile_in_place(&keyed, &Keyfile::generate().unwrap(), None, false).await.unwrap();
+
+    // Nothing to do while the configuration has not changed
+    assert!(old.outdated_files().await.unwrap().is_empty());
+
+    let new = build(strong).await;
+    let outdated = new.outdated_files().await.unwrap();
+    assert_eq!(outdated.len(), 2, "keyfile-only files have no KDF to upgrade: {:?}", outdated);
+    assert!(outdated.iter().all(|(_, kdf)| *kdf == weak));
+
+    let report = new.upgrade_files("Upgrade_Password123!").await.unwrap();
+    assert_eq!(report.upgraded(), 1);
+    assert!(!report.is_success());
+    let failed = report.files.iter().find(|(_, outcome)| matches!(outcome, UpgradeOutcome::Failed(_))).unwrap();
+    assert_eq!(failed.0, other_encrypted);
+    assert_eq!(*EncryptedSecret::from_bytes(&fs::read(&other_encrypted).unwrap()).unwrap().kdf(), weak);
+
+    assert_eq!(*EncryptedSecret::from_bytes(&fs::read(&encrypted_path).unwrap()).unwrap().kdf(), strong);
+    let plaintext = new.decrypt_to_memory(&encrypted_path, "Upgrade_Password123!").await.unwrap();
+    assert_eq!(plaintext.as_bytes(), b"DB_PASSWORD=hunter2");
+    assert_eq!(new.outdated_files().await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_large_files_are_encrypted_in_chunks() {
+    use cargocrypt::crypto::{defaults, EncryptedSecret};
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = fast_config();
+    config.file_ops.chunk_threshold = 1024 * 1024;
+    config.file_ops.backup_originals = false;
+    let crypt = crypt_with(temp_dir.path(), config).await;
+
+    let small = temp_dir.path().join("small.sql");
+    fs::write(&small, "select 1;").unwrap();
+    let small_encrypted = crypt.encrypt_file(&small, "Chunked_Password123!").await.unwrap();
+    assert!(EncryptedSecret::from_bytes(&fs::read(&small_encrypted).unwrap()).unwrap().chunk_layout().is_none());
+
+    let dump = temp_dir.path().join("dump.sql");
+    let data: Vec<u8> = (0..2 * defaults::CHUNK_SIZE + 1).map(|i| (i % 253) as u8).collect();
+    fs::write(&dump, &data).unwrap();
+    let encrypted_path = crypt.encrypt_file(&dump, "Chunked_Password123!").await.unwrap();
+    let encrypted = EncryptedSecret::from_bytes(&fs::read(&encrypted_path).unwrap()).unwrap();
+    assert_eq!(encrypted.chunk_layout().map(|layout| layout.chunks), Some(3));
+
+    fs::remove_file(&dump).unwrap();
+    let decrypted = crypt.decrypt_file(&encrypted_path, "Chunked_Password123!").await.unwrap();
+    assert_eq!(fs::read(decrypted).unwrap(), data);
+}
+
+#[tokio::test]
+async fn test_decrypt_restores_file_metadata() {
+    use cargocrypt::crypto::EncryptedSecret;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let temp_dir = TempDir::new().unwrap();
+    let crypt = fast_crypt(temp_dir.path()).await;
+
+    let path = temp_dir.path().join("db.toml");
+    fs::write(&path, "password = \"hunter2\"").unwrap();
+    let mtime = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    fs::File::options().write(true).open(&path).unwrap().set_modified(mtime).unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+    }
+
+    let encrypted_path = crypt.encrypt_file_in_place(&path, "Metadata_Password123!", false).await.unwrap();
+    let header = EncryptedSecret::from_bytes(&fs::read(&encrypted_path).unwrap()).unwrap();
+    assert!(header.metadata().file.is_some());
+
+    let decrypted_path = crypt.decrypt_file(&encrypted_path, "Metadata_Password123!").await.unwrap();
+    let metadata = fs::metadata(&decrypted_path).unwrap();
+    assert_eq!(metadata.modified().unwrap(), mtime);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+    }
+}
+
+#[tokio::test]
+async fn test_vault_encrypt_decrypt() {
+    let temp_dir = TempDir::new().unwrap();
+    let crypt = fast_crypt(temp_dir.path()).await;
+
+    crypt.create_vault("prod", "Prod_Password123!", Some("Production".to_string())).await.unwrap();
+    crypt.create_vault("dev", "Dev_Password123!", None).await.unwrap();
+    assert!(crypt.create_vault("prod", "Prod_Password123!", None).await.is_err());
+    let names: Vec<_> = crypt.vaults().unwrap().iter().map(|vault| vault.name().to_string()).collect();
+    assert_eq!(names, ["dev", "prod"]);
+
+    let path = temp_dir.path().join("prod.env");
+    fs::write(&path, "DATABASE_URL=postgres://prod").unwrap();
+    let encrypted_path = crypt.encrypt_file_in_vault(&path, "prod", "Prod_Password123!").await.unwrap();
+    assert_eq!(crypt.file_vault(&encrypted_path).await.unwrap().as_deref(), Some("prod"));
+    fs::remove_file(&path).unwrap();
+
+    // Neither another vault, a wrong password nor the plain password path opens it
+    assert!(crypt.decrypt_file_in_vault(&encrypted_path, "dev", "Dev_Password123!").await.is_err());
+    assert!(crypt.decrypt_file_in_vault(&encrypted_path, "prod", "Dev_Password123!").await.is_err());
+    assert!(crypt.decrypt_file(&encrypted_path, "Prod_Password123!").await.is_err());
+
+    // Changing the vault password leaves its files readable
+    crypt.change_vault_password("prod", "Prod_Password123!", "New_Prod_Password123!").await.unwrap();
+    assert!(crypt.unlock_vault("prod", "Prod_Password123!").await.is_err());
+    let decrypted_path = crypt.decrypt_file_in_vault(&encrypted_path, "prod", "New_Prod_Password123!").await.unwrap();
+    assert_eq!(fs::read_to_string(decrypted_path).unwrap(), "DATABASE_URL=postgres://prod");
+}
+
+#[tokio::test]
+async fn test_backup_restores_project_state() {
+    let temp_dir = TempDir::new().unwrap();
+    let crypt = fast_crypt(temp_dir.path()).await;
+
+    crypt.create_vault("prod", "Vault_Password123!", None).await.unwrap();
+    let state_dir = temp_dir.path().join(".cargocrypt");
+    fs::write(state_dir.join("config.toml"), "[file_ops]\n").unwrap();
+    let backup = crypt.create_backup(None, "Backup_Password123!").await.unwrap();
+    assert!(backup.path.starts_with(cargocrypt::backup::backups_dir(temp_dir.path())));
+    assert!(backup.files.iter().any(|entry| entry.path.ends_with("prod/key.enc")));
+    assert!(!backup.files.iter().any(|entry| entry.path.starts_with("locks")));
+    assert_eq!(cargocrypt::backup::list(temp_dir.path()).unwrap().len(), 1);
+
+    let key_path = cargocrypt::vault::Vault::dir_for(temp_dir.path(), "prod").join("key.enc");
+    let key = fs::read(&key_path).unwrap();
+    fs::remove_file(&key_path).unwrap();
+    fs::write(state_dir.join("config.toml"), "[file_ops]\nbackup_originals = false\n").unwrap();
+
+    assert!(crypt.read_backup(&backup.path, "Wrong_Password123!").await.is_err());
+    let plan = crypt.plan_restore_backup(&backup.path, "Backup_Password123!").await.unwrap();
+    assert_eq!(plan.len(), 2);
+    assert!(crypt.restore_backup(&backup.path, "Backup_Password123!", false).await.is_err());
+    assert!(!key_path.exists(), "nothing is restored while a changed file is in the way");
+
+    crypt.restore_backup(&backup.path, "Backup_Password123!", true).await.unwrap();
+    assert_eq!(fs::read(&key_path).unwrap(), key);
+    assert_eq!(fs::read_to_string(state_dir.join("config.toml")).unwrap(), "[file_ops]\n");
+    crypt.unlock_vault("prod", "Vault_Password123!").await.unwrap();
+}
+
+#[tokio::test]
+async fn test_init_writes_wizard_choices() {
+    use cargocrypt::init::{InitOptions, KeyMode, DEFAULT_KEYFILE};
+    use cargocrypt::PerformanceProfile;
+
+    let temp_dir = TempDir::new().unwrap();
+    let original_dir = std::env::current_dir().unwrap();
+    fs::write(temp_dir.path().join("Cargo.toml"), "[package]\nname = \"wizard\"\nversion = \"0.1.0\"\n").unwrap();
+    let options = InitOptions { profile: Some(PerformanceProfile::Fast), key_mode: Some(KeyMode::Keyfile) };
+
+    std::env::set_current_dir(temp_dir.path()).unwrap();
+    let first = CargoCrypt::init_project_with(&options).await;
+    let keyfile = fs::read(temp_dir.path().join(DEFAULT_KEYFILE));
+    let second = CargoCrypt::init_project_with(&options).await;
+    std::env::set_current_dir(original_dir).unwrap();
+    first.unwrap();
+    second.unwrap();
+
+    let config = CryptoConfig::load_project(temp_dir.path()).unwrap();
+    assert_eq!(config.performance_profile, PerformanceProfile::Fast);
+    assert_eq!(config.key_params.kdf, PerformanceProfile::Fast.kdf());
+    assert_eq!(config.key_params.memory_cost, 4096);
+    assert_eq!(config.keys.mode, KeyMode::Keyfile);
+    assert_eq!(config.keys.keyfile.as_deref(), Some(std::path::Path::new(DEFAULT_KEYFILE)));
+    assert_eq!(
+        fs::read(temp_dir.path().join(DEFAULT_KEYFILE)).unwrap(),
+        keyfile.unwrap(),
+        "an existing keyfile is kept"
+    );
+}
+
+#[tokio::test]
+async fn test_change_password_rewraps_keys() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = fast_config();
+    config.file_ops.backup_originals = false;
+    let crypt = crypt_with(temp_dir.path(), config).await;
+
+    crypt.create_vault("prod", "Team_Password123!", None).await.unwrap();
+    crypt.create_vault("dev", "Dev_Password123!", None).await.unwrap();
+    let signing_key = crypt.load_or_create_signing_key("Team_Password123!").await.unwrap();
+
+    let vault_file = temp_dir.path().join("prod.env");
+    fs::write(&vault_file, "DATABASE_URL=postgres://prod").unwrap();
+    let vault_encrypted = crypt.encrypt_file_in_vault_in_place(&vault_file, "prod", "Team_Password123!", false).await.unwrap();
+    let password_file = temp_dir.path().join("legacy.env");
+    fs::write(&password_file, "TOKEN=legacy").unwrap();
+    let password_encrypted = crypt.encrypt_file(&password_file, "Team_Password123!").await.unwrap();
+    let vault_bytes = fs::read(&vault_encrypted).unwrap();
+
+    assert!(crypt.change_password("Wrong_Password123!", "New_Team_Password123!").await.is_err());
+    let change = crypt.change_password("Team_Password123!", "New_Team_Password123!").await.unwrap();
+    assert_eq!(change.rewrapped, [
+        cargocrypt::vault::Vault::dir_for(temp_dir.path(), "prod").join("key.enc"),
+        crypt.signing_key_path(),
+    ]);
+    assert_eq!(change.skipped, [cargocrypt::vault::Vault::dir_for(temp_dir.path(), "dev").join("key.enc")]);
+    assert_eq!(change.password_files, [password_encrypted]);
+
+    // Files are untouched and open with the new password only
+    assert_eq!(fs::read(&vault_encrypted).unwrap(), vault_bytes);
+    assert!(crypt.unlock_vault("prod", "Team_Password123!").await.is_err());
+    crypt.unlock_vault("dev", "Dev_Password123!").await.unwrap();
+    let decrypted = crypt.decrypt_file_in_vault(&vault_encrypted, "prod", "New_Team_Password123!").await.unwrap();
+    assert_eq!(fs::read_to_string(decrypted).unwrap(), "DATABASE_URL=postgres://prod");
+    let reloaded = crypt.load_or_create_signing_key("New_Team_Password123!").await.unwrap();
+    assert_eq!(reloaded.fingerprint(), signing_key.fingerprint());
+}
+
+#[tokio::test]
+async fn test_decrypt_failures_are_uniform() {
+    use cargocrypt::core::{DECRYPTION_FAILED, DECRYPT_FAILURE_FLOOR};
+    use std::time::Instant;
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = fast_config();
+    assert!(config.security.constant_time_operations);
+    let crypt = crypt_with(temp_dir.path(), config.clone()).await;
+
+    let path = temp_dir.path().join("secret.txt");
+    fs::write(&path, "uniform failures").unwrap();
+    let encrypted_path = crypt.encrypt_file(&path, "Uniform_Password123!").await.unwrap();
+    let bytes = fs::read(&encrypted_path).unwrap();
+
+    let mut corrupted = bytes.clone();
+    *corrupted.last_mut().unwrap() ^= 0x01;
+    let corrupted_path = temp_dir.path().join("corrupted.enc");
+    fs::write(&corrupted_path, corrupted).unwrap();
+    let truncated_path = temp_dir.path().join("truncated.enc");
+    fs::write(&truncated_path, &bytes[..bytes.len() / 2]).unwrap();
+
+    // Timings are too noisy to compare in CI; the floor is what can be checked
+    let cases = [
+        (&encrypted_path, "Wrong_Password123!"),
+        (&corrupted_path, "Uniform_Password123!"),
+        (&truncated_path, "Uniform_Password123!"),
+    ];
+    for (path, password) in cases {
+        let start = Instant::now();
+        let error = crypt.decrypt_file(path, password).await.unwrap_err();
+        assert!(start.elapsed() >= DECRYPT_FAILURE_FLOOR);
+        assert!(error.to_string().contains(DECRYPTION_FAILED), "{}", error);
+        assert!(error.is_authentication_failure(), "{:?}", error);
+    }
+
+    // Without the setting a truncated file reports what is wrong with it
+    config.security.constant_time_operations = false;
+    let crypt = crypt_with(temp_dir.path(), config).await;
+    let error = crypt.decrypt_file(&truncated_path, "Uniform_Password123!").await.unwrap_err();
+    assert!(!error.to_string().contains(DECRYPTION_FAILED), "{}", error);
+}
+
+#[tokio::test]
+async fn test_locked_files_are_not_touched() {
+    use cargocrypt::lock::FileLock;
+    use cargocrypt::CargoCryptError;
+
+    let temp_dir = TempDir::new().unwrap();
+    let crypt = fast_crypt(temp_dir.path()).await;
+
+    let path = temp_dir.path().join("db.env");
+    let encrypted_path = temp_dir.path().join("db.env.enc");
+    fs::write(&path, "DB_PASSWORD=hunter2").unwrap();
+
+    // Another process is working on the same file
+    let held = FileLock::path(temp_dir.path(), &encrypted_path).await.unwrap();
+    let error = crypt.encrypt_file(&path, "Locked_Password123!").await.unwrap_err();
+    assert!(matches!(error, CargoCryptError::Locked { .. }), "{}", error);
+    assert!(error.is_recoverable() && error.suggestion().unwrap().contains("--wait"));
+    assert!(!encrypted_path.exists());
+
+    drop(held);
+    crypt.encrypt_file(&path, "Locked_Password123!").await.unwrap();
+
+    let _held = FileLock::path(temp_dir.path(), &encrypted_path).await.unwrap();
+    let error = crypt.decrypt_file(&encrypted_path, "Locked_Password123!").await.unwrap_err();
+    assert!(matches!(error, CargoCryptError::Locked { .. }), "{}", error);
+}
+
+#[tokio::test]
+async fn test_managed_files_are_reencrypted_and_decrypted() {
+    use cargocrypt::managed::{ManagedKeys, ManagedOutcome, ManagedState};
+
+    let temp_dir = TempDir::new().unwrap();
+    let crypt = fast_crypt(temp_dir.path()).await;
+
+    let path = temp_dir.path().join("db.env");
+    fs::write(&path, "DB_PASSWORD=hunter2").unwrap();
+    let encrypted_path = crypt.encrypt_file(&path, "Managed_Password123!").await.unwrap();
+
+    let managed = crypt.managed_files().unwrap();
+    let file = managed.get("db.env").unwrap();
+    assert_eq!(file.encrypted, std::path::PathBuf::from("db.env.enc"));
+    assert!(file.encrypted_at.is_some());
+    assert_eq!(file.state(temp_dir.path()), ManagedState::Decrypted);
+
+    // Nothing to do until the plaintext changes or a key is given
+    let keys = ManagedKeys { password: Some("Managed_Password123!".into()), ..Default::default() };
+    let report = crypt.encrypt_managed(&keys, false, false).await.unwrap();
+    assert!(matches!(report.files[0].1, ManagedOutcome::Skipped(_)));
+
+    fs::write(&path, "DB_PASSWORD=changed").unwrap();
+    let encrypted = fs::File::options().write(true).open(&encrypted_path).unwrap();
+    encrypted.set_modified(std::time::SystemTime::UNIX_EPOCH).unwrap();
+    assert_eq!(crypt.managed_files().unwrap().files[0].state(temp_dir.path()), ManagedState::Modified);
+
+    // Edits are never overwritten by a decryption
+    let report = crypt.decrypt_managed(&keys).await.unwrap();
+    assert!(matches!(report.files[0].1, ManagedOutcome::Skipped(_)));
+    assert_eq!(fs::read_to_string(&path).unwrap(), "DB_PASSWORD=changed");
+
+    let report = crypt.encrypt_managed(&ManagedKeys::default(), false, false).await.unwrap();
+    assert!(matches!(report.files[0].1, ManagedOutcome::Skipped(_)));
+
+    let report = crypt.encrypt_managed(&keys, true, false).await.unwrap();
+    assert!(report.is_success() && report.written() == 1);
+    assert!(!path.exists());
+    assert_eq!(crypt.managed_files().unwrap().files[0].state(temp_dir.path()), ManagedState::Encrypted);
+
+    let report = crypt.decrypt_managed(&keys).await.unwrap();
+    assert!(report.is_success() && report.written() == 1);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "DB_PASSWORD=changed");
+    assert!(encrypted_path.exists());
+    let file = crypt.managed_files().unwrap().files[0].clone();
+    assert!(file.decrypted_at.is_some());
+    assert_eq!(file.state(temp_dir.path()), ManagedState::Decrypted);
+}
+
+#[tokio::test]
+async fn test_opened_files_are_reencrypted_when_closed() {
+    use cargocrypt::SessionClose;
+    use std::time::Duration;
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = fast_config();
+    config.file_ops.backup_originals = false;
+    let crypt = crypt_with(temp_dir.path(), config).await;
+
+    let path = temp_dir.path().join("db.env");
+    fs::write(&path, "DB_PASSWORD=hunter2").unwrap();
+    let encrypted_path = crypt.encrypt_file_in_place(&path, "Session_Password123!", false).await.unwrap();
+
+    let password = Some("Session_Password123!");
+    let session = crypt.open_file(&encrypted_path, password, None, Duration::from_secs(600), false).await.unwrap();
+    assert!(!session.plaintext.starts_with(temp_dir.path()));
+    assert_eq!(fs::read_to_string(&session.plaintext).unwrap(), "DB_PASSWORD=hunter2");
+    assert!(crypt.open_file(&encrypted_path, password, None, Duration::from_secs(600), true).await.is_err());
+    assert_eq!(crypt.sessions().unwrap(), [session.clone()]);
+
+    // Changes need the right password to be encrypted again
+    fs::write(&session.plaintext, "DB_PASSWORD=changed").unwrap();
+    assert!(crypt.close_file(&session, None, false).await.is_err());
+    assert!(crypt.close_file(&session, Some("Other_Password123!"), false).await.is_err());
+    assert_eq!(crypt.close_file(&session, password, false).await.unwrap(), SessionClose::Reencrypted);
+    assert!(!session.plaintext.exists());
+    assert!(crypt.sessions().unwrap().is_empty());
+    let decrypted = crypt.decrypt_to_memory(&encrypted_path, "Session_Password123!").await.unwrap();
+    assert_eq!(decrypted.as_bytes(), b"DB_PASSWORD=changed");
+
+    // An unchanged plaintext is only removed
+    let session = crypt.open_file(&encrypted_path, password, None, Duration::from_secs(600), true).await.unwrap();
+    assert_eq!(session.plaintext, path);
+    assert_eq!(crypt.close_file(&session, None, true).await.unwrap(), SessionClose::Unchanged);
+    assert!(!path.exists());
+}
+
+#[tokio::test]
+async fn test_close_shreds_the_plaintext_after_reencrypting() {
+    use cargocrypt::SessionClose;
+    use std::time::Duration;
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = fast_config();
+    config.file_ops.backup_originals = false;
+    let crypt = crypt_with(temp_dir.path(), config).await;
+
+    let path = temp_dir.path().join("db.env");
+    fs::write(&path, "DB_PASSWORD=hunter2").unwrap();
+    let encrypted_path = crypt.encrypt_file_in_place(&path, "Session_Password123!", false).await.unwrap();
+    let before = fs::read(&encrypted_path).unwrap();
+
+    let password = Some("Session_Password123!");
+    let session = crypt.open_file(&encrypted_path, password, None, Duration::from_secs(600), true).await.unwrap();
+    fs::write(&session.plaintext, "DB_PASSWORD=shredded").unwrap();
+    assert_eq!(crypt.close_file(&session, password, true).await.unwrap(), SessionClose::Reencrypted);
+
+    assert!(!path.exists());
+    assert!(crypt.sessions().unwrap().is_empty());
+    let after = fs::read(&encrypted_path).unwrap();
+    assert_ne!(after, before);
+    assert!(!String::from_utf8_lossy(&after).contains("shredded"));
+    let decrypted = crypt.decrypt_to_memory(&encrypted_path, "Session_Password123!").await.unwrap();
+    assert_eq!(decrypted.as_bytes(), b"DB_PASSWORD=shredded");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_edit_reencrypts_the_editors_changes() {
+    use std::io::Write;
+    use std::os::unix::fs::PermissionsExt;
+    use std::process::{Command, Stdio};
+
+    let temp_dir = TempDir::new().unwrap();
+    let project = temp_dir.path().join("project");
+    let home = temp_dir.path().join("home");
+    fs::create_dir_all(&project).unwrap();
+    fs::create_dir_all(&home).unwrap();
+    fs::write(project.join("Cargo.toml"), "[package]\nname = \"edit-test\"\nversion = \"0.1.0\"\n").unwrap();
+    fs::write(project.join("db.env"), "DB_PASSWORD=hunter2\n").unwrap();
+
+    // Stands in for the user's editor: replaces the file it is given
+    let editor = temp_dir.path().join("editor.sh");
+    fs::write(&editor, "#!/bin/sh\nprintf 'DB_PASSWORD=edited\\n' > \"$1\"\n").unwrap();
+    fs::set_permissions(&editor, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let cargocrypt = |args: &[&str], editor: &std::path::Path| {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_cargocrypt"))
+            .args(args)
+            .arg("--password-stdin")
+            .current_dir(&project)
+            .env("HOME", &home)
+            .env("XDG_RUNTIME_DIR", temp_dir.path().join("run"))
+            .env("EDITOR", editor)
+            .env_remove("VISUAL")
+            .env("RUST_LOG", "error")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(b"Edit_Password123!\n").unwrap();
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success(), "cargocrypt {:?} failed: {}", args, String::from_utf8_lossy(&output.stderr));
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+
+    cargocrypt(&["encrypt", "db.env", "--in-place"], &editor);
+    let encrypted_path = project.join("db.env.enc");
+    let before = fs::read(&encrypted_path).unwrap();
+
+    let stdout = cargocrypt(&["edit", "db.env.enc"], &editor);
+    assert!(stdout.contains("Saved db.env.enc"), "{}", stdout);
+    assert!(!project.join("db.env").exists());
+    let after = fs::read(&encrypted_path).unwrap();
+    assert_ne!(after, before);
+    assert!(!String::from_utf8_lossy(&after).contains("edited"));
+
+    // An editor that changes nothing leaves the encrypted file alone
+    let stdout = cargocrypt(&["edit", "db.env.enc"], std::path::Path::new("true"));
+    assert!(stdout.contains("No changes to db.env.enc"), "{}", stdout);
+    assert_eq!(fs::read(&encrypted_path).unwrap(), after);
+
+    cargocrypt(&["decrypt", "db.env.enc"], &editor);
+    assert_eq!(fs::read_to_string(project.join("db.env")).unwrap(), "DB_PASSWORD=edited\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_edit_encrypts_again_when_the_editor_fails() {
+    use std::io::Write;
+    use std::os::unix::fs::PermissionsExt;
+    use std::process::{Command, Stdio};
+
+    let temp_dir = TempDir::new().unwrap();
+    let project = temp_dir.path().join("project");
+    let home = temp_dir.path().join("home");
+    let run = temp_dir.path().join("run");
+    fs::create_dir_all(&project).unwrap();
+    fs::create_dir_all(&home).unwrap();
+    fs::write(project.join("Cargo.toml"), "[package]\nname = \"edit-test\"\nversion = \"0.1.0\"\n").unwrap();
+    fs::write(project.join("db.env"), "DB_PASSWORD=hunter2\n").unwrap();
+
+    // Saves a change, then exits unsuccessfully like an editor that crashed
+    let editor = temp_dir.path().join("editor.sh");
+    fs::write(&editor, "#!/bin/sh\nprintf 'DB_PASSWORD=half-edited\\n' > \"$1\"\nexit 1\n").unwrap();
+    fs::set_permissions(&editor, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let cargocrypt = |args: &[&str]| {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_cargocrypt"))
+            .args(args)
+            .arg("--password-stdin")
+            .current_dir(&project)
+            .env("HOME", &home)
+            .env("XDG_RUNTIME_DIR", &run)
+            .env("EDITOR", &editor)
+            .env_remove("VISUAL")
+            .env("RUST_LOG", "error")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(b"Edit_Password123!\n").unwrap();
+        child.wait_with_output().unwrap()
+    };
+
+    assert!(cargocrypt(&["encrypt", "db.env", "--in-place"]).status.success());
+    let output = cargocrypt(&["edit", "db.env.enc"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("exited with"), "{}", stderr);
+
+    // No plaintext is left behind in the session directory
+    for entry in walkdir::WalkDir::new(&run).into_iter().filter_map(Result::ok) {
+        if entry.file_type().is_file() {
+            let content = fs::read(entry.path()).unwrap();
+            assert!(!String::from_utf8_lossy(&content).contains("DB_PASSWORD"), "{} left behind", entry.path().display());
+        }
+    }
+    assert!(!project.join("db.env").exists());
+
+    assert!(cargocrypt(&["decrypt", "db.env.enc"]).status.success());
+    assert_eq!(fs::read_to_string(project.join("db.env")).unwrap(), "DB_PASSWORD=half-edited\n");
+}
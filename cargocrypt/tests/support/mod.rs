@@ -0,0 +1,300 @@
+//! Scripted git server and users for end-to-end tests
+//!
+//! A [`GitServer`] is a bare repository on disk that acts as `origin`. Each
+//! [`User`] gets its own checkout, home directory and password, and drives the
+//! real `cargocrypt` binary and `git` the way a developer would, so the clean
+//! and smudge filters run exactly as they do in the field.
+
+#![allow(dead_code)]
+
+use cargocrypt::git::AttributeConfig;
+use std::ffi::OsStr;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use tempfile::TempDir;
+
+/// Branch every scripted repository works on
+pub const BRANCH: &str = "main";
+
+/// Whether a usable `git` is installed
+pub fn git_available() -> bool {
+    Command::new("git")
+        .arg("--version")
+        .output()
+        .map(|output| output.status.success())
+        .unwrap_or(false)
+}
+
+/// Bare repository acting as the shared remote
+pub struct GitServer {
+    root: TempDir,
+    origin: PathBuf,
+}
+
+impl GitServer {
+    /// Create an empty bare `origin.git`
+    pub fn new() -> Self {
+        let root = TempDir::new().expect("create server directory");
+        let origin = root.path().join("origin.git");
+
+        run(Command::new("git").args(["init", "--quiet", "--bare"]).arg(&origin));
+        run(Command::new("git")
+            .arg("--git-dir")
+            .arg(&origin)
+            .args(["symbolic-ref", "HEAD", &format!("refs/heads/{}", BRANCH)]));
+
+        Self { root, origin }
+    }
+
+    /// Path of the bare repository, usable as a remote URL
+    pub fn url(&self) -> &Path {
+        &self.origin
+    }
+
+    /// Create a user with their own home directory and password
+    pub fn user(&self, name: &str, password: &str) -> User {
+        let home = self.root.path().join(format!("home-{}", name));
+        std::fs::create_dir_all(&home).expect("create home directory");
+
+        User {
+            name: name.to_string(),
+            workdir: self.root.path().join(name),
+            home,
+            password: password.to_string(),
+        }
+    }
+
+    /// Raw content of `path` on the server's main branch, as stored in git
+    pub fn blob(&self, path: &str) -> Vec<u8> {
+        run(Command::new("git")
+            .arg("--git-dir")
+            .arg(&self.origin)
+            .args(["cat-file", "blob", &format!("{}:{}", BRANCH, path)]))
+        .stdout
+    }
+}
+
+/// A developer with a checkout of the server's repository
+#[derive(Clone)]
+pub struct User {
+    name: String,
+    workdir: PathBuf,
+    home: PathBuf,
+    password: String,
+}
+
+impl User {
+    /// Start a new CargoCrypt project and connect it to `server`
+    pub fn init_project(&self, server: &GitServer) {
+        std::fs::create_dir_all(&self.workdir).expect("create workdir");
+        self.git(["init", "--quiet"]);
+        self.git(["symbolic-ref", "HEAD", &format!("refs/heads/{}", BRANCH)]);
+        self.write(
+            "Cargo.toml",
+            "[package]\nname = \"e2e-project\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+        );
+
+        self.cargocrypt(["init"]);
+        self.cargocrypt(["git", "configure-attributes"]);
+        self.git([OsStr::new("remote"), OsStr::new("add"), OsStr::new("origin"), server.url().as_os_str()]);
+    }
+
+    /// Clone `server` with the CargoCrypt filters configured before checkout
+    pub fn clone_from(&self, server: &GitServer) {
+        let mut command = self.command("git");
+        command.current_dir(self.home.parent().expect("home has a parent"));
+        command.arg("clone").arg("--quiet");
+        for (key, value) in filter_config() {
+            command.arg("-c").arg(format!("{}={}", key, value));
+        }
+        command.arg(server.url()).arg(&self.workdir);
+        run(&mut command);
+    }
+
+    /// Commit everything in the working tree
+    pub fn commit_all(&self, message: &str) {
+        self.git(["add", "--all"]);
+        self.git(["commit", "--quiet", "-m", message]);
+    }
+
+    /// Push the main branch to the server
+    pub fn push(&self) {
+        self.git(["push", "--quiet", "origin", &format!("HEAD:{}", BRANCH)]);
+    }
+
+    /// Fetch and fast-forward to the server's main branch
+    pub fn pull(&self) {
+        self.git(["pull", "--quiet", "--ff-only", "origin", BRANCH]);
+    }
+
+    /// The same developer working in another directory, like a worktree or submodule
+    pub fn in_dir<P: AsRef<Path>>(&self, workdir: P) -> User {
+        User {
+            workdir: workdir.as_ref().to_path_buf(),
+            ..self.clone()
+        }
+    }
+
+    /// Root of the checkout
+    pub fn workdir(&self) -> &Path {
+        &self.workdir
+    }
+
+    /// Switch to a new password, as after a team-wide rotation
+    pub fn set_password(&mut self, password: &str) {
+        self.password = password.to_string();
+    }
+
+    /// Re-run the clean filter on every tracked file
+    pub fn renormalize(&self) {
+        self.git(["add", "--renormalize", "."]);
+    }
+
+    /// Porcelain status of the working tree (empty when clean)
+    pub fn status(&self) -> String {
+        self.git(["status", "--porcelain"])
+    }
+
+    /// Write a file relative to the checkout
+    pub fn write(&self, path: &str, content: &str) {
+        let path = self.workdir.join(path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).expect("create parent directory");
+        }
+        std::fs::write(path, content).expect("write file");
+    }
+
+    /// Read a file relative to the checkout
+    pub fn read(&self, path: &str) -> Vec<u8> {
+        std::fs::read(self.workdir.join(path)).expect("read file")
+    }
+
+    /// Run git in the checkout and return its stdout
+    pub fn git<I, S>(&self, args: I) -> String
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let output = run(self.command("git").args(args));
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    }
+
+    /// Run git in the checkout, failing with its stderr
+    pub fn try_git<I, S>(&self, args: I) -> Result<String, String>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let output = self.command("git").args(args).output().expect("run git");
+        if output.status.success() {
+            Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+        } else {
+            Err(String::from_utf8_lossy(&output.stderr).into_owned())
+        }
+    }
+
+    /// Run cargocrypt in the checkout and return its stdout
+    pub fn cargocrypt<I, S>(&self, args: I) -> String
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let output = run(self.command(env!("CARGO_BIN_EXE_cargocrypt")).args(args));
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    }
+
+    /// Run cargocrypt in the checkout, failing with its stderr
+    pub fn try_cargocrypt<I, S>(&self, args: I) -> Result<String, String>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let output = self.command(env!("CARGO_BIN_EXE_cargocrypt")).args(args).output().expect("run cargocrypt");
+        if output.status.success() {
+            Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+        } else {
+            Err(String::from_utf8_lossy(&output.stderr).into_owned())
+        }
+    }
+
+    /// Run cargocrypt in the checkout and return its output, whether or not it succeeded
+    pub fn cargocrypt_output<I, S>(&self, args: I) -> Output
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.command(env!("CARGO_BIN_EXE_cargocrypt")).args(args).output().expect("run cargocrypt")
+    }
+
+    /// Run the smudge filter on `input` with this user's password, failing with its stderr
+    pub fn smudge(&self, input: &[u8]) -> Result<Vec<u8>, String> {
+        let mut child = self
+            .command(env!("CARGO_BIN_EXE_cargocrypt"))
+            .args(["git", "filter-smudge"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("spawn cargocrypt");
+        child.stdin.take().expect("stdin").write_all(input).expect("write stdin");
+        let output = child.wait_with_output().expect("wait for cargocrypt");
+        if output.status.success() {
+            Ok(output.stdout)
+        } else {
+            Err(String::from_utf8_lossy(&output.stderr).into_owned())
+        }
+    }
+
+    fn command<S: AsRef<OsStr>>(&self, program: S) -> Command {
+        let bin_dir = Path::new(env!("CARGO_BIN_EXE_cargocrypt")).parent().expect("binary directory");
+        let path = std::env::join_paths(
+            std::iter::once(bin_dir.to_path_buf()).chain(std::env::split_paths(&std::env::var_os("PATH").unwrap_or_default())),
+        )
+        .expect("PATH");
+
+        let mut command = Command::new(program);
+        command
+            .current_dir(&self.workdir)
+            .env("PATH", path)
+            .env("HOME", &self.home)
+            .env("GIT_CONFIG_NOSYSTEM", "1")
+            .env("GIT_AUTHOR_NAME", &self.name)
+            .env("GIT_AUTHOR_EMAIL", format!("{}@example.com", self.name))
+            .env("GIT_COMMITTER_NAME", &self.name)
+            .env("GIT_COMMITTER_EMAIL", format!("{}@example.com", self.name))
+            .env("CARGOCRYPT_PASSWORD", &self.password)
+            // Never talk to a developer's running key agent
+            .env("CARGOCRYPT_AGENT_SOCK", self.home.join("agent.sock"))
+            .env("RUST_LOG", "error");
+        command
+    }
+}
+
+/// `git config` entries that install the CargoCrypt filters
+pub fn filter_config() -> Vec<(String, String)> {
+    AttributeConfig::default()
+        .filters
+        .into_iter()
+        .flat_map(|(name, filter)| {
+            [
+                (format!("filter.{}.clean", name), filter.clean),
+                (format!("filter.{}.smudge", name), filter.smudge),
+                (format!("filter.{}.required", name), filter.required.to_string()),
+            ]
+        })
+        .collect()
+}
+
+fn run(command: &mut Command) -> Output {
+    let output = command.output().unwrap_or_else(|e| panic!("failed to run {:?}: {}", command, e));
+    assert!(
+        output.status.success(),
+        "{:?} failed with {}\nstdout: {}\nstderr: {}",
+        command,
+        output.status,
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr),
+    );
+    output
+}